@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type DeadAirSettings = {
+  enabled: boolean;
+  silence_threshold_db: number;
+  min_kbitrate: number;
+  duration_secs: number;
+  notify: boolean;
+  failover: boolean;
+};
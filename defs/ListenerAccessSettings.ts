@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CountryCode } from "./CountryCode.js";
+
+export type ListenerAccessSettings = {
+  allowed_countries: Array<CountryCode>;
+  denied_countries: Array<CountryCode>;
+  allowed_cidrs: Array<string>;
+  denied_cidrs: Array<string>;
+  allowed_domains: Array<string>;
+  allow_missing_referer: boolean;
+};
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type LiveRecordingSettings = {
+  enabled: boolean;
+  split_on_now_playing: boolean;
+  split_secs: number;
+  retention_days: number;
+};
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { StationRenditionCodec } from "./StationRenditionCodec.js";
+
+export type StationRendition = {
+  slug: string;
+  codec: StationRenditionCodec;
+  kbitrate: number;
+};
@@ -9,6 +9,7 @@ export type UserPublicAccount = {
   payment_method_id: string | null | undefined;
   name: string;
   limits: AccountLimits;
+  billing_period_start: DateTime | null | undefined;
   created_at: DateTime;
   updated_at: DateTime;
   user_metadata: Metadata;
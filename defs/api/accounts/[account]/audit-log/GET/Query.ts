@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AuditLogAction } from "../../../../../db/AuditLogAction.js";
+import type { DateTime } from "../../../../../DateTime.js";
+import type { PaginationQs } from "../../../../../qs/PaginationQs.js";
+
+export type Query =
+  & {
+    action?: AuditLogAction;
+    station_id?: string;
+    since?: DateTime;
+    until?: DateTime;
+  }
+  & PaginationQs;
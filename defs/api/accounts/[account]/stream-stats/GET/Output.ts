@@ -1,4 +1,5 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RejectionStats } from "../../../../../stream-connection-stats/RejectionStats.js";
 import type { Stats } from "../../../../../stream-connection-stats/Stats.js";
 
-export type Output = { stats: Stats };
+export type Output = { stats: Stats; rejections: RejectionStats };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EventKind } from "../../../../../../db/EventKind.js";
+
+export type Payload = {
+  url?: string;
+  events?: Array<EventKind>;
+  enabled?: boolean;
+  rotate_secret?: boolean;
+};
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MediaSessionNowPlaying } from "../../../../../../db/MediaSessionNowPlaying.js";
+import type { StationState } from "./StationState.js";
+import type { StationStatus } from "./StationStatus.js";
+
+export type ServerEvent =
+  | { kind: "ping" }
+  | { kind: "pong" }
+  | ({ kind: "start" } & { state: StationState })
+  | ({ kind: "status" } & { status: StationStatus })
+  | ({ kind: "now-playing" } & {
+    now_playing: MediaSessionNowPlaying | null | undefined;
+  })
+  | ({ kind: "listeners" } & { listeners: number });
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MediaSessionNowPlaying } from "../../../../../../db/MediaSessionNowPlaying.js";
+import type { StationStatus } from "./StationStatus.js";
+
+export type StationState = {
+  status: StationStatus;
+  now_playing: MediaSessionNowPlaying | null | undefined;
+  listeners: number;
+};
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../../../../../../DateTime.js";
+
+export type Song = {
+  title: string;
+  artist: string | null | undefined;
+  played_at: DateTime;
+};
@@ -1,4 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ListenersLimitPolicy } from "../../../db/ListenersLimitPolicy.js";
+import type { TransferOveragePolicy } from "../../../db/TransferOveragePolicy.js";
 
 export type Payload = {
   identifier: string;
@@ -11,4 +13,6 @@ export type Payload = {
   listeners: number;
   transfer: number;
   storage: number;
+  listeners_limit_policy?: ListenersLimitPolicy;
+  transfer_overage_policy?: TransferOveragePolicy;
 };
@@ -1,4 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ListenersLimitPolicy } from "../../../../db/ListenersLimitPolicy.js";
+import type { TransferOveragePolicy } from "../../../../db/TransferOveragePolicy.js";
 
 export type Payload = {
   price?: number;
@@ -11,4 +13,6 @@ export type Payload = {
   transfer?: number;
   storage?: number;
   is_user_selectable?: boolean;
+  listeners_limit_policy?: ListenersLimitPolicy;
+  transfer_overage_policy?: TransferOveragePolicy;
 };
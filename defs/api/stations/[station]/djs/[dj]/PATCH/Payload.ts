@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { StationScheduleTime } from "../../../../../../db/StationScheduleTime.js";
+
+export type Payload = {
+  name?: string;
+  username?: string;
+  password?: string;
+  enabled?: boolean;
+  allowed_time?: StationScheduleTime | null | undefined;
+};
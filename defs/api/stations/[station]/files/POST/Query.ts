@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DuplicateMode } from "../../../../../DuplicateMode.js";
 
-export type Query = { filename: string };
+export type Query = { filename: string; on_duplicate?: DuplicateMode };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioFileDuplicateSet } from "../../../../../../AudioFileDuplicateSet.js";
+
+export type Output = {
+  sets: Array<AudioFileDuplicateSet>;
+  wasted_bytes: number;
+};
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioImport } from "../../../../../db/AudioImport.js";
+import type { Paged } from "../../../../../Paged.js";
+
+export type Output = { imports: Paged<AudioImport> };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DuplicateMode } from "../../../../../DuplicateMode.js";
+import type { PayloadSource } from "./PayloadSource.js";
+
+export type Payload = { source: PayloadSource; on_duplicate?: DuplicateMode };
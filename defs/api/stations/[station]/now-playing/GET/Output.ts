@@ -1,4 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MediaSessionDeadAir } from "../../../../../db/MediaSessionDeadAir.js";
+import type { MediaSessionRelaySource } from "../../../../../db/MediaSessionRelaySource.js";
 
 export type Output =
   | ({ kind: "none" } & {
@@ -9,11 +11,17 @@ export type Output =
   | ({ kind: "live" } & {
     title: string | null | undefined;
     artist: string | null | undefined;
+    dead_air: MediaSessionDeadAir | null | undefined;
+  })
+  | ({ kind: "external-relay" } & {
+    url: string;
+    on_air: MediaSessionRelaySource | null | undefined;
+    dead_air: MediaSessionDeadAir | null | undefined;
   })
-  | ({ kind: "external-relay" } & { url: string })
   | ({ kind: "playlist" } & {
     file_id: string;
     filename: string;
     title: string | null | undefined;
     artist: string | null | undefined;
+    dead_air: MediaSessionDeadAir | null | undefined;
   });
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../../../../../DateTime.js";
+import type { PaginationQs } from "../../../../../qs/PaginationQs.js";
+
+export type Query =
+  & { since?: DateTime; until?: DateTime; q?: string }
+  & PaginationQs;
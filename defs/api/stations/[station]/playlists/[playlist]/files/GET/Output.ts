@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Paged } from "../../../../../../../Paged.js";
+import type { PlaylistItem } from "../../../../../../../db/PlaylistItem.js";
+
+export type Output = {
+  items: Paged<PlaylistItem>;
+  playlist_is_randomly_shuffled: boolean;
+};
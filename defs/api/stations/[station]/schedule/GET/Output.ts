@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Paged } from "../../../../../Paged.js";
+import type { StationSchedule } from "../../../../../db/StationSchedule.js";
+
+export type Output = Paged<StationSchedule>;
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { StationScheduleSource } from "../../../../../../db/StationScheduleSource.js";
+import type { StationScheduleTime } from "../../../../../../db/StationScheduleTime.js";
+
+export type Payload = {
+  time?: StationScheduleTime;
+  source?: StationScheduleSource;
+};
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DuplicateMode } from "../../../../../DuplicateMode.js";
+
+export type Payload = {
+  filename: string;
+  len: number;
+  on_duplicate?: DuplicateMode;
+};
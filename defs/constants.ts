@@ -25,6 +25,54 @@ export const AUDIO_FILE_BYTERATE = 16000;
 /** audio file chunk size in bytes */
 export const AUDIO_FILE_CHUNK_SIZE = 256000;
 
+/** audio files within this distance of the target loudness of the station are not re-encoded, in dB */
+export const AUDIO_FILE_LOUDNESS_TOLERANCE_DB = 0.5;
+
+/** maximum true peak of an audio file after loudness normalization, in dBTP */
+export const AUDIO_FILE_MAX_TRUE_PEAK_DBTP = -1.0;
+
+/** minimum duration of the silence at the start or the end of an audio file to trim it */
+export const AUDIO_FILE_SILENCE_MIN_DURATION_MS = 500;
+
+/** audio level below which an audio file is considered silent, in dBFS */
+export const AUDIO_FILE_SILENCE_THRESHOLD_DB = -50.0;
+
+/** interval at which a running audio import checks if it was cancelled */
+export const AUDIO_IMPORT_CANCEL_CHECK_INTERVAL_SECS = 5;
+
+/** max number of files imported from an archive, the following entries are ignored */
+export const AUDIO_IMPORT_MAX_ARCHIVE_ENTRIES = 5000;
+
+/** max decompressed size of an archive, the import fails when it is exceeded */
+export const AUDIO_IMPORT_MAX_ARCHIVE_SIZE = 50000000000;
+
+/** max decompressed size of a file imported from an archive, larger files are not imported */
+export const AUDIO_IMPORT_MAX_ENTRY_SIZE = 2000000000;
+
+/** max number of urls of an audio import */
+export const AUDIO_IMPORT_MAX_URLS = 500;
+
+/** timeout to connect to the server of an imported url */
+export const AUDIO_IMPORT_URL_CONNECT_TIMEOUT_SECS = 10;
+
+/** max time to wait for the response headers, and then for each chunk of the body, of an imported url */
+export const AUDIO_IMPORT_URL_READ_TIMEOUT_SECS = 60;
+
+/** interval of the background job that rolls over the accounts billing periods */
+export const BILLING_PERIOD_ROLLOVER_INTERVAL_SECS = 300;
+
+/** interval at which the audio of a live or external relay source is measured for dead air */
+export const DEAD_AIR_CHECK_INTERVAL_SECS = 5;
+
+/** minimum time between two dead air emails for the same station, across its media sessions */
+export const DEAD_AIR_EMAIL_COOLDOWN_SECS = 3600;
+
+/** a source in dead air is considered recovered after it has audio for this duration */
+export const DEAD_AIR_RECOVERY_SECS = 10;
+
+/** max bytes of each check interval that are decoded to measure the audio level of a source */
+export const DEAD_AIR_SAMPLE_BYTES = 32000;
+
 export const DEPLOYMENT_HEALTH_CHECK_INTERVAL_SECS = 1;
 
 export const DEPLOYMENT_HEALTH_CHECK_SHUTDOWN_DELAY_SECS = 240;
@@ -47,17 +95,57 @@ export const EXTERNAL_RELAY_NO_LISTENERS_SHUTDOWN_DELAY_SECS = 60;
 
 export const EXTERNAL_RELAY_NO_LISTENERS_SHUTDOWN_INTERVAL_SECS = 20;
 
+/** interval to check if the primary external relay url is healthy again
+ *  while a backup url or the playlist is on air */
+export const EXTERNAL_RELAY_PRIMARY_CHECK_INTERVAL_SECS = 30;
+
+/** time for the primary external relay url to produce data to be considered healthy */
+export const EXTERNAL_RELAY_PRIMARY_CHECK_TIMEOUT_SECS = 10;
+
+/** max number of source switches recorded in an external relay media session */
+export const EXTERNAL_RELAY_SESSION_SWITCHES_MAX = 100;
+
+/** time to wait before retrying an external relay url that failed
+ *  while it is waiting, the relay uses the next url of the failover chain or the station playlist */
+export const EXTERNAL_RELAY_SOURCE_RETRY_SECS = 60;
+
 /** Internal forwarded ip header used when openstream servers are connecting with each other */
 export const FORWARD_IP_HEADER = "x-openstream-forwarded-ip";
 
+/** unix timestamp (in seconds) after which a signed internal relay request is no longer valid */
+export const HEADER_RELAY_EXPIRES = "x-openstream-relay-expires";
+
+/** random value of a signed internal relay request, each nonce is accepted only once */
+export const HEADER_RELAY_NONCE = "x-openstream-relay-nonce";
+
+/** hex encoded HMAC-SHA256 of an internal relay request, signed with the cluster relay secret */
+export const HEADER_RELAY_SIGNATURE = "x-openstream-relay-signature";
+
 export const HEADER_RELAY_SOURCE_DEPLOYMENT = "x-source-deployment";
 
+/** id of the webhook delivery, the same event can be delivered more than once
+ *  if the delivery is retried or replayed */
+export const HEADER_WEBHOOK_DELIVERY = "x-openstream-webhook-delivery";
+
+/** kind of the event of a webhook delivery */
+export const HEADER_WEBHOOK_EVENT = "x-openstream-webhook-event";
+
+/** hex encoded HMAC-SHA256 of "{timestamp}.{body}" signed with the webhook secret */
+export const HEADER_WEBHOOK_SIGNATURE = "x-openstream-webhook-signature";
+
+/** unix timestamp (in seconds) of a webhook request, part of the signed message */
+export const HEADER_WEBHOOK_TIMESTAMP = "x-openstream-webhook-timestamp";
+
 /** Internal relay rejection code header */
 export const INTERNAL_RELAY_REJECTION_CODE_HEADER = "x-openstream-rejection-code";
 
 /** header name to get the result of the stream is-hls-redirect query */
 export const IS_HLS_REDIRECT_HEADER = "x-is-hls-redirect";
 
+/** a live recording is not split before it reaches this duration
+ *  so fast changing now playing info does not create a file per change */
+export const LIVE_RECORDING_MIN_SEGMENT_SECS = 30;
+
 /** timeout to wait to obtain a lock on a media session items
  *  if not released in this timeout, probably the item is poisoned
  *  and the process is aborted with a panic (and restarted by the process manager) */
@@ -78,12 +166,48 @@ export const MEDIA_SESSION_HEALTH_CHECK_KILL_INTERVAL_SECS = 5;
  *  otherwise kill it in database */
 export const MEDIA_SESSION_HEALTH_SHUTDOWN_TIMEOUT_SECS = 33;
 
+/** interval in which the now playing info of live and internal-relay media sessions
+ *  is read from $media_sessions and passed to the stream listeners */
+export const MEDIA_SESSION_NOW_PLAYING_SYNC_INTERVAL_SECS = 5;
+
+/** interval of the pings sent to the now playing push channel subscribers */
+export const NOW_PLAYING_PUSH_PING_INTERVAL_SECS = 30;
+
+/** subscribers of the now playing push channel that do not answer a ping in this time are disconnected */
+export const NOW_PLAYING_PUSH_PONG_TIMEOUT_SECS = 75;
+
+/** interval at which the now playing push channel refreshes the state of the subscribed stations */
+export const NOW_PLAYING_PUSH_REFRESH_INTERVAL_SECS = 3;
+
 /** Access token header used by payments servers implementations */
 export const PAYMENTS_ACCESS_TOKEN_HEADER = "x-access-token";
 
+/** duration of the boundary between two playlist files that is re-encoded in gapless mode */
+export const PLAYLIST_GAPLESS_WINDOW_MS = 250;
+
 /** delay to shutdown a playlist media session when it run out of listeners */
 export const PLAYLIST_NO_LISTENERS_SHUTDOWN_DELAY_SECS = 10;
 
+/** mp3 frames decoded before the end of a playlist file transition and then discarded
+ *  they prime the decoder because mp3 frames can reference the data of the previous frames */
+export const PLAYLIST_TRANSITION_WARMUP_FRAMES = 2;
+
+/** number of songs of the public recently played embed when not specified */
+export const PLAY_HISTORY_EMBED_DEFAULT_ITEMS = 10;
+
+/** limit of requests to the public recently played embed from the same ip
+ *  counted apart from API_IP_LIMIT */
+export const PLAY_HISTORY_EMBED_IP_LIMIT = 300;
+
+/** limit restart interval of PLAY_HISTORY_EMBED_IP_LIMIT */
+export const PLAY_HISTORY_EMBED_IP_LIMIT_DURATION_SECS = 60;
+
+/** max number of songs of the public recently played embed */
+export const PLAY_HISTORY_EMBED_MAX_ITEMS = 50;
+
+/** max number of play history items in an export, the oldest items of the range are left out */
+export const PLAY_HISTORY_EXPORT_MAX_ITEMS = 100000;
+
 /** internal interval to start new probe task if needed */
 export const PROBE_BACKGROUND_JOB_CHECK_INTERVAL_SECS = 10;
 
@@ -96,11 +220,40 @@ export const REAL_IP_HEADER = "x-real-ip";
 /** delay to shutdown a relay session when it run out of listeners */
 export const RELAY_NO_LISTENERS_SHUTDOWN_DELAY_SECS = 10;
 
+/** allowed clock difference between deployments when checking the expiration of a relay request */
+export const RELAY_SIGNATURE_CLOCK_SKEW_SECS = 5;
+
+/** validity of a signed internal relay request */
+export const RELAY_SIGNATURE_TTL_SECS = 30;
+
+/** delay to shutdown a station output rendition when it run out of listeners */
+export const RENDITION_NO_LISTENERS_SHUTDOWN_DELAY_SECS = 10;
+
+/** resumable uploads that are not updated in this time are removed with their parts */
+export const RESUMABLE_UPLOAD_EXPIRE_SECS = 86400;
+
+/** max size in bytes of each part of a resumable upload stored in the storage db
+ *  an interrupted append keeps all the parts that were completely received */
+export const RESUMABLE_UPLOAD_PART_SIZE = 1000000;
+
+/** a play of a royalty report lasts until the next play, up to this duration */
+export const ROYALTY_REPORT_MAX_PLAY_DURATION_SECS = 3600;
+
+/** max range of a royalty report, in days */
+export const ROYALTY_REPORT_MAX_RANGE_DAYS = 93;
+
+/** stream connections shorter than this duration are not counted as listeners in royalty reports */
+export const ROYALTY_REPORT_MIN_SESSION_SECS = 60;
+
 /** we need to update this value after making code changes to the station pictures logic or sizes
  *  changing to this value will make startup check and recreation of outdated images
  *  and invalidation of service workers station pictures caches */
 export const STATION_PICTURES_VERSION = 5.0;
 
+/** interval to check the schedule of the stations running in a deployment
+ *  and switch their source if the active slot changed */
+export const STATION_SCHEDULE_CHECK_INTERVAL_SECS = 5;
+
 /** stream bust len in elements (use with STREAM_CHUNK_SIZE) */
 export const STREAM_BURST_LENGTH = 12;
 
@@ -113,12 +266,49 @@ export const STREAM_CHUNK_SIZE = 16000;
 /** open connections that last more than this time in seconds will be terminated */
 export const STREAM_CONNECTION_MAX_DURATION_SECS = 21600;
 
+/** header set in the HLS requests forwarded between openstream servers
+ *  a request with this header is never forwarded again */
+export const STREAM_HLS_FORWARDED_HEADER = "x-openstream-hls-forwarded";
+
+/** timeout for an HLS request forwarded to the deployment that segments the station
+ *  it must be longer than STREAM_HLS_PLAYLIST_WAIT_SECS */
+export const STREAM_HLS_FORWARD_TIMEOUT_SECS = 20;
+
+/** delay to stop segmenting a station when its HLS stream had no requests */
+export const STREAM_HLS_IDLE_SHUTDOWN_SECS = 60;
+
+/** number of segments in the sliding window of the HLS live playlist */
+export const STREAM_HLS_PLAYLIST_SEGMENTS = 6;
+
+/** time to wait for the first segment of an HLS stream before responding to the playlist request */
+export const STREAM_HLS_PLAYLIST_WAIT_SECS = 10;
+
+/** target duration of the HLS segments in seconds
+ *  segments are cut at mp3 frame boundaries so the actual duration can be slightly longer */
+export const STREAM_HLS_SEGMENT_DURATION_SECS = 6;
+
+/** delay to close an HLS listener session that had no playlist or segment requests
+ *  the session holds the listener slot and the stream connection of the listener */
+export const STREAM_HLS_SESSION_IDLE_SECS = 30;
+
+/** amount of audio bytes between in-band icy metadata blocks
+ *  sent to clients that request it with the Icy-MetaData: 1 header */
+export const STREAM_ICY_METAINT = 16000;
+
 /** limit of concurrent stream connections from the same ip */
 export const STREAM_IP_CONNECTIONS_LIMIT = 8;
 
 /** stream kbps */
 export const STREAM_KBITRATE = 128;
 
+/** retry-after header value in seconds for stream connections rejected
+ *  because the account reached its listeners limit */
+export const STREAM_LISTENERS_LIMIT_RETRY_AFTER_SECS = 60;
+
+/** retry-after header value in seconds for stream connections rejected
+ *  because the account reached its transfer limit for the current billing period */
+export const STREAM_TRANSFER_LIMIT_RETRY_AFTER_SECS = 3600;
+
 /** time in seconds for which an user recovery token code is valid */
 export const TOKEN_USER_RECOVERY_VALIDITY_SECS = 3600;
 
@@ -161,24 +351,96 @@ export const VALIDATE_PLAN_NAME_MAX_LEN = 100;
 
 export const VALIDATE_PLAN_SLUG_MAX_LEN = 100;
 
+export const VALIDATE_PLAYLIST_FILES_MAX_LEN = 10000;
+
+export const VALIDATE_PLAYLIST_NAME_MAX_LEN = 60;
+
+export const VALIDATE_PLAYLIST_NAME_MIN_LEN = 1;
+
+export const VALIDATE_PLAYLIST_ROTATIONS_MAX_LEN = 10;
+
+export const VALIDATE_PLAYLIST_ROTATION_EVERY_MAX = 1000;
+
+export const VALIDATE_PLAYLIST_ROTATION_EVERY_MIN = 1;
+
+export const VALIDATE_PLAY_HISTORY_SEARCH_MAX_LEN = 100;
+
+export const VALIDATE_STATION_DEAD_AIR_DURATION_SECS_MAX = 3600;
+
+export const VALIDATE_STATION_DEAD_AIR_DURATION_SECS_MIN = 10;
+
+export const VALIDATE_STATION_DEAD_AIR_MIN_KBITRATE_MAX = 128;
+
+export const VALIDATE_STATION_DEAD_AIR_THRESHOLD_DB_MAX = -20;
+
+export const VALIDATE_STATION_DEAD_AIR_THRESHOLD_DB_MIN = -90;
+
 export const VALIDATE_STATION_DESC_MAX_LEN = 4000;
 
 export const VALIDATE_STATION_DESC_MIN_LEN = 1;
 
+export const VALIDATE_STATION_DJS_MAX_LEN = 100;
+
+export const VALIDATE_STATION_DJ_NAME_MAX_LEN = 60;
+
+export const VALIDATE_STATION_DJ_NAME_MIN_LEN = 1;
+
+export const VALIDATE_STATION_DJ_PASSWORD_MAX_LEN = 100;
+
+export const VALIDATE_STATION_DJ_PASSWORD_MIN_LEN = 8;
+
+export const VALIDATE_STATION_DJ_USERNAME_MAX_LEN = 40;
+
+export const VALIDATE_STATION_DJ_USERNAME_MIN_LEN = 1;
+
 export const VALIDATE_STATION_EMAIL_MAX_LEN = 100;
 
+export const VALIDATE_STATION_EXTERNAL_RELAY_BACKUP_URLS_MAX_LEN = 4;
+
 export const VALIDATE_STATION_EXTERNAL_RELAY_URL_MAX_LEN = 2000;
 
 export const VALIDATE_STATION_FREQUENCY_MAX = 100000.0;
 
 export const VALIDATE_STATION_FREQUENCY_MIN = 0.0;
 
+export const VALIDATE_STATION_LISTENER_ACCESS_CIDRS_MAX_LEN = 100;
+
+export const VALIDATE_STATION_LISTENER_ACCESS_COUNTRIES_MAX_LEN = 250;
+
+export const VALIDATE_STATION_LISTENER_ACCESS_DOMAINS_MAX_LEN = 50;
+
+export const VALIDATE_STATION_LISTENER_ACCESS_DOMAIN_MAX_LEN = 253;
+
+export const VALIDATE_STATION_LIVE_RECORDING_RETENTION_DAYS_MAX = 365;
+
+export const VALIDATE_STATION_LIVE_RECORDING_SPLIT_SECS_MAX = 21600;
+
 export const VALIDATE_STATION_NAME_MAX_LEN = 60;
 
 export const VALIDATE_STATION_NAME_MIN_LEN = 1;
 
 export const VALIDATE_STATION_PHONE_MAX_LEN = 60;
 
+export const VALIDATE_STATION_PLAYLISTS_MAX_LEN = 100;
+
+export const VALIDATE_STATION_PLAYLIST_CROSSFADE_MS_MAX = 12000;
+
+export const VALIDATE_STATION_RENDITIONS_MAX_LEN = 8;
+
+export const VALIDATE_STATION_RENDITION_KBITRATE_MAX = 320;
+
+export const VALIDATE_STATION_RENDITION_KBITRATE_MIN = 16;
+
+export const VALIDATE_STATION_RENDITION_SLUG_MAX_LEN = 40;
+
+export const VALIDATE_STATION_RENDITION_SLUG_MIN_LEN = 1;
+
+export const VALIDATE_STATION_SCHEDULE_PLAYLIST_FILES_MAX_LEN = 1000;
+
+export const VALIDATE_STATION_SCHEDULE_SLOTS_MAX_LEN = 200;
+
+export const VALIDATE_STATION_SCHEDULE_WEEKLY_DURATION_MINUTES_MAX = 10080;
+
 export const VALIDATE_STATION_SLOGAN_MAX_LEN = 100;
 
 export const VALIDATE_STATION_SLOGAN_MIN_LEN = 1;
@@ -187,8 +449,13 @@ export const VALIDATE_STATION_SLUG_MAX_LEN = 60;
 
 export const VALIDATE_STATION_SLUG_MIN_LEN = 1;
 
+export const VALIDATE_STATION_TARGET_LOUDNESS_MAX = -6.0;
+
+export const VALIDATE_STATION_TARGET_LOUDNESS_MIN = -36.0;
+
 export const VALIDATE_STATION_URLS_MAX_LEN = 200;
 
+
 export const VALIDATE_STATION_WHATSAPP_MAX_LEN = 60;
 
 export const VALIDATE_USER_EMAIL_MAX_LEN = 100;
@@ -203,4 +470,35 @@ export const VALIDATE_USER_PASSWORD_MAX_LEN = 60;
 
 export const VALIDATE_USER_PASSWORD_MIN_LEN = 8;
 
-export const VALIDATE_USER_PHONE_MAX_LEN = 40;
\ No newline at end of file
+export const VALIDATE_USER_PHONE_MAX_LEN = 40;
+
+export const VALIDATE_WEBHOOK_URL_MAX_LEN = 2000;
+
+/** max number of webhooks of an account */
+export const WEBHOOKS_MAX_PER_ACCOUNT = 10;
+
+/** interval at which the delivery job looks for due webhook deliveries */
+export const WEBHOOK_DELIVERY_CHECK_INTERVAL_SECS = 5;
+
+/** max number of webhook deliveries sent concurrently by a deployment */
+export const WEBHOOK_DELIVERY_CONCURRENCY = 16;
+
+/** time a deployment holds a webhook delivery while sending it,
+ *  if the deployment stops the delivery is retried after this time */
+export const WEBHOOK_DELIVERY_LEASE_SECS = 60;
+
+/** attempts of a webhook delivery before it is marked as failed */
+export const WEBHOOK_DELIVERY_MAX_ATTEMPTS = 8;
+
+/** delay before the first retry of a webhook delivery, it doubles on each following retry */
+export const WEBHOOK_DELIVERY_RETRY_BASE_SECS = 30;
+
+/** max delay between the retries of a webhook delivery */
+export const WEBHOOK_DELIVERY_RETRY_MAX_SECS = 21600;
+
+/** timeout of a webhook request, including the connection and the response */
+export const WEBHOOK_DELIVERY_TIMEOUT_SECS = 15;
+
+/** time the enabled webhooks of an account are cached by a deployment
+ *  to decide if the high frequency events, like the listener events, must be dispatched */
+export const WEBHOOK_SUBSCRIPTIONS_CACHE_SECS = 60;
\ No newline at end of file
@@ -9,6 +9,8 @@ export type Account = {
   payment_method_id: string | null | undefined;
   name: string;
   limits: AccountLimits;
+  billing_period_start: DateTime | null | undefined;
+  billing_period_anchor_day: number | null | undefined;
   user_metadata: Metadata;
   system_metadata: Metadata;
   created_at: DateTime;
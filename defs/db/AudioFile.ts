@@ -1,11 +1,14 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioFileAnalysis } from "./AudioFileAnalysis.js";
 import type { AudioMetadata } from "./AudioMetadata.js";
+import type { AudioProcessing } from "../AudioProcessing.js";
 import type { DateTime } from "../DateTime.js";
 
 export type AudioFile = {
   _id: string;
   station_id: string;
   sha256: string;
+  source_sha256: string | null | undefined;
   len: number;
   duration_ms: number;
   bytes_sec: number;
@@ -14,6 +17,9 @@ export type AudioFile = {
   chunk_duration_ms: number;
   filename: string;
   metadata: AudioMetadata;
+  analysis: AudioFileAnalysis | null | undefined;
+  processing: AudioProcessing;
+  media_session_id: string | null | undefined;
   order: number;
   created_at: DateTime;
 };
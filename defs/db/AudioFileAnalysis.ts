@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type AudioFileAnalysis = {
+  integrated_lufs: number;
+  true_peak_dbtp: number;
+  leading_silence_ms: number;
+  trailing_silence_ms: number;
+};
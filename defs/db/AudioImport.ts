@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioImportItem } from "./AudioImportItem.js";
+import type { AudioImportSource } from "./AudioImportSource.js";
+import type { AudioImportState } from "./AudioImportState.js";
+import type { DateTime } from "../DateTime.js";
+import type { DuplicateMode } from "../DuplicateMode.js";
+
+export type AudioImport = {
+  _id: string;
+  station_id: string;
+  deployment_id: string;
+  source: AudioImportSource;
+  on_duplicate: DuplicateMode;
+  items: Array<AudioImportItem>;
+  state: AudioImportState;
+  created_at: DateTime;
+  updated_at: DateTime;
+};
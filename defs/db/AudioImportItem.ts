@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioImportItemState } from "./AudioImportItemState.js";
+
+export type AudioImportItem = {
+  name: string;
+  operation_id: string;
+  state: AudioImportItemState;
+};
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type AudioImportItemState =
+  | { kind: "pending" }
+  | ({ kind: "success" } & { audio_file_id: string })
+  | { kind: "skipped" }
+  | ({ kind: "error" } & { message: string })
+  | { kind: "cancelled" };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioImportArchiveFormat } from "./AudioImportArchiveFormat.js";
+
+export type AudioImportSource =
+  | ({ kind: "archive" } & {
+    upload_id: string;
+    filename: string;
+    format: AudioImportArchiveFormat;
+  })
+  | ({ kind: "urls" } & { urls: Array<string> });
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+
+export type AudioImportState =
+  | { kind: "pending" }
+  | { kind: "running" }
+  | ({ kind: "finished" } & { finished_at: DateTime })
+  | ({ kind: "failed" } & { message: string; failed_at: DateTime })
+  | ({ kind: "cancelled" } & { cancelled_at: DateTime });
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { AuditLogAction } from "./AuditLogAction.js";
+import type { AuditLogActor } from "./AuditLogActor.js";
+import type { AuditLogChange } from "./AuditLogChange.js";
+import type { AuditLogTarget } from "./AuditLogTarget.js";
+
+export type AuditLog = {
+  _id: string;
+  account_ids: Array<string>;
+  actor: AuditLogActor;
+  action: AuditLogAction;
+  target: AuditLogTarget;
+  changes: Array<AuditLogChange>;
+  ip: string;
+  user_agent: string | null | undefined;
+  created_at: DateTime;
+};
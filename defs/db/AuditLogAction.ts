@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type AuditLogAction =
+  | "account.patch"
+  | "station.patch"
+  | "station.delete"
+  | "station.transfer"
+  | "station.source_password_reset"
+  | "station.live_source_kick"
+  | "station.dj_create"
+  | "station.dj_patch"
+  | "station.dj_delete"
+  | "file.delete"
+  | "recording.delete"
+  | "admin.delegate";
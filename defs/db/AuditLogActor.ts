@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type AuditLogActor =
+  | { scope: "global" }
+  | ({ scope: "global_as_user" } & { user_id: string })
+  | ({ scope: "admin" } & { admin_id: string })
+  | ({ scope: "admin_as_user" } & { admin_id: string; user_id: string })
+  | ({ scope: "user" } & { user_id: string });
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type AuditLogTarget =
+  | ({ kind: "account" } & { account_id: string })
+  | ({ kind: "station" } & { station_id: string })
+  | ({ kind: "audio_file" } & { station_id: string; file_id: string })
+  | ({ kind: "live_recording" } & { station_id: string; recording_id: string })
+  | ({ kind: "station_dj" } & { station_id: string; dj_id: string })
+  | ({ kind: "user" } & { user_id: string });
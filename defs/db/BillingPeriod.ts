@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AccountLimit } from "../AccountLimit.js";
+import type { DateTime } from "../DateTime.js";
+
+export type BillingPeriod = {
+  _id: string;
+  account_id: string;
+  plan_id: string;
+  start: DateTime;
+  end: DateTime;
+  transfer: AccountLimit;
+  created_at: DateTime;
+};
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type EventKind =
+  | "listener.start"
+  | "listener.end"
+  | "live.connected"
+  | "live.disconnected"
+  | "now_playing.changed"
+  | "relay.failed"
+  | "dead_air.started"
+  | "dead_air.ended"
+  | "quota.threshold"
+  | "upload.finished"
+  | "member.invited";
@@ -1,8 +1,25 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { AudioListenerEnd } from "./event-payload/AudioListenerEnd.js";
 import type { AudioListenerStart } from "./event-payload/AudioListenerStart.js";
+import type { DeadAirEnded } from "./event-payload/DeadAirEnded.js";
+import type { DeadAirStarted } from "./event-payload/DeadAirStarted.js";
+import type { LiveSourceConnected } from "./event-payload/LiveSourceConnected.js";
+import type { LiveSourceDisconnected } from "./event-payload/LiveSourceDisconnected.js";
+import type { MemberInvited } from "./event-payload/MemberInvited.js";
+import type { NowPlayingChanged } from "./event-payload/NowPlayingChanged.js";
+import type { QuotaThreshold } from "./event-payload/QuotaThreshold.js";
+import type { RelayFailed } from "./event-payload/RelayFailed.js";
+import type { UploadFinished } from "./event-payload/UploadFinished.js";
 
-export type EventVariant = {
-  kind: "listener.start";
-  payload: AudioListenerStart;
-} | { kind: "listener.end"; payload: AudioListenerEnd };
+export type EventVariant =
+  | { kind: "listener.start"; payload: AudioListenerStart }
+  | { kind: "listener.end"; payload: AudioListenerEnd }
+  | { kind: "live.connected"; payload: LiveSourceConnected }
+  | { kind: "live.disconnected"; payload: LiveSourceDisconnected }
+  | { kind: "now_playing.changed"; payload: NowPlayingChanged }
+  | { kind: "relay.failed"; payload: RelayFailed }
+  | { kind: "dead_air.started"; payload: DeadAirStarted }
+  | { kind: "dead_air.ended"; payload: DeadAirEnded }
+  | { kind: "quota.threshold"; payload: QuotaThreshold }
+  | { kind: "upload.finished"; payload: UploadFinished }
+  | { kind: "member.invited"; payload: MemberInvited };
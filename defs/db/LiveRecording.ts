@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioFile } from "./AudioFile.js";
+import type { DateTime } from "../DateTime.js";
+
+export type LiveRecording = {
+  _id: string;
+  station_id: string;
+  media_session_id: string;
+  play_history_item_id: string | null | undefined;
+  title: string | null | undefined;
+  artist: string | null | undefined;
+  file: AudioFile;
+  started_at: DateTime;
+  ended_at: DateTime;
+  expires_at: DateTime | null | undefined;
+  created_at: DateTime;
+};
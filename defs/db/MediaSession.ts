@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { DateTime } from "../DateTime.js";
+import type { MediaSessionDeadAir } from "./MediaSessionDeadAir.js";
 import type { MediaSessionKind } from "./MediaSessionKind.js";
 import type { MediaSessionNowPlaying } from "./MediaSessionNowPlaying.js";
 import type { MediaSessionState } from "./MediaSessionState.js";
@@ -10,6 +11,7 @@ export type MediaSession = {
   deployment_id: string;
   state: MediaSessionState;
   now_playing: MediaSessionNowPlaying | null | undefined;
+  dead_air: MediaSessionDeadAir | null | undefined;
   transfer_bytes: number;
   closed_at: DateTime | null | undefined;
   duration_ms: number | null | undefined;
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { DeadAirReason } from "./DeadAirReason.js";
+
+export type MediaSessionDeadAir = {
+  reason: DeadAirReason;
+  since: DateTime;
+  level_dbfs: number | null | undefined;
+  kbitrate: number;
+  failover: boolean;
+};
@@ -1,15 +1,30 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { DateTime } from "../DateTime.js";
+import type { MediaSessionLiveInput } from "./MediaSessionLiveInput.js";
+import type { MediaSessionRelaySource } from "./MediaSessionRelaySource.js";
+import type { MediaSessionRelaySourceHealth } from "./MediaSessionRelaySourceHealth.js";
+import type { MediaSessionRelaySwitch } from "./MediaSessionRelaySwitch.js";
+import type { StationDjRef } from "./StationDjRef.js";
 import type { Request } from "./http/Request.js";
 
 export type MediaSessionKind =
   | ({ kind: "playlist" } & {
     resumed_from: string | null | undefined;
+    playlist_id: string | null | undefined;
     last_audio_file_id: string;
     last_audio_file_order: number;
     last_audio_chunk_i: number;
     last_audio_chunk_skip_parts: number;
     last_audio_chunk_date: DateTime;
   })
-  | ({ kind: "live" } & { request: Request })
-  | ({ kind: "external-relay" } & { url: string });
+  | ({ kind: "live" } & {
+    request: Request;
+    input: MediaSessionLiveInput | null | undefined;
+    dj: StationDjRef | null | undefined;
+  })
+  | ({ kind: "external-relay" } & {
+    url: string;
+    on_air: MediaSessionRelaySource | null | undefined;
+    source_health: Array<MediaSessionRelaySourceHealth>;
+    switches: Array<MediaSessionRelaySwitch>;
+  });
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MediaSessionLiveInputCodec } from "./MediaSessionLiveInputCodec.js";
+
+export type MediaSessionLiveInput = {
+  codec: MediaSessionLiveInputCodec | null | undefined;
+  sample_rate: number | null | undefined;
+  channels: number | null | undefined;
+  kbitrate: number | null | undefined;
+  transcoded: boolean;
+};
@@ -3,4 +3,5 @@
 export type MediaSessionNowPlaying = {
   title: string;
   artist: string | null | undefined;
+  play_history_item_id?: string;
 };
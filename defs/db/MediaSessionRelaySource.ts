@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type MediaSessionRelaySource =
+  | ({ kind: "url" } & { index: number; url: string })
+  | { kind: "playlist" }
+  | { kind: "off_air" };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { MediaSessionRelaySwitchReason } from "./MediaSessionRelaySwitchReason.js";
+
+export type MediaSessionRelaySourceHealth = {
+  url: string;
+  healthy: boolean;
+  failures: number;
+  last_failure_at: DateTime | null | undefined;
+  last_failure_reason: MediaSessionRelaySwitchReason | null | undefined;
+};
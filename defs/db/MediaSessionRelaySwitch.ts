@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { MediaSessionRelaySource } from "./MediaSessionRelaySource.js";
+import type { MediaSessionRelaySwitchReason } from "./MediaSessionRelaySwitchReason.js";
+
+export type MediaSessionRelaySwitch = {
+  to: MediaSessionRelaySource;
+  reason: MediaSessionRelaySwitchReason;
+  at: DateTime;
+};
@@ -1,6 +1,8 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { DateTime } from "../DateTime.js";
+import type { ListenersLimitPolicy } from "./ListenersLimitPolicy.js";
 import type { PlanLimits } from "./PlanLimits.js";
+import type { TransferOveragePolicy } from "./TransferOveragePolicy.js";
 
 export type Plan = {
   _id: string;
@@ -9,6 +11,8 @@ export type Plan = {
   display_name: string;
   price: number;
   limits: PlanLimits;
+  listeners_limit_policy: ListenersLimitPolicy;
+  transfer_overage_policy: TransferOveragePolicy;
   color: string;
   order: number;
   is_user_selectable: boolean;
@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { StationDjRef } from "./StationDjRef.js";
 
 export type PlayHistoryItemKind =
-  | { kind: "live" }
+  | ({ kind: "live" } & { dj: StationDjRef | null | undefined })
   | ({ kind: "playlist" } & { file_id: string });
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { PlaylistRotation } from "./PlaylistRotation.js";
+
+export type Playlist = {
+  _id: string;
+  station_id: string;
+  name: string;
+  rotations: Array<PlaylistRotation>;
+  is_randomly_shuffled: boolean;
+  created_at: DateTime;
+  updated_at: DateTime;
+};
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+
+export type PlaylistItem = {
+  _id: string;
+  station_id: string;
+  playlist_id: string;
+  file_id: string;
+  order: number;
+  created_at: DateTime;
+};
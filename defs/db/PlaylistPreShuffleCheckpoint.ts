@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+
+export type PlaylistPreShuffleCheckpoint = {
+  _id: string;
+  file_ids: Array<string>;
+  created_at: DateTime;
+};
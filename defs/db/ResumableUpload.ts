@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { DuplicateMode } from "../DuplicateMode.js";
+import type { ResumableUploadState } from "./ResumableUploadState.js";
+
+export type ResumableUpload = {
+  _id: string;
+  station_id: string;
+  filename: string;
+  len: number;
+  offset: number;
+  on_duplicate: DuplicateMode;
+  state: ResumableUploadState;
+  created_at: DateTime;
+  updated_at: DateTime;
+};
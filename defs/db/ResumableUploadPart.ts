@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+
+export type ResumableUploadPart = {
+  _id: string;
+  upload_id: string;
+  station_id: string;
+  offset: number;
+  len: number;
+  data: string;
+  created_at: DateTime;
+};
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+
+export type ResumableUploadState =
+  | { kind: "open" }
+  | { kind: "committing" }
+  | ({ kind: "committed" } & { audio_file_id: string; committed_at: DateTime });
@@ -14,4 +14,7 @@ export type SentEmailKind = {
     user_sender_id: string | null | undefined;
     admin_sender_id: string | null | undefined;
   };
+} | {
+  kind: "station-dead-air";
+  data: { station_id: string; media_session_id: string; user_id: string };
 };
@@ -1,10 +1,17 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioProcessing } from "../AudioProcessing.js";
 import type { CountryCode } from "../CountryCode.js";
 import type { DateTime } from "../DateTime.js";
+import type { DeadAirSettings } from "../DeadAirSettings.js";
 import type { LangCode } from "../LangCode.js";
+import type { ListenerAccessSettings } from "../ListenerAccessSettings.js";
+import type { LiveInputSettings } from "../LiveInputSettings.js";
+import type { LiveRecordingSettings } from "../LiveRecordingSettings.js";
 import type { Metadata } from "./Metadata.js";
 import type { OwnerDeploymentInfo } from "./OwnerDeploymentInfo.js";
+import type { PlaylistTransition } from "../PlaylistTransition.js";
 import type { StationFrequency } from "../StationFrequency.js";
+import type { StationRendition } from "../StationRendition.js";
 import type { StationTypeOfContent } from "./StationTypeOfContent.js";
 
 export type Station = {
@@ -38,6 +45,16 @@ export type Station = {
   system_metadata: Metadata;
   external_relay_url: string | null | undefined;
   external_relay_redirect: boolean;
+  external_relay_backup_urls: Array<string>;
+  renditions: Array<StationRendition>;
+  default_playlist_id: string | null | undefined;
+  playlist_transition: PlaylistTransition;
+  audio_processing: AudioProcessing;
+  live_recording: LiveRecordingSettings;
+  live_input: LiveInputSettings;
+  dead_air: DeadAirSettings;
+  listener_access: ListenerAccessSettings;
+  timezone: string;
   source_password: string;
   owner_deployment_info: OwnerDeploymentInfo | null | undefined;
   last_external_relay_probe_started_at: DateTime | null | undefined;
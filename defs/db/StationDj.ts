@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { StationScheduleTime } from "./StationScheduleTime.js";
+
+export type StationDj = {
+  _id: string;
+  station_id: string;
+  name: string;
+  username: string;
+  password: string;
+  enabled: boolean;
+  allowed_time: StationScheduleTime | null | undefined;
+  created_at: DateTime;
+  updated_at: DateTime;
+};
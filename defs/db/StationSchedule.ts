@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { StationScheduleSource } from "./StationScheduleSource.js";
+import type { StationScheduleTime } from "./StationScheduleTime.js";
+
+export type StationSchedule = {
+  _id: string;
+  station_id: string;
+  time: StationScheduleTime;
+  source: StationScheduleSource;
+  created_at: DateTime;
+  updated_at: DateTime;
+};
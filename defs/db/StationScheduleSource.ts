@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type StationScheduleSource =
+  | ({ kind: "playlist" } & {
+    playlist_id: string | null | undefined;
+    file_ids: Array<string>;
+  })
+  | ({ kind: "external-relay" } & { url: string })
+  | { kind: "live" };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { StationScheduleWeekday } from "./StationScheduleWeekday.js";
+
+export type StationScheduleTime =
+  | ({ kind: "weekly" } & {
+    weekdays: Array<StationScheduleWeekday>;
+    start_minute: number;
+    duration_minutes: number;
+  })
+  | ({ kind: "once" } & { start: DateTime; end: DateTime });
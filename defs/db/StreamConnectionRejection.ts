@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CountryCode } from "../CountryCode.js";
+import type { DateTime } from "../DateTime.js";
+import type { StreamConnectionRejectionReason } from "./StreamConnectionRejectionReason.js";
+
+export type StreamConnectionRejection = {
+  _id: string;
+  station_id: string;
+  account_id: string;
+  deployment_id: string;
+  reason: StreamConnectionRejectionReason;
+  ip: string;
+  country_code: CountryCode | null | undefined;
+  created_at: DateTime;
+};
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type StreamConnectionRejectionReason =
+  | "listeners_limit"
+  | "transfer_limit"
+  | "ip_blocked"
+  | "country_blocked"
+  | "referer_blocked";
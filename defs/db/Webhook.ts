@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { EventKind } from "./EventKind.js";
+
+export type Webhook = {
+  _id: string;
+  account_id: string;
+  url: string;
+  secret: string;
+  events: Array<EventKind>;
+  enabled: boolean;
+  created_at: DateTime;
+  updated_at: DateTime;
+};
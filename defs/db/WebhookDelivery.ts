@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+import type { EventKind } from "./EventKind.js";
+import type { WebhookDeliveryAttempt } from "./WebhookDeliveryAttempt.js";
+import type { WebhookDeliveryState } from "./WebhookDeliveryState.js";
+
+export type WebhookDelivery = {
+  _id: string;
+  webhook_id: string;
+  account_id: string;
+  event_id: string;
+  event_kind: EventKind;
+  payload: string;
+  replay_of: string | null | undefined;
+  state: WebhookDeliveryState;
+  attempts: Array<WebhookDeliveryAttempt>;
+  next_attempt_at: DateTime | null | undefined;
+  created_at: DateTime;
+  updated_at: DateTime;
+};
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DateTime } from "../DateTime.js";
+
+export type WebhookDeliveryAttempt = {
+  at: DateTime;
+  status: number | null | undefined;
+  error: string | null | undefined;
+  duration_ms: number;
+};
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type DeadAirEnded = {
+  station_id: string;
+  media_session_id: string;
+  duration_ms: number;
+};
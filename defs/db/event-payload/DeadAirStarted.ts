@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeadAirReason } from "../DeadAirReason.js";
+
+export type DeadAirStarted = {
+  station_id: string;
+  media_session_id: string;
+  reason: DeadAirReason;
+  level_dbfs: number | null | undefined;
+  kbitrate: number;
+  failover: boolean;
+};
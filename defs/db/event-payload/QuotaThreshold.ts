@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LimitKind } from "../LimitKind.js";
+
+export type QuotaThreshold = {
+  account_id: string;
+  limit: LimitKind;
+  percent: number;
+  used: number;
+  total: number;
+};
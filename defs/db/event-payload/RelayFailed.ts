@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MediaSessionRelaySwitchReason } from "../MediaSessionRelaySwitchReason.js";
+
+export type RelayFailed = {
+  station_id: string;
+  url: string;
+  reason: MediaSessionRelaySwitchReason;
+};
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type UploadFinished = {
+  station_id: string;
+  audio_file_id: string;
+  filename: string;
+};
@@ -26,6 +26,12 @@ export type PublicErrorCode =
   | "PLAN_NOT_FOUND"
   | "PAYMENT_METHOD_NOT_FOUND"
   | "INVITATION_NOT_FOUND"
+  | "SCHEDULE_SLOT_NOT_FOUND"
+  | "PLAYLIST_NOT_FOUND"
+  | "WEBHOOK_NOT_FOUND"
+  | "WEBHOOK_DELIVERY_NOT_FOUND"
+  | "LIVE_RECORDING_NOT_FOUND"
+  | "STATION_DJ_NOT_FOUND"
   | "QUERY_STRING_INVALID"
   | "PAYLOAD_IO"
   | "PAYLOAD_JSON"
@@ -38,6 +44,13 @@ export type PublicErrorCode =
   | "ADMIN_EMAIL_EXISTS"
   | "UPLOAD_EMPTY"
   | "UPLOAD_QUOTA_EXCEEDED"
+  | "UPLOAD_DUPLICATE"
+  | "UPLOAD_NOT_FOUND"
+  | "UPLOAD_NOT_OPEN"
+  | "UPLOAD_OFFSET_MISMATCH"
+  | "UPLOAD_INCOMPLETE"
+  | "UPLOAD_TOO_LARGE"
+  | "IMPORT_NOT_FOUND"
   | "UPLOAD_INTERNAL_SPAWN"
   | "UPLOAD_INTENRAL_IO"
   | "UPLOAD_EXIT"
@@ -55,4 +68,6 @@ export type PublicErrorCode =
   | "RENDER_MAIL"
   | "SEND_MAIL"
   | "CREATE_STATION_ACCOUNT_LIMIT"
+  | "TRANSFER_QUOTA_EXCEEDED"
+  | "WEBHOOK_ACCOUNT_LIMIT"
   | "PAYMENTS_PERFORM";
@@ -1,8 +1,15 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioProcessing } from "../AudioProcessing.js";
 import type { CountryCode } from "../CountryCode.js";
+import type { DeadAirSettings } from "../DeadAirSettings.js";
 import type { LangCode } from "../LangCode.js";
+import type { ListenerAccessSettings } from "../ListenerAccessSettings.js";
+import type { LiveInputSettings } from "../LiveInputSettings.js";
+import type { LiveRecordingSettings } from "../LiveRecordingSettings.js";
 import type { Metadata } from "../db/Metadata.js";
+import type { PlaylistTransition } from "../PlaylistTransition.js";
 import type { StationFrequency } from "../StationFrequency.js";
+import type { StationRendition } from "../StationRendition.js";
 import type { StationTypeOfContent } from "../db/StationTypeOfContent.js";
 
 export type StationPatch = {
@@ -32,6 +39,16 @@ export type StationPatch = {
   app_store_url?: string | null | undefined;
   external_relay_url?: string | null | undefined;
   external_relay_redirect?: boolean;
+  external_relay_backup_urls?: Array<string>;
+  renditions?: Array<StationRendition>;
+  timezone?: string;
+  default_playlist_id?: string | null | undefined;
+  playlist_transition?: PlaylistTransition;
+  audio_processing?: AudioProcessing;
+  live_recording?: LiveRecordingSettings;
+  live_input?: LiveInputSettings;
+  dead_air?: DeadAirSettings;
+  listener_access?: ListenerAccessSettings;
   user_metadata?: Metadata;
   system_metadata?: Metadata;
 };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RejectionStats = {
+  last_24h: number;
+  last_7d: number;
+  last_30d: number;
+  last_30d_by_reason: Record<string, number | undefined>;
+};
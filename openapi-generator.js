@@ -31,6 +31,7 @@ const security = [
 const paths = {};
 
 const public_error_payload = JSON.parse(fs.readFileSync(`${__dirname}/defs/error/PublicErrorPayload.schema.json`));
+const country_code = JSON.parse(fs.readFileSync(`${__dirname}/defs/CountryCode.schema.json`));
 
 const process_dir = (target) => {
   const full_target = `${basedir}${target == null ? "" : `/${target}`}`;
@@ -195,6 +196,7 @@ const document = {
 
     schemas: {
       Error: public_error_payload,
+      CountryCode: country_code,
     }
   }
 }
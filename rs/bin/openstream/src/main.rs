@@ -9,6 +9,7 @@ use db::access_token::{AccessToken, GeneratedBy};
 use db::account::recalculate_storage_quota;
 use db::{run_transaction, Model};
 use db::admin::Admin;
+use db::play_history_item::royalty_report::{RoyaltyReportFormat, RoyaltyReportPreset};
 use db::registry::Registry;
 use drop_tracer::DropTracer;
 use futures::{FutureExt, TryStreamExt};
@@ -49,6 +50,7 @@ enum Command {
   CreateToken(CreateToken),
   CreateAdmin(CreateAdmin),
   CheckDb(CheckDb),
+  RoyaltyReport(RoyaltyReport),
 }
 
 #[derive(Debug, Parser)]
@@ -67,6 +69,51 @@ struct CheckDb {
   config: String,
 }
 
+#[derive(Debug, Parser)]
+#[command(about = "Write the royalty report of a station with the listeners and tuning hours")]
+struct RoyaltyReport {
+  /// Path to the configuration file (relative to cwd)
+  #[clap(short, long, default_value_t = String::from("./openstream.toml"))]
+  config: String,
+
+  /// Id of the station
+  #[clap(long)]
+  station: String,
+
+  /// Start of the report, inclusive (RFC 3339, eg: 2024-01-01T00:00:00Z)
+  #[clap(long, value_parser = parse_datetime)]
+  since: DateTime,
+
+  /// End of the report, exclusive (RFC 3339, eg: 2024-02-01T00:00:00Z)
+  #[clap(long, value_parser = parse_datetime)]
+  until: DateTime,
+
+  /// Layout of the report: detailed, tuning_hours or collecting_society
+  #[clap(long, default_value_t = RoyaltyReportPreset::Detailed)]
+  preset: RoyaltyReportPreset,
+
+  /// Override the format of the preset: csv or tsv
+  #[clap(long)]
+  format: Option<RoyaltyReportFormat>,
+
+  /// Override the columns of the preset with a comma separated list of columns
+  #[clap(long)]
+  columns: Option<String>,
+
+  /// Offset of the dates and times of the report in minutes, defaults to UTC
+  #[clap(long, allow_hyphen_values = true)]
+  utc_offset_minutes: Option<i32>,
+
+  /// Path to the output file (relative to cwd)
+  #[clap(short, long)]
+  output: String,
+}
+
+fn parse_datetime(s: &str) -> Result<DateTime, time::error::Parse> {
+  let date = time::OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339)?;
+  Ok(DateTime::from(date))
+}
+
 // #[derive(Debug, Parser)]
 // #[command(about = "Create a cluster of n --instances of `openstream start` processes")]
 // struct Cluster {
@@ -149,6 +196,7 @@ fn cmd() -> Result<(), anyhow::Error> {
     Command::CreateToken(opts) => token(opts),
     Command::CreateAdmin(opts) => create_admin(opts),
     Command::CheckDb(opts) => check_db(opts),
+    Command::RoyaltyReport(opts) => royalty_report(opts),
   }
 }
 
@@ -327,6 +375,65 @@ async fn check_db_async(opts: CheckDb) -> Result<(), anyhow::Error> {
   }
 }
 
+fn royalty_report(opts: RoyaltyReport) -> Result<(), anyhow::Error> {
+  runtime().block_on(royalty_report_async(opts))
+}
+
+async fn royalty_report_async(opts: RoyaltyReport) -> Result<(), anyhow::Error> {
+  use db::play_history_item::royalty_report::{self, RoyaltyReportColumn, RoyaltyReportLayout};
+  use std::io::Write;
+
+  logger::init();
+  shared_init(opts.config).await?;
+
+  royalty_report::check_range(opts.since, opts.until)?;
+
+  let columns = match opts.columns {
+    None => None,
+    Some(columns) => Some(RoyaltyReportColumn::parse_list(&columns)?),
+  };
+
+  let layout = RoyaltyReportLayout::new(
+    Some(opts.preset),
+    opts.format,
+    columns,
+    opts.utc_offset_minutes,
+  )?;
+
+  if !db::station::Station::exists(opts.station.as_str()).await? {
+    bail!("station with id {} not found", opts.station);
+  }
+
+  let mut rows = royalty_report::generate(&opts.station, opts.since, opts.until).await?;
+
+  let file = std::fs::File::create(&opts.output)
+    .with_context(|| format!("error creating royalty report file {}", opts.output))?;
+  let mut writer = std::io::BufWriter::new(file);
+
+  writer
+    .write_all(layout.header().as_bytes())
+    .with_context(|| format!("error writing royalty report to {}", opts.output))?;
+
+  let mut plays = 0;
+  while let Some(row) = rows.next().await? {
+    writer
+      .write_all(layout.row(&row).as_bytes())
+      .with_context(|| format!("error writing royalty report to {}", opts.output))?;
+    plays += 1;
+  }
+
+  writer
+    .flush()
+    .with_context(|| format!("error writing royalty report to {}", opts.output))?;
+
+  info!(
+    "royalty report with {} plays written to {}",
+    plays, opts.output
+  );
+
+  Ok(())
+}
+
 #[cfg(feature = "tracing")]
 fn start_tracing() {
   let console_addr = std::env::var("TOKIO_CONSOLE_BIND").unwrap_or_else(|_| format!("{}:{}", console_subscriber::Server::DEFAULT_IP, console_subscriber::Server::DEFAULT_PORT));
@@ -440,9 +547,12 @@ async fn start_async(Start { config }: Start) -> Result<(), anyhow::Error> {
     ref smtp,
     ref payments,
     ref ws_stats,
+    ref relay,
   } = config.as_ref();
 
   db::access_token::AccessToken::start_autoremove_job();
+  db::resumable_upload::ResumableUpload::start_expire_job();
+  db::live_recording::LiveRecording::start_expire_job();
 
   let mailer = mailer::send::Mailer {
     hostname: smtp.hostname.clone(),
@@ -455,7 +565,24 @@ async fn start_async(Start { config }: Start) -> Result<(), anyhow::Error> {
     let shutdown = Shutdown::new();
     let drop_tracer = DropTracer::new("main");
     //let media_sessions = MediaSessionMap::new(deployment.id.clone(), drop_tracer.clone());
-    let media_sessions = media::MediaSessionMap::new(deployment.id.clone(), drop_tracer.clone(), shutdown.clone());
+    if relay.is_none() {
+      warn!(
+        target: "start",
+        "no relay secret configured, internal relay between deployments is disabled"
+      );
+    }
+
+    let relay_auth =
+      media::relay_auth::RelayAuth::new(relay.as_ref().map(|relay| relay.secret.as_str()));
+
+    let media_sessions = media::MediaSessionMap::new(
+      deployment.id.clone(),
+      relay_auth,
+      mailer.clone(),
+      drop_tracer.clone(),
+      shutdown.clone(),
+    );
+    media_sessions.start_schedule_job();
 
     let futs = futures::stream::FuturesUnordered::new();
 
@@ -494,6 +621,7 @@ async fn start_async(Start { config }: Start) -> Result<(), anyhow::Error> {
         shutdown.clone(),
         drop_tracer.clone(),
         media_sessions.clone(),
+        stream_config.capacity_message_file.clone(),
       );
       let fut = stream.start()?;
       futs.push(async move {
@@ -570,7 +698,7 @@ async fn start_async(Start { config }: Start) -> Result<(), anyhow::Error> {
     //   futs.push(fut.boxed());
     // }
 
-    db::models::transfer_checkpoint::start_background_task();
+    db::models::billing_period::start_background_task();
 
     info!(
       target: "start",
@@ -584,6 +712,8 @@ async fn start_async(Start { config }: Start) -> Result<(), anyhow::Error> {
     tokio::spawn(db::station_picture::upgrade_images_if_needed());
     tokio::spawn(media::health::health_shutdown_job());
     tokio::spawn(db::probe::start_probe_background_job());
+    tokio::spawn(upload::import::start_resume_job(deployment_id.clone()));
+    tokio::spawn(webhooks::start_delivery_job());
     tokio::spawn(async {
       info!(target: "start", "recalculating storage quota");
 
@@ -26,6 +26,43 @@ pub const STREAM_BURST_LENGTH: usize = 12;
 #[const_register]
 pub const STREAM_CHANNEL_CAPACITY: usize = 16;
 
+/// amount of audio bytes between in-band icy metadata blocks
+/// sent to clients that request it with the Icy-MetaData: 1 header
+#[const_register]
+pub const STREAM_ICY_METAINT: usize = 16_000;
+
+/// target duration of the HLS segments in seconds
+/// segments are cut at mp3 frame boundaries so the actual duration can be slightly longer
+#[const_register]
+pub const STREAM_HLS_SEGMENT_DURATION_SECS: u64 = 6;
+
+/// number of segments in the sliding window of the HLS live playlist
+#[const_register]
+pub const STREAM_HLS_PLAYLIST_SEGMENTS: usize = 6;
+
+/// time to wait for the first segment of an HLS stream before responding to the playlist request
+#[const_register]
+pub const STREAM_HLS_PLAYLIST_WAIT_SECS: u64 = 10;
+
+/// delay to stop segmenting a station when its HLS stream had no requests
+#[const_register]
+pub const STREAM_HLS_IDLE_SHUTDOWN_SECS: u64 = 60;
+
+/// delay to close an HLS listener session that had no playlist or segment requests
+/// the session holds the listener slot and the stream connection of the listener
+#[const_register]
+pub const STREAM_HLS_SESSION_IDLE_SECS: u64 = 30;
+
+/// timeout for an HLS request forwarded to the deployment that segments the station
+/// it must be longer than STREAM_HLS_PLAYLIST_WAIT_SECS
+#[const_register]
+pub const STREAM_HLS_FORWARD_TIMEOUT_SECS: u64 = 20;
+
+/// header set in the HLS requests forwarded between openstream servers
+/// a request with this header is never forwarded again
+#[const_register]
+pub const STREAM_HLS_FORWARDED_HEADER: &str = "x-openstream-hls-forwarded";
+
 /// audio file chunk size in bytes
 #[const_register]
 pub const AUDIO_FILE_CHUNK_SIZE: usize = 256 * 1000;
@@ -34,6 +71,180 @@ pub const AUDIO_FILE_CHUNK_SIZE: usize = 256 * 1000;
 #[const_register]
 pub const AUDIO_FILE_BYTERATE: usize = 128_000 / 8; // 128 kbps
 
+/// audio level below which an audio file is considered silent, in dBFS
+#[const_register]
+pub const AUDIO_FILE_SILENCE_THRESHOLD_DB: f64 = -50.0;
+
+/// minimum duration of the silence at the start or the end of an audio file to trim it
+#[const_register]
+pub const AUDIO_FILE_SILENCE_MIN_DURATION_MS: u64 = 500;
+
+/// maximum true peak of an audio file after loudness normalization, in dBTP
+#[const_register]
+pub const AUDIO_FILE_MAX_TRUE_PEAK_DBTP: f64 = -1.0;
+
+/// audio files within this distance of the target loudness of the station are not re-encoded, in dB
+#[const_register]
+pub const AUDIO_FILE_LOUDNESS_TOLERANCE_DB: f64 = 0.5;
+
+/// max size in bytes of each part of a resumable upload stored in the storage db
+/// an interrupted append keeps all the parts that were completely received
+#[const_register]
+pub const RESUMABLE_UPLOAD_PART_SIZE: usize = 1_000_000;
+
+/// resumable uploads that are not updated in this time are removed with their parts
+#[const_register]
+pub const RESUMABLE_UPLOAD_EXPIRE_SECS: u32 = secs::DAY;
+
+/// a live recording is not split before it reaches this duration
+/// so fast changing now playing info does not create a file per change
+#[const_register]
+pub const LIVE_RECORDING_MIN_SEGMENT_SECS: u64 = 30;
+
+/// interval at which the audio of a live or external relay source is measured for dead air
+#[const_register]
+pub const DEAD_AIR_CHECK_INTERVAL_SECS: u64 = 5;
+
+/// max bytes of each check interval that are decoded to measure the audio level of a source
+#[const_register]
+pub const DEAD_AIR_SAMPLE_BYTES: usize = 32_000; // 2 secs at 128 kbps
+
+/// a source in dead air is considered recovered after it has audio for this duration
+#[const_register]
+pub const DEAD_AIR_RECOVERY_SECS: u64 = 10;
+
+/// minimum time between two dead air emails for the same station, across its media sessions
+#[const_register]
+pub const DEAD_AIR_EMAIL_COOLDOWN_SECS: u64 = 60 * 60;
+
+/// max number of urls of an audio import
+#[const_register]
+pub const AUDIO_IMPORT_MAX_URLS: usize = 500;
+
+/// max number of files imported from an archive, the following entries are ignored
+#[const_register]
+pub const AUDIO_IMPORT_MAX_ARCHIVE_ENTRIES: usize = 5_000;
+
+/// max decompressed size of a file imported from an archive, larger files are not imported
+#[const_register]
+pub const AUDIO_IMPORT_MAX_ENTRY_SIZE: u64 = 2_000_000_000; // 2 GB
+
+/// max decompressed size of an archive, the import fails when it is exceeded
+#[const_register]
+pub const AUDIO_IMPORT_MAX_ARCHIVE_SIZE: u64 = 50_000_000_000; // 50 GB
+
+/// interval at which a running audio import checks if it was cancelled
+#[const_register]
+pub const AUDIO_IMPORT_CANCEL_CHECK_INTERVAL_SECS: u64 = 5;
+
+/// timeout to connect to the server of an imported url
+#[const_register]
+pub const AUDIO_IMPORT_URL_CONNECT_TIMEOUT_SECS: u64 = 10;
+
+/// max time to wait for the response headers, and then for each chunk of the body, of an imported url
+#[const_register]
+pub const AUDIO_IMPORT_URL_READ_TIMEOUT_SECS: u64 = 60;
+
+/// max number of webhooks of an account
+#[const_register]
+pub const WEBHOOKS_MAX_PER_ACCOUNT: u64 = 10;
+
+/// time the enabled webhooks of an account are cached by a deployment
+/// to decide if the high frequency events, like the listener events, must be dispatched
+#[const_register]
+pub const WEBHOOK_SUBSCRIPTIONS_CACHE_SECS: u64 = 60;
+
+/// interval at which the delivery job looks for due webhook deliveries
+#[const_register]
+pub const WEBHOOK_DELIVERY_CHECK_INTERVAL_SECS: u64 = 5;
+
+/// time a deployment holds a webhook delivery while sending it,
+/// if the deployment stops the delivery is retried after this time
+#[const_register]
+pub const WEBHOOK_DELIVERY_LEASE_SECS: u64 = 60;
+
+/// timeout of a webhook request, including the connection and the response
+#[const_register]
+pub const WEBHOOK_DELIVERY_TIMEOUT_SECS: u64 = 15;
+
+/// max number of webhook deliveries sent concurrently by a deployment
+#[const_register]
+pub const WEBHOOK_DELIVERY_CONCURRENCY: usize = 16;
+
+/// attempts of a webhook delivery before it is marked as failed
+#[const_register]
+pub const WEBHOOK_DELIVERY_MAX_ATTEMPTS: usize = 8;
+
+/// delay before the first retry of a webhook delivery, it doubles on each following retry
+#[const_register]
+pub const WEBHOOK_DELIVERY_RETRY_BASE_SECS: u64 = 30;
+
+/// max delay between the retries of a webhook delivery
+#[const_register]
+pub const WEBHOOK_DELIVERY_RETRY_MAX_SECS: u64 = 6 * 60 * 60; // 6 hours
+
+/// id of the webhook delivery, the same event can be delivered more than once
+/// if the delivery is retried or replayed
+#[const_register]
+pub const HEADER_WEBHOOK_DELIVERY: &str = "x-openstream-webhook-delivery";
+
+/// kind of the event of a webhook delivery
+#[const_register]
+pub const HEADER_WEBHOOK_EVENT: &str = "x-openstream-webhook-event";
+
+/// unix timestamp (in seconds) of a webhook request, part of the signed message
+#[const_register]
+pub const HEADER_WEBHOOK_TIMESTAMP: &str = "x-openstream-webhook-timestamp";
+
+/// hex encoded HMAC-SHA256 of "{timestamp}.{body}" signed with the webhook secret
+#[const_register]
+pub const HEADER_WEBHOOK_SIGNATURE: &str = "x-openstream-webhook-signature";
+
+/// interval at which the now playing push channel refreshes the state of the subscribed stations
+#[const_register]
+pub const NOW_PLAYING_PUSH_REFRESH_INTERVAL_SECS: u64 = 3;
+
+/// interval of the pings sent to the now playing push channel subscribers
+#[const_register]
+pub const NOW_PLAYING_PUSH_PING_INTERVAL_SECS: u64 = 30;
+
+/// subscribers of the now playing push channel that do not answer a ping in this time are disconnected
+#[const_register]
+pub const NOW_PLAYING_PUSH_PONG_TIMEOUT_SECS: u64 = 75;
+
+/// max number of play history items in an export, the oldest items of the range are left out
+#[const_register]
+pub const PLAY_HISTORY_EXPORT_MAX_ITEMS: u64 = 100_000;
+
+/// number of songs of the public recently played embed when not specified
+#[const_register]
+pub const PLAY_HISTORY_EMBED_DEFAULT_ITEMS: u32 = 10;
+
+/// max number of songs of the public recently played embed
+#[const_register]
+pub const PLAY_HISTORY_EMBED_MAX_ITEMS: u32 = 50;
+
+/// limit of requests to the public recently played embed from the same ip
+/// counted apart from API_IP_LIMIT
+#[const_register]
+pub const PLAY_HISTORY_EMBED_IP_LIMIT: usize = 300;
+
+/// limit restart interval of PLAY_HISTORY_EMBED_IP_LIMIT
+#[const_register]
+pub const PLAY_HISTORY_EMBED_IP_LIMIT_DURATION_SECS: u64 = 60;
+
+/// max range of a royalty report, in days
+#[const_register]
+pub const ROYALTY_REPORT_MAX_RANGE_DAYS: u32 = 93;
+
+/// a play of a royalty report lasts until the next play, up to this duration
+#[const_register]
+pub const ROYALTY_REPORT_MAX_PLAY_DURATION_SECS: u64 = 60 * 60;
+
+/// stream connections shorter than this duration are not counted as listeners in royalty reports
+#[const_register]
+pub const ROYALTY_REPORT_MIN_SESSION_SECS: u64 = 60;
+
 /// station's transfer save interval in milliseconds
 #[const_register]
 pub const TRANSFER_SAVE_INTERVAL_MILLIS: u64 = 5_000;
@@ -42,10 +253,37 @@ pub const TRANSFER_SAVE_INTERVAL_MILLIS: u64 = 5_000;
 #[const_register]
 pub const STREAM_IP_CONNECTIONS_LIMIT: u64 = 8;
 
+/// retry-after header value in seconds for stream connections rejected
+/// because the account reached its listeners limit
+#[const_register]
+pub const STREAM_LISTENERS_LIMIT_RETRY_AFTER_SECS: u32 = 60;
+
+/// retry-after header value in seconds for stream connections rejected
+/// because the account reached its transfer limit for the current billing period
+#[const_register]
+pub const STREAM_TRANSFER_LIMIT_RETRY_AFTER_SECS: u32 = secs::HOUR;
+
+/// interval of the background job that rolls over the accounts billing periods
+#[const_register]
+pub const BILLING_PERIOD_ROLLOVER_INTERVAL_SECS: u64 = 60 * 5;
+
 /// delay to shutdown a playlist media session when it run out of listeners
 #[const_register]
 pub const PLAYLIST_NO_LISTENERS_SHUTDOWN_DELAY_SECS: u64 = 10;
 
+/// duration of the boundary between two playlist files that is re-encoded in gapless mode
+#[const_register]
+pub const PLAYLIST_GAPLESS_WINDOW_MS: u32 = 250;
+
+/// mp3 frames decoded before the end of a playlist file transition and then discarded
+/// they prime the decoder because mp3 frames can reference the data of the previous frames
+#[const_register]
+pub const PLAYLIST_TRANSITION_WARMUP_FRAMES: usize = 2;
+
+/// delay to shutdown a station output rendition when it run out of listeners
+#[const_register]
+pub const RENDITION_NO_LISTENERS_SHUTDOWN_DELAY_SECS: u64 = 10;
+
 #[const_register]
 pub const EXTERNAL_RELAY_NO_LISTENERS_SHUTDOWN_DELAY_SECS: u64 = 60;
 
@@ -64,6 +302,29 @@ pub const EXTERNAL_RELAY_NO_DATA_SHUTDOWN_SECS: u64 = 30;
 #[const_register]
 pub const EXTERNAL_RELAY_NO_DATA_START_SHUTDOWN_SECS: u64 = 90;
 
+/// time to wait before retrying an external relay url that failed
+/// while it is waiting, the relay uses the next url of the failover chain or the station playlist
+#[const_register]
+pub const EXTERNAL_RELAY_SOURCE_RETRY_SECS: u64 = 60;
+
+/// interval to check if the primary external relay url is healthy again
+/// while a backup url or the playlist is on air
+#[const_register]
+pub const EXTERNAL_RELAY_PRIMARY_CHECK_INTERVAL_SECS: u64 = 30;
+
+/// time for the primary external relay url to produce data to be considered healthy
+#[const_register]
+pub const EXTERNAL_RELAY_PRIMARY_CHECK_TIMEOUT_SECS: u64 = 10;
+
+/// max number of source switches recorded in an external relay media session
+#[const_register]
+pub const EXTERNAL_RELAY_SESSION_SWITCHES_MAX: usize = 100;
+
+/// interval to check the schedule of the stations running in a deployment
+/// and switch their source if the active slot changed
+#[const_register]
+pub const STATION_SCHEDULE_CHECK_INTERVAL_SECS: u64 = 5;
+
 /// limit of authotization, or other sensible api endpoints requests from the same ip
 #[const_register]
 pub const API_IP_LIMIT: usize = 60;
@@ -146,6 +407,11 @@ pub const MEDIA_SESSION_HEALTH_SHUTDOWN_TIMEOUT_SECS: u16 = 33;
 #[const_register]
 pub const MEDIA_SESSION_HEALTH_CHECK_KILL_INTERVAL_SECS: u16 = 5;
 
+/// interval in which the now playing info of live and internal-relay media sessions
+/// is read from $media_sessions and passed to the stream listeners
+#[const_register]
+pub const MEDIA_SESSION_NOW_PLAYING_SYNC_INTERVAL_SECS: u64 = 5;
+
 /// internal interval to start new probe task if needed
 #[const_register]
 pub const PROBE_BACKGROUND_JOB_CHECK_INTERVAL_SECS: u32 = 10; // 10 secs
@@ -157,6 +423,26 @@ pub const PROBE_STATION_INTERVAL_SECS: u32 = 5 * 60; // 5 min
 #[const_register]
 pub const HEADER_RELAY_SOURCE_DEPLOYMENT: &str = "x-source-deployment";
 
+/// unix timestamp (in seconds) after which a signed internal relay request is no longer valid
+#[const_register]
+pub const HEADER_RELAY_EXPIRES: &str = "x-openstream-relay-expires";
+
+/// random value of a signed internal relay request, each nonce is accepted only once
+#[const_register]
+pub const HEADER_RELAY_NONCE: &str = "x-openstream-relay-nonce";
+
+/// hex encoded HMAC-SHA256 of an internal relay request, signed with the cluster relay secret
+#[const_register]
+pub const HEADER_RELAY_SIGNATURE: &str = "x-openstream-relay-signature";
+
+/// validity of a signed internal relay request
+#[const_register]
+pub const RELAY_SIGNATURE_TTL_SECS: u64 = 30;
+
+/// allowed clock difference between deployments when checking the expiration of a relay request
+#[const_register]
+pub const RELAY_SIGNATURE_CLOCK_SKEW_SECS: u64 = 5;
+
 #[const_register]
 pub const MEDIA_RELAY_TIMEOUT_SECS: u64 = 35;
 
@@ -228,18 +514,148 @@ pub mod validate {
   #[const_register]
   pub const VALIDATE_STATION_URLS_MAX_LEN: usize = 200;
 
+  // webhooks
+  #[const_register]
+  pub const VALIDATE_WEBHOOK_URL_MAX_LEN: usize = 2000;
+
+  // play history
+  #[const_register]
+  pub const VALIDATE_PLAY_HISTORY_SEARCH_MAX_LEN: usize = 100;
+
   // see https://stackoverflow.com/questions/417142/what-is-the-maximum-length-of-a-url-in-different-browsers
   // for limits on the url length
   // external relay url
   #[const_register]
   pub const VALIDATE_STATION_EXTERNAL_RELAY_URL_MAX_LEN: usize = 2_000;
 
+  #[const_register]
+  pub const VALIDATE_STATION_EXTERNAL_RELAY_BACKUP_URLS_MAX_LEN: usize = 4;
+
   #[const_register]
   pub const VALIDATE_STATION_FREQUENCY_MAX: f64 = 100_000.0;
 
   #[const_register]
   pub const VALIDATE_STATION_FREQUENCY_MIN: f64 = 0.0;
 
+  // audio processing
+  #[const_register]
+  pub const VALIDATE_STATION_TARGET_LOUDNESS_MIN: f64 = -36.0;
+
+  #[const_register]
+  pub const VALIDATE_STATION_TARGET_LOUDNESS_MAX: f64 = -6.0;
+
+  // live recording
+  #[const_register]
+  pub const VALIDATE_STATION_LIVE_RECORDING_SPLIT_SECS_MAX: u32 = 60 * 60 * 6;
+
+  #[const_register]
+  pub const VALIDATE_STATION_LIVE_RECORDING_RETENTION_DAYS_MAX: u32 = 365;
+
+  // dead air
+  #[const_register]
+  pub const VALIDATE_STATION_DEAD_AIR_THRESHOLD_DB_MIN: i16 = -90;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DEAD_AIR_THRESHOLD_DB_MAX: i16 = -20;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DEAD_AIR_DURATION_SECS_MIN: u32 = 10;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DEAD_AIR_DURATION_SECS_MAX: u32 = 60 * 60;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DEAD_AIR_MIN_KBITRATE_MAX: u32 = 128;
+
+  // listener access
+  #[const_register]
+  pub const VALIDATE_STATION_LISTENER_ACCESS_COUNTRIES_MAX_LEN: usize = 250;
+
+  #[const_register]
+  pub const VALIDATE_STATION_LISTENER_ACCESS_CIDRS_MAX_LEN: usize = 100;
+
+  #[const_register]
+  pub const VALIDATE_STATION_LISTENER_ACCESS_DOMAINS_MAX_LEN: usize = 50;
+
+  #[const_register]
+  pub const VALIDATE_STATION_LISTENER_ACCESS_DOMAIN_MAX_LEN: usize = 253;
+
+  // output renditions
+  #[const_register]
+  pub const VALIDATE_STATION_RENDITIONS_MAX_LEN: usize = 8;
+
+  #[const_register]
+  pub const VALIDATE_STATION_RENDITION_SLUG_MIN_LEN: usize = 1;
+
+  #[const_register]
+  pub const VALIDATE_STATION_RENDITION_SLUG_MAX_LEN: usize = 40;
+
+  pub static VALIDATE_STATION_RENDITION_SLUG_PATTERN: Lazy<Regex> =
+    lazy_regex!(r"^([a-z0-9\-\_])+$");
+
+  #[const_register]
+  pub const VALIDATE_STATION_RENDITION_KBITRATE_MIN: u16 = 16;
+
+  #[const_register]
+  pub const VALIDATE_STATION_RENDITION_KBITRATE_MAX: u16 = 320;
+
+  // schedule
+  #[const_register]
+  pub const VALIDATE_STATION_SCHEDULE_SLOTS_MAX_LEN: usize = 200;
+
+  #[const_register]
+  pub const VALIDATE_STATION_SCHEDULE_WEEKLY_DURATION_MINUTES_MAX: u16 = 10_080;
+
+  #[const_register]
+  pub const VALIDATE_STATION_SCHEDULE_PLAYLIST_FILES_MAX_LEN: usize = 1000;
+
+  // djs
+  #[const_register]
+  pub const VALIDATE_STATION_DJS_MAX_LEN: usize = 100;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DJ_NAME_MIN_LEN: usize = 1;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DJ_NAME_MAX_LEN: usize = 60;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DJ_USERNAME_MIN_LEN: usize = 1;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DJ_USERNAME_MAX_LEN: usize = 40;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DJ_PASSWORD_MIN_LEN: usize = 8;
+
+  #[const_register]
+  pub const VALIDATE_STATION_DJ_PASSWORD_MAX_LEN: usize = 100;
+
+  // playlists
+  #[const_register]
+  pub const VALIDATE_PLAYLIST_NAME_MIN_LEN: usize = 1;
+
+  #[const_register]
+  pub const VALIDATE_PLAYLIST_NAME_MAX_LEN: usize = 60;
+
+  #[const_register]
+  pub const VALIDATE_STATION_PLAYLISTS_MAX_LEN: usize = 100;
+
+  #[const_register]
+  pub const VALIDATE_PLAYLIST_ROTATIONS_MAX_LEN: usize = 10;
+
+  #[const_register]
+  pub const VALIDATE_PLAYLIST_ROTATION_EVERY_MIN: u16 = 1;
+
+  #[const_register]
+  pub const VALIDATE_PLAYLIST_ROTATION_EVERY_MAX: u16 = 1000;
+
+  #[const_register]
+  pub const VALIDATE_PLAYLIST_FILES_MAX_LEN: usize = 10_000;
+
+  #[const_register]
+  pub const VALIDATE_STATION_PLAYLIST_CROSSFADE_MS_MAX: u32 = 12_000;
+
   #[const_register]
   pub const VALIDATE_ACCOUNT_NAME_MIN_LEN: usize = 1;
 
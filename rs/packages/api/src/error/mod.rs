@@ -16,6 +16,8 @@ use std::process::ExitStatus;
 
 use self::public::{PublicErrorCode, PublicErrorPayload};
 use db::error::ApplyPatchError;
+use upload::import::ImportError;
+use upload::resumable::ResumableUploadError;
 use upload::UploadError;
 
 pub mod public;
@@ -97,6 +99,24 @@ pub enum ApiError {
   #[error("invitation not found: {0}")]
   InvitationNotFound(String),
 
+  #[error("schedule slot not found: {0}")]
+  ScheduleSlotNotFound(String),
+
+  #[error("playlist not found: {0}")]
+  PlaylistNotFound(String),
+
+  #[error("webhook not found: {0}")]
+  WebhookNotFound(String),
+
+  #[error("webhook delivery not found: {0}")]
+  WebhookDeliveryNotFound(String),
+
+  #[error("live recording not found: {0}")]
+  LiveRecordingNotFound(String),
+
+  #[error("station dj not found: {0}")]
+  StationDjNotFound(String),
+
   #[error("payload io: {0}")]
   PayloadIo(hyper::Error),
 
@@ -142,6 +162,27 @@ pub enum ApiError {
   #[error("upload quota exceeded")]
   UploadQuotaExceeded,
 
+  #[error("upload duplicate: {0}")]
+  UploadDuplicate(String),
+
+  #[error("upload not found: {0}")]
+  UploadNotFound(String),
+
+  #[error("upload not open: {0}")]
+  UploadNotOpen(String),
+
+  #[error("upload offset mismatch: expected {expected}, received {received}")]
+  UploadOffsetMismatch { expected: u64, received: u64 },
+
+  #[error("upload incomplete: received {offset} of {len} bytes")]
+  UploadIncomplete { offset: u64, len: u64 },
+
+  #[error("upload too large: {0}")]
+  UploadTooLarge(u64),
+
+  #[error("import not found: {0}")]
+  ImportNotFound(String),
+
   #[error("range invalid")]
   RangeInvalid,
 
@@ -187,6 +228,12 @@ pub enum ApiError {
   #[error("create station account limit")]
   CreateStationAccountLimit,
 
+  #[error("transfer quota exceeded")]
+  TransferQuotaExceeded,
+
+  #[error("webhook account limit")]
+  WebhookAccountLimit,
+
   #[error("payments perform: {0}")]
   PaymentsPerform(payments::error::PerformError),
 }
@@ -221,6 +268,12 @@ impl ApiError {
       UserNotFound(_) => StatusCode::NOT_FOUND,
       AudioFileNotFound(_) => StatusCode::NOT_FOUND,
       InvitationNotFound(_) => StatusCode::NOT_FOUND,
+      ScheduleSlotNotFound(_) => StatusCode::NOT_FOUND,
+      PlaylistNotFound(_) => StatusCode::NOT_FOUND,
+      WebhookNotFound(_) => StatusCode::NOT_FOUND,
+      WebhookDeliveryNotFound(_) => StatusCode::NOT_FOUND,
+      LiveRecordingNotFound(_) => StatusCode::NOT_FOUND,
+      StationDjNotFound(_) => StatusCode::NOT_FOUND,
 
       QueryString(_) => StatusCode::BAD_REQUEST,
       QueryStringCustom(_) => StatusCode::BAD_REQUEST,
@@ -238,6 +291,13 @@ impl ApiError {
 
       UploadEmpty => StatusCode::BAD_REQUEST,
       UploadQuotaExceeded => StatusCode::BAD_REQUEST,
+      UploadDuplicate(_) => StatusCode::CONFLICT,
+      UploadNotFound(_) => StatusCode::NOT_FOUND,
+      UploadNotOpen(_) => StatusCode::CONFLICT,
+      UploadOffsetMismatch { .. } => StatusCode::CONFLICT,
+      UploadIncomplete { .. } => StatusCode::BAD_REQUEST,
+      UploadTooLarge(_) => StatusCode::BAD_REQUEST,
+      ImportNotFound(_) => StatusCode::NOT_FOUND,
       UploadSpawn(_) => StatusCode::INTERNAL_SERVER_ERROR,
       UploadFfmpegIo(_) => StatusCode::INTERNAL_SERVER_ERROR,
       UploadFfmpegExit { .. } => StatusCode::BAD_REQUEST,
@@ -264,6 +324,8 @@ impl ApiError {
       SendMail(_) => StatusCode::INTERNAL_SERVER_ERROR,
 
       CreateStationAccountLimit => StatusCode::FAILED_DEPENDENCY,
+      TransferQuotaExceeded => StatusCode::FAILED_DEPENDENCY,
+      WebhookAccountLimit => StatusCode::BAD_REQUEST,
 
       PaymentsPerform(_) => StatusCode::SERVICE_UNAVAILABLE,
     }
@@ -297,6 +359,12 @@ impl ApiError {
       ApiKeyNotFound(id) => format!("API key with id {id} not found"),
       AudioFileNotFound(id) => format!("Audio file with id {id} not found"),
       InvitationNotFound(id) => format!("Invitation with id {id} not found"),
+      ScheduleSlotNotFound(id) => format!("Schedule slot with id {id} not found"),
+      PlaylistNotFound(id) => format!("Playlist with id {id} not found"),
+      WebhookNotFound(id) => format!("Webhook with id {id} not found"),
+      WebhookDeliveryNotFound(id) => format!("Webhook delivery with id {id} not found"),
+      LiveRecordingNotFound(id) => format!("Live recording with id {id} not found"),
+      StationDjNotFound(id) => format!("DJ with id {id} not found"),
       
       QueryString(e) => format!("Invalid query string: {e}"),
       QueryStringCustom(message) => format!("Invalid query string: {message}"),
@@ -312,6 +380,17 @@ impl ApiError {
 
       UploadEmpty => format!("Payload is empty"),
       UploadQuotaExceeded => format!("Audio quota exceeded"),
+      UploadDuplicate(id) => format!("An audio file with the same content already exists: {id}"),
+      UploadNotFound(id) => format!("Upload with id {id} not found"),
+      UploadNotOpen(id) => format!("Upload with id {id} is being committed or was already committed"),
+      UploadOffsetMismatch { expected, received } => {
+        format!("Upload offset mismatch, expected {expected}, received {received}")
+      }
+      UploadIncomplete { offset, len } => {
+        format!("Upload is incomplete, received {offset} of {len} bytes")
+      }
+      UploadTooLarge(len) => format!("Upload exceeds its declared size of {len} bytes"),
+      ImportNotFound(id) => format!("Import with id {id} not found"),
       UploadSpawn(_) => format!("Internal server error"),
       UploadFfmpegIo(_) => format!("Internal server error"),
       UploadFfmpegExit { .. } => {
@@ -339,6 +418,8 @@ impl ApiError {
       SendMail(_) => format!("There was an error sending the email, try again later"),
       
       CreateStationAccountLimit => format!("You reached your limit of stations for this account, upgrade your plan to add more stations"),
+      TransferQuotaExceeded => format!("This account reached its transfer limit for the current billing period, upgrade your plan to get more transfer"),
+      WebhookAccountLimit => format!("This account reached its limit of {} webhooks", constants::WEBHOOKS_MAX_PER_ACCOUNT),
 
       PaymentsPerform(_) => format!("An error ocurred when processing payment information, try again later"),
     }
@@ -372,6 +453,12 @@ impl ApiError {
       ApiKeyNotFound(_) => PublicErrorCode::ApiKeyNotFound,
       PaymentMethodNotFound(_) => PublicErrorCode::PaymentMethodNotFound,
       InvitationNotFound(_) => PublicErrorCode::InvitationNotFound,
+      ScheduleSlotNotFound(_) => PublicErrorCode::ScheduleSlotNotFound,
+      PlaylistNotFound(_) => PublicErrorCode::PlaylistNotFound,
+      WebhookNotFound(_) => PublicErrorCode::WebhookNotFound,
+      WebhookDeliveryNotFound(_) => PublicErrorCode::WebhookDeliveryNotFound,
+      LiveRecordingNotFound(_) => PublicErrorCode::LiveRecordingNotFound,
+      StationDjNotFound(_) => PublicErrorCode::StationDjNotFound,
 
       QueryString(_) => PublicErrorCode::QueryStringInvalid,
       QueryStringCustom(_) => PublicErrorCode::QueryStringInvalid,
@@ -389,6 +476,13 @@ impl ApiError {
 
       UploadEmpty => PublicErrorCode::UploadEmpty,
       UploadQuotaExceeded => PublicErrorCode::UploadQuotaExceeded,
+      UploadDuplicate(_) => PublicErrorCode::UploadDuplicate,
+      UploadNotFound(_) => PublicErrorCode::UploadNotFound,
+      UploadNotOpen(_) => PublicErrorCode::UploadNotOpen,
+      UploadOffsetMismatch { .. } => PublicErrorCode::UploadOffsetMismatch,
+      UploadIncomplete { .. } => PublicErrorCode::UploadIncomplete,
+      UploadTooLarge(_) => PublicErrorCode::UploadTooLarge,
+      ImportNotFound(_) => PublicErrorCode::ImportNotFound,
       UploadSpawn(_) => PublicErrorCode::UploadInternalSpawn,
       UploadFfmpegIo(_) => PublicErrorCode::UploadIntenralIo,
       UploadFfmpegExit { .. } => PublicErrorCode::UploadExit,
@@ -414,6 +508,8 @@ impl ApiError {
       SendMail(_) => PublicErrorCode::SendMail,
       
       CreateStationAccountLimit => PublicErrorCode::CreateStationAccountLimit,
+      TransferQuotaExceeded => PublicErrorCode::TransferQuotaExceeded,
+      WebhookAccountLimit => PublicErrorCode::WebhookAccountLimit,
       
       PaymentsPerform(_) => PublicErrorCode::PaymentsPerform,
     
@@ -482,13 +578,67 @@ impl<E: Into<ApiError>> From<UploadError<E>> for ApiError {
         ApiError::PayloadInvalid(format!("Account with id {id} not found"))
       }
       UploadError::FfmpegIo(e) => ApiError::UploadFfmpegIo(e),
+      UploadError::Spool(e) => ApiError::UploadFfmpegIo(e),
       UploadError::FfmpegSpawn(e) => ApiError::UploadSpawn(e),
       UploadError::QuotaExceeded => ApiError::UploadQuotaExceeded,
+      UploadError::Duplicate(id) => ApiError::UploadDuplicate(id),
       UploadError::Stream(s) => s.into(),
     }
   }
 }
 
+impl<E: Into<ApiError>> From<ResumableUploadError<E>> for ApiError {
+  fn from(e: ResumableUploadError<E>) -> Self {
+    match e {
+      ResumableUploadError::Stream(e) => e.into(),
+      ResumableUploadError::Mongo(e) => e.into(),
+      ResumableUploadError::StationNotFound(id) => ApiError::StationNotFound(id),
+      ResumableUploadError::AccountNotFound(id) => ApiError::AccountNotFound(id),
+      ResumableUploadError::NotFound(id) => ApiError::UploadNotFound(id),
+      ResumableUploadError::NotOpen(id) => ApiError::UploadNotOpen(id),
+      ResumableUploadError::QuotaExceeded => ApiError::UploadQuotaExceeded,
+      ResumableUploadError::Empty => ApiError::UploadEmpty,
+      ResumableUploadError::OffsetMismatch { expected, received } => {
+        ApiError::UploadOffsetMismatch { expected, received }
+      }
+      ResumableUploadError::TooLarge { len } => ApiError::UploadTooLarge(len),
+      ResumableUploadError::Incomplete { offset, len } => {
+        ApiError::UploadIncomplete { offset, len }
+      }
+      ResumableUploadError::Upload(e) => e.into(),
+    }
+  }
+}
+
+impl From<ImportError> for ApiError {
+  fn from(e: ImportError) -> Self {
+    match e {
+      ImportError::Mongo(e) => e.into(),
+      ImportError::StationNotFound(id) => ApiError::StationNotFound(id),
+      ImportError::UploadNotFound(id) => ApiError::UploadNotFound(id),
+      ImportError::UploadNotOpen(id) => ApiError::UploadNotOpen(id),
+      ImportError::UploadIncomplete { offset, len } => ApiError::UploadIncomplete { offset, len },
+      ImportError::UnknownArchiveFormat(filename) => ApiError::PayloadInvalid(format!(
+        "Unknown archive format for {filename}, supported formats are .zip, .tar, .tar.gz and .tgz"
+      )),
+      ImportError::NoUrls => ApiError::PayloadInvalid(String::from("At least one url is required")),
+      ImportError::TooManyUrls(max) => {
+        ApiError::PayloadInvalid(format!("At most {max} urls can be imported at once"))
+      }
+      ImportError::InvalidUrl(url) => ApiError::PayloadInvalid(format!(
+        "Invalid url {url}, only http and https urls are supported"
+      )),
+      ImportError::NotFound(id) => ApiError::ImportNotFound(id),
+    }
+  }
+}
+
+impl From<webhooks::WebhookUrlError> for ApiError {
+  fn from(e: webhooks::WebhookUrlError) -> Self {
+    ApiError::PayloadInvalid(format!("Invalid webhook url: {e}"))
+  }
+}
+
 impl From<HttpRangeParseError> for ApiError {
   fn from(e: HttpRangeParseError) -> Self {
     match e {
@@ -84,6 +84,12 @@ pub enum PublicErrorCode {
   PlanNotFound,
   PaymentMethodNotFound,
   InvitationNotFound,
+  ScheduleSlotNotFound,
+  PlaylistNotFound,
+  WebhookNotFound,
+  WebhookDeliveryNotFound,
+  LiveRecordingNotFound,
+  StationDjNotFound,
 
   QueryStringInvalid,
 
@@ -101,6 +107,13 @@ pub enum PublicErrorCode {
 
   UploadEmpty,
   UploadQuotaExceeded,
+  UploadDuplicate,
+  UploadNotFound,
+  UploadNotOpen,
+  UploadOffsetMismatch,
+  UploadIncomplete,
+  UploadTooLarge,
+  ImportNotFound,
   UploadInternalSpawn,
   UploadIntenralIo,
   UploadExit,
@@ -125,6 +138,8 @@ pub enum PublicErrorCode {
   SendMail,
 
   CreateStationAccountLimit,
+  TransferQuotaExceeded,
+  WebhookAccountLimit,
 
   PaymentsPerform,
 }
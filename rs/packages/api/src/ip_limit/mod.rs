@@ -4,8 +4,25 @@ use std::collections::{btree_map::Entry, BTreeMap};
 use std::net::IpAddr;
 use std::time::Duration;
 
+/// counts the requests of each ip in a sliding window of `duration_ms`
+#[derive(Debug)]
+pub struct IpLimitMap {
+  map: RwLock<BTreeMap<IpAddr, usize>>,
+  limit: usize,
+  duration_ms: u64,
+}
+
+/// shared by the authorization and other sensible endpoints
+#[dynamic]
+static IP_LIMIT_MAP: IpLimitMap = IpLimitMap::new(LIMIT, LIMIT_DURATION_MS);
+
+/// the public play history embed has its own budget so that embeds
+/// polled by many listeners don't lock them out of the sensible endpoints
 #[dynamic]
-static IP_LIMIT_MAP: RwLock<BTreeMap<IpAddr, usize>> = RwLock::new(BTreeMap::new());
+pub static PLAY_HISTORY_EMBED_IP_LIMIT_MAP: IpLimitMap = IpLimitMap::new(
+  constants::PLAY_HISTORY_EMBED_IP_LIMIT,
+  constants::PLAY_HISTORY_EMBED_IP_LIMIT_DURATION_SECS * 1000,
+);
 
 #[cfg(test)]
 pub const LIMIT: usize = 60;
@@ -18,58 +35,81 @@ pub const LIMIT: usize = constants::API_IP_LIMIT;
 pub const LIMIT_DURATION_MS: u64 = constants::API_IP_LIMIT_DURATION_SECS * 1000;
 
 pub fn get(ip: IpAddr) -> usize {
-  let map = IP_LIMIT_MAP.read();
-  *map.get(&ip).unwrap_or(&0)
+  IP_LIMIT_MAP.get(ip)
 }
 
 pub fn should_reject(ip: IpAddr) -> bool {
-  get(ip) >= LIMIT
+  IP_LIMIT_MAP.should_reject(ip)
 }
 
 pub fn hit(ip: IpAddr) -> usize {
-  let v = increment(ip);
-  let _handle = tokio::spawn(async move {
-    tokio::time::sleep(Duration::from_millis(LIMIT_DURATION_MS)).await;
-    decrement(ip);
-  });
-
-  #[cfg(test)]
-  eprintln!(
-    "size of decrement handle: {} bytes",
-    std::mem::size_of_val(&_handle)
-  );
-
-  v
+  IP_LIMIT_MAP.hit(ip)
 }
 
-fn increment(ip: IpAddr) -> usize {
-  let mut map = IP_LIMIT_MAP.write();
-  match map.entry(ip) {
-    Entry::Vacant(entry) => {
-      entry.insert(1);
-      1
+impl IpLimitMap {
+  pub fn new(limit: usize, duration_ms: u64) -> Self {
+    Self {
+      map: RwLock::new(BTreeMap::new()),
+      limit,
+      duration_ms,
     }
-    Entry::Occupied(mut entry) => {
-      let v = entry.get_mut();
-      *v += 1;
-      *v
+  }
+
+  pub fn get(&self, ip: IpAddr) -> usize {
+    let map = self.map.read();
+    *map.get(&ip).unwrap_or(&0)
+  }
+
+  pub fn should_reject(&self, ip: IpAddr) -> bool {
+    self.get(ip) >= self.limit
+  }
+
+  pub fn hit(&'static self, ip: IpAddr) -> usize {
+    let v = self.increment(ip);
+    let duration_ms = self.duration_ms;
+    let _handle = tokio::spawn(async move {
+      tokio::time::sleep(Duration::from_millis(duration_ms)).await;
+      self.decrement(ip);
+    });
+
+    #[cfg(test)]
+    eprintln!(
+      "size of decrement handle: {} bytes",
+      std::mem::size_of_val(&_handle)
+    );
+
+    v
+  }
+
+  fn increment(&self, ip: IpAddr) -> usize {
+    let mut map = self.map.write();
+    match map.entry(ip) {
+      Entry::Vacant(entry) => {
+        entry.insert(1);
+        1
+      }
+      Entry::Occupied(mut entry) => {
+        let v = entry.get_mut();
+        *v += 1;
+        *v
+      }
     }
   }
-}
 
-fn decrement(ip: IpAddr) -> usize {
-  let mut map = IP_LIMIT_MAP.write();
-  if let Entry::Occupied(mut entry) = map.entry(ip) {
-    let v = entry.get_mut();
-    if *v <= 1 {
-      entry.remove();
-      0
+  fn decrement(&self, ip: IpAddr) -> usize {
+    let mut map = self.map.write();
+    if let Entry::Occupied(mut entry) = map.entry(ip) {
+      let v = entry.get_mut();
+      if *v <= 1 {
+        entry.remove();
+        0
+      } else {
+        *v = v.saturating_sub(1);
+        *v
+      }
     } else {
-      *v = v.saturating_sub(1);
-      *v
+      0
     }
-  } else {
-    0
   }
 }
 
@@ -91,5 +131,16 @@ async fn hit_count_and_reset() {
 
   assert!(!should_reject(ip));
 
-  assert!(IP_LIMIT_MAP.read().is_empty())
+  assert!(IP_LIMIT_MAP.map.read().is_empty())
+}
+
+#[cfg(test)]
+#[test_util::async_test]
+async fn maps_have_separate_counts() {
+  let ip = IpAddr::from([1, 1, 1, 2]);
+
+  PLAY_HISTORY_EMBED_IP_LIMIT_MAP.hit(ip);
+
+  assert_eq!(PLAY_HISTORY_EMBED_IP_LIMIT_MAP.get(ip), 1);
+  assert_eq!(get(ip), 0);
 }
@@ -2,6 +2,7 @@ pub mod error;
 pub mod ip_limit;
 pub mod json;
 pub mod me;
+pub mod now_playing;
 pub mod qs;
 pub mod request_ext;
 pub mod routes;
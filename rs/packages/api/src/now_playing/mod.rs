@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use constants::NOW_PLAYING_PUSH_REFRESH_INTERVAL_SECS;
+use db::media_session::{MediaSession, MediaSessionKind, MediaSessionNowPlaying};
+use db::stream_connection::index::{IsOpenFilter, MemIndex};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Notify};
+use ts_rs::TS;
+
+/// what a station is transmitting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(
+  export,
+  export_to = "../../../defs/api/embed/station/[station]/now-playing/WS/"
+)]
+pub enum StationStatus {
+  #[serde(rename = "offline")]
+  Offline,
+  #[serde(rename = "live")]
+  Live,
+  #[serde(rename = "playlist")]
+  Playlist,
+  #[serde(rename = "external-relay")]
+  ExternalRelay,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(
+  export,
+  export_to = "../../../defs/api/embed/station/[station]/now-playing/WS/"
+)]
+pub struct StationState {
+  pub status: StationStatus,
+  pub now_playing: Option<MediaSessionNowPlaying>,
+  /// open stream connections of the station, in all the deployments
+  pub listeners: u32,
+}
+
+impl StationState {
+  pub fn new(media_session: Option<MediaSession>, listeners: u32) -> Self {
+    match media_session {
+      None => Self {
+        status: StationStatus::Offline,
+        now_playing: None,
+        listeners,
+      },
+      Some(media_session) => Self {
+        status: match media_session.kind {
+          MediaSessionKind::Live { .. } => StationStatus::Live,
+          MediaSessionKind::Playlist { .. } => StationStatus::Playlist,
+          MediaSessionKind::ExternalRelay { .. } => StationStatus::ExternalRelay,
+        },
+        // the play history item id is internal to the station
+        now_playing: media_session
+          .now_playing
+          .map(|now_playing| MediaSessionNowPlaying {
+            play_history_item_id: None,
+            ..now_playing
+          }),
+        listeners,
+      },
+    }
+  }
+}
+
+type Channels = HashMap<String, watch::Sender<Option<StationState>>>;
+
+/// shares the state of the stations between all the push channel subscribers of this deployment
+///
+/// there is one channel per subscribed station, and a single task refreshes all of them,
+/// with one query for the current media sessions and one read of the stream connections index,
+/// so the cost of a refresh does not depend on the number of subscribers
+#[derive(Debug, Clone)]
+pub struct NowPlayingHub {
+  inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+  index: MemIndex,
+  channels: Mutex<Channels>,
+  refresh: Notify,
+}
+
+impl NowPlayingHub {
+  /// creates the hub and starts its refresh task, the task ends when the hub is dropped
+  pub fn new(index: MemIndex) -> Self {
+    let inner = Arc::new(Inner {
+      index,
+      channels: Mutex::new(HashMap::new()),
+      refresh: Notify::new(),
+    });
+
+    tokio::spawn(run(Arc::downgrade(&inner)));
+
+    Self { inner }
+  }
+
+  /// the receiver holds None until the first refresh of a newly subscribed station
+  pub fn subscribe(&self, station_id: &str) -> watch::Receiver<Option<StationState>> {
+    let mut channels = self.inner.channels.lock();
+    if let Some(sender) = channels.get(station_id) {
+      return sender.subscribe();
+    }
+
+    let (sender, receiver) = watch::channel(None);
+    channels.insert(station_id.to_string(), sender);
+    drop(channels);
+
+    self.inner.refresh.notify_one();
+
+    receiver
+  }
+}
+
+async fn run(inner: Weak<Inner>) {
+  let mut interval =
+    tokio::time::interval(Duration::from_secs(NOW_PLAYING_PUSH_REFRESH_INTERVAL_SECS));
+
+  loop {
+    {
+      let inner = match inner.upgrade() {
+        Some(inner) => inner,
+        None => return,
+      };
+
+      if let Err(e) = refresh(&inner).await {
+        log::warn!(
+          target: "now-playing-push",
+          "error refreshing now playing state: {e} => {e:?}"
+        );
+      }
+    }
+
+    let inner = match inner.upgrade() {
+      Some(inner) => inner,
+      None => return,
+    };
+
+    tokio::select! {
+      _ = interval.tick() => {}
+      _ = inner.refresh.notified() => {}
+    }
+  }
+}
+
+async fn refresh(inner: &Inner) -> Result<(), mongodb::error::Error> {
+  let station_ids: Vec<String> = {
+    let mut channels = inner.channels.lock();
+    channels.retain(|_, sender| sender.receiver_count() != 0);
+    channels.keys().cloned().collect()
+  };
+
+  if station_ids.is_empty() {
+    return Ok(());
+  }
+
+  let mut media_sessions = MediaSession::get_current_for_stations(&station_ids).await?;
+
+  let mut listeners = inner
+    .index
+    .count_by_station(
+      station_ids.iter().cloned().collect::<HashSet<String>>(),
+      IsOpenFilter(true),
+    )
+    .await;
+
+  let channels = inner.channels.lock();
+  for station_id in station_ids {
+    let sender = match channels.get(&station_id) {
+      Some(sender) => sender,
+      None => continue,
+    };
+
+    let state = StationState::new(
+      media_sessions.remove(&station_id),
+      listeners.remove(&station_id).unwrap_or(0),
+    );
+
+    sender.send_if_modified(|current| {
+      if current.as_ref() == Some(&state) {
+        false
+      } else {
+        *current = Some(state);
+        true
+      }
+    });
+  }
+
+  Ok(())
+}
@@ -5,6 +5,7 @@ use db::station::Station;
 use mongodb::bson::doc;
 
 use db::admin::Admin;
+use db::audit_log::{AuditLogActor, AuditLogOrigin};
 use db::models::user_account_relation::{UserAccountRelation, UserAccountRelationKind};
 use db::{
   access_token::{AccessToken, Scope},
@@ -417,6 +418,38 @@ pub async fn get_access_token_scope(
   internal_get_access_token_scope(req, false).await
 }
 
+/// the access token scope of the request, with the origin of the request to write it to the audit log
+pub async fn get_audited_access_token_scope(
+  req: &Request,
+) -> Result<(AccessTokenScope, AuditLogOrigin), GetAccessTokenScopeError> {
+  let token = internal_get_access_token(req, false).await?;
+  let scope = get_scope_from_token(req, &token).await?;
+
+  // global and admin tokens are delegated to a user with the as_user query
+  let as_user_id = match (&token.scope, &scope) {
+    (Scope::Global | Scope::Admin { .. }, AccessTokenScope::User(user)) => Some(user.id.as_str()),
+    _ => None,
+  };
+
+  let actor = AuditLogActor::from_scope(&token.scope, as_user_id);
+
+  Ok((scope, get_audit_log_origin(req, actor)))
+}
+
+pub fn get_audit_log_origin(req: &Request, actor: AuditLogActor) -> AuditLogOrigin {
+  let user_agent = req
+    .headers()
+    .get(hyper::header::USER_AGENT)
+    .and_then(|v| v.to_str().ok())
+    .map(ToString::to_string);
+
+  AuditLogOrigin {
+    actor,
+    ip: req.isomorphic_ip(),
+    user_agent,
+  }
+}
+
 // TODO: this should be in reverse order
 pub async fn get_optional_access_token_scope(
   req: &Request,
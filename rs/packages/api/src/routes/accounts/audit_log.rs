@@ -0,0 +1,136 @@
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::audit_log::{AuditLog, AuditLogAction, AuditLogFilter};
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use crate::qs::PaginationQs;
+  use db::Paged;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/audit-log/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+    /// only the entries of this action
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<AuditLogAction>,
+    /// only the entries of this station
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub station_id: Option<String>,
+    /// only the entries created at or after this date
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime>,
+    /// only the entries created before this date
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    account_id: String,
+    query: Query,
+  }
+
+  /// the audit log of the account, newest first
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/audit-log/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Paged<AuditLog>);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    Query(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => e.into(),
+        ParseError::Query(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = ApiError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let account_id = req.param("account").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let account = access_token_scope
+        .grant_account_owner_scope(account_id)
+        .await?;
+
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input {
+        account_id: account.id,
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        account_id,
+        query:
+          Query {
+            page: PaginationQs { skip, limit },
+            action,
+            station_id,
+            since,
+            until,
+          },
+      } = input;
+
+      let filter = AuditLogFilter {
+        account_id: Some(&account_id),
+        station_id: station_id.as_deref(),
+        action,
+        since,
+        until,
+        ..Default::default()
+      };
+
+      let sort = doc! { AuditLog::KEY_CREATED_AT: -1 };
+      let page = AuditLog::paged(filter.to_document(), sort, skip, limit).await?;
+
+      Ok(Output(page))
+    }
+  }
+}
@@ -90,6 +90,7 @@ pub mod patch {
   use super::*;
   use db::{
     account::{Account, AccountPatch, PublicAccount},
+    audit_log::{AuditLog, AuditLogAction, AuditLogChange, AuditLogOrigin, AuditLogTarget},
     plan::Plan,
     run_transaction, Model,
   };
@@ -115,6 +116,7 @@ pub mod patch {
     account_id: String,
     payload: Payload,
     access_token_scope: AccessTokenScope,
+    audit_log_origin: AuditLogOrigin,
   }
 
   #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
@@ -174,13 +176,15 @@ pub mod patch {
     async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
       let account_id = req.param("account").unwrap().to_string();
 
-      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
 
       let payload: Payload = req.read_body_json(100_000).await?;
 
       Ok(Self::Input {
         account_id,
         access_token_scope,
+        audit_log_origin,
         payload,
       })
     }
@@ -189,9 +193,22 @@ pub mod patch {
       let Self::Input {
         payload: Payload { patch },
         access_token_scope,
+        audit_log_origin,
         account_id,
       } = input;
 
+      let audit_log = |before: &Account, after: &Account| {
+        AuditLog::new(
+          audit_log_origin.clone(),
+          vec![after.id.clone()],
+          AuditLogAction::AccountPatch,
+          AuditLogTarget::Account {
+            account_id: after.id.clone(),
+          },
+          AuditLogChange::from_patch(&patch, before, after),
+        )
+      };
+
       let account = match access_token_scope {
         AccessTokenScope::Global | AccessTokenScope::Admin(_) => {
           run_transaction!(session => {
@@ -200,6 +217,8 @@ pub mod patch {
               Some(account) => account,
             };
 
+            let before = account.clone();
+
             if let Some(ref name) = patch.name {
               account.name.clone_from(name);
             }
@@ -232,6 +251,8 @@ pub mod patch {
 
             tx_try!(Account::replace_with_session(&account.id, &account, &mut session).await);
 
+            tx_try!(AuditLog::insert_with_session(&audit_log(&before, &account), &mut session).await);
+
             account
           })
         }
@@ -244,6 +265,8 @@ pub mod patch {
               Some(account) => account,
             };
 
+            let before = account.clone();
+
             if let Some(name) = &patch.name {
               account.name = name.to_string();
             }
@@ -271,6 +294,8 @@ pub mod patch {
 
             tx_try!(Account::replace_with_session(&account.id, &account, &mut session).await);
 
+            tx_try!(AuditLog::insert_with_session(&audit_log(&before, &account), &mut session).await);
+
             account
           })
         }
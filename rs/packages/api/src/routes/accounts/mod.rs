@@ -1,6 +1,8 @@
+pub mod audit_log;
 pub mod id;
 pub mod members;
 pub mod stream_stats;
+pub mod webhooks;
 
 use crate::json::JsonHandler;
 use crate::request_ext::{self, AccessTokenScope, GetAccessTokenScopeError};
@@ -366,6 +368,8 @@ pub mod post {
         payment_method_id: None,
         name,
         limits,
+        billing_period_start: Some(now),
+        billing_period_anchor_day: Some(now.day()),
         system_metadata,
         user_metadata,
         created_at: now,
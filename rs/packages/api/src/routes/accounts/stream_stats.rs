@@ -3,7 +3,8 @@ use crate::request_ext::{self, GetAccessTokenScopeError};
 
 use async_trait::async_trait;
 use db::station::Station;
-use db::stream_connection::stats::Stats;
+use db::stream_connection::rejection::StreamConnectionRejection;
+use db::stream_connection::stats::{RejectionStats, Stats};
 // use db::stream_connection::StreamConnection;
 use db::Model;
 use mongodb::bson::doc;
@@ -36,6 +37,7 @@ pub mod get {
   #[macros::schema_ts_export]
   pub struct Output {
     pub stats: Stats,
+    pub rejections: RejectionStats,
   }
 
   #[async_trait]
@@ -57,6 +59,11 @@ pub mod get {
     async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
       let Input { account_id } = input;
 
+      let rejections = StreamConnectionRejection::get_stats(
+        doc! { StreamConnectionRejection::KEY_ACCOUNT_ID: &account_id },
+      )
+      .await?;
+
       let station_ids = Station::cl()
         .distinct(
           Station::KEY_ID,
@@ -68,6 +75,7 @@ pub mod get {
       if station_ids.is_empty() {
         return Ok(Output {
           stats: Default::default(),
+          rejections,
         });
       }
 
@@ -94,7 +102,7 @@ pub mod get {
         }
       };
 
-      Ok(Output { stats })
+      Ok(Output { stats, rejections })
     }
   }
 }
@@ -0,0 +1,136 @@
+pub mod id;
+pub mod replay;
+
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::webhook_delivery::WebhookDelivery;
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::get_account_webhook;
+
+/// gets a delivery of a webhook of the account
+pub async fn get_webhook_delivery(
+  account_id: &str,
+  webhook_id: &str,
+  delivery_id: &str,
+) -> Result<WebhookDelivery, ApiError> {
+  let webhook = get_account_webhook(account_id, webhook_id).await?;
+
+  let filter = doc! {
+    WebhookDelivery::KEY_ID: delivery_id,
+    WebhookDelivery::KEY_WEBHOOK_ID: &webhook.id,
+  };
+
+  match WebhookDelivery::get(filter).await? {
+    Some(delivery) => Ok(delivery),
+    None => Err(ApiError::WebhookDeliveryNotFound(delivery_id.to_string())),
+  }
+}
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use crate::qs::PaginationQs;
+  use db::Paged;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/[webhook]/deliveries/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    account_id: String,
+    webhook_id: String,
+    query: Query,
+  }
+
+  /// the deliveries of the webhook, newest first
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/[webhook]/deliveries/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Paged<WebhookDelivery>);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    Query(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => e.into(),
+        ParseError::Query(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = ApiError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let account_id = req.param("account").unwrap();
+      let webhook_id = req.param("webhook").unwrap().to_string();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let account = access_token_scope
+        .grant_account_owner_scope(account_id)
+        .await?;
+
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input {
+        account_id: account.id,
+        webhook_id,
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        account_id,
+        webhook_id,
+        query: Query {
+          page: PaginationQs { skip, limit },
+        },
+      } = input;
+
+      let webhook = get_account_webhook(&account_id, &webhook_id).await?;
+
+      let filter = doc! { WebhookDelivery::KEY_WEBHOOK_ID: &webhook.id };
+      let sort = doc! { WebhookDelivery::KEY_CREATED_AT: -1 };
+      let page = WebhookDelivery::paged(filter, sort, skip, limit).await?;
+
+      Ok(Output(page))
+    }
+  }
+}
@@ -0,0 +1,74 @@
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::webhook_delivery::WebhookDelivery;
+use prex::Request;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::get_webhook_delivery;
+
+pub mod post {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    account_id: String,
+    webhook_id: String,
+    delivery_id: String,
+  }
+
+  /// the new pending delivery, with the same payload of the replayed one
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/[webhook]/deliveries/[delivery]/replay/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(WebhookDelivery);
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = ApiError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let account_id = req.param("account").unwrap();
+      let webhook_id = req.param("webhook").unwrap().to_string();
+      let delivery_id = req.param("delivery").unwrap().to_string();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let account = access_token_scope
+        .grant_account_owner_scope(account_id)
+        .await?;
+
+      Ok(Self::Input {
+        account_id: account.id,
+        webhook_id,
+        delivery_id,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        account_id,
+        webhook_id,
+        delivery_id,
+      } = input;
+
+      let delivery = get_webhook_delivery(&account_id, &webhook_id, &delivery_id).await?;
+      let replay = delivery.replay().await?;
+
+      Ok(Output(replay))
+    }
+  }
+}
@@ -0,0 +1,281 @@
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::webhook::Webhook;
+use db::webhook_delivery::WebhookDelivery;
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::get_account_webhook;
+
+pub mod get {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    account_id: String,
+    webhook_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/[webhook]/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Webhook);
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = ApiError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let account_id = req.param("account").unwrap();
+      let webhook_id = req.param("webhook").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let account = access_token_scope
+        .grant_account_owner_scope(account_id)
+        .await?;
+
+      Ok(Self::Input {
+        account_id: account.id,
+        webhook_id: webhook_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        account_id,
+        webhook_id,
+      } = input;
+
+      let webhook = get_account_webhook(&account_id, &webhook_id).await?;
+
+      Ok(Output(webhook))
+    }
+  }
+}
+
+pub mod patch {
+
+  use db::event::EventKind;
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use serde_util::DateTime;
+  use validator::Validate;
+
+  use super::super::unique_events;
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/[webhook]/PATCH/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    #[ts(optional)]
+    #[modify(trim)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<EventKind>>,
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// replaces the secret of the webhook with a new random one
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate_secret: Option<bool>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    account_id: String,
+    webhook_id: String,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/[webhook]/PATCH/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Webhook);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => e.into(),
+        ParseError::Payload(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = ApiError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let account_id = req.param("account").unwrap();
+      let webhook_id = req.param("webhook").unwrap().to_string();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let account = access_token_scope
+        .grant_account_owner_scope(account_id)
+        .await?;
+
+      let payload: Payload = req.read_body_json(100_000).await?;
+
+      Ok(Self::Input {
+        account_id: account.id,
+        webhook_id,
+        payload,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        account_id,
+        webhook_id,
+        payload:
+          Payload {
+            url,
+            events,
+            enabled,
+            rotate_secret,
+          },
+      } = input;
+
+      let rotate_secret = rotate_secret.unwrap_or(false);
+
+      if url.is_none() && events.is_none() && enabled.is_none() && !rotate_secret {
+        return Err(ApiError::PatchEmpty);
+      }
+
+      let mut webhook = get_account_webhook(&account_id, &webhook_id).await?;
+
+      if let Some(url) = url {
+        webhook.url = webhooks::parse_url(&url)?.to_string();
+      }
+
+      if let Some(events) = events {
+        webhook.events = unique_events(events);
+      }
+
+      if let Some(enabled) = enabled {
+        webhook.enabled = enabled;
+      }
+
+      if rotate_secret {
+        webhook.secret = Webhook::random_secret();
+      }
+
+      webhook.updated_at = DateTime::now();
+
+      Webhook::replace(&webhook.id, &webhook).await?;
+
+      Ok(Output(webhook))
+    }
+  }
+}
+
+pub mod delete {
+
+  use serde_util::empty_struct::EmptyStruct;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    account_id: String,
+    webhook_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/[webhook]/DELETE/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(EmptyStruct);
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = ApiError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let account_id = req.param("account").unwrap();
+      let webhook_id = req.param("webhook").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let account = access_token_scope
+        .grant_account_owner_scope(account_id)
+        .await?;
+
+      Ok(Self::Input {
+        account_id: account.id,
+        webhook_id: webhook_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        account_id,
+        webhook_id,
+      } = input;
+
+      let filter = doc! {
+        Webhook::KEY_ID: &webhook_id,
+        Webhook::KEY_ACCOUNT_ID: &account_id,
+      };
+
+      let webhook = match Webhook::cl().find_one_and_delete(filter, None).await? {
+        Some(webhook) => webhook,
+        None => return Err(ApiError::WebhookNotFound(webhook_id)),
+      };
+
+      WebhookDelivery::cl()
+        .delete_many(doc! { WebhookDelivery::KEY_WEBHOOK_ID: &webhook.id }, None)
+        .await?;
+
+      Ok(Output(EmptyStruct(())))
+    }
+  }
+}
@@ -0,0 +1,255 @@
+pub mod deliveries;
+pub mod id;
+
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::event::EventKind;
+use db::webhook::Webhook;
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// gets a webhook of the account, the webhooks of other accounts are reported as not found
+pub async fn get_account_webhook(account_id: &str, webhook_id: &str) -> Result<Webhook, ApiError> {
+  let filter = doc! {
+    Webhook::KEY_ID: webhook_id,
+    Webhook::KEY_ACCOUNT_ID: account_id,
+  };
+
+  match Webhook::get(filter).await? {
+    Some(webhook) => Ok(webhook),
+    None => Err(ApiError::WebhookNotFound(webhook_id.to_string())),
+  }
+}
+
+/// removes the repeated event kinds of a webhook payload, keeping the order
+pub fn unique_events(events: Vec<EventKind>) -> Vec<EventKind> {
+  let mut unique = Vec::with_capacity(events.len());
+  for kind in events {
+    if !unique.contains(&kind) {
+      unique.push(kind);
+    }
+  }
+  unique
+}
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use crate::qs::PaginationQs;
+  use db::Paged;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    account_id: String,
+    query: Query,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Paged<Webhook>);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    Query(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => e.into(),
+        ParseError::Query(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = mongodb::error::Error;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let account_id = req.param("account").unwrap();
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let account = access_token_scope
+        .grant_account_owner_scope(account_id)
+        .await?;
+
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input {
+        account_id: account.id,
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        account_id,
+        query: Query {
+          page: PaginationQs { skip, limit },
+        },
+      } = input;
+
+      let filter = doc! { Webhook::KEY_ACCOUNT_ID: &account_id };
+      let sort = doc! { Webhook::KEY_CREATED_AT: 1 };
+      let page = Webhook::paged(filter, sort, skip, limit).await?;
+
+      Ok(Output(page))
+    }
+  }
+}
+
+pub mod post {
+
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use schemars::JsonSchema;
+  use serde_util::DateTime;
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    #[modify(trim)]
+    pub url: String,
+    /// the kinds of events sent to the url, all the events are sent if empty or not present
+    #[ts(optional)]
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    account_id: String,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/accounts/[account]/webhooks/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Webhook);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => e.into(),
+        ParseError::Payload(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = ApiError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let account_id = req.param("account").unwrap();
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let account = access_token_scope
+        .grant_account_owner_scope(account_id)
+        .await?;
+
+      let payload: Payload = req.read_body_json(100_000).await?;
+
+      Ok(Self::Input {
+        account_id: account.id,
+        payload,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        account_id,
+        payload: Payload {
+          url,
+          events,
+          enabled,
+        },
+      } = input;
+
+      let url = webhooks::parse_url(&url)?;
+
+      let count = Webhook::cl()
+        .count_documents(doc! { Webhook::KEY_ACCOUNT_ID: &account_id }, None)
+        .await?;
+
+      if count >= constants::WEBHOOKS_MAX_PER_ACCOUNT {
+        return Err(ApiError::WebhookAccountLimit);
+      }
+
+      let now = DateTime::now();
+      let webhook = Webhook {
+        id: Webhook::uid(),
+        account_id,
+        url: url.to_string(),
+        secret: Webhook::random_secret(),
+        events: unique_events(events),
+        enabled: enabled.unwrap_or(true),
+        created_at: now,
+        updated_at: now,
+      };
+
+      Webhook::insert(&webhook).await?;
+
+      Ok(Output(webhook))
+    }
+  }
+}
@@ -3,7 +3,9 @@ pub mod post {
   use async_trait::async_trait;
   use constants::validate::*;
   use db::access_token::{AccessToken, GeneratedBy, Scope};
+  use db::audit_log::{AuditLog, AuditLogAction, AuditLogActor, AuditLogOrigin, AuditLogTarget};
   use db::user::{AdminPublicUser, User};
+  use db::user_account_relation::UserAccountRelation;
   use db::Model;
   use modify::Modify;
   use mongodb::bson::doc;
@@ -24,6 +26,7 @@ pub mod post {
     title: String,
     user_id: String,
     access_token: AccessToken,
+    audit_log_origin: AuditLogOrigin,
   }
 
   #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
@@ -104,11 +107,16 @@ pub mod post {
     async fn parse(&self, mut req: Request) -> Result<Input, Self::ParseError> {
       let user_id = req.param("user").unwrap().to_string();
       let access_token = request_ext::get_access_token(&req).await?;
+      let audit_log_origin = request_ext::get_audit_log_origin(
+        &req,
+        AuditLogActor::from_scope(&access_token.scope, None),
+      );
       let Payload { title } = req.read_body_json(2_000).await?;
       Ok(Input {
         user_id,
         title,
         access_token,
+        audit_log_origin,
       })
     }
 
@@ -117,6 +125,7 @@ pub mod post {
         user_id,
         title,
         access_token,
+        audit_log_origin,
       } = input;
       match access_token.scope {
         Scope::Global | Scope::User { .. } | Scope::AdminAsUser { .. } => {
@@ -148,6 +157,24 @@ pub mod post {
 
           AccessToken::insert(&token).await?;
 
+          // the owners of the accounts of the user can see that an admin acted as the user
+          let filter = doc! { UserAccountRelation::KEY_USER_ID: &user.id };
+          let account_ids: Vec<String> =
+            UserAccountRelation::distinct_string(UserAccountRelation::KEY_ACCOUNT_ID, filter)
+              .await?;
+
+          let audit_log = AuditLog::new(
+            audit_log_origin,
+            account_ids,
+            AuditLogAction::AdminDelegate,
+            AuditLogTarget::User {
+              user_id: user.id.clone(),
+            },
+            vec![],
+          );
+
+          AuditLog::insert(&audit_log).await?;
+
           let out = Output {
             user: user.into(),
             token: format!("{}-{}", id, key),
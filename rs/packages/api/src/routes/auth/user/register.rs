@@ -534,6 +534,8 @@ pub mod post {
         payment_method_id: None,
         limits,
         name: account_name,
+        billing_period_start: Some(now),
+        billing_period_anchor_day: Some(now.day()),
         user_metadata: account_user_metadata,
         system_metadata: account_system_metadata,
         created_at: now,
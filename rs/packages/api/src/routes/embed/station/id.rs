@@ -113,7 +113,7 @@ pub mod get {
         Some(media_session) => match media_session.kind {
           MediaSessionKind::Live { .. } => match media_session.now_playing {
             None => {}
-            Some(MediaSessionNowPlaying { title, artist }) => match artist {
+            Some(MediaSessionNowPlaying { title, artist, .. }) => match artist {
               Some(artist) => {
                 now_playing = Some(format!("{} - {}", title, artist));
               }
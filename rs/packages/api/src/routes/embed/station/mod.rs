@@ -1 +1,3 @@
 pub mod id;
+pub mod now_playing;
+pub mod play_history;
@@ -0,0 +1,349 @@
+use std::sync::{
+  atomic::{AtomicU64, Ordering},
+  Arc,
+};
+use std::time::{Duration, Instant};
+
+use constants::{NOW_PLAYING_PUSH_PING_INTERVAL_SECS, NOW_PLAYING_PUSH_PONG_TIMEOUT_SECS};
+use db::{media_session::MediaSessionNowPlaying, station::Station, Model};
+use drop_tracer::DropTracer;
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use hyper::{Body, StatusCode};
+use prex::{
+  handler::Handler,
+  ws::tungstenite::{error::ProtocolError, Message},
+  Next, Request, Response,
+};
+use serde::{Deserialize, Serialize};
+use shutdown::Shutdown;
+use ts_rs::TS;
+
+use crate::now_playing::{NowPlayingHub, StationState, StationStatus};
+
+/// websocket that pushes the now playing info, the live/offline transitions
+/// and the listener count of a station as they change
+#[derive(Debug, Clone)]
+pub struct NowPlayingWsHandler {
+  pub hub: NowPlayingHub,
+  pub drop_tracer: DropTracer,
+  pub shutdown: Shutdown,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NowPlayingWsHandlerError {
+  #[error("db: {0}")]
+  Db(#[from] mongodb::error::Error),
+
+  #[error("station with id {0} not found")]
+  StationNotFound(String),
+
+  #[error("expecting websocket request")]
+  NotWs,
+
+  #[error("websocket protocol error: {0}")]
+  ProtocolError(#[from] ProtocolError),
+}
+
+impl From<NowPlayingWsHandlerError> for Response {
+  fn from(err: NowPlayingWsHandlerError) -> Self {
+    let status = match &err {
+      NowPlayingWsHandlerError::Db(_) => StatusCode::INTERNAL_SERVER_ERROR,
+      NowPlayingWsHandlerError::StationNotFound(_) => StatusCode::NOT_FOUND,
+      NowPlayingWsHandlerError::NotWs => StatusCode::BAD_REQUEST,
+      NowPlayingWsHandlerError::ProtocolError(_) => StatusCode::BAD_REQUEST,
+    };
+
+    let body = match &err {
+      NowPlayingWsHandlerError::Db(_) => Body::from("internal server error (db)"),
+      _ => Body::from(format!("{}", err)),
+    };
+
+    let mut res = Response::new(status);
+    *res.body_mut() = body;
+
+    res
+  }
+}
+
+/// the first event is always start, with the full state of the station,
+/// the following events are sent only for the parts of the state that changed
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(
+  export,
+  export_to = "../../../defs/api/embed/station/[station]/now-playing/WS/"
+)]
+#[serde(tag = "kind")]
+pub enum ServerEvent {
+  #[serde(rename = "ping")]
+  Ping,
+  #[serde(rename = "pong")]
+  Pong,
+  #[serde(rename = "start")]
+  Start { state: StationState },
+  #[serde(rename = "status")]
+  Status { status: StationStatus },
+  #[serde(rename = "now-playing")]
+  NowPlaying {
+    now_playing: Option<MediaSessionNowPlaying>,
+  },
+  #[serde(rename = "listeners")]
+  Listeners { listeners: u32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(
+  export,
+  export_to = "../../../defs/api/embed/station/[station]/now-playing/WS/"
+)]
+#[serde(tag = "kind")]
+pub enum ClientEvent {
+  #[serde(rename = "ping")]
+  Ping,
+  #[serde(rename = "pong")]
+  Pong,
+}
+
+/// the events that take a subscriber from the prev to the current state of the station
+pub fn diff_events(prev: &StationState, current: &StationState) -> Vec<ServerEvent> {
+  let mut events = vec![];
+
+  if prev.status != current.status {
+    events.push(ServerEvent::Status {
+      status: current.status,
+    });
+  }
+
+  if prev.now_playing != current.now_playing {
+    events.push(ServerEvent::NowPlaying {
+      now_playing: current.now_playing.clone(),
+    });
+  }
+
+  if prev.listeners != current.listeners {
+    events.push(ServerEvent::Listeners {
+      listeners: current.listeners,
+    });
+  }
+
+  events
+}
+
+impl NowPlayingWsHandler {
+  async fn handle(&self, mut req: Request) -> Result<Response, NowPlayingWsHandlerError> {
+    if !prex::ws::is_upgrade_request(&req) {
+      return Err(NowPlayingWsHandlerError::NotWs);
+    }
+
+    let station_id = req.param("station").unwrap().to_string();
+
+    if !Station::exists(station_id.as_str()).await? {
+      return Err(NowPlayingWsHandlerError::StationNotFound(station_id));
+    }
+
+    let (res, stream_future) = prex::ws::upgrade(&mut req, None)?;
+
+    let mut state_rx = self.hub.subscribe(&station_id);
+    let shutdown = self.shutdown.clone();
+    let token = self.drop_tracer.token();
+
+    tokio::spawn(async move {
+      let stream = match stream_future.await {
+        Ok(stream) => stream,
+        Err(e) => {
+          log::warn!(
+            target: "now-playing-push",
+            "ERR now playing ws for station {station_id} => {e} {e:?}"
+          );
+          return;
+        }
+      };
+
+      let (mut sink, mut stream) = stream.split();
+
+      let (tx, mut rx) = tokio::sync::mpsc::channel::<Message>(8);
+
+      let start = Instant::now();
+      let last_pong_timestamp = Arc::new(AtomicU64::new(0));
+
+      macro_rules! send {
+        ($event:expr) => {{
+          let event: ServerEvent = $event;
+          let text = serde_json::to_string(&event).unwrap();
+          match tx.send(Message::Text(text)).await {
+            Ok(_) => {}
+            Err(_) => return,
+          };
+        }};
+      }
+
+      let write = async {
+        while let Some(message) = rx.recv().await {
+          if sink.send(message).await.is_err() {
+            return;
+          }
+        }
+      };
+
+      let pong = async {
+        loop {
+          tokio::time::sleep(Duration::from_secs(10)).await;
+
+          if start.elapsed().as_secs() - last_pong_timestamp.load(Ordering::Acquire)
+            > NOW_PLAYING_PUSH_PONG_TIMEOUT_SECS
+          {
+            break;
+          }
+        }
+      };
+
+      let ping = async {
+        loop {
+          tokio::time::sleep(Duration::from_secs(NOW_PLAYING_PUSH_PING_INTERVAL_SECS)).await;
+
+          if tx.send(Message::Ping(vec![])).await.is_err() {
+            return;
+          }
+        }
+      };
+
+      let push = async {
+        // wait for the first refresh of the station
+        let mut prev = loop {
+          if let Some(state) = state_rx.borrow_and_update().clone() {
+            break state;
+          }
+
+          if state_rx.changed().await.is_err() {
+            return;
+          }
+        };
+
+        send!(ServerEvent::Start {
+          state: prev.clone()
+        });
+
+        loop {
+          if state_rx.changed().await.is_err() {
+            return;
+          }
+
+          let current = match state_rx.borrow_and_update().clone() {
+            Some(state) => state,
+            None => continue,
+          };
+
+          for event in diff_events(&prev, &current) {
+            send!(event);
+          }
+
+          prev = current;
+        }
+      };
+
+      let handle = async {
+        'messages: loop {
+          let msg = match stream.next().await {
+            None => break 'messages,
+            Some(Err(_)) => break 'messages,
+            Some(Ok(msg)) => msg,
+          };
+
+          match msg {
+            Message::Text(text) => {
+              let event = match serde_json::from_str::<ClientEvent>(&text) {
+                Ok(event) => event,
+                Err(_) => continue 'messages,
+              };
+
+              match event {
+                ClientEvent::Pong => {}
+                ClientEvent::Ping => {
+                  send!(ServerEvent::Pong);
+                }
+              }
+            }
+
+            Message::Pong(_) => {
+              last_pong_timestamp.store(start.elapsed().as_secs(), Ordering::Release);
+            }
+
+            Message::Close(_) => break 'messages,
+
+            _ => continue 'messages,
+          }
+        }
+      };
+
+      tokio::select! {
+        _ = ping => {}
+        _ = pong => {}
+        _ = write => {}
+        _ = push => {}
+        _ = handle => {}
+        _ = shutdown.signal() => {}
+      };
+
+      drop(token);
+    });
+
+    Ok(res)
+  }
+}
+
+#[async_trait::async_trait]
+impl Handler for NowPlayingWsHandler {
+  async fn call(&self, req: Request, _: Next) -> Response {
+    match self.handle(req).await {
+      Ok(res) => res,
+      Err(err) => {
+        log::warn!(
+          target: "now-playing-push",
+          "now playing ws handle err: {}",
+          err
+        );
+        err.into()
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn state(status: StationStatus, title: Option<&str>, listeners: u32) -> StationState {
+    StationState {
+      status,
+      now_playing: title.map(|title| MediaSessionNowPlaying {
+        title: title.to_string(),
+        artist: None,
+        play_history_item_id: None,
+      }),
+      listeners,
+    }
+  }
+
+  #[test]
+  fn diff_events_only_for_changes() {
+    let a = state(StationStatus::Playlist, Some("a"), 10);
+
+    assert!(diff_events(&a, &a).is_empty());
+
+    let events = diff_events(&a, &state(StationStatus::Playlist, Some("a"), 11));
+    assert!(matches!(
+      events.as_slice(),
+      [ServerEvent::Listeners { listeners: 11 }]
+    ));
+
+    let events = diff_events(&a, &state(StationStatus::Offline, None, 10));
+    assert!(matches!(
+      events.as_slice(),
+      [
+        ServerEvent::Status {
+          status: StationStatus::Offline
+        },
+        ServerEvent::NowPlaying { now_playing: None }
+      ]
+    ));
+  }
+}
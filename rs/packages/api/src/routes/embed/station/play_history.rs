@@ -0,0 +1,171 @@
+use crate::json::JsonHandler;
+
+use async_trait::async_trait;
+use db::station::Station;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+pub mod get {
+
+  use std::net::IpAddr;
+
+  use constants::{PLAY_HISTORY_EMBED_DEFAULT_ITEMS, PLAY_HISTORY_EMBED_MAX_ITEMS};
+  use db::{play_history_item::PlayHistoryItem, Model};
+  use futures_util::TryStreamExt;
+  use mongodb::options::FindOptions;
+  use schemars::JsonSchema;
+  use serde_util::DateTime;
+
+  use crate::error::ApiError;
+  use crate::ip_limit::PLAY_HISTORY_EMBED_IP_LIMIT_MAP;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/embed/station/[station]/play-history/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    /// number of songs, defaults to PLAY_HISTORY_EMBED_DEFAULT_ITEMS, max PLAY_HISTORY_EMBED_MAX_ITEMS
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    limit: u32,
+    ip: IpAddr,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/embed/station/[station]/play-history/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Song {
+    pub title: String,
+    pub artist: Option<String>,
+    pub played_at: DateTime,
+  }
+
+  /// the last songs played in the station, newest first
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/embed/station/[station]/play-history/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    pub station_id: String,
+    pub songs: Vec<Song>,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("mongodb error")]
+    Db(#[from] mongodb::error::Error),
+    #[error("querystring: {0}")]
+    QueryString(#[from] serde_qs::Error),
+    #[error("station with id {station_id} not found")]
+    StationNotFound { station_id: String },
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(err: ParseError) -> Self {
+      match err {
+        ParseError::Db(e) => e.into(),
+        ParseError::QueryString(e) => e.into(),
+        ParseError::StationNotFound { station_id } => ApiError::StationNotFound(station_id),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("too many requests")]
+    TooManyRequests,
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::TooManyRequests => ApiError::TooManyRequests,
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let ip = req.isomorphic_ip();
+      let query: Query = req.qs()?;
+
+      let station = match Station::get_by_id(station_id).await? {
+        Some(station) => station,
+        None => {
+          return Err(ParseError::StationNotFound {
+            station_id: station_id.to_string(),
+          })
+        }
+      };
+
+      let limit = query
+        .limit
+        .unwrap_or(PLAY_HISTORY_EMBED_DEFAULT_ITEMS)
+        .clamp(1, PLAY_HISTORY_EMBED_MAX_ITEMS);
+
+      Ok(Self::Input { station, limit, ip })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input { station, limit, ip } = input;
+
+      if PLAY_HISTORY_EMBED_IP_LIMIT_MAP.should_reject(ip) {
+        return Err(HandleError::TooManyRequests);
+      }
+
+      PLAY_HISTORY_EMBED_IP_LIMIT_MAP.hit(ip);
+
+      let filter = doc! { PlayHistoryItem::KEY_STATION_ID: &station.id };
+      let options = FindOptions::builder()
+        .sort(doc! { PlayHistoryItem::KEY_CREATED_AT: -1 })
+        .limit(limit as i64)
+        .build();
+
+      let songs: Vec<Song> = PlayHistoryItem::cl()
+        .find(filter, options)
+        .await?
+        .map_ok(|item| Song {
+          title: item.title,
+          artist: item.artist,
+          played_at: item.created_at,
+        })
+        .try_collect()
+        .await?;
+
+      Ok(Output {
+        station_id: station.id,
+        songs,
+      })
+    }
+  }
+}
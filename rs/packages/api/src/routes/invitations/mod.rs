@@ -7,6 +7,7 @@ use async_trait::async_trait;
 use db::account::Account;
 use db::account_invitations::{AccountInvitation, AccountInvitationState};
 use db::admin::Admin;
+use db::event::{Event, MemberInvited};
 use db::sent_email::{SentEmail, SentEmailAddress, SentEmailKind};
 use db::user::User;
 use db::{Model, Paged};
@@ -500,6 +501,12 @@ pub mod post {
 
       AccountInvitation::insert(&invitation).await?;
 
+      Event::dispatch_detached(MemberInvited {
+        account_id: invitation.account_id.clone(),
+        invitation_id: invitation.id.clone(),
+        receiver_email: invitation.receiver_email.clone(),
+      });
+
       // email
       {
         let subject = match &sender_name {
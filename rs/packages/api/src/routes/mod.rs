@@ -2,6 +2,7 @@ pub mod me;
 
 pub mod accounts;
 pub mod admins;
+pub mod audit_log;
 pub mod auth;
 pub mod stations;
 pub mod users;
@@ -31,6 +32,7 @@ use shutdown::Shutdown;
 
 use crate::error::ApiError;
 use crate::json::JsonHandler;
+use crate::now_playing::NowPlayingHub;
 
 use payments::client::PaymentsClient;
 
@@ -173,6 +175,20 @@ pub fn router(
     .into_handler(),
   );
 
+  app.at("/runtime/kick-live-source/:station").post(
+    runtime::kick_live_source::station_id::post::Endpoint {
+      media_sessions: media_sessions.clone(),
+    }
+    .into_handler(),
+  );
+
+  app.at("/runtime/dj-updated/:station").post(
+    runtime::dj_updated::station_id::post::Endpoint {
+      media_sessions: media_sessions.clone(),
+    }
+    .into_handler(),
+  );
+
   app
     .at("/analytics")
     .get(analytics::get::Endpoint {}.into_handler());
@@ -280,6 +296,33 @@ pub fn router(
     .at("/accounts/:account/members/:member/set-role")
     .post(accounts::members::id::set_role::post::Endpoint {}.into_handler());
 
+  app
+    .at("/accounts/:account/audit-log")
+    .get(accounts::audit_log::get::Endpoint {}.into_handler());
+
+  app
+    .at("/accounts/:account/webhooks")
+    .get(accounts::webhooks::get::Endpoint {}.into_handler())
+    .post(accounts::webhooks::post::Endpoint {}.into_handler());
+
+  app
+    .at("/accounts/:account/webhooks/:webhook")
+    .get(accounts::webhooks::id::get::Endpoint {}.into_handler())
+    .patch(accounts::webhooks::id::patch::Endpoint {}.into_handler())
+    .delete(accounts::webhooks::id::delete::Endpoint {}.into_handler());
+
+  app
+    .at("/accounts/:account/webhooks/:webhook/deliveries")
+    .get(accounts::webhooks::deliveries::get::Endpoint {}.into_handler());
+
+  app
+    .at("/accounts/:account/webhooks/:webhook/deliveries/:delivery")
+    .get(accounts::webhooks::deliveries::id::get::Endpoint {}.into_handler());
+
+  app
+    .at("/accounts/:account/webhooks/:webhook/deliveries/:delivery/replay")
+    .post(accounts::webhooks::deliveries::replay::post::Endpoint {}.into_handler());
+
   app.at("/accounts/:account/stream-stats").get(
     accounts::stream_stats::get::Endpoint {
       index: stream_connections_index.clone(),
@@ -393,7 +436,7 @@ pub fn router(
     .at("/stations/:station/stream-stats/last-:num([0-9]+):unit(ms|s|min|h|d|w)/count")
     .get(
       stations::stream_stats::since::count::get::Endpoint {
-        index: stream_connections_index,
+        index: stream_connections_index.clone(),
       }
       .into_handler(),
     );
@@ -402,8 +445,8 @@ pub fn router(
     stations::restart_playlist::post::Endpoint {
       deployment_id: deployment_id.clone(),
       media_sessions: media_sessions.clone(),
-      shutdown,
-      drop_tracer,
+      shutdown: shutdown.clone(),
+      drop_tracer: drop_tracer.clone(),
     }
     .into_handler(),
   );
@@ -426,6 +469,14 @@ pub fn router(
     .at("/stations/:station/files/unshuffle")
     .post(stations::files::unshuffle::post::Endpoint {}.into_handler());
 
+  app
+    .at("/stations/:station/files/reprocess")
+    .post(stations::files::reprocess::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/files/duplicates")
+    .get(stations::files::duplicates::get::Endpoint {}.into_handler());
+
   app
     .at("/stations/:station/files/:file")
     .get(stations::files::id::get::Endpoint {}.into_handler())
@@ -459,10 +510,167 @@ pub fn router(
     .at("/stations/:station/files/:file/order/move-to-last")
     .post(stations::files::order::move_to_last::post::Endpoint {}.into_handler());
 
+  app
+    .at("/stations/:station/recordings")
+    .get(stations::recordings::get::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/recordings/:recording")
+    .get(stations::recordings::id::get::Endpoint {}.into_handler())
+    .delete(stations::recordings::id::delete::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/recordings/:recording/stream")
+    .get(stations::recordings::id::stream::Handler {});
+
+  app
+    .at("/stations/:station/recordings/:recording/publish")
+    .post(stations::recordings::publish::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/uploads")
+    .post(stations::uploads::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/uploads/:upload")
+    .get(stations::uploads::id::get::Endpoint {}.into_handler())
+    .delete(stations::uploads::id::delete::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/uploads/:upload/append")
+    .post(stations::uploads::append::post::Endpoint {}.into_handler());
+
+  app.at("/stations/:station/uploads/:upload/commit").post(
+    stations::uploads::commit::post::Endpoint {
+      deployment_id: deployment_id.clone(),
+    }
+    .into_handler(),
+  );
+
+  app
+    .at("/stations/:station/imports")
+    .get(stations::imports::get::Endpoint {}.into_handler())
+    .post(
+      stations::imports::post::Endpoint {
+        deployment_id: deployment_id.clone(),
+      }
+      .into_handler(),
+    );
+
+  app
+    .at("/stations/:station/imports/:import")
+    .get(stations::imports::id::get::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/imports/:import/cancel")
+    .post(stations::imports::cancel::post::Endpoint {}.into_handler());
+
   app
     .at("/stations/:station/now-playing")
     .get(stations::now_playing::get::Endpoint {}.into_handler());
 
+  app
+    .at("/stations/:station/play-history")
+    .get(stations::play_history::get::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/play-history/export")
+    .get(stations::play_history::export::Handler {});
+
+  app
+    .at("/stations/:station/royalty-report")
+    .get(stations::royalty_report::get::Handler {});
+
+  app
+    .at("/stations/:station/schedule")
+    .get(stations::schedule::get::Endpoint {}.into_handler())
+    .post(stations::schedule::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/schedule/:slot")
+    .get(stations::schedule::id::get::Endpoint {}.into_handler())
+    .patch(stations::schedule::id::patch::Endpoint {}.into_handler())
+    .delete(stations::schedule::id::delete::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/djs")
+    .get(stations::djs::get::Endpoint {}.into_handler())
+    .post(stations::djs::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/djs/:dj")
+    .get(stations::djs::id::get::Endpoint {}.into_handler())
+    .patch(
+      stations::djs::id::patch::Endpoint {
+        deployment_id: deployment_id.clone(),
+        media_sessions: media_sessions.clone(),
+      }
+      .into_handler(),
+    )
+    .delete(
+      stations::djs::id::delete::Endpoint {
+        deployment_id: deployment_id.clone(),
+        media_sessions: media_sessions.clone(),
+      }
+      .into_handler(),
+    );
+
+  app.at("/stations/:station/kick-live-source").post(
+    stations::kick_live_source::post::Endpoint {
+      deployment_id: deployment_id.clone(),
+      media_sessions: media_sessions.clone(),
+    }
+    .into_handler(),
+  );
+
+  app
+    .at("/stations/:station/playlists")
+    .get(stations::playlists::get::Endpoint {}.into_handler())
+    .post(stations::playlists::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist")
+    .get(stations::playlists::id::get::Endpoint {}.into_handler())
+    .patch(stations::playlists::id::patch::Endpoint {}.into_handler())
+    .delete(stations::playlists::id::delete::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist/shuffle")
+    .post(stations::playlists::shuffle::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist/unshuffle")
+    .post(stations::playlists::unshuffle::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist/files")
+    .get(stations::playlists::files::get::Endpoint {}.into_handler())
+    .post(stations::playlists::files::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist/files/:file")
+    .delete(stations::playlists::files::id::delete::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist/files/:file/order/swap")
+    .post(stations::playlists::files::order::swap::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist/files/:file/order/move-before")
+    .post(stations::playlists::files::order::move_before::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist/files/:file/order/move-after")
+    .post(stations::playlists::files::order::move_after::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist/files/:file/order/move-to-first")
+    .post(stations::playlists::files::order::move_to_first::post::Endpoint {}.into_handler());
+
+  app
+    .at("/stations/:station/playlists/:playlist/files/:file/order/move-to-last")
+    .post(stations::playlists::files::order::move_to_last::post::Endpoint {}.into_handler());
+
   // app
   //   .at("/stations/:station/dashboard-stats")
   //   .get(stations::dashboard_stats::get::Endpoint {}.into_handler());
@@ -510,6 +718,10 @@ pub fn router(
     .at("/admins/:admin/change-password")
     .post(admins::change_password::post::Endpoint {}.into_handler());
 
+  app
+    .at("/audit-log")
+    .get(audit_log::get::Endpoint {}.into_handler());
+
   app
     .at("/invitations")
     .get(invitations::get::Endpoint {}.into_handler())
@@ -553,6 +765,18 @@ pub fn router(
     .at("/embed/station/:station")
     .get(embed::station::id::get::Endpoint {}.into_handler());
 
+  app
+    .at("/embed/station/:station/play-history")
+    .get(embed::station::play_history::get::Endpoint {}.into_handler());
+
+  app.at("/embed/station/:station/now-playing").get(
+    embed::station::now_playing::NowPlayingWsHandler {
+      hub: NowPlayingHub::new(stream_connections_index),
+      drop_tracer,
+      shutdown,
+    },
+  );
+
   // 404 catch all
   app.with(ResourceNotFound.into_handler());
 
@@ -177,7 +177,7 @@ pub mod patch {
   use constants::validate::*;
   use db::{
     account::{Account, Limit, Limits},
-    plan::Plan,
+    plan::{ListenersLimitPolicy, Plan, TransferOveragePolicy},
     run_transaction, Model,
   };
   use modify::Modify;
@@ -252,6 +252,12 @@ pub mod patch {
 
     #[ts(optional)]
     is_user_selectable: Option<bool>,
+
+    #[ts(optional)]
+    listeners_limit_policy: Option<ListenersLimitPolicy>,
+
+    #[ts(optional)]
+    transfer_overage_policy: Option<TransferOveragePolicy>,
   }
 
   #[derive(Debug, Clone)]
@@ -337,6 +343,8 @@ pub mod patch {
         transfer,
         storage,
         is_user_selectable,
+        listeners_limit_policy,
+        transfer_overage_policy,
       } = payload;
 
       let slug = slug.map(|s| s.trim().to_lowercase());
@@ -395,6 +403,14 @@ pub mod patch {
           plan.is_user_selectable = is_user_selectable;
         }
 
+        if let Some(listeners_limit_policy) = listeners_limit_policy {
+          plan.listeners_limit_policy = listeners_limit_policy;
+        }
+
+        if let Some(transfer_overage_policy) = transfer_overage_policy {
+          plan.transfer_overage_policy = transfer_overage_policy;
+        }
+
         plan.updated_at = DateTime::now();
 
         tx_try!(Plan::replace_with_session(&plan.id, &plan, &mut session).await);
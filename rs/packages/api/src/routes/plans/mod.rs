@@ -118,7 +118,7 @@ pub mod post {
   use db::Model;
   use db::{
     current_filter_doc,
-    plan::{Plan, PlanLimits},
+    plan::{ListenersLimitPolicy, Plan, PlanLimits, TransferOveragePolicy},
     run_transaction,
   };
   use modify::Modify;
@@ -188,6 +188,14 @@ pub mod post {
     pub listeners: u64,
     pub transfer: u64,
     pub storage: u64,
+
+    #[serde(default)]
+    #[ts(optional)]
+    pub listeners_limit_policy: ListenersLimitPolicy,
+
+    #[serde(default)]
+    #[ts(optional)]
+    pub transfer_overage_policy: TransferOveragePolicy,
   }
 
   #[derive(Debug, Clone)]
@@ -269,6 +277,8 @@ pub mod post {
         listeners,
         transfer,
         storage,
+        listeners_limit_policy,
+        transfer_overage_policy,
       } = payload;
 
       let now = DateTime::now();
@@ -302,6 +312,8 @@ pub mod post {
             transfer,
             storage,
           },
+          listeners_limit_policy,
+          transfer_overage_policy,
           color: color.clone(),
           order,
           is_user_selectable,
@@ -0,0 +1,204 @@
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError, X_ACCESS_TOKEN};
+
+use async_trait::async_trait;
+use db::deployment::Deployment;
+use db::station::Station;
+use db::station_dj::StationDj;
+use db::Model;
+use hyper::http::HeaderValue;
+use media::{Kind, MediaSessionMap};
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+pub mod post {
+
+  use crate::error::ApiError;
+  use schemars::JsonSchema;
+  use serde_util::empty_struct::EmptyStruct;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {
+    pub media_sessions: MediaSessionMap,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/runtime/dj-updated/[station]/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    /// the dj that was updated
+    pub dj: String,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station_id: String,
+    query: Query,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/runtime/dj-updated/[station]/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(EmptyStruct);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("access: {0}")]
+    Access(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    QueryString(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> ApiError {
+      match e {
+        ParseError::Access(e) => e.into(),
+        ParseError::QueryString(e) => e.into(),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let _ = access_token_scope.grant_station_scope(station_id).await?;
+      let query: Query = req.qs()?;
+      Ok(Self::Input {
+        station_id: station_id.to_string(),
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input { station_id, query } = input;
+      perform(&self.media_sessions, &station_id, &query.dj).await?;
+      Ok(Output(EmptyStruct(())))
+    }
+  }
+}
+
+/// re-evaluates the allowed time of the dj if it is connected to the live source of the station in this deployment
+///
+/// the dj is disconnected if it is no longer allowed to broadcast,
+/// otherwise its disconnection is moved to the end of its current allowed time (or cancelled if it has none)
+pub async fn perform(
+  media_sessions: &MediaSessionMap,
+  station_id: &str,
+  dj_id: &str,
+) -> Result<(), mongodb::error::Error> {
+  let station = match Station::get_by_id(station_id).await? {
+    None => return Ok(()),
+    Some(station) => station,
+  };
+
+  let filter = doc! { StationDj::KEY_ID: dj_id, StationDj::KEY_STATION_ID: station_id };
+  let dj = StationDj::get(filter).await?;
+
+  let mut lock = media_sessions.lock(station_id).await;
+  let handle = match &*lock {
+    None => return Ok(()),
+    Some(handle) => handle,
+  };
+
+  if handle.info().kind != Kind::Live || handle.dj_id() != Some(dj_id) {
+    return Ok(());
+  }
+
+  let now = *DateTime::now();
+  match dj {
+    Some(dj) if dj.enabled && dj.is_allowed_at(now, station.tz()) => {
+      handle.set_dj_allowed_until(dj.allowed_until(now, station.tz()));
+    }
+    _ => {
+      *lock = None;
+    }
+  }
+
+  Ok(())
+}
+
+/// re-evaluates the allowed time of the dj in the deployment that owns the station,
+/// in this deployment or with a runtime request to the owner deployment
+pub async fn dj_updated(
+  deployment_id: &str,
+  media_sessions: &MediaSessionMap,
+  station: &Station,
+  dj_id: &str,
+  access_token_header: Option<HeaderValue>,
+) -> Result<(), mongodb::error::Error> {
+  let info = match &station.owner_deployment_info {
+    None => return Ok(()),
+    Some(info) => info,
+  };
+
+  if info.deployment_id == deployment_id {
+    return perform(media_sessions, &station.id, dj_id).await;
+  }
+
+  let deployment = match Deployment::get_by_id(&info.deployment_id).await? {
+    None => return Ok(()),
+    Some(deployment) => deployment,
+  };
+
+  use rand::seq::SliceRandom;
+  let port = match deployment.api_ports.choose(&mut rand::thread_rng()) {
+    None => return Ok(()),
+    Some(port) => *port,
+  };
+
+  let uri = format!(
+    "http://{}:{}/runtime/dj-updated/{}?dj={}",
+    deployment.local_ip, port, station.id, dj_id
+  );
+
+  let mut req = hyper::Request::builder()
+    .method(hyper::http::Method::POST)
+    .uri(uri);
+
+  if let Some(v) = access_token_header {
+    if let Ok(v) = v.to_str() {
+      req = req.header(X_ACCESS_TOKEN, v);
+    }
+  }
+
+  if let Ok(req) = req.body(hyper::Body::empty()) {
+    let client = hyper::Client::default();
+    tokio::spawn(async move {
+      let _ = client.request(req).await;
+    });
+  }
+
+  Ok(())
+}
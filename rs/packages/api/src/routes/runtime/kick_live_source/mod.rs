@@ -0,0 +1 @@
+pub mod station_id;
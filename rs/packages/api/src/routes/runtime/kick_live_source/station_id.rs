@@ -0,0 +1,184 @@
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError, X_ACCESS_TOKEN};
+
+use async_trait::async_trait;
+use db::deployment::Deployment;
+use db::station::Station;
+use db::Model;
+use hyper::http::HeaderValue;
+use media::{Kind, MediaSessionMap};
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+pub mod post {
+
+  use crate::error::ApiError;
+  use schemars::JsonSchema;
+  use serde_util::empty_struct::EmptyStruct;
+  use std::convert::Infallible;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {
+    pub media_sessions: MediaSessionMap,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/runtime/kick-live-source/[station]/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    /// only kick the live source if it is connected with the credentials of this dj
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dj: Option<String>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station_id: String,
+    query: Query,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/runtime/kick-live-source/[station]/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(EmptyStruct);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("access: {0}")]
+    Access(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    QueryString(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> ApiError {
+      match e {
+        ParseError::Access(e) => e.into(),
+        ParseError::QueryString(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = Infallible;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let _ = access_token_scope.grant_station_scope(station_id).await?;
+      let query: Query = req.qs()?;
+      Ok(Self::Input {
+        station_id: station_id.to_string(),
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input { station_id, query } = input;
+      perform(&self.media_sessions, &station_id, query.dj.as_deref()).await;
+      Ok(Output(EmptyStruct(())))
+    }
+  }
+}
+
+/// disconnects the live source of the station if it is running in this deployment
+/// if dj_id is set, only if the source is connected with the credentials of that dj
+///
+/// returns true if a live source was disconnected
+pub async fn perform(
+  media_sessions: &MediaSessionMap,
+  station_id: &str,
+  dj_id: Option<&str>,
+) -> bool {
+  let mut lock = media_sessions.lock(station_id).await;
+  match &*lock {
+    None => false,
+
+    Some(handle) => match handle.info().kind {
+      Kind::ExternalRelay => false,
+      Kind::InternalRelay => false,
+      Kind::Playlist => false,
+      Kind::Live => {
+        if dj_id.is_some() && handle.dj_id() != dj_id {
+          return false;
+        }
+
+        *lock = None;
+        true
+      }
+    },
+  }
+}
+
+/// disconnects the live source of the station in the deployment that owns it,
+/// in this deployment or with a runtime request to the owner deployment
+pub async fn kick_live_source(
+  deployment_id: &str,
+  media_sessions: &MediaSessionMap,
+  station: &Station,
+  dj_id: Option<&str>,
+  access_token_header: Option<HeaderValue>,
+) -> Result<(), mongodb::error::Error> {
+  let info = match &station.owner_deployment_info {
+    None => return Ok(()),
+    Some(info) => info,
+  };
+
+  if info.deployment_id == deployment_id {
+    perform(media_sessions, &station.id, dj_id).await;
+    return Ok(());
+  }
+
+  let deployment = match Deployment::get_by_id(&info.deployment_id).await? {
+    None => return Ok(()),
+    Some(deployment) => deployment,
+  };
+
+  use rand::seq::SliceRandom;
+  let port = match deployment.api_ports.choose(&mut rand::thread_rng()) {
+    None => return Ok(()),
+    Some(port) => *port,
+  };
+
+  let mut uri = format!(
+    "http://{}:{}/runtime/kick-live-source/{}",
+    deployment.local_ip, port, station.id
+  );
+
+  if let Some(dj_id) = dj_id {
+    uri.push_str(&format!("?dj={dj_id}"));
+  }
+
+  let mut req = hyper::Request::builder()
+    .method(hyper::http::Method::POST)
+    .uri(uri);
+
+  if let Some(v) = access_token_header {
+    if let Ok(v) = v.to_str() {
+      req = req.header(X_ACCESS_TOKEN, v);
+    }
+  }
+
+  if let Ok(req) = req.body(hyper::Body::empty()) {
+    let client = hyper::Client::default();
+    tokio::spawn(async move {
+      let _ = client.request(req).await;
+    });
+  }
+
+  Ok(())
+}
@@ -1,4 +1,6 @@
+pub mod dj_updated;
 pub mod external_relay_updated;
+pub mod kick_live_source;
 pub mod restart_playlist;
 pub mod source_password_updated;
 pub mod station_deleted;
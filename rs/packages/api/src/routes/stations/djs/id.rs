@@ -0,0 +1,446 @@
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError, X_ACCESS_TOKEN};
+use crate::routes::runtime::dj_updated::station_id::dj_updated;
+use crate::routes::runtime::kick_live_source::station_id::kick_live_source;
+
+use async_trait::async_trait;
+use db::audit_log::{AuditLog, AuditLogAction, AuditLogChange, AuditLogOrigin, AuditLogTarget};
+use db::station::Station;
+use db::station_dj::StationDj;
+use db::station_schedule::StationScheduleTime;
+use db::Model;
+use hyper::http::HeaderValue;
+use media::MediaSessionMap;
+use mongodb::bson::doc;
+use prex::Request;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::dj_validation_error;
+
+async fn get_dj(
+  station: &Station,
+  dj_id: &str,
+) -> Result<Option<StationDj>, mongodb::error::Error> {
+  let filter = doc! {
+    StationDj::KEY_ID: dj_id,
+    StationDj::KEY_STATION_ID: &station.id,
+  };
+
+  StationDj::get(filter).await
+}
+
+pub mod get {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    dj_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/djs/[dj]/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(StationDj);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("dj not found: {0}")]
+    DjNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::DjNotFound(id) => Self::StationDjNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let dj_id = req.param("dj").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        station,
+        dj_id: dj_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input { station, dj_id } = input;
+
+      match get_dj(&station, &dj_id).await? {
+        None => Err(HandleError::DjNotFound(dj_id)),
+        Some(dj) => Ok(Output(dj)),
+      }
+    }
+  }
+}
+
+pub mod patch {
+
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use serde_util::{map_some, DateTime};
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {
+    pub deployment_id: String,
+    pub media_sessions: MediaSessionMap,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/djs/[dj]/PATCH/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[modify(trim)]
+    pub name: Option<String>,
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[modify(trim)]
+    pub username: Option<String>,
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// null removes the allowed time of the dj
+    #[ts(optional)]
+    #[serde(
+      default,
+      deserialize_with = "map_some",
+      skip_serializing_if = "Option::is_none"
+    )]
+    pub allowed_time: Option<Option<StationScheduleTime>>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    access_token_header: Option<HeaderValue>,
+    audit_log_origin: AuditLogOrigin,
+    station: Station,
+    dj_id: String,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/djs/[dj]/PATCH/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(StationDj);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => Self::from(e),
+        ParseError::Payload(e) => Self::from(e),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("dj not found: {0}")]
+    DjNotFound(String),
+    #[error("invalid dj: {0}")]
+    Invalid(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => Self::from(e),
+        HandleError::DjNotFound(id) => Self::StationDjNotFound(id),
+        HandleError::Invalid(message) => Self::PayloadInvalid(message),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let dj_id = req.param("dj").unwrap().to_string();
+      let access_token_header = req.headers().get(X_ACCESS_TOKEN).cloned();
+
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let payload: Payload = req.read_body_json(100_000).await?;
+
+      Ok(Self::Input {
+        access_token_header,
+        audit_log_origin,
+        station,
+        dj_id,
+        payload,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        access_token_header,
+        audit_log_origin,
+        station,
+        dj_id,
+        payload,
+      } = input;
+
+      let mut dj = match get_dj(&station, &dj_id).await? {
+        None => return Err(HandleError::DjNotFound(dj_id)),
+        Some(dj) => dj,
+      };
+
+      let before = dj.clone();
+
+      let Payload {
+        name,
+        username,
+        password,
+        enabled,
+        allowed_time,
+      } = payload.clone();
+
+      if let Some(name) = name {
+        dj.name = name;
+      }
+
+      if let Some(username) = username {
+        dj.username = username;
+      }
+
+      if let Some(password) = password {
+        dj.password = password;
+      }
+
+      if let Some(enabled) = enabled {
+        dj.enabled = enabled;
+      }
+
+      if let Some(allowed_time) = allowed_time {
+        dj.allowed_time = allowed_time;
+      }
+
+      dj.updated_at = DateTime::now();
+
+      if let Some(message) = dj_validation_error(&dj).await? {
+        return Err(HandleError::Invalid(message));
+      }
+
+      StationDj::replace(&dj.id, &dj).await?;
+
+      // the password of the dj is not written to the audit log
+      let audit_patch = Payload {
+        password: None,
+        ..payload
+      };
+
+      let audit_log = AuditLog::new(
+        audit_log_origin,
+        vec![station.account_id.clone()],
+        AuditLogAction::StationDjPatch,
+        AuditLogTarget::StationDj {
+          station_id: station.id.clone(),
+          dj_id: dj.id.clone(),
+        },
+        AuditLogChange::from_patch(&audit_patch, &before, &dj),
+      );
+
+      AuditLog::insert(&audit_log).await?;
+
+      // revoke the current session of the dj if it can no longer connect with its previous credentials
+      let revoked = dj.username != before.username
+        || dj.password != before.password
+        || !dj.is_allowed_at(*DateTime::now(), station.tz());
+
+      if revoked {
+        kick_live_source(
+          &self.deployment_id,
+          &self.media_sessions,
+          &station,
+          Some(&dj.id),
+          access_token_header,
+        )
+        .await?;
+      } else if dj.allowed_time != before.allowed_time {
+        // a connected dj is disconnected at the end of its new allowed time
+        dj_updated(
+          &self.deployment_id,
+          &self.media_sessions,
+          &station,
+          &dj.id,
+          access_token_header,
+        )
+        .await?;
+      }
+
+      Ok(Output(dj))
+    }
+  }
+}
+
+pub mod delete {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {
+    pub deployment_id: String,
+    pub media_sessions: MediaSessionMap,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    access_token_header: Option<HeaderValue>,
+    audit_log_origin: AuditLogOrigin,
+    station: Station,
+    dj_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/djs/[dj]/DELETE/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(StationDj);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("dj not found: {0}")]
+    DjNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::DjNotFound(id) => Self::StationDjNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let dj_id = req.param("dj").unwrap();
+      let access_token_header = req.headers().get(X_ACCESS_TOKEN).cloned();
+
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        access_token_header,
+        audit_log_origin,
+        station,
+        dj_id: dj_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        access_token_header,
+        audit_log_origin,
+        station,
+        dj_id,
+      } = input;
+
+      let filter = doc! {
+        StationDj::KEY_ID: &dj_id,
+        StationDj::KEY_STATION_ID: &station.id,
+      };
+
+      let dj = match StationDj::cl().find_one_and_delete(filter, None).await? {
+        None => return Err(HandleError::DjNotFound(dj_id)),
+        Some(dj) => dj,
+      };
+
+      let audit_log = AuditLog::new(
+        audit_log_origin,
+        vec![station.account_id.clone()],
+        AuditLogAction::StationDjDelete,
+        AuditLogTarget::StationDj {
+          station_id: station.id.clone(),
+          dj_id: dj.id.clone(),
+        },
+        vec![],
+      );
+
+      AuditLog::insert(&audit_log).await?;
+
+      kick_live_source(
+        &self.deployment_id,
+        &self.media_sessions,
+        &station,
+        Some(&dj.id),
+        access_token_header,
+      )
+      .await?;
+
+      Ok(Output(dj))
+    }
+  }
+}
@@ -0,0 +1,294 @@
+pub mod id;
+
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, AccessTokenScope, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::audit_log::{AuditLog, AuditLogAction, AuditLogOrigin, AuditLogTarget};
+use db::station::Station;
+use db::station_dj::StationDj;
+use db::station_schedule::StationScheduleTime;
+use db::Model;
+use db::Paged;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// validates `dj` and checks that its username is not used by other dj of the station
+/// returns the message for ApiError::PayloadInvalid if the dj is invalid
+pub async fn dj_validation_error(dj: &StationDj) -> Result<Option<String>, mongodb::error::Error> {
+  if let Some(message) = dj.validation_error() {
+    return Ok(Some(message));
+  }
+
+  if let Some(other) = StationDj::get_by_username(&dj.station_id, &dj.username).await? {
+    if other.id != dj.id {
+      return Ok(Some(format!(
+        "DJ username {} is already in use in the station",
+        dj.username
+      )));
+    }
+  }
+
+  Ok(None)
+}
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use crate::qs::PaginationQs;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Serialize, Deserialize, Clone, TS, JsonSchema)]
+  #[ts(export, export_to = "../../../defs/api/stations/[station]/djs/GET/")]
+  #[macros::schema_ts_export]
+  pub struct Output(Paged<StationDj>);
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(export, export_to = "../../../defs/api/stations/[station]/djs/GET/")]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    #[allow(unused)]
+    access_token_scope: AccessTokenScope,
+    query: Query,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("access: {0}")]
+    Access(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    QueryString(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> ApiError {
+      match e {
+        ParseError::Access(e) => e.into(),
+        ParseError::QueryString(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = mongodb::error::Error;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input {
+        access_token_scope,
+        station,
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        access_token_scope: _,
+        station,
+        query: Query {
+          page: PaginationQs { skip, limit },
+        },
+      } = input;
+
+      let filter = doc! { StationDj::KEY_STATION_ID: &station.id };
+      let sort = doc! { StationDj::KEY_CREATED_AT: 1 };
+      let page = StationDj::paged(filter, sort, skip, limit).await?;
+
+      Ok(Output(page))
+    }
+  }
+}
+
+pub mod post {
+
+  use constants::validate::VALIDATE_STATION_DJS_MAX_LEN;
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use schemars::JsonSchema;
+  use serde_util::DateTime;
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(export, export_to = "../../../defs/api/stations/[station]/djs/POST/")]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    #[modify(trim)]
+    pub name: String,
+    #[modify(trim)]
+    pub username: String,
+    /// a random password is generated if not set
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// defaults to true
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_time: Option<StationScheduleTime>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    audit_log_origin: AuditLogOrigin,
+    station: Station,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(export, export_to = "../../../defs/api/stations/[station]/djs/POST/")]
+  #[macros::schema_ts_export]
+  pub struct Output(StationDj);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => Self::from(e),
+        ParseError::Payload(e) => Self::from(e),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("too many djs")]
+    TooManyDjs,
+    #[error("invalid dj: {0}")]
+    Invalid(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => Self::from(e),
+        HandleError::TooManyDjs => Self::PayloadInvalid(format!(
+          "A station cannot have more than {VALIDATE_STATION_DJS_MAX_LEN} DJs"
+        )),
+        HandleError::Invalid(message) => Self::PayloadInvalid(message),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let payload: Payload = req.read_body_json(100_000).await?;
+
+      Ok(Self::Input {
+        audit_log_origin,
+        station,
+        payload,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        audit_log_origin,
+        station,
+        payload:
+          Payload {
+            name,
+            username,
+            password,
+            enabled,
+            allowed_time,
+          },
+      } = input;
+
+      let filter = doc! { StationDj::KEY_STATION_ID: &station.id };
+      let count = StationDj::cl().count_documents(filter, None).await?;
+      if count >= VALIDATE_STATION_DJS_MAX_LEN as u64 {
+        return Err(HandleError::TooManyDjs);
+      }
+
+      let now = DateTime::now();
+      let dj = StationDj {
+        id: StationDj::uid(),
+        station_id: station.id.clone(),
+        name,
+        username,
+        password: password.unwrap_or_else(StationDj::random_password),
+        enabled: enabled.unwrap_or(true),
+        allowed_time,
+        created_at: now,
+        updated_at: now,
+      };
+
+      if let Some(message) = dj_validation_error(&dj).await? {
+        return Err(HandleError::Invalid(message));
+      }
+
+      StationDj::insert(&dj).await?;
+
+      // the password of the dj is not written to the audit log
+      let audit_log = AuditLog::new(
+        audit_log_origin,
+        vec![station.account_id],
+        AuditLogAction::StationDjCreate,
+        AuditLogTarget::StationDj {
+          station_id: station.id,
+          dj_id: dj.id.clone(),
+        },
+        vec![],
+      );
+
+      AuditLog::insert(&audit_log).await?;
+
+      Ok(Output(dj))
+    }
+  }
+}
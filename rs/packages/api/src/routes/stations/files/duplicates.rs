@@ -0,0 +1,59 @@
+pub mod get {
+  use db::audio_file::{AudioFile, AudioFileDuplicateSet};
+  use prex::Request;
+  use schemars::JsonSchema;
+  use serde::{Deserialize, Serialize};
+  use ts_rs::TS;
+
+  use crate::{
+    json::JsonHandler,
+    request_ext::{self, GetAccessTokenScopeError},
+  };
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/files/duplicates/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    /// sets of files with the same content, the sets that waste more bytes first
+    pub sets: Vec<AudioFileDuplicateSet>,
+    /// bytes used by all the duplicates of the station
+    #[serde(serialize_with = "serde_util::as_f64::serialize")]
+    #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+    pub wasted_bytes: u64,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[async_trait::async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = mongodb::error::Error;
+
+    async fn parse(&self, request: Request) -> Result<Input, Self::ParseError> {
+      let station_id = request.param("station").unwrap();
+      let access_token_scope = request_ext::get_access_token_scope(&request).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+      Ok(Input {
+        station_id: station.id,
+      })
+    }
+
+    async fn perform(&self, input: Input) -> Result<Output, Self::HandleError> {
+      let Input { station_id } = input;
+      let sets = AudioFile::duplicate_sets(&station_id).await?;
+      let wasted_bytes = sets.iter().map(|set| set.wasted_bytes).sum();
+      Ok(Output { sets, wasted_bytes })
+    }
+  }
+}
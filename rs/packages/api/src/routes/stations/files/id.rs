@@ -2,6 +2,7 @@ use crate::json::JsonHandler;
 use crate::request_ext::{self, AccessTokenScope, GetAccessTokenScopeError};
 
 use async_trait::async_trait;
+use db::account::Account;
 use db::audio_chunk::AudioChunk;
 use db::audio_file::AudioFile;
 use db::station::Station;
@@ -141,11 +142,27 @@ pub mod stream {
         Err(e) => return ApiError::from(e).into_json_response(),
       };
 
-      let _station = match scope.grant_station_scope(station_id).await {
+      let station = match scope.grant_station_scope(station_id).await {
         Ok(station) => station,
         Err(e) => return ApiError::from(e).into_json_response(),
       };
 
+      // admins can always listen to the files, users are cut off
+      // when the account used all the transfer of the billing period
+      if !scope.is_admin_or_global() {
+        let account = match Account::get_by_id(&station.account_id).await {
+          Ok(Some(account)) => account,
+          Ok(None) => return ApiError::AccountNotFound(station.account_id).into_json_response(),
+          Err(e) => return ApiError::from(e).into_json_response(),
+        };
+
+        match account.is_transfer_blocked().await {
+          Ok(false) => {}
+          Ok(true) => return ApiError::TransferQuotaExceeded.into_json_response(),
+          Err(e) => return ApiError::from(e).into_json_response(),
+        }
+      }
+
       let filter = doc! { AudioFile::KEY_ID: file_id, AudioFile::KEY_STATION_ID: station_id };
       let file = match AudioFile::get(filter).await {
         Ok(Some(file)) => file,
@@ -153,133 +170,140 @@ pub mod stream {
         Err(e) => return ApiError::from(e).into_json_response(),
       };
 
-      let req_etag = request
-        .headers()
-        .get(IF_NONE_MATCH)
-        .and_then(|v| v.to_str().ok());
+      file_response(&request, file)
+    }
+  }
 
-      let res_etag = format!("\"{}\"", file.sha256);
+  /// the response with the audio of a stored file, with support for range requests
+  pub fn file_response(request: &Request, file: AudioFile) -> prex::Response {
+    let req_etag = request
+      .headers()
+      .get(IF_NONE_MATCH)
+      .and_then(|v| v.to_str().ok());
 
-      if let Some(req_etag) = req_etag {
-        if req_etag == res_etag {
-          return Response::new(StatusCode::NOT_MODIFIED);
-        }
+    let res_etag = format!("\"{}\"", file.sha256);
+
+    if let Some(req_etag) = req_etag {
+      if req_etag == res_etag {
+        return Response::new(StatusCode::NOT_MODIFIED);
       }
+    }
 
-      let is_range_request = request.headers().contains_key(RANGE);
+    let is_range_request = request.headers().contains_key(RANGE);
 
-      let range: Option<HttpRange> =
-        match request.headers().get(RANGE).and_then(|v| v.to_str().ok()) {
-          None => None,
-          Some(v) => match http_range::HttpRange::parse(v, file.len) {
-            Err(e) => return ApiError::from(e).into_json_response(),
-            Ok(ranges) => ranges.first().copied(),
-          },
-        };
+    let range: Option<HttpRange> = match request.headers().get(RANGE).and_then(|v| v.to_str().ok())
+    {
+      None => None,
+      Some(v) => match http_range::HttpRange::parse(v, file.len) {
+        Err(e) => return ApiError::from(e).into_json_response(),
+        Ok(ranges) => ranges.first().copied(),
+      },
+    };
 
-      let (start, length) = match range {
-        None => (0, file.len),
-        Some(range) => (range.start, range.length),
-      };
+    let (start, length) = match range {
+      None => (0, file.len),
+      Some(range) => (range.start, range.length),
+    };
 
-      let end = start + length - 1;
+    let end = start + length - 1;
 
-      let file_len = file.len;
-      let chunk_len = file.chunk_len;
+    let file_len = file.len;
+    let chunk_len = file.chunk_len;
 
-      let start_i = start as usize / chunk_len;
-      let skip_first_item_bytes = start as usize % chunk_len;
+    let start_i = start as usize / chunk_len;
+    let skip_first_item_bytes = start as usize % chunk_len;
 
-      let end_i = (start as usize + length as usize) / chunk_len;
-      let end_item_len = (start as usize + length as usize) % chunk_len;
+    let end_i = (start as usize + length as usize) / chunk_len;
+    let end_item_len = (start as usize + length as usize) % chunk_len;
 
-      let stream = async_stream::stream! {
+    let stream = async_stream::stream! {
 
-        let mut i = start_i;
+      let mut i = start_i;
 
-        loop {
-          if i > end_i {
-            break;
-          }
+      loop {
+        if i > end_i {
+          break;
+        }
 
-          let filter = doc!{AudioChunk::KEY_AUDIO_FILE_ID: &file.id, AudioChunk::KEY_I: i as f64 };
-          let data = match AudioChunk::get(filter).await {
-            Err(e) => {
-              yield Err(e);
-              break;
-            },
-            Ok(None) => break,
-            Ok(Some(chunk)) => {
-              if i == end_i && i == start_i {
-                chunk.data.slice(skip_first_item_bytes..end_item_len)
-              } else if i == start_i {
-                chunk.data.slice(skip_first_item_bytes..)
-              } else if i == end_i {
-                chunk.data.slice(..end_item_len)
-              } else {
-                chunk.data
-              }
+        let filter = doc!{AudioChunk::KEY_AUDIO_FILE_ID: &file.id, AudioChunk::KEY_I: i as f64 };
+        let data = match AudioChunk::get(filter).await {
+          Err(e) => {
+            yield Err(e);
+            break;
+          },
+          Ok(None) => break,
+          Ok(Some(chunk)) => {
+            if i == end_i && i == start_i {
+              chunk.data.slice(skip_first_item_bytes..end_item_len)
+            } else if i == start_i {
+              chunk.data.slice(skip_first_item_bytes..)
+            } else if i == end_i {
+              chunk.data.slice(..end_item_len)
+            } else {
+              chunk.data
             }
-          };
-
-          i += 1;
+          }
+        };
 
-          yield Ok(data)
-        }
-      };
+        i += 1;
 
-      let mut response = {
-        if is_range_request {
-          let mut response = Response::new(StatusCode::OK);
-
-          response.headers_mut().append(
-            CONTENT_LENGTH,
-            HeaderValue::from_str(&file_len.to_string()).unwrap(),
-          );
-
-          response
-        } else {
-          let mut response = Response::new(StatusCode::PARTIAL_CONTENT);
-          response.headers_mut().append(
-            CONTENT_LENGTH,
-            HeaderValue::from_str(&length.to_string()).unwrap(),
-          );
-
-          response.headers_mut().append(
-            CONTENT_RANGE,
-            HeaderValue::from_str(&format!("bytes {start}-{end}/{file_len}")).unwrap(),
-          );
-
-          response
-        }
-      };
+        yield Ok(data)
+      }
+    };
+
+    let mut response = {
+      if is_range_request {
+        let mut response = Response::new(StatusCode::OK);
+
+        response.headers_mut().append(
+          CONTENT_LENGTH,
+          HeaderValue::from_str(&file_len.to_string()).unwrap(),
+        );
+
+        response
+      } else {
+        let mut response = Response::new(StatusCode::PARTIAL_CONTENT);
+        response.headers_mut().append(
+          CONTENT_LENGTH,
+          HeaderValue::from_str(&length.to_string()).unwrap(),
+        );
+
+        response.headers_mut().append(
+          CONTENT_RANGE,
+          HeaderValue::from_str(&format!("bytes {start}-{end}/{file_len}")).unwrap(),
+        );
+
+        response
+      }
+    };
 
-      response
-        .headers_mut()
-        .append(ETAG, HeaderValue::from_str(res_etag.as_str()).unwrap());
+    response
+      .headers_mut()
+      .append(ETAG, HeaderValue::from_str(res_etag.as_str()).unwrap());
 
-      response
-        .headers_mut()
-        .append(CONTENT_TYPE, CONTENT_TYPE_AUDIO_MPEG);
+    response
+      .headers_mut()
+      .append(CONTENT_TYPE, CONTENT_TYPE_AUDIO_MPEG);
 
-      response
-        .headers_mut()
-        .append(ACCEPT_RANGES, ACCEPT_RANGES_BYTES);
+    response
+      .headers_mut()
+      .append(ACCEPT_RANGES, ACCEPT_RANGES_BYTES);
 
-      response
-        .headers_mut()
-        .append(VARY, VARY_RANGE_X_ACCESS_TOKEN);
+    response
+      .headers_mut()
+      .append(VARY, VARY_RANGE_X_ACCESS_TOKEN);
 
-      let body = Body::wrap_stream(stream);
+    let body = Body::wrap_stream(stream);
 
-      *response.body_mut() = body;
+    *response.body_mut() = body;
 
-      response
-    }
+    response
   }
 }
 
 pub mod delete {
+  use db::audit_log::{AuditLog, AuditLogAction, AuditLogChange, AuditLogOrigin, AuditLogTarget};
+  use db::run_transaction;
   use schemars::JsonSchema;
   use ts_rs::TS;
 
@@ -293,6 +317,7 @@ pub mod delete {
   pub struct Input {
     station: Station,
     file_id: String,
+    audit_log_origin: AuditLogOrigin,
   }
 
   #[derive(Debug, thiserror::Error)]
@@ -330,21 +355,51 @@ pub mod delete {
     async fn parse(&self, request: Request) -> Result<Input, Self::ParseError> {
       let station_id = request.param("station").unwrap();
       let file_id = request.param("file").unwrap();
-      let access_token_scope = request_ext::get_access_token_scope(&request).await?;
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&request).await?;
       let station = access_token_scope.grant_station_scope(station_id).await?;
 
       Ok(Input {
         station,
         file_id: file_id.to_string(),
+        audit_log_origin,
       })
     }
 
     async fn perform(&self, input: Input) -> Result<Output, HandleError> {
-      let Input { station, file_id } = input;
-      match AudioFile::delete_audio_file(&station.id, &file_id).await? {
-        None => Err(HandleError::FileNotFound(file_id)),
-        Some(audio_file) => Ok(Output(audio_file)),
-      }
+      let Input {
+        station,
+        file_id,
+        audit_log_origin,
+      } = input;
+
+      let audio_file = run_transaction!(session => {
+        let audio_file = match tx_try!(AudioFile::delete_audio_file_with_session(&station.id, &file_id, &mut session).await) {
+          None => return Err(HandleError::FileNotFound(file_id)),
+          Some(audio_file) => audio_file,
+        };
+
+        let audit_log = AuditLog::new(
+          audit_log_origin.clone(),
+          vec![station.account_id.clone()],
+          AuditLogAction::FileDelete,
+          AuditLogTarget::AudioFile {
+            station_id: station.id.clone(),
+            file_id: audio_file.id.clone(),
+          },
+          vec![AuditLogChange {
+            field: AudioFile::KEY_FILENAME.to_string(),
+            before: audio_file.filename.clone().into(),
+            after: serde_json::Value::Null,
+          }],
+        );
+
+        tx_try!(AuditLog::insert_with_session(&audit_log, &mut session).await);
+
+        audio_file
+      });
+
+      Ok(Output(audio_file))
     }
   }
 }
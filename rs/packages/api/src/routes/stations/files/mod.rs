@@ -1,6 +1,8 @@
+pub mod duplicates;
 pub mod id;
 pub mod metadata;
 pub mod order;
+pub mod reprocess;
 pub mod shuffle;
 pub mod unshuffle;
 
@@ -10,7 +12,7 @@ use mongodb::bson::doc;
 
 use crate::error::ApiError;
 use async_trait::async_trait;
-use db::audio_file::AudioFile;
+use db::audio_file::{AudioFile, DuplicateMode};
 use db::station::Station;
 use db::Model;
 use db::Paged;
@@ -137,12 +139,17 @@ pub mod post {
   #[macros::schema_ts_export]
   pub struct Query {
     pub filename: String,
+    /// what to do if a file with the same content already exists in the station
+    #[serde(default)]
+    #[ts(optional)]
+    pub on_duplicate: DuplicateMode,
   }
 
   #[derive(Debug, Clone)]
   pub struct Input<S> {
     pub station: Station,
     pub filename: String,
+    pub on_duplicate: DuplicateMode,
     pub stream: S,
     pub size_hint: Option<u64>,
   }
@@ -172,6 +179,7 @@ pub mod post {
         None,
         input.size_hint,
         input.filename,
+        input.on_duplicate,
         input.stream,
       )
       .await?;
@@ -235,6 +243,7 @@ pub mod post {
       let input = Self::Input {
         station,
         filename: filename.to_string(),
+        on_duplicate: query.on_duplicate,
         stream,
         size_hint: Some(content_length),
       };
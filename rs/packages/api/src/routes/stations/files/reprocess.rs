@@ -0,0 +1,73 @@
+pub mod post {
+  use prex::Request;
+  use schemars::JsonSchema;
+  use serde::{Deserialize, Serialize};
+  use ts_rs::TS;
+  use upload::process::{process_station_files, ProcessError};
+
+  use crate::{
+    error::ApiError,
+    json::JsonHandler,
+    request_ext::{self, GetAccessTokenScopeError},
+  };
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station_id: String,
+  }
+
+  #[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/files/reprocess/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    /// number of files that will be processed in the background
+    pub files: u64,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("process: {0}")]
+    Process(#[from] ProcessError),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Process(ProcessError::Mongo(e)) => e.into(),
+        HandleError::Process(ProcessError::StationNotFound(id)) => ApiError::StationNotFound(id),
+        HandleError::Process(e) => {
+          ApiError::Internal(format!("Error processing station files: {e}"))
+        }
+      }
+    }
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[async_trait::async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, request: Request) -> Result<Input, Self::ParseError> {
+      let station_id = request.param("station").unwrap();
+      let access_token_scope = request_ext::get_access_token_scope(&request).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+      Ok(Input {
+        station_id: station.id,
+      })
+    }
+
+    async fn perform(&self, input: Input) -> Result<Output, Self::HandleError> {
+      let Input { station_id } = input;
+      let files = process_station_files(&station_id).await?;
+      Ok(Output { files })
+    }
+  }
+}
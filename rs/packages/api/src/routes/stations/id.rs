@@ -74,7 +74,9 @@ pub mod delete {
   use db::account::{Account, Limit, Limits};
   use db::audio_chunk::AudioChunk;
   use db::audio_file::AudioFile;
+  use db::audit_log::{AuditLog, AuditLogAction, AuditLogOrigin, AuditLogTarget};
   use db::deployment::Deployment;
+  use db::live_recording::LiveRecording;
   use db::{run_transaction, Model};
   use hyper::http::HeaderValue;
   use hyper::Body;
@@ -97,6 +99,7 @@ pub mod delete {
   #[derive(Debug, Clone)]
   pub struct Input {
     access_token_header: Option<HeaderValue>,
+    audit_log_origin: AuditLogOrigin,
     station_id: String,
   }
 
@@ -137,12 +140,14 @@ pub mod delete {
 
     async fn parse(&self, req: Request) -> Result<Input, Self::ParseError> {
       let station_id = req.param("station").unwrap();
-      let access_token = request_ext::get_access_token_scope(&req).await?;
+      let (access_token, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
       let access_token_header = req.headers().get(ACCESS_TOKEN_HEADER).cloned();
       let station = access_token.grant_station_owner_scope(station_id).await?;
 
       Ok(Input {
         access_token_header,
+        audit_log_origin,
         station_id: station.id,
       })
     }
@@ -150,6 +155,7 @@ pub mod delete {
     async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
       let Input {
         access_token_header,
+        audit_log_origin,
         station_id,
       } = input;
 
@@ -179,6 +185,19 @@ pub mod delete {
           }
         };
 
+        let recordings_filter = doc! { LiveRecording::KEY_STATION_ID: &station.id };
+
+        let mut cursor = tx_try!(LiveRecording::cl().find_with_session(recordings_filter, None, &mut session).await);
+
+        while let Some(item) = tx_try!(cursor.next(&mut session).await.transpose()) {
+          storage_used += item.file.len;
+          tx_try!(LiveRecording::delete_by_id_with_session(&item.id, &mut session).await);
+          {
+            let filter = doc! { AudioChunk::KEY_AUDIO_FILE_ID: &item.id };
+            tx_try!(AudioChunk::cl().delete_many_with_session(filter, None, &mut session).await);
+          }
+        };
+
         const KEY_STATIONS_USED: &str = db::key!(Account::KEY_LIMITS, Limits::KEY_STATIONS, Limit::KEY_USED);
         const KEY_STORAGE_USED: &str = db::key!(Account::KEY_LIMITS, Limits::KEY_STORAGE, Limit::KEY_USED);
 
@@ -201,6 +220,16 @@ pub mod delete {
           return Err(HandleError::AccountNotFound(station.account_id));
         }
 
+        let audit_log = AuditLog::new(
+          audit_log_origin.clone(),
+          vec![station.account_id.clone()],
+          AuditLogAction::StationDelete,
+          AuditLogTarget::Station { station_id: station.id.clone() },
+          vec![],
+        );
+
+        tx_try!(AuditLog::insert_with_session(&audit_log, &mut session).await);
+
         station
       });
 
@@ -260,9 +289,11 @@ pub mod patch {
 
   use super::*;
   use constants::ACCESS_TOKEN_HEADER;
+  use db::audit_log::{AuditLog, AuditLogAction, AuditLogChange, AuditLogOrigin, AuditLogTarget};
+  use db::station::StationRenditionCodec;
   use db::{
-    deployment::Deployment, error::ApplyPatchError, fetch_and_patch, run_transaction,
-    station::StationPatch, station_slug::StationSlug, Model,
+    deployment::Deployment, error::ApplyPatchError, fetch_and_patch, playlist::Playlist,
+    run_transaction, station::StationPatch, station_slug::StationSlug, Model,
   };
   use hyper::{http::HeaderValue, Body};
   use media::MediaSessionMap;
@@ -291,6 +322,7 @@ pub mod patch {
   pub struct Input {
     payload: Payload,
     access_token_scope: AccessTokenScope,
+    audit_log_origin: AuditLogOrigin,
     access_token_header: Option<HeaderValue>,
     station: Station,
   }
@@ -327,8 +359,12 @@ pub mod patch {
     StationNotFound(String),
     #[error("picture not found {0}")]
     PictureNotFound(String),
+    #[error("playlist not found {0}")]
+    PlaylistNotFound(String),
     #[error("station slug already taken")]
     SlugTaken,
+    #[error("rendition codec not available: {0:?}")]
+    RenditionCodecNotAvailable(StationRenditionCodec),
   }
 
   impl From<HandleError> for ApiError {
@@ -340,7 +376,14 @@ pub mod patch {
         HandleError::PictureNotFound(id) => {
           Self::PayloadInvalid(format!("Picture with id {id} not found"))
         }
+        HandleError::PlaylistNotFound(id) => {
+          Self::PayloadInvalid(format!("Playlist with id {id} not found"))
+        }
         HandleError::SlugTaken => Self::PayloadInvalid("Station slug is already taken".into()),
+        HandleError::RenditionCodecNotAvailable(codec) => Self::PayloadInvalid(format!(
+          "Rendition codec {} is not available in this server",
+          codec.as_str()
+        )),
       }
     }
   }
@@ -355,7 +398,8 @@ pub mod patch {
     async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
       let station_id = req.param("station").unwrap();
 
-      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
 
       let station = access_token_scope.grant_station_scope(station_id).await?;
 
@@ -366,6 +410,7 @@ pub mod patch {
       Ok(Self::Input {
         payload,
         access_token_scope,
+        audit_log_origin,
         access_token_header,
         station,
       })
@@ -375,17 +420,29 @@ pub mod patch {
       let Self::Input {
         payload: Payload { patch },
         access_token_scope,
+        audit_log_origin,
         access_token_header,
         station,
       } = input;
 
       let id = station.id;
 
+      if let Some(renditions) = &patch.renditions {
+        for rendition in renditions {
+          if !media::rendition::is_codec_available(rendition.codec).await {
+            return Err(HandleError::RenditionCodecNotAvailable(rendition.codec));
+          }
+        }
+      }
+
       let mut prev_external_relay_redirect: bool;
       let mut prev_external_relay_url: Option<String>;
+      let mut prev_external_relay_backup_urls: Vec<String>;
 
       let station = run_transaction!(session => {
         fetch_and_patch!(Station, station, &id, Err(HandleError::StationNotFound(id)), session, {
+          let before = station.clone();
+
           if let Some(picture_id) = &patch.picture_id {
             let filter = doc! { StationPicture::KEY_ACCOUNT_ID: &station.account_id, StationPicture::KEY_ID: picture_id };
             match tx_try!(StationPicture::exists_with_session(filter, &mut session).await) {
@@ -396,6 +453,16 @@ pub mod patch {
             }
           }
 
+          if let Some(Some(playlist_id)) = &patch.default_playlist_id {
+            let filter = doc! { Playlist::KEY_STATION_ID: &station.id, Playlist::KEY_ID: playlist_id };
+            match tx_try!(Playlist::exists_with_session(filter, &mut session).await) {
+              true => {},
+              false => {
+                return Err(HandleError::PlaylistNotFound(playlist_id.to_string()))
+              }
+            }
+          }
+
           if let Some(Some(slug)) = &patch.slug {
             if Some(slug) != station.slug.as_ref() {
               let is_available = tx_try!(StationSlug::is_slug_available_for_station_with_session(
@@ -421,8 +488,19 @@ pub mod patch {
 
           prev_external_relay_url = station.external_relay_url.clone();
           prev_external_relay_redirect = station.external_relay_redirect;
+          prev_external_relay_backup_urls = station.external_relay_backup_urls.clone();
 
           station.apply_patch(patch.clone(), access_token_scope.as_public_scope())?;
+
+          let audit_log = AuditLog::new(
+            audit_log_origin.clone(),
+            vec![station.account_id.clone()],
+            AuditLogAction::StationPatch,
+            AuditLogTarget::Station { station_id: station.id.clone() },
+            AuditLogChange::from_patch(&patch, &before, &station),
+          );
+
+          tx_try!(AuditLog::insert_with_session(&audit_log, &mut session).await);
         })
       });
 
@@ -431,6 +509,12 @@ pub mod patch {
           break 'reset true;
         }
 
+        if station.external_relay_url.is_some()
+          && station.external_relay_backup_urls != prev_external_relay_backup_urls
+        {
+          break 'reset true;
+        }
+
         if station.external_relay_redirect
           && station.external_relay_redirect != prev_external_relay_redirect
         {
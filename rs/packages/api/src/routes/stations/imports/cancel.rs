@@ -0,0 +1,60 @@
+pub mod post {
+  use crate::json::JsonHandler;
+  use crate::request_ext::{self, GetAccessTokenScopeError};
+
+  use async_trait::async_trait;
+  use db::audio_import::AudioImport;
+  use db::station::Station;
+  use prex::Request;
+  use schemars::JsonSchema;
+  use serde::{Deserialize, Serialize};
+  use ts_rs::TS;
+  use upload::import::ImportError;
+
+  use super::super::get_station_import;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    import_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/imports/[import]/cancel/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    import: AudioImport,
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = ImportError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let import_id = req.param("import").unwrap().to_string();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input { station, import_id })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Input { station, import_id } = input;
+      let import = get_station_import(&station.id, &import_id).await?;
+      let import = upload::import::cancel(&import.id).await?;
+      Ok(Output { import })
+    }
+  }
+}
@@ -0,0 +1,249 @@
+pub mod cancel;
+pub mod id;
+
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::audio_file::DuplicateMode;
+use db::audio_import::AudioImport;
+use db::station::Station;
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use upload::import::ImportError;
+
+/// gets an import of the station, the imports of other stations are reported as not found
+pub async fn get_station_import(
+  station_id: &str,
+  import_id: &str,
+) -> Result<AudioImport, ImportError> {
+  let filter = doc! {
+    AudioImport::KEY_ID: import_id,
+    AudioImport::KEY_STATION_ID: station_id,
+  };
+
+  match AudioImport::get(filter).await? {
+    Some(import) => Ok(import),
+    None => Err(ImportError::NotFound(import_id.to_string())),
+  }
+}
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use crate::qs::PaginationQs;
+  use db::Paged;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/imports/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    query: Query,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/imports/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    /// the imports of the station, newest first
+    imports: Paged<AudioImport>,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    Query(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => e.into(),
+        ParseError::Query(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = mongodb::error::Error;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input { station, query })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        query: Query {
+          page: PaginationQs { skip, limit },
+        },
+      } = input;
+
+      let filter = doc! { AudioImport::KEY_STATION_ID: &station.id };
+      let sort = doc! { AudioImport::KEY_CREATED_AT: -1 };
+      let imports = AudioImport::paged(filter, sort, skip, limit).await?;
+
+      Ok(Output { imports })
+    }
+  }
+}
+
+pub mod post {
+
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use schemars::JsonSchema;
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {
+    pub deployment_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/imports/POST/"
+  )]
+  #[serde(rename_all = "snake_case")]
+  #[serde(tag = "kind")]
+  #[macros::schema_ts_export]
+  pub enum PayloadSource {
+    /// a .zip, .tar, .tar.gz or .tgz archive sent as a complete resumable upload
+    #[serde(rename = "archive")]
+    Archive { upload_id: String },
+    /// http(s) urls that the server will fetch
+    #[serde(rename = "urls")]
+    Urls { urls: Vec<String> },
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/imports/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    pub source: PayloadSource,
+    /// what to do with the files that already exist in the station
+    #[serde(default)]
+    #[ts(optional)]
+    pub on_duplicate: DuplicateMode,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/imports/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    import: AudioImport,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => e.into(),
+        ParseError::Payload(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = ImportError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let payload: Payload = req.read_body_json(1_000_000).await?;
+
+      Ok(Self::Input { station, payload })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        payload: Payload {
+          source,
+          on_duplicate,
+        },
+      } = input;
+
+      let deployment_id = self.deployment_id.clone();
+
+      let import = match source {
+        PayloadSource::Archive { upload_id } => {
+          upload::import::create_from_archive(deployment_id, &station.id, &upload_id, on_duplicate)
+            .await?
+        }
+        PayloadSource::Urls { urls } => {
+          upload::import::create_from_urls(deployment_id, &station.id, urls, on_duplicate).await?
+        }
+      };
+
+      Ok(Output { import })
+    }
+  }
+}
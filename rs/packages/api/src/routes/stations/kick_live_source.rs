@@ -0,0 +1,111 @@
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use crate::error::ApiError;
+use async_trait::async_trait;
+use db::station::Station;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+pub mod post {
+
+  use db::audit_log::{AuditLog, AuditLogAction, AuditLogOrigin, AuditLogTarget};
+  use hyper::http::HeaderValue;
+  use media::MediaSessionMap;
+  use schemars::JsonSchema;
+  use serde_util::empty_struct::EmptyStruct;
+
+  use crate::request_ext::X_ACCESS_TOKEN;
+  use crate::routes::runtime::kick_live_source::station_id::kick_live_source;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {
+    pub deployment_id: String,
+    pub media_sessions: MediaSessionMap,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    access_token_header: Option<HeaderValue>,
+    audit_log_origin: AuditLogOrigin,
+    station: Station,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/kick-live-source/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(EmptyStruct);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongo: {0}")]
+    Db(#[from] mongodb::error::Error),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let access_token_header = req.headers().get(X_ACCESS_TOKEN).cloned();
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+      Ok(Input {
+        station,
+        access_token_header,
+        audit_log_origin,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Input {
+        station,
+        access_token_header,
+        audit_log_origin,
+      } = input;
+
+      // disconnects the current live source, whether it is the station source password or a dj
+      kick_live_source(
+        &self.deployment_id,
+        &self.media_sessions,
+        &station,
+        None,
+        access_token_header,
+      )
+      .await?;
+
+      let audit_log = AuditLog::new(
+        audit_log_origin,
+        vec![station.account_id.clone()],
+        AuditLogAction::StationLiveSourceKick,
+        AuditLogTarget::Station {
+          station_id: station.id.clone(),
+        },
+        vec![],
+      );
+
+      AuditLog::insert(&audit_log).await?;
+
+      Ok(Output(EmptyStruct(())))
+    }
+  }
+}
@@ -1,12 +1,21 @@
 // pub mod dashboard_stats;
+pub mod djs;
 pub mod files;
 pub mod id;
+pub mod imports;
 pub mod is_slug_available;
+pub mod kick_live_source;
 pub mod now_playing;
+pub mod play_history;
+pub mod playlists;
+pub mod recordings;
 pub mod reset_source_password;
 pub mod restart_playlist;
+pub mod royalty_report;
+pub mod schedule;
 pub mod stream_stats;
 pub mod transfer;
+pub mod uploads;
 
 use crate::json::JsonHandler;
 use crate::request_ext::{self, AccessTokenScope, GetAccessTokenScopeError};
@@ -14,10 +23,14 @@ use crate::request_ext::{self, AccessTokenScope, GetAccessTokenScopeError};
 use crate::error::ApiError;
 use async_trait::async_trait;
 use constants::validate::*;
+use db::audio_file::AudioProcessing;
 use db::metadata::Metadata;
 use db::models::user_account_relation::UserAccountRelation;
+use db::station::listener_access::ListenerAccessSettings;
 use db::station::PublicStation;
-use db::station::Station;
+use db::station::{
+  DeadAirSettings, LiveInputSettings, LiveRecordingSettings, PlaylistTransition, Station,
+};
 use db::{Model, Paged, PublicScope};
 use mongodb::bson::doc;
 use prex::request::ReadBodyJsonError;
@@ -636,6 +649,21 @@ pub mod post {
 
         external_relay_url,
         external_relay_redirect: false,
+        external_relay_backup_urls: vec![],
+
+        renditions: vec![],
+
+        default_playlist_id: None,
+        playlist_transition: PlaylistTransition::default(),
+
+        audio_processing: AudioProcessing::default(),
+
+        live_recording: LiveRecordingSettings::default(),
+        live_input: LiveInputSettings::default(),
+        dead_air: DeadAirSettings::default(),
+        listener_access: ListenerAccessSettings::default(),
+
+        timezone: db::station::default_timezone(),
 
         owner_deployment_info: None,
 
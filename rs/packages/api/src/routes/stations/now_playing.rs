@@ -12,7 +12,10 @@ pub mod get {
 
   use db::{
     audio_file::AudioFile,
-    media_session::{MediaSession, MediaSessionKind, MediaSessionNowPlaying},
+    media_session::{
+      MediaSession, MediaSessionDeadAir, MediaSessionKind, MediaSessionNowPlaying,
+      MediaSessionRelaySource,
+    },
     probe::Probe,
     Model,
   };
@@ -47,15 +50,24 @@ pub mod get {
     Live {
       title: Option<String>,
       artist: Option<String>,
+      /// set while the live source is in dead air
+      dead_air: Option<MediaSessionDeadAir>,
     },
     #[serde(rename = "external-relay")]
-    ExternalRelay { url: String },
+    ExternalRelay {
+      url: String,
+      on_air: Option<MediaSessionRelaySource>,
+      /// set while the relay is in dead air
+      dead_air: Option<MediaSessionDeadAir>,
+    },
     #[serde(rename = "playlist")]
     Playilist {
       file_id: String,
       filename: String,
       title: Option<String>,
       artist: Option<String>,
+      /// set while the playlist is on air as the dead air failover of a live source or relay
+      dead_air: Option<MediaSessionDeadAir>,
     },
   }
 
@@ -111,15 +123,22 @@ pub mod get {
             None => Output::Live {
               title: None,
               artist: None,
+              dead_air: media_session.dead_air,
             },
-            Some(MediaSessionNowPlaying { title, artist }) => Output::Live {
+            Some(MediaSessionNowPlaying { title, artist, .. }) => Output::Live {
               title: Some(title),
               artist,
+              dead_air: media_session.dead_air,
             },
           },
 
-          MediaSessionKind::ExternalRelay { url } => Output::ExternalRelay { url },
+          MediaSessionKind::ExternalRelay { url, on_air, .. } => Output::ExternalRelay {
+            url,
+            on_air,
+            dead_air: media_session.dead_air,
+          },
 
+          // the session in dead air is the one of the source the playlist replaces
           MediaSessionKind::Playlist {
             last_audio_file_id, ..
           } => match AudioFile::get_by_id(&last_audio_file_id).await? {
@@ -138,6 +157,7 @@ pub mod get {
               filename: file.filename,
               title: file.metadata.title,
               artist: file.metadata.artist,
+              dead_air: MediaSession::get_dead_air_for_station(&station.id).await?,
             },
           },
         },
@@ -0,0 +1,344 @@
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use constants::validate::VALIDATE_PLAY_HISTORY_SEARCH_MAX_LEN;
+use db::play_history_item::PlayHistoryItem;
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+fn validate_search(search: Option<&str>) -> Result<(), ApiError> {
+  match search {
+    Some(search) if search.chars().count() > VALIDATE_PLAY_HISTORY_SEARCH_MAX_LEN => {
+      Err(ApiError::QueryStringCustom(format!(
+        "q must be at most {VALIDATE_PLAY_HISTORY_SEARCH_MAX_LEN} characters long"
+      )))
+    }
+    _ => Ok(()),
+  }
+}
+
+pub mod get {
+
+  use crate::qs::PaginationQs;
+  use db::Paged;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/play-history/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+    /// only the items played at or after this date
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime>,
+    /// only the items played before this date
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime>,
+    /// search in the title and the artist of the items
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station_id: String,
+    query: Query,
+  }
+
+  /// the play history of the station, newest first
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/play-history/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Paged<PlayHistoryItem>);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("access: {0}")]
+    Access(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    QueryString(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Access(e) => e.into(),
+        ParseError::QueryString(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = ApiError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input {
+        station_id: station.id,
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station_id,
+        query:
+          Query {
+            page: PaginationQs { skip, limit },
+            since,
+            until,
+            q,
+          },
+      } = input;
+
+      validate_search(q.as_deref())?;
+
+      let filter = PlayHistoryItem::filter(&station_id, since, until, q.as_deref());
+      let sort = doc! { PlayHistoryItem::KEY_CREATED_AT: -1 };
+      let page = PlayHistoryItem::paged(filter, sort, skip, limit).await?;
+
+      Ok(Output(page))
+    }
+  }
+}
+
+pub mod export {
+
+  use bytes::Bytes;
+  use constants::PLAY_HISTORY_EXPORT_MAX_ITEMS;
+  use db::play_history_item::{csv_field, Kind};
+  use futures_util::TryStreamExt;
+  use hyper::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE};
+  use hyper::{Body, StatusCode};
+  use mongodb::options::FindOptions;
+  use prex::Response;
+
+  use super::*;
+
+  #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+  #[serde(rename_all = "snake_case")]
+  pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+  pub struct Query {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// only the items played at or after this date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime>,
+    /// only the items played before this date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime>,
+    /// search in the title and the artist of the items
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+  }
+
+  pub const CSV_HEADER: &str = "created_at,kind,title,artist,file_id,dj\r\n";
+
+  pub fn csv_row(item: &PlayHistoryItem) -> String {
+    let (kind, file_id, dj) = match &item.kind {
+      Kind::Live { dj } => ("live", "", dj.as_ref().map(|dj| dj.name.as_str())),
+      Kind::Playlist { file_id } => ("playlist", file_id.as_str(), None),
+    };
+
+    format!(
+      "{},{},{},{},{},{}\r\n",
+      item.created_at,
+      kind,
+      csv_field(&item.title),
+      csv_field(item.artist.as_deref().unwrap_or("")),
+      csv_field(file_id),
+      csv_field(dj.unwrap_or("")),
+    )
+  }
+
+  /// streams the play history of the station as a csv or json file, newest first
+  #[derive(Debug, Clone)]
+  pub struct Handler {}
+
+  #[async_trait]
+  impl prex::handler::Handler for Handler {
+    async fn call(&self, req: Request, _: prex::Next) -> Response {
+      let station_id = req.param("station").unwrap();
+
+      let scope = match request_ext::get_access_token_scope(&req).await {
+        Ok(scope) => scope,
+        Err(e) => return ApiError::from(e).into_json_response(),
+      };
+
+      let station = match scope.grant_station_scope(station_id).await {
+        Ok(station) => station,
+        Err(e) => return ApiError::from(e).into_json_response(),
+      };
+
+      let Query {
+        format,
+        since,
+        until,
+        q,
+      } = match req.qs() {
+        Ok(query) => query,
+        Err(e) => return ApiError::from(e).into_json_response(),
+      };
+
+      if let Err(e) = validate_search(q.as_deref()) {
+        return e.into_json_response();
+      }
+
+      let filter = PlayHistoryItem::filter(&station.id, since, until, q.as_deref());
+      let options = FindOptions::builder()
+        .sort(doc! { PlayHistoryItem::KEY_CREATED_AT: -1 })
+        .limit(PLAY_HISTORY_EXPORT_MAX_ITEMS as i64)
+        .build();
+
+      let mut cursor = match PlayHistoryItem::cl().find(filter, options).await {
+        Ok(cursor) => cursor,
+        Err(e) => return ApiError::from(e).into_json_response(),
+      };
+
+      let stream = async_stream::stream! {
+        match format {
+          ExportFormat::Csv => yield Ok(Bytes::from_static(CSV_HEADER.as_bytes())),
+          ExportFormat::Json => yield Ok(Bytes::from_static(b"[")),
+        }
+
+        let mut first = true;
+        loop {
+          let item = match cursor.try_next().await {
+            Ok(Some(item)) => item,
+            Ok(None) => break,
+            Err(e) => {
+              yield Err(e);
+              return;
+            }
+          };
+
+          let chunk = match format {
+            ExportFormat::Csv => csv_row(&item),
+            ExportFormat::Json => {
+              let json = serde_json::to_string(&item).expect("error serializing PlayHistoryItem");
+              if first {
+                json
+              } else {
+                format!(",{json}")
+              }
+            }
+          };
+
+          first = false;
+          yield Ok(Bytes::from(chunk));
+        }
+
+        if let ExportFormat::Json = format {
+          yield Ok(Bytes::from_static(b"]"));
+        }
+      };
+
+      let (content_type, extension) = match format {
+        ExportFormat::Csv => ("text/csv;charset=utf-8", "csv"),
+        ExportFormat::Json => ("application/json;charset=utf-8", "json"),
+      };
+
+      let mut res = Response::new(StatusCode::OK);
+
+      res
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+
+      if let Ok(value) = HeaderValue::from_str(&format!(
+        "attachment; filename=\"play-history-{}.{extension}\"",
+        station.id
+      )) {
+        res.headers_mut().insert(CONTENT_DISPOSITION, value);
+      }
+
+      *res.body_mut() = Body::wrap_stream(stream);
+
+      res
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::export::*;
+  use db::play_history_item::{Kind, PlayHistoryItem};
+  use db::station_dj::StationDjRef;
+
+  #[test]
+  fn csv_rows() {
+    let item = PlayHistoryItem {
+      id: "id".into(),
+      station_id: "station".into(),
+      deployment_id: "deployment".into(),
+      title: "Title, Part 1".into(),
+      artist: None,
+      kind: Kind::Playlist {
+        file_id: "file".into(),
+      },
+      created_at: serde_util::DateTime::now(),
+    };
+
+    let row = csv_row(&item);
+    assert!(row.ends_with(",playlist,\"Title, Part 1\",,file,\r\n"));
+    assert_eq!(
+      row.matches(',').count(),
+      CSV_HEADER.matches(',').count() + 1
+    );
+  }
+
+  #[test]
+  fn csv_live_rows_with_dj() {
+    let item = PlayHistoryItem {
+      id: "id".into(),
+      station_id: "station".into(),
+      deployment_id: "deployment".into(),
+      title: "Title".into(),
+      artist: Some("Artist".into()),
+      kind: Kind::Live {
+        dj: Some(StationDjRef {
+          dj_id: "dj".into(),
+          name: "DJ Night".into(),
+        }),
+      },
+      created_at: serde_util::DateTime::now(),
+    };
+
+    let row = csv_row(&item);
+    assert!(row.ends_with(",live,Title,Artist,,DJ Night\r\n"));
+  }
+}
@@ -0,0 +1,95 @@
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::playlist_item::PlaylistItem;
+use db::station::Station;
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+pub mod delete {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    playlist_id: String,
+    file_id: String,
+  }
+
+  /// removes the file from the playlist, the audio file itself is not deleted
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/files/[file]/DELETE/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(PlaylistItem);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("audio file not found in playlist: {0}")]
+    FileNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::FileNotFound(id) => Self::AudioFileNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, request: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = request.param("station").unwrap();
+      let playlist_id = request.param("playlist").unwrap().to_string();
+      let file_id = request.param("file").unwrap().to_string();
+      let access_token_scope = request_ext::get_access_token_scope(&request).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        station,
+        playlist_id,
+        file_id,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        playlist_id,
+        file_id,
+      } = input;
+
+      let filter = doc! {
+        PlaylistItem::KEY_STATION_ID: &station.id,
+        PlaylistItem::KEY_PLAYLIST_ID: &playlist_id,
+        PlaylistItem::KEY_FILE_ID: &file_id,
+      };
+
+      match PlaylistItem::cl().find_one_and_delete(filter, None).await? {
+        None => Err(HandleError::FileNotFound(file_id)),
+        Some(item) => Ok(Output(item)),
+      }
+    }
+  }
+}
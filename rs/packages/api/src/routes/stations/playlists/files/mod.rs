@@ -0,0 +1,306 @@
+pub mod id;
+pub mod order;
+
+use crate::json::JsonHandler;
+use crate::request_ext::{self, AccessTokenScope, GetAccessTokenScopeError};
+use mongodb::bson::doc;
+
+use crate::error::ApiError;
+use async_trait::async_trait;
+use db::audio_file::AudioFile;
+use db::playlist_item::PlaylistItem;
+use db::station::Station;
+use db::Model;
+use db::Paged;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::get_playlist;
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use crate::qs::PaginationQs;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Serialize, Deserialize, Clone, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/files/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    items: Paged<PlaylistItem>,
+    playlist_is_randomly_shuffled: bool,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/files/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    #[allow(unused)]
+    access_token_scope: AccessTokenScope,
+    playlist_id: String,
+    query: Query,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("access: {0}")]
+    Access(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    QueryString(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> ApiError {
+      match e {
+        ParseError::Access(e) => e.into(),
+        ParseError::QueryString(e) => e.into(),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("playlist not found: {0}")]
+    PlaylistNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::PlaylistNotFound(id) => Self::PlaylistNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let playlist_id = req.param("playlist").unwrap().to_string();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input {
+        access_token_scope,
+        station,
+        playlist_id,
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        access_token_scope: _,
+        station,
+        playlist_id,
+        query: Query {
+          page: PaginationQs { skip, limit },
+        },
+      } = input;
+
+      let playlist = match get_playlist(&station.id, &playlist_id).await? {
+        None => return Err(HandleError::PlaylistNotFound(playlist_id)),
+        Some(playlist) => playlist,
+      };
+
+      let filter = doc! { PlaylistItem::KEY_PLAYLIST_ID: &playlist.id };
+      let sort = doc! { PlaylistItem::KEY_ORDER: 1 };
+      let page = PlaylistItem::paged(filter, sort, skip, limit).await?;
+
+      Ok(Output {
+        items: page,
+        playlist_is_randomly_shuffled: playlist.is_randomly_shuffled,
+      })
+    }
+  }
+}
+
+pub mod post {
+
+  use constants::validate::VALIDATE_PLAYLIST_FILES_MAX_LEN;
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use schemars::JsonSchema;
+  use serde_util::DateTime;
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  /// appends an audio file of the station at the end of the playlist
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/files/POST/"
+  )]
+  #[macros::schema_ts_export]
+  #[serde(rename_all = "snake_case")]
+  pub struct Payload {
+    pub file_id: String,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    playlist_id: String,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/files/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(PlaylistItem);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => Self::from(e),
+        ParseError::Payload(e) => Self::from(e),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("playlist not found: {0}")]
+    PlaylistNotFound(String),
+    #[error("audio file not found: {0}")]
+    FileNotFound(String),
+    #[error("file already in playlist: {0}")]
+    FileAlreadyInPlaylist(String),
+    #[error("too many files in playlist")]
+    TooManyFiles,
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => Self::from(e),
+        HandleError::PlaylistNotFound(id) => Self::PlaylistNotFound(id),
+        HandleError::FileNotFound(id) => Self::AudioFileNotFound(id),
+        HandleError::FileAlreadyInPlaylist(id) => Self::PayloadInvalid(format!(
+          "Audio file with id {id} is already in the playlist"
+        )),
+        HandleError::TooManyFiles => Self::PayloadInvalid(format!(
+          "A playlist cannot have more than {VALIDATE_PLAYLIST_FILES_MAX_LEN} files"
+        )),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let playlist_id = req.param("playlist").unwrap().to_string();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let payload: Payload = req.read_body_json(1_000).await?;
+
+      Ok(Self::Input {
+        station,
+        playlist_id,
+        payload,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        playlist_id,
+        payload: Payload { file_id },
+      } = input;
+
+      let playlist = match get_playlist(&station.id, &playlist_id).await? {
+        None => return Err(HandleError::PlaylistNotFound(playlist_id)),
+        Some(playlist) => playlist,
+      };
+
+      let filter = doc! { AudioFile::KEY_ID: &file_id, AudioFile::KEY_STATION_ID: &station.id };
+      if !AudioFile::exists(filter).await? {
+        return Err(HandleError::FileNotFound(file_id));
+      }
+
+      let filter =
+        doc! { PlaylistItem::KEY_PLAYLIST_ID: &playlist.id, PlaylistItem::KEY_FILE_ID: &file_id };
+      if PlaylistItem::exists(filter).await? {
+        return Err(HandleError::FileAlreadyInPlaylist(file_id));
+      }
+
+      let filter = doc! { PlaylistItem::KEY_PLAYLIST_ID: &playlist.id };
+      let count = PlaylistItem::cl().count_documents(filter, None).await?;
+      if count >= VALIDATE_PLAYLIST_FILES_MAX_LEN as u64 {
+        return Err(HandleError::TooManyFiles);
+      }
+
+      let order = PlaylistItem::next_max_order(&playlist.id, None).await?;
+
+      let item = PlaylistItem {
+        id: PlaylistItem::uid(),
+        station_id: station.id,
+        playlist_id: playlist.id,
+        file_id,
+        order,
+        created_at: DateTime::now(),
+      };
+
+      PlaylistItem::insert(&item).await?;
+
+      Ok(Output(item))
+    }
+  }
+}
@@ -0,0 +1,312 @@
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::playlist::{Playlist, PlaylistRotation};
+use db::station::Station;
+use db::Model;
+use prex::Request;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::{get_playlist, rotations_validation_error};
+
+pub mod get {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    playlist_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Playlist);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("playlist not found: {0}")]
+    PlaylistNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::PlaylistNotFound(id) => Self::PlaylistNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let playlist_id = req.param("playlist").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        station,
+        playlist_id: playlist_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        playlist_id,
+      } = input;
+
+      match get_playlist(&station.id, &playlist_id).await? {
+        None => Err(HandleError::PlaylistNotFound(playlist_id)),
+        Some(playlist) => Ok(Output(playlist)),
+      }
+    }
+  }
+}
+
+pub mod patch {
+
+  use constants::validate::*;
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use serde_util::DateTime;
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/PATCH/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[modify(trim)]
+    #[validate(
+      length(
+        min = "VALIDATE_PLAYLIST_NAME_MIN_LEN",
+        max = "VALIDATE_PLAYLIST_NAME_MAX_LEN",
+        message = "Playlist name is empty or too long"
+      ),
+      non_control_character(message = "Playlist name cannot have control characters")
+    )]
+    pub name: Option<String>,
+
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(
+      max = "VALIDATE_PLAYLIST_ROTATIONS_MAX_LEN",
+      message = "Too many playlist rotations"
+    ))]
+    #[validate]
+    pub rotations: Option<Vec<PlaylistRotation>>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    playlist_id: String,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/PATCH/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Playlist);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => Self::from(e),
+        ParseError::Payload(e) => Self::from(e),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("playlist not found: {0}")]
+    PlaylistNotFound(String),
+    #[error("invalid playlist: {0}")]
+    Invalid(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => Self::from(e),
+        HandleError::PlaylistNotFound(id) => Self::PlaylistNotFound(id),
+        HandleError::Invalid(message) => Self::PayloadInvalid(message),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let playlist_id = req.param("playlist").unwrap().to_string();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let payload: Payload = req.read_body_json(10_000).await?;
+
+      Ok(Self::Input {
+        station,
+        playlist_id,
+        payload,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        playlist_id,
+        payload: Payload { name, rotations },
+      } = input;
+
+      let mut playlist = match get_playlist(&station.id, &playlist_id).await? {
+        None => return Err(HandleError::PlaylistNotFound(playlist_id)),
+        Some(playlist) => playlist,
+      };
+
+      if let Some(name) = name {
+        playlist.name = name;
+      }
+
+      if let Some(rotations) = rotations {
+        playlist.rotations = rotations;
+      }
+
+      playlist.updated_at = DateTime::now();
+
+      if let Some(message) = rotations_validation_error(&playlist).await? {
+        return Err(HandleError::Invalid(message));
+      }
+
+      Playlist::replace(&playlist.id, &playlist).await?;
+
+      Ok(Output(playlist))
+    }
+  }
+}
+
+pub mod delete {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    playlist_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/DELETE/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Playlist);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("playlist not found: {0}")]
+    PlaylistNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::PlaylistNotFound(id) => Self::PlaylistNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let playlist_id = req.param("playlist").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        station,
+        playlist_id: playlist_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        playlist_id,
+      } = input;
+
+      match Playlist::delete_playlist(&station.id, &playlist_id).await? {
+        None => Err(HandleError::PlaylistNotFound(playlist_id)),
+        Some(playlist) => Ok(Output(playlist)),
+      }
+    }
+  }
+}
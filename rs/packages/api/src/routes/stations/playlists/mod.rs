@@ -0,0 +1,311 @@
+pub mod files;
+pub mod id;
+pub mod shuffle;
+pub mod unshuffle;
+
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, AccessTokenScope, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::playlist::{Playlist, PlaylistRotation};
+use db::station::Station;
+use db::Model;
+use db::Paged;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use ts_rs::TS;
+
+pub async fn get_playlist(
+  station_id: &str,
+  playlist_id: &str,
+) -> Result<Option<Playlist>, mongodb::error::Error> {
+  let filter = doc! {
+    Playlist::KEY_ID: playlist_id,
+    Playlist::KEY_STATION_ID: station_id,
+  };
+
+  Playlist::get(filter).await
+}
+
+/// validates the rotations of `playlist` against the other playlists of the station
+/// returns the message for ApiError::PayloadInvalid if the rotations are invalid
+pub async fn rotations_validation_error(
+  playlist: &Playlist,
+) -> Result<Option<String>, mongodb::error::Error> {
+  let ids = playlist
+    .rotations
+    .iter()
+    .map(|rotation| &rotation.playlist_id)
+    .collect::<HashSet<&String>>();
+
+  if ids.len() != playlist.rotations.len() {
+    return Ok(Some("Playlist rotations cannot be repeated".into()));
+  }
+
+  if ids.contains(&playlist.id) {
+    return Ok(Some("A playlist cannot rotate with itself".into()));
+  }
+
+  if !ids.is_empty() {
+    let filter = doc! {
+      Playlist::KEY_STATION_ID: &playlist.station_id,
+      Playlist::KEY_ID: { "$in": ids.into_iter().collect::<Vec<&String>>() },
+    };
+
+    let count = Playlist::cl().count_documents(filter, None).await?;
+    if count != playlist.rotations.len() as u64 {
+      return Ok(Some(
+        "Some of the rotation playlists were not found in the station".into(),
+      ));
+    }
+  }
+
+  Ok(None)
+}
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use crate::qs::PaginationQs;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Serialize, Deserialize, Clone, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Paged<Playlist>);
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    #[allow(unused)]
+    access_token_scope: AccessTokenScope,
+    query: Query,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("access: {0}")]
+    Access(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    QueryString(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> ApiError {
+      match e {
+        ParseError::Access(e) => e.into(),
+        ParseError::QueryString(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = mongodb::error::Error;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input {
+        access_token_scope,
+        station,
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        access_token_scope: _,
+        station,
+        query: Query {
+          page: PaginationQs { skip, limit },
+        },
+      } = input;
+
+      let filter = doc! { Playlist::KEY_STATION_ID: &station.id };
+      let sort = doc! { Playlist::KEY_CREATED_AT: 1 };
+      let page = Playlist::paged(filter, sort, skip, limit).await?;
+
+      Ok(Output(page))
+    }
+  }
+}
+
+pub mod post {
+
+  use constants::validate::*;
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use schemars::JsonSchema;
+  use serde_util::DateTime;
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    #[modify(trim)]
+    #[validate(
+      length(
+        min = "VALIDATE_PLAYLIST_NAME_MIN_LEN",
+        max = "VALIDATE_PLAYLIST_NAME_MAX_LEN",
+        message = "Playlist name is empty or too long"
+      ),
+      non_control_character(message = "Playlist name cannot have control characters")
+    )]
+    pub name: String,
+
+    #[ts(optional)]
+    #[serde(default)]
+    #[validate(length(
+      max = "VALIDATE_PLAYLIST_ROTATIONS_MAX_LEN",
+      message = "Too many playlist rotations"
+    ))]
+    #[validate]
+    pub rotations: Vec<PlaylistRotation>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Playlist);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => Self::from(e),
+        ParseError::Payload(e) => Self::from(e),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("too many playlists")]
+    TooManyPlaylists,
+    #[error("invalid playlist: {0}")]
+    Invalid(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => Self::from(e),
+        HandleError::TooManyPlaylists => Self::PayloadInvalid(format!(
+          "A station cannot have more than {VALIDATE_STATION_PLAYLISTS_MAX_LEN} playlists"
+        )),
+        HandleError::Invalid(message) => Self::PayloadInvalid(message),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let payload: Payload = req.read_body_json(10_000).await?;
+
+      Ok(Self::Input { station, payload })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        payload: Payload { name, rotations },
+      } = input;
+
+      let filter = doc! { Playlist::KEY_STATION_ID: &station.id };
+      let count = Playlist::cl().count_documents(filter, None).await?;
+      if count >= VALIDATE_STATION_PLAYLISTS_MAX_LEN as u64 {
+        return Err(HandleError::TooManyPlaylists);
+      }
+
+      let now = DateTime::now();
+      let playlist = Playlist {
+        id: Playlist::uid(),
+        station_id: station.id,
+        name,
+        rotations,
+        is_randomly_shuffled: false,
+        created_at: now,
+        updated_at: now,
+      };
+
+      if let Some(message) = rotations_validation_error(&playlist).await? {
+        return Err(HandleError::Invalid(message));
+      }
+
+      Playlist::insert(&playlist).await?;
+
+      Ok(Output(playlist))
+    }
+  }
+}
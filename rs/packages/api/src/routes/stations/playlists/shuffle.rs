@@ -0,0 +1,129 @@
+pub mod post {
+  use db::playlist::Playlist;
+  use db::playlist_item::PlaylistItem;
+  use db::playlist_pre_shuffle_checkpoint::PlaylistPreShuffleCheckpoint;
+  use db::{run_transaction, Model};
+  use mongodb::bson::doc;
+  use mongodb::options::{FindOneAndReplaceOptions, FindOptions};
+  use prex::Request;
+  use rand::seq::SliceRandom;
+  use schemars::JsonSchema;
+  use serde::{Deserialize, Serialize};
+  use serde_util::empty_struct::EmptyStruct;
+  use serde_util::DateTime;
+  use ts_rs::TS;
+
+  use crate::{
+    error::ApiError,
+    json::JsonHandler,
+    request_ext::{self, GetAccessTokenScopeError},
+  };
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station_id: String,
+    playlist_id: String,
+  }
+
+  #[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/shuffle/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(EmptyStruct);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongo: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("playlist not found: {0}")]
+    PlaylistNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::PlaylistNotFound(id) => ApiError::PlaylistNotFound(id),
+      }
+    }
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[async_trait::async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, request: Request) -> Result<Input, Self::ParseError> {
+      let station_id = request.param("station").unwrap();
+      let playlist_id = request.param("playlist").unwrap().to_string();
+      let access_token_scope = request_ext::get_access_token_scope(&request).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+      Ok(Input {
+        station_id: station.id,
+        playlist_id,
+      })
+    }
+
+    async fn perform(&self, input: Input) -> Result<Output, Self::HandleError> {
+      let Input {
+        station_id,
+        playlist_id,
+      } = input;
+
+      run_transaction!(session => {
+
+        let filter = doc!{ Playlist::KEY_ID: &playlist_id, Playlist::KEY_STATION_ID: &station_id };
+        let playlist = match tx_try!(Playlist::get_with_session(filter, &mut session).await) {
+          Some(playlist) => playlist,
+          None => return Err(HandleError::PlaylistNotFound(playlist_id))
+        };
+
+        let filter = doc!{ PlaylistItem::KEY_PLAYLIST_ID: &playlist.id };
+        let sort = doc!{ PlaylistItem::KEY_ORDER: 1 };
+        let options = FindOptions::builder().sort(sort).build();
+        let mut cursor = tx_try!(PlaylistItem::cl().find_with_session(filter, options, &mut session).await);
+
+        let mut items = vec![];
+        while let Some(r) = cursor.next(&mut session).await {
+          items.push(tx_try!(r));
+        }
+
+        if !playlist.is_randomly_shuffled {
+          let update = doc!{ "$set": { Playlist::KEY_IS_RANDOMLY_SHUFFLED: true } };
+          tx_try!(Playlist::update_by_id_with_session(&playlist.id, update, &mut session).await);
+
+          let checkpoint = PlaylistPreShuffleCheckpoint {
+            id: playlist.id.clone(),
+            file_ids: items.iter().map(|item| item.file_id.clone()).collect(),
+            created_at: DateTime::now(),
+          };
+
+          let filter = doc!{ PlaylistPreShuffleCheckpoint::KEY_ID: &playlist.id };
+          let options = FindOneAndReplaceOptions::builder().upsert(true).build();
+          tx_try!(PlaylistPreShuffleCheckpoint::cl().find_one_and_replace_with_session(filter, &checkpoint, options, &mut session).await);
+        }
+
+        {
+          let mut rng = rand::thread_rng();
+          items.shuffle(&mut rng);
+        }
+
+        for (i, item) in items.iter().enumerate() {
+          let order = ((i as f64 + 1.0) * 2.0) + rand::random::<f64>();
+          let filter = doc! { PlaylistItem::KEY_ID: &item.id };
+          let update = doc! { "$set": { PlaylistItem::KEY_ORDER: order } };
+          tx_try!(PlaylistItem::cl().update_one_with_session(filter, update, None, &mut session).await);
+        }
+      });
+
+      Ok(Output(EmptyStruct(())))
+    }
+  }
+}
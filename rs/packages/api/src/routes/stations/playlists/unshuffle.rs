@@ -0,0 +1,123 @@
+pub mod post {
+  use db::playlist::Playlist;
+  use db::playlist_item::PlaylistItem;
+  use db::playlist_pre_shuffle_checkpoint::PlaylistPreShuffleCheckpoint;
+  use db::{run_transaction, Model};
+  use mongodb::bson::doc;
+  use mongodb::options::FindOptions;
+  use prex::Request;
+  use schemars::JsonSchema;
+  use serde::{Deserialize, Serialize};
+  use serde_util::empty_struct::EmptyStruct;
+  use ts_rs::TS;
+
+  use crate::{
+    error::ApiError,
+    json::JsonHandler,
+    request_ext::{self, GetAccessTokenScopeError},
+  };
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station_id: String,
+    playlist_id: String,
+  }
+
+  #[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/playlists/[playlist]/unshuffle/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(EmptyStruct);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongo: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("playlist not found: {0}")]
+    PlaylistNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::PlaylistNotFound(id) => ApiError::PlaylistNotFound(id),
+      }
+    }
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[async_trait::async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, request: Request) -> Result<Input, Self::ParseError> {
+      let station_id = request.param("station").unwrap();
+      let playlist_id = request.param("playlist").unwrap().to_string();
+      let access_token_scope = request_ext::get_access_token_scope(&request).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+      Ok(Input {
+        station_id: station.id,
+        playlist_id,
+      })
+    }
+
+    async fn perform(&self, input: Input) -> Result<Output, Self::HandleError> {
+      let Input {
+        station_id,
+        playlist_id,
+      } = input;
+
+      run_transaction!(session => {
+
+        let filter = doc!{ Playlist::KEY_ID: &playlist_id, Playlist::KEY_STATION_ID: &station_id };
+        let playlist = match tx_try!(Playlist::get_with_session(filter, &mut session).await) {
+          Some(playlist) => playlist,
+          None => return Err(HandleError::PlaylistNotFound(playlist_id)),
+        };
+
+        // if not randomly shuffle we ignore and return Ok
+        if !playlist.is_randomly_shuffled {
+          return Ok(Output(EmptyStruct(())));
+        }
+
+        let update = doc!{ "$set": { Playlist::KEY_IS_RANDOMLY_SHUFFLED: false } };
+        tx_try!(Playlist::update_by_id_with_session(&playlist.id, update, &mut session).await);
+
+        let mut sorted_file_ids = match tx_try!(PlaylistPreShuffleCheckpoint::get_by_id_with_session(&playlist.id, &mut session).await) {
+          Some(doc) => doc.file_ids,
+          None => vec![],
+        };
+
+        // files added after the shuffle go at the end in the order they were added
+        let filter = doc!{ PlaylistItem::KEY_PLAYLIST_ID: &playlist.id, PlaylistItem::KEY_FILE_ID: { "$nin": &sorted_file_ids } };
+        let sort = doc!{ PlaylistItem::KEY_CREATED_AT: 1 };
+        let options = FindOptions::builder().sort(sort).build();
+        let mut cursor = tx_try!(PlaylistItem::cl().find_with_session(filter, options, &mut session).await);
+
+        while let Some(r) = cursor.next(&mut session).await {
+          let item = tx_try!(r);
+          sorted_file_ids.push(item.file_id);
+        }
+
+        let mut i = 0.0;
+        for file_id in sorted_file_ids {
+          i += 1.0;
+          let order = (i * 2.0) + rand::random::<f64>();
+          let filter = doc! { PlaylistItem::KEY_PLAYLIST_ID: &playlist.id, PlaylistItem::KEY_FILE_ID: file_id };
+          let update = doc! { "$set": { PlaylistItem::KEY_ORDER: order } };
+          tx_try!(PlaylistItem::cl().update_one_with_session(filter, update, None, &mut session).await);
+        }
+      });
+
+      Ok(Output(EmptyStruct(())))
+    }
+  }
+}
@@ -0,0 +1,269 @@
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::live_recording::LiveRecording;
+use db::station::Station;
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    recording_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/recordings/[recording]/GET/"
+  )]
+  #[macros::schema_ts_export]
+  #[serde(rename_all = "snake_case")]
+  pub struct Output {
+    item: LiveRecording,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("live recording not found: {0}")]
+    RecordingNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::RecordingNotFound(id) => Self::LiveRecordingNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let recording_id = req.param("recording").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        station,
+        recording_id: recording_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        recording_id,
+      } = input;
+
+      let filter =
+        doc! { LiveRecording::KEY_ID: &recording_id, LiveRecording::KEY_STATION_ID: &station.id };
+      let item = match LiveRecording::get(filter).await? {
+        None => return Err(HandleError::RecordingNotFound(recording_id)),
+        Some(item) => item,
+      };
+
+      Ok(Output { item })
+    }
+  }
+}
+
+pub mod stream {
+
+  use db::account::Account;
+  use hyper::header::{HeaderValue, CONTENT_DISPOSITION};
+
+  use crate::routes::stations::files::id::stream::file_response;
+
+  use super::*;
+
+  /// downloads the audio of a live recording
+  #[derive(Debug, Clone)]
+  pub struct Handler {}
+
+  #[async_trait]
+  impl prex::handler::Handler for Handler {
+    async fn call(&self, request: Request, _: prex::Next) -> prex::Response {
+      let station_id = request.param("station").unwrap();
+      let recording_id = request.param("recording").unwrap();
+
+      let scope = match request_ext::get_media_access_token_scope(&request).await {
+        Ok(scope) => scope,
+        Err(e) => return ApiError::from(e).into_json_response(),
+      };
+
+      let station = match scope.grant_station_scope(station_id).await {
+        Ok(station) => station,
+        Err(e) => return ApiError::from(e).into_json_response(),
+      };
+
+      // as with the station files, users are cut off
+      // when the account used all the transfer of the billing period
+      if !scope.is_admin_or_global() {
+        let account = match Account::get_by_id(&station.account_id).await {
+          Ok(Some(account)) => account,
+          Ok(None) => return ApiError::AccountNotFound(station.account_id).into_json_response(),
+          Err(e) => return ApiError::from(e).into_json_response(),
+        };
+
+        match account.is_transfer_blocked().await {
+          Ok(false) => {}
+          Ok(true) => return ApiError::TransferQuotaExceeded.into_json_response(),
+          Err(e) => return ApiError::from(e).into_json_response(),
+        }
+      }
+
+      let filter =
+        doc! { LiveRecording::KEY_ID: recording_id, LiveRecording::KEY_STATION_ID: &station.id };
+      let recording = match LiveRecording::get(filter).await {
+        Ok(Some(recording)) => recording,
+        Ok(None) => {
+          return ApiError::LiveRecordingNotFound(recording_id.to_string()).into_json_response()
+        }
+        Err(e) => return ApiError::from(e).into_json_response(),
+      };
+
+      let disposition = format!(
+        "attachment; filename=\"{}\"",
+        recording.file.filename.replace('"', "'")
+      );
+
+      let mut response = file_response(&request, recording.file);
+
+      if let Ok(value) = HeaderValue::from_str(&disposition) {
+        response.headers_mut().insert(CONTENT_DISPOSITION, value);
+      }
+
+      response
+    }
+  }
+}
+
+pub mod delete {
+
+  use db::audio_file::AudioFile;
+  use db::audit_log::{AuditLog, AuditLogAction, AuditLogChange, AuditLogOrigin, AuditLogTarget};
+  use db::run_transaction;
+  use schemars::JsonSchema;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    recording_id: String,
+    audit_log_origin: AuditLogOrigin,
+  }
+
+  /// the removed recording, the storage used by it is freed
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/recordings/[recording]/DELETE/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(LiveRecording);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("live recording not found: {0}")]
+    RecordingNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::RecordingNotFound(id) => Self::LiveRecordingNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let recording_id = req.param("recording").unwrap();
+
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        station,
+        recording_id: recording_id.to_string(),
+        audit_log_origin,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        recording_id,
+        audit_log_origin,
+      } = input;
+
+      let recording = run_transaction!(session => {
+        let recording = match tx_try!(LiveRecording::delete_with_session(&station.id, &recording_id, &mut session).await) {
+          None => return Err(HandleError::RecordingNotFound(recording_id)),
+          Some(recording) => recording,
+        };
+
+        let audit_log = AuditLog::new(
+          audit_log_origin.clone(),
+          vec![station.account_id.clone()],
+          AuditLogAction::RecordingDelete,
+          AuditLogTarget::LiveRecording {
+            station_id: station.id.clone(),
+            recording_id: recording.id.clone(),
+          },
+          vec![AuditLogChange {
+            field: AudioFile::KEY_FILENAME.to_string(),
+            before: recording.file.filename.clone().into(),
+            after: serde_json::Value::Null,
+          }],
+        );
+
+        tx_try!(AuditLog::insert_with_session(&audit_log, &mut session).await);
+
+        recording
+      });
+
+      Ok(Output(recording))
+    }
+  }
+}
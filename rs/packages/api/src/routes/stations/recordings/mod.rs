@@ -0,0 +1,111 @@
+pub mod id;
+pub mod publish;
+
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::live_recording::LiveRecording;
+use db::station::Station;
+use db::Model;
+use db::Paged;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use crate::qs::PaginationQs;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/recordings/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+    /// only the recordings of this live media session
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_session_id: Option<String>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    query: Query,
+  }
+
+  /// the live recordings of the station that are not published, newest first
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/recordings/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Paged<LiveRecording>);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("access: {0}")]
+    Access(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    QueryString(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> ApiError {
+      match e {
+        ParseError::Access(e) => e.into(),
+        ParseError::QueryString(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = mongodb::error::Error;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input { station, query })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        query:
+          Query {
+            page: PaginationQs { skip, limit },
+            media_session_id,
+          },
+      } = input;
+
+      let filter = LiveRecording::filter(&station.id, media_session_id.as_deref());
+      let sort = doc! { LiveRecording::KEY_CREATED_AT: -1 };
+      let page = LiveRecording::paged(filter, sort, skip, limit).await?;
+
+      Ok(Output(page))
+    }
+  }
+}
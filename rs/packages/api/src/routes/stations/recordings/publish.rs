@@ -0,0 +1,85 @@
+pub mod post {
+  use async_trait::async_trait;
+  use db::audio_file::AudioFile;
+  use db::live_recording::LiveRecording;
+  use db::station::Station;
+  use prex::Request;
+  use schemars::JsonSchema;
+  use serde::{Deserialize, Serialize};
+  use ts_rs::TS;
+
+  use crate::error::ApiError;
+  use crate::json::JsonHandler;
+  use crate::request_ext::{self, GetAccessTokenScopeError};
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    recording_id: String,
+  }
+
+  /// the file the recording was published as, at the end of the station files
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/recordings/[recording]/publish/POST/"
+  )]
+  #[macros::schema_ts_export]
+  #[serde(rename_all = "snake_case")]
+  pub struct Output {
+    item: AudioFile,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("live recording not found: {0}")]
+    RecordingNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::RecordingNotFound(id) => Self::LiveRecordingNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let recording_id = req.param("recording").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        station,
+        recording_id: recording_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        recording_id,
+      } = input;
+
+      match LiveRecording::publish(&station.id, &recording_id).await? {
+        None => Err(HandleError::RecordingNotFound(recording_id)),
+        Some(item) => Ok(Output { item }),
+      }
+    }
+  }
+}
@@ -10,7 +10,8 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 pub mod post {
 
-  use db::{deployment::Deployment, Model};
+  use db::audit_log::{AuditLog, AuditLogAction, AuditLogOrigin, AuditLogTarget};
+  use db::{deployment::Deployment, run_transaction, Model};
   use hyper::{http::HeaderValue, Body};
   use media::MediaSessionMap;
   use schemars::JsonSchema;
@@ -28,6 +29,7 @@ pub mod post {
   #[derive(Debug, Clone)]
   pub struct Input {
     access_token_header: Option<HeaderValue>,
+    audit_log_origin: AuditLogOrigin,
     station: Station,
   }
 
@@ -65,11 +67,13 @@ pub mod post {
     async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
       let station_id = req.param("station").unwrap();
       let access_token_header = req.headers().get(X_ACCESS_TOKEN).cloned();
-      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
       let station = access_token_scope.grant_station_scope(station_id).await?;
       Ok(Input {
         station,
         access_token_header,
+        audit_log_origin,
       })
     }
 
@@ -77,12 +81,27 @@ pub mod post {
       let Input {
         station,
         access_token_header,
+        audit_log_origin,
       } = input;
 
       let new_password = Station::random_source_password();
 
-      let update = doc! { "$set": { Station::KEY_SOURCE_PASSWORD: &new_password } };
-      Station::update_by_id(&station.id, update).await?;
+      // the new password is not written to the audit log
+      let audit_log = AuditLog::new(
+        audit_log_origin,
+        vec![station.account_id.clone()],
+        AuditLogAction::StationSourcePasswordReset,
+        AuditLogTarget::Station {
+          station_id: station.id.clone(),
+        },
+        vec![],
+      );
+
+      run_transaction!(session => {
+        let update = doc! { "$set": { Station::KEY_SOURCE_PASSWORD: &new_password } };
+        tx_try!(Station::update_by_id_with_session(&station.id, update, &mut session).await);
+        tx_try!(AuditLog::insert_with_session(&audit_log, &mut session).await);
+      });
 
       let deployment_id = self.deployment_id.clone();
       let media_sessions = self.media_sessions.clone();
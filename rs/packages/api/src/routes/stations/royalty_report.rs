@@ -0,0 +1,118 @@
+use crate::error::ApiError;
+use crate::request_ext;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use db::play_history_item::royalty_report::{
+  self, RoyaltyReportColumn, RoyaltyReportFormat, RoyaltyReportLayout, RoyaltyReportPreset,
+};
+use hyper::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE};
+use hyper::{Body, StatusCode};
+use prex::{Request, Response};
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+
+pub mod get {
+
+  use super::*;
+
+  #[derive(Debug, Clone, Serialize, Deserialize)]
+  pub struct Query {
+    /// start of the report, inclusive
+    pub since: DateTime,
+    /// end of the report, exclusive
+    pub until: DateTime,
+    /// defaults to detailed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<RoyaltyReportPreset>,
+    /// overrides the format of the preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<RoyaltyReportFormat>,
+    /// comma separated list of columns, overrides the columns of the preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<String>,
+    /// offset of the dates and times of the report, defaults to utc
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utc_offset_minutes: Option<i32>,
+  }
+
+  impl Query {
+    fn layout(&self) -> Result<RoyaltyReportLayout, ApiError> {
+      let columns = match &self.columns {
+        None => None,
+        Some(columns) => Some(
+          RoyaltyReportColumn::parse_list(columns)
+            .map_err(|e| ApiError::QueryStringCustom(format!("columns: {e}")))?,
+        ),
+      };
+
+      RoyaltyReportLayout::new(self.preset, self.format, columns, self.utc_offset_minutes)
+        .map_err(|e| ApiError::QueryStringCustom(e.to_string()))
+    }
+  }
+
+  /// the royalty report of the plays of the station in a date range,
+  /// with the listeners and aggregate tuning hours of each play
+  #[derive(Debug, Clone)]
+  pub struct Handler {}
+
+  impl Handler {
+    async fn handle(&self, req: Request) -> Result<Response, ApiError> {
+      let station_id = req.param("station").unwrap();
+      let scope = request_ext::get_access_token_scope(&req).await?;
+      let station = scope.grant_station_scope(station_id).await?;
+      let query: Query = req.qs()?;
+
+      royalty_report::check_range(query.since, query.until)
+        .map_err(|e| ApiError::QueryStringCustom(e.to_string()))?;
+
+      let layout = query.layout()?;
+
+      let mut rows = royalty_report::generate(&station.id, query.since, query.until).await?;
+
+      let mut res = Response::new(StatusCode::OK);
+
+      res.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(layout.format.content_type()),
+      );
+
+      if let Ok(value) = HeaderValue::from_str(&format!(
+        "attachment; filename=\"royalty-report-{}.{}\"",
+        station.id, layout.format,
+      )) {
+        res.headers_mut().insert(CONTENT_DISPOSITION, value);
+      }
+
+      // the rows are written as they are computed
+      let stream = async_stream::stream! {
+        yield Ok(Bytes::from(layout.header()));
+
+        loop {
+          match rows.next().await {
+            Ok(Some(row)) => yield Ok(Bytes::from(layout.row(&row))),
+            Ok(None) => break,
+            Err(e) => {
+              yield Err(e);
+              return;
+            }
+          }
+        }
+      };
+
+      *res.body_mut() = Body::wrap_stream(stream);
+
+      Ok(res)
+    }
+  }
+
+  #[async_trait]
+  impl prex::handler::Handler for Handler {
+    async fn call(&self, req: Request, _: prex::Next) -> Response {
+      match self.handle(req).await {
+        Ok(res) => res,
+        Err(e) => e.into_json_response(),
+      }
+    }
+  }
+}
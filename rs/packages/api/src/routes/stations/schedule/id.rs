@@ -0,0 +1,311 @@
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::station::Station;
+use db::station_schedule::{StationSchedule, StationScheduleSource, StationScheduleTime};
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::slot_validation_error;
+
+async fn get_slot(
+  station: &Station,
+  slot_id: &str,
+) -> Result<Option<StationSchedule>, mongodb::error::Error> {
+  let filter = doc! {
+    StationSchedule::KEY_ID: slot_id,
+    StationSchedule::KEY_STATION_ID: &station.id,
+  };
+
+  StationSchedule::get(filter).await
+}
+
+pub mod get {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    slot_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/schedule/[slot]/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(StationSchedule);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("schedule slot not found: {0}")]
+    SlotNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::SlotNotFound(id) => Self::ScheduleSlotNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let slot_id = req.param("slot").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        station,
+        slot_id: slot_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input { station, slot_id } = input;
+
+      match get_slot(&station, &slot_id).await? {
+        None => Err(HandleError::SlotNotFound(slot_id)),
+        Some(slot) => Ok(Output(slot)),
+      }
+    }
+  }
+}
+
+pub mod patch {
+
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use serde_util::DateTime;
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/schedule/[slot]/PATCH/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<StationScheduleTime>,
+    #[ts(optional)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<StationScheduleSource>,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    slot_id: String,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/schedule/[slot]/PATCH/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(StationSchedule);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => Self::from(e),
+        ParseError::Payload(e) => Self::from(e),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("schedule slot not found: {0}")]
+    SlotNotFound(String),
+    #[error("invalid slot: {0}")]
+    Invalid(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => Self::from(e),
+        HandleError::SlotNotFound(id) => Self::ScheduleSlotNotFound(id),
+        HandleError::Invalid(message) => Self::PayloadInvalid(message),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let slot_id = req.param("slot").unwrap().to_string();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let payload: Payload = req.read_body_json(100_000).await?;
+
+      Ok(Self::Input {
+        station,
+        slot_id,
+        payload,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        slot_id,
+        payload: Payload { time, source },
+      } = input;
+
+      let mut slot = match get_slot(&station, &slot_id).await? {
+        None => return Err(HandleError::SlotNotFound(slot_id)),
+        Some(slot) => slot,
+      };
+
+      if let Some(time) = time {
+        slot.time = time;
+      }
+
+      if let Some(source) = source {
+        slot.source = source;
+      }
+
+      slot.updated_at = DateTime::now();
+
+      if let Some(message) = slot_validation_error(&slot).await? {
+        return Err(HandleError::Invalid(message));
+      }
+
+      StationSchedule::replace(&slot.id, &slot).await?;
+
+      Ok(Output(slot))
+    }
+  }
+}
+
+pub mod delete {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    slot_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/schedule/[slot]/DELETE/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(StationSchedule);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("schedule slot not found: {0}")]
+    SlotNotFound(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => e.into(),
+        HandleError::SlotNotFound(id) => Self::ScheduleSlotNotFound(id),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let slot_id = req.param("slot").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input {
+        station,
+        slot_id: slot_id.to_string(),
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input { station, slot_id } = input;
+
+      let filter = doc! {
+        StationSchedule::KEY_ID: &slot_id,
+        StationSchedule::KEY_STATION_ID: &station.id,
+      };
+
+      match StationSchedule::cl()
+        .find_one_and_delete(filter, None)
+        .await?
+      {
+        None => Err(HandleError::SlotNotFound(slot_id)),
+        Some(slot) => Ok(Output(slot)),
+      }
+    }
+  }
+}
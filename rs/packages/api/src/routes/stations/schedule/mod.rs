@@ -0,0 +1,296 @@
+pub mod id;
+
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, AccessTokenScope, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::audio_file::AudioFile;
+use db::playlist::Playlist;
+use db::station::Station;
+use db::station_schedule::{StationSchedule, StationScheduleSource, StationScheduleTime};
+use db::Model;
+use db::Paged;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use ts_rs::TS;
+
+/// validates `slot` against the other slots of the station and the playlists and files of the station
+/// returns the message for ApiError::PayloadInvalid if the slot is invalid
+pub async fn slot_validation_error(
+  slot: &StationSchedule,
+) -> Result<Option<String>, mongodb::error::Error> {
+  let others = StationSchedule::list_for_station(&slot.station_id).await?;
+
+  if let Some(message) = slot.validation_error(&others) {
+    return Ok(Some(message));
+  }
+
+  if let StationScheduleSource::Playlist {
+    playlist_id,
+    file_ids,
+  } = &slot.source
+  {
+    if let Some(playlist_id) = playlist_id {
+      let filter = doc! {
+        Playlist::KEY_ID: playlist_id,
+        Playlist::KEY_STATION_ID: &slot.station_id,
+      };
+
+      if !Playlist::exists(filter).await? {
+        return Ok(Some(format!(
+          "Playlist {playlist_id} not found in the station"
+        )));
+      }
+    }
+
+    let unique = file_ids.iter().collect::<HashSet<&String>>();
+    if unique.len() != file_ids.len() {
+      return Ok(Some("Slot playlist files cannot be repeated".into()));
+    }
+
+    if !file_ids.is_empty() {
+      let filter = doc! {
+        AudioFile::KEY_STATION_ID: &slot.station_id,
+        AudioFile::KEY_ID: { "$in": file_ids },
+      };
+
+      let count = AudioFile::cl().count_documents(filter, None).await?;
+      if count != file_ids.len() as u64 {
+        return Ok(Some(
+          "Some of the slot playlist files were not found in the station".into(),
+        ));
+      }
+    }
+  }
+
+  Ok(None)
+}
+
+pub mod get {
+
+  use schemars::JsonSchema;
+
+  use crate::qs::PaginationQs;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Serialize, Deserialize, Clone, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/schedule/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(Paged<StationSchedule>);
+
+  #[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/schedule/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    #[serde(flatten)]
+    pub page: PaginationQs,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    #[allow(unused)]
+    access_token_scope: AccessTokenScope,
+    query: Query,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("access: {0}")]
+    Access(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    QueryString(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> ApiError {
+      match e {
+        ParseError::Access(e) => e.into(),
+        ParseError::QueryString(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = mongodb::error::Error;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let query: Query = req.qs()?;
+
+      Ok(Self::Input {
+        access_token_scope,
+        station,
+        query,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        access_token_scope: _,
+        station,
+        query: Query {
+          page: PaginationQs { skip, limit },
+        },
+      } = input;
+
+      let filter = doc! { StationSchedule::KEY_STATION_ID: &station.id };
+      let sort = doc! { StationSchedule::KEY_CREATED_AT: 1 };
+      let page = StationSchedule::paged(filter, sort, skip, limit).await?;
+
+      Ok(Output(page))
+    }
+  }
+}
+
+pub mod post {
+
+  use constants::validate::VALIDATE_STATION_SCHEDULE_SLOTS_MAX_LEN;
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use schemars::JsonSchema;
+  use serde_util::DateTime;
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/schedule/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    pub time: StationScheduleTime,
+    pub source: StationScheduleSource,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/schedule/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(StationSchedule);
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => Self::from(e),
+        ParseError::Payload(e) => Self::from(e),
+      }
+    }
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum HandleError {
+    #[error("mongodb: {0}")]
+    Db(#[from] mongodb::error::Error),
+    #[error("too many slots")]
+    TooManySlots,
+    #[error("invalid slot: {0}")]
+    Invalid(String),
+  }
+
+  impl From<HandleError> for ApiError {
+    fn from(e: HandleError) -> Self {
+      match e {
+        HandleError::Db(e) => Self::from(e),
+        HandleError::TooManySlots => Self::PayloadInvalid(format!(
+          "A station cannot have more than {VALIDATE_STATION_SCHEDULE_SLOTS_MAX_LEN} schedule slots"
+        )),
+        HandleError::Invalid(message) => Self::PayloadInvalid(message),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = HandleError;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let payload: Payload = req.read_body_json(100_000).await?;
+
+      Ok(Self::Input { station, payload })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        payload: Payload { time, source },
+      } = input;
+
+      let filter = doc! { StationSchedule::KEY_STATION_ID: &station.id };
+      let count = StationSchedule::cl().count_documents(filter, None).await?;
+      if count >= VALIDATE_STATION_SCHEDULE_SLOTS_MAX_LEN as u64 {
+        return Err(HandleError::TooManySlots);
+      }
+
+      let now = DateTime::now();
+      let slot = StationSchedule {
+        id: StationSchedule::uid(),
+        station_id: station.id,
+        time,
+        source,
+        created_at: now,
+        updated_at: now,
+      };
+
+      if let Some(message) = slot_validation_error(&slot).await? {
+        return Err(HandleError::Invalid(message));
+      }
+
+      StationSchedule::insert(&slot).await?;
+
+      Ok(Output(slot))
+    }
+  }
+}
@@ -2,7 +2,8 @@ use crate::json::JsonHandler;
 use crate::request_ext::{self, GetAccessTokenScopeError};
 
 use async_trait::async_trait;
-use db::stream_connection::stats::Stats;
+use db::stream_connection::rejection::StreamConnectionRejection;
+use db::stream_connection::stats::{RejectionStats, Stats};
 // use db::stream_connection::StreamConnection;
 use db::stream_connection::index::MemIndex;
 use mongodb::bson::doc;
@@ -35,6 +36,7 @@ pub mod get {
   #[macros::schema_ts_export]
   pub struct Output {
     pub stats: Stats,
+    pub rejections: RejectionStats,
   }
 
   #[async_trait]
@@ -58,12 +60,17 @@ pub mod get {
       //let filter = doc! { StreamConnection::KEY_STATION_ID: station_id };
       //let stats = Stats::get_for_filter(filter).await?;
 
+      let rejections = StreamConnectionRejection::get_stats(
+        doc! { StreamConnectionRejection::KEY_STATION_ID: &station_id },
+      )
+      .await?;
+
       let stats = self
         .index
         .get_stats(StationQuery::one(station_id), AllFilter)
         .await;
 
-      Ok(Output { stats })
+      Ok(Output { stats, rejections })
     }
   }
 }
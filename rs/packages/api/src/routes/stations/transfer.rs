@@ -4,6 +4,7 @@ use crate::request_ext::{self, GetAccessTokenScopeError};
 use crate::error::ApiError;
 use crate::request_ext::AccessTokenScope;
 use async_trait::async_trait;
+use db::audit_log::{AuditLog, AuditLogAction, AuditLogChange, AuditLogOrigin, AuditLogTarget};
 use db::station::Station;
 use db::Model;
 use db::{account::Account, run_transaction};
@@ -38,6 +39,7 @@ pub mod post {
   #[derive(Debug, Clone)]
   pub struct Input {
     access_token_scope: AccessTokenScope,
+    audit_log_origin: AuditLogOrigin,
     station_id: String,
     payload: Payload,
   }
@@ -113,12 +115,14 @@ pub mod post {
 
     async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
       let station_id = req.param("station").unwrap().to_string();
-      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+      let (access_token_scope, audit_log_origin) =
+        request_ext::get_audited_access_token_scope(&req).await?;
       let payload: Payload = req.read_body_json(1_000).await?;
 
       Ok(Input {
         station_id,
         access_token_scope,
+        audit_log_origin,
         payload,
       })
     }
@@ -128,6 +132,7 @@ pub mod post {
 
       let Input {
         access_token_scope,
+        audit_log_origin,
         station_id,
         payload,
       } = input;
@@ -174,6 +179,20 @@ pub mod post {
         tx_try!(Account::replace_with_session(&source_account.id, &source_account, &mut session).await);
         tx_try!(Account::replace_with_session(&target_account.id, &target_account, &mut session).await);
 
+        let audit_log = AuditLog::new(
+          audit_log_origin.clone(),
+          vec![source_account.id.clone(), target_account.id.clone()],
+          AuditLogAction::StationTransfer,
+          AuditLogTarget::Station { station_id: station.id.clone() },
+          vec![AuditLogChange {
+            field: Station::KEY_ACCOUNT_ID.to_string(),
+            before: source_account.id.clone().into(),
+            after: target_account.id.clone().into(),
+          }],
+        );
+
+        tx_try!(AuditLog::insert_with_session(&audit_log, &mut session).await);
+
         station
       });
 
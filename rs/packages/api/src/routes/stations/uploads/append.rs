@@ -0,0 +1,108 @@
+pub mod post {
+  use crate::error::ApiError;
+  use crate::json::JsonHandler;
+  use crate::request_ext::{self, GetAccessTokenScopeError};
+
+  use async_trait::async_trait;
+  use db::resumable_upload::ResumableUpload;
+  use db::station::Station;
+  use prex::Request;
+  use schemars::JsonSchema;
+  use serde::{Deserialize, Serialize};
+  use ts_rs::TS;
+  use upload::resumable::ResumableUploadError;
+
+  use super::super::get_station_upload;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/uploads/[upload]/append/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Query {
+    /// position of the first byte of the body in the file, must be the current offset of the upload
+    #[serde(serialize_with = "serde_util::as_f64::serialize")]
+    #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+    pub offset: u64,
+  }
+
+  #[derive(Debug)]
+  pub struct Input {
+    station: Station,
+    upload_id: String,
+    offset: u64,
+    body: hyper::Body,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/uploads/[upload]/append/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    upload: ResumableUpload,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("querystring: {0}")]
+    Query(#[from] serde_qs::Error),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => e.into(),
+        ParseError::Query(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = ResumableUploadError<hyper::Error>;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let upload_id = req.param("upload").unwrap().to_string();
+      let Query { offset } = req.qs()?;
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let body = req.into_body();
+
+      Ok(Self::Input {
+        station,
+        upload_id,
+        offset,
+        body,
+      })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Input {
+        station,
+        upload_id,
+        offset,
+        body,
+      } = input;
+
+      let upload = get_station_upload(&station.id, &upload_id).await?;
+      let upload = upload::resumable::append(&upload.id, offset, body).await?;
+
+      Ok(Output { upload })
+    }
+  }
+}
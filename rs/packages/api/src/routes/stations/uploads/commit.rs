@@ -0,0 +1,66 @@
+pub mod post {
+  use std::convert::Infallible;
+
+  use crate::json::JsonHandler;
+  use crate::request_ext::{self, GetAccessTokenScopeError};
+
+  use async_trait::async_trait;
+  use db::audio_file::AudioFile;
+  use db::station::Station;
+  use prex::Request;
+  use schemars::JsonSchema;
+  use serde::{Deserialize, Serialize};
+  use ts_rs::TS;
+  use upload::resumable::ResumableUploadError;
+
+  use super::super::get_station_upload;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {
+    pub deployment_id: String,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    upload_id: String,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/uploads/[upload]/commit/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    file: AudioFile,
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = ResumableUploadError<Infallible>;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+      let upload_id = req.param("upload").unwrap().to_string();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      Ok(Self::Input { station, upload_id })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Input { station, upload_id } = input;
+
+      let upload = get_station_upload(&station.id, &upload_id).await?;
+      let file = upload::resumable::commit(self.deployment_id.clone(), &upload.id).await?;
+
+      Ok(Output { file })
+    }
+  }
+}
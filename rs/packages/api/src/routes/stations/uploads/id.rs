@@ -0,0 +1,107 @@
+use std::convert::Infallible;
+
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::resumable_upload::ResumableUpload;
+use db::station::Station;
+use prex::Request;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use upload::resumable::ResumableUploadError;
+
+use super::get_station_upload;
+
+#[derive(Debug, Clone)]
+pub struct Input {
+  station: Station,
+  upload_id: String,
+}
+
+async fn parse_input(req: &Request) -> Result<Input, GetAccessTokenScopeError> {
+  let station_id = req.param("station").unwrap();
+  let upload_id = req.param("upload").unwrap();
+
+  let access_token_scope = request_ext::get_access_token_scope(req).await?;
+
+  let station = access_token_scope.grant_station_scope(station_id).await?;
+
+  Ok(Input {
+    station,
+    upload_id: upload_id.to_string(),
+  })
+}
+
+pub mod get {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/uploads/[upload]/GET/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    /// upload.offset is where the next append must start
+    upload: ResumableUpload,
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = ResumableUploadError<Infallible>;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      parse_input(&req).await
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Input { station, upload_id } = input;
+      let upload = get_station_upload(&station.id, &upload_id).await?;
+      Ok(Output { upload })
+    }
+  }
+}
+
+pub mod delete {
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/uploads/[upload]/DELETE/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output(ResumableUpload);
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = GetAccessTokenScopeError;
+    type HandleError = ResumableUploadError<Infallible>;
+
+    async fn parse(&self, req: Request) -> Result<Self::Input, Self::ParseError> {
+      parse_input(&req).await
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Input { station, upload_id } = input;
+      let upload = get_station_upload(&station.id, &upload_id).await?;
+      upload::resumable::abort(&upload.id).await?;
+      Ok(Output(upload))
+    }
+  }
+}
@@ -0,0 +1,137 @@
+pub mod append;
+pub mod commit;
+pub mod id;
+
+use crate::error::ApiError;
+use crate::json::JsonHandler;
+use crate::request_ext::{self, GetAccessTokenScopeError};
+
+use async_trait::async_trait;
+use db::audio_file::DuplicateMode;
+use db::resumable_upload::ResumableUpload;
+use db::station::Station;
+use db::Model;
+use mongodb::bson::doc;
+use prex::Request;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use upload::resumable::ResumableUploadError;
+
+/// gets an upload of the station, the uploads of other stations are reported as not found
+pub async fn get_station_upload<E>(
+  station_id: &str,
+  upload_id: &str,
+) -> Result<ResumableUpload, ResumableUploadError<E>> {
+  let filter = doc! {
+    ResumableUpload::KEY_ID: upload_id,
+    ResumableUpload::KEY_STATION_ID: station_id,
+  };
+
+  match ResumableUpload::get(filter).await? {
+    Some(upload) => Ok(upload),
+    None => Err(ResumableUploadError::NotFound(upload_id.to_string())),
+  }
+}
+
+pub mod post {
+
+  use std::convert::Infallible;
+
+  use modify::Modify;
+  use prex::request::ReadBodyJsonError;
+  use schemars::JsonSchema;
+  use validator::Validate;
+
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub struct Endpoint {}
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/uploads/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Payload {
+    #[modify(trim)]
+    #[validate(length(min = 1, message = "Filename is required"))]
+    pub filename: String,
+    /// total size of the file in bytes
+    #[serde(serialize_with = "serde_util::as_f64::serialize")]
+    #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+    pub len: u64,
+    /// what to do if a file with the same content already exists in the station
+    #[serde(default)]
+    #[ts(optional)]
+    pub on_duplicate: DuplicateMode,
+  }
+
+  #[derive(Debug, Clone)]
+  pub struct Input {
+    station: Station,
+    payload: Payload,
+  }
+
+  #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+  #[ts(
+    export,
+    export_to = "../../../defs/api/stations/[station]/uploads/POST/"
+  )]
+  #[macros::schema_ts_export]
+  pub struct Output {
+    upload: ResumableUpload,
+  }
+
+  #[derive(Debug, thiserror::Error)]
+  pub enum ParseError {
+    #[error("token: {0}")]
+    Token(#[from] GetAccessTokenScopeError),
+    #[error("payload: {0}")]
+    Payload(#[from] ReadBodyJsonError),
+  }
+
+  impl From<ParseError> for ApiError {
+    fn from(e: ParseError) -> Self {
+      match e {
+        ParseError::Token(e) => e.into(),
+        ParseError::Payload(e) => e.into(),
+      }
+    }
+  }
+
+  #[async_trait]
+  impl JsonHandler for Endpoint {
+    type Input = Input;
+    type Output = Output;
+    type ParseError = ParseError;
+    type HandleError = ResumableUploadError<Infallible>;
+
+    async fn parse(&self, mut req: Request) -> Result<Self::Input, Self::ParseError> {
+      let station_id = req.param("station").unwrap();
+
+      let access_token_scope = request_ext::get_access_token_scope(&req).await?;
+
+      let station = access_token_scope.grant_station_scope(station_id).await?;
+
+      let payload: Payload = req.read_body_json(10_000).await?;
+
+      Ok(Self::Input { station, payload })
+    }
+
+    async fn perform(&self, input: Self::Input) -> Result<Self::Output, Self::HandleError> {
+      let Self::Input {
+        station,
+        payload: Payload {
+          filename,
+          len,
+          on_duplicate,
+        },
+      } = input;
+
+      let upload = upload::resumable::create(&station.id, filename, len, on_duplicate).await?;
+
+      Ok(Output { upload })
+    }
+  }
+}
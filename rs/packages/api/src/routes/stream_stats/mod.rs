@@ -3,7 +3,8 @@ use crate::request_ext::{self, GetAccessTokenScopeError};
 
 use async_trait::async_trait;
 use db::stream_connection::index::{AllFilter, MemIndex};
-use db::stream_connection::stats::Stats;
+use db::stream_connection::rejection::StreamConnectionRejection;
+use db::stream_connection::stats::{RejectionStats, Stats};
 use mongodb::bson::doc;
 use prex::Request;
 use serde::{Deserialize, Serialize};
@@ -29,6 +30,7 @@ pub mod get {
   #[macros::schema_ts_export]
   pub struct Output {
     pub stats: Stats,
+    pub rejections: RejectionStats,
   }
 
   #[async_trait]
@@ -49,8 +51,9 @@ pub mod get {
     async fn perform(&self, _input: Self::Input) -> Result<Self::Output, Self::HandleError> {
       // let filter = doc! {};
       // let stats = Stats::get_for_filter(filter).await?;
+      let rejections = StreamConnectionRejection::get_stats(doc! {}).await?;
       let stats = self.index.get_stats(StationQuery::all(), AllFilter).await;
-      Ok(Output { stats })
+      Ok(Output { stats, rejections })
     }
   }
 }
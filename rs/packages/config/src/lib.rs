@@ -67,6 +67,10 @@ pub struct Config {
   #[config(nested)]
   #[garde(dive)]
   pub payments: Payments,
+
+  #[config(nested)]
+  #[garde(dive)]
+  pub relay: Option<Relay>,
 }
 
 impl Config {
@@ -113,6 +117,11 @@ pub struct Stream {
   #[config(parse_env = parse_addrs)]
   #[garde(length(min = 1))]
   pub addrs: Vec<SocketAddr>,
+
+  /// path to an mp3 file served to new listeners when the account is at its listeners limit
+  /// and the plan listeners limit policy is capacity_message
+  #[garde(skip)]
+  pub capacity_message_file: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, MetreConfig, garde::Validate)]
@@ -176,6 +185,17 @@ pub struct Payments {
   pub access_token: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, MetreConfig, garde::Validate)]
+#[serde(deny_unknown_fields)]
+#[config(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub struct Relay {
+  /// secret shared by all the deployments of the cluster
+  /// internal relay requests between deployments are signed with it
+  #[garde(length(min = 32))]
+  pub secret: String,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum LoadConfigError {
   #[error(transparent)]
@@ -1,4 +1,5 @@
 use hex::ToHex;
+use hmac::{Hmac, Mac};
 use sha2::{Digest, Sha256};
 
 pub const COST: u32 = 8;
@@ -18,3 +19,27 @@ pub fn sha256(src: impl AsRef<[u8]>) -> String {
   let hex_string: String = ToHex::encode_hex(&hex);
   hex_string
 }
+
+/// hex encoded HMAC-SHA256 of `message` with `key`
+pub fn hmac_sha256(key: impl AsRef<[u8]>, message: impl AsRef<[u8]>) -> String {
+  let mut mac = Hmac::<Sha256>::new_from_slice(key.as_ref()).expect("hmac key");
+  mac.update(message.as_ref());
+  let bytes = mac.finalize().into_bytes();
+  ToHex::encode_hex(&bytes)
+}
+
+/// checks a hex encoded HMAC-SHA256 in constant time
+pub fn verify_hmac_sha256(
+  key: impl AsRef<[u8]>,
+  message: impl AsRef<[u8]>,
+  hex_signature: impl AsRef<str>,
+) -> bool {
+  let signature = match hex::decode(hex_signature.as_ref()) {
+    Ok(signature) => signature,
+    Err(_) => return false,
+  };
+
+  let mut mac = Hmac::<Sha256>::new_from_slice(key.as_ref()).expect("hmac key");
+  mac.update(message.as_ref());
+  mac.verify_slice(&signature).is_ok()
+}
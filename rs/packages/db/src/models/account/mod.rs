@@ -1,10 +1,14 @@
 use crate::audio_file::AudioFile;
+use crate::event::{Event, QuotaThreshold};
+use crate::live_recording::LiveRecording;
+use crate::plan::{Plan, TransferOveragePolicy};
 use crate::station::Station;
 use crate::stream_connection::lite::StreamConnectionLite;
 use crate::Model;
 use crate::{metadata::Metadata, PublicScope};
 use constants::validate::*;
 use mongodb::bson::{doc, Bson};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
 use mongodb::ClientSession;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -28,6 +32,16 @@ pub struct Account {
   pub payment_method_id: Option<String>,
   pub name: String,
   pub limits: Limits,
+  /// start of the current billing period, the transfer used counter is reset
+  /// and archived as a BillingPeriod when the period rolls over
+  /// None for accounts created before billing periods existed
+  #[serde(default)]
+  pub billing_period_start: Option<DateTime>,
+  /// day of the month the billing periods of the account start, the day of its first period
+  /// the periods of the months that are shorter than this day start at their last day
+  /// None to use the day of billing_period_start
+  #[serde(default)]
+  pub billing_period_anchor_day: Option<u8>,
   pub user_metadata: Metadata,
   pub system_metadata: Metadata,
   pub created_at: DateTime,
@@ -45,6 +59,8 @@ pub struct UserPublicAccount {
   pub payment_method_id: Option<String>,
   pub name: String,
   pub limits: Limits,
+  #[serde(default)]
+  pub billing_period_start: Option<DateTime>,
   pub created_at: DateTime,
   pub updated_at: DateTime,
   pub user_metadata: Metadata,
@@ -88,20 +104,83 @@ pub struct AccountPatch {
 }
 
 impl Account {
+  /// increments the used transfer of the account
+  /// returns the updated account document or None if the account does not exist
   pub async fn increment_used_transfer(
     id: &str,
     size: usize,
-  ) -> Result<mongodb::results::UpdateResult, mongodb::error::Error> {
+  ) -> Result<Option<Account>, mongodb::error::Error> {
     const KEY: &str = crate::key!(Account::KEY_LIMITS, Limits::KEY_TRANSFER, Limit::KEY_USED);
-    Self::update_by_id(id, doc! { "$inc": { KEY: size as f64 } }).await
+    let filter = doc! { Account::KEY_ID: id };
+    let update = doc! { "$inc": { KEY: size as f64 } };
+    let options = FindOneAndUpdateOptions::builder()
+      .return_document(ReturnDocument::After)
+      .build();
+    let account = Self::cl()
+      .find_one_and_update(filter, update, options)
+      .await?;
+
+    if let Some(account) = &account {
+      let before = account.limits.transfer.used.saturating_sub(size as u64);
+      account.dispatch_crossed_threshold(LimitKind::Transfer, before);
+    }
+
+    Ok(account)
   }
 
-  pub async fn increment_used_listeners(
-    id: &str,
-  ) -> Result<mongodb::results::UpdateResult, mongodb::error::Error> {
-    const KEY: &str = crate::key!(Account::KEY_LIMITS, Limits::KEY_LISTENERS, Limit::KEY_USED);
-    let update = doc! { "$inc": { KEY: 1.0_f64 } };
-    Self::update_by_id(id, update).await
+  /// dispatches a quota.threshold event if the used amount of the limit crossed a threshold
+  /// since it was `before`
+  pub fn dispatch_crossed_threshold(&self, kind: LimitKind, before: u64) {
+    let limit = match kind {
+      LimitKind::Transfer => &self.limits.transfer,
+      LimitKind::Storage => &self.limits.storage,
+    };
+
+    if let Some(percent) = limit.crossed_threshold(before) {
+      Event::dispatch_detached(QuotaThreshold {
+        account_id: self.id.clone(),
+        limit: kind,
+        percent,
+        used: limit.used,
+        total: limit.total,
+      });
+    }
+  }
+
+  /// returns true if the account used all the transfer of its current billing period
+  /// and the plan of the account does not allow transfer overage
+  pub async fn is_transfer_blocked(&self) -> Result<bool, mongodb::error::Error> {
+    if !self.limits.transfer.is_exhausted() {
+      return Ok(false);
+    }
+
+    let policy = match Plan::get_by_id(&self.plan_id).await? {
+      None => TransferOveragePolicy::default(),
+      Some(plan) => plan.transfer_overage_policy,
+    };
+
+    Ok(policy == TransferOveragePolicy::Block)
+  }
+
+  /// increments the used listeners of the account only if it is below the listeners limit
+  /// the check and the increment are done in one atomic operation
+  /// so it is consistent across deployments
+  /// returns true if the listener slot was acquired
+  pub async fn try_increment_used_listeners(id: &str) -> Result<bool, mongodb::error::Error> {
+    const KEY_USED: &str = crate::key!(Account::KEY_LIMITS, Limits::KEY_LISTENERS, Limit::KEY_USED);
+    const KEY_TOTAL: &str =
+      crate::key!(Account::KEY_LIMITS, Limits::KEY_LISTENERS, Limit::KEY_TOTAL);
+
+    let filter = doc! {
+      Account::KEY_ID: id,
+      "$expr": { "$lt": [ format!("${KEY_USED}"), format!("${KEY_TOTAL}") ] },
+    };
+
+    let update = doc! { "$inc": { KEY_USED: 1.0_f64 } };
+
+    let r = Self::cl().update_one(filter, update, None).await?;
+
+    Ok(r.matched_count == 1)
   }
 
   pub async fn decrement_used_listeners(
@@ -121,6 +200,7 @@ impl From<Account> for UserPublicAccount {
       payment_method_id: account.payment_method_id,
       name: account.name,
       limits: account.limits,
+      billing_period_start: account.billing_period_start,
       created_at: account.created_at,
       updated_at: account.updated_at,
       user_metadata: account.user_metadata,
@@ -173,10 +253,38 @@ pub struct Limit {
   pub total: u64,
 }
 
+/// percentages of a limit that dispatch a quota.threshold event when the used amount reaches them
+pub const QUOTA_THRESHOLDS: [u8; 2] = [80, 100];
+
+/// the limits of an account that dispatch quota.threshold events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKind {
+  Transfer,
+  Storage,
+}
+
 impl Limit {
   pub fn avail(&self) -> u64 {
     self.total.saturating_sub(self.used)
   }
+
+  pub fn is_exhausted(&self) -> bool {
+    self.used >= self.total
+  }
+
+  /// the highest of QUOTA_THRESHOLDS reached when the used amount grew from `before` to the current value
+  pub fn crossed_threshold(&self, before: u64) -> Option<u8> {
+    if self.total == 0 {
+      return None;
+    }
+
+    QUOTA_THRESHOLDS.iter().rev().copied().find(|percent| {
+      let at = self.total as u128 * *percent as u128 / 100;
+      (before as u128) < at && self.used as u128 >= at
+    })
+  }
 }
 
 pub async fn recalculate_used_listeners_quota(
@@ -249,6 +357,17 @@ pub async fn recalculate_storage_quota(
     }
   }
 
+  let mut recordings = LiveRecording::cl()
+    .find_with_session(None, None, session)
+    .await?;
+
+  while let Some(recording) = recordings.next(session).await.transpose()? {
+    let account_id = station_account_map.get(&recording.station_id);
+    if let Some(account_id) = account_id {
+      *account_used_map.entry(account_id.to_string()).or_insert(0) += recording.file.len;
+    }
+  }
+
   const KEY_LIMITS_STORAGE_USED: &str =
     crate::key!(Account::KEY_LIMITS, Limits::KEY_STORAGE, Limit::KEY_USED);
 
@@ -279,4 +398,15 @@ mod test {
   fn keys_match() {
     assert_eq!(crate::KEY_ID, Account::KEY_ID);
   }
+
+  #[test]
+  fn limit_crossed_threshold() {
+    let limit = |used| Limit { used, total: 1000 };
+    assert_eq!(limit(500).crossed_threshold(0), None);
+    assert_eq!(limit(800).crossed_threshold(799), Some(80));
+    assert_eq!(limit(900).crossed_threshold(800), None);
+    assert_eq!(limit(1000).crossed_threshold(999), Some(100));
+    assert_eq!(limit(1200).crossed_threshold(100), Some(100));
+    assert_eq!(Limit { used: 10, total: 0 }.crossed_threshold(0), None);
+  }
 }
@@ -2,6 +2,7 @@ use crate::{storage_db, Model};
 use async_stream::try_stream;
 use bytes::Bytes;
 use futures_util::stream::Stream;
+use futures_util::TryStreamExt;
 use mongodb::results::DeleteResult;
 use mongodb::ClientSession;
 use mongodb::{bson::doc, options::IndexOptions, IndexModel};
@@ -65,11 +66,27 @@ impl AudioChunk {
   pub fn stream_from(
     file_id: &str,
     skip: f64,
+  ) -> impl Stream<Item = Result<Bytes, mongodb::error::Error>> + Send + 'static {
+    Self::stream_range(file_id, skip, None)
+  }
+
+  /// streams the chunks of a file from index `skip` up to index `end` (exclusive)
+  /// or up to the last chunk if `end` is None
+  pub fn stream_range(
+    file_id: &str,
+    skip: f64,
+    end: Option<f64>,
   ) -> impl Stream<Item = Result<Bytes, mongodb::error::Error>> + Send + 'static {
     let file_id = file_id.to_string();
     try_stream! {
       let mut i = skip;
       loop {
+        if let Some(end) = end {
+          if i >= end {
+            break;
+          }
+        }
+
         let filter = doc!{ Self::KEY_AUDIO_FILE_ID: &file_id, Self::KEY_I: i };
         let item = match Self::get(filter).await? {
           Some(item) => item,
@@ -81,6 +98,29 @@ impl AudioChunk {
       };
     }
   }
+
+  /// reads the chunks of a file from index `start` up to index `end` (exclusive) into one buffer
+  pub async fn read_range(
+    file_id: &str,
+    start: f64,
+    end: f64,
+  ) -> Result<Bytes, mongodb::error::Error> {
+    let filter = doc! {
+      Self::KEY_AUDIO_FILE_ID: file_id,
+      Self::KEY_I: { "$gte": start, "$lt": end },
+    };
+
+    let sort = doc! { Self::KEY_I: 1 };
+    let options = mongodb::options::FindOptions::builder().sort(sort).build();
+
+    let mut cursor = Self::cl().find(filter, options).await?;
+    let mut buf = bytes::BytesMut::new();
+    while let Some(item) = cursor.try_next().await? {
+      buf.extend_from_slice(&item.data);
+    }
+
+    Ok(buf.freeze())
+  }
 }
 
 impl Model for AudioChunk {
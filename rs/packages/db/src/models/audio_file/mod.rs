@@ -1,8 +1,10 @@
 use crate::{
   account::{Account, Limit, Limits},
   audio_chunk::AudioChunk,
+  playlist_item::PlaylistItem,
   run_transaction, station, Model,
 };
+use futures_util::TryStreamExt;
 use mongodb::{
   bson::{doc, Document},
   options::FindOneOptions,
@@ -11,7 +13,11 @@ use mongodb::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_util::DateTime;
+use std::collections::{BTreeMap, HashMap};
 use ts_rs::TS;
+use validator::Validate;
+
+use constants::validate::*;
 
 crate::register!(AudioFile);
 
@@ -22,8 +28,14 @@ pub struct AudioFile {
   #[serde(rename = "_id")]
   pub id: String,
   pub station_id: String,
+  /// hash of the audio as it is stored
   pub sha256: String,
 
+  /// hash of the uploaded bytes before transcoding
+  /// None for files uploaded before it was recorded
+  #[serde(default)]
+  pub source_sha256: Option<String>,
+
   #[serde(serialize_with = "serde_util::as_f64::serialize")]
   #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
   pub len: u64,
@@ -48,11 +60,206 @@ pub struct AudioFile {
 
   pub metadata: Metadata,
 
+  /// loudness and silence of the audio as it is stored
+  /// None for files uploaded before the analysis or if the analysis failed
+  #[serde(default)]
+  pub analysis: Option<AudioFileAnalysis>,
+
+  /// the station audio processing settings applied to this file
+  #[serde(default)]
+  pub processing: AudioProcessing,
+
+  /// the live media session this file was recorded from, None for uploaded files
+  #[serde(default)]
+  pub media_session_id: Option<String>,
+
   pub order: f64,
 
   pub created_at: DateTime,
 }
 
+/// result of the analysis pass of an audio file
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct AudioFileAnalysis {
+  /// EBU R128 integrated loudness in LUFS
+  pub integrated_lufs: f64,
+  /// true peak in dBTP
+  pub true_peak_dbtp: f64,
+  /// duration of the silence at the start of the file
+  pub leading_silence_ms: f64,
+  /// duration of the silence at the end of the file
+  pub trailing_silence_ms: f64,
+}
+
+impl AudioFileAnalysis {
+  /// gain to apply to reach `target_lufs` without exceeding AUDIO_FILE_MAX_TRUE_PEAK_DBTP
+  /// returns 0 if the file is already within AUDIO_FILE_LOUDNESS_TOLERANCE_DB of the target
+  pub fn gain_db(&self, target_lufs: f64) -> f64 {
+    let gain = target_lufs - self.integrated_lufs;
+    let gain = gain.min(constants::AUDIO_FILE_MAX_TRUE_PEAK_DBTP - self.true_peak_dbtp);
+    if gain.abs() < constants::AUDIO_FILE_LOUDNESS_TOLERANCE_DB {
+      0.0
+    } else {
+      gain
+    }
+  }
+
+  /// durations to trim from the start and the end of the file
+  /// silences shorter than AUDIO_FILE_SILENCE_MIN_DURATION_MS are kept
+  pub fn trim_ms(&self) -> (f64, f64) {
+    let min = constants::AUDIO_FILE_SILENCE_MIN_DURATION_MS as f64;
+    let start = if self.leading_silence_ms >= min {
+      self.leading_silence_ms
+    } else {
+      0.0
+    };
+
+    let end = if self.trailing_silence_ms >= min {
+      self.trailing_silence_ms
+    } else {
+      0.0
+    };
+
+    (start, end)
+  }
+}
+
+/// loudness normalization and silence trimming applied to the files of a station
+#[derive(
+  Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, TS, JsonSchema, Validate,
+)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct AudioProcessing {
+  /// integrated loudness in LUFS the files are normalized to, None to keep their loudness
+  #[validate(range(
+    min = "VALIDATE_STATION_TARGET_LOUDNESS_MIN",
+    max = "VALIDATE_STATION_TARGET_LOUDNESS_MAX",
+    message = "Target loudness is out of range"
+  ))]
+  pub target_lufs: Option<f64>,
+  /// remove the silence at the start and at the end of the files
+  pub trim_silence: bool,
+}
+
+impl AudioProcessing {
+  /// returns true if the files must be re-encoded with these settings
+  pub fn is_enabled(&self) -> bool {
+    self.target_lufs.is_some() || self.trim_silence
+  }
+}
+
+/// what to do when an uploaded file has the same content as a file of the station
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateMode {
+  /// fail the upload
+  Reject,
+  /// discard the upload and return the existing file
+  Link,
+  /// store the upload as a new file
+  #[default]
+  Force,
+}
+
+/// a set of files of a station with the same content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "snake_case")]
+pub struct AudioFileDuplicateSet {
+  /// the files of the set, the oldest first
+  pub file_ids: Vec<String>,
+  /// bytes used by all the files of the set except the oldest one
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub wasted_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[macros::keys]
+pub struct DuplicateCandidateDocument {
+  #[serde(rename = "_id")]
+  pub id: String,
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub len: u64,
+  pub sha256: String,
+  #[serde(default)]
+  pub source_sha256: Option<String>,
+  pub created_at: DateTime,
+}
+
+impl DuplicateCandidateDocument {
+  pub fn projection() -> Document {
+    doc! {
+      crate::KEY_ID: 1,
+      Self::KEY_LEN: 1,
+      Self::KEY_SHA256: 1,
+      Self::KEY_SOURCE_SHA256: 1,
+      Self::KEY_CREATED_AT: 1,
+    }
+  }
+}
+
+/// groups the files that share the hash of their stored audio or of their source
+/// files without duplicates are not included in the result
+pub fn duplicate_sets(files: &[DuplicateCandidateDocument]) -> Vec<AudioFileDuplicateSet> {
+  let mut parents = (0..files.len()).collect::<Vec<usize>>();
+
+  fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+      parents[i] = parents[parents[i]];
+      i = parents[i];
+    }
+    i
+  }
+
+  let mut by_hash = HashMap::<&str, usize>::new();
+  for (i, file) in files.iter().enumerate() {
+    let hashes = std::iter::once(file.sha256.as_str()).chain(file.source_sha256.as_deref());
+    for hash in hashes {
+      match by_hash.get(hash) {
+        None => {
+          by_hash.insert(hash, i);
+        }
+        Some(other) => {
+          let a = root(&mut parents, i);
+          let b = root(&mut parents, *other);
+          parents[a] = b;
+        }
+      }
+    }
+  }
+
+  let mut groups = BTreeMap::<usize, Vec<&DuplicateCandidateDocument>>::new();
+  for (i, file) in files.iter().enumerate() {
+    let r = root(&mut parents, i);
+    groups.entry(r).or_default().push(file);
+  }
+
+  let mut sets = groups
+    .into_values()
+    .filter(|group| group.len() > 1)
+    .map(|mut group| {
+      group.sort_by(|a, b| {
+        (a.created_at.timestamp_millis(), &a.id).cmp(&(b.created_at.timestamp_millis(), &b.id))
+      });
+      AudioFileDuplicateSet {
+        wasted_bytes: group[1..].iter().map(|file| file.len).sum(),
+        file_ids: group.into_iter().map(|file| file.id.clone()).collect(),
+      }
+    })
+    .collect::<Vec<_>>();
+
+  sets.sort_by_key(|set| std::cmp::Reverse(set.wasted_bytes));
+  sets
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[macros::keys]
 #[serde(rename_all = "snake_case")]
@@ -67,6 +274,55 @@ impl OrderDocument {
 }
 
 impl AudioFile {
+  /// returns a file of the station other than `file_id` with the same stored or source content
+  pub async fn find_duplicate(
+    station_id: &str,
+    file_id: &str,
+    sha256: &str,
+    source_sha256: Option<&str>,
+    session: Option<&mut ClientSession>,
+  ) -> Result<Option<AudioFile>, mongodb::error::Error> {
+    let mut hashes = vec![doc! { Self::KEY_SHA256: sha256 }];
+    if let Some(source_sha256) = source_sha256 {
+      hashes.push(doc! { Self::KEY_SOURCE_SHA256: source_sha256 });
+    }
+
+    let filter = doc! {
+      Self::KEY_STATION_ID: station_id,
+      Self::KEY_ID: { "$ne": file_id },
+      "$or": hashes,
+    };
+
+    let sort = doc! { Self::KEY_CREATED_AT: 1 };
+    let options = FindOneOptions::builder().sort(sort).build();
+
+    match session {
+      None => Self::cl().find_one(filter, options).await,
+      Some(session) => {
+        Self::cl()
+          .find_one_with_session(filter, options, session)
+          .await
+      }
+    }
+  }
+
+  /// returns the sets of files of the station with the same content
+  pub async fn duplicate_sets(
+    station_id: &str,
+  ) -> Result<Vec<AudioFileDuplicateSet>, mongodb::error::Error> {
+    let filter = doc! { Self::KEY_STATION_ID: station_id };
+    let options = mongodb::options::FindOptions::builder()
+      .projection(DuplicateCandidateDocument::projection())
+      .build();
+    let files = Self::cl_as::<DuplicateCandidateDocument>()
+      .find(filter, options)
+      .await?
+      .try_collect::<Vec<_>>()
+      .await?;
+
+    Ok(duplicate_sets(&files))
+  }
+
   pub async fn next_max_order(
     station_id: &str,
     session: Option<&mut ClientSession>,
@@ -161,6 +417,9 @@ impl AudioFile {
     // delete file
     AudioFile::delete_by_id_with_session(&audio_file.id, session).await?;
 
+    // remove the file from the playlists
+    PlaylistItem::delete_by_file_id_with_session(&audio_file.id, session).await?;
+
     // update station
     const KEY: &str = crate::key!(Account::KEY_LIMITS, Limits::KEY_STORAGE, Limit::KEY_USED);
     let update = doc! { "$inc": { KEY: (audio_file.len as f64) * -1.0 } };
@@ -179,19 +438,28 @@ impl AudioFile {
     })
   }
 
+  /// the first file of the station playlist
+  /// if `file_ids` is not empty only those files are considered
   pub async fn playlist_first(
     station_id: &str,
+    file_ids: &[String],
   ) -> Result<Option<AudioFile>, mongodb::error::Error> {
-    let filter = doc! { Self::KEY_STATION_ID: station_id };
+    let mut filter = doc! { Self::KEY_STATION_ID: station_id };
+    if !file_ids.is_empty() {
+      filter.insert(Self::KEY_ID, doc! { "$in": file_ids });
+    }
     let sort = doc! { Self::KEY_ORDER: 1 };
     let options = FindOneOptions::builder().sort(sort).build();
     Self::cl().find_one(filter, options).await
   }
 
+  /// the file that follows `current_id` in the station playlist, wraps to the first file
+  /// if `file_ids` is not empty only those files are considered
   pub async fn playlist_next(
     station_id: &str,
     current_id: &str,
     current_order: f64,
+    file_ids: &[String],
   ) -> Result<Option<AudioFile>, mongodb::error::Error> {
     let filter = doc! { Self::KEY_ID: current_id, Self::KEY_STATION_ID: station_id };
     let order_projection = doc! { Self::KEY_ID: -1, Self::KEY_ORDER: 1 };
@@ -208,8 +476,11 @@ impl AudioFile {
       Some(document) => document.order,
     };
 
-    let filter =
+    let mut filter =
       doc! { Self::KEY_STATION_ID: station_id, Self::KEY_ORDER: { "$gt": current_order } };
+    if !file_ids.is_empty() {
+      filter.insert(Self::KEY_ID, doc! { "$in": file_ids });
+    }
     let sort = doc! { Self::KEY_ORDER: 1 };
 
     let options = FindOneOptions::builder().sort(sort).build();
@@ -217,7 +488,7 @@ impl AudioFile {
     let next = Self::cl().find_one(filter, options).await?;
 
     let next = match next {
-      None => return Self::playlist_first(station_id).await,
+      None => return Self::playlist_first(station_id, file_ids).await,
       Some(next) => next,
     };
 
@@ -238,7 +509,20 @@ impl Model for AudioFile {
       .keys(doc! { Self::KEY_STATION_ID: 1, Self::KEY_ORDER: 1 })
       .build();
 
-    vec![station_id, station_id_order]
+    let station_id_sha256 = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1, Self::KEY_SHA256: 1 })
+      .build();
+
+    let station_id_source_sha256 = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1, Self::KEY_SOURCE_SHA256: 1 })
+      .build();
+
+    vec![
+      station_id,
+      station_id_order,
+      station_id_sha256,
+      station_id_source_sha256,
+    ]
   }
 }
 
@@ -305,5 +589,101 @@ mod test {
   fn keys_match() {
     assert_eq!(crate::KEY_ID, AudioFile::KEY_ID);
     assert_eq!(AudioFile::KEY_ORDER, OrderDocument::KEY_ORDER);
+    assert_eq!(crate::KEY_ID, DuplicateCandidateDocument::KEY_ID);
+    assert_eq!(AudioFile::KEY_LEN, DuplicateCandidateDocument::KEY_LEN);
+    assert_eq!(
+      AudioFile::KEY_SHA256,
+      DuplicateCandidateDocument::KEY_SHA256
+    );
+    assert_eq!(
+      AudioFile::KEY_SOURCE_SHA256,
+      DuplicateCandidateDocument::KEY_SOURCE_SHA256
+    );
+    assert_eq!(
+      AudioFile::KEY_CREATED_AT,
+      DuplicateCandidateDocument::KEY_CREATED_AT
+    );
+  }
+
+  fn analysis(integrated_lufs: f64, true_peak_dbtp: f64) -> AudioFileAnalysis {
+    AudioFileAnalysis {
+      integrated_lufs,
+      true_peak_dbtp,
+      leading_silence_ms: 0.0,
+      trailing_silence_ms: 0.0,
+    }
+  }
+
+  #[test]
+  fn gain_reaches_target_loudness() {
+    assert_eq!(analysis(-20.0, -6.0).gain_db(-14.0), 5.0);
+    assert_eq!(analysis(-8.0, 0.0).gain_db(-14.0), -6.0);
+  }
+
+  #[test]
+  fn gain_is_limited_by_true_peak() {
+    assert_eq!(analysis(-20.0, -3.0).gain_db(-14.0), 2.0);
+  }
+
+  #[test]
+  fn gain_within_tolerance_is_zero() {
+    assert_eq!(analysis(-14.2, -6.0).gain_db(-14.0), 0.0);
+  }
+
+  #[test]
+  fn short_silences_are_not_trimmed() {
+    let mut analysis = analysis(-14.0, -1.0);
+    analysis.leading_silence_ms = 100.0;
+    analysis.trailing_silence_ms = 3_000.0;
+    assert_eq!(analysis.trim_ms(), (0.0, 3_000.0));
+  }
+
+  fn candidate(
+    id: &str,
+    len: u64,
+    sha256: &str,
+    source_sha256: Option<&str>,
+  ) -> DuplicateCandidateDocument {
+    DuplicateCandidateDocument {
+      id: id.into(),
+      len,
+      sha256: sha256.into(),
+      source_sha256: source_sha256.map(Into::into),
+      created_at: DateTime::from_bson(mongodb::bson::DateTime::from_millis(id.len() as i64)),
+    }
+  }
+
+  #[test]
+  fn duplicate_sets_group_by_any_hash() {
+    let files = vec![
+      candidate("a", 10, "x", Some("s1")),
+      candidate("bb", 20, "y", Some("s1")),
+      candidate("ccc", 30, "y", None),
+      candidate("dddd", 40, "z", Some("s2")),
+    ];
+
+    let sets = duplicate_sets(&files);
+    assert_eq!(
+      sets,
+      vec![AudioFileDuplicateSet {
+        file_ids: vec!["a".into(), "bb".into(), "ccc".into()],
+        wasted_bytes: 50,
+      }]
+    );
+  }
+
+  #[test]
+  fn duplicate_sets_are_sorted_by_wasted_bytes() {
+    let files = vec![
+      candidate("a", 10, "x", None),
+      candidate("bb", 10, "x", None),
+      candidate("ccc", 50, "y", None),
+      candidate("dddd", 50, "y", None),
+    ];
+
+    let sets = duplicate_sets(&files);
+    assert_eq!(sets.len(), 2);
+    assert_eq!(sets[0].wasted_bytes, 50);
+    assert_eq!(sets[1].wasted_bytes, 10);
   }
 }
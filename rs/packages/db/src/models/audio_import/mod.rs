@@ -0,0 +1,378 @@
+use crate::audio_file::DuplicateMode;
+use crate::Model;
+use mongodb::bson::{doc, Bson, SerializerOptions};
+use mongodb::IndexModel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+crate::register!(AudioImport);
+
+/// a background job that imports many audio files into a station
+///
+/// each item is uploaded with upload::upload_audio_file using its operation_id
+/// as the id of the AudioUploadOperation, so running a job again skips the items already imported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct AudioImport {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub station_id: String,
+  /// the deployment that is running the job
+  pub deployment_id: String,
+  pub source: AudioImportSource,
+  pub on_duplicate: DuplicateMode,
+  /// for archives the items are added as the entries are found
+  pub items: Vec<AudioImportItem>,
+  pub state: AudioImportState,
+  pub created_at: DateTime,
+  pub updated_at: DateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+#[macros::keys]
+pub enum AudioImportSource {
+  /// an archive uploaded as a resumable upload
+  #[serde(rename = "archive")]
+  Archive {
+    upload_id: String,
+    filename: String,
+    format: AudioImportArchiveFormat,
+  },
+  /// http(s) urls fetched by the server
+  #[serde(rename = "urls")]
+  Urls { urls: Vec<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+pub enum AudioImportArchiveFormat {
+  Zip,
+  Tar,
+  TarGz,
+}
+
+impl AudioImportArchiveFormat {
+  /// detects the format of an archive from its filename
+  pub fn from_filename(filename: &str) -> Option<Self> {
+    let lower = filename.to_lowercase();
+    if lower.ends_with(".zip") {
+      Some(Self::Zip)
+    } else if lower.ends_with(".tar") {
+      Some(Self::Tar)
+    } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+      Some(Self::TarGz)
+    } else {
+      None
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct AudioImportItem {
+  /// the url or the path of the entry in the archive
+  pub name: String,
+  /// id of the AudioUploadOperation of this item
+  pub operation_id: String,
+  pub state: AudioImportItemState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+#[macros::keys]
+pub enum AudioImportItemState {
+  #[serde(rename = "pending")]
+  Pending,
+  /// the item was stored as (or linked to) the audio file
+  #[serde(rename = "success")]
+  Success { audio_file_id: String },
+  /// the item was uploaded by a previous run of the job
+  #[serde(rename = "skipped")]
+  Skipped,
+  #[serde(rename = "error")]
+  Error { message: String },
+  #[serde(rename = "cancelled")]
+  Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+#[macros::keys]
+pub enum AudioImportState {
+  #[serde(rename = "pending")]
+  Pending,
+  #[serde(rename = "running")]
+  Running,
+  #[serde(rename = "finished")]
+  Finished { finished_at: DateTime },
+  /// the job could not read its source, the items imported before the failure are kept
+  #[serde(rename = "failed")]
+  Failed {
+    message: String,
+    failed_at: DateTime,
+  },
+  #[serde(rename = "cancelled")]
+  Cancelled { cancelled_at: DateTime },
+}
+
+impl AudioImportState {
+  pub fn is_active(&self) -> bool {
+    matches!(self, Self::Pending | Self::Running)
+  }
+}
+
+macro_rules! into_bson {
+  ($type:ty) => {
+    impl From<$type> for Bson {
+      fn from(value: $type) -> Self {
+        mongodb::bson::to_bson_with_options(
+          &value,
+          SerializerOptions::builder().human_readable(false).build(),
+        )
+        .expect(concat!("error convering ", stringify!($type), " to Bson"))
+      }
+    }
+  };
+}
+
+into_bson!(AudioImportItem);
+into_bson!(AudioImportItemState);
+into_bson!(AudioImportState);
+
+const KEY_STATE_KIND: &str =
+  const_str::concat!(AudioImport::KEY_STATE, ".", AudioImportState::KEY_ENUM_TAG);
+
+impl AudioImport {
+  /// sets the state of a job only if its current state is one of `from`
+  ///
+  /// `from` are KEY_ENUM_VARIANT_* constants of AudioImportState
+  pub async fn set_state_if(
+    id: &str,
+    from: &[&str],
+    state: AudioImportState,
+  ) -> Result<bool, mongodb::error::Error> {
+    let filter = doc! {
+      crate::KEY_ID: id,
+      KEY_STATE_KIND: { "$in": from },
+    };
+
+    let update = doc! {
+      "$set": {
+        Self::KEY_STATE: state,
+        Self::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    let r = Self::cl().update_one(filter, update, None).await?;
+    Ok(r.matched_count == 1)
+  }
+
+  pub async fn set_item_state(
+    id: &str,
+    index: usize,
+    state: AudioImportItemState,
+  ) -> Result<(), mongodb::error::Error> {
+    let key = format!(
+      "{}.{}.{}",
+      Self::KEY_ITEMS,
+      index,
+      AudioImportItem::KEY_STATE
+    );
+
+    let update = doc! {
+      "$set": {
+        key: state,
+        Self::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    Self::update_by_id(id, update).await?;
+    Ok(())
+  }
+
+  pub async fn push_item(id: &str, item: AudioImportItem) -> Result<(), mongodb::error::Error> {
+    let update = doc! {
+      "$push": { Self::KEY_ITEMS: item },
+      "$set": { Self::KEY_UPDATED_AT: DateTime::now() },
+    };
+
+    Self::update_by_id(id, update).await?;
+    Ok(())
+  }
+
+  /// moves the jobs that were active in `from_deployment_id` to `to_deployment_id`
+  /// returns the ids of the moved jobs
+  pub async fn take_over_active(
+    from_deployment_id: &str,
+    to_deployment_id: &str,
+  ) -> Result<Vec<String>, mongodb::error::Error> {
+    let filter = doc! {
+      Self::KEY_DEPLOYMENT_ID: from_deployment_id,
+      KEY_STATE_KIND: {
+        "$in": [
+          AudioImportState::KEY_ENUM_VARIANT_PENDING,
+          AudioImportState::KEY_ENUM_VARIANT_RUNNING,
+        ]
+      },
+    };
+
+    let ids: Vec<String> = Self::distinct_string(crate::KEY_ID, filter.clone()).await?;
+
+    let mut taken = vec![];
+    for id in ids {
+      // other deployments can be taking over the same jobs
+      let mut filter = filter.clone();
+      filter.insert(crate::KEY_ID, &id);
+
+      let update = doc! {
+        "$set": {
+          Self::KEY_DEPLOYMENT_ID: to_deployment_id,
+          Self::KEY_UPDATED_AT: DateTime::now(),
+        }
+      };
+
+      let r = Self::cl().update_one(filter, update, None).await?;
+      if r.matched_count == 1 {
+        taken.push(id);
+      }
+    }
+
+    Ok(taken)
+  }
+
+  /// moves the active jobs of deployments that are no longer active to `to_deployment_id`
+  /// returns the ids of the moved jobs
+  pub async fn take_over_orphans(
+    to_deployment_id: &str,
+  ) -> Result<Vec<String>, mongodb::error::Error> {
+    let filter = doc! {
+      KEY_STATE_KIND: {
+        "$in": [
+          AudioImportState::KEY_ENUM_VARIANT_PENDING,
+          AudioImportState::KEY_ENUM_VARIANT_RUNNING,
+        ]
+      },
+    };
+
+    let job_deployment_ids: Vec<String> =
+      Self::distinct_string(Self::KEY_DEPLOYMENT_ID, filter).await?;
+    if job_deployment_ids.is_empty() {
+      return Ok(vec![]);
+    }
+
+    let active_deployment_ids = crate::deployment::active_deployment_ids().await?;
+
+    let mut taken = vec![];
+    for deployment_id in job_deployment_ids {
+      if deployment_id == to_deployment_id || active_deployment_ids.contains(&deployment_id) {
+        continue;
+      }
+
+      let ids = Self::take_over_active(&deployment_id, to_deployment_id).await?;
+      taken.extend(ids);
+    }
+
+    Ok(taken)
+  }
+
+  /// marks the items that were not imported yet as cancelled
+  pub async fn cancel_pending_items(id: &str) -> Result<(), mongodb::error::Error> {
+    const KEY_ITEM_STATE: &str = const_str::concat!(
+      AudioImport::KEY_ITEMS,
+      ".$[item].",
+      AudioImportItem::KEY_STATE
+    );
+
+    const KEY_ITEM_STATE_KIND: &str = const_str::concat!(
+      "item.",
+      AudioImportItem::KEY_STATE,
+      ".",
+      AudioImportItemState::KEY_ENUM_TAG
+    );
+
+    let filter = doc! { crate::KEY_ID: id };
+    let update = doc! {
+      "$set": {
+        KEY_ITEM_STATE: AudioImportItemState::Cancelled,
+        Self::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    let options = mongodb::options::UpdateOptions::builder()
+      .array_filters(vec![
+        doc! { KEY_ITEM_STATE_KIND: AudioImportItemState::KEY_ENUM_VARIANT_PENDING },
+      ])
+      .build();
+
+    Self::cl().update_one(filter, update, options).await?;
+    Ok(())
+  }
+
+  pub async fn is_cancelled(id: &str) -> Result<bool, mongodb::error::Error> {
+    let filter = doc! {
+      crate::KEY_ID: id,
+      KEY_STATE_KIND: AudioImportState::KEY_ENUM_VARIANT_CANCELLED,
+    };
+
+    Self::exists(filter).await
+  }
+}
+
+impl Model for AudioImport {
+  const UID_LEN: usize = 12;
+  const CL_NAME: &'static str = "audio_imports";
+
+  fn indexes() -> Vec<IndexModel> {
+    let station_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1 })
+      .build();
+    let deployment_id_state = IndexModel::builder()
+      .keys(doc! { Self::KEY_DEPLOYMENT_ID: 1, KEY_STATE_KIND: 1 })
+      .build();
+    vec![station_id, deployment_id_state]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, AudioImport::KEY_ID);
+    assert_eq!(KEY_STATE_KIND, "state.kind");
+  }
+
+  #[test]
+  fn archive_format_from_filename() {
+    use AudioImportArchiveFormat::*;
+    assert_eq!(
+      AudioImportArchiveFormat::from_filename("Library.ZIP"),
+      Some(Zip)
+    );
+    assert_eq!(AudioImportArchiveFormat::from_filename("a.tar"), Some(Tar));
+    assert_eq!(
+      AudioImportArchiveFormat::from_filename("a.tar.gz"),
+      Some(TarGz)
+    );
+    assert_eq!(
+      AudioImportArchiveFormat::from_filename("a.tgz"),
+      Some(TarGz)
+    );
+    assert_eq!(AudioImportArchiveFormat::from_filename("a.rar"), None);
+  }
+}
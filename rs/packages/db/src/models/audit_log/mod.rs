@@ -0,0 +1,396 @@
+use std::net::IpAddr;
+
+use crate::access_token::Scope;
+use crate::Model;
+use mongodb::bson::{doc, Document};
+use mongodb::IndexModel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+crate::register!(AuditLog);
+
+/// an administrative or user action done through the api, with who did it and what it changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct AuditLog {
+  #[serde(rename = "_id")]
+  pub id: String,
+  /// the accounts whose owners can see the entry, empty for entries only visible to admins
+  pub account_ids: Vec<String>,
+  pub actor: AuditLogActor,
+  pub action: AuditLogAction,
+  pub target: AuditLogTarget,
+  /// the fields changed by the action, with their values before and after it
+  pub changes: Vec<AuditLogChange>,
+  #[serde(with = "serde_util::ip")]
+  #[ts(type = "string")]
+  pub ip: IpAddr,
+  pub user_agent: Option<String>,
+  pub created_at: DateTime,
+}
+
+/// who did the action, mirrors the scope of the access token used for it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "scope")]
+#[macros::keys]
+pub enum AuditLogActor {
+  Global,
+  /// a global token delegated to a user with the as_user query
+  GlobalAsUser {
+    user_id: String,
+  },
+  Admin {
+    admin_id: String,
+  },
+  /// an admin delegated into a user, with a delegated token or the as_user query
+  AdminAsUser {
+    admin_id: String,
+    user_id: String,
+  },
+  User {
+    user_id: String,
+  },
+}
+
+impl AuditLogActor {
+  /// the actor of a token scope, delegated to a user when the request has an as_user query
+  pub fn from_scope(scope: &Scope, as_user_id: Option<&str>) -> Self {
+    match (scope, as_user_id) {
+      (Scope::Global, None) => Self::Global,
+      (Scope::Global, Some(user_id)) => Self::GlobalAsUser {
+        user_id: user_id.to_string(),
+      },
+      (Scope::Admin { admin_id }, None) => Self::Admin {
+        admin_id: admin_id.clone(),
+      },
+      (Scope::Admin { admin_id }, Some(user_id)) => Self::AdminAsUser {
+        admin_id: admin_id.clone(),
+        user_id: user_id.to_string(),
+      },
+      (Scope::AdminAsUser { admin_id, user_id }, _) => Self::AdminAsUser {
+        admin_id: admin_id.clone(),
+        user_id: user_id.clone(),
+      },
+      (Scope::User { user_id }, _) => Self::User {
+        user_id: user_id.clone(),
+      },
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+pub enum AuditLogAction {
+  #[serde(rename = "account.patch")]
+  AccountPatch,
+  #[serde(rename = "station.patch")]
+  StationPatch,
+  #[serde(rename = "station.delete")]
+  StationDelete,
+  #[serde(rename = "station.transfer")]
+  StationTransfer,
+  #[serde(rename = "station.source_password_reset")]
+  StationSourcePasswordReset,
+  #[serde(rename = "station.live_source_kick")]
+  StationLiveSourceKick,
+  #[serde(rename = "station.dj_create")]
+  StationDjCreate,
+  #[serde(rename = "station.dj_patch")]
+  StationDjPatch,
+  #[serde(rename = "station.dj_delete")]
+  StationDjDelete,
+  #[serde(rename = "file.delete")]
+  FileDelete,
+  #[serde(rename = "recording.delete")]
+  RecordingDelete,
+  #[serde(rename = "admin.delegate")]
+  AdminDelegate,
+}
+
+/// the resource the action was done to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+#[macros::keys]
+pub enum AuditLogTarget {
+  Account {
+    account_id: String,
+  },
+  Station {
+    station_id: String,
+  },
+  AudioFile {
+    station_id: String,
+    file_id: String,
+  },
+  LiveRecording {
+    station_id: String,
+    recording_id: String,
+  },
+  StationDj {
+    station_id: String,
+    dj_id: String,
+  },
+  User {
+    user_id: String,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+pub struct AuditLogChange {
+  pub field: String,
+  #[ts(type = "unknown")]
+  pub before: serde_json::Value,
+  #[ts(type = "unknown")]
+  pub after: serde_json::Value,
+}
+
+impl AuditLogChange {
+  /// the fields of a patch that changed a document, with the values of the document before and after the patch
+  ///
+  /// the patch fields must have the same names as the document fields, as in StationPatch and AccountPatch
+  pub fn from_patch<P: Serialize, T: Serialize>(patch: &P, before: &T, after: &T) -> Vec<Self> {
+    let (patch, before, after) = match (
+      serde_json::to_value(patch),
+      serde_json::to_value(before),
+      serde_json::to_value(after),
+    ) {
+      (
+        Ok(serde_json::Value::Object(patch)),
+        Ok(serde_json::Value::Object(before)),
+        Ok(serde_json::Value::Object(after)),
+      ) => (patch, before, after),
+      _ => return vec![],
+    };
+
+    let mut changes = vec![];
+    for field in patch.keys() {
+      let before = before.get(field).cloned().unwrap_or_default();
+      let after = after.get(field).cloned().unwrap_or_default();
+      if before != after {
+        changes.push(Self {
+          field: field.clone(),
+          before,
+          after,
+        });
+      }
+    }
+
+    changes
+  }
+}
+
+/// who did a request and from where, the common part of the audit log entries of the request
+#[derive(Debug, Clone)]
+pub struct AuditLogOrigin {
+  pub actor: AuditLogActor,
+  pub ip: IpAddr,
+  pub user_agent: Option<String>,
+}
+
+impl AuditLog {
+  pub fn new(
+    origin: AuditLogOrigin,
+    account_ids: Vec<String>,
+    action: AuditLogAction,
+    target: AuditLogTarget,
+    changes: Vec<AuditLogChange>,
+  ) -> Self {
+    Self {
+      id: Self::uid(),
+      account_ids,
+      actor: origin.actor,
+      action,
+      target,
+      changes,
+      ip: origin.ip,
+      user_agent: origin.user_agent,
+      created_at: DateTime::now(),
+    }
+  }
+}
+
+const KEY_ACTOR_ADMIN_ID: &str = crate::key!(AuditLog::KEY_ACTOR, AuditLogActor::KEY_ADMIN_ID);
+const KEY_ACTOR_USER_ID: &str = crate::key!(AuditLog::KEY_ACTOR, AuditLogActor::KEY_USER_ID);
+const KEY_TARGET_STATION_ID: &str =
+  crate::key!(AuditLog::KEY_TARGET, AuditLogTarget::KEY_STATION_ID);
+
+/// the filters of the audit log query endpoints
+#[derive(Debug, Clone, Default)]
+pub struct AuditLogFilter<'a> {
+  pub account_id: Option<&'a str>,
+  pub station_id: Option<&'a str>,
+  /// the id of the admin or the user that did the action
+  pub actor_id: Option<&'a str>,
+  pub action: Option<AuditLogAction>,
+  pub since: Option<DateTime>,
+  pub until: Option<DateTime>,
+}
+
+impl AuditLogFilter<'_> {
+  pub fn to_document(&self) -> Document {
+    let mut filter = doc! {};
+
+    if let Some(account_id) = self.account_id {
+      filter.insert(AuditLog::KEY_ACCOUNT_IDS, account_id);
+    }
+
+    if let Some(station_id) = self.station_id {
+      filter.insert(KEY_TARGET_STATION_ID, station_id);
+    }
+
+    if let Some(actor_id) = self.actor_id {
+      filter.insert(
+        "$or",
+        vec![
+          doc! { KEY_ACTOR_ADMIN_ID: actor_id },
+          doc! { KEY_ACTOR_USER_ID: actor_id },
+        ],
+      );
+    }
+
+    if let Some(action) = self.action {
+      if let Ok(action) = mongodb::bson::to_bson(&action) {
+        filter.insert(AuditLog::KEY_ACTION, action);
+      }
+    }
+
+    match (self.since, self.until) {
+      (None, None) => {}
+      (Some(since), None) => {
+        filter.insert(AuditLog::KEY_CREATED_AT, doc! { "$gte": since });
+      }
+      (None, Some(until)) => {
+        filter.insert(AuditLog::KEY_CREATED_AT, doc! { "$lt": until });
+      }
+      (Some(since), Some(until)) => {
+        filter.insert(
+          AuditLog::KEY_CREATED_AT,
+          doc! { "$gte": since, "$lt": until },
+        );
+      }
+    }
+
+    filter
+  }
+}
+
+impl Model for AuditLog {
+  const UID_LEN: usize = 16;
+  const CL_NAME: &'static str = "audit_logs";
+
+  fn indexes() -> Vec<IndexModel> {
+    let created_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_CREATED_AT: 1 })
+      .build();
+
+    let account_ids_created_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_ACCOUNT_IDS: 1, Self::KEY_CREATED_AT: 1 })
+      .build();
+
+    let target_station_id = IndexModel::builder()
+      .keys(doc! { KEY_TARGET_STATION_ID: 1 })
+      .build();
+
+    vec![created_at, account_ids_created_at, target_station_id]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::account::AccountPatch;
+  use serde_json::json;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, AuditLog::KEY_ID);
+  }
+
+  #[test]
+  fn actor_from_scope() {
+    let admin = Scope::Admin {
+      admin_id: "admin".into(),
+    };
+
+    assert_eq!(
+      AuditLogActor::from_scope(&admin, None),
+      AuditLogActor::Admin {
+        admin_id: "admin".into()
+      }
+    );
+
+    assert_eq!(
+      AuditLogActor::from_scope(&admin, Some("user")),
+      AuditLogActor::AdminAsUser {
+        admin_id: "admin".into(),
+        user_id: "user".into(),
+      }
+    );
+
+    assert_eq!(
+      AuditLogActor::from_scope(&Scope::Global, Some("user")),
+      AuditLogActor::GlobalAsUser {
+        user_id: "user".into()
+      }
+    );
+
+    let user = Scope::User {
+      user_id: "user".into(),
+    };
+
+    assert_eq!(
+      AuditLogActor::from_scope(&user, Some("other")),
+      AuditLogActor::User {
+        user_id: "user".into()
+      }
+    );
+  }
+
+  #[test]
+  fn changes_from_patch() {
+    let patch = AccountPatch {
+      name: Some("New".into()),
+      plan_id: Some("plan".into()),
+      user_metadata: None,
+      system_metadata: None,
+    };
+
+    let before = json!({ "name": "Old", "plan_id": "plan", "user_metadata": {} });
+    let after = json!({ "name": "New", "plan_id": "plan", "user_metadata": {} });
+
+    let changes = AuditLogChange::from_patch(&patch, &before, &after);
+
+    assert_eq!(
+      changes,
+      vec![AuditLogChange {
+        field: "name".into(),
+        before: json!("Old"),
+        after: json!("New"),
+      }]
+    );
+  }
+
+  #[test]
+  fn filter_document() {
+    let filter = AuditLogFilter {
+      account_id: Some("account"),
+      action: Some(AuditLogAction::StationTransfer),
+      ..Default::default()
+    };
+
+    assert_eq!(
+      filter.to_document(),
+      doc! { AuditLog::KEY_ACCOUNT_IDS: "account", AuditLog::KEY_ACTION: "station.transfer" }
+    );
+  }
+}
@@ -0,0 +1,266 @@
+use crate::account::{Account, Limit, Limits};
+use crate::{current_filter_doc, run_transaction, Model};
+use log::*;
+use mongodb::bson::doc;
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+crate::register!(BillingPeriod);
+
+/// the archived usage of an account for a past billing period
+/// a new document is created every time the billing period of an account rolls over
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct BillingPeriod {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub account_id: String,
+  pub plan_id: String,
+  pub start: DateTime,
+  pub end: DateTime,
+  /// transfer used and transfer total of the account at the end of the period
+  /// used can be greater than total if the plan allows transfer overage
+  pub transfer: Limit,
+  pub created_at: DateTime,
+}
+
+impl Model for BillingPeriod {
+  const CL_NAME: &'static str = "billing_periods";
+  const UID_LEN: usize = 12;
+
+  fn indexes() -> Vec<IndexModel> {
+    let opts = IndexOptions::builder().unique(true).build();
+    let account_id_start = IndexModel::builder()
+      .keys(doc! { Self::KEY_ACCOUNT_ID: 1, Self::KEY_START: 1 })
+      .options(opts)
+      .build();
+
+    let start = IndexModel::builder()
+      .keys(doc! { Self::KEY_START: 1 })
+      .build();
+
+    vec![account_id_start, start]
+  }
+}
+
+/// the start of the billing period that follows the one that started at `start`
+/// billing periods are one calendar month long and start at `anchor_day` of the month,
+/// if the next month is shorter than the anchor day, the period ends at the last day of the next month
+/// the anchor day is kept, so a period that was clamped doesn't move the start of the following ones
+pub fn next_period_start(start: DateTime, anchor_day: u8) -> DateTime {
+  let start = start.inner();
+
+  let (year, month) = match start.month() {
+    time::Month::December => (start.year() + 1, time::Month::January),
+    month => (start.year(), month.next()),
+  };
+
+  let day = anchor_day.clamp(1, time::util::days_in_year_month(year, month));
+
+  let date = time::Date::from_calendar_date(year, month, day).unwrap();
+
+  start.replace_date(date).into()
+}
+
+/// the billing period start assigned to accounts created before billing periods existed
+/// it is the start of the current calendar month, the same period the old
+/// global transfer checkpoint used
+pub fn legacy_period_start(now: DateTime) -> DateTime {
+  let now = now.inner();
+  now
+    .replace_day(1)
+    .unwrap()
+    .replace_time(time::Time::MIDNIGHT)
+    .into()
+}
+
+/// rolls over the billing period of the account if it has ended
+/// the usage of the ended period is archived and the transfer used counter is reset to 0
+/// returns the archived period or None if the current period has not ended yet
+pub async fn rollover_account(
+  account_id: &str,
+  now: DateTime,
+) -> Result<Option<BillingPeriod>, mongodb::error::Error> {
+  const KEY_LIMITS_TRANSFER_USED: &str =
+    crate::key!(Account::KEY_LIMITS, Limits::KEY_TRANSFER, Limit::KEY_USED);
+
+  run_transaction!(session => {
+    let account = tx_try!(Account::get_by_id_with_session(account_id, &mut session).await);
+
+    let period = match account {
+      None => None,
+
+      Some(account) => match account.billing_period_start {
+        None => {
+          let update = doc! {
+            "$set": {
+              Account::KEY_BILLING_PERIOD_START: legacy_period_start(now),
+              Account::KEY_BILLING_PERIOD_ANCHOR_DAY: 1,
+            }
+          };
+
+          tx_try!(Account::update_by_id_with_session(&account.id, update, &mut session).await);
+
+          None
+        }
+
+        Some(start) => {
+          let anchor_day = account
+            .billing_period_anchor_day
+            .unwrap_or_else(|| start.day());
+
+          let end = next_period_start(start, anchor_day);
+
+          if *end > *now {
+            None
+          } else {
+            let period = BillingPeriod {
+              id: BillingPeriod::uid(),
+              account_id: account.id.clone(),
+              plan_id: account.plan_id.clone(),
+              start,
+              end,
+              transfer: account.limits.transfer.clone(),
+              created_at: now,
+            };
+
+            tx_try!(BillingPeriod::insert_with_session(&period, &mut session).await);
+
+            let update = doc! {
+              "$set": {
+                Account::KEY_BILLING_PERIOD_START: end,
+                Account::KEY_BILLING_PERIOD_ANCHOR_DAY: anchor_day as i32,
+                KEY_LIMITS_TRANSFER_USED: 0_f64,
+              }
+            };
+
+            tx_try!(Account::update_by_id_with_session(&account.id, update, &mut session).await);
+
+            Some(period)
+          }
+        }
+      },
+    };
+
+    Ok(period)
+  })
+}
+
+/// rolls over the billing periods of all the accounts whose current period has ended
+/// returns the number of archived periods
+pub async fn rollover_now() -> Result<usize, mongodb::error::Error> {
+  let now = DateTime::now();
+
+  // no billing period is shorter than 28 days
+  let min_start: DateTime = (now.inner() - time::Duration::days(28)).into();
+
+  let filter = current_filter_doc! {
+    "$or": [
+      { Account::KEY_BILLING_PERIOD_START: null },
+      { Account::KEY_BILLING_PERIOD_START: { "$lte": min_start } },
+    ]
+  };
+
+  let account_ids = Account::cl()
+    .distinct(Account::KEY_ID, filter, None)
+    .await?;
+
+  let mut count = 0;
+
+  for id in account_ids {
+    let id = match id.as_str() {
+      Some(id) => id,
+      None => continue,
+    };
+
+    // an account can be more than one period behind if this job was not running
+    while let Some(period) = rollover_account(id, now).await? {
+      info!(
+        target: "service",
+        "billing period rolled over for account {}: {} - {}, transfer used={} total={}",
+        period.account_id, period.start, period.end, period.transfer.used, period.transfer.total
+      );
+      count += 1;
+    }
+  }
+
+  Ok(count)
+}
+
+/// This job will run every BILLING_PERIOD_ROLLOVER_INTERVAL_SECS
+/// and roll over the billing periods of the accounts that reached the end of the period
+pub fn start_background_task() -> tokio::task::JoinHandle<()> {
+  tokio::spawn(async move {
+    info!(target: "service", "billing period rollover background job started");
+
+    loop {
+      match rollover_now().await {
+        Err(e) => {
+          warn!(target: "service", "error rolling over billing periods: {e}");
+        }
+
+        Ok(count) => {
+          debug!(target: "service", "billing period rollover => {count} periods archived");
+        }
+      }
+
+      let rand_offset_secs = rand::random::<f64>() * 10.0;
+
+      tokio::time::sleep(std::time::Duration::from_secs_f64(
+        constants::BILLING_PERIOD_ROLLOVER_INTERVAL_SECS as f64 + rand_offset_secs,
+      ))
+      .await;
+    }
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, BillingPeriod::KEY_ID);
+  }
+
+  #[test]
+  fn next_period_start_clamps_to_month_end() {
+    let start: DateTime = time::macros::datetime!(2023-01-31 10:00 UTC).into();
+    let next = next_period_start(start, 31);
+    assert_eq!(next.inner(), time::macros::datetime!(2023-02-28 10:00 UTC));
+
+    let start: DateTime = time::macros::datetime!(2023-12-15 00:00 UTC).into();
+    let next = next_period_start(start, 15);
+    assert_eq!(next.inner(), time::macros::datetime!(2024-01-15 00:00 UTC));
+  }
+
+  #[test]
+  fn next_period_start_keeps_the_anchor_day() {
+    let mut start: DateTime = time::macros::datetime!(2023-01-31 10:00 UTC).into();
+    let mut starts = vec![];
+    for _ in 0..4 {
+      start = next_period_start(start, 31);
+      starts.push(start.inner());
+    }
+
+    assert_eq!(
+      starts,
+      vec![
+        time::macros::datetime!(2023-02-28 10:00 UTC),
+        time::macros::datetime!(2023-03-31 10:00 UTC),
+        time::macros::datetime!(2023-04-30 10:00 UTC),
+        time::macros::datetime!(2023-05-31 10:00 UTC),
+      ]
+    );
+
+    let start: DateTime = time::macros::datetime!(2024-01-29 00:00 UTC).into();
+    let next = next_period_start(start, 29);
+    assert_eq!(next.inner(), time::macros::datetime!(2024-02-29 00:00 UTC));
+  }
+}
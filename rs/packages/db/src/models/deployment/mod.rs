@@ -92,7 +92,8 @@ impl Model for Deployment {
   }
 }
 
-pub async fn check_now() -> Result<(), mongodb::error::Error> {
+/// ids of the deployments that are not closed and passed the health check recently
+pub async fn active_deployment_ids() -> Result<Vec<String>, mongodb::error::Error> {
   let active_time_limit: DateTime = (time::OffsetDateTime::now_utc()
     - time::Duration::seconds(constants::DEPLOYMENT_HEALTH_CHECK_SHUTDOWN_DELAY_SECS as i64))
   .into();
@@ -105,8 +106,11 @@ pub async fn check_now() -> Result<(), mongodb::error::Error> {
     ]
   };
 
-  let active_deployment_ids: Vec<String> =
-    Deployment::distinct_string(crate::KEY_ID, active_filter).await?;
+  Deployment::distinct_string(crate::KEY_ID, active_filter).await
+}
+
+pub async fn check_now() -> Result<(), mongodb::error::Error> {
+  let active_deployment_ids = active_deployment_ids().await?;
 
   let mut to_close_deployment_ids = HashSet::<String>::new();
 
@@ -4,12 +4,17 @@ use log::{trace, warn};
 use mongodb::bson::doc;
 use mongodb::options::FindOptions;
 use mongodb::{options::CreateCollectionOptions, IndexModel};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_util::DateTime;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use ts_rs::TS;
 
+use crate::account::LimitKind;
+use crate::media_session::{DeadAirReason, MediaSessionRelaySwitchReason};
+use crate::webhook::Webhook;
+use crate::webhook_delivery::WebhookDelivery;
 use crate::{db, Model};
 
 crate::register!(Event);
@@ -26,11 +31,11 @@ struct Watcher {
 #[macros::keys]
 pub struct Event {
   #[serde(rename = "_id")]
-  id: String,
-  created_at: DateTime,
+  pub id: String,
+  pub created_at: DateTime,
 
   #[serde(flatten)]
-  variant: Variant,
+  pub variant: Variant,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -43,6 +48,95 @@ pub enum Variant {
   AudioListenerStart(AudioListenerStart),
   #[serde(rename = "listener.end")]
   AudioListenerEnd(AudioListenerEnd),
+  #[serde(rename = "live.connected")]
+  LiveSourceConnected(LiveSourceConnected),
+  #[serde(rename = "live.disconnected")]
+  LiveSourceDisconnected(LiveSourceDisconnected),
+  #[serde(rename = "now_playing.changed")]
+  NowPlayingChanged(NowPlayingChanged),
+  #[serde(rename = "relay.failed")]
+  RelayFailed(RelayFailed),
+  #[serde(rename = "dead_air.started")]
+  DeadAirStarted(DeadAirStarted),
+  #[serde(rename = "dead_air.ended")]
+  DeadAirEnded(DeadAirEnded),
+  #[serde(rename = "quota.threshold")]
+  QuotaThreshold(QuotaThreshold),
+  #[serde(rename = "upload.finished")]
+  UploadFinished(UploadFinished),
+  #[serde(rename = "member.invited")]
+  MemberInvited(MemberInvited),
+}
+
+/// the kind of an event, used to filter the events that a webhook receives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+pub enum EventKind {
+  #[serde(rename = "listener.start")]
+  AudioListenerStart,
+  #[serde(rename = "listener.end")]
+  AudioListenerEnd,
+  #[serde(rename = "live.connected")]
+  LiveSourceConnected,
+  #[serde(rename = "live.disconnected")]
+  LiveSourceDisconnected,
+  #[serde(rename = "now_playing.changed")]
+  NowPlayingChanged,
+  #[serde(rename = "relay.failed")]
+  RelayFailed,
+  #[serde(rename = "dead_air.started")]
+  DeadAirStarted,
+  #[serde(rename = "dead_air.ended")]
+  DeadAirEnded,
+  #[serde(rename = "quota.threshold")]
+  QuotaThreshold,
+  #[serde(rename = "upload.finished")]
+  UploadFinished,
+  #[serde(rename = "member.invited")]
+  MemberInvited,
+}
+
+impl Variant {
+  pub fn kind(&self) -> EventKind {
+    match self {
+      Self::AudioListenerStart(_) => EventKind::AudioListenerStart,
+      Self::AudioListenerEnd(_) => EventKind::AudioListenerEnd,
+      Self::LiveSourceConnected(_) => EventKind::LiveSourceConnected,
+      Self::LiveSourceDisconnected(_) => EventKind::LiveSourceDisconnected,
+      Self::NowPlayingChanged(_) => EventKind::NowPlayingChanged,
+      Self::RelayFailed(_) => EventKind::RelayFailed,
+      Self::DeadAirStarted(_) => EventKind::DeadAirStarted,
+      Self::DeadAirEnded(_) => EventKind::DeadAirEnded,
+      Self::QuotaThreshold(_) => EventKind::QuotaThreshold,
+      Self::UploadFinished(_) => EventKind::UploadFinished,
+      Self::MemberInvited(_) => EventKind::MemberInvited,
+    }
+  }
+
+  /// the station of the event, for events of a station
+  pub fn station_id(&self) -> Option<&str> {
+    match self {
+      Self::AudioListenerStart(v) => Some(&v.station_id),
+      Self::AudioListenerEnd(v) => Some(&v.station_id),
+      Self::LiveSourceConnected(v) => Some(&v.station_id),
+      Self::LiveSourceDisconnected(v) => Some(&v.station_id),
+      Self::NowPlayingChanged(v) => Some(&v.station_id),
+      Self::RelayFailed(v) => Some(&v.station_id),
+      Self::DeadAirStarted(v) => Some(&v.station_id),
+      Self::DeadAirEnded(v) => Some(&v.station_id),
+      Self::UploadFinished(v) => Some(&v.station_id),
+      Self::QuotaThreshold(_) | Self::MemberInvited(_) => None,
+    }
+  }
+
+  /// the account of the event, for events that are not of a station
+  pub fn account_id(&self) -> Option<&str> {
+    match self {
+      Self::QuotaThreshold(v) => Some(&v.account_id),
+      Self::MemberInvited(v) => Some(&v.account_id),
+      _ => None,
+    }
+  }
 }
 
 impl From<Variant> for Event {
@@ -67,13 +161,101 @@ impl From<AudioListenerEnd> for Event {
   }
 }
 
+impl From<LiveSourceConnected> for Event {
+  fn from(value: LiveSourceConnected) -> Self {
+    Event::from(Variant::LiveSourceConnected(value))
+  }
+}
+
+impl From<LiveSourceDisconnected> for Event {
+  fn from(value: LiveSourceDisconnected) -> Self {
+    Event::from(Variant::LiveSourceDisconnected(value))
+  }
+}
+
+impl From<NowPlayingChanged> for Event {
+  fn from(value: NowPlayingChanged) -> Self {
+    Event::from(Variant::NowPlayingChanged(value))
+  }
+}
+
+impl From<RelayFailed> for Event {
+  fn from(value: RelayFailed) -> Self {
+    Event::from(Variant::RelayFailed(value))
+  }
+}
+
+impl From<DeadAirStarted> for Event {
+  fn from(value: DeadAirStarted) -> Self {
+    Event::from(Variant::DeadAirStarted(value))
+  }
+}
+
+impl From<DeadAirEnded> for Event {
+  fn from(value: DeadAirEnded) -> Self {
+    Event::from(Variant::DeadAirEnded(value))
+  }
+}
+
+impl From<QuotaThreshold> for Event {
+  fn from(value: QuotaThreshold) -> Self {
+    Event::from(Variant::QuotaThreshold(value))
+  }
+}
+
+impl From<UploadFinished> for Event {
+  fn from(value: UploadFinished) -> Self {
+    Event::from(Variant::UploadFinished(value))
+  }
+}
+
+impl From<MemberInvited> for Event {
+  fn from(value: MemberInvited) -> Self {
+    Event::from(Variant::MemberInvited(value))
+  }
+}
+
 impl Event {
+  /// inserts the event and creates the deliveries of the webhooks subscribed to it
   pub async fn dispatch(event: impl Into<Event>) -> Result<Event, mongodb::error::Error> {
     let event = event.into();
     Self::insert(&event).await?;
+    WebhookDelivery::create_for_event(&event).await?;
     Ok(event)
   }
 
+  /// dispatches the event in a background task, errors are logged
+  /// used where a failure to record the event must not affect the operation that produced it
+  pub fn dispatch_detached(event: impl Into<Event>) {
+    let event = event.into();
+    tokio::spawn(async move {
+      let kind = event.variant.kind();
+      if let Err(e) = Self::dispatch(event).await {
+        warn!(target: "events", "error dispatching {kind:?} event: {e} => {e:?}");
+      }
+    });
+  }
+
+  /// dispatches the event in a background task only if a webhook of the account accepts it
+  /// used for the high frequency events, like the listener events, that are only recorded to be delivered
+  pub fn dispatch_subscribed_detached(account_id: String, event: impl Into<Event>) {
+    let event = event.into();
+    tokio::spawn(async move {
+      let kind = event.variant.kind();
+      match Webhook::is_subscribed(&account_id, kind).await {
+        Ok(false) => {}
+        Ok(true) => {
+          if let Err(e) = Self::dispatch(event).await {
+            warn!(target: "events", "error dispatching {kind:?} event: {e} => {e:?}");
+          }
+        }
+        Err(e) => {
+          warn!(target: "events", "error checking webhooks for {kind:?} event: {e} => {e:?}");
+        }
+      }
+    });
+  }
+
   pub async fn watch() -> Result<tokio::sync::broadcast::Receiver<Arc<Event>>, mongodb::error::Error>
   {
     let mut watcher = WATCHER.lock().await;
@@ -172,8 +354,8 @@ impl Model for Event {
 #[serde(rename_all = "snake_case")]
 #[macros::keys]
 pub struct AudioListenerStart {
-  station_id: String,
-  connection_id: String,
+  pub station_id: String,
+  pub connection_id: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -181,8 +363,118 @@ pub struct AudioListenerStart {
 #[serde(rename_all = "snake_case")]
 #[macros::keys]
 pub struct AudioListenerEnd {
-  station_id: String,
-  connection_id: String,
+  pub station_id: String,
+  pub connection_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/event-payload/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct LiveSourceConnected {
+  pub station_id: String,
+  pub media_session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/event-payload/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct LiveSourceDisconnected {
+  pub station_id: String,
+  pub media_session_id: String,
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub duration_ms: u64,
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub transfer_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/event-payload/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct NowPlayingChanged {
+  pub station_id: String,
+  pub title: String,
+  pub artist: Option<String>,
+}
+
+/// an external relay url failed and the relay switched to other source
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/event-payload/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct RelayFailed {
+  pub station_id: String,
+  pub url: String,
+  pub reason: MediaSessionRelaySwitchReason,
+}
+
+/// the source of a media session is in dead air, see Station::dead_air
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/event-payload/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct DeadAirStarted {
+  pub station_id: String,
+  pub media_session_id: String,
+  pub reason: DeadAirReason,
+  pub level_dbfs: Option<f64>,
+  pub kbitrate: u32,
+  /// the station playlist was put on air instead of the source
+  pub failover: bool,
+}
+
+/// the source of a media session is back from dead air
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/event-payload/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct DeadAirEnded {
+  pub station_id: String,
+  pub media_session_id: String,
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub duration_ms: u64,
+}
+
+/// the used amount of an account limit reached `percent` of its total
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/event-payload/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct QuotaThreshold {
+  pub account_id: String,
+  pub limit: LimitKind,
+  pub percent: u8,
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub used: u64,
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub total: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/event-payload/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct UploadFinished {
+  pub station_id: String,
+  pub audio_file_id: String,
+  pub filename: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/event-payload/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct MemberInvited {
+  pub account_id: String,
+  pub invitation_id: String,
+  pub receiver_email: String,
 }
 
 #[cfg(test)]
@@ -193,4 +485,19 @@ mod test {
   fn keys_match() {
     assert_eq!(crate::KEY_ID, Event::KEY_ID);
   }
+
+  #[test]
+  fn kind_matches_variant_tag() {
+    let event = Event::from(MemberInvited {
+      account_id: "account".into(),
+      invitation_id: "invitation".into(),
+      receiver_email: "a@example.com".into(),
+    });
+
+    let value = serde_json::to_value(&event).unwrap();
+    let kind = serde_json::to_value(event.variant.kind()).unwrap();
+    assert_eq!(value[Variant::KEY_ENUM_TAG], kind);
+    assert_eq!(event.variant.account_id(), Some("account"));
+    assert_eq!(event.variant.station_id(), None);
+  }
 }
@@ -0,0 +1,215 @@
+use crate::account::{Account, Limit, Limits};
+use crate::audio_chunk::AudioChunk;
+use crate::audio_file::AudioFile;
+use crate::station::Station;
+use crate::{run_transaction, Model};
+use futures_util::TryStreamExt;
+use log::*;
+use mongodb::bson::{doc, Document};
+use mongodb::{ClientSession, IndexModel};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+crate::register!(LiveRecording);
+
+/// a recording of a live broadcast of a station
+///
+/// the audio is stored as the audio file `file` and its AudioChunk documents,
+/// but the file is not part of the station files until the recording is published
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct LiveRecording {
+  /// the same as the id of the file, the chunks of the recording are stored with this id
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub station_id: String,
+  pub media_session_id: String,
+  /// the live play history item that was playing when the recording started
+  pub play_history_item_id: Option<String>,
+  pub title: Option<String>,
+  pub artist: Option<String>,
+  pub file: AudioFile,
+  pub started_at: DateTime,
+  pub ended_at: DateTime,
+  /// the recording is removed after this date if it is not published, None to keep it
+  pub expires_at: Option<DateTime>,
+  pub created_at: DateTime,
+}
+
+impl LiveRecording {
+  pub const EXPIRE_JOB_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(60 * 10); // 10 min
+
+  /// filter of the recordings of a station
+  /// if `media_session_id` is set only the recordings of that live session are matched
+  pub fn filter(station_id: &str, media_session_id: Option<&str>) -> Document {
+    let mut filter = doc! { Self::KEY_STATION_ID: station_id };
+    if let Some(media_session_id) = media_session_id {
+      filter.insert(Self::KEY_MEDIA_SESSION_ID, media_session_id);
+    }
+    filter
+  }
+
+  /// the date a recording created now expires with a retention of `retention_days`, 0 to keep it
+  pub fn expires_at(retention_days: u32) -> Option<DateTime> {
+    if retention_days == 0 {
+      None
+    } else {
+      let date = time::OffsetDateTime::now_utc() + time::Duration::days(retention_days as i64);
+      Some(date.into())
+    }
+  }
+
+  /// removes the recording with its chunks and frees the storage used by it
+  pub async fn delete_with_session(
+    station_id: &str,
+    id: &str,
+    session: &mut ClientSession,
+  ) -> Result<Option<LiveRecording>, mongodb::error::Error> {
+    let filter = doc! { Self::KEY_ID: id, Self::KEY_STATION_ID: station_id };
+    let recording = match Self::get_with_session(filter, session).await? {
+      None => return Ok(None),
+      Some(recording) => recording,
+    };
+
+    AudioChunk::delete_by_audio_file_id_with_session(&recording.id, session).await?;
+    Self::delete_by_id_with_session(&recording.id, session).await?;
+
+    if let Some(station) = Station::get_by_id_with_session(station_id, session).await? {
+      const KEY: &str = crate::key!(Account::KEY_LIMITS, Limits::KEY_STORAGE, Limit::KEY_USED);
+      let update = doc! { "$inc": { KEY: (recording.file.len as f64) * -1.0 } };
+      Account::update_by_id_with_session(&station.account_id, update, session).await?;
+    }
+
+    Ok(Some(recording))
+  }
+
+  pub async fn delete(
+    station_id: &str,
+    id: &str,
+  ) -> Result<Option<LiveRecording>, mongodb::error::Error> {
+    run_transaction!(session => {
+      let recording = tx_try!(Self::delete_with_session(station_id, id, &mut session).await);
+      Ok(recording)
+    })
+  }
+
+  /// moves the recording to the end of the station files
+  ///
+  /// the chunks and the storage used by the recording are kept by the file
+  pub async fn publish(
+    station_id: &str,
+    id: &str,
+  ) -> Result<Option<AudioFile>, mongodb::error::Error> {
+    run_transaction!(session => {
+      let filter = doc! { Self::KEY_ID: id, Self::KEY_STATION_ID: station_id };
+      let recording = match tx_try!(Self::get_with_session(filter, &mut session).await) {
+        None => return Ok(None),
+        Some(recording) => recording,
+      };
+
+      let mut file = recording.file;
+      file.order = tx_try!(AudioFile::next_max_order(station_id, Some(&mut session)).await);
+
+      tx_try!(AudioFile::insert_with_session(&file, &mut session).await);
+      tx_try!(Self::delete_by_id_with_session(&recording.id, &mut session).await);
+
+      Ok(Some(file))
+    })
+  }
+
+  /// storage used by the recordings of a station
+  pub async fn get_used_storage_with_session(
+    station_id: &str,
+    session: &mut ClientSession,
+  ) -> Result<u64, mongodb::error::Error> {
+    let filter = doc! { Self::KEY_STATION_ID: station_id };
+    let mut cursor = Self::cl().find_with_session(filter, None, session).await?;
+    let mut acc: u64 = 0;
+    while let Some(recording) = cursor.next(session).await.transpose()? {
+      acc += recording.file.len;
+    }
+
+    Ok(acc)
+  }
+
+  /// removes the recordings that passed their expiration date
+  pub async fn remove_expired() -> Result<u64, mongodb::error::Error> {
+    let filter = doc! { Self::KEY_EXPIRES_AT: { "$lt": DateTime::now() } };
+    let recordings: Vec<LiveRecording> = Self::cl().find(filter, None).await?.try_collect().await?;
+
+    let mut n = 0;
+    for recording in recordings.iter() {
+      if Self::delete(&recording.station_id, &recording.id)
+        .await?
+        .is_some()
+      {
+        n += 1;
+      }
+    }
+
+    Ok(n)
+  }
+
+  pub fn start_expire_job() -> tokio::task::JoinHandle<()> {
+    info!(target: "live-recording-expire", "live recording expire job started");
+    tokio::spawn(async move {
+      let mut interval = tokio::time::interval(Self::EXPIRE_JOB_INTERVAL);
+      loop {
+        // first tick is instantaneous
+        interval.tick().await;
+
+        match Self::remove_expired().await {
+          Ok(n) => {
+            if n != 0 {
+              info!(target: "live-recording-expire", "{n} expired live recordings removed");
+            }
+          }
+          Err(e) => {
+            error!(target: "live-recording-expire", "mongodb error removing expired live recordings: {e}, {e:?}");
+          }
+        }
+      }
+    })
+  }
+}
+
+impl Model for LiveRecording {
+  const UID_LEN: usize = AudioFile::UID_LEN;
+  const CL_NAME: &'static str = "live_recordings";
+
+  fn indexes() -> Vec<IndexModel> {
+    let station_id_created_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1, Self::KEY_CREATED_AT: 1 })
+      .build();
+
+    let media_session_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_MEDIA_SESSION_ID: 1 })
+      .build();
+
+    let expires_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_EXPIRES_AT: 1 })
+      .build();
+
+    vec![station_id_created_at, media_session_id, expires_at]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, LiveRecording::KEY_ID);
+  }
+
+  #[test]
+  fn expires_at_without_retention() {
+    assert!(LiveRecording::expires_at(0).is_none());
+    assert!(*LiveRecording::expires_at(1).unwrap() > *DateTime::now());
+  }
+}
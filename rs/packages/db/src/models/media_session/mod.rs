@@ -1,13 +1,17 @@
 use crate::deployment::{Deployment, DeploymentState};
+use crate::station_dj::StationDjRef;
 use crate::Model;
+use futures_util::TryStreamExt;
 use mongodb::{
   bson::{doc, SerializerOptions},
-  options::FindOneOptions,
+  options::{FindOneOptions, FindOptions},
   results::UpdateResult,
   IndexModel,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_util::DateTime;
+use std::collections::HashMap;
 use ts_rs::TS;
 
 crate::register!(MediaSession);
@@ -30,6 +34,10 @@ pub struct MediaSession {
 
   pub now_playing: Option<MediaSessionNowPlaying>,
 
+  /// set while the source is in dead air, see Station::dead_air
+  #[serde(default)]
+  pub dead_air: Option<MediaSessionDeadAir>,
+
   #[serde(with = "serde_util::as_f64")]
   pub transfer_bytes: u64,
   pub closed_at: Option<DateTime>,
@@ -54,6 +62,9 @@ pub enum MediaSessionKind {
   #[serde(rename = "playlist")]
   Playlist {
     resumed_from: Option<String>,
+    /// the named playlist of the session, None for the station files
+    #[serde(default)]
+    playlist_id: Option<String>,
     last_audio_file_id: String,
     last_audio_file_order: f64,
     last_audio_chunk_i: f64,
@@ -63,10 +74,205 @@ pub enum MediaSessionKind {
   },
 
   #[serde(rename = "live")]
-  Live { request: crate::http::Request },
+  Live {
+    request: crate::http::Request,
+    /// the audio sent by the encoder, None until its first frames are validated
+    /// and for sessions created before it was recorded
+    #[serde(default)]
+    input: Option<MediaSessionLiveInput>,
+    /// the dj connected to the source, None for the station source password
+    #[serde(default)]
+    dj: Option<StationDjRef>,
+  },
 
   #[serde(rename = "external-relay")]
-  ExternalRelay { url: String },
+  ExternalRelay {
+    /// the primary url of the relay
+    url: String,
+    /// the source that is currently on air
+    /// None for sessions created before relay failover existed
+    #[serde(default)]
+    on_air: Option<MediaSessionRelaySource>,
+    /// health of each of the relay urls, in failover order
+    #[serde(default)]
+    source_health: Vec<MediaSessionRelaySourceHealth>,
+    /// the last source switches of the session, oldest first
+    #[serde(default)]
+    switches: Vec<MediaSessionRelaySwitch>,
+  },
+}
+
+/// the audio sent by the encoder of a live media session
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct MediaSessionLiveInput {
+  /// None if the input is not MP3 or AAC ADTS and is transcoded
+  pub codec: Option<MediaSessionLiveInputCodec>,
+  pub sample_rate: Option<u32>,
+  pub channels: Option<u8>,
+  /// average bitrate of the validated frames in kbps
+  pub kbitrate: Option<u32>,
+  /// the input is transcoded to the stream format of the station instead of broadcasted as is
+  pub transcoded: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub enum MediaSessionLiveInputCodec {
+  Mp3,
+  Aac,
+}
+
+/// a source of an external relay media session
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+#[macros::keys]
+pub enum MediaSessionRelaySource {
+  /// one of the relay urls, index 0 is the primary url
+  #[serde(rename = "url")]
+  Url { index: u32, url: String },
+  /// the station playlist, used when none of the relay urls is available
+  #[serde(rename = "playlist")]
+  Playlist,
+  /// none of the relay urls is available and the station has no playlist files
+  #[serde(rename = "off_air")]
+  OffAir,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct MediaSessionRelaySourceHealth {
+  pub url: String,
+  pub healthy: bool,
+  /// consecutive failures of this url
+  pub failures: u32,
+  pub last_failure_at: Option<DateTime>,
+  pub last_failure_reason: Option<MediaSessionRelaySwitchReason>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct MediaSessionRelaySwitch {
+  pub to: MediaSessionRelaySource,
+  pub reason: MediaSessionRelaySwitchReason,
+  pub at: DateTime,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub enum MediaSessionRelaySwitchReason {
+  /// first source of the session
+  Start,
+  /// the previous source ended its stream
+  Eof,
+  /// the previous source produced no data in the allowed window
+  NoData,
+  /// the previous source could not be started
+  Error,
+  /// a url with higher priority than the one on air is healthy again
+  Recovered,
+  /// a failed url waited EXTERNAL_RELAY_SOURCE_RETRY_SECS and is tried again
+  Retry,
+}
+
+/// the dead air state of a live or external relay media session
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct MediaSessionDeadAir {
+  pub reason: DeadAirReason,
+  /// start of the dead air, the first dead window of the detection
+  pub since: DateTime,
+  /// RMS level of the last dead window in dBFS, None if the audio could not be decoded
+  pub level_dbfs: Option<f64>,
+  /// bitrate of the source in the last dead window
+  pub kbitrate: u32,
+  /// the station playlist is on air instead of the source
+  pub failover: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub enum DeadAirReason {
+  /// the audio level is under the silence threshold of the station
+  Silence,
+  /// the bitrate of the source is under the minimum bitrate of the station
+  LowBitrate,
+}
+
+impl From<MediaSessionDeadAir> for mongodb::bson::Bson {
+  fn from(value: MediaSessionDeadAir) -> Self {
+    mongodb::bson::to_bson_with_options(
+      &value,
+      SerializerOptions::builder().human_readable(false).build(),
+    )
+    .expect("error convering MediaSessionDeadAir to Bson")
+  }
+}
+
+impl From<DeadAirReason> for mongodb::bson::Bson {
+  fn from(value: DeadAirReason) -> Self {
+    mongodb::bson::to_bson_with_options(
+      &value,
+      SerializerOptions::builder().human_readable(false).build(),
+    )
+    .expect("error convering DeadAirReason to Bson")
+  }
+}
+
+impl From<MediaSessionLiveInput> for mongodb::bson::Bson {
+  fn from(value: MediaSessionLiveInput) -> Self {
+    mongodb::bson::to_bson_with_options(
+      &value,
+      SerializerOptions::builder().human_readable(false).build(),
+    )
+    .expect("error convering MediaSessionLiveInput to Bson")
+  }
+}
+
+impl From<MediaSessionRelaySource> for mongodb::bson::Bson {
+  fn from(value: MediaSessionRelaySource) -> Self {
+    mongodb::bson::to_bson_with_options(
+      &value,
+      SerializerOptions::builder().human_readable(false).build(),
+    )
+    .expect("error convering MediaSessionRelaySource to Bson")
+  }
+}
+
+impl From<MediaSessionRelaySwitch> for mongodb::bson::Bson {
+  fn from(value: MediaSessionRelaySwitch) -> Self {
+    mongodb::bson::to_bson_with_options(
+      &value,
+      SerializerOptions::builder().human_readable(false).build(),
+    )
+    .expect("error convering MediaSessionRelaySwitch to Bson")
+  }
+}
+
+impl From<MediaSessionRelaySourceHealth> for mongodb::bson::Bson {
+  fn from(value: MediaSessionRelaySourceHealth) -> Self {
+    mongodb::bson::to_bson_with_options(
+      &value,
+      SerializerOptions::builder().human_readable(false).build(),
+    )
+    .expect("error convering MediaSessionRelaySourceHealth to Bson")
+  }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, TS)]
@@ -85,6 +291,10 @@ pub enum MediaSessionState {
 pub struct MediaSessionNowPlaying {
   pub title: String,
   pub artist: Option<String>,
+  /// the play history item that was created when this now playing info started
+  #[ts(optional)]
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub play_history_item_id: Option<String>,
 }
 
 impl From<MediaSessionNowPlaying> for mongodb::bson::Bson {
@@ -139,6 +349,116 @@ impl MediaSession {
     Self::cl().find_one(filter, options).await
   }
 
+  /// the current media session of each of the stations, stations without a current session are not included
+  pub async fn get_current_for_stations(
+    station_ids: &[String],
+  ) -> Result<HashMap<String, MediaSession>, mongodb::error::Error> {
+    let open_deployment_ids = crate::deployment::Deployment::cl()
+      .distinct(
+        Deployment::KEY_ID,
+        doc! { Deployment::KEY_STATE: DeploymentState::KEY_ENUM_VARIANT_ACTIVE },
+        None,
+      )
+      .await?;
+
+    let filter = doc! {
+      MediaSession::KEY_DEPLOYMENT_ID: { "$in": open_deployment_ids },
+      MediaSession::KEY_STATION_ID: { "$in": station_ids },
+      MediaSession::KEY_STATE: MediaSessionState::KEY_ENUM_VARIANT_OPEN
+    };
+
+    // oldest first, so the newest session of each station is the one that remains in the map
+    let sort = doc! {
+      MediaSession::KEY_CREATED_AT: 1
+    };
+
+    let options = FindOptions::builder().sort(sort).build();
+
+    let mut cursor = Self::cl().find(filter, options).await?;
+    let mut map = HashMap::with_capacity(station_ids.len());
+    while let Some(session) = cursor.try_next().await? {
+      map.insert(session.station_id.clone(), session);
+    }
+
+    Ok(map)
+  }
+
+  /// records a source switch of an external relay session
+  /// only the last EXTERNAL_RELAY_SESSION_SWITCHES_MAX switches are kept
+  pub async fn record_relay_switch(
+    id: &str,
+    switch: MediaSessionRelaySwitch,
+    source_health: Vec<MediaSessionRelaySourceHealth>,
+  ) -> Result<UpdateResult, mongodb::error::Error> {
+    let update = doc! {
+      "$set": {
+        MediaSessionKind::KEY_ON_AIR: switch.to.clone(),
+        MediaSessionKind::KEY_SOURCE_HEALTH: source_health,
+        // the now playing of the playlist fallback is set by the playlist itself
+        MediaSession::KEY_NOW_PLAYING: null,
+        MediaSession::KEY_UPDATED_AT: switch.at,
+      },
+      "$push": {
+        MediaSessionKind::KEY_SWITCHES: {
+          "$each": [ switch ],
+          "$slice": -(constants::EXTERNAL_RELAY_SESSION_SWITCHES_MAX as i64),
+        }
+      }
+    };
+
+    MediaSession::update_by_id(id, update).await
+  }
+
+  pub async fn set_live_input(
+    id: &str,
+    input: MediaSessionLiveInput,
+  ) -> Result<UpdateResult, mongodb::error::Error> {
+    let update = doc! {
+      "$set": {
+        MediaSessionKind::KEY_INPUT: input,
+        MediaSession::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    MediaSession::update_by_id(id, update).await
+  }
+
+  /// sets or clears the dead air state of the session
+  pub async fn set_dead_air(
+    id: &str,
+    dead_air: Option<MediaSessionDeadAir>,
+  ) -> Result<UpdateResult, mongodb::error::Error> {
+    let update = doc! {
+      "$set": {
+        MediaSession::KEY_DEAD_AIR: dead_air,
+        MediaSession::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    MediaSession::update_by_id(id, update).await
+  }
+
+  /// the dead air state of the open media session of the station that is in dead air, if any
+  /// while the source is in failover the current session of the station is the playlist one
+  pub async fn get_dead_air_for_station(
+    station_id: &str,
+  ) -> Result<Option<MediaSessionDeadAir>, mongodb::error::Error> {
+    let filter = doc! {
+      MediaSession::KEY_STATION_ID: station_id,
+      MediaSession::KEY_STATE: MediaSessionState::KEY_ENUM_VARIANT_OPEN,
+      MediaSession::KEY_DEAD_AIR: { "$ne": null },
+    };
+
+    let sort = doc! {
+      MediaSession::KEY_CREATED_AT: -1
+    };
+
+    let options = FindOneOptions::builder().sort(sort).build();
+
+    let session = Self::cl().find_one(filter, options).await?;
+    Ok(session.and_then(|session| session.dead_air))
+  }
+
   pub async fn set_file_chunk_part(
     id: &str,
     file_id: &str,
@@ -198,6 +518,7 @@ mod test {
       transfer_bytes: 0,
       kind: MediaSessionKind::Playlist {
         resumed_from: None,
+        playlist_id: None,
         last_audio_file_id: AudioFile::uid(),
         last_audio_file_order: 0.0,
         last_audio_chunk_i: 0.0,
@@ -205,6 +526,7 @@ mod test {
         last_audio_chunk_date: DateTime::now(),
       },
       now_playing: None,
+      dead_air: None,
       state: MediaSessionState::Closed,
       health_checked_at: Some(DateTime::now()),
       closed_at: Some(DateTime::now()),
@@ -218,6 +540,37 @@ mod test {
     assert_eq!(doc, target);
   }
 
+  #[test]
+  fn external_relay_without_failover_fields() {
+    let doc = doc! {
+      MediaSession::KEY_ID: MediaSession::uid(),
+      MediaSession::KEY_STATION_ID: Station::uid(),
+      MediaSession::KEY_DEPLOYMENT_ID: Station::uid(),
+      MediaSession::KEY_STATE: MediaSessionState::KEY_ENUM_VARIANT_OPEN,
+      MediaSessionKind::KEY_ENUM_TAG: MediaSessionKind::KEY_ENUM_VARIANT_EXTERNALRELAY,
+      MediaSessionKind::KEY_URL: "https://example.com/stream",
+      MediaSession::KEY_NOW_PLAYING: null,
+      MediaSession::KEY_TRANSFER_BYTES: 0.0,
+      MediaSession::KEY_CLOSED_AT: null,
+      MediaSession::KEY_DURATION_MS: null,
+      MediaSession::KEY_HEALTH_CHECKED_AT: null,
+      MediaSession::KEY_CREATED_AT: DateTime::now(),
+      MediaSession::KEY_UPDATED_AT: DateTime::now(),
+    };
+
+    let target: MediaSession = mongodb::bson::from_document(doc).unwrap();
+
+    assert_eq!(
+      target.kind,
+      MediaSessionKind::ExternalRelay {
+        url: "https://example.com/stream".into(),
+        on_air: None,
+        source_health: vec![],
+        switches: vec![],
+      }
+    );
+  }
+
   #[test]
   fn keys_match() {
     assert_eq!(crate::KEY_ID, MediaSession::KEY_ID);
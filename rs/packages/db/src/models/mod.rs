@@ -3,25 +3,38 @@ pub mod account;
 pub mod admin;
 pub mod audio_chunk;
 pub mod audio_file;
+pub mod audio_import;
 pub mod audio_upload_operation;
+pub mod audit_log;
+pub mod billing_period;
 pub mod config;
 pub mod db_writable_test;
 pub mod deployment;
 pub mod event;
+pub mod live_recording;
 pub mod media_session;
 pub mod plan;
 pub mod play_history_item;
+pub mod playlist;
+pub mod playlist_item;
+pub mod playlist_pre_shuffle_checkpoint;
 pub mod relay_session;
+pub mod resumable_upload;
+pub mod resumable_upload_part;
 pub mod station;
+pub mod station_dj;
 pub mod station_files_pre_shuffle_checkpoint;
 pub mod station_picture;
 pub mod station_picture_variant;
+pub mod station_schedule;
 pub mod stream_connection;
 // pub mod token_user_email_confirmation;
 pub mod token_user_recovery;
 pub mod transfer_checkpoint;
 pub mod user;
 pub mod user_account_relation;
+pub mod webhook;
+pub mod webhook_delivery;
 
 pub mod sent_email;
 
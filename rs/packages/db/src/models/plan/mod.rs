@@ -26,6 +26,10 @@ pub struct Plan {
   pub display_name: String,
   pub price: f64,
   pub limits: PlanLimits,
+  #[serde(default)]
+  pub listeners_limit_policy: ListenersLimitPolicy,
+  #[serde(default)]
+  pub transfer_overage_policy: TransferOveragePolicy,
   pub color: String,
   pub order: f64,
   pub is_user_selectable: bool,
@@ -76,6 +80,36 @@ impl Plan {
   }
 }
 
+/// what the stream server does with new listeners
+/// when the account reached the listeners limit of the plan
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub enum ListenersLimitPolicy {
+  /// respond with a 503 status code and a retry-after header
+  #[default]
+  Reject,
+  /// respond with a short "station at capacity" audio message
+  /// (if not configured in the stream server it fallbacks to reject)
+  CapacityMessage,
+}
+
+/// what the stream server does when the account used all the transfer
+/// of its current billing period
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub enum TransferOveragePolicy {
+  /// reject new listeners and cut off the open connections
+  /// until the start of the next billing period
+  #[default]
+  Block,
+  /// keep streaming, the overage is recorded in the billing period
+  Allow,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
 #[ts(export, export_to = "../../../defs/db/")]
 #[serde(rename_all = "snake_case")]
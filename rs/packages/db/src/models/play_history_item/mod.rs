@@ -1,14 +1,18 @@
-use mongodb::bson::doc;
+use mongodb::bson::{doc, Document};
 use mongodb::IndexModel;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_util::DateTime;
 use ts_rs::TS;
 
+use crate::station_dj::StationDjRef;
 use crate::Model;
 
+pub mod royalty_report;
+
 crate::register!(PlayHistoryItem);
 
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
 #[ts(export, export_to = "../../../defs/db/")]
 #[serde(rename_all = "snake_case")]
 #[macros::keys]
@@ -31,18 +35,106 @@ pub struct PlayHistoryItem {
   pub created_at: DateTime,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
 #[ts(
   export,
   export_to = "../../../defs/db/",
   rename = "PlayHistoryItemKind"
 )]
+#[schemars(rename = "PlayHistoryItemKind")]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "kind")]
 #[macros::keys]
 pub enum Kind {
-  Live,
-  Playlist { file_id: String },
+  Live {
+    /// the dj of the live session, None for the station source password
+    #[serde(default)]
+    dj: Option<StationDjRef>,
+  },
+  Playlist {
+    file_id: String,
+  },
+}
+
+impl PlayHistoryItem {
+  /// filter of the play history of a station
+  ///
+  /// since is inclusive and until is exclusive,
+  /// search matches the title or the artist, case insensitive
+  pub fn filter(
+    station_id: &str,
+    since: Option<DateTime>,
+    until: Option<DateTime>,
+    search: Option<&str>,
+  ) -> Document {
+    let mut filter = doc! { Self::KEY_STATION_ID: station_id };
+
+    match (since, until) {
+      (None, None) => {}
+      (Some(since), None) => {
+        filter.insert(Self::KEY_CREATED_AT, doc! { "$gte": since });
+      }
+      (None, Some(until)) => {
+        filter.insert(Self::KEY_CREATED_AT, doc! { "$lt": until });
+      }
+      (Some(since), Some(until)) => {
+        filter.insert(Self::KEY_CREATED_AT, doc! { "$gte": since, "$lt": until });
+      }
+    }
+
+    if let Some(search) = search.map(str::trim).filter(|s| !s.is_empty()) {
+      let regex = doc! { "$regex": escape_regex(search), "$options": "i" };
+      filter.insert(
+        "$or",
+        vec![
+          doc! { Self::KEY_TITLE: regex.clone() },
+          doc! { Self::KEY_ARTIST: regex },
+        ],
+      );
+    }
+
+    filter
+  }
+}
+
+/// escapes the regex metacharacters of a search so it matches literally
+fn escape_regex(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len());
+  for c in s.chars() {
+    if matches!(
+      c,
+      '\\' | '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$'
+    ) {
+      escaped.push('\\');
+    }
+    escaped.push(c);
+  }
+  escaped
+}
+
+/// prefixes the values that spreadsheets would run as formulas
+fn neutralize_formula(value: &str) -> String {
+  match value.chars().next() {
+    Some('=' | '+' | '-' | '@' | '\t' | '\r') => format!("'{value}"),
+    _ => value.to_string(),
+  }
+}
+
+/// quotes a csv field of the play history exports if needed,
+/// and neutralizes values that spreadsheets would run as formulas
+pub fn csv_field(value: &str) -> String {
+  let value = neutralize_formula(value);
+  if value.contains([',', '"', '\r', '\n']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value
+  }
+}
+
+/// replaces the separators in a tsv field of the play history exports,
+/// and neutralizes values that spreadsheets would run as formulas
+pub fn tsv_field(value: &str) -> String {
+  neutralize_formula(value).replace(['\t', '\r', '\n'], " ")
 }
 
 impl Model for PlayHistoryItem {
@@ -74,4 +166,43 @@ mod test {
   fn keys_match() {
     assert_eq!(crate::KEY_ID, PlayHistoryItem::KEY_ID);
   }
+
+  #[test]
+  fn escape_regex_metacharacters() {
+    assert_eq!(escape_regex("AC/DC"), "AC/DC");
+    assert_eq!(escape_regex("What? (Live)"), r"What\? \(Live\)");
+    assert_eq!(escape_regex(r"a.b*c\d"), r"a\.b\*c\\d");
+  }
+
+  #[test]
+  fn csv_fields() {
+    assert_eq!(csv_field("Song"), "Song");
+    assert_eq!(csv_field("Hello, World"), "\"Hello, World\"");
+    assert_eq!(csv_field("The \"Best\""), "\"The \"\"Best\"\"\"");
+    assert_eq!(csv_field("=SUM(A1)"), "'=SUM(A1)");
+    assert_eq!(csv_field(""), "");
+  }
+
+  #[test]
+  fn tsv_fields() {
+    assert_eq!(tsv_field("Hello, World"), "Hello, World");
+    assert_eq!(tsv_field("a\tb\nc"), "a b c");
+    assert_eq!(tsv_field("@handle"), "'@handle");
+  }
+
+  #[test]
+  fn filter_range_and_search() {
+    let filter = PlayHistoryItem::filter("station", None, None, Some("  "));
+    assert_eq!(filter, doc! { PlayHistoryItem::KEY_STATION_ID: "station" });
+
+    let since = DateTime::now();
+    let filter = PlayHistoryItem::filter("station", Some(since), None, Some("song"));
+    assert_eq!(
+      filter
+        .get_document(PlayHistoryItem::KEY_CREATED_AT)
+        .unwrap(),
+      &doc! { "$gte": since }
+    );
+    assert_eq!(filter.get_array("$or").unwrap().len(), 2);
+  }
 }
@@ -0,0 +1,820 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use constants::{
+  ROYALTY_REPORT_MAX_PLAY_DURATION_SECS, ROYALTY_REPORT_MAX_RANGE_DAYS,
+  ROYALTY_REPORT_MIN_SESSION_SECS,
+};
+use futures_util::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::{FindOneOptions, FindOptions};
+use mongodb::Cursor;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{OffsetDateTime, UtcOffset};
+
+use super::{Kind, PlayHistoryItem};
+use crate::deployment::Deployment;
+use crate::stream_connection::lite::StreamConnectionLite;
+use crate::Model;
+
+/// a column of a royalty report, the header of the column is its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoyaltyReportColumn {
+  /// date of the start of the play, in the offset of the report
+  Date,
+  /// time of the start of the play, in the offset of the report
+  Time,
+  StartedAt,
+  EndedAt,
+  DurationSecs,
+  Title,
+  Artist,
+  Kind,
+  FileId,
+  /// number of listeners that heard at least a part of the play
+  Listeners,
+  /// aggregate tuning hours, the sum of the time each listener heard of the play
+  TuningHours,
+}
+
+impl RoyaltyReportColumn {
+  pub const ALL: [Self; 11] = [
+    Self::Date,
+    Self::Time,
+    Self::StartedAt,
+    Self::EndedAt,
+    Self::DurationSecs,
+    Self::Title,
+    Self::Artist,
+    Self::Kind,
+    Self::FileId,
+    Self::Listeners,
+    Self::TuningHours,
+  ];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Date => "date",
+      Self::Time => "time",
+      Self::StartedAt => "started_at",
+      Self::EndedAt => "ended_at",
+      Self::DurationSecs => "duration_secs",
+      Self::Title => "title",
+      Self::Artist => "artist",
+      Self::Kind => "kind",
+      Self::FileId => "file_id",
+      Self::Listeners => "listeners",
+      Self::TuningHours => "tuning_hours",
+    }
+  }
+
+  /// parses a comma separated list of columns
+  pub fn parse_list(s: &str) -> Result<Vec<Self>, UnknownValueError> {
+    s.split(',')
+      .map(str::trim)
+      .filter(|s| !s.is_empty())
+      .map(Self::from_str)
+      .collect()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoyaltyReportFormat {
+  #[default]
+  Csv,
+  Tsv,
+}
+
+impl RoyaltyReportFormat {
+  pub const ALL: [Self; 2] = [Self::Csv, Self::Tsv];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Csv => "csv",
+      Self::Tsv => "tsv",
+    }
+  }
+
+  pub fn content_type(self) -> &'static str {
+    match self {
+      Self::Csv => "text/csv;charset=utf-8",
+      Self::Tsv => "text/tab-separated-values;charset=utf-8",
+    }
+  }
+
+  fn delimiter(self) -> char {
+    match self {
+      Self::Csv => ',',
+      Self::Tsv => '\t',
+    }
+  }
+
+  /// escapes a field for the format, and neutralizes values that spreadsheets would run as formulas
+  pub fn field(self, value: &str) -> String {
+    match self {
+      Self::Csv => super::csv_field(value),
+      Self::Tsv => super::tsv_field(value),
+    }
+  }
+}
+
+/// predefined layouts of royalty reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoyaltyReportPreset {
+  /// all the columns
+  #[default]
+  Detailed,
+  /// one line per play with artist, title, start and aggregate tuning hours, tab separated
+  TuningHours,
+  /// one line per play with date, time, duration, title, artist and listeners,
+  /// as requested by most collecting societies
+  CollectingSociety,
+}
+
+impl RoyaltyReportPreset {
+  pub const ALL: [Self; 3] = [Self::Detailed, Self::TuningHours, Self::CollectingSociety];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Detailed => "detailed",
+      Self::TuningHours => "tuning_hours",
+      Self::CollectingSociety => "collecting_society",
+    }
+  }
+
+  pub fn format(self) -> RoyaltyReportFormat {
+    match self {
+      Self::Detailed => RoyaltyReportFormat::Csv,
+      Self::TuningHours => RoyaltyReportFormat::Tsv,
+      Self::CollectingSociety => RoyaltyReportFormat::Csv,
+    }
+  }
+
+  pub fn columns(self) -> Vec<RoyaltyReportColumn> {
+    use RoyaltyReportColumn::*;
+    match self {
+      Self::Detailed => vec![
+        StartedAt,
+        EndedAt,
+        DurationSecs,
+        Title,
+        Artist,
+        Kind,
+        FileId,
+        Listeners,
+        TuningHours,
+      ],
+      Self::TuningHours => vec![Artist, Title, Date, Time, TuningHours],
+      Self::CollectingSociety => vec![Date, Time, DurationSecs, Title, Artist, Listeners],
+    }
+  }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown value {value}, expected one of {expected}")]
+pub struct UnknownValueError {
+  pub value: String,
+  pub expected: String,
+}
+
+macro_rules! impl_from_str {
+  ($ty:ty) => {
+    impl FromStr for $ty {
+      type Err = UnknownValueError;
+      fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::ALL.iter().find(|item| item.name() == s) {
+          Some(item) => Ok(*item),
+          None => Err(UnknownValueError {
+            value: s.to_string(),
+            expected: Self::ALL.map(|item| item.name()).join(", "),
+          }),
+        }
+      }
+    }
+
+    impl Display for $ty {
+      fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+      }
+    }
+  };
+}
+
+impl_from_str!(RoyaltyReportColumn);
+impl_from_str!(RoyaltyReportFormat);
+impl_from_str!(RoyaltyReportPreset);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RoyaltyReportLayoutError {
+  #[error("a royalty report must have at least one column")]
+  NoColumns,
+  #[error("utc offset must be between -840 and 840 minutes")]
+  UtcOffset,
+}
+
+/// how the rows of a royalty report are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoyaltyReportLayout {
+  pub format: RoyaltyReportFormat,
+  pub columns: Vec<RoyaltyReportColumn>,
+  /// offset of the dates and times of the report
+  pub utc_offset: UtcOffset,
+}
+
+impl RoyaltyReportLayout {
+  /// the layout of the preset (detailed if not specified),
+  /// with the format and the columns replaced by the ones that are specified
+  pub fn new(
+    preset: Option<RoyaltyReportPreset>,
+    format: Option<RoyaltyReportFormat>,
+    columns: Option<Vec<RoyaltyReportColumn>>,
+    utc_offset_minutes: Option<i32>,
+  ) -> Result<Self, RoyaltyReportLayoutError> {
+    let preset = preset.unwrap_or_default();
+    let format = format.unwrap_or_else(|| preset.format());
+    let columns = columns.unwrap_or_else(|| preset.columns());
+
+    if columns.is_empty() {
+      return Err(RoyaltyReportLayoutError::NoColumns);
+    }
+
+    let utc_offset_minutes = utc_offset_minutes.unwrap_or(0);
+    if !(-840..=840).contains(&utc_offset_minutes) {
+      return Err(RoyaltyReportLayoutError::UtcOffset);
+    }
+
+    let utc_offset = UtcOffset::from_whole_seconds(utc_offset_minutes * 60)
+      .map_err(|_| RoyaltyReportLayoutError::UtcOffset)?;
+
+    Ok(Self {
+      format,
+      columns,
+      utc_offset,
+    })
+  }
+
+  fn line<I: IntoIterator<Item = String>>(&self, fields: I) -> String {
+    let mut line = fields
+      .into_iter()
+      .collect::<Vec<String>>()
+      .join(&self.format.delimiter().to_string());
+    line.push_str("\r\n");
+    line
+  }
+
+  pub fn header(&self) -> String {
+    self.line(self.columns.iter().map(|column| column.name().to_string()))
+  }
+
+  pub fn row(&self, row: &RoyaltyReportRow) -> String {
+    let started_at = row.started_at.inner().to_offset(self.utc_offset);
+    let ended_at = row.ended_at.inner().to_offset(self.utc_offset);
+
+    self.line(self.columns.iter().map(|column| {
+      match column {
+        RoyaltyReportColumn::Date => started_at
+          .format(format_description!("[year]-[month]-[day]"))
+          .unwrap_or_default(),
+        RoyaltyReportColumn::Time => started_at
+          .format(format_description!("[hour]:[minute]:[second]"))
+          .unwrap_or_default(),
+        RoyaltyReportColumn::StartedAt => started_at.format(&Rfc3339).unwrap_or_default(),
+        RoyaltyReportColumn::EndedAt => ended_at.format(&Rfc3339).unwrap_or_default(),
+        RoyaltyReportColumn::DurationSecs => (row.duration_ms() / 1000).to_string(),
+        RoyaltyReportColumn::Title => self.format.field(&row.title),
+        RoyaltyReportColumn::Artist => self.format.field(row.artist.as_deref().unwrap_or("")),
+        RoyaltyReportColumn::Kind => match row.kind {
+          Kind::Live { .. } => String::from("live"),
+          Kind::Playlist { .. } => String::from("playlist"),
+        },
+        RoyaltyReportColumn::FileId => match &row.kind {
+          Kind::Live { .. } => String::new(),
+          Kind::Playlist { file_id } => self.format.field(file_id),
+        },
+        RoyaltyReportColumn::Listeners => row.listeners.to_string(),
+        RoyaltyReportColumn::TuningHours => format!("{:.4}", row.tuning_hours()),
+      }
+    }))
+  }
+}
+
+/// a play of the report with the listening it had
+#[derive(Debug, Clone)]
+pub struct RoyaltyReportRow {
+  pub started_at: DateTime,
+  pub ended_at: DateTime,
+  pub title: String,
+  pub artist: Option<String>,
+  pub kind: Kind,
+  pub listeners: u64,
+  /// sum of the time that each listener was connected during the play
+  pub tuning_ms: u64,
+}
+
+impl RoyaltyReportRow {
+  pub fn duration_ms(&self) -> u64 {
+    (ms(self.ended_at) - ms(self.started_at)).max(0) as u64
+  }
+
+  pub fn tuning_hours(&self) -> f64 {
+    self.tuning_ms as f64 / 3_600_000.0
+  }
+}
+
+/// the time a stream connection was open, in milliseconds since the unix epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session {
+  pub start: i64,
+  pub end: i64,
+}
+
+fn ms(date: DateTime) -> i64 {
+  date.timestamp_millis() as i64
+}
+
+fn from_ms(ms: i64) -> DateTime {
+  DateTime::from(OffsetDateTime::from_unix_timestamp_nanos(ms as i128 * 1_000_000).unwrap())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RoyaltyReportRangeError {
+  #[error("until must be after since")]
+  Empty,
+  #[error("the range of a royalty report can be at most {ROYALTY_REPORT_MAX_RANGE_DAYS} days")]
+  TooLong,
+}
+
+pub fn check_range(since: DateTime, until: DateTime) -> Result<(), RoyaltyReportRangeError> {
+  let range = ms(until) - ms(since);
+  if range <= 0 {
+    Err(RoyaltyReportRangeError::Empty)
+  } else if range > ROYALTY_REPORT_MAX_RANGE_DAYS as i64 * 24 * 60 * 60 * 1000 {
+    Err(RoyaltyReportRangeError::TooLong)
+  } else {
+    Ok(())
+  }
+}
+
+/// the end of a play: the start of the next one, up to ROYALTY_REPORT_MAX_PLAY_DURATION_SECS
+/// and never after `end`
+fn play_end(start: i64, next_start: Option<i64>, end: i64) -> i64 {
+  let max_play_ms = ROYALTY_REPORT_MAX_PLAY_DURATION_SECS as i64 * 1000;
+  next_start
+    .unwrap_or(end)
+    .min(start + max_play_ms)
+    .min(end)
+    .max(start)
+}
+
+/// the sessions that can overlap the current play of a sweep over the plays sorted by start
+#[derive(Debug, Default)]
+struct ActiveSessions {
+  sessions: Vec<Session>,
+}
+
+impl ActiveSessions {
+  /// sessions must be pushed sorted by start
+  fn push(&mut self, session: Session) {
+    if session.end - session.start >= ROYALTY_REPORT_MIN_SESSION_SECS as i64 * 1000 {
+      self.sessions.push(session);
+    }
+  }
+
+  fn row(&mut self, play: PlayHistoryItem, start: i64, play_end: i64) -> RoyaltyReportRow {
+    // plays start in order, so the sessions that ended before this play are not needed anymore
+    self.sessions.retain(|session| session.end > start);
+
+    let mut listeners = 0;
+    let mut tuning_ms = 0;
+    for session in self.sessions.iter() {
+      let overlap = session.end.min(play_end) - session.start.max(start);
+      if overlap > 0 {
+        listeners += 1;
+        tuning_ms += overlap as u64;
+      }
+    }
+
+    RoyaltyReportRow {
+      started_at: play.created_at,
+      ended_at: from_ms(play_end),
+      title: play.title,
+      artist: play.artist,
+      kind: play.kind,
+      listeners,
+      tuning_ms,
+    }
+  }
+}
+
+/// joins the plays with the listening sessions
+///
+/// plays must be sorted by start, a play lasts until the next one
+/// (or `next_play_start` for the last one), up to ROYALTY_REPORT_MAX_PLAY_DURATION_SECS and never after `end`
+pub fn compute_rows(
+  plays: Vec<PlayHistoryItem>,
+  next_play_start: Option<DateTime>,
+  mut sessions: Vec<Session>,
+  end: DateTime,
+) -> Vec<RoyaltyReportRow> {
+  let end = ms(end);
+
+  sessions.sort_by_key(|session| session.start);
+  let mut sessions = sessions.into_iter().peekable();
+
+  let starts: Vec<i64> = plays.iter().map(|play| ms(play.created_at)).collect();
+
+  let mut rows = Vec::with_capacity(plays.len());
+  let mut active = ActiveSessions::default();
+
+  for (i, play) in plays.into_iter().enumerate() {
+    let start = starts[i];
+    let next = match starts.get(i + 1) {
+      Some(next) => Some(*next),
+      None => next_play_start.map(ms),
+    };
+
+    let play_end = play_end(start, next, end);
+
+    while let Some(session) = sessions.next_if(|session| session.start < play_end) {
+      active.push(session);
+    }
+
+    rows.push(active.row(play, start, play_end));
+  }
+
+  rows
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[macros::keys]
+struct SessionItem {
+  #[serde(rename = "dp")]
+  deployment_id: Option<String>,
+
+  #[serde(rename = "op")]
+  is_open: bool,
+
+  #[serde(rename = "du")]
+  #[serde(with = "serde_util::as_f64::option")]
+  duration_ms: Option<u64>,
+
+  #[serde(rename = "ca")]
+  created_at: DateTime,
+
+  #[serde(rename = "cl")]
+  closed_at: Option<DateTime>,
+}
+
+impl SessionItem {
+  fn projection() -> mongodb::bson::Document {
+    doc! {
+      crate::KEY_ID: 0,
+      SessionItem::KEY_DEPLOYMENT_ID: 1,
+      SessionItem::KEY_IS_OPEN: 1,
+      SessionItem::KEY_DURATION_MS: 1,
+      SessionItem::KEY_CREATED_AT: 1,
+      SessionItem::KEY_CLOSED_AT: 1,
+    }
+  }
+}
+
+/// the rows of a royalty report, computed while the plays and the sessions
+/// are read from the database so the memory used doesn't grow with the range
+pub struct RoyaltyReportRows {
+  plays: Cursor<PlayHistoryItem>,
+  sessions: Cursor<SessionItem>,
+  /// the row of a play is computed when the start of the next play is known
+  current: Option<PlayHistoryItem>,
+  next_play_start: Option<DateTime>,
+  /// the first session read that starts after the end of the current play
+  next_session: Option<Session>,
+  active: ActiveSessions,
+  /// last health check of the deployments of the open sessions, by deployment id
+  health_checked_at: HashMap<String, Option<i64>>,
+  end: i64,
+  now: i64,
+}
+
+impl RoyaltyReportRows {
+  /// the next row of the report, None when all the plays were read
+  pub async fn next(&mut self) -> Result<Option<RoyaltyReportRow>, mongodb::error::Error> {
+    let play = match self.current.take() {
+      Some(play) => play,
+      None => return Ok(None),
+    };
+
+    self.current = self.plays.try_next().await?;
+
+    let next_start = match &self.current {
+      Some(next) => Some(ms(next.created_at)),
+      None => self.next_play_start.map(ms),
+    };
+
+    let start = ms(play.created_at);
+    let play_end = play_end(start, next_start, self.end);
+
+    loop {
+      let session = match self.next_session.take() {
+        Some(session) => session,
+        None => match self.sessions.try_next().await? {
+          Some(item) => match self.session(item).await? {
+            Some(session) => session,
+            None => continue,
+          },
+          None => break,
+        },
+      };
+
+      if session.start >= play_end {
+        self.next_session = Some(session);
+        break;
+      }
+
+      self.active.push(session);
+    }
+
+    Ok(Some(self.active.row(play, start, play_end)))
+  }
+
+  async fn session(&mut self, item: SessionItem) -> Result<Option<Session>, mongodb::error::Error> {
+    let start = ms(item.created_at);
+
+    let end = if let Some(closed_at) = item.closed_at {
+      ms(closed_at)
+    } else if !item.is_open {
+      match item.duration_ms {
+        Some(duration_ms) => start + duration_ms as i64,
+        None => return Ok(None),
+      }
+    } else {
+      // a connection can be left open by a deployment that stopped abnormally
+      // until the health check closes it, it is counted only while its deployment was alive
+      match item.deployment_id {
+        None => self.now,
+        Some(deployment_id) => match self.deployment_health_checked_at(deployment_id).await? {
+          Some(health_checked_at) => health_checked_at.min(self.now),
+          None => start,
+        },
+      }
+    };
+
+    Ok(Some(Session { start, end }))
+  }
+
+  async fn deployment_health_checked_at(
+    &mut self,
+    deployment_id: String,
+  ) -> Result<Option<i64>, mongodb::error::Error> {
+    if let Some(health_checked_at) = self.health_checked_at.get(&deployment_id) {
+      return Ok(*health_checked_at);
+    }
+
+    let health_checked_at = Deployment::get_by_id(&deployment_id)
+      .await?
+      .and_then(|deployment| deployment.health_checked_at)
+      .map(ms);
+
+    self
+      .health_checked_at
+      .insert(deployment_id, health_checked_at);
+
+    Ok(health_checked_at)
+  }
+}
+
+/// the royalty report rows of the plays of a station between since (inclusive) and until (exclusive)
+pub async fn generate(
+  station_id: &str,
+  since: DateTime,
+  until: DateTime,
+) -> Result<RoyaltyReportRows, mongodb::error::Error> {
+  let now = DateTime::now();
+
+  let filter = PlayHistoryItem::filter(station_id, Some(since), Some(until), None);
+  let options = FindOptions::builder()
+    .sort(doc! { PlayHistoryItem::KEY_CREATED_AT: 1 })
+    .build();
+
+  let mut plays = PlayHistoryItem::cl().find(filter, options).await?;
+
+  let current = plays.try_next().await?;
+
+  let filter = PlayHistoryItem::filter(station_id, Some(until), None, None);
+  let options = FindOneOptions::builder()
+    .sort(doc! { PlayHistoryItem::KEY_CREATED_AT: 1 })
+    .build();
+
+  let next_play_start = PlayHistoryItem::cl()
+    .find_one(filter, options)
+    .await?
+    .map(|item| item.created_at);
+
+  // the sessions that started before the range are included if they were still open at its start
+  let filter = doc! {
+    StreamConnectionLite::KEY_STATION_ID: station_id,
+    StreamConnectionLite::KEY_CREATED_AT: { "$lt": until },
+    "$or": [
+      { StreamConnectionLite::KEY_CREATED_AT: { "$gte": since } },
+      { StreamConnectionLite::KEY_IS_OPEN: true },
+      { StreamConnectionLite::KEY_CLOSED_AT: { "$gt": since } },
+    ],
+  };
+
+  let options = FindOptions::builder()
+    .sort(doc! { StreamConnectionLite::KEY_CREATED_AT: 1 })
+    .projection(SessionItem::projection())
+    .build();
+
+  let sessions = StreamConnectionLite::cl_as::<SessionItem>()
+    .find(filter, options)
+    .await?;
+
+  let end = if ms(until) < ms(now) { until } else { now };
+
+  Ok(RoyaltyReportRows {
+    plays,
+    sessions,
+    current,
+    next_play_start,
+    next_session: None,
+    active: ActiveSessions::default(),
+    health_checked_at: HashMap::new(),
+    end: ms(end),
+    now: ms(now),
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn session_item_keys_match() {
+    assert_eq!(
+      StreamConnectionLite::KEY_DEPLOYMENT_ID,
+      SessionItem::KEY_DEPLOYMENT_ID
+    );
+    assert_eq!(StreamConnectionLite::KEY_IS_OPEN, SessionItem::KEY_IS_OPEN);
+    assert_eq!(
+      StreamConnectionLite::KEY_DURATION_MS,
+      SessionItem::KEY_DURATION_MS
+    );
+    assert_eq!(
+      StreamConnectionLite::KEY_CREATED_AT,
+      SessionItem::KEY_CREATED_AT
+    );
+    assert_eq!(
+      StreamConnectionLite::KEY_CLOSED_AT,
+      SessionItem::KEY_CLOSED_AT
+    );
+  }
+
+  const MIN: i64 = 60 * 1000;
+
+  fn play(title: &str, start: i64) -> PlayHistoryItem {
+    PlayHistoryItem {
+      id: title.into(),
+      station_id: "station".into(),
+      deployment_id: "deployment".into(),
+      title: title.into(),
+      artist: Some("Artist".into()),
+      kind: Kind::Live { dj: None },
+      created_at: from_ms(start),
+    }
+  }
+
+  fn session(start: i64, end: i64) -> Session {
+    Session { start, end }
+  }
+
+  #[test]
+  fn rows_listeners_and_tuning() {
+    let plays = vec![play("a", 0), play("b", 4 * MIN)];
+    let sessions = vec![
+      // the whole time
+      session(-10 * MIN, 100 * MIN),
+      // only the second play
+      session(5 * MIN, 7 * MIN),
+      // the end of the first play and the start of the second
+      session(3 * MIN, 6 * MIN),
+      // too short to count
+      session(MIN, MIN + 1000),
+      // before the plays
+      session(-5 * MIN, -MIN),
+    ];
+
+    let rows = compute_rows(plays, Some(from_ms(8 * MIN)), sessions, from_ms(1000 * MIN));
+
+    assert_eq!(rows.len(), 2);
+
+    assert_eq!(rows[0].duration_ms(), 4 * MIN as u64);
+    assert_eq!(rows[0].listeners, 2);
+    assert_eq!(rows[0].tuning_ms, (4 * MIN + MIN) as u64);
+
+    assert_eq!(rows[1].duration_ms(), 4 * MIN as u64);
+    assert_eq!(rows[1].listeners, 3);
+    assert_eq!(rows[1].tuning_ms, (4 * MIN + 2 * MIN + 2 * MIN) as u64);
+  }
+
+  #[test]
+  fn rows_play_end_is_capped() {
+    let max = ROYALTY_REPORT_MAX_PLAY_DURATION_SECS as i64 * 1000;
+
+    let rows = compute_rows(vec![play("a", 0)], None, vec![], from_ms(max * 10));
+    assert_eq!(rows[0].duration_ms(), max as u64);
+
+    let rows = compute_rows(vec![play("a", 0)], None, vec![], from_ms(MIN));
+    assert_eq!(rows[0].duration_ms(), MIN as u64);
+  }
+
+  #[test]
+  fn layout_rendering() {
+    let layout = RoyaltyReportLayout::new(
+      Some(RoyaltyReportPreset::CollectingSociety),
+      None,
+      None,
+      Some(-180),
+    )
+    .unwrap();
+
+    let row = RoyaltyReportRow {
+      started_at: from_ms(0),
+      ended_at: from_ms(3 * MIN + 30 * 1000),
+      title: "Hello, World".into(),
+      artist: None,
+      kind: Kind::Live { dj: None },
+      listeners: 2,
+      tuning_ms: 3_600_000,
+    };
+
+    assert_eq!(
+      layout.header(),
+      "date,time,duration_secs,title,artist,listeners\r\n"
+    );
+    assert_eq!(
+      layout.row(&row),
+      "1969-12-31,21:00:00,210,\"Hello, World\",,2\r\n"
+    );
+
+    let layout = RoyaltyReportLayout::new(
+      Some(RoyaltyReportPreset::TuningHours),
+      None,
+      Some(vec![
+        RoyaltyReportColumn::Title,
+        RoyaltyReportColumn::TuningHours,
+      ]),
+      None,
+    )
+    .unwrap();
+
+    assert_eq!(layout.header(), "title\ttuning_hours\r\n");
+    assert_eq!(layout.row(&row), "Hello, World\t1.0000\r\n");
+  }
+
+  #[test]
+  fn layout_errors() {
+    assert!(RoyaltyReportLayout::new(None, None, Some(vec![]), None).is_err());
+    assert!(RoyaltyReportLayout::new(None, None, None, Some(841)).is_err());
+    assert!(RoyaltyReportLayout::new(None, None, None, Some(-840)).is_ok());
+  }
+
+  #[test]
+  fn parse_names() {
+    assert_eq!(
+      RoyaltyReportColumn::parse_list("date, title,tuning_hours").unwrap(),
+      vec![
+        RoyaltyReportColumn::Date,
+        RoyaltyReportColumn::Title,
+        RoyaltyReportColumn::TuningHours
+      ]
+    );
+    assert!(RoyaltyReportColumn::parse_list("date,isrc").is_err());
+    assert_eq!(
+      "tuning_hours".parse::<RoyaltyReportPreset>().unwrap(),
+      RoyaltyReportPreset::TuningHours
+    );
+    assert_eq!(
+      "tsv".parse::<RoyaltyReportFormat>().unwrap(),
+      RoyaltyReportFormat::Tsv
+    );
+  }
+
+  #[test]
+  fn ranges() {
+    let since = from_ms(0);
+    assert!(check_range(since, from_ms(MIN)).is_ok());
+    assert!(check_range(since, since).is_err());
+    assert!(check_range(
+      since,
+      from_ms((ROYALTY_REPORT_MAX_RANGE_DAYS as i64 + 1) * 24 * 60 * MIN)
+    )
+    .is_err());
+  }
+}
@@ -0,0 +1,148 @@
+use crate::playlist_item::PlaylistItem;
+use crate::playlist_pre_shuffle_checkpoint::PlaylistPreShuffleCheckpoint;
+use crate::station::Station;
+use crate::{run_transaction, Model};
+use constants::validate::*;
+use modify::Modify;
+use mongodb::bson::doc;
+use mongodb::{ClientSession, IndexModel};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+use validator::Validate;
+
+crate::register!(Playlist);
+
+/// a named playlist of a station
+///
+/// the files of the playlist and their order are stored as PlaylistItem documents
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct Playlist {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub station_id: String,
+
+  #[modify(trim)]
+  #[validate(
+    length(
+      min = "VALIDATE_PLAYLIST_NAME_MIN_LEN",
+      max = "VALIDATE_PLAYLIST_NAME_MAX_LEN",
+      message = "Playlist name is empty or too long"
+    ),
+    non_control_character(message = "Playlist name cannot have control characters")
+  )]
+  pub name: String,
+
+  /// other playlists of the station interleaved with this one while it plays
+  #[validate(length(
+    max = "VALIDATE_PLAYLIST_ROTATIONS_MAX_LEN",
+    message = "Too many playlist rotations"
+  ))]
+  #[validate]
+  pub rotations: Vec<PlaylistRotation>,
+
+  pub is_randomly_shuffled: bool,
+
+  pub created_at: DateTime,
+  pub updated_at: DateTime,
+}
+
+/// plays one file of `playlist_id` after every `every` files of the playlist
+/// eg: a jingles playlist with every = 4 plays one jingle every 4 songs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema, Validate)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct PlaylistRotation {
+  pub playlist_id: String,
+  #[validate(range(
+    min = "VALIDATE_PLAYLIST_ROTATION_EVERY_MIN",
+    max = "VALIDATE_PLAYLIST_ROTATION_EVERY_MAX",
+    message = "Playlist rotation interval is invalid"
+  ))]
+  pub every: u16,
+}
+
+impl Playlist {
+  /// deletes the playlist with its items and its pre shuffle checkpoint
+  /// and removes it from the rotations of other playlists and from the station default
+  pub async fn delete_playlist_with_session(
+    station_id: &str,
+    playlist_id: &str,
+    session: &mut ClientSession,
+  ) -> Result<Option<Playlist>, mongodb::error::Error> {
+    let filter = doc! { Playlist::KEY_ID: playlist_id, Playlist::KEY_STATION_ID: station_id };
+    let playlist = match Self::cl()
+      .find_one_and_delete_with_session(filter, None, session)
+      .await?
+    {
+      None => return Ok(None),
+      Some(playlist) => playlist,
+    };
+
+    let filter = doc! { PlaylistItem::KEY_PLAYLIST_ID: playlist_id };
+    PlaylistItem::cl()
+      .delete_many_with_session(filter, None, session)
+      .await?;
+
+    PlaylistPreShuffleCheckpoint::delete_by_id_with_session(playlist_id, session).await?;
+
+    const KEY_ROTATION_PLAYLIST_ID: &str =
+      crate::key!(Playlist::KEY_ROTATIONS, PlaylistRotation::KEY_PLAYLIST_ID);
+
+    let filter =
+      doc! { Playlist::KEY_STATION_ID: station_id, KEY_ROTATION_PLAYLIST_ID: playlist_id };
+    let update = doc! {
+      "$pull": { Playlist::KEY_ROTATIONS: { PlaylistRotation::KEY_PLAYLIST_ID: playlist_id } }
+    };
+    Self::cl()
+      .update_many_with_session(filter, update, None, session)
+      .await?;
+
+    let filter =
+      doc! { Station::KEY_ID: station_id, Station::KEY_DEFAULT_PLAYLIST_ID: playlist_id };
+    let update = doc! { "$set": { Station::KEY_DEFAULT_PLAYLIST_ID: null } };
+    Station::cl()
+      .update_one_with_session(filter, update, None, session)
+      .await?;
+
+    Ok(Some(playlist))
+  }
+
+  pub async fn delete_playlist(
+    station_id: &str,
+    playlist_id: &str,
+  ) -> Result<Option<Playlist>, mongodb::error::Error> {
+    run_transaction!(session => {
+      let playlist = tx_try!(Self::delete_playlist_with_session(station_id, playlist_id, &mut session).await);
+      Ok(playlist)
+    })
+  }
+}
+
+impl Model for Playlist {
+  const CL_NAME: &'static str = "playlists";
+  const UID_LEN: usize = 12;
+
+  fn indexes() -> Vec<IndexModel> {
+    let station_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1 })
+      .build();
+
+    vec![station_id]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, Playlist::KEY_ID);
+  }
+}
@@ -0,0 +1,183 @@
+use crate::Model;
+use mongodb::{
+  bson::{doc, Document},
+  options::{FindOneOptions, IndexOptions},
+  ClientSession, IndexModel,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+crate::register!(PlaylistItem);
+
+/// an audio file of the station in a named playlist
+/// a file can be at most once in each playlist
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct PlaylistItem {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub station_id: String,
+  pub playlist_id: String,
+  pub file_id: String,
+  /// the position of the file in the playlist, like AudioFile::order for the station files
+  pub order: f64,
+  pub created_at: DateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[macros::keys]
+pub struct ItemOrderDocument {
+  pub order: f64,
+}
+
+impl ItemOrderDocument {
+  pub fn projection() -> Document {
+    doc! { crate::KEY_ID: 0, ItemOrderDocument::KEY_ORDER: 1 }
+  }
+}
+
+impl PlaylistItem {
+  async fn find_order(
+    playlist_id: &str,
+    sort: i32,
+    session: Option<&mut ClientSession>,
+  ) -> Result<Option<f64>, mongodb::error::Error> {
+    let sort = doc! { PlaylistItem::KEY_ORDER: sort };
+    let options = FindOneOptions::builder()
+      .sort(sort)
+      .projection(ItemOrderDocument::projection())
+      .build();
+    let filter = doc! { PlaylistItem::KEY_PLAYLIST_ID: playlist_id };
+
+    let document = match session {
+      None => {
+        Self::cl_as::<ItemOrderDocument>()
+          .find_one(filter, options)
+          .await?
+      }
+      Some(session) => {
+        Self::cl_as::<ItemOrderDocument>()
+          .find_one_with_session(filter, options, session)
+          .await?
+      }
+    };
+
+    Ok(document.map(|doc| doc.order))
+  }
+
+  pub async fn next_max_order(
+    playlist_id: &str,
+    session: Option<&mut ClientSession>,
+  ) -> Result<f64, mongodb::error::Error> {
+    let n = match Self::find_order(playlist_id, -1, session).await? {
+      None => 1.0 + rand::random::<f64>(),
+      Some(order) => order + 1.0 + rand::random::<f64>(),
+    };
+
+    Ok(n)
+  }
+
+  pub async fn next_min_order(
+    playlist_id: &str,
+    session: Option<&mut ClientSession>,
+  ) -> Result<f64, mongodb::error::Error> {
+    let n = match Self::find_order(playlist_id, 1, session).await? {
+      None => -1.0 - rand::random::<f64>(),
+      Some(order) => order - 1.0 - rand::random::<f64>(),
+    };
+
+    Ok(n)
+  }
+
+  pub async fn playlist_first(
+    playlist_id: &str,
+  ) -> Result<Option<PlaylistItem>, mongodb::error::Error> {
+    let filter = doc! { Self::KEY_PLAYLIST_ID: playlist_id };
+    let sort = doc! { Self::KEY_ORDER: 1 };
+    let options = FindOneOptions::builder().sort(sort).build();
+    Self::cl().find_one(filter, options).await
+  }
+
+  /// the item that follows `current_file_id` in the playlist, wraps to the first item
+  pub async fn playlist_next(
+    playlist_id: &str,
+    current_file_id: &str,
+    current_order: f64,
+  ) -> Result<Option<PlaylistItem>, mongodb::error::Error> {
+    let filter = doc! { Self::KEY_PLAYLIST_ID: playlist_id, Self::KEY_FILE_ID: current_file_id };
+    let options = FindOneOptions::builder()
+      .projection(ItemOrderDocument::projection())
+      .build();
+    let current_order_document = Self::cl_as::<ItemOrderDocument>()
+      .find_one(filter, options)
+      .await?;
+
+    // if cant update the current order we use the last one (when the item was present)
+    let current_order = match current_order_document {
+      None => current_order,
+      Some(document) => document.order,
+    };
+
+    let filter =
+      doc! { Self::KEY_PLAYLIST_ID: playlist_id, Self::KEY_ORDER: { "$gt": current_order } };
+    let sort = doc! { Self::KEY_ORDER: 1 };
+    let options = FindOneOptions::builder().sort(sort).build();
+
+    match Self::cl().find_one(filter, options).await? {
+      None => Self::playlist_first(playlist_id).await,
+      Some(next) => Ok(Some(next)),
+    }
+  }
+
+  pub async fn delete_by_file_id_with_session(
+    file_id: &str,
+    session: &mut ClientSession,
+  ) -> Result<(), mongodb::error::Error> {
+    let filter = doc! { Self::KEY_FILE_ID: file_id };
+    Self::cl()
+      .delete_many_with_session(filter, None, session)
+      .await?;
+    Ok(())
+  }
+}
+
+impl Model for PlaylistItem {
+  const CL_NAME: &'static str = "playlist_items";
+  const UID_LEN: usize = 16;
+
+  fn indexes() -> Vec<IndexModel> {
+    let playlist_id_order = IndexModel::builder()
+      .keys(doc! { Self::KEY_PLAYLIST_ID: 1, Self::KEY_ORDER: 1 })
+      .build();
+
+    let unique_opts = IndexOptions::builder().unique(true).build();
+    let playlist_id_file_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_PLAYLIST_ID: 1, Self::KEY_FILE_ID: 1 })
+      .options(unique_opts)
+      .build();
+
+    let station_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1 })
+      .build();
+
+    let file_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_FILE_ID: 1 })
+      .build();
+
+    vec![playlist_id_order, playlist_id_file_id, station_id, file_id]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, PlaylistItem::KEY_ID);
+  }
+}
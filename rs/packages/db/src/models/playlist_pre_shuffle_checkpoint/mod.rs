@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+use crate::models::playlist::Playlist;
+use crate::Model;
+
+crate::register!(PlaylistPreShuffleCheckpoint);
+
+/// the order of the files of a playlist before it was shuffled
+/// the id is the id of the playlist
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(
+  export,
+  export_to = "../../../defs/db/",
+  rename = "PlaylistPreShuffleCheckpoint"
+)]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct PlaylistPreShuffleCheckpoint {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub file_ids: Vec<String>,
+  pub created_at: DateTime,
+}
+
+impl Model for PlaylistPreShuffleCheckpoint {
+  const CL_NAME: &'static str = "playlist_pre_shuffle_checkpoints";
+  const UID_LEN: usize = Playlist::UID_LEN;
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, PlaylistPreShuffleCheckpoint::KEY_ID);
+  }
+}
@@ -0,0 +1,335 @@
+use crate::audio_file::DuplicateMode;
+use crate::resumable_upload_part::ResumableUploadPart;
+use crate::Model;
+use log::*;
+use mongodb::bson::{doc, Document, SerializerOptions};
+use mongodb::IndexModel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+crate::register!(ResumableUpload);
+
+/// an upload that is received in many requests
+///
+/// the received bytes are stored as ResumableUploadPart documents in the storage db
+/// and are sent to the transcoder when the upload is committed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct ResumableUpload {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub station_id: String,
+  pub filename: String,
+
+  /// total size of the file in bytes, declared when the upload is created
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub len: u64,
+
+  /// amount of bytes received, the next append must start at this offset
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub offset: u64,
+
+  pub on_duplicate: DuplicateMode,
+  pub state: ResumableUploadState,
+  pub created_at: DateTime,
+  pub updated_at: DateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+#[macros::keys]
+pub enum ResumableUploadState {
+  /// receiving bytes
+  #[serde(rename = "open")]
+  Open,
+  /// the received bytes are being transcoded into an audio file
+  #[serde(rename = "committing")]
+  Committing,
+  /// the upload was stored as (or linked to) the audio file
+  #[serde(rename = "committed")]
+  Committed {
+    audio_file_id: String,
+    committed_at: DateTime,
+  },
+}
+
+impl From<ResumableUploadState> for mongodb::bson::Bson {
+  fn from(value: ResumableUploadState) -> Self {
+    mongodb::bson::to_bson_with_options(
+      &value,
+      SerializerOptions::builder().human_readable(false).build(),
+    )
+    .expect("error convering ResumableUploadState to Bson")
+  }
+}
+
+const KEY_STATE_KIND: &str = const_str::concat!(
+  ResumableUpload::KEY_STATE,
+  ".",
+  ResumableUploadState::KEY_ENUM_TAG
+);
+
+/// uploads not updated since `limit` that are not being committed
+fn expired_filter(limit: DateTime) -> Document {
+  doc! {
+    ResumableUpload::KEY_UPDATED_AT: { "$lt": limit },
+    KEY_STATE_KIND: { "$ne": ResumableUploadState::KEY_ENUM_VARIANT_COMMITTING },
+  }
+}
+
+/// committing uploads whose commit stopped touching them since `limit`
+fn stale_committing_filter(limit: DateTime) -> Document {
+  doc! {
+    ResumableUpload::KEY_UPDATED_AT: { "$lt": limit },
+    KEY_STATE_KIND: ResumableUploadState::KEY_ENUM_VARIANT_COMMITTING,
+  }
+}
+
+impl ResumableUpload {
+  pub const EXPIRE_JOB_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(60 * 10); // 10 min
+
+  /// a running commit updates the upload with this interval, see [`Self::touch_committing`]
+  pub const COMMITTING_TOUCH_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(60); // 1 min
+
+  /// a committing upload that was not touched in this time was interrupted (eg: by a crash of the server)
+  pub const COMMITTING_STALE_AFTER: time::Duration = time::Duration::minutes(10);
+
+  pub fn is_open(&self) -> bool {
+    matches!(self.state, ResumableUploadState::Open)
+  }
+
+  pub fn is_complete(&self) -> bool {
+    self.offset == self.len
+  }
+
+  /// moves the offset of an open upload from `from` to `to`
+  ///
+  /// returns false if the upload is not open or its offset is not `from`,
+  /// in that case the part that was written at `from` must be discarded
+  pub async fn advance(id: &str, from: u64, to: u64) -> Result<bool, mongodb::error::Error> {
+    let filter = doc! {
+      crate::KEY_ID: id,
+      Self::KEY_OFFSET: from as f64,
+      KEY_STATE_KIND: ResumableUploadState::KEY_ENUM_VARIANT_OPEN,
+    };
+
+    let update = doc! {
+      "$set": {
+        Self::KEY_OFFSET: to as f64,
+        Self::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    let r = Self::cl().update_one(filter, update, None).await?;
+    Ok(r.matched_count == 1)
+  }
+
+  /// sets the state of an upload only if its current state is `from`
+  ///
+  /// `from` is a KEY_ENUM_VARIANT_* constant of ResumableUploadState
+  pub async fn set_state_if(
+    id: &str,
+    from: &str,
+    state: ResumableUploadState,
+  ) -> Result<bool, mongodb::error::Error> {
+    let filter = doc! {
+      crate::KEY_ID: id,
+      KEY_STATE_KIND: from,
+    };
+
+    let update = doc! {
+      "$set": {
+        Self::KEY_STATE: state,
+        Self::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    let r = Self::cl().update_one(filter, update, None).await?;
+    Ok(r.matched_count == 1)
+  }
+
+  /// updates the updated_at of an upload that is being committed, so it is not taken as interrupted
+  pub async fn touch_committing(id: &str) -> Result<bool, mongodb::error::Error> {
+    let filter = doc! {
+      crate::KEY_ID: id,
+      KEY_STATE_KIND: ResumableUploadState::KEY_ENUM_VARIANT_COMMITTING,
+    };
+
+    let update = doc! {
+      "$set": {
+        Self::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    let r = Self::cl().update_one(filter, update, None).await?;
+    Ok(r.matched_count == 1)
+  }
+
+  /// sets back to open the uploads whose commit was interrupted (not touched in COMMITTING_STALE_AFTER),
+  /// so they can be committed again or aborted, the received bytes are kept until the commit succeeds
+  pub async fn reset_stale_committing() -> Result<u64, mongodb::error::Error> {
+    let limit: DateTime = (time::OffsetDateTime::now_utc() - Self::COMMITTING_STALE_AFTER).into();
+
+    let update = doc! {
+      "$set": {
+        Self::KEY_STATE: ResumableUploadState::Open,
+        Self::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    let r = Self::cl()
+      .update_many(stale_committing_filter(limit), update, None)
+      .await?;
+
+    Ok(r.modified_count)
+  }
+
+  /// the declared len of the uploads of the stations of the account that are not committed yet
+  ///
+  /// this space is reserved in the storage quota of the account until the uploads are committed or removed
+  pub async fn pending_len_for_account(account_id: &str) -> Result<u64, mongodb::error::Error> {
+    use futures_util::TryStreamExt;
+
+    let filter = doc! { crate::station::Station::KEY_ACCOUNT_ID: account_id };
+    let station_ids: Vec<String> =
+      crate::station::Station::distinct_string(crate::KEY_ID, filter).await?;
+
+    let filter = doc! {
+      Self::KEY_STATION_ID: { "$in": station_ids },
+      KEY_STATE_KIND: { "$ne": ResumableUploadState::KEY_ENUM_VARIANT_COMMITTED },
+    };
+
+    let mut len = 0;
+    let mut cursor = Self::cl().find(filter, None).await?;
+    while let Some(upload) = cursor.try_next().await? {
+      len += upload.len;
+    }
+
+    Ok(len)
+  }
+
+  /// removes the upload and its parts
+  pub async fn remove(id: &str) -> Result<(), mongodb::error::Error> {
+    ResumableUploadPart::delete_by_upload_id(id).await?;
+    Self::delete_by_id(id).await?;
+    Ok(())
+  }
+
+  /// removes the uploads (and its parts) that were not updated in RESUMABLE_UPLOAD_EXPIRE_SECS
+  ///
+  /// the uploads that are being committed are not removed, the interrupted commits are set back to open
+  /// by [`Self::reset_stale_committing`] and expire from then
+  pub async fn remove_expired() -> Result<u64, mongodb::error::Error> {
+    let limit = time::OffsetDateTime::now_utc()
+      - time::Duration::seconds(constants::RESUMABLE_UPLOAD_EXPIRE_SECS as i64);
+    let limit: DateTime = limit.into();
+
+    let ids: Vec<String> = Self::distinct_string(crate::KEY_ID, expired_filter(limit)).await?;
+
+    for id in ids.iter() {
+      Self::remove(id).await?;
+    }
+
+    Ok(ids.len() as u64)
+  }
+
+  pub fn start_expire_job() -> tokio::task::JoinHandle<()> {
+    info!(target: "resumable-upload-expire", "resumable upload expire job started");
+    tokio::spawn(async move {
+      let mut interval = tokio::time::interval(Self::EXPIRE_JOB_INTERVAL);
+      loop {
+        // first tick is instantaneous
+        interval.tick().await;
+
+        match Self::reset_stale_committing().await {
+          Ok(n) => {
+            if n != 0 {
+              info!(target: "resumable-upload-expire", "{n} interrupted resumable upload commits set back to open");
+            }
+          }
+          Err(e) => {
+            error!(target: "resumable-upload-expire", "mongodb error resetting interrupted resumable upload commits: {e}, {e:?}");
+          }
+        }
+
+        match Self::remove_expired().await {
+          Ok(n) => {
+            if n != 0 {
+              info!(target: "resumable-upload-expire", "{n} expired resumable uploads removed");
+            }
+          }
+          Err(e) => {
+            error!(target: "resumable-upload-expire", "mongodb error removing expired resumable uploads: {e}, {e:?}");
+          }
+        }
+      }
+    })
+  }
+}
+
+impl Model for ResumableUpload {
+  const UID_LEN: usize = 16;
+  const CL_NAME: &'static str = "resumable_uploads";
+
+  fn indexes() -> Vec<IndexModel> {
+    let station_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1 })
+      .build();
+    let updated_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_UPDATED_AT: 1 })
+      .build();
+    vec![station_id, updated_at]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, ResumableUpload::KEY_ID);
+    assert_eq!(KEY_STATE_KIND, "state.kind");
+  }
+
+  #[test]
+  fn committing_uploads_do_not_expire() {
+    let limit = DateTime::now();
+
+    let expired = expired_filter(limit);
+    assert_eq!(
+      expired.get_document(KEY_STATE_KIND).unwrap(),
+      &doc! { "$ne": ResumableUploadState::KEY_ENUM_VARIANT_COMMITTING }
+    );
+
+    let stale = stale_committing_filter(limit);
+    assert_eq!(
+      stale.get_str(KEY_STATE_KIND).unwrap(),
+      ResumableUploadState::KEY_ENUM_VARIANT_COMMITTING
+    );
+    assert_eq!(
+      expired.get(ResumableUpload::KEY_UPDATED_AT),
+      stale.get(ResumableUpload::KEY_UPDATED_AT)
+    );
+  }
+
+  #[test]
+  fn state_serializes_tagged() {
+    let state = ResumableUploadState::Open;
+    let bson = mongodb::bson::Bson::from(state);
+    assert_eq!(
+      bson,
+      mongodb::bson::Bson::Document(
+        doc! { ResumableUploadState::KEY_ENUM_TAG: ResumableUploadState::KEY_ENUM_VARIANT_OPEN }
+      )
+    );
+  }
+}
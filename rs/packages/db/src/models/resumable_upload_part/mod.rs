@@ -0,0 +1,155 @@
+use crate::{storage_db, Model};
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures_util::stream::Stream;
+use futures_util::TryStreamExt;
+use mongodb::results::DeleteResult;
+use mongodb::{bson::doc, IndexModel};
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+crate::register!(ResumableUploadPart);
+
+/// bytes received for a resumable upload, in the storage db
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct ResumableUploadPart {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub upload_id: String,
+  pub station_id: String,
+
+  /// position of the first byte of this part in the uploaded file
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub offset: u64,
+
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub len: u64,
+
+  #[serde(with = "serde_util::bytes")]
+  #[ts(type = "string")]
+  pub data: Bytes,
+
+  pub created_at: DateTime,
+}
+
+impl ResumableUploadPart {
+  /// streams the parts of an upload in offset order
+  pub fn stream(
+    upload_id: &str,
+  ) -> impl Stream<Item = Result<Bytes, mongodb::error::Error>> + Send + 'static {
+    let upload_id = upload_id.to_string();
+    try_stream! {
+      let filter = doc! { Self::KEY_UPLOAD_ID: &upload_id };
+      let sort = doc! { Self::KEY_OFFSET: 1 };
+      let options = mongodb::options::FindOptions::builder().sort(sort).build();
+      let mut cursor = Self::cl().find(filter, options).await?;
+      while let Some(item) = cursor.try_next().await? {
+        yield item.data;
+      }
+    }
+  }
+
+  /// streams the bytes of an upload from `start` up to `end` (exclusive)
+  pub fn stream_range(
+    upload_id: &str,
+    start: u64,
+    end: u64,
+  ) -> impl Stream<Item = Result<Bytes, mongodb::error::Error>> + Send + 'static {
+    let upload_id = upload_id.to_string();
+    try_stream! {
+      // parts are at most RESUMABLE_UPLOAD_PART_SIZE long, so the first one can start before `start`
+      let min_offset = start.saturating_sub(constants::RESUMABLE_UPLOAD_PART_SIZE as u64);
+      let filter = doc! {
+        Self::KEY_UPLOAD_ID: &upload_id,
+        Self::KEY_OFFSET: { "$gte": min_offset as f64, "$lt": end as f64 },
+      };
+      let sort = doc! { Self::KEY_OFFSET: 1 };
+      let options = mongodb::options::FindOptions::builder().sort(sort).build();
+      let mut cursor = Self::cl().find(filter, options).await?;
+      while let Some(item) = cursor.try_next().await? {
+        if let Some(data) = slice_part(item.offset, &item.data, start, end) {
+          yield data;
+        }
+      }
+    }
+  }
+
+  /// reads the bytes of an upload from `start` up to `end` (exclusive) into one buffer
+  pub async fn read_range(
+    upload_id: &str,
+    start: u64,
+    end: u64,
+  ) -> Result<Bytes, mongodb::error::Error> {
+    let stream = Self::stream_range(upload_id, start, end);
+    tokio::pin!(stream);
+    let mut buf = bytes::BytesMut::new();
+    while let Some(data) = stream.try_next().await? {
+      buf.extend_from_slice(&data);
+    }
+    Ok(buf.freeze())
+  }
+
+  pub async fn delete_by_upload_id(upload_id: &str) -> Result<DeleteResult, mongodb::error::Error> {
+    let filter = doc! { Self::KEY_UPLOAD_ID: upload_id };
+    Self::cl().delete_many(filter, None).await
+  }
+}
+
+/// the bytes of a part at `offset` that are between `start` and `end` (exclusive)
+fn slice_part(offset: u64, data: &Bytes, start: u64, end: u64) -> Option<Bytes> {
+  let part_end = offset + data.len() as u64;
+  let from = start.max(offset);
+  let to = end.min(part_end);
+  if from >= to {
+    return None;
+  }
+  Some(data.slice((from - offset) as usize..(to - offset) as usize))
+}
+
+impl Model for ResumableUploadPart {
+  const UID_LEN: usize = 16;
+  const CL_NAME: &'static str = "resumable_upload_parts";
+
+  fn db() -> mongodb::Database {
+    storage_db()
+  }
+
+  fn indexes() -> Vec<IndexModel> {
+    let upload_id_offset = IndexModel::builder()
+      .keys(doc! { Self::KEY_UPLOAD_ID: 1, Self::KEY_OFFSET: 1 })
+      .build();
+    vec![upload_id_offset]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, ResumableUploadPart::KEY_ID);
+  }
+
+  #[test]
+  fn slice_part_range() {
+    let data = Bytes::from_static(b"0123456789");
+    assert_eq!(slice_part(10, &data, 0, 100), Some(data.clone()));
+    assert_eq!(
+      slice_part(10, &data, 12, 15),
+      Some(Bytes::from_static(b"234"))
+    );
+    assert_eq!(
+      slice_part(10, &data, 0, 12),
+      Some(Bytes::from_static(b"01"))
+    );
+    assert_eq!(slice_part(10, &data, 20, 30), None);
+    assert_eq!(slice_part(10, &data, 0, 10), None);
+  }
+}
@@ -53,4 +53,11 @@ pub enum SentEmailKind {
     user_sender_id: Option<String>,
     admin_sender_id: Option<String>,
   },
+
+  #[serde(rename = "station-dead-air")]
+  StationDeadAir {
+    station_id: String,
+    media_session_id: String,
+    user_id: String,
+  },
 }
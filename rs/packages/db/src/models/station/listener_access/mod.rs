@@ -0,0 +1,403 @@
+use constants::validate::*;
+use geoip::CountryCode;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{ArrayValidation, InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+use ts_rs::TS;
+use validator::Validate;
+
+/// restrictions on the listeners that can connect to the streams of a station
+///
+/// every list is checked on its own and a listener must pass all of them to connect,
+/// a deny list rejects the listeners that match it and a non empty allow list rejects the ones that don't
+#[derive(
+  Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema, Validate,
+)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct ListenerAccessSettings {
+  /// countries that can listen to the station, empty to allow all the countries
+  /// listeners whose country cannot be resolved are rejected when this list is not empty
+  #[validate(length(
+    max = "VALIDATE_STATION_LISTENER_ACCESS_COUNTRIES_MAX_LEN",
+    message = "Too many allowed countries"
+  ))]
+  #[schemars(schema_with = "country_codes_schema")]
+  pub allowed_countries: Vec<CountryCode>,
+  /// countries that cannot listen to the station
+  #[validate(length(
+    max = "VALIDATE_STATION_LISTENER_ACCESS_COUNTRIES_MAX_LEN",
+    message = "Too many denied countries"
+  ))]
+  #[schemars(schema_with = "country_codes_schema")]
+  pub denied_countries: Vec<CountryCode>,
+  /// networks in CIDR notation or single IP addresses that can listen to the station, empty to allow all the networks
+  #[validate(length(
+    max = "VALIDATE_STATION_LISTENER_ACCESS_CIDRS_MAX_LEN",
+    message = "Too many allowed networks"
+  ))]
+  pub allowed_cidrs: Vec<String>,
+  /// networks in CIDR notation or single IP addresses that cannot listen to the station
+  #[validate(length(
+    max = "VALIDATE_STATION_LISTENER_ACCESS_CIDRS_MAX_LEN",
+    message = "Too many denied networks"
+  ))]
+  pub denied_cidrs: Vec<String>,
+  /// domains of the web pages that can play the streams of the station, subdomains included, empty to allow all
+  /// the domain is taken from the referer header of the request, or from the origin header if there is no referer
+  #[validate(length(
+    max = "VALIDATE_STATION_LISTENER_ACCESS_DOMAINS_MAX_LEN",
+    message = "Too many allowed domains"
+  ))]
+  pub allowed_domains: Vec<String>,
+  /// let through the requests without referer and origin headers when allowed_domains is not empty
+  /// mobile apps and desktop players usually don't send these headers
+  pub allow_missing_referer: bool,
+}
+
+/// the country lists reference the shared CountryCode schema instead of inlining the whole enum
+fn country_codes_schema(_: &mut SchemaGenerator) -> Schema {
+  SchemaObject {
+    instance_type: Some(InstanceType::Array.into()),
+    array: Some(Box::new(ArrayValidation {
+      items: Some(openapi::component_ref("CountryCode").into()),
+      ..Default::default()
+    })),
+    ..Default::default()
+  }
+  .into()
+}
+
+/// the rule of the station listener access settings that rejected a listener
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ListenerAccessDenied {
+  #[error("the network of the listener is not allowed")]
+  Ip,
+  #[error("the country of the listener is not allowed")]
+  Country,
+  #[error("the referer of the request is not allowed")]
+  Referer,
+}
+
+impl ListenerAccessSettings {
+  /// trims the lists entries and lowercases the domains,
+  /// returns a message naming the first invalid entry
+  pub fn normalize(&mut self) -> Result<(), String> {
+    for cidr in self
+      .allowed_cidrs
+      .iter_mut()
+      .chain(self.denied_cidrs.iter_mut())
+    {
+      *cidr = cidr.trim().to_string();
+      if Cidr::from_str(cidr).is_err() {
+        return Err(format!(
+          "Network {cidr} is not a valid IP address or CIDR block"
+        ));
+      }
+    }
+
+    for domain in self.allowed_domains.iter_mut() {
+      *domain = match normalize_domain(domain) {
+        Some(normalized) => normalized,
+        None => return Err(format!("Domain {} is invalid", domain.trim())),
+      };
+    }
+
+    Ok(())
+  }
+
+  /// checks the address, country and referer of a listener request against these settings
+  pub fn check(
+    &self,
+    ip: IpAddr,
+    country_code: Option<CountryCode>,
+    referer: Option<&str>,
+  ) -> Result<(), ListenerAccessDenied> {
+    let in_cidrs = |cidrs: &[String]| {
+      cidrs
+        .iter()
+        .filter_map(|cidr| Cidr::from_str(cidr).ok())
+        .any(|cidr| cidr.contains(ip))
+    };
+
+    if in_cidrs(&self.denied_cidrs) {
+      return Err(ListenerAccessDenied::Ip);
+    }
+
+    if !self.allowed_cidrs.is_empty() && !in_cidrs(&self.allowed_cidrs) {
+      return Err(ListenerAccessDenied::Ip);
+    }
+
+    if let Some(country_code) = country_code {
+      if self.denied_countries.contains(&country_code) {
+        return Err(ListenerAccessDenied::Country);
+      }
+    }
+
+    if !self.allowed_countries.is_empty() {
+      match country_code {
+        Some(country_code) if self.allowed_countries.contains(&country_code) => {}
+        _ => return Err(ListenerAccessDenied::Country),
+      }
+    }
+
+    if !self.allowed_domains.is_empty() {
+      match referer {
+        None => {
+          if !self.allow_missing_referer {
+            return Err(ListenerAccessDenied::Referer);
+          }
+        }
+
+        Some(referer) => {
+          let host = url::Url::parse(referer)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase));
+
+          let allowed = match host {
+            None => false,
+            Some(host) => self.allowed_domains.iter().any(|domain| {
+              host == *domain
+                || (host.ends_with(domain.as_str())
+                  && host[..host.len() - domain.len()].ends_with('.'))
+            }),
+          };
+
+          if !allowed {
+            return Err(ListenerAccessDenied::Referer);
+          }
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// accepts bare domains, wildcard domains (*.example.com) and urls, returns the lowercased domain
+fn normalize_domain(input: &str) -> Option<String> {
+  let input = input.trim().to_lowercase();
+
+  let domain = match input.contains("://") {
+    true => url::Url::parse(&input).ok()?.domain()?.to_string(),
+    false => input
+      .trim_start_matches("*.")
+      .trim_end_matches('.')
+      .to_string(),
+  };
+
+  if domain.is_empty() || domain.len() > VALIDATE_STATION_LISTENER_ACCESS_DOMAIN_MAX_LEN {
+    return None;
+  }
+
+  let valid = domain.split('.').all(|label| {
+    !label.is_empty()
+      && !label.starts_with('-')
+      && !label.ends_with('-')
+      && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+  });
+
+  valid.then_some(domain)
+}
+
+/// an IP network in CIDR notation, a single address is a network with the full prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+  addr: IpAddr,
+  prefix: u8,
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("invalid CIDR block")]
+pub struct InvalidCidr;
+
+impl FromStr for Cidr {
+  type Err = InvalidCidr;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (addr, prefix) = match s.split_once('/') {
+      Some((addr, prefix)) => (addr, Some(prefix)),
+      None => (s, None),
+    };
+
+    let addr = IpAddr::from_str(addr).map_err(|_| InvalidCidr)?;
+
+    let max = match addr {
+      IpAddr::V4(_) => 32,
+      IpAddr::V6(_) => 128,
+    };
+
+    let prefix = match prefix {
+      None => max,
+      Some(prefix) => match prefix.parse::<u8>() {
+        Ok(prefix) if prefix <= max => prefix,
+        _ => return Err(InvalidCidr),
+      },
+    };
+
+    Ok(Self { addr, prefix })
+  }
+}
+
+impl Cidr {
+  /// returns true if the address is inside the network
+  /// IPv4 mapped IPv6 addresses are compared as IPv4 addresses
+  pub fn contains(&self, ip: IpAddr) -> bool {
+    let ip = match ip {
+      IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+        Some(v4) => IpAddr::V4(v4),
+        None => ip,
+      },
+      IpAddr::V4(_) => ip,
+    };
+
+    match (self.addr, ip) {
+      (IpAddr::V4(net), IpAddr::V4(ip)) => {
+        let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+        u32::from(net) & mask == u32::from(ip) & mask
+      }
+
+      (IpAddr::V6(net), IpAddr::V6(ip)) => {
+        let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+        u128::from(net) & mask == u128::from(ip) & mask
+      }
+
+      _ => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+  }
+
+  #[test]
+  fn cidr_contains() {
+    let net = Cidr::from_str("10.1.0.0/16").unwrap();
+    assert!(net.contains(ip("10.1.200.3")));
+    assert!(!net.contains(ip("10.2.0.1")));
+    assert!(net.contains(ip("::ffff:10.1.0.1")));
+
+    let single = Cidr::from_str("192.168.0.1").unwrap();
+    assert!(single.contains(ip("192.168.0.1")));
+    assert!(!single.contains(ip("192.168.0.2")));
+
+    let all = Cidr::from_str("0.0.0.0/0").unwrap();
+    assert!(all.contains(ip("8.8.8.8")));
+
+    let v6 = Cidr::from_str("2001:db8::/32").unwrap();
+    assert!(v6.contains(ip("2001:db8:1::1")));
+    assert!(!v6.contains(ip("2001:db9::1")));
+    assert!(!v6.contains(ip("10.1.0.1")));
+  }
+
+  #[test]
+  fn cidr_invalid() {
+    assert!(Cidr::from_str("10.0.0.0/33").is_err());
+    assert!(Cidr::from_str("10.0.0/8").is_err());
+    assert!(Cidr::from_str("2001:db8::/129").is_err());
+    assert!(Cidr::from_str("example.com").is_err());
+  }
+
+  #[test]
+  fn normalize_lists() {
+    let mut settings = ListenerAccessSettings {
+      allowed_cidrs: vec![" 10.0.0.0/8 ".into()],
+      allowed_domains: vec![
+        "https://Player.Example.com/embed".into(),
+        "*.radio.net".into(),
+      ],
+      ..Default::default()
+    };
+
+    settings.normalize().unwrap();
+    assert_eq!(settings.allowed_cidrs, vec!["10.0.0.0/8"]);
+    assert_eq!(
+      settings.allowed_domains,
+      vec!["player.example.com", "radio.net"]
+    );
+
+    settings.denied_cidrs = vec!["10.0.0.0/40".into()];
+    assert!(settings.normalize().is_err());
+
+    settings.denied_cidrs = vec![];
+    settings.allowed_domains = vec!["bad domain.com".into()];
+    assert!(settings.normalize().is_err());
+  }
+
+  #[test]
+  fn check_ip_and_country() {
+    let settings = ListenerAccessSettings {
+      allowed_countries: vec![CountryCode::AR, CountryCode::UY],
+      denied_cidrs: vec!["200.0.0.0/24".into()],
+      ..Default::default()
+    };
+
+    assert_eq!(
+      settings.check(ip("200.1.0.1"), Some(CountryCode::AR), None),
+      Ok(())
+    );
+    assert_eq!(
+      settings.check(ip("200.0.0.1"), Some(CountryCode::AR), None),
+      Err(ListenerAccessDenied::Ip)
+    );
+    assert_eq!(
+      settings.check(ip("200.1.0.1"), Some(CountryCode::BR), None),
+      Err(ListenerAccessDenied::Country)
+    );
+    assert_eq!(
+      settings.check(ip("200.1.0.1"), None, None),
+      Err(ListenerAccessDenied::Country)
+    );
+
+    let settings = ListenerAccessSettings {
+      denied_countries: vec![CountryCode::BR],
+      allowed_cidrs: vec!["200.0.0.0/8".into()],
+      ..Default::default()
+    };
+
+    assert_eq!(settings.check(ip("200.1.0.1"), None, None), Ok(()));
+    assert_eq!(
+      settings.check(ip("201.1.0.1"), None, None),
+      Err(ListenerAccessDenied::Ip)
+    );
+    assert_eq!(
+      settings.check(ip("200.1.0.1"), Some(CountryCode::BR), None),
+      Err(ListenerAccessDenied::Country)
+    );
+  }
+
+  #[test]
+  fn check_referer() {
+    let mut settings = ListenerAccessSettings {
+      allowed_domains: vec!["example.com".into()],
+      ..Default::default()
+    };
+
+    let check =
+      |settings: &ListenerAccessSettings, referer| settings.check(ip("1.1.1.1"), None, referer);
+
+    assert_eq!(check(&settings, Some("https://example.com/player")), Ok(()));
+    assert_eq!(check(&settings, Some("https://www.Example.com/")), Ok(()));
+    assert_eq!(
+      check(&settings, Some("https://notexample.com/")),
+      Err(ListenerAccessDenied::Referer)
+    );
+    assert_eq!(
+      check(&settings, Some("null")),
+      Err(ListenerAccessDenied::Referer)
+    );
+    assert_eq!(check(&settings, None), Err(ListenerAccessDenied::Referer));
+
+    settings.allow_missing_referer = true;
+    assert_eq!(check(&settings, None), Ok(()));
+  }
+}
@@ -1,5 +1,6 @@
-use crate::audio_file::AudioFile;
+use crate::audio_file::{AudioFile, AudioProcessing};
 use crate::error::ApplyPatchError;
+use crate::live_recording::LiveRecording;
 use crate::{current_filter_doc, Model};
 use crate::{metadata::Metadata, PublicScope};
 use constants::validate::*;
@@ -18,6 +19,9 @@ use ts_rs::TS;
 use validate::url::patterns::*;
 use validator::Validate;
 
+pub mod listener_access;
+use listener_access::ListenerAccessSettings;
+
 crate::register!(Station);
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema, Modify, Validate)]
@@ -272,6 +276,64 @@ pub struct Station {
   pub external_relay_url: Option<String>,
   pub external_relay_redirect: bool,
 
+  /// urls to relay from when external_relay_url fails, in failover order
+  #[serde(default)]
+  #[validate(length(
+    max = "VALIDATE_STATION_EXTERNAL_RELAY_BACKUP_URLS_MAX_LEN",
+    message = "Too many External Relay backup URLs"
+  ))]
+  pub external_relay_backup_urls: Vec<String>,
+
+  // output renditions
+  #[serde(default)]
+  #[validate(length(
+    max = "VALIDATE_STATION_RENDITIONS_MAX_LEN",
+    message = "Too many output renditions"
+  ))]
+  #[validate]
+  pub renditions: Vec<StationRendition>,
+
+  // playlists
+  /// the named playlist played when no schedule slot selects another one
+  /// None to play all the files of the station in their order
+  #[serde(default)]
+  pub default_playlist_id: Option<String>,
+
+  /// how the playlist moves from one file to the next
+  #[serde(default)]
+  #[validate]
+  pub playlist_transition: PlaylistTransition,
+
+  /// loudness normalization and silence trimming applied to the uploaded files
+  #[serde(default)]
+  #[validate]
+  pub audio_processing: AudioProcessing,
+
+  /// recording of the live broadcasts of the station
+  #[serde(default)]
+  #[validate]
+  pub live_recording: LiveRecordingSettings,
+
+  /// validation of the audio sent by the live encoders of the station
+  #[serde(default)]
+  pub live_input: LiveInputSettings,
+
+  /// detection of silence and dead air in the live and external relay sources of the station
+  #[serde(default)]
+  #[validate]
+  pub dead_air: DeadAirSettings,
+
+  /// country, network and referer restrictions on the listeners of the station
+  #[serde(default)]
+  #[validate]
+  pub listener_access: ListenerAccessSettings,
+
+  // schedule
+  /// IANA time zone of the station, eg: America/Argentina/Buenos_Aires
+  /// the weekly slots of the station schedule and the dj allowed times are evaluated in this time zone
+  #[serde(default = "default_timezone")]
+  pub timezone: String,
+
   // auth
   pub source_password: String,
 
@@ -404,6 +466,28 @@ pub struct UserPublicStation {
   // external-relay
   pub external_relay_url: Option<String>,
   pub external_relay_redirect: bool,
+  pub external_relay_backup_urls: Vec<String>,
+
+  // output renditions
+  pub renditions: Vec<StationRendition>,
+
+  // playlists
+  pub default_playlist_id: Option<String>,
+  pub playlist_transition: PlaylistTransition,
+
+  // audio processing
+  pub audio_processing: AudioProcessing,
+
+  // live
+  pub live_recording: LiveRecordingSettings,
+  pub live_input: LiveInputSettings,
+  pub dead_air: DeadAirSettings,
+
+  // listeners
+  pub listener_access: ListenerAccessSettings,
+
+  // schedule
+  pub timezone: String,
 
   // misc
   pub playlist_is_randomly_shuffled: bool,
@@ -438,6 +522,186 @@ pub enum StationFrequencyKind {
   Fm,
 }
 
+/// an extra output format of the station
+/// renditions are transcoded on demand from the station main output
+/// and are available at /stream/:id/:slug
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema, Validate)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct StationRendition {
+  #[validate(
+    regex(
+      path = "VALIDATE_STATION_RENDITION_SLUG_PATTERN",
+      message = "Rendition slug can only contain lowercase letters, numbers, dashes and underscores"
+    ),
+    length(
+      min = "VALIDATE_STATION_RENDITION_SLUG_MIN_LEN",
+      max = "VALIDATE_STATION_RENDITION_SLUG_MAX_LEN",
+      message = "Rendition slug is empty or too long"
+    )
+  )]
+  pub slug: String,
+  pub codec: StationRenditionCodec,
+  #[validate(range(
+    min = "VALIDATE_STATION_RENDITION_KBITRATE_MIN",
+    max = "VALIDATE_STATION_RENDITION_KBITRATE_MAX",
+    message = "Rendition bitrate is either too low or too high"
+  ))]
+  pub kbitrate: u16,
+}
+
+/// how the playlist of a station moves from one file to the next
+/// with both options off the files are played one after the other as they are stored
+#[derive(
+  Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema, Validate,
+)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct PlaylistTransition {
+  /// re-encode the boundary between two files without the encoder delay and padding
+  pub gapless: bool,
+  /// mix the end of each file with the start of the next one for this duration, 0 to disable
+  #[validate(range(
+    max = "VALIDATE_STATION_PLAYLIST_CROSSFADE_MS_MAX",
+    message = "Playlist crossfade duration is too long"
+  ))]
+  pub crossfade_ms: u32,
+}
+
+impl PlaylistTransition {
+  /// returns true if the boundaries between files are decoded and re-encoded
+  pub fn is_enabled(&self) -> bool {
+    self.gapless || self.crossfade_ms != 0
+  }
+}
+
+/// recording of the live broadcasts of a station into recordings that can be published to the station files
+#[derive(
+  Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema, Validate,
+)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct LiveRecordingSettings {
+  /// record the live broadcasts of the station
+  pub enabled: bool,
+  /// start a new recording when the now playing info of the broadcast changes
+  pub split_on_now_playing: bool,
+  /// start a new recording after this duration, 0 to not split by duration
+  #[validate(range(
+    max = "VALIDATE_STATION_LIVE_RECORDING_SPLIT_SECS_MAX",
+    message = "Live recording split duration is too long"
+  ))]
+  pub split_secs: u32,
+  /// recordings that are not published are removed after this number of days, 0 to keep them
+  #[validate(range(
+    max = "VALIDATE_STATION_LIVE_RECORDING_RETENTION_DAYS_MAX",
+    message = "Live recording retention is too long"
+  ))]
+  pub retention_days: u32,
+}
+
+/// validation of the audio sent by the live encoders of a station
+///
+/// the frames of MP3 and AAC streams are always validated, encoders that send
+/// invalid audio or change the sample rate or channels are disconnected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct LiveInputSettings {
+  /// transcode the input that does not match the stream format of the station
+  /// instead of disconnecting the encoder, the live stream is always sent as MP3
+  pub transcode: bool,
+}
+
+/// detection of dead air in the live and external relay sources of a station
+///
+/// the audio of the source is measured every DEAD_AIR_CHECK_INTERVAL_SECS, the source is in dead air
+/// when its level stays under the silence threshold or its bitrate under the minimum for duration_secs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema, Validate)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct DeadAirSettings {
+  /// monitor the audio of the live and external relay sources of the station
+  pub enabled: bool,
+  /// RMS audio level under which the source is considered silent, in dBFS
+  #[validate(range(
+    min = "VALIDATE_STATION_DEAD_AIR_THRESHOLD_DB_MIN",
+    max = "VALIDATE_STATION_DEAD_AIR_THRESHOLD_DB_MAX",
+    message = "Dead air silence threshold is out of range"
+  ))]
+  pub silence_threshold_db: i16,
+  /// bitrate in kbps under which the source is considered to be sending no audio, 0 to disable
+  #[validate(range(
+    max = "VALIDATE_STATION_DEAD_AIR_MIN_KBITRATE_MAX",
+    message = "Dead air minimum bitrate is too high"
+  ))]
+  pub min_kbitrate: u32,
+  /// seconds of silence or low bitrate after which the dead air is raised
+  #[validate(range(
+    min = "VALIDATE_STATION_DEAD_AIR_DURATION_SECS_MIN",
+    max = "VALIDATE_STATION_DEAD_AIR_DURATION_SECS_MAX",
+    message = "Dead air duration is out of range"
+  ))]
+  pub duration_secs: u32,
+  /// email the owners of the station account when the dead air is raised
+  pub notify: bool,
+  /// play the station playlist instead of the source until its audio returns
+  pub failover: bool,
+}
+
+impl Default for DeadAirSettings {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      silence_threshold_db: -50,
+      min_kbitrate: 8,
+      duration_secs: 30,
+      notify: true,
+      failover: false,
+    }
+  }
+}
+
+impl StationRendition {
+  /// slugs that would collide with other routes of the stream server
+  pub const RESERVED_SLUGS: &'static [&'static str] = &["hls", "is-hls-redirect"];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/")]
+#[serde(rename_all = "kebab-case")]
+pub enum StationRenditionCodec {
+  Mp3,
+  Aac,
+  AacHe,
+  Opus,
+}
+
+impl StationRenditionCodec {
+  /// the name of the codec in the api
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::Mp3 => "mp-3",
+      Self::Aac => "aac",
+      Self::AacHe => "aac-he",
+      Self::Opus => "opus",
+    }
+  }
+
+  pub fn content_type(&self) -> &'static str {
+    match self {
+      Self::Mp3 => "audio/mpeg",
+      Self::Aac | Self::AacHe => "audio/aac",
+      Self::Opus => "audio/ogg",
+    }
+  }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
 #[ts(export, export_to = "../../../defs/")]
 pub struct AdminPublicStation(pub Station);
@@ -839,6 +1103,64 @@ pub struct StationPatch {
   #[serde(skip_serializing_if = "Option::is_none")]
   pub external_relay_redirect: Option<bool>,
 
+  #[ts(optional)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[validate(length(
+    max = "VALIDATE_STATION_EXTERNAL_RELAY_BACKUP_URLS_MAX_LEN",
+    message = "Too many External Relay backup URLs"
+  ))]
+  pub external_relay_backup_urls: Option<Vec<String>>,
+
+  #[ts(optional)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[validate(length(
+    max = "VALIDATE_STATION_RENDITIONS_MAX_LEN",
+    message = "Too many output renditions"
+  ))]
+  #[validate]
+  pub renditions: Option<Vec<StationRendition>>,
+
+  #[ts(optional)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub timezone: Option<String>,
+
+  #[ts(optional)]
+  #[serde(
+    default,
+    deserialize_with = "map_some",
+    skip_serializing_if = "Option::is_none"
+  )]
+  pub default_playlist_id: Option<Option<String>>,
+
+  #[ts(optional)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[validate]
+  pub playlist_transition: Option<PlaylistTransition>,
+
+  #[ts(optional)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[validate]
+  pub audio_processing: Option<AudioProcessing>,
+
+  #[ts(optional)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[validate]
+  pub live_recording: Option<LiveRecordingSettings>,
+
+  #[ts(optional)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub live_input: Option<LiveInputSettings>,
+
+  #[ts(optional)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[validate]
+  pub dead_air: Option<DeadAirSettings>,
+
+  #[ts(optional)]
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[validate]
+  pub listener_access: Option<ListenerAccessSettings>,
+
   //#[ts(optional)]
   //#[serde(skip_serializing_if = "Option::is_none")]
   //pub limits: Option<StationPatchLimits>,
@@ -851,7 +1173,39 @@ pub struct StationPatch {
   pub system_metadata: Option<Metadata>,
 }
 
+pub fn default_timezone() -> String {
+  String::from("UTC")
+}
+
+/// parses an IANA time zone name, eg: America/Argentina/Buenos_Aires
+pub fn parse_timezone(name: &str) -> Option<&'static time_tz::Tz> {
+  time_tz::timezones::get_by_name(name)
+}
+
+/// the same checks applied to external_relay_url by its validate attribute
+pub fn is_valid_external_relay_url(url: &str) -> bool {
+  url.len() <= VALIDATE_STATION_EXTERNAL_RELAY_URL_MAX_LEN
+    && !url.chars().any(char::is_control)
+    && url::Url::parse(url).is_ok()
+    && WEBSITE.is_match(url)
+}
+
 impl Station {
+  /// the time zone of the station, UTC if the stored name is not a known time zone
+  pub fn tz(&self) -> &'static time_tz::Tz {
+    parse_timezone(&self.timezone).unwrap_or(time_tz::timezones::db::UTC)
+  }
+
+  /// the external relay urls of the station in failover order, the primary url first
+  pub fn external_relay_urls(&self) -> Vec<String> {
+    match &self.external_relay_url {
+      None => vec![],
+      Some(url) => std::iter::once(url.clone())
+        .chain(self.external_relay_backup_urls.iter().cloned())
+        .collect(),
+    }
+  }
+
   pub fn random_owner_task_id() -> String {
     uid::uid(6)
   }
@@ -978,6 +1332,60 @@ impl Station {
     apply!(external_relay_url);
     apply!(external_relay_redirect);
 
+    if let Some(urls) = &mut patch.external_relay_backup_urls {
+      for url in urls.iter_mut() {
+        *url = url.trim().to_string();
+        if !is_valid_external_relay_url(url) {
+          return Err(ApplyPatchError::invalid(format!(
+            "External Relay backup URL {url} is invalid"
+          )));
+        }
+      }
+    }
+    apply!(external_relay_backup_urls);
+
+    if let Some(renditions) = &patch.renditions {
+      for (i, rendition) in renditions.iter().enumerate() {
+        if StationRendition::RESERVED_SLUGS.contains(&rendition.slug.as_str()) {
+          return Err(ApplyPatchError::invalid(format!(
+            "Rendition slug {} is reserved",
+            rendition.slug
+          )));
+        }
+
+        if renditions[..i].iter().any(|r| r.slug == rendition.slug) {
+          return Err(ApplyPatchError::invalid(format!(
+            "Rendition slug {} is duplicated",
+            rendition.slug
+          )));
+        }
+      }
+    }
+    apply!(renditions);
+
+    if let Some(timezone) = &patch.timezone {
+      if parse_timezone(timezone).is_none() {
+        return Err(ApplyPatchError::invalid(format!(
+          "Time zone {timezone} is invalid"
+        )));
+      }
+    }
+    apply!(timezone);
+
+    apply!(default_playlist_id);
+    apply!(playlist_transition);
+    apply!(audio_processing);
+    apply!(live_recording);
+    apply!(live_input);
+    apply!(dead_air);
+
+    if let Some(listener_access) = &mut patch.listener_access {
+      listener_access
+        .normalize()
+        .map_err(ApplyPatchError::invalid)?;
+    }
+    apply!(listener_access);
+
     if let Some(metadata) = patch.user_metadata {
       self.user_metadata.merge(metadata);
     }
@@ -1000,6 +1408,8 @@ impl Station {
       acc += file.len;
     }
 
+    acc += LiveRecording::get_used_storage_with_session(station_id, session).await?;
+
     Ok(acc)
   }
 }
@@ -1047,6 +1457,16 @@ impl From<Station> for UserPublicStation {
 
       external_relay_url: station.external_relay_url,
       external_relay_redirect: station.external_relay_redirect,
+      external_relay_backup_urls: station.external_relay_backup_urls,
+      renditions: station.renditions,
+      default_playlist_id: station.default_playlist_id,
+      playlist_transition: station.playlist_transition,
+      audio_processing: station.audio_processing,
+      live_recording: station.live_recording,
+      live_input: station.live_input,
+      dead_air: station.dead_air,
+      listener_access: station.listener_access,
+      timezone: station.timezone,
 
       source_password: station.source_password,
 
@@ -1177,4 +1597,16 @@ mod test {
   fn keys_match() {
     assert_eq!(crate::KEY_ID, Station::KEY_ID);
   }
+
+  #[test]
+  fn rendition_codec_names_match_serde() {
+    for codec in [
+      StationRenditionCodec::Mp3,
+      StationRenditionCodec::Aac,
+      StationRenditionCodec::AacHe,
+      StationRenditionCodec::Opus,
+    ] {
+      assert_eq!(serde_json::to_value(codec).unwrap(), codec.as_str());
+    }
+  }
 }
@@ -0,0 +1,274 @@
+use crate::station::Station;
+use crate::station_schedule::StationScheduleTime;
+use crate::Model;
+use mongodb::bson::doc;
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use time::OffsetDateTime;
+use time_tz::Tz;
+use ts_rs::TS;
+
+crate::register!(StationDj);
+
+/// the username of the station source password, it cannot be used by a dj
+pub const SOURCE_USERNAME: &str = "source";
+
+/// named source credentials of a station
+///
+/// a dj connects the live encoder with its own username and password instead of the station source password,
+/// so it can be revoked without resetting the credentials of the other djs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct StationDj {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub station_id: String,
+  /// the name the live sessions and play history of the dj are attributed to
+  pub name: String,
+  /// unique in the station
+  pub username: String,
+  pub password: String,
+  /// a disabled dj cannot connect, it is disconnected if it is live when disabled
+  pub enabled: bool,
+  /// the dj can only connect while this slot is active, in the station local time
+  /// it is disconnected when the slot ends
+  pub allowed_time: Option<StationScheduleTime>,
+  pub created_at: DateTime,
+  pub updated_at: DateTime,
+}
+
+/// the dj a live media session or a play history item is attributed to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct StationDjRef {
+  pub dj_id: String,
+  /// the name of the dj at the time, the dj may be renamed or deleted later
+  pub name: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SourceAuthError {
+  #[error("db: {0}")]
+  Db(#[from] mongodb::error::Error),
+  #[error("credentials mismatch")]
+  Mismatch,
+  #[error("dj {0} is disabled")]
+  Disabled(String),
+  #[error("dj {0} is not allowed to broadcast at this time")]
+  NotAllowedNow(String),
+}
+
+impl StationDj {
+  pub fn attribution(&self) -> StationDjRef {
+    StationDjRef {
+      dj_id: self.id.clone(),
+      name: self.name.clone(),
+    }
+  }
+
+  /// returns true if the dj can connect at `now`
+  pub fn is_allowed_at(&self, now: OffsetDateTime, tz: &Tz) -> bool {
+    self.enabled
+      && match &self.allowed_time {
+        None => true,
+        Some(time) => time.is_active_at(now, tz),
+      }
+  }
+
+  /// the time the dj must be disconnected if it connects at `now`, None if it can stay connected
+  pub fn allowed_until(&self, now: OffsetDateTime, tz: &Tz) -> Option<OffsetDateTime> {
+    self
+      .allowed_time
+      .as_ref()
+      .and_then(|time| time.active_until(now, tz))
+  }
+
+  /// returns a description of the problem if the dj is invalid
+  pub fn validation_error(&self) -> Option<String> {
+    use constants::validate::*;
+
+    let name_len = self.name.chars().count();
+    if !(VALIDATE_STATION_DJ_NAME_MIN_LEN..=VALIDATE_STATION_DJ_NAME_MAX_LEN).contains(&name_len) {
+      return Some("DJ name is either too short or too long".into());
+    }
+
+    if !(VALIDATE_STATION_DJ_USERNAME_MIN_LEN..=VALIDATE_STATION_DJ_USERNAME_MAX_LEN)
+      .contains(&self.username.len())
+    {
+      return Some("DJ username is either too short or too long".into());
+    }
+
+    if !self
+      .username
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+      return Some(
+        "DJ username can only contain letters, numbers, dashes, underscores and dots".into(),
+      );
+    }
+
+    if self.username.eq_ignore_ascii_case(SOURCE_USERNAME) {
+      return Some(format!("DJ username cannot be {SOURCE_USERNAME}"));
+    }
+
+    if !(VALIDATE_STATION_DJ_PASSWORD_MIN_LEN..=VALIDATE_STATION_DJ_PASSWORD_MAX_LEN)
+      .contains(&self.password.len())
+    {
+      return Some("DJ password is either too short or too long".into());
+    }
+
+    if self.password.chars().any(char::is_control) {
+      return Some("DJ password cannot contain control characters".into());
+    }
+
+    if let Some(time) = &self.allowed_time {
+      if let Some(message) = time.validation_error() {
+        return Some(format!("DJ allowed time: {message}"));
+      }
+    }
+
+    None
+  }
+
+  pub fn random_password() -> String {
+    uid::uid(Station::SOURCE_PASSWORD_LEN)
+  }
+
+  pub async fn get_by_username(
+    station_id: &str,
+    username: &str,
+  ) -> Result<Option<StationDj>, mongodb::error::Error> {
+    let filter = doc! {
+      Self::KEY_STATION_ID: station_id,
+      Self::KEY_USERNAME: username,
+    };
+
+    Self::get(filter).await
+  }
+
+  /// checks the basic auth credentials of a source or metadata request of the station
+  ///
+  /// returns Ok(None) for the station source password and Ok(Some(dj)) for the credentials of a dj
+  pub async fn authenticate_source(
+    station: &Station,
+    username: &str,
+    password: &str,
+  ) -> Result<Option<StationDj>, SourceAuthError> {
+    if username == SOURCE_USERNAME {
+      return match password == station.source_password {
+        true => Ok(None),
+        false => Err(SourceAuthError::Mismatch),
+      };
+    }
+
+    let dj = match Self::get_by_username(&station.id, username).await? {
+      None => return Err(SourceAuthError::Mismatch),
+      Some(dj) => dj,
+    };
+
+    if dj.password != password {
+      return Err(SourceAuthError::Mismatch);
+    }
+
+    if !dj.enabled {
+      return Err(SourceAuthError::Disabled(dj.username));
+    }
+
+    if !dj.is_allowed_at(OffsetDateTime::now_utc(), station.tz()) {
+      return Err(SourceAuthError::NotAllowedNow(dj.username));
+    }
+
+    Ok(Some(dj))
+  }
+}
+
+impl Model for StationDj {
+  const CL_NAME: &'static str = "station_djs";
+  const UID_LEN: usize = 12;
+
+  fn indexes() -> Vec<IndexModel> {
+    let station_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1 })
+      .build();
+
+    let username = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1, Self::KEY_USERNAME: 1 })
+      .options(IndexOptions::builder().unique(true).build())
+      .build();
+
+    vec![station_id, username]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::station_schedule::StationScheduleWeekday;
+  use time::macros::datetime;
+  use time_tz::timezones::db::UTC;
+
+  fn dj(username: &str, allowed_time: Option<StationScheduleTime>) -> StationDj {
+    StationDj {
+      id: "dj".into(),
+      station_id: "station".into(),
+      name: "DJ".into(),
+      username: username.into(),
+      password: StationDj::random_password(),
+      enabled: true,
+      allowed_time,
+      created_at: DateTime::now(),
+      updated_at: DateTime::now(),
+    }
+  }
+
+  #[test]
+  fn dj_is_allowed_in_its_time() {
+    // mondays from 20:00 to 22:00
+    let time = StationScheduleTime::Weekly {
+      weekdays: vec![StationScheduleWeekday::Monday],
+      start_minute: 20 * 60,
+      duration_minutes: 120,
+    };
+
+    let mut dj = dj("night", Some(time));
+
+    // 2024-01-01 is a monday
+    assert!(dj.is_allowed_at(datetime!(2024-01-01 21:00 UTC), UTC));
+    assert!(!dj.is_allowed_at(datetime!(2024-01-01 22:00 UTC), UTC));
+    assert_eq!(
+      dj.allowed_until(datetime!(2024-01-01 21:00 UTC), UTC),
+      Some(datetime!(2024-01-01 22:00 UTC))
+    );
+
+    dj.enabled = false;
+    assert!(!dj.is_allowed_at(datetime!(2024-01-01 21:00 UTC), UTC));
+
+    dj.allowed_time = None;
+    dj.enabled = true;
+    assert!(dj.is_allowed_at(datetime!(2024-01-01 22:00 UTC), UTC));
+    assert_eq!(dj.allowed_until(datetime!(2024-01-01 22:00 UTC), UTC), None);
+  }
+
+  #[test]
+  fn dj_usernames() {
+    assert!(dj("dj.night-1_a", None).validation_error().is_none());
+    assert!(dj("source", None).validation_error().is_some());
+    assert!(dj("Source", None).validation_error().is_some());
+    assert!(dj("", None).validation_error().is_some());
+    assert!(dj("dj night", None).validation_error().is_some());
+    assert!(dj("dj:night", None).validation_error().is_some());
+  }
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, StationDj::KEY_ID);
+  }
+}
@@ -0,0 +1,616 @@
+use crate::station::Station;
+use crate::Model;
+use futures_util::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::IndexModel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use time_tz::{Offset, OffsetDateTimeExt, OffsetResult, PrimitiveDateTimeExt, TimeZone, Tz};
+use ts_rs::TS;
+
+crate::register!(StationSchedule);
+
+const MINUTES_IN_DAY: u32 = 24 * 60;
+const MINUTES_IN_WEEK: u32 = 7 * MINUTES_IN_DAY;
+
+/// the local time of `now` in the time zone
+fn local_time(now: OffsetDateTime, tz: &Tz) -> PrimitiveDateTime {
+  let local = now.to_timezone(tz);
+  PrimitiveDateTime::new(local.date(), local.time())
+}
+
+/// minutes since monday at 00:00 of a local time
+fn minute_of_week(local: PrimitiveDateTime) -> u32 {
+  local.weekday().number_days_from_monday() as u32 * MINUTES_IN_DAY
+    + local.hour() as u32 * 60
+    + local.minute() as u32
+}
+
+/// the first instant after `after` with the local time `local` in the time zone
+///
+/// a local time that is skipped when the clocks go forward
+/// is taken with the offset before the change
+fn from_local_time(local: PrimitiveDateTime, tz: &Tz, after: OffsetDateTime) -> OffsetDateTime {
+  let datetime = match local.assume_timezone(tz) {
+    OffsetResult::Some(datetime) => datetime,
+    OffsetResult::Ambiguous(a, b) => {
+      let (first, second) = (a.min(b), a.max(b));
+      if first > after {
+        first
+      } else {
+        second
+      }
+    }
+    OffsetResult::None => {
+      let offset = tz
+        .get_offset_utc(&(local - Duration::days(1)).assume_utc())
+        .to_utc();
+      local.assume_offset(offset)
+    }
+  };
+
+  datetime.to_offset(UtcOffset::UTC)
+}
+
+/// a slot of the programming schedule of a station
+///
+/// weekly slots repeat every week in the station local time (see Station::timezone)
+/// one-off slots override the weekly slots while they are active
+/// when no slot is active the station uses its default source (external relay or playlist)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct StationSchedule {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub station_id: String,
+  pub time: StationScheduleTime,
+  pub source: StationScheduleSource,
+  pub created_at: DateTime,
+  pub updated_at: DateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+#[macros::keys]
+pub enum StationScheduleTime {
+  /// repeats every week in the selected weekdays
+  #[serde(rename = "weekly")]
+  Weekly {
+    weekdays: Vec<StationScheduleWeekday>,
+    /// minute of the day the slot starts, in the station local time
+    start_minute: u16,
+    /// the slot can continue in the following days
+    duration_minutes: u16,
+  },
+
+  /// happens once, overrides the weekly slots
+  #[serde(rename = "once")]
+  Once { start: DateTime, end: DateTime },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+pub enum StationScheduleWeekday {
+  Monday,
+  Tuesday,
+  Wednesday,
+  Thursday,
+  Friday,
+  Saturday,
+  Sunday,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+#[macros::keys]
+pub enum StationScheduleSource {
+  /// the named playlist if playlist_id is set
+  /// otherwise the station files, only these files if file_ids is not empty
+  #[serde(rename = "playlist")]
+  Playlist {
+    #[serde(default)]
+    playlist_id: Option<String>,
+    file_ids: Vec<String>,
+  },
+
+  /// relays this url instead of the station external relay urls
+  #[serde(rename = "external-relay")]
+  ExternalRelay { url: String },
+
+  /// a live source is expected in this slot
+  /// the station default source is used while the live source is not connected
+  #[serde(rename = "live")]
+  Live,
+}
+
+impl StationScheduleWeekday {
+  fn days_from_monday(self) -> u32 {
+    match self {
+      Self::Monday => 0,
+      Self::Tuesday => 1,
+      Self::Wednesday => 2,
+      Self::Thursday => 3,
+      Self::Friday => 4,
+      Self::Saturday => 5,
+      Self::Sunday => 6,
+    }
+  }
+}
+
+impl StationScheduleTime {
+  /// the minutes of the week covered by a weekly slot as [start, end) ranges
+  /// ranges that cross the end of the week are split in two
+  fn week_ranges(&self) -> Vec<(u32, u32)> {
+    match self {
+      Self::Once { .. } => vec![],
+      Self::Weekly {
+        weekdays,
+        start_minute,
+        duration_minutes,
+      } => {
+        let mut ranges = vec![];
+        for weekday in weekdays {
+          let start = weekday.days_from_monday() * MINUTES_IN_DAY + *start_minute as u32;
+          let end = start + *duration_minutes as u32;
+          if end > MINUTES_IN_WEEK {
+            ranges.push((start, MINUTES_IN_WEEK));
+            ranges.push((0, end - MINUTES_IN_WEEK));
+          } else {
+            ranges.push((start, end));
+          }
+        }
+        ranges
+      }
+    }
+  }
+
+  /// returns true if the slot is active at `now`, weekly slots are evaluated in the time zone `tz`
+  pub fn is_active_at(&self, now: OffsetDateTime, tz: &Tz) -> bool {
+    match self {
+      Self::Once { start, end } => **start <= now && now < **end,
+      Self::Weekly { .. } => {
+        let minute = minute_of_week(local_time(now, tz));
+
+        self
+          .week_ranges()
+          .iter()
+          .any(|(start, end)| *start <= minute && minute < *end)
+      }
+    }
+  }
+
+  /// the end of the slot if it is active at `now`, None otherwise
+  /// consecutive weekly ranges are joined, so a slot that continues in the next weekday ends at the end of it
+  pub fn active_until(&self, now: OffsetDateTime, tz: &Tz) -> Option<OffsetDateTime> {
+    match self {
+      Self::Once { end, .. } => match self.is_active_at(now, tz) {
+        true => Some(**end),
+        false => None,
+      },
+
+      Self::Weekly { .. } => {
+        let local = local_time(now, tz);
+        let minute = minute_of_week(local);
+
+        let ranges = self.week_ranges();
+        let (_, mut end) = *ranges
+          .iter()
+          .find(|(start, end)| *start <= minute && minute < *end)?;
+
+        let mut minutes = end - minute;
+        // bounded, a slot that covers the whole week would join its ranges forever
+        for _ in 0..ranges.len() {
+          let next_start = end % MINUTES_IN_WEEK;
+          match ranges.iter().find(|(start, _)| *start == next_start) {
+            None => break,
+            Some((start, next_end)) => {
+              minutes += next_end - start;
+              end = *next_end;
+            }
+          }
+        }
+
+        // the end is computed in local time, so a slot keeps its local end across a DST change
+        let start_of_minute = local
+          - Duration::seconds(local.second() as i64)
+          - Duration::nanoseconds(local.nanosecond() as i64);
+
+        Some(from_local_time(
+          start_of_minute + Duration::minutes(minutes as i64),
+          tz,
+          now,
+        ))
+      }
+    }
+  }
+
+  /// returns true if both slots are active at the same time at some point
+  /// weekly and one-off slots never overlap, as one-off slots override weekly slots
+  pub fn overlaps(&self, other: &Self) -> bool {
+    match (self, other) {
+      (
+        Self::Once { start, end },
+        Self::Once {
+          start: other_start,
+          end: other_end,
+        },
+      ) => **start < **other_end && **other_start < **end,
+
+      (Self::Weekly { .. }, Self::Weekly { .. }) => {
+        let ranges = self.week_ranges();
+        other.week_ranges().iter().any(|(other_start, other_end)| {
+          ranges
+            .iter()
+            .any(|(start, end)| start < other_end && other_start < end)
+        })
+      }
+
+      _ => false,
+    }
+  }
+
+  /// returns a description of the problem if the slot time is invalid
+  pub fn validation_error(&self) -> Option<String> {
+    use constants::validate::VALIDATE_STATION_SCHEDULE_WEEKLY_DURATION_MINUTES_MAX;
+
+    match self {
+      Self::Once { start, end } => {
+        if **end <= **start {
+          return Some("Slot end must be after its start".into());
+        }
+      }
+
+      Self::Weekly {
+        weekdays,
+        start_minute,
+        duration_minutes,
+      } => {
+        if weekdays.is_empty() {
+          return Some("Slot must have at least one weekday".into());
+        }
+
+        for (i, weekday) in weekdays.iter().enumerate() {
+          if weekdays[..i].contains(weekday) {
+            return Some("Slot weekdays cannot be repeated".into());
+          }
+        }
+
+        if *start_minute as u32 >= MINUTES_IN_DAY {
+          return Some("Slot start minute must be less than 1440".into());
+        }
+
+        if *duration_minutes == 0
+          || *duration_minutes > VALIDATE_STATION_SCHEDULE_WEEKLY_DURATION_MINUTES_MAX
+        {
+          return Some("Slot duration is either too short or too long".into());
+        }
+
+        // a slot that covers the whole week would overlap with itself
+        if *duration_minutes as u32 * weekdays.len() as u32 > MINUTES_IN_WEEK
+          || self.week_ranges_overlap()
+        {
+          return Some("Slot weekdays overlap with its duration".into());
+        }
+      }
+    }
+
+    None
+  }
+
+  fn week_ranges_overlap(&self) -> bool {
+    let ranges = self.week_ranges();
+    ranges.iter().enumerate().any(|(i, (start, end))| {
+      ranges[..i]
+        .iter()
+        .any(|(other_start, other_end)| start < other_end && other_start < end)
+    })
+  }
+}
+
+impl StationScheduleSource {
+  /// returns a description of the problem if the slot source is invalid
+  pub fn validation_error(&self) -> Option<String> {
+    use constants::validate::VALIDATE_STATION_SCHEDULE_PLAYLIST_FILES_MAX_LEN;
+
+    match self {
+      Self::Live => {}
+
+      Self::Playlist {
+        playlist_id,
+        file_ids,
+      } => {
+        if playlist_id.is_some() && !file_ids.is_empty() {
+          return Some("Slot cannot select both a playlist and playlist files".into());
+        }
+
+        if file_ids.len() > VALIDATE_STATION_SCHEDULE_PLAYLIST_FILES_MAX_LEN {
+          return Some(format!(
+            "Slot playlist cannot have more than {VALIDATE_STATION_SCHEDULE_PLAYLIST_FILES_MAX_LEN} files"
+          ));
+        }
+      }
+
+      Self::ExternalRelay { url } => {
+        if !crate::station::is_valid_external_relay_url(url) {
+          return Some(format!("Slot external relay URL {url} is invalid"));
+        }
+      }
+    }
+
+    None
+  }
+}
+
+impl StationSchedule {
+  /// returns a description of the problem if this slot is invalid
+  /// or if it overlaps with any of the `others` slots of the station
+  pub fn validation_error(&self, others: &[StationSchedule]) -> Option<String> {
+    if let Some(message) = self.time.validation_error() {
+      return Some(message);
+    }
+
+    if let Some(message) = self.source.validation_error() {
+      return Some(message);
+    }
+
+    for other in others {
+      if other.id != self.id && self.time.overlaps(&other.time) {
+        return Some(format!("Slot overlaps with slot {}", other.id));
+      }
+    }
+
+    None
+  }
+
+  /// the slot active at `now`, one-off slots have precedence over weekly slots
+  pub fn active_at(
+    slots: &[StationSchedule],
+    now: OffsetDateTime,
+    tz: &Tz,
+  ) -> Option<&StationSchedule> {
+    let is_active = |slot: &&StationSchedule| slot.time.is_active_at(now, tz);
+
+    let once = slots
+      .iter()
+      .filter(|slot| matches!(slot.time, StationScheduleTime::Once { .. }))
+      .find(is_active);
+
+    once.or_else(|| {
+      slots
+        .iter()
+        .filter(|slot| matches!(slot.time, StationScheduleTime::Weekly { .. }))
+        .find(is_active)
+    })
+  }
+
+  pub async fn list_for_station(
+    station_id: &str,
+  ) -> Result<Vec<StationSchedule>, mongodb::error::Error> {
+    let filter = doc! { Self::KEY_STATION_ID: station_id };
+    Self::cl().find(filter, None).await?.try_collect().await
+  }
+
+  /// the slot of the station schedule that is active now
+  pub async fn active_for_station(
+    station: &Station,
+  ) -> Result<Option<StationSchedule>, mongodb::error::Error> {
+    let slots = Self::list_for_station(&station.id).await?;
+    let now = OffsetDateTime::now_utc();
+    let slot = Self::active_at(&slots, now, station.tz()).cloned();
+    Ok(slot)
+  }
+}
+
+impl Model for StationSchedule {
+  const CL_NAME: &'static str = "station_schedules";
+  const UID_LEN: usize = 12;
+
+  fn indexes() -> Vec<IndexModel> {
+    let station_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1 })
+      .build();
+
+    vec![station_id]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use time::macros::datetime;
+  use time_tz::timezones::db::america::{argentina::BUENOS_AIRES, NEW_YORK};
+  use time_tz::timezones::db::UTC;
+
+  fn weekly(
+    weekdays: Vec<StationScheduleWeekday>,
+    start_minute: u16,
+    duration_minutes: u16,
+  ) -> StationScheduleTime {
+    StationScheduleTime::Weekly {
+      weekdays,
+      start_minute,
+      duration_minutes,
+    }
+  }
+
+  fn slot(id: &str, time: StationScheduleTime) -> StationSchedule {
+    StationSchedule {
+      id: id.into(),
+      station_id: "station".into(),
+      time,
+      source: StationScheduleSource::Live,
+      created_at: DateTime::now(),
+      updated_at: DateTime::now(),
+    }
+  }
+
+  #[test]
+  fn weekly_slot_is_active_in_local_time() {
+    use StationScheduleWeekday::*;
+    // mondays from 22:00 to 02:00
+    let time = weekly(vec![Monday], 22 * 60, 4 * 60);
+
+    // 2024-01-01 is a monday
+    assert!(time.is_active_at(datetime!(2024-01-01 22:30 UTC), UTC));
+    assert!(time.is_active_at(datetime!(2024-01-02 01:59 UTC), UTC));
+    assert!(!time.is_active_at(datetime!(2024-01-02 02:00 UTC), UTC));
+
+    // UTC-3 without DST
+    assert!(!time.is_active_at(datetime!(2024-01-01 22:30 UTC), BUENOS_AIRES));
+    assert!(time.is_active_at(datetime!(2024-01-02 01:30 UTC), BUENOS_AIRES));
+  }
+
+  #[test]
+  fn weekly_slot_follows_dst_changes() {
+    use StationScheduleWeekday::*;
+    // mondays from 22:00 to 02:00
+    let time = weekly(vec![Monday], 22 * 60, 4 * 60);
+
+    // 2024-01-01 and 2024-07-01 are mondays, New York is UTC-5 in winter and UTC-4 in summer
+    assert!(time.is_active_at(datetime!(2024-01-02 03:30 UTC), NEW_YORK));
+    assert!(!time.is_active_at(datetime!(2024-01-02 02:30 UTC), NEW_YORK));
+    assert!(time.is_active_at(datetime!(2024-07-02 02:30 UTC), NEW_YORK));
+    assert!(!time.is_active_at(datetime!(2024-07-02 06:00 UTC), NEW_YORK));
+
+    assert_eq!(
+      time.active_until(datetime!(2024-01-02 03:30 UTC), NEW_YORK),
+      Some(datetime!(2024-01-02 07:00 UTC))
+    );
+    assert_eq!(
+      time.active_until(datetime!(2024-07-02 02:30 UTC), NEW_YORK),
+      Some(datetime!(2024-07-02 06:00 UTC))
+    );
+
+    // sundays from 00:00 to 04:00, 2024-11-03 is a sunday and 01:00 to 02:00 happens twice
+    let time = weekly(vec![Sunday], 0, 4 * 60);
+    assert_eq!(
+      time.active_until(datetime!(2024-11-03 05:30 UTC), NEW_YORK),
+      Some(datetime!(2024-11-03 09:00 UTC))
+    );
+  }
+
+  #[test]
+  fn weekly_slot_wraps_at_end_of_week() {
+    use StationScheduleWeekday::*;
+    // sundays from 23:00 to 01:00
+    let time = weekly(vec![Sunday], 23 * 60, 120);
+
+    // 2024-01-07 is a sunday
+    assert!(time.is_active_at(datetime!(2024-01-07 23:30 UTC), UTC));
+    assert!(time.is_active_at(datetime!(2024-01-08 00:30 UTC), UTC));
+    assert!(!time.is_active_at(datetime!(2024-01-08 01:00 UTC), UTC));
+  }
+
+  #[test]
+  fn weekly_slot_active_until_joins_consecutive_days() {
+    use StationScheduleWeekday::*;
+    // sunday and monday all day
+    let time = weekly(vec![Sunday, Monday], 0, 24 * 60);
+
+    // 2024-01-07 is a sunday
+    assert_eq!(
+      time.active_until(datetime!(2024-01-07 10:30:15 UTC), UTC),
+      Some(datetime!(2024-01-09 00:00 UTC))
+    );
+
+    // UTC-3 without DST
+    assert_eq!(
+      time.active_until(datetime!(2024-01-08 12:00 UTC), BUENOS_AIRES),
+      Some(datetime!(2024-01-09 03:00 UTC))
+    );
+
+    assert_eq!(
+      time.active_until(datetime!(2024-01-09 00:00 UTC), UTC),
+      None
+    );
+
+    let time = weekly(vec![Saturday], 22 * 60, 60);
+    assert_eq!(
+      time.active_until(datetime!(2024-01-06 22:59:59 UTC), UTC),
+      Some(datetime!(2024-01-06 23:00 UTC))
+    );
+  }
+
+  #[test]
+  fn once_slots_override_weekly_slots() {
+    use StationScheduleWeekday::*;
+    let slots = vec![
+      slot("weekly", weekly(vec![Monday], 0, 24 * 60)),
+      slot(
+        "once",
+        StationScheduleTime::Once {
+          start: datetime!(2024-01-01 10:00 UTC).into(),
+          end: datetime!(2024-01-01 11:00 UTC).into(),
+        },
+      ),
+    ];
+
+    let active = |now| StationSchedule::active_at(&slots, now, UTC).map(|slot| slot.id.as_str());
+
+    assert_eq!(active(datetime!(2024-01-01 09:59 UTC)), Some("weekly"));
+    assert_eq!(active(datetime!(2024-01-01 10:30 UTC)), Some("once"));
+    assert_eq!(active(datetime!(2024-01-02 10:30 UTC)), None);
+  }
+
+  #[test]
+  fn weekly_slots_overlap() {
+    use StationScheduleWeekday::*;
+    let a = weekly(vec![Monday, Wednesday], 20 * 60, 5 * 60);
+    let b = weekly(vec![Tuesday], 0, 60);
+    let c = weekly(vec![Tuesday], 60, 60);
+
+    assert!(a.overlaps(&b));
+    assert!(!a.overlaps(&c));
+    assert!(!b.overlaps(&c));
+  }
+
+  #[test]
+  fn invalid_weekly_slots() {
+    use StationScheduleWeekday::*;
+    assert!(weekly(vec![], 0, 60).validation_error().is_some());
+    assert!(weekly(vec![Monday, Monday], 0, 60)
+      .validation_error()
+      .is_some());
+    assert!(weekly(vec![Monday], 24 * 60, 60)
+      .validation_error()
+      .is_some());
+    assert!(weekly(vec![Monday], 0, 0).validation_error().is_some());
+    assert!(weekly(vec![Monday, Tuesday], 0, 25 * 60)
+      .validation_error()
+      .is_some());
+    assert!(weekly(vec![Monday, Tuesday], 0, 24 * 60)
+      .validation_error()
+      .is_none());
+  }
+
+  #[test]
+  fn playlist_source_selects_a_playlist_or_files() {
+    let source = |playlist_id: Option<&str>, file_ids: Vec<&str>| StationScheduleSource::Playlist {
+      playlist_id: playlist_id.map(String::from),
+      file_ids: file_ids.into_iter().map(String::from).collect(),
+    };
+
+    assert!(source(None, vec![]).validation_error().is_none());
+    assert!(source(Some("playlist"), vec![])
+      .validation_error()
+      .is_none());
+    assert!(source(None, vec!["file"]).validation_error().is_none());
+    assert!(source(Some("playlist"), vec!["file"])
+      .validation_error()
+      .is_some());
+  }
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, StationSchedule::KEY_ID);
+  }
+}
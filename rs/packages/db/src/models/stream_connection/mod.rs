@@ -12,6 +12,7 @@ pub mod analytics;
 pub mod app_analytics;
 pub mod index;
 pub mod lite;
+pub mod rejection;
 pub mod stats;
 
 #[allow(clippy::bool_comparison)]
@@ -0,0 +1,155 @@
+use super::stats::RejectionStats;
+use crate::station::listener_access::ListenerAccessDenied;
+use crate::Model;
+use geoip::CountryCode;
+use mongodb::bson::{doc, Document};
+use mongodb::IndexModel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use ts_rs::TS;
+
+crate::register!(StreamConnectionRejection);
+
+/// a stream connection that was rejected by the stream server before starting to stream
+/// these are not stream connections, so they are not counted in the stream stats sessions
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct StreamConnectionRejection {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub station_id: String,
+  pub account_id: String,
+  pub deployment_id: String,
+  pub reason: StreamConnectionRejectionReason,
+  #[serde(serialize_with = "serde_util::ip::serialize")]
+  #[serde(deserialize_with = "serde_util::ip::deserialize")]
+  #[ts(type = "string")]
+  pub ip: IpAddr,
+  pub country_code: Option<CountryCode>,
+  pub created_at: DateTime,
+}
+
+#[derive(
+  Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, TS, JsonSchema,
+)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub enum StreamConnectionRejectionReason {
+  /// the account of the station reached its listeners limit
+  ListenersLimit,
+  /// the account of the station used all the transfer of its current billing period
+  TransferLimit,
+  /// the network of the listener is blocked by the station listener access settings
+  IpBlocked,
+  /// the country of the listener is blocked by the station listener access settings
+  CountryBlocked,
+  /// the referer of the request is blocked by the station listener access settings
+  RefererBlocked,
+}
+
+impl StreamConnectionRejectionReason {
+  pub const ALL: [Self; 5] = [
+    Self::ListenersLimit,
+    Self::TransferLimit,
+    Self::IpBlocked,
+    Self::CountryBlocked,
+    Self::RefererBlocked,
+  ];
+}
+
+impl From<ListenerAccessDenied> for StreamConnectionRejectionReason {
+  fn from(denied: ListenerAccessDenied) -> Self {
+    match denied {
+      ListenerAccessDenied::Ip => Self::IpBlocked,
+      ListenerAccessDenied::Country => Self::CountryBlocked,
+      ListenerAccessDenied::Referer => Self::RefererBlocked,
+    }
+  }
+}
+
+impl Model for StreamConnectionRejection {
+  const CL_NAME: &'static str = "stream_connection_rejections";
+  const UID_LEN: usize = 12;
+
+  fn indexes() -> Vec<IndexModel> {
+    let created_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_CREATED_AT: 1 })
+      .build();
+
+    let station_id_created_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATION_ID: 1, Self::KEY_CREATED_AT: 1 })
+      .build();
+
+    let account_id_created_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_ACCOUNT_ID: 1, Self::KEY_CREATED_AT: 1 })
+      .build();
+
+    vec![created_at, station_id_created_at, account_id_created_at]
+  }
+}
+
+impl StreamConnectionRejection {
+  pub async fn get_stats(filter: Document) -> Result<RejectionStats, mongodb::error::Error> {
+    let now = time::OffsetDateTime::now_utc();
+
+    let count_since = |duration: time::Duration| {
+      let since: DateTime = (now - duration).into();
+      let mut filter = filter.clone();
+      filter.insert(Self::KEY_CREATED_AT, doc! { "$gte": since });
+      async move { Self::cl().count_documents(filter, None).await }
+    };
+
+    let count_reason_since = |reason: StreamConnectionRejectionReason, duration: time::Duration| {
+      let since: DateTime = (now - duration).into();
+      let mut filter = filter.clone();
+      filter.insert(Self::KEY_CREATED_AT, doc! { "$gte": since });
+      filter.insert(Self::KEY_REASON, mongodb::bson::to_bson(&reason).unwrap());
+      async move {
+        let count = Self::cl().count_documents(filter, None).await?;
+        Ok::<_, mongodb::error::Error>((reason, count))
+      }
+    };
+
+    let by_reason = futures_util::future::try_join_all(
+      StreamConnectionRejectionReason::ALL
+        .into_iter()
+        .map(|reason| count_reason_since(reason, time::Duration::DAY * 30)),
+    );
+
+    let (last_24h, last_7d, last_30d, by_reason) = tokio::try_join!(
+      count_since(time::Duration::HOUR * 24),
+      count_since(time::Duration::DAY * 7),
+      count_since(time::Duration::DAY * 30),
+      by_reason,
+    )?;
+
+    let last_30d_by_reason: BTreeMap<StreamConnectionRejectionReason, f64> = by_reason
+      .into_iter()
+      .filter(|(_, count)| *count != 0)
+      .map(|(reason, count)| (reason, count as f64))
+      .collect();
+
+    Ok(RejectionStats {
+      last_24h: last_24h as f64,
+      last_7d: last_7d as f64,
+      last_30d: last_30d as f64,
+      last_30d_by_reason,
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, StreamConnectionRejection::KEY_ID);
+  }
+}
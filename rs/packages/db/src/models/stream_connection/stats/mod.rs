@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 
+use super::rejection::StreamConnectionRejectionReason;
 use geoip::CountryCode;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -24,3 +25,13 @@ pub struct StatsItem {
   // #[ts(type = "Record<string, number | undefined>")]
   // pub country_ips: BTreeMap<CountryCode, f64>,
 }
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/stream-connection-stats/")]
+pub struct RejectionStats {
+  pub last_24h: f64,
+  pub last_7d: f64,
+  pub last_30d: f64,
+  #[ts(type = "Record<string, number | undefined>")]
+  pub last_30d_by_reason: BTreeMap<StreamConnectionRejectionReason, f64>,
+}
@@ -1,26 +1,27 @@
-use log::*;
 use mongodb::IndexModel;
 use mongodb::{bson::doc, options::IndexOptions};
 use serde::{Deserialize, Serialize};
 use serde_util::DateTime;
 use ts_rs::TS;
 
-use crate::account::{Account, Limit, Limits};
-use crate::{run_transaction, Model};
+use crate::Model;
 
 crate::register!(TransferCheckpoint);
 
+/// a global reset of the used transfer of all accounts at the start of a calendar month
+/// this was replaced by per account billing periods (see [`crate::billing_period`])
+/// and is kept to access the checkpoints created before that
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export, export_to = "../../../defs/db/")]
 #[macros::keys]
 pub struct TransferCheckpoint {
   #[serde(rename = "_id")]
-  id: String,
-  year: f64,
-  month: f64,
-  matched_count: f64,
-  modified_count: f64,
-  created_at: DateTime,
+  pub id: String,
+  pub year: f64,
+  pub month: f64,
+  pub matched_count: f64,
+  pub modified_count: f64,
+  pub created_at: DateTime,
 }
 
 impl Model for TransferCheckpoint {
@@ -41,95 +42,6 @@ impl Model for TransferCheckpoint {
   }
 }
 
-pub async fn checkpoint_now() -> Result<Option<TransferCheckpoint>, mongodb::error::Error> {
-  let now: time::OffsetDateTime = DateTime::now().into();
-
-  let year = now.year() as f64;
-  let month = now.month() as u8 as f64;
-
-  run_transaction!(session => {
-    let filter = doc! { TransferCheckpoint::KEY_YEAR: year , TransferCheckpoint::KEY_MONTH: month };
-    let exists = tx_try!(TransferCheckpoint::exists_with_session(filter, &mut session).await);
-    if exists {
-      return Ok(None)
-    }
-
-    const KEY_LIMITS_TRANSFER_USED: &str = const_str::concat!(
-      Account::KEY_LIMITS,
-      ".",
-      Limits::KEY_TRANSFER,
-      ".",
-      Limit::KEY_USED
-    );
-
-    let update = doc!{
-      "$set": {
-        KEY_LIMITS_TRANSFER_USED: 0_f64
-      }
-    };
-
-    let update_result = tx_try!(Account::cl().update_many_with_session(doc!{}, update, None, &mut session).await);
-
-    let doc = TransferCheckpoint {
-      id: TransferCheckpoint::uid(),
-      year,
-      month,
-      matched_count: update_result.matched_count as f64,
-      modified_count: update_result.modified_count as f64,
-      created_at: DateTime::now(),
-    };
-
-    tx_try!(TransferCheckpoint::insert_with_session(&doc, &mut session).await);
-
-    Ok(Some(doc))
-  })
-}
-
-/// This job will run every hour and check that a TransferCheckpoint was created for the current month
-/// TransferCheckpoint will set used transfer to 0 for all stations at the start of every month
-pub fn start_background_task() -> tokio::task::JoinHandle<()> {
-  tokio::spawn(async move {
-    info!(target: "service", "transfer checkpoint background job started");
-
-    loop {
-      match checkpoint_now().await {
-        Err(e) => {
-          warn!(target: "service", "error creating transfer checkpoint: {e}");
-        }
-
-        Ok(Some(doc)) => {
-          info!(
-            target: "service",
-            "transfer checkpoint created: {} {}-{} matched={}, modified={}",
-            doc.id, doc.year, doc.month, doc.matched_count, doc.modified_count
-          );
-        }
-
-        Ok(None) => {
-          info!(target: "service", "transfer checkpoint => current month checkpoint already exists");
-        }
-      }
-
-      let rand_offset_secs = rand::random::<f64>() * 10.0;
-
-      let now: time::OffsetDateTime = DateTime::now().into();
-      let sleep_until = now
-        .replace_minute(0)
-        .unwrap()
-        .replace_second(0)
-        .unwrap()
-        .replace_microsecond(0)
-        .unwrap()
-        + time::Duration::HOUR
-        + time::Duration::seconds_f64(rand_offset_secs);
-
-      info!(target: "service", "transfer checkpoint: sleeping until {}", sleep_until);
-      let sleep_secs = (sleep_until - now).as_seconds_f64();
-      tokio::time::sleep(std::time::Duration::from_secs_f64(sleep_secs)).await;
-    }
-  })
-}
-
 #[cfg(test)]
 mod test {
   use super::*;
@@ -0,0 +1,144 @@
+use crate::event::EventKind;
+use crate::Model;
+use constants::WEBHOOK_SUBSCRIPTIONS_CACHE_SECS;
+use futures_util::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::IndexModel;
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use ts_rs::TS;
+
+crate::register!(Webhook);
+
+struct SubscriptionsEntry {
+  fetched_at: Instant,
+  webhooks: Vec<Webhook>,
+}
+
+/// the enabled webhooks of each account, by account id
+static SUBSCRIPTIONS: Mutex<Option<HashMap<String, SubscriptionsEntry>>> = Mutex::new(None);
+
+/// an url of an account that receives the events of the account stations
+///
+/// each event is sent as a WebhookDelivery, signed with the secret of the webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct Webhook {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub account_id: String,
+  pub url: String,
+  /// key of the HMAC-SHA256 signature sent with each delivery
+  pub secret: String,
+  /// the kinds of events sent to the url, all the events are sent if empty
+  pub events: Vec<EventKind>,
+  /// disabled webhooks do not receive new deliveries
+  pub enabled: bool,
+  pub created_at: DateTime,
+  pub updated_at: DateTime,
+}
+
+impl Webhook {
+  pub const RANDOM_SECRET_LEN: usize = 32;
+
+  pub fn random_secret() -> String {
+    uid::uid(Self::RANDOM_SECRET_LEN)
+  }
+
+  /// if an event of this kind must be delivered to this webhook
+  pub fn accepts(&self, kind: EventKind) -> bool {
+    self.enabled && (self.events.is_empty() || self.events.contains(&kind))
+  }
+
+  /// if an enabled webhook of the account accepts events of this kind
+  ///
+  /// the webhooks of the account are cached for WEBHOOK_SUBSCRIPTIONS_CACHE_SECS,
+  /// so a change in the webhooks can take that long to be seen here
+  pub async fn is_subscribed(
+    account_id: &str,
+    kind: EventKind,
+  ) -> Result<bool, mongodb::error::Error> {
+    let ttl = Duration::from_secs(WEBHOOK_SUBSCRIPTIONS_CACHE_SECS);
+
+    if let Some(map) = SUBSCRIPTIONS.lock().as_ref() {
+      if let Some(entry) = map.get(account_id) {
+        if entry.fetched_at.elapsed() < ttl {
+          return Ok(entry.webhooks.iter().any(|w| w.accepts(kind)));
+        }
+      }
+    }
+
+    let filter = doc! {
+      Self::KEY_ACCOUNT_ID: account_id,
+      Self::KEY_ENABLED: true,
+    };
+
+    let webhooks: Vec<Self> = Self::cl().find(filter, None).await?.try_collect().await?;
+    let subscribed = webhooks.iter().any(|w| w.accepts(kind));
+
+    let mut lock = SUBSCRIPTIONS.lock();
+    let map = lock.get_or_insert_with(HashMap::new);
+    map.retain(|_, entry| entry.fetched_at.elapsed() < ttl);
+    map.insert(
+      account_id.to_string(),
+      SubscriptionsEntry {
+        fetched_at: Instant::now(),
+        webhooks,
+      },
+    );
+
+    Ok(subscribed)
+  }
+}
+
+impl Model for Webhook {
+  const UID_LEN: usize = 12;
+  const CL_NAME: &'static str = "webhooks";
+
+  fn indexes() -> Vec<IndexModel> {
+    let account_id = IndexModel::builder()
+      .keys(doc! { Self::KEY_ACCOUNT_ID: 1 })
+      .build();
+    vec![account_id]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, Webhook::KEY_ID);
+  }
+
+  #[test]
+  fn accepts_events() {
+    let now = DateTime::now();
+    let mut webhook = Webhook {
+      id: Webhook::uid(),
+      account_id: "account".into(),
+      url: "https://example.com/hook".into(),
+      secret: Webhook::random_secret(),
+      events: vec![],
+      enabled: true,
+      created_at: now,
+      updated_at: now,
+    };
+
+    assert!(webhook.accepts(EventKind::UploadFinished));
+
+    webhook.events = vec![EventKind::LiveSourceConnected];
+    assert!(webhook.accepts(EventKind::LiveSourceConnected));
+    assert!(!webhook.accepts(EventKind::UploadFinished));
+
+    webhook.enabled = false;
+    assert!(!webhook.accepts(EventKind::LiveSourceConnected));
+  }
+}
@@ -0,0 +1,250 @@
+use crate::event::{Event, EventKind};
+use crate::station::Station;
+use crate::webhook::Webhook;
+use crate::Model;
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, Bson, SerializerOptions};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use mongodb::IndexModel;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_util::DateTime;
+use ts_rs::TS;
+
+crate::register!(WebhookDelivery);
+
+/// an event sent (or to be sent) to a webhook url, with the log of its attempts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct WebhookDelivery {
+  #[serde(rename = "_id")]
+  pub id: String,
+  pub webhook_id: String,
+  pub account_id: String,
+  pub event_id: String,
+  pub event_kind: EventKind,
+  /// the json body sent to the webhook url
+  pub payload: String,
+  /// the delivery that was manually replayed to create this one
+  pub replay_of: Option<String>,
+  pub state: WebhookDeliveryState,
+  pub attempts: Vec<WebhookDeliveryAttempt>,
+  /// when the next attempt is due, None if the delivery is no longer pending
+  pub next_attempt_at: Option<DateTime>,
+  pub created_at: DateTime,
+  pub updated_at: DateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookDeliveryState {
+  Pending,
+  /// the url responded with a 2xx status
+  Success,
+  /// all the attempts failed
+  Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export, export_to = "../../../defs/db/")]
+#[serde(rename_all = "snake_case")]
+#[macros::keys]
+pub struct WebhookDeliveryAttempt {
+  pub at: DateTime,
+  /// the response status, None if there was no response
+  pub status: Option<u16>,
+  /// the connection error or the reason the response was not accepted
+  pub error: Option<String>,
+  #[serde(serialize_with = "serde_util::as_f64::serialize")]
+  #[serde(deserialize_with = "serde_util::as_f64::deserialize")]
+  pub duration_ms: u64,
+}
+
+impl From<WebhookDeliveryState> for Bson {
+  fn from(value: WebhookDeliveryState) -> Self {
+    mongodb::bson::to_bson(&value).expect("error converting WebhookDeliveryState to Bson")
+  }
+}
+
+impl From<WebhookDeliveryAttempt> for Bson {
+  fn from(value: WebhookDeliveryAttempt) -> Self {
+    mongodb::bson::to_bson_with_options(
+      &value,
+      SerializerOptions::builder().human_readable(false).build(),
+    )
+    .expect("error converting WebhookDeliveryAttempt to Bson")
+  }
+}
+
+impl WebhookDelivery {
+  fn new(webhook: &Webhook, event: &Event, payload: String) -> Self {
+    let now = DateTime::now();
+    Self {
+      id: Self::uid(),
+      webhook_id: webhook.id.clone(),
+      account_id: webhook.account_id.clone(),
+      event_id: event.id.clone(),
+      event_kind: event.variant.kind(),
+      payload,
+      replay_of: None,
+      state: WebhookDeliveryState::Pending,
+      attempts: vec![],
+      next_attempt_at: Some(now),
+      created_at: now,
+      updated_at: now,
+    }
+  }
+
+  /// creates a pending delivery of the event for each webhook of the event account that accepts it
+  pub async fn create_for_event(event: &Event) -> Result<Vec<Self>, mongodb::error::Error> {
+    let account_id = match (event.variant.account_id(), event.variant.station_id()) {
+      (Some(account_id), _) => account_id.to_string(),
+      (None, Some(station_id)) => match Station::get_by_id(station_id).await? {
+        Some(station) => station.account_id,
+        None => return Ok(vec![]),
+      },
+      (None, None) => return Ok(vec![]),
+    };
+
+    let filter = doc! {
+      Webhook::KEY_ACCOUNT_ID: &account_id,
+      Webhook::KEY_ENABLED: true,
+    };
+
+    let webhooks: Vec<Webhook> = Webhook::cl()
+      .find(filter, None)
+      .await?
+      .try_collect()
+      .await?;
+
+    let kind = event.variant.kind();
+    let webhooks: Vec<Webhook> = webhooks.into_iter().filter(|w| w.accepts(kind)).collect();
+    if webhooks.is_empty() {
+      return Ok(vec![]);
+    }
+
+    let payload = serde_json::to_string(event).expect("error serializing Event");
+
+    let deliveries: Vec<Self> = webhooks
+      .iter()
+      .map(|webhook| Self::new(webhook, event, payload.clone()))
+      .collect();
+
+    Self::insert_many(&deliveries).await?;
+
+    Ok(deliveries)
+  }
+
+  /// creates a new pending delivery with the same payload of this one
+  pub async fn replay(&self) -> Result<Self, mongodb::error::Error> {
+    let now = DateTime::now();
+    let delivery = Self {
+      id: Self::uid(),
+      webhook_id: self.webhook_id.clone(),
+      account_id: self.account_id.clone(),
+      event_id: self.event_id.clone(),
+      event_kind: self.event_kind,
+      payload: self.payload.clone(),
+      replay_of: Some(self.id.clone()),
+      state: WebhookDeliveryState::Pending,
+      attempts: vec![],
+      next_attempt_at: Some(now),
+      created_at: now,
+      updated_at: now,
+    };
+
+    Self::insert(&delivery).await?;
+    Ok(delivery)
+  }
+
+  /// takes the pending delivery with the oldest due attempt
+  ///
+  /// the next attempt of the returned delivery is moved `lease_secs` to the future,
+  /// so other deployments do not take it while it is being sent,
+  /// if the deployment stops before recording the attempt the delivery is retried after the lease
+  pub async fn claim_due(lease_secs: u64) -> Result<Option<Self>, mongodb::error::Error> {
+    let now = DateTime::now();
+    let lease_until = DateTime::from(
+      now
+        .inner()
+        .saturating_add(time::Duration::SECOND * lease_secs as f64),
+    );
+
+    let filter = doc! {
+      Self::KEY_STATE: WebhookDeliveryState::Pending,
+      Self::KEY_NEXT_ATTEMPT_AT: { "$lte": now },
+    };
+
+    let update = doc! {
+      "$set": {
+        Self::KEY_NEXT_ATTEMPT_AT: lease_until,
+        Self::KEY_UPDATED_AT: now,
+      }
+    };
+
+    let options = FindOneAndUpdateOptions::builder()
+      .sort(doc! { Self::KEY_NEXT_ATTEMPT_AT: 1 })
+      .return_document(ReturnDocument::After)
+      .build();
+
+    Self::cl()
+      .find_one_and_update(filter, update, options)
+      .await
+  }
+
+  /// appends an attempt to the log of the delivery and sets its new state
+  pub async fn record_attempt(
+    id: &str,
+    attempt: WebhookDeliveryAttempt,
+    state: WebhookDeliveryState,
+    next_attempt_at: Option<DateTime>,
+  ) -> Result<(), mongodb::error::Error> {
+    let update = doc! {
+      "$push": { Self::KEY_ATTEMPTS: attempt },
+      "$set": {
+        Self::KEY_STATE: state,
+        Self::KEY_NEXT_ATTEMPT_AT: next_attempt_at,
+        Self::KEY_UPDATED_AT: DateTime::now(),
+      }
+    };
+
+    Self::update_by_id(id, update).await?;
+    Ok(())
+  }
+}
+
+impl Model for WebhookDelivery {
+  const UID_LEN: usize = 16;
+  const CL_NAME: &'static str = "webhook_deliveries";
+
+  fn indexes() -> Vec<IndexModel> {
+    let webhook_id_created_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_WEBHOOK_ID: 1, Self::KEY_CREATED_AT: -1 })
+      .build();
+    let state_next_attempt_at = IndexModel::builder()
+      .keys(doc! { Self::KEY_STATE: 1, Self::KEY_NEXT_ATTEMPT_AT: 1 })
+      .build();
+    vec![webhook_id_created_at, state_next_attempt_at]
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn keys_match() {
+    assert_eq!(crate::KEY_ID, WebhookDelivery::KEY_ID);
+  }
+
+  #[test]
+  fn state_to_bson() {
+    assert_eq!(
+      Bson::from(WebhookDeliveryState::Pending),
+      Bson::String("pending".into())
+    );
+  }
+}
@@ -0,0 +1,211 @@
+use bytes::Bytes;
+use constants::{AUDIO_FILE_SILENCE_MIN_DURATION_MS, AUDIO_FILE_SILENCE_THRESHOLD_DB};
+use std::process::Stdio;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::sync::mpsc::Receiver;
+
+/// silences closer than this to the start or the end of the input are considered leading or trailing
+const EDGE_TOLERANCE_SECS: f64 = 0.05;
+
+/// loudness and silences of an audio input
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Analysis {
+  /// EBU R128 integrated loudness in LUFS
+  pub integrated_lufs: Option<f64>,
+  /// true peak in dBTP
+  pub true_peak_dbtp: Option<f64>,
+  /// start and end in seconds of the silences of the input
+  /// the end is None if the input ended in silence
+  pub silences: Vec<(f64, Option<f64>)>,
+  /// duration in seconds of the decoded input
+  pub duration: Option<f64>,
+}
+
+impl Analysis {
+  /// parses the log output of the ebur128 and silencedetect filters and the progress output
+  pub fn parse(log: &str) -> Self {
+    let mut analysis = Self::default();
+
+    fn number(src: &str) -> Option<f64> {
+      src.split_whitespace().next()?.parse().ok()
+    }
+
+    for line in log.lines() {
+      let trimmed = line.trim();
+      if let Some(v) = trimmed.strip_prefix("I:") {
+        analysis.integrated_lufs = number(v);
+      } else if let Some(v) = trimmed.strip_prefix("Peak:") {
+        analysis.true_peak_dbtp = number(v);
+      } else if let Some(v) = trimmed.strip_prefix("out_time_us=") {
+        // the progress is reported periodically, the last report has the full duration
+        if let Some(us) = number(v) {
+          analysis.duration = Some(us / 1_000_000.0);
+        }
+      } else if let Some(i) = line.find("silence_start:") {
+        if let Some(start) = number(&line[i + "silence_start:".len()..]) {
+          analysis.silences.push((start.max(0.0), None));
+        }
+      } else if let Some(i) = line.find("silence_end:") {
+        if let (Some(end), Some(last)) = (
+          number(&line[i + "silence_end:".len()..]),
+          analysis.silences.last_mut(),
+        ) {
+          last.1 = Some(end);
+        }
+      }
+    }
+
+    analysis
+  }
+
+  /// duration in seconds of the silence at the start of the input
+  pub fn leading_silence(&self) -> f64 {
+    match self.silences.first() {
+      Some((start, Some(end))) if *start <= EDGE_TOLERANCE_SECS => *end,
+      _ => 0.0,
+    }
+  }
+
+  /// duration in seconds of the silence at the end of an input of `duration` seconds
+  pub fn trailing_silence(&self, duration: f64) -> f64 {
+    match self.silences.last() {
+      Some((start, None)) => (duration - start).max(0.0),
+      Some((start, Some(end))) if *end >= duration - EDGE_TOLERANCE_SECS => {
+        (duration - start).max(0.0)
+      }
+      _ => 0.0,
+    }
+  }
+}
+
+/// measures the loudness and the silences of the input with ffmpeg
+pub async fn get(mut data: Receiver<Bytes>) -> Result<Analysis, std::io::Error> {
+  let mut cmd = Command::new("ffmpeg");
+
+  cmd.kill_on_drop(true);
+
+  // verbosity, the filters log their results at info level
+  cmd.arg("-v");
+  cmd.arg("info");
+  cmd.arg("-nostats");
+
+  // the progress reports the duration of the decoded input
+  cmd.arg("-progress");
+  cmd.arg("pipe:2");
+
+  // input
+  cmd.arg("-i");
+  cmd.arg("-");
+
+  cmd.arg("-vn");
+
+  // filters
+  cmd.arg("-af");
+  cmd.arg(format!(
+    "ebur128=peak=true:framelog=quiet,silencedetect=noise={}dB:d={}",
+    AUDIO_FILE_SILENCE_THRESHOLD_DB,
+    AUDIO_FILE_SILENCE_MIN_DURATION_MS as f64 / 1000.0,
+  ));
+
+  // output
+  cmd.arg("-f");
+  cmd.arg("null");
+  cmd.arg("-");
+
+  cmd.stdin(Stdio::piped());
+  cmd.stdout(Stdio::null());
+  cmd.stderr(Stdio::piped());
+
+  let mut child = cmd.spawn()?;
+
+  let mut stdin = child.stdin.take().unwrap();
+  let mut stderr = child.stderr.take().unwrap();
+
+  let write = async move {
+    loop {
+      match data.recv().await {
+        None => break,
+        Some(bytes) => match stdin.write_all(bytes.as_ref()).await {
+          Err(_) => break,
+          Ok(_) => continue,
+        },
+      }
+    }
+  };
+
+  let read_stderr = async move {
+    let mut buff = vec![];
+    let _ = stderr.read_to_end(&mut buff).await;
+    buff
+  };
+
+  let wait_status = async move { child.wait().await };
+
+  let (_write, stderr, status) = tokio::join!(write, read_stderr, wait_status);
+
+  let status = status?;
+  let stderr = String::from_utf8_lossy(stderr.as_ref());
+
+  if status.success() {
+    Ok(Analysis::parse(&stderr))
+  } else {
+    Err(std::io::Error::other(format!(
+      "ffmpeg analysis process ended with non success status: {status}, stderr: {stderr}",
+    )))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const LOG: &str = "[silencedetect @ 0x5581] silence_start: 0
+[silencedetect @ 0x5581] silence_end: 1.2 | silence_duration: 1.2
+[silencedetect @ 0x5581] silence_start: 60.5
+[silencedetect @ 0x5581] silence_end: 62 | silence_duration: 1.5
+[silencedetect @ 0x5581] silence_start: 178.25
+[Parsed_ebur128_0 @ 0x5582] Summary:
+
+  Integrated loudness:
+    I:         -19.8 LUFS
+    Threshold: -30.4 LUFS
+
+  Loudness range:
+    LRA:         8.9 LU
+
+  True peak:
+    Peak:       -0.4 dBFS
+out_time_us=179500000
+progress=continue
+out_time_us=180000000
+progress=end
+";
+
+  #[test]
+  fn parses_loudness_and_silences() {
+    let analysis = Analysis::parse(LOG);
+    assert_eq!(analysis.integrated_lufs, Some(-19.8));
+    assert_eq!(analysis.true_peak_dbtp, Some(-0.4));
+    assert_eq!(
+      analysis.silences,
+      vec![(0.0, Some(1.2)), (60.5, Some(62.0)), (178.25, None)]
+    );
+    assert_eq!(analysis.duration, Some(180.0));
+  }
+
+  #[test]
+  fn leading_and_trailing_silence() {
+    let analysis = Analysis::parse(LOG);
+    assert_eq!(analysis.leading_silence(), 1.2);
+    assert_eq!(analysis.trailing_silence(180.0), 1.75);
+
+    let analysis = Analysis {
+      silences: vec![(10.0, Some(12.0))],
+      ..Default::default()
+    };
+    assert_eq!(analysis.leading_silence(), 0.0);
+    assert_eq!(analysis.trailing_silence(180.0), 0.0);
+    assert_eq!(analysis.trailing_silence(12.0), 2.0);
+  }
+}
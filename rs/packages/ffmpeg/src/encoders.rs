@@ -0,0 +1,85 @@
+use log::*;
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::sync::OnceCell;
+
+/// the encoders of the ffmpeg binary, listed once per process
+static ENCODERS: OnceCell<Vec<String>> = OnceCell::const_new();
+
+/// parses the output of `ffmpeg -encoders`
+///
+/// the list starts after a line of dashes, each line has the capability flags and the encoder name
+pub fn parse(output: &str) -> Vec<String> {
+  output
+    .lines()
+    .skip_while(|line| !line.trim().starts_with("---"))
+    .skip(1)
+    .filter_map(|line| line.split_whitespace().nth(1))
+    .map(ToString::to_string)
+    .collect()
+}
+
+async fn list() -> Result<Vec<String>, std::io::Error> {
+  let mut cmd = Command::new("ffmpeg");
+
+  cmd.kill_on_drop(true);
+
+  cmd.arg("-hide_banner");
+  cmd.arg("-encoders");
+
+  cmd.stdin(Stdio::null());
+  cmd.stdout(Stdio::piped());
+  cmd.stderr(Stdio::null());
+
+  let output = cmd.output().await?;
+
+  if !output.status.success() {
+    return Err(std::io::Error::other(format!(
+      "ffmpeg encoders process ended with non success status: {}",
+      output.status
+    )));
+  }
+
+  Ok(parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// if the ffmpeg binary was built with the encoder `name`, eg: libfdk_aac
+/// if the encoders can not be listed it is considered unavailable, the list is retried on the next call
+pub async fn has(name: &str) -> bool {
+  match ENCODERS.get_or_try_init(list).await {
+    Ok(encoders) => encoders.iter().any(|encoder| encoder == name),
+    Err(e) => {
+      warn!("error listing ffmpeg encoders: {e} => {e:?}");
+      false
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const OUTPUT: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ .F.... = Frame-level multithreading
+ ..S... = Slice-level multithreading
+ ...X.. = Codec is experimental
+ ....B. = Supports draw_horiz_band
+ .....D = Supports direct rendering method 1
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)
+ A....D libmp3lame           libmp3lame MP3 (MPEG audio layer 3) (codec mp3)
+ A....D libopus              libopus Opus (codec opus)
+";
+
+  #[test]
+  fn parses_encoders() {
+    assert_eq!(
+      parse(OUTPUT),
+      vec!["libx264", "aac", "libmp3lame", "libopus"]
+    );
+  }
+}
@@ -9,6 +9,8 @@ use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 
+pub mod analysis;
+pub mod encoders;
 pub mod metadata;
 pub mod probe;
 
@@ -53,6 +55,8 @@ pub enum Format {
   AAC,
   OGG,
   WEBM,
+  /// raw signed 16 bits little endian pcm samples
+  PCM,
 }
 
 impl Format {
@@ -62,6 +66,7 @@ impl Format {
       Self::AAC => "adts",
       Self::OGG => "ogg",
       Self::WEBM => "webm",
+      Self::PCM => "s16le",
     }
   }
 }
@@ -76,6 +81,11 @@ impl Display for Format {
 pub struct FfmpegConfig {
   pub bin: &'static str,
   pub input: Option<String>,
+
+  /// format of the input, if None ffmpeg detects it
+  /// raw PCM input is read with the FREQ and CHANNELS of the output
+  pub input_format: Option<Format>,
+
   pub loglevel: LogLevel,
   pub format: Format,
   pub kbitrate: usize,
@@ -93,6 +103,19 @@ pub struct FfmpegConfig {
   pub readrate_initial_burst: f64,
 
   pub copycodec: bool,
+
+  /// audio encoder to use, if None ffmpeg picks the default encoder of the format
+  /// only applies if COPYCODEC is false
+  pub codec: Option<&'static str>,
+
+  /// audio encoder profile, eg: aac_he
+  /// only applies if CODEC is set
+  pub profile: Option<&'static str>,
+
+  /// audio filter graph applied before encoding, eg: volume=3dB
+  /// only applies if COPYCODEC is false
+  pub filters: Option<String>,
+
   pub headers: BTreeMap<String, String>,
 }
 
@@ -153,6 +176,9 @@ impl FfmpegConfig {
   /// path to ffmpeg bin
   pub const BIN: &'static str = "ffmpeg";
 
+  /// input format
+  pub const INPUT_FORMAT: Option<Format> = None;
+
   /// log level
   pub const LOGLEVEL: LogLevel = LogLevel::Error;
 
@@ -186,6 +212,12 @@ impl FfmpegConfig {
 
   /// whether to copy as is the audio codec of the source
   pub const COPYCODEC: bool = false;
+
+  /// audio encoder
+  pub const CODEC: Option<&'static str> = None;
+
+  /// audio encoder profile
+  pub const PROFILE: Option<&'static str> = None;
 }
 
 impl Default for FfmpegConfig {
@@ -193,6 +225,7 @@ impl Default for FfmpegConfig {
     Self {
       bin: Self::BIN,
       input: None,
+      input_format: Self::INPUT_FORMAT,
       loglevel: Self::LOGLEVEL,
       kbitrate: Self::KBITRATE,
       //kminrate: Self::KMINRATE,
@@ -206,6 +239,9 @@ impl Default for FfmpegConfig {
       readrate: Self::READRATE,
       readrate_initial_burst: Self::READRATE_INITIAL_BURST,
       copycodec: Self::COPYCODEC,
+      codec: Self::CODEC,
+      profile: Self::PROFILE,
+      filters: None,
       headers: BTreeMap::new(),
     }
   }
@@ -253,6 +289,20 @@ impl Ffmpeg {
       );
     }
 
+    // input format
+    if let Some(format) = self.config.input_format {
+      cmd.arg("-f");
+      cmd.arg(format.as_str());
+
+      if format == Format::PCM {
+        cmd.arg("-ac");
+        cmd.arg(self.config.channels.to_string());
+
+        cmd.arg("-ar");
+        cmd.arg(self.config.freq.to_string());
+      }
+    }
+
     // input
     cmd.arg("-i");
     match &self.config.input {
@@ -265,6 +315,23 @@ impl Ffmpeg {
       cmd.arg("-c:a");
       cmd.arg("copy");
     } else {
+      // codec
+      if let Some(codec) = self.config.codec {
+        cmd.arg("-c:a");
+        cmd.arg(codec);
+
+        if let Some(profile) = self.config.profile {
+          cmd.arg("-profile:a");
+          cmd.arg(profile);
+        }
+      }
+
+      // filters
+      if let Some(filters) = &self.config.filters {
+        cmd.arg("-af");
+        cmd.arg(filters);
+      }
+
       // bitrate
       cmd.arg("-ab");
       cmd.arg(format!("{}k", self.config.kbitrate));
@@ -483,3 +550,48 @@ pub fn transform(
 
   Ok((producer, output))
 }
+
+/// runs ffmpeg with `input` as stdin and returns the whole output
+pub async fn transform_bytes(
+  config: FfmpegConfig,
+  input: Bytes,
+) -> Result<Vec<u8>, TransformError> {
+  let FfmpegSpawn {
+    config: _,
+    mut child,
+    mut stdin,
+    mut stdout,
+    mut stderr,
+  } = Ffmpeg::new(config).spawn()?;
+
+  let stdin_fut = async move {
+    stdin.write_all(input.as_ref()).await?;
+    // stdin is dropped here so ffmpeg sees the end of the input
+    Result::<(), std::io::Error>::Ok(())
+  };
+
+  let stdout_fut = async move {
+    let mut buf = vec![];
+    stdout.read_to_end(&mut buf).await?;
+    Result::<Vec<u8>, std::io::Error>::Ok(buf)
+  };
+
+  let stderr_fut = async move {
+    let mut buf = vec![];
+    stderr.read_to_end(&mut buf).await?;
+    Result::<String, std::io::Error>::Ok(String::from_utf8_lossy(&buf).to_string())
+  };
+
+  let (stdin, stdout, stderr) = tokio::join!(stdin_fut, stdout_fut, stderr_fut);
+  let status = child.wait().await?;
+
+  if !status.success() {
+    return Err(TransformError::Exit {
+      status,
+      stderr: stderr.ok(),
+    });
+  }
+
+  stdin?;
+  Ok(stdout?)
+}
@@ -51,6 +51,7 @@ fn it_works() {
   deepsize::DeepSizeOf,
 )]
 #[ts(export, export_to = "../../../defs/")]
+#[macros::schema_ts_export]
 #[macros::keys]
 // #[repr(u8)]
 pub enum CountryCode {
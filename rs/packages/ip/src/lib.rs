@@ -1,8 +1,9 @@
 use std::fmt::Display;
-use std::net::{AddrParseError, IpAddr, Ipv4Addr};
+use std::net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::str::FromStr;
 
 use reqwest::{header::ToStrError, Client};
+use url::{Host, Url};
 
 #[derive(Debug)]
 pub enum Error {
@@ -95,3 +96,142 @@ pub async fn get_ip_v4_ssl() -> Result<Ipv4Addr, Error> {
     },
   }
 }
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+  let [a, b, ..] = ip.octets();
+  !(ip.is_loopback()
+    || ip.is_private()
+    || ip.is_link_local()
+    || ip.is_unspecified()
+    || ip.is_broadcast()
+    || ip.is_documentation()
+    || ip.is_multicast()
+    // 0.0.0.0/8
+    || a == 0
+    // 100.64.0.0/10 carrier grade nat
+    || (a == 100 && (b & 0xc0) == 64))
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+  if let Some(v4) = ip.to_ipv4_mapped() {
+    return is_public_ipv4(v4);
+  }
+
+  let first = ip.segments()[0];
+  !(ip.is_loopback()
+    || ip.is_unspecified()
+    || ip.is_multicast()
+    // fc00::/7 unique local
+    || (first & 0xfe00) == 0xfc00
+    // fe80::/10 link local
+    || (first & 0xffc0) == 0xfe80)
+}
+
+/// if the address is reachable from the internet
+/// the server only sends requests to user provided urls that resolve to public addresses
+pub fn is_public_ip(ip: IpAddr) -> bool {
+  match ip {
+    IpAddr::V4(ip) => is_public_ipv4(ip),
+    IpAddr::V6(ip) => is_public_ipv6(ip),
+  }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResolvePublicError {
+  #[error("url has no host")]
+  NoHost,
+  #[error("error resolving host {0}: {1}")]
+  Resolve(String, std::io::Error),
+  #[error("host {0} has no addresses")]
+  NoAddress(String),
+  #[error("host {0} resolves to a non public address")]
+  NotPublic(String),
+}
+
+/// resolves the host of a user provided url, all its addresses must be public
+///
+/// the request must then be sent to the returned address (eg: with reqwest's `ClientBuilder::resolve`)
+/// and not to a new resolution of the host, that could return a different address
+pub async fn resolve_public(url: &Url) -> Result<SocketAddr, ResolvePublicError> {
+  let host = match url.host_str() {
+    Some(host) => host.to_string(),
+    None => return Err(ResolvePublicError::NoHost),
+  };
+
+  let port = url.port_or_known_default().unwrap_or(80);
+
+  let addrs: Vec<SocketAddr> = match url.host() {
+    Some(Host::Ipv4(ip)) => vec![SocketAddr::new(ip.into(), port)],
+    Some(Host::Ipv6(ip)) => vec![SocketAddr::new(ip.into(), port)],
+    _ => tokio::net::lookup_host((host.as_str(), port))
+      .await
+      .map_err(|e| ResolvePublicError::Resolve(host.clone(), e))?
+      .collect(),
+  };
+
+  let addr = match addrs.first() {
+    Some(addr) => *addr,
+    None => return Err(ResolvePublicError::NoAddress(host)),
+  };
+
+  if !addrs.iter().all(|addr| is_public_ip(addr.ip())) {
+    return Err(ResolvePublicError::NotPublic(host));
+  }
+
+  Ok(addr)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn public_ips() {
+    let public = ["8.8.8.8", "1.1.1.1", "2001:4860:4860::8888"];
+    for ip in public {
+      assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+    }
+
+    let private = [
+      "127.0.0.1",
+      "10.0.0.1",
+      "172.16.5.4",
+      "192.168.1.1",
+      "169.254.169.254",
+      "0.0.0.0",
+      "100.64.0.1",
+      "255.255.255.255",
+      "::1",
+      "::",
+      "fc00::1",
+      "fd12:3456::1",
+      "fe80::1",
+      "::ffff:127.0.0.1",
+      "::ffff:10.0.0.1",
+    ];
+    for ip in private {
+      assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+    }
+  }
+
+  #[test_util::async_test]
+  async fn resolve_public_ips() {
+    let url = Url::parse("https://8.8.8.8:8443/a").unwrap();
+    let addr = resolve_public(&url).await.unwrap();
+    assert_eq!(addr, "8.8.8.8:8443".parse().unwrap());
+
+    let private = [
+      "http://127.0.0.1/",
+      "http://[::1]:8080/",
+      "http://10.0.0.1/",
+    ];
+    for url in private {
+      let url = Url::parse(url).unwrap();
+      let result = resolve_public(&url).await;
+      assert!(
+        matches!(result, Err(ResolvePublicError::NotPublic(_))),
+        "{url}"
+      );
+    }
+  }
+}
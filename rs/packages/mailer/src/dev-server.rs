@@ -32,6 +32,7 @@ pub async fn main() -> Result<(), anyhow::Error> {
       <li><a href="/user-recovery">User recovery</a></li>
       <li><a href="/email-verification">Email verification</a></li>
       <li><a href="/no-reply-autoreply">No reply autoreply</a></li>
+      <li><a href="/station-dead-air">Station dead air</a></li>
     <ul>
 </body>
 </html>
@@ -61,6 +62,10 @@ pub async fn main() -> Result<(), anyhow::Error> {
     .at("/no-reply-autoreply")
     .get(TemplateHandler(NoReplyAutoreply::sample()));
 
+  app
+    .at("/station-dead-air")
+    .get(TemplateHandler(StationDeadAir::sample()));
+
   let app = app.build().context("prex build")?;
 
   let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
@@ -101,3 +101,37 @@ impl Sample for NoReplyAutoreply {
     }
   }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, Template)]
+#[template(path = "station-dead-air.html")]
+pub struct StationDeadAir {
+  pub first_name: String,
+  pub station_name: String,
+  /// "live source" or "external relay"
+  pub source: String,
+  /// "silent" or "under the minimum bitrate"
+  pub reason: String,
+  pub duration_secs: u32,
+  pub failover: bool,
+  pub station_url: String,
+}
+
+impl Redactable for StationDeadAir {
+  fn into_redacted(self) -> Self {
+    self
+  }
+}
+
+impl Sample for StationDeadAir {
+  fn sample() -> Self {
+    Self {
+      first_name: String::from("Name"),
+      station_name: String::from("Station"),
+      source: String::from("live source"),
+      reason: String::from("silent"),
+      duration_secs: 30,
+      failover: true,
+      station_url: String::from("https://studio.openstream.fm/accounts/:account/stations/:station"),
+    }
+  }
+}
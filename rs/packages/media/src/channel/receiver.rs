@@ -1,7 +1,9 @@
 use burst::Burst;
 use bytes::Bytes;
+use db::media_session::MediaSessionNowPlaying;
 use log::*;
 use tokio::sync::broadcast::{self, error::RecvError as TokioRecvError};
+use tokio::sync::watch;
 
 use crate::Kind;
 
@@ -32,6 +34,7 @@ pub struct Receiver {
   pub(crate) receiver: broadcast::Receiver<Bytes>,
   /// this is an owned copy of the burst at subscription time (Bytes instances are copied by owned reference)
   pub(crate) burst: Burst,
+  pub(crate) now_playing: watch::Receiver<Option<MediaSessionNowPlaying>>,
 }
 
 impl Receiver {
@@ -42,6 +45,20 @@ impl Receiver {
   pub fn kind(&self) -> Kind {
     self.kind
   }
+
+  /// the current now playing info of the channel
+  pub fn now_playing(&self) -> Option<MediaSessionNowPlaying> {
+    self.now_playing.borrow().clone()
+  }
+
+  /// returns Some(now_playing) if the now playing info of the channel
+  /// has changed since the last call to this method (or since subscription time)
+  pub fn now_playing_changed(&mut self) -> Option<Option<MediaSessionNowPlaying>> {
+    match self.now_playing.has_changed() {
+      Ok(true) => Some(self.now_playing.borrow_and_update().clone()),
+      Ok(false) | Err(_) => None,
+    }
+  }
   /**
    * Receive the next Bytes value
    * first the internal burst of the channel will be drained
@@ -1,10 +1,13 @@
 use burst::Burst;
 use bytes::Bytes;
+use db::media_session::MediaSessionNowPlaying;
 use parking_lot::RwLock;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::broadcast::{self, error::SendError as TokioSendError};
+use tokio::sync::watch;
 
+use crate::dead_air::AudioTap;
 use crate::Info;
 
 use super::Receiver;
@@ -33,6 +36,9 @@ pub struct Sender {
   pub(crate) terminated: Arc<AtomicBool>,
   pub(crate) sender: broadcast::Sender<Bytes>,
   pub(crate) burst: Arc<RwLock<Burst>>,
+  pub(crate) now_playing: Arc<watch::Sender<Option<MediaSessionNowPlaying>>>,
+  /// the dead air monitor of the source, see crate::dead_air
+  pub(crate) tap: Option<AudioTap>,
 }
 
 impl Sender {
@@ -40,12 +46,23 @@ impl Sender {
     let terminated = Arc::new(AtomicBool::new(false));
     let (sender, _) = broadcast::channel(constants::STREAM_CHANNEL_CAPACITY);
     let burst = Arc::new(RwLock::new(Burst::new()));
+    let (now_playing, _) = watch::channel(None);
     Self {
       station_id,
       info,
       terminated,
       sender,
       burst,
+      now_playing: Arc::new(now_playing),
+      tap: None,
+    }
+  }
+
+  /// returns a clone of this sender that feeds the sent audio to `tap`
+  pub fn with_tap(&self, tap: AudioTap) -> Sender {
+    Self {
+      tap: Some(tap),
+      ..self.clone()
     }
   }
 
@@ -54,6 +71,18 @@ impl Sender {
       return Err(SendError::Terminated(bytes));
     }
 
+    if let Some(tap) = &self.tap {
+      tap.push(&bytes);
+      // the source is in dead air failover and the station playlist is on air
+      // the audio of the source is only measured until it is back
+      if tap.is_muted() {
+        return match self.sender.receiver_count() {
+          0 => Err(SendError::NoSubscribers(bytes)),
+          _ => Ok(0),
+        };
+      }
+    }
+
     {
       let mut burst = self.burst.write();
       if burst.is_full() {
@@ -69,6 +98,50 @@ impl Sender {
     Ok(n)
   }
 
+  /// set the now playing info of this channel
+  /// receivers will only be notified if the value is different from the previous one
+  pub fn set_now_playing(&self, now_playing: Option<MediaSessionNowPlaying>) {
+    self.now_playing.send_if_modified(|current| {
+      if *current == now_playing {
+        false
+      } else {
+        *current = now_playing;
+        true
+      }
+    });
+  }
+
+  /// terminates the channel, following sends will fail with SendError::Terminated
+  /// returns true if the channel was not terminated before
+  pub fn terminate(&self) -> bool {
+    !self.terminated.swap(true, Ordering::SeqCst)
+  }
+
+  pub fn is_terminated(&self) -> bool {
+    self.terminated.load(Ordering::SeqCst)
+  }
+
+  /// creates a sender for a new source that broadcasts to the same receivers of this channel
+  /// the returned sender has its own terminated flag, so the previous source can be terminated
+  /// without closing the channel, the now playing info is reset
+  pub fn handover(&self, info: Info) -> Sender {
+    self.set_now_playing(None);
+    Self {
+      station_id: self.station_id.clone(),
+      info,
+      terminated: Arc::new(AtomicBool::new(false)),
+      sender: self.sender.clone(),
+      burst: self.burst.clone(),
+      now_playing: self.now_playing.clone(),
+      tap: None,
+    }
+  }
+
+  /// returns true if both senders are clones of the same channel
+  pub fn is_same_channel(&self, other: &Sender) -> bool {
+    Arc::ptr_eq(&self.terminated, &other.terminated)
+  }
+
   pub fn receiver_count(&self) -> usize {
     self.sender.receiver_count()
   }
@@ -80,6 +153,31 @@ impl Sender {
       content_type: self.info.content_type.clone(),
       receiver: self.sender.subscribe(),
       burst: self.burst.read().clone(),
+      now_playing: self.now_playing.subscribe(),
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::Kind;
+
+  #[tokio::test]
+  async fn handover_keeps_receivers_connected() {
+    let info = Info::new(Kind::Live, "live".into(), "audio/mpeg".into());
+    let live = Sender::new("station".into(), info);
+    let mut rx = live.subscribe();
+
+    let info = Info::new(Kind::Playlist, "playlist".into(), "audio/mpeg".into());
+    let playlist = live.handover(info);
+
+    live.terminate();
+    drop(live);
+
+    assert!(!playlist.is_terminated());
+    playlist.send(Bytes::from_static(b"data")).unwrap();
+
+    assert_eq!(rx.recv().await.unwrap(), Bytes::from_static(b"data"));
+  }
+}
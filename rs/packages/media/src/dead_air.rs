@@ -0,0 +1,815 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use constants::{
+  DEAD_AIR_CHECK_INTERVAL_SECS, DEAD_AIR_EMAIL_COOLDOWN_SECS, DEAD_AIR_RECOVERY_SECS,
+  DEAD_AIR_SAMPLE_BYTES,
+};
+use db::event::{DeadAirEnded, DeadAirStarted, Event};
+use db::media_session::{DeadAirReason, MediaSession, MediaSessionDeadAir};
+use db::sent_email::{SentEmail, SentEmailAddress, SentEmailKind};
+use db::station::{DeadAirSettings, Station};
+use db::user::User;
+use db::user_account_relation::{UserAccountRelation, UserAccountRelationKind};
+use db::Model;
+use drop_tracer::DropTracer;
+use futures_util::TryStreamExt;
+use log::*;
+use mailer::send::{Address, Email, Mailer};
+use mongodb::bson::doc;
+use parking_lot::Mutex;
+use serde_util::DateTime;
+use shutdown::Shutdown;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tokio::task::JoinHandle;
+
+use crate::channel::Sender;
+use crate::handle::playlist::PlaylistSelection;
+use crate::handle::run_playlist_source;
+use crate::{Info, Kind};
+
+/// levels under this are reported as this, digital silence has no finite level
+const DBFS_FLOOR: f64 = -120.0;
+
+/// the audio sent by a source since the last check of its dead air monitor
+///
+/// cloned into the sender of the source, see Sender::with_tap
+#[derive(Debug, Clone, Default)]
+pub struct AudioTap {
+  window: Arc<Mutex<Window>>,
+  muted: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+  bytes: u64,
+  /// the last DEAD_AIR_SAMPLE_BYTES of the window
+  sample: VecDeque<Bytes>,
+  sample_len: usize,
+}
+
+impl AudioTap {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub(crate) fn push(&self, bytes: &Bytes) {
+    let mut window = self.window.lock();
+    window.bytes += bytes.len() as u64;
+    window.sample_len += bytes.len();
+    window.sample.push_back(bytes.clone());
+    while let Some(front) = window.sample.front() {
+      if window.sample_len - front.len() < DEAD_AIR_SAMPLE_BYTES {
+        break;
+      }
+      window.sample_len -= front.len();
+      window.sample.pop_front();
+    }
+  }
+
+  /// returns the byte count and the sample of the current window and starts a new one
+  fn take(&self) -> (u64, Bytes) {
+    let window = std::mem::take(&mut *self.window.lock());
+    let mut sample = BytesMut::with_capacity(window.sample_len);
+    for bytes in window.sample {
+      sample.extend_from_slice(&bytes);
+    }
+    (window.bytes, sample.freeze())
+  }
+
+  /// while muted the sender measures the audio of the source but doesn't broadcast it
+  pub(crate) fn is_muted(&self) -> bool {
+    self.muted.load(Ordering::Acquire)
+  }
+
+  fn set_muted(&self, muted: bool) {
+    self.muted.store(muted, Ordering::Release);
+  }
+}
+
+/// the measure of a window of audio
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Measure {
+  /// None if the sample could not be decoded
+  level_dbfs: Option<f64>,
+  kbitrate: u32,
+}
+
+/// RMS level in dBFS of pcm samples in the [-1, 1] range, None if there are no samples
+fn rms_dbfs(samples: &[f32]) -> Option<f64> {
+  if samples.is_empty() {
+    return None;
+  }
+
+  let sum: f64 = samples.iter().map(|v| *v as f64 * *v as f64).sum();
+  let rms = (sum / samples.len() as f64).sqrt();
+  if rms == 0.0 {
+    return Some(DBFS_FLOOR);
+  }
+
+  Some((20.0 * rms.log10()).max(DBFS_FLOOR))
+}
+
+fn dead_air_reason(settings: &DeadAirSettings, measure: &Measure) -> Option<DeadAirReason> {
+  if let Some(level) = measure.level_dbfs {
+    if level < settings.silence_threshold_db as f64 {
+      return Some(DeadAirReason::Silence);
+    }
+  }
+
+  if settings.min_kbitrate != 0 && measure.kbitrate < settings.min_kbitrate {
+    return Some(DeadAirReason::LowBitrate);
+  }
+
+  None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transition {
+  Started(DeadAirReason),
+  Ended,
+}
+
+/// the dead air state machine of a source
+#[derive(Debug)]
+struct Detector {
+  settings: DeadAirSettings,
+  dead_for: Duration,
+  alive_for: Duration,
+  active: bool,
+}
+
+impl Detector {
+  fn new(settings: DeadAirSettings) -> Self {
+    Self {
+      settings,
+      dead_for: Duration::ZERO,
+      alive_for: Duration::ZERO,
+      active: false,
+    }
+  }
+
+  /// feeds the measure of a window of `elapsed` duration, None if the source sent no audio in the window
+  ///
+  /// dead air starts after duration_secs of dead windows and ends after DEAD_AIR_RECOVERY_SECS of good ones
+  /// it also ends if the source stops sending audio, as the source has its own timeouts and fallbacks
+  fn observe(&mut self, measure: Option<&Measure>, elapsed: Duration) -> Option<Transition> {
+    let measure = match measure {
+      Some(measure) => measure,
+      None => {
+        self.dead_for = Duration::ZERO;
+        self.alive_for = Duration::ZERO;
+        return self.end();
+      }
+    };
+
+    match dead_air_reason(&self.settings, measure) {
+      Some(reason) => {
+        self.dead_for += elapsed;
+        self.alive_for = Duration::ZERO;
+        if !self.active && self.dead_for >= Duration::from_secs(self.settings.duration_secs as u64)
+        {
+          self.active = true;
+          return Some(Transition::Started(reason));
+        }
+        None
+      }
+
+      None => {
+        self.alive_for += elapsed;
+        if !self.active {
+          self.dead_for = Duration::ZERO;
+          return None;
+        }
+
+        if self.alive_for >= Duration::from_secs(DEAD_AIR_RECOVERY_SECS) {
+          self.dead_for = Duration::ZERO;
+          return self.end();
+        }
+
+        None
+      }
+    }
+  }
+
+  fn end(&mut self) -> Option<Transition> {
+    match std::mem::replace(&mut self.active, false) {
+      true => Some(Transition::Ended),
+      false => None,
+    }
+  }
+}
+
+/// the kind of source of a monitored media session, used in the notification emails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadAirSource {
+  Live,
+  ExternalRelay,
+}
+
+impl DeadAirSource {
+  fn display(self) -> &'static str {
+    match self {
+      Self::Live => "live source",
+      Self::ExternalRelay => "external relay",
+    }
+  }
+}
+
+/// detects dead air in the audio of a live or external relay source, see Station::dead_air
+///
+/// the audio is measured every DEAD_AIR_CHECK_INTERVAL_SECS, when the source is silent or under the
+/// minimum bitrate of the station for longer than the configured duration the media session is marked in dead air,
+/// a dead_air.started event is dispatched, the account owners are notified and the station playlist
+/// is optionally put on air until the source is back
+#[derive(Debug)]
+pub struct DeadAirMonitor {
+  /// the sender of the source, it must be the one with the tap
+  pub sender: Sender,
+  pub tap: AudioTap,
+  pub station: Station,
+  pub media_session_id: String,
+  pub source: DeadAirSource,
+  pub deployment_id: String,
+  pub task_id: String,
+  pub mailer: Mailer,
+  pub drop_tracer: DropTracer,
+  pub shutdown: Shutdown,
+}
+
+/// the station playlist on air while the source is in dead air
+#[derive(Debug)]
+struct Failover {
+  sender: Sender,
+  handle: JoinHandle<Result<(), mongodb::error::Error>>,
+}
+
+/// ends the dead air of the session if the monitor is dropped while it is active
+#[derive(Debug)]
+struct ActiveDeadAir {
+  station_id: String,
+  media_session_id: String,
+  tap: AudioTap,
+  since: Instant,
+  failover: Option<Failover>,
+}
+
+impl ActiveDeadAir {
+  fn stop_failover(&mut self) {
+    if let Some(failover) = self.failover.take() {
+      failover.sender.terminate();
+      failover.handle.abort();
+    }
+    self.tap.set_muted(false);
+  }
+}
+
+impl Drop for ActiveDeadAir {
+  fn drop(&mut self) {
+    self.stop_failover();
+    Event::dispatch_detached(DeadAirEnded {
+      station_id: self.station_id.clone(),
+      media_session_id: self.media_session_id.clone(),
+      duration_ms: self.since.elapsed().as_millis() as u64,
+    });
+  }
+}
+
+impl DeadAirMonitor {
+  pub async fn run(mut self) -> Infallible {
+    let mut detector = Detector::new(self.station.dead_air);
+    let mut active: Option<ActiveDeadAir> = None;
+
+    let mut interval = tokio::time::interval(Duration::from_secs(DEAD_AIR_CHECK_INTERVAL_SECS));
+    interval.tick().await;
+    // the audio sent before the monitor started is discarded so the first window starts now
+    self.tap.take();
+    let mut last_tick = Instant::now();
+
+    loop {
+      interval.tick().await;
+      let elapsed = last_tick.elapsed();
+      last_tick = Instant::now();
+
+      let (bytes, sample) = self.tap.take();
+
+      // the settings are read on every check so a change in the station applies to the running source
+      self.reload_station().await;
+      let settings = self.station.dead_air;
+
+      if !settings.enabled {
+        detector = Detector::new(settings);
+        if let Some(active) = active.take() {
+          self.end(active).await;
+        }
+        continue;
+      }
+
+      detector.settings = settings;
+
+      // failover disabled while the playlist is on air over the source
+      if let Some(active) = &mut active {
+        if !settings.failover && active.failover.is_some() {
+          active.stop_failover();
+          self.sender.set_now_playing(None);
+        }
+      }
+
+      let measure = match bytes {
+        0 => None,
+        _ => Some(Measure {
+          level_dbfs: decode_level(sample, self.sender.info.content_type.clone()).await,
+          kbitrate: (bytes * 8 / 1000 / elapsed.as_secs().max(1)) as u32,
+        }),
+      };
+
+      match (detector.observe(measure.as_ref(), elapsed), measure) {
+        (Some(Transition::Started(reason)), Some(measure)) => {
+          let since = detector.dead_for;
+          active = Some(self.start(reason, measure, since).await);
+        }
+
+        (Some(Transition::Ended), _) => {
+          if let Some(active) = active.take() {
+            self.end(active).await;
+          }
+        }
+
+        _ => {}
+      }
+    }
+  }
+
+  /// reads the station again, the previous settings are kept if it could not be read
+  async fn reload_station(&mut self) {
+    match Station::get_by_id(&self.station.id).await {
+      Ok(Some(station)) => self.station = station,
+      Ok(None) => {}
+      Err(e) => {
+        warn!(
+          target: "media-dead-air",
+          "error reloading station {} for dead air monitor: {e} => {e:?}",
+          self.station.id
+        );
+      }
+    }
+  }
+
+  /// marks the session in dead air, `dead_for` is the duration of the dead windows until now
+  async fn start(
+    &self,
+    reason: DeadAirReason,
+    measure: Measure,
+    dead_for: Duration,
+  ) -> ActiveDeadAir {
+    let failover = match self.station.dead_air.failover {
+      true => self.start_failover().await,
+      false => None,
+    };
+
+    let dead_air = MediaSessionDeadAir {
+      reason,
+      since: DateTime::from(time::OffsetDateTime::now_utc() - dead_for),
+      level_dbfs: measure.level_dbfs,
+      kbitrate: measure.kbitrate,
+      failover: failover.is_some(),
+    };
+
+    warn!(
+      target: "media-dead-air",
+      "dead air in media session {} of station {}: {dead_air:?}",
+      self.media_session_id,
+      self.station.id,
+    );
+
+    if let Err(e) = MediaSession::set_dead_air(&self.media_session_id, Some(dead_air)).await {
+      warn!(
+        target: "media-dead-air",
+        "error setting dead air of media session {}: {e} => {e:?}",
+        self.media_session_id
+      );
+    }
+
+    Event::dispatch_detached(DeadAirStarted {
+      station_id: self.station.id.clone(),
+      media_session_id: self.media_session_id.clone(),
+      reason,
+      level_dbfs: dead_air.level_dbfs,
+      kbitrate: dead_air.kbitrate,
+      failover: dead_air.failover,
+    });
+
+    if self.station.dead_air.notify {
+      let station = self.station.clone();
+      let media_session_id = self.media_session_id.clone();
+      let source = self.source;
+      let mailer = self.mailer.clone();
+      tokio::spawn(async move {
+        if let Err(e) = notify(&mailer, &station, &media_session_id, source, &dead_air).await {
+          warn!(
+            target: "media-dead-air",
+            "error notifying dead air of station {}: {e} => {e:?}",
+            station.id
+          );
+        }
+      });
+    }
+
+    ActiveDeadAir {
+      station_id: self.station.id.clone(),
+      media_session_id: self.media_session_id.clone(),
+      tap: self.tap.clone(),
+      since: Instant::now() - dead_for,
+      failover,
+    }
+  }
+
+  /// puts the source back on air and clears the dead air of the session
+  async fn end(&self, mut active: ActiveDeadAir) {
+    info!(
+      target: "media-dead-air",
+      "dead air ended in media session {} of station {}",
+      self.media_session_id,
+      self.station.id,
+    );
+
+    if active.failover.is_some() {
+      active.stop_failover();
+      self.sender.set_now_playing(None);
+    }
+
+    // the dead_air.ended event is dispatched on drop
+    drop(active);
+
+    if let Err(e) = MediaSession::set_dead_air(&self.media_session_id, None).await {
+      warn!(
+        target: "media-dead-air",
+        "error clearing dead air of media session {}: {e} => {e:?}",
+        self.media_session_id
+      );
+    }
+  }
+
+  /// puts the station playlist on air over the source
+  /// returns None if the content type of the source is not the one of the playlist or the playlist is empty
+  async fn start_failover(&self) -> Option<Failover> {
+    let content_type = "audio/mpeg".to_string();
+    if self.sender.info.content_type != content_type {
+      return None;
+    }
+
+    let selection = PlaylistSelection::for_station(&self.station);
+    match selection.is_playable(&self.station.id).await {
+      Ok(true) => {}
+      Ok(false) => return None,
+      Err(e) => {
+        warn!(
+          target: "media-dead-air",
+          "error checking the playlist of station {} for dead air failover: {e} => {e:?}",
+          self.station.id
+        );
+        return None;
+      }
+    }
+
+    let info = Info::new(Kind::Playlist, self.task_id.clone(), content_type);
+    let sender = self.sender.handover(info);
+
+    let handle = run_playlist_source(
+      sender.clone(),
+      self.deployment_id.clone(),
+      self.task_id.clone(),
+      self.station.id.clone(),
+      true,
+      selection,
+      None,
+      self.drop_tracer.clone(),
+      self.shutdown.clone(),
+    );
+
+    self.tap.set_muted(true);
+
+    Some(Failover { sender, handle })
+  }
+}
+
+/// decodes the sample in a blocking thread and measures its level, None if it could not be decoded
+async fn decode_level(sample: Bytes, content_type: String) -> Option<f64> {
+  match tokio::task::spawn_blocking(move || decode_pcm(sample, &content_type)).await {
+    Ok(Ok(samples)) => rms_dbfs(&samples),
+    Ok(Err(e)) => {
+      debug!(target: "media-dead-air", "error decoding dead air sample: {e} => {e:?}");
+      None
+    }
+    Err(e) => {
+      warn!(target: "media-dead-air", "error joining dead air decoder: {e} => {e:?}");
+      None
+    }
+  }
+}
+
+/// decodes a sample of mp3 or aac audio to interleaved pcm samples
+///
+/// the decoding is done in process as it runs for every source every DEAD_AIR_CHECK_INTERVAL_SECS,
+/// the sample can start in the middle of a frame, the decoder syncs to the next one
+fn decode_pcm(
+  sample: Bytes,
+  content_type: &str,
+) -> Result<Vec<f32>, symphonia::core::errors::Error> {
+  use symphonia::core::errors::Error;
+
+  let mut hint = Hint::new();
+  hint.mime_type(content_type);
+
+  let source = MediaSourceStream::new(
+    Box::new(std::io::Cursor::new(sample)),
+    MediaSourceStreamOptions::default(),
+  );
+
+  let mut format = symphonia::default::get_probe()
+    .format(
+      &hint,
+      source,
+      &FormatOptions::default(),
+      &MetadataOptions::default(),
+    )?
+    .format;
+
+  let (track_id, codec_params) = match format.default_track() {
+    Some(track) => (track.id, track.codec_params.clone()),
+    None => return Err(Error::Unsupported("no audio track")),
+  };
+
+  let mut decoder =
+    symphonia::default::get_codecs().make(&codec_params, &DecoderOptions::default())?;
+
+  let mut samples = vec![];
+  let mut buf: Option<SampleBuffer<f32>> = None;
+
+  loop {
+    let packet = match format.next_packet() {
+      Ok(packet) => packet,
+      // the end of the sample, it can also be cut in the middle of a frame
+      Err(Error::IoError(_)) => break,
+      Err(e) => return Err(e),
+    };
+
+    if packet.track_id() != track_id {
+      continue;
+    }
+
+    let decoded = match decoder.decode(&packet) {
+      Ok(decoded) => decoded,
+      // a corrupt frame is skipped
+      Err(Error::DecodeError(_)) => continue,
+      Err(e) => return Err(e),
+    };
+
+    let buf =
+      buf.get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+    buf.copy_interleaved_ref(decoded);
+    samples.extend_from_slice(buf.samples());
+  }
+
+  Ok(samples)
+}
+
+#[derive(Debug, thiserror::Error)]
+enum NotifyError {
+  #[error("db: {0}")]
+  Db(#[from] mongodb::error::Error),
+  #[error("render: {0}")]
+  Render(#[from] mailer::error::RenderError),
+  #[error("send: {0}")]
+  Send(#[from] mailer::send::SendError),
+}
+
+/// emails the owners of the station account
+/// at most one email is sent for each station every DEAD_AIR_EMAIL_COOLDOWN_SECS
+async fn notify(
+  mailer: &Mailer,
+  station: &Station,
+  media_session_id: &str,
+  source: DeadAirSource,
+  dead_air: &MediaSessionDeadAir,
+) -> Result<(), NotifyError> {
+  let cooldown_since =
+    time::OffsetDateTime::now_utc() - Duration::from_secs(DEAD_AIR_EMAIL_COOLDOWN_SECS);
+
+  let filter = doc! {
+    "kind": "station-dead-air",
+    "data.station_id": &station.id,
+    "created_at": { "$gt": DateTime::from(cooldown_since) },
+  };
+
+  if SentEmail::exists(filter).await? {
+    return Ok(());
+  }
+
+  let filter = doc! {
+    UserAccountRelation::KEY_ACCOUNT_ID: &station.account_id,
+    UserAccountRelation::KEY_KIND: UserAccountRelationKind::KEY_ENUM_VARIANT_OWNER,
+  };
+
+  let user_ids: Vec<String> = UserAccountRelation::cl()
+    .find(filter, None)
+    .await?
+    .map_ok(|relation| relation.user_id)
+    .try_collect()
+    .await?;
+
+  let filter = db::current_filter_doc! {
+    User::KEY_ID: { "$in": user_ids }
+  };
+
+  let users: Vec<User> = User::cl().find(filter, None).await?.try_collect().await?;
+
+  let subject = format!("Dead air detected in {}", station.name);
+  let from_name = String::from("Openstream");
+
+  for user in users {
+    // TODO: not hardcode the URL
+    let template = mailer::templates::StationDeadAir {
+      first_name: user.first_name.clone(),
+      station_name: station.name.clone(),
+      source: source.display().to_string(),
+      reason: match dead_air.reason {
+        DeadAirReason::Silence => String::from("silent"),
+        DeadAirReason::LowBitrate => String::from("under the minimum bitrate"),
+      },
+      duration_secs: station.dead_air.duration_secs,
+      failover: dead_air.failover,
+      station_url: format!(
+        "https://studio.openstream.fm/accounts/{}/stations/{}",
+        station.account_id, station.id
+      ),
+    };
+
+    let render = mailer::render::render(template)?;
+
+    let sent_email = SentEmail {
+      id: SentEmail::uid(),
+      from: SentEmailAddress {
+        name: Some(from_name.clone()),
+        email: mailer.username.clone(),
+      },
+      to: SentEmailAddress {
+        name: Some(user.first_name.clone()),
+        email: user.email.clone(),
+      },
+      reply_to: None,
+      subject: subject.clone(),
+      text: render.storable.text,
+      html: render.storable.html,
+      kind: SentEmailKind::StationDeadAir {
+        station_id: station.id.clone(),
+        media_session_id: media_session_id.to_string(),
+        user_id: user.id.clone(),
+      },
+      created_at: DateTime::now(),
+    };
+
+    SentEmail::insert(sent_email).await?;
+
+    let email = Email {
+      from: Address {
+        name: Some(from_name.clone()),
+        email: mailer.username.clone(),
+      },
+      to: Address {
+        name: Some(user.first_name),
+        email: user.email,
+      },
+      html: render.sendable.html,
+      text: render.sendable.text,
+      subject: subject.clone(),
+    };
+
+    mailer.send(email).await?;
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn settings() -> DeadAirSettings {
+    DeadAirSettings {
+      enabled: true,
+      silence_threshold_db: -50,
+      min_kbitrate: 32,
+      duration_secs: 10,
+      notify: false,
+      failover: false,
+    }
+  }
+
+  fn measure(level_dbfs: f64, kbitrate: u32) -> Measure {
+    Measure {
+      level_dbfs: Some(level_dbfs),
+      kbitrate,
+    }
+  }
+
+  #[test]
+  fn rms_of_pcm() {
+    assert_eq!(rms_dbfs(&[]), None);
+    assert_eq!(rms_dbfs(&[0.0; 64]), Some(DBFS_FLOOR));
+
+    let full: Vec<f32> = [1.0, -1.0].iter().cycle().take(64).copied().collect();
+    assert!(rms_dbfs(&full).unwrap().abs() < 0.01);
+
+    let half: Vec<f32> = [0.5, -0.5].iter().cycle().take(64).copied().collect();
+    assert!((rms_dbfs(&half).unwrap() + 6.02).abs() < 0.01);
+  }
+
+  #[test]
+  fn undecodable_samples() {
+    assert!(decode_pcm(Bytes::from_static(&[0; 1024]), "audio/mpeg").is_err());
+  }
+
+  #[test]
+  fn reasons() {
+    let settings = settings();
+    assert_eq!(dead_air_reason(&settings, &measure(-20.0, 128)), None);
+    assert_eq!(
+      dead_air_reason(&settings, &measure(-60.0, 128)),
+      Some(DeadAirReason::Silence)
+    );
+    assert_eq!(
+      dead_air_reason(&settings, &measure(-20.0, 16)),
+      Some(DeadAirReason::LowBitrate)
+    );
+
+    // undecodable samples are only checked for bitrate
+    let undecodable = Measure {
+      level_dbfs: None,
+      kbitrate: 128,
+    };
+    assert_eq!(dead_air_reason(&settings, &undecodable), None);
+  }
+
+  #[test]
+  fn detector_starts_and_recovers() {
+    let step = Duration::from_secs(5);
+    let mut detector = Detector::new(settings());
+
+    assert_eq!(detector.observe(Some(&measure(-60.0, 128)), step), None);
+    // a good window resets the count
+    assert_eq!(detector.observe(Some(&measure(-20.0, 128)), step), None);
+    assert_eq!(detector.observe(Some(&measure(-60.0, 128)), step), None);
+    assert_eq!(
+      detector.observe(Some(&measure(-60.0, 128)), step),
+      Some(Transition::Started(DeadAirReason::Silence))
+    );
+    assert_eq!(detector.observe(Some(&measure(-60.0, 128)), step), None);
+
+    // DEAD_AIR_RECOVERY_SECS of good audio are needed to recover
+    let mut ended = None;
+    for _ in 0..(DEAD_AIR_RECOVERY_SECS / 5) {
+      ended = detector.observe(Some(&measure(-20.0, 128)), step);
+    }
+    assert_eq!(ended, Some(Transition::Ended));
+  }
+
+  #[test]
+  fn detector_ends_when_the_source_stops() {
+    let step = Duration::from_secs(10);
+    let mut detector = Detector::new(settings());
+
+    assert_eq!(
+      detector.observe(Some(&measure(-60.0, 128)), step),
+      Some(Transition::Started(DeadAirReason::Silence))
+    );
+    assert_eq!(detector.observe(None, step), Some(Transition::Ended));
+    assert_eq!(detector.observe(None, step), None);
+  }
+
+  #[test]
+  fn tap_keeps_the_last_sample_bytes() {
+    let tap = AudioTap::new();
+    let chunk = Bytes::from(vec![1u8; DEAD_AIR_SAMPLE_BYTES / 2]);
+    for _ in 0..5 {
+      tap.push(&chunk);
+    }
+
+    let (bytes, sample) = tap.take();
+    assert_eq!(bytes, (DEAD_AIR_SAMPLE_BYTES / 2 * 5) as u64);
+    assert_eq!(sample.len(), DEAD_AIR_SAMPLE_BYTES);
+
+    let (bytes, sample) = tap.take();
+    assert_eq!(bytes, 0);
+    assert!(sample.is_empty());
+  }
+}
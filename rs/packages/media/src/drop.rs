@@ -88,7 +88,7 @@ impl Drop for MapEntryRelease {
       tokio::spawn(async move {
         let _ = inner
           .map
-          .terminate_task(&inner.station_id, &inner.task_id)
+          .release_task(&inner.station_id, &inner.task_id)
           .await;
       });
     }
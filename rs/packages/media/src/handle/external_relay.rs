@@ -1,55 +1,83 @@
-use std::process::ExitStatus;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 
-use db::media_session::MediaSession;
+use db::event::{Event, RelayFailed};
+use db::media_session::{
+  MediaSession, MediaSessionRelaySource, MediaSessionRelaySourceHealth, MediaSessionRelaySwitch,
+  MediaSessionRelaySwitchReason,
+};
 use db::{media_session::MediaSessionState, Model};
 use drop_tracer::{DropTracer, Token};
 use ffmpeg::{Ffmpeg, FfmpegConfig, FfmpegSpawn, Format};
 use futures_util::StreamExt;
 use log::*;
+use mailer::send::Mailer;
 use mongodb::bson::doc;
 use serde_util::DateTime;
 use shutdown::Shutdown;
 use std::sync::Arc;
 use tokio::io::AsyncReadExt;
 use tokio::task::JoinHandle;
+use tokio::time::sleep;
 
 use crate::channel::{SendError, Sender};
+use crate::dead_air::{AudioTap, DeadAirMonitor, DeadAirSource};
+use crate::handle::playlist::PlaylistSelection;
+use crate::handle::run_playlist_source;
 use crate::handle::util::PrettyDuration;
 use crate::ProbeCodec;
 
 use constants::{
   EXTERNAL_RELAY_NO_DATA_SHUTDOWN_SECS, EXTERNAL_RELAY_NO_DATA_START_SHUTDOWN_SECS,
-  EXTERNAL_RELAY_NO_LISTENERS_SHUTDOWN_DELAY_SECS, /*STREAM_BURST_LENGTH,*/
-  STREAM_BURST_LENGTH, STREAM_CHUNK_SIZE, STREAM_KBITRATE,
+  EXTERNAL_RELAY_NO_LISTENERS_SHUTDOWN_DELAY_SECS, EXTERNAL_RELAY_PRIMARY_CHECK_INTERVAL_SECS,
+  EXTERNAL_RELAY_PRIMARY_CHECK_TIMEOUT_SECS, EXTERNAL_RELAY_SOURCE_RETRY_SECS, STREAM_BURST_LENGTH,
+  STREAM_CHUNK_SIZE, STREAM_KBITRATE,
 };
 
+/// relays the station from its external relay urls
+///
+/// urls are tried in order (the primary url first), when the url on air ends or stops producing data
+/// the relay switches to the next available url, and to the station playlist if no url is available
+/// while a backup url or the playlist is on air, the higher priority urls are checked periodically
+/// and the relay switches back to them as soon as they are healthy
+///
+/// the output is always mp3 so the sources can be switched without listeners reconnecting
+///
+/// the audio of the relay urls is monitored for dead air if the station has it enabled, see crate::dead_air
 #[allow(clippy::too_many_arguments)]
 pub fn run_external_relay_source(
   sender: Sender,
   deployment_id: String,
   task_id: String,
   station_id: String,
-  url: String,
+  urls: Vec<String>,
   codec_info: Option<(ProbeCodec, usize)>,
+  mailer: Mailer,
   drop_tracer: DropTracer,
   shutdown: Shutdown,
 ) -> JoinHandle<Result<(), ExternalRelayError>> {
   tokio::spawn(async move {
+    let mut chain = Chain::new(urls);
+
     let media_session_id = MediaSession::uid();
     let document = {
       use db::media_session::*;
       let now = DateTime::now();
       let document = MediaSession {
         id: media_session_id.clone(),
-        deployment_id,
+        deployment_id: deployment_id.clone(),
         station_id: station_id.clone(),
-        kind: MediaSessionKind::ExternalRelay { url: url.clone() },
+        kind: MediaSessionKind::ExternalRelay {
+          url: chain.urls[0].clone(),
+          on_air: None,
+          source_health: chain.health(),
+          switches: vec![],
+        },
         state: MediaSessionState::Open,
         closed_at: None,
         duration_ms: None,
         now_playing: None,
+        dead_air: None,
         transfer_bytes: 0,
         health_checked_at: Some(now),
         created_at: now,
@@ -81,155 +109,149 @@ pub fn run_external_relay_source(
       drop_tracer.token(),
     )));
 
-    let fut = async move {
-      let headers = ffmpeg::headers_for_url(&url);
-
-      let (ffmpeg_config, chunk_size) = match codec_info {
-        Some((codec, bitrate)) => {
-          let format = match codec {
-            ProbeCodec::Mp3 => Format::MP3,
-            ProbeCodec::Aac => Format::AAC,
-          };
-
-          let config = FfmpegConfig {
-            input: Some(url),
-            kbitrate: bitrate / 1000,
-            readrate: true,
-            readrate_initial_burst: STREAM_BURST_LENGTH as f64,
-            copycodec: true,
-            headers,
-            format,
-            ..FfmpegConfig::default()
-          };
+    let signal = shutdown.signal();
 
-          let chunk_size = STREAM_CHUNK_SIZE;
+    // only the audio of the relay urls is monitored, the relay playlist fallback is sent without the tap
+    let tap = AudioTap::new();
+    let url_sender = sender.with_tap(tap.clone());
+
+    // a failure to get the station must not prevent the relay session
+    let station = match db::station::Station::get_by_id(&station_id).await {
+      Ok(station) => station,
+      Err(e) => {
+        warn!(
+          target: "media",
+          "error getting station {station_id} to start the dead air monitor: {e} => {e:?}"
+        );
+        None
+      }
+    };
 
-          (config, chunk_size)
-        }
+    let dead_air_monitor = station.map(|station| DeadAirMonitor {
+      sender: url_sender.clone(),
+      tap,
+      station,
+      media_session_id: media_session_id.clone(),
+      source: DeadAirSource::ExternalRelay,
+      deployment_id: deployment_id.clone(),
+      task_id: task_id.clone(),
+      mailer,
+      drop_tracer: drop_tracer.clone(),
+      shutdown: shutdown.clone(),
+    });
 
-        None => {
-          let config = FfmpegConfig {
-            input: Some(url),
-            kbitrate: STREAM_KBITRATE,
-            readrate: true,
-            readrate_initial_burst: STREAM_BURST_LENGTH as f64,
-            headers,
-            ..FfmpegConfig::default()
+    let fut = async move {
+      let relay = async {
+        let mut reason = MediaSessionRelaySwitchReason::Start;
+        let mut fallback = PlaylistSelection::Files { file_ids: vec![] };
+
+        loop {
+          let source = match chain.next_source() {
+            Some(index) => MediaSessionRelaySource::Url {
+              index: index as u32,
+              url: chain.urls[index].clone(),
+            },
+            None => {
+              fallback = PlaylistSelection::for_station_id(&station_id).await?;
+              match fallback.is_playable(&station_id).await? {
+                true => MediaSessionRelaySource::Playlist,
+                false => MediaSessionRelaySource::OffAir,
+              }
+            }
           };
 
-          let chunk_size = STREAM_CHUNK_SIZE;
-
-          (config, chunk_size)
-        }
-      };
-
-      let ff_spawn = match Ffmpeg::new(ffmpeg_config).spawn() {
-        Ok(spawn) => spawn,
-        Err(e) => {
-          error!(
+          info!(
             target: "media",
-            "relay session ffmpeg spawn io: {e} => {e:?}"
+            "external-relay for station {station_id} switching to {source:?} ({reason:?})"
           );
-          return Err(ExternalRelayError::Spawn(e));
-        }
-      };
-
-      let FfmpegSpawn {
-        mut stderr,
-        stdin: _stdin,
-        stdout,
-        mut child,
-        config: _,
-      } = ff_spawn;
-
-      let stderr_handle = async move {
-        let mut data = Vec::new();
-        stderr.read_to_end(&mut data).await?;
-        Result::<Vec<u8>, std::io::Error>::Ok(data)
-      };
 
-      let status_handle = {
-        let station_id = station_id.clone();
-        async move {
-          use stream_util::*;
-          use tokio::time::sleep;
+          let switch = MediaSessionRelaySwitch {
+            to: source.clone(),
+            reason,
+            at: DateTime::now(),
+          };
 
-          let mut chunks = stdout.into_bytes_stream(chunk_size);
+          if let Err(e) =
+            MediaSession::record_relay_switch(&media_session_id, switch, chain.health()).await
+          {
+            warn!(
+              target: "media",
+              "error recording external-relay switch for station {station_id}: {e} => {e:?}"
+            );
+          }
 
-          let mut no_listeners_since: Option<Instant> = None;
+          let end = match source {
+            MediaSessionRelaySource::Url { index, .. } => {
+              let index = index as usize;
+              let codec_info = if index == 0 { codec_info } else { None };
+              let higher = chain.urls[..index].to_vec();
+              run_url(
+                &url_sender,
+                &station_id,
+                index,
+                &mut chain,
+                codec_info,
+                &transfer,
+                higher,
+              )
+              .await
+            }
 
-          let mut is_first_chunk = true;
+            MediaSessionRelaySource::Playlist => {
+              let handle = run_playlist_source(
+                sender.clone(),
+                deployment_id.clone(),
+                task_id.clone(),
+                station_id.clone(),
+                true,
+                fallback.clone(),
+                Some(media_session_id.clone()),
+                drop_tracer.clone(),
+                shutdown.clone(),
+              );
 
-          'chunks: loop {
-            let timeout_secs: u64;
-            let start_str: &str;
+              run_playlist_fallback(&sender, handle, chain.urls.clone()).await
+            }
 
-            if is_first_chunk {
-              timeout_secs = EXTERNAL_RELAY_NO_DATA_START_SHUTDOWN_SECS;
-              start_str = " start ";
-            } else {
-              timeout_secs = EXTERNAL_RELAY_NO_DATA_SHUTDOWN_SECS;
-              start_str = " ";
-            };
+            MediaSessionRelaySource::OffAir => {
+              run_off_air(&sender, chain.next_retry_in(), chain.urls.clone()).await
+            }
+          };
 
-            let chunk = tokio::select! {
+          reason = match end {
+            SourceEnd::NoListeners => {
+              info!(
+                target: "media",
+                "shutting down external-relay for station {} (no listeners shutdown delay elapsed)",
+                station_id
+              );
+              break;
+            }
 
-              chunk = chunks.next() => chunk,
+            SourceEnd::Terminated => break,
 
-              _ = sleep(Duration::from_secs(timeout_secs)) => {
-                info!(
-                  target: "media",
-                  "shutting down external-relay for station {} (no data received in specified{start_str}window)",
-                  station_id
-                );
-                break 'chunks;
-              }
-            };
-
-            is_first_chunk = false;
-
-            match chunk {
-              None => break 'chunks,
-              Some(Err(_e)) => break 'chunks,
-              Some(Ok(bytes)) => {
-                transfer.fetch_add(bytes.len(), Ordering::Relaxed);
-
-                match sender.send(bytes) {
-                  Ok(_) => {
-                    no_listeners_since = None;
-                  }
-
-                  // check if shutdown delay is elapsed
-                  Err(SendError::NoSubscribers(_)) => match no_listeners_since {
-                    Some(instant) => {
-                      if instant.elapsed().as_secs()
-                        > EXTERNAL_RELAY_NO_LISTENERS_SHUTDOWN_DELAY_SECS
-                      {
-                        info!(
-                        "shutting down external-relay for station {} (no listeners shutdown delay elapsed)",
-                        station_id
-                      );
-                        break 'chunks;
-                      }
-                    }
-
-                    None => {
-                      no_listeners_since = Some(Instant::now());
-                    }
-                  },
-
-                  Err(SendError::Terminated(_)) => break 'chunks,
-                };
+            SourceEnd::Failed(reason) => {
+              if let MediaSessionRelaySource::Url { index, url } = source {
+                Event::dispatch_detached(RelayFailed {
+                  station_id: station_id.clone(),
+                  url,
+                  reason,
+                });
+                chain.fail(index as usize, reason);
               }
+              reason
             }
-          }
-
-          child.kill().await?;
 
-          let exit = child.wait().await?;
+            SourceEnd::Recovered(index) => {
+              chain.recover(index);
+              MediaSessionRelaySwitchReason::Recovered
+            }
 
-          Ok::<ExitStatus, std::io::Error>(exit)
+            SourceEnd::Retry => MediaSessionRelaySwitchReason::Retry,
+          };
         }
+
+        Ok::<(), ExternalRelayError>(())
       };
 
       let health_handle = crate::health::run_health_check_interval_for_station_and_media_session(
@@ -238,86 +260,402 @@ pub fn run_external_relay_source(
         &task_id,
       );
 
-      let join_fut = async { tokio::join!(status_handle, stderr_handle) };
+      let dead_air_monitor = async move {
+        match dead_air_monitor {
+          None => std::future::pending().await,
+          Some(monitor) => monitor.run().await,
+        }
+      };
 
-      let (status, stderr) = tokio::select! {
-        (status, stderr) = join_fut => (status, stderr),
+      let r = tokio::select! {
+        r = relay => r,
         r = health_handle => match r {
           Ok(never) => match never {},
-          Err(e) => return Err(e.into()),
-        }
+          Err(e) => Err(e.into()),
+        },
+        never = dead_air_monitor => match never {},
       };
 
-      let exit = match status {
-        Ok(exit) => exit,
+      drop(dropper);
 
-        Err(e) => {
-          warn!(
-            target: "media",
-            "external-relay session for station {station_id}: ffmpeg child error: {} => {:?}",
-            e, e
-          );
-          return Err(ExternalRelayError::ExitIo(e));
+      r
+    };
+
+    let r = tokio::select! {
+      r = fut => r,
+      _ = signal => Ok(())
+    };
+
+    r
+  })
+}
+
+/// the external relay urls of a station and their health
+#[derive(Debug)]
+struct Chain {
+  urls: Vec<String>,
+  health: Vec<SourceHealth>,
+}
+
+#[derive(Debug, Default)]
+struct SourceHealth {
+  failures: u32,
+  /// set while the url is considered failed
+  failed_at: Option<Instant>,
+  last_failure_at: Option<DateTime>,
+  last_failure_reason: Option<MediaSessionRelaySwitchReason>,
+}
+
+impl Chain {
+  fn new(urls: Vec<String>) -> Self {
+    let health = urls.iter().map(|_| SourceHealth::default()).collect();
+    Self { urls, health }
+  }
+
+  /// the first url that is healthy or whose retry delay has elapsed
+  fn next_source(&self) -> Option<usize> {
+    let retry = Duration::from_secs(EXTERNAL_RELAY_SOURCE_RETRY_SECS);
+    self.health.iter().position(|h| match h.failed_at {
+      None => true,
+      Some(instant) => instant.elapsed() >= retry,
+    })
+  }
+
+  /// time until the next failed url can be retried
+  fn next_retry_in(&self) -> Duration {
+    let retry = Duration::from_secs(EXTERNAL_RELAY_SOURCE_RETRY_SECS);
+    self
+      .health
+      .iter()
+      .filter_map(|h| h.failed_at)
+      .map(|instant| retry.saturating_sub(instant.elapsed()))
+      .min()
+      .unwrap_or(Duration::ZERO)
+  }
+
+  fn fail(&mut self, index: usize, reason: MediaSessionRelaySwitchReason) {
+    if let Some(health) = self.health.get_mut(index) {
+      health.failures += 1;
+      health.failed_at = Some(Instant::now());
+      health.last_failure_at = Some(DateTime::now());
+      health.last_failure_reason = Some(reason);
+    }
+  }
+
+  /// marks the url as healthy, keeping the info of its last failure
+  fn recover(&mut self, index: usize) {
+    if let Some(health) = self.health.get_mut(index) {
+      health.failures = 0;
+      health.failed_at = None;
+    }
+  }
+
+  fn health(&self) -> Vec<MediaSessionRelaySourceHealth> {
+    self
+      .urls
+      .iter()
+      .zip(self.health.iter())
+      .map(|(url, health)| MediaSessionRelaySourceHealth {
+        url: url.clone(),
+        healthy: health.failed_at.is_none(),
+        failures: health.failures,
+        last_failure_at: health.last_failure_at,
+        last_failure_reason: health.last_failure_reason,
+      })
+      .collect()
+  }
+}
+
+#[derive(Debug)]
+enum SourceEnd {
+  /// the relay has no listeners, it must be shut down
+  NoListeners,
+  /// the channel was terminated (maybe replaced with a newer transmitter)
+  Terminated,
+  /// the source failed and the next one must be used
+  Failed(MediaSessionRelaySwitchReason),
+  /// the url with this index is healthy again and has higher priority than the current source
+  Recovered(usize),
+  /// a failed url can be retried
+  Retry,
+}
+
+fn ffmpeg_config(url: &str, codec_info: Option<(ProbeCodec, usize)>) -> FfmpegConfig {
+  let headers = ffmpeg::headers_for_url(url);
+
+  match codec_info {
+    // mp3 sources are relayed as is
+    Some((ProbeCodec::Mp3, bitrate)) => FfmpegConfig {
+      input: Some(url.to_string()),
+      kbitrate: bitrate / 1000,
+      readrate: true,
+      readrate_initial_burst: STREAM_BURST_LENGTH as f64,
+      copycodec: true,
+      headers,
+      format: Format::MP3,
+      ..FfmpegConfig::default()
+    },
+
+    // other sources are converted to mp3
+    Some((ProbeCodec::Aac, _)) | None => FfmpegConfig {
+      input: Some(url.to_string()),
+      kbitrate: STREAM_KBITRATE,
+      readrate: true,
+      readrate_initial_burst: STREAM_BURST_LENGTH as f64,
+      headers,
+      ..FfmpegConfig::default()
+    },
+  }
+}
+
+async fn run_url(
+  sender: &Sender,
+  station_id: &str,
+  index: usize,
+  chain: &mut Chain,
+  codec_info: Option<(ProbeCodec, usize)>,
+  transfer: &AtomicUsize,
+  higher: Vec<String>,
+) -> SourceEnd {
+  use stream_util::*;
+
+  let url = chain.urls[index].clone();
+
+  let ff_spawn = match Ffmpeg::new(ffmpeg_config(&url, codec_info)).spawn() {
+    Ok(spawn) => spawn,
+    Err(e) => {
+      error!(
+        target: "media",
+        "relay session ffmpeg spawn io: {e} => {e:?}"
+      );
+      return SourceEnd::Failed(MediaSessionRelaySwitchReason::Error);
+    }
+  };
+
+  let FfmpegSpawn {
+    mut stderr,
+    stdin: _stdin,
+    stdout,
+    mut child,
+    config: _,
+  } = ff_spawn;
+
+  let stderr_handle = tokio::spawn(async move {
+    let mut data = Vec::new();
+    stderr.read_to_end(&mut data).await?;
+    Result::<Vec<u8>, std::io::Error>::Ok(data)
+  });
+
+  let recovered = wait_recovered(higher);
+  tokio::pin!(recovered);
+
+  let mut chunks = stdout.into_bytes_stream(STREAM_CHUNK_SIZE);
+
+  let mut no_listeners_since: Option<Instant> = None;
+
+  let mut is_first_chunk = true;
+
+  let end = 'chunks: loop {
+    let timeout_secs: u64;
+    let start_str: &str;
+
+    if is_first_chunk {
+      timeout_secs = EXTERNAL_RELAY_NO_DATA_START_SHUTDOWN_SECS;
+      start_str = " start ";
+    } else {
+      timeout_secs = EXTERNAL_RELAY_NO_DATA_SHUTDOWN_SECS;
+      start_str = " ";
+    };
+
+    let chunk = tokio::select! {
+      chunk = chunks.next() => chunk,
+
+      index = &mut recovered => break 'chunks SourceEnd::Recovered(index),
+
+      _ = sleep(Duration::from_secs(timeout_secs)) => {
+        info!(
+          target: "media",
+          "external-relay url {url} for station {station_id} produced no data in specified{start_str}window"
+        );
+        break 'chunks SourceEnd::Failed(MediaSessionRelaySwitchReason::NoData);
+      }
+    };
+
+    match chunk {
+      None | Some(Err(_)) => break 'chunks SourceEnd::Failed(MediaSessionRelaySwitchReason::Eof),
+      Some(Ok(bytes)) => {
+        if is_first_chunk {
+          is_first_chunk = false;
+          chain.recover(index);
         }
-      };
 
-      let r = if exit.success() {
-        Ok(())
-      } else {
-        match stderr {
-          Err(e) => {
-            warn!(
-              target: "media",
-              "external-relay session {station_id}: ffmpeg exit non-zero: exit={exit} stderr_error={e}"
-            );
-            Err(ExternalRelayError::StderrError(e))
-            // format!("internal error allocating stream converter (stderr 1)")
+        transfer.fetch_add(bytes.len(), Ordering::Relaxed);
+
+        match sender.send(bytes) {
+          Ok(_) => {
+            no_listeners_since = None;
           }
 
-          Ok(v) => {
-            let stderr = String::from_utf8_lossy(v.as_ref()).to_string();
-            // 224 happens when stdout is terminated (broken pipe) that happens normally when the session is cancelled
-            if exit.code() == Some(224) && stderr.contains("Broken pipe") {
-              Ok(())
-            } else {
-              warn!(
-                target: "media",
-                "external-relay session for station {station_id}: ffmpeg exit non-zero: exit={exit} stderr={stderr}"
-              );
-              Err(ExternalRelayError::ExitNotOk { stderr })
+          // check if shutdown delay is elapsed
+          Err(SendError::NoSubscribers(_)) => match no_listeners_since {
+            Some(instant) => {
+              if instant.elapsed().as_secs() > EXTERNAL_RELAY_NO_LISTENERS_SHUTDOWN_DELAY_SECS {
+                break 'chunks SourceEnd::NoListeners;
+              }
+            }
+
+            None => {
+              no_listeners_since = Some(Instant::now());
+            }
+          },
+
+          Err(SendError::Terminated(_)) => break 'chunks SourceEnd::Terminated,
+        };
+      }
+    }
+  };
+
+  let _ = child.kill().await;
+
+  if let SourceEnd::Failed(_) = end {
+    if let Ok(exit) = child.wait().await {
+      // 224 happens when stdout is terminated (broken pipe) that happens normally when the session is cancelled
+      if !exit.success() && exit.code() != Some(224) {
+        let stderr = match stderr_handle.await {
+          Ok(Ok(data)) => String::from_utf8_lossy(&data).to_string(),
+          _ => String::new(),
+        };
+
+        warn!(
+          target: "media",
+          "external-relay url {url} for station {station_id}: ffmpeg exit non-zero: exit={exit} stderr={stderr}"
+        );
+      }
+    }
+  }
+
+  end
+}
+
+async fn run_playlist_fallback(
+  sender: &Sender,
+  mut handle: JoinHandle<Result<(), mongodb::error::Error>>,
+  urls: Vec<String>,
+) -> SourceEnd {
+  let recovered = wait_recovered(urls);
+  tokio::pin!(recovered);
+
+  let end = tokio::select! {
+    _ = &mut handle => {
+      if sender.is_terminated() {
+        SourceEnd::Terminated
+      } else if sender.receiver_count() == 0 {
+        SourceEnd::NoListeners
+      } else {
+        SourceEnd::Failed(MediaSessionRelaySwitchReason::Eof)
+      }
+    }
+
+    index = &mut recovered => {
+      handle.abort();
+      SourceEnd::Recovered(index)
+    }
+  };
+
+  sender.set_now_playing(None);
+
+  end
+}
+
+/// no url is available and the station has no playlist
+/// the listeners are kept connected until a url recovers or can be retried
+async fn run_off_air(sender: &Sender, retry_in: Duration, urls: Vec<String>) -> SourceEnd {
+  let recovered = wait_recovered(urls);
+  tokio::pin!(recovered);
+
+  let retry = sleep(retry_in);
+  tokio::pin!(retry);
+
+  let mut no_listeners_since: Option<Instant> = None;
+
+  loop {
+    tokio::select! {
+      index = &mut recovered => return SourceEnd::Recovered(index),
+
+      _ = &mut retry => return SourceEnd::Retry,
+
+      _ = sleep(Duration::from_secs(1)) => {
+        if sender.is_terminated() {
+          return SourceEnd::Terminated;
+        }
+
+        if sender.receiver_count() != 0 {
+          no_listeners_since = None;
+        } else {
+          match no_listeners_since {
+            None => no_listeners_since = Some(Instant::now()),
+            Some(instant) => {
+              if instant.elapsed().as_secs() > EXTERNAL_RELAY_NO_LISTENERS_SHUTDOWN_DELAY_SECS {
+                return SourceEnd::NoListeners;
+              }
             }
           }
         }
-      };
+      }
+    }
+  }
+}
 
-      drop(dropper);
+/// resolves with the index of the first url that is healthy
+/// the urls are checked every EXTERNAL_RELAY_PRIMARY_CHECK_INTERVAL_SECS
+/// if urls is empty this future never resolves
+async fn wait_recovered(urls: Vec<String>) -> usize {
+  if urls.is_empty() {
+    return std::future::pending().await;
+  }
 
-      r
-    };
+  let interval = Duration::from_secs(EXTERNAL_RELAY_PRIMARY_CHECK_INTERVAL_SECS);
+  loop {
+    sleep(interval).await;
 
-    let signal = shutdown.signal();
+    for (index, url) in urls.iter().enumerate() {
+      if is_url_healthy(url).await {
+        return index;
+      }
+    }
+  }
+}
 
-    let r = tokio::select! {
-      r = fut => r,
-      _ = signal => Ok(())
-    };
+/// returns true if the url produces data within EXTERNAL_RELAY_PRIMARY_CHECK_TIMEOUT_SECS
+async fn is_url_healthy(url: &str) -> bool {
+  let config = FfmpegConfig {
+    input: Some(url.to_string()),
+    headers: ffmpeg::headers_for_url(url),
+    ..FfmpegConfig::default()
+  };
 
-    r
-  })
+  let mut spawn = match Ffmpeg::new(config).spawn() {
+    Ok(spawn) => spawn,
+    Err(_) => return false,
+  };
+
+  let mut buf = [0u8; 1024];
+
+  let read = tokio::time::timeout(
+    Duration::from_secs(EXTERNAL_RELAY_PRIMARY_CHECK_TIMEOUT_SECS),
+    spawn.stdout.read(&mut buf),
+  )
+  .await;
+
+  let _ = spawn.child.kill().await;
+
+  matches!(read, Ok(Ok(n)) if n > 0)
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum ExternalRelayError {
   #[error("live error db: {0}")]
   Db(#[from] mongodb::error::Error),
-  #[error("live error spawn: {0}")]
-  Spawn(std::io::Error),
-  #[error("live error stderr io: {0}")]
-  StderrError(std::io::Error),
-  #[error("live error exit io: {0}")]
-  ExitIo(std::io::Error),
-  #[error("live error exit not ok: stderr = {stderr}")]
-  ExitNotOk { stderr: String },
 }
 
 #[derive(Debug)]
@@ -359,3 +697,35 @@ impl Drop for MediaSessionDropper {
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn chain_skips_failed_urls() {
+    let mut chain = Chain::new(vec!["a".into(), "b".into(), "c".into()]);
+    assert_eq!(chain.next_source(), Some(0));
+
+    chain.fail(0, MediaSessionRelaySwitchReason::Eof);
+    assert_eq!(chain.next_source(), Some(1));
+
+    chain.fail(1, MediaSessionRelaySwitchReason::NoData);
+    chain.fail(2, MediaSessionRelaySwitchReason::Error);
+    assert_eq!(chain.next_source(), None);
+    assert!(chain.next_retry_in() > Duration::ZERO);
+
+    chain.recover(0);
+    assert_eq!(chain.next_source(), Some(0));
+
+    let health = chain.health();
+    assert!(health[0].healthy);
+    assert_eq!(health[0].failures, 0);
+    assert_eq!(
+      health[0].last_failure_reason,
+      Some(MediaSessionRelaySwitchReason::Eof)
+    );
+    assert!(!health[1].healthy);
+    assert_eq!(health[1].failures, 1);
+  }
+}
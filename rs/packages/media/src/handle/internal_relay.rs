@@ -16,6 +16,7 @@ use std::time::Instant;
 use tokio::task::JoinHandle;
 
 use crate::channel::{SendError, Sender};
+use crate::relay_auth::{RelayAuth, RelayAuthError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum GetInternalRelayError {
@@ -25,6 +26,8 @@ pub enum GetInternalRelayError {
   DeploymentNotFound(String),
   #[error("deployment no port")]
   DeploymentNoPort,
+  #[error("relay auth: {0}")]
+  RelayAuth(#[from] RelayAuthError),
   #[error("craete request: {0}")]
   CreateRequest(hyper::http::Error),
   #[error("send request: {0}")]
@@ -35,9 +38,11 @@ pub enum GetInternalRelayError {
   RelayStatus(StatusCode, Option<String>),
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_internal_relay_source(
   sender: Sender,
   deployment_id: String,
+  relay_auth: RelayAuth,
   _task_id: String,
   station_id: String,
   info: OwnerDeploymentInfo,
@@ -75,10 +80,15 @@ pub async fn get_internal_relay_source(
       station_id
     );
 
-    let hyper_req = hyper::Request::builder()
+    let signature = relay_auth.sign(&station_id, &deployment_id, &deployment.id)?;
+
+    let mut hyper_req = hyper::Request::builder()
       .uri(url)
-      .header("connection", "close")
-      .header(constants::HEADER_RELAY_SOURCE_DEPLOYMENT, &deployment_id);
+      .header("connection", "close");
+
+    for (name, value) in signature.headers() {
+      hyper_req = hyper_req.header(name, value);
+    }
 
     let hyper_req = match hyper_req.body(Body::empty()) {
       Ok(req) => req,
@@ -141,8 +151,15 @@ pub async fn get_internal_relay_source(
 
     let mut body = hyper_res.into_body();
 
+    let now_playing_sender = sender.clone();
+    let now_playing_station_id = station_id.clone();
+
     let spawn = tokio::spawn(async move {
       let signal = shutdown.signal();
+      let now_playing_handle = crate::now_playing::run_now_playing_sync_interval_for_station(
+        &now_playing_sender,
+        &now_playing_station_id,
+      );
       let task = async move {
         'root: loop {
           match body.try_next().await {
@@ -199,7 +216,8 @@ pub async fn get_internal_relay_source(
 
       tokio::select! {
         _ = signal => {},
-        _ = task => {}
+        _ = task => {},
+        never = now_playing_handle => match never {},
       }
     });
 
@@ -4,18 +4,23 @@ use std::sync::{
 };
 
 use crate::{
-  channel::{SendError, Sender},
+  channel::Sender,
+  dead_air::{AudioTap, DeadAirMonitor, DeadAirSource},
+  handle::live_input::{Broadcast, LiveInput, LiveInputError, LiveInputProcessor},
   handle::util::{PrettyBytes, PrettyDuration},
 };
 use bytes::Bytes;
 use constants::STREAM_CHUNK_SIZE;
-use db::{media_session::MediaSessionState, Model};
+use db::event::{Event, LiveSourceConnected, LiveSourceDisconnected};
+use db::{media_session::MediaSessionState, station_dj::StationDjRef, Model};
 use drop_tracer::{DropTracer, Token};
 use futures_util::{Stream, StreamExt};
 use log::*;
+use mailer::send::Mailer;
 use serde_util::DateTime;
 use shutdown::Shutdown;
 use stream_util::IntoTryBytesStreamChunked;
+use upload::recording::LiveRecorder;
 
 #[derive(Debug, thiserror::Error)]
 pub enum LiveError<E> {
@@ -23,10 +28,8 @@ pub enum LiveError<E> {
   Db(#[from] mongodb::error::Error),
   #[error("data: {0}")]
   Data(E),
-  // #[error("probe: {0}")]
-  // Probe(#[from] mp3::ProbeError),
-  // #[error("play: {0}")]
-  // Play(#[from] mp3::PlayError),
+  #[error("input: {0}")]
+  Input(#[from] LiveInputError),
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -35,22 +38,30 @@ pub async fn run_live_source<E: std::error::Error + Send + Sync + 'static>(
   deployment_id: String,
   task_id: String,
   station_id: String,
+  media_session_id: String,
   data: impl Stream<Item = Result<Bytes, E>> + Send + Sync + 'static,
+  input: LiveInput,
+  dj: Option<StationDjRef>,
   request: db::http::Request,
+  mailer: Mailer,
   shutdown: Shutdown,
   drop_tracer: DropTracer,
 ) -> Result<(), LiveError<E>> {
-  let media_session_id = db::media_session::MediaSession::uid();
   let document = {
     use db::media_session::*;
     let now = DateTime::now();
     let document = MediaSession {
       id: media_session_id.clone(),
-      deployment_id,
+      deployment_id: deployment_id.clone(),
       station_id: station_id.clone(),
       transfer_bytes: 0,
-      kind: MediaSessionKind::Live { request },
+      kind: MediaSessionKind::Live {
+        request,
+        input: None,
+        dj,
+      },
       now_playing: None,
+      dead_air: None,
       state: MediaSessionState::Open,
       closed_at: None,
       duration_ms: None,
@@ -79,6 +90,11 @@ pub async fn run_live_source<E: std::error::Error + Send + Sync + 'static>(
     "live media session start {document_id}, station {station_id}"
   );
 
+  Event::dispatch_detached(LiveSourceConnected {
+    station_id: station_id.clone(),
+    media_session_id: document_id.clone(),
+  });
+
   let transfer_bytes = Arc::new(AtomicU64::new(0));
 
   let dropper = MediaSessionDropper(Some((
@@ -89,6 +105,40 @@ pub async fn run_live_source<E: std::error::Error + Send + Sync + 'static>(
     drop_tracer.token(),
   )));
 
+  let now_playing_sender = sender.clone();
+
+  // a failure to get the station must not prevent the live session
+  let station = match db::station::Station::get_by_id(&station_id).await {
+    Ok(station) => station,
+    Err(e) => {
+      warn!(
+        target: "media",
+        "error getting station {station_id} to start the live recording and dead air monitor: {e} => {e:?}"
+      );
+      None
+    }
+  };
+
+  let recorder = station.as_ref().and_then(|station| {
+    LiveRecorder::start(station, &media_session_id, sender.now_playing.subscribe())
+  });
+
+  let tap = AudioTap::new();
+  let sender = sender.with_tap(tap.clone());
+
+  let dead_air_monitor = station.map(|station| DeadAirMonitor {
+    sender: sender.clone(),
+    tap,
+    station,
+    media_session_id: media_session_id.clone(),
+    source: DeadAirSource::Live,
+    deployment_id: deployment_id.clone(),
+    task_id: task_id.clone(),
+    mailer,
+    drop_tracer: drop_tracer.clone(),
+    shutdown: shutdown.clone(),
+  });
+
   let handle = async move {
     use stream_util::IntoTryBytesStreamRated;
     let output = data.rated(400_000 / 8).chunked(STREAM_CHUNK_SIZE);
@@ -98,6 +148,8 @@ pub async fn run_live_source<E: std::error::Error + Send + Sync + 'static>(
 
     let fut = async move {
       let mut transfer = 0u64;
+      let mut processor =
+        LiveInputProcessor::new(input, document_id.clone(), Broadcast { sender, recorder });
 
       loop {
         match output.next().await {
@@ -109,15 +161,23 @@ pub async fn run_live_source<E: std::error::Error + Send + Sync + 'static>(
           Some(Ok(bytes)) => {
             transfer += bytes.len() as u64;
             transfer_bytes.store(transfer, Ordering::Release);
-            match sender.send(bytes) {
-              Ok(_) => continue,
-              Err(SendError::NoSubscribers(_)) => continue,
-              Err(SendError::Terminated(_)) => break,
+            match processor.push(bytes).await {
+              Ok(true) => continue,
+              Ok(false) => break,
+              Err(e) => {
+                warn!(
+                  target: "media",
+                  "live media session {document_id} input error: {e} => {e:?}"
+                );
+                return Err(LiveError::Input(e));
+              }
             }
           }
         }
       }
 
+      processor.end().await?;
+
       Ok(())
     };
 
@@ -135,12 +195,26 @@ pub async fn run_live_source<E: std::error::Error + Send + Sync + 'static>(
     &task_id,
   );
 
+  let now_playing_handle = crate::now_playing::run_now_playing_sync_interval_for_media_session(
+    &now_playing_sender,
+    &media_session_id,
+  );
+
+  let dead_air_handle = async move {
+    match dead_air_monitor {
+      None => std::future::pending().await,
+      Some(monitor) => monitor.run().await,
+    }
+  };
+
   let result = tokio::select! {
     result = handle => result,
     r = health_handle => match r {
       Ok(never) => match never {},
       Err(e) => return Err(e.into()),
-    }
+    },
+    never = now_playing_handle => match never {},
+    never = dead_air_handle => match never {},
   };
 
   drop(dropper);
@@ -188,6 +262,13 @@ impl Drop for MediaSessionDropper {
           )
         }
 
+        Event::dispatch_detached(LiveSourceDisconnected {
+          station_id,
+          media_session_id: id,
+          duration_ms,
+          transfer_bytes,
+        });
+
         drop(token)
       });
     }
@@ -0,0 +1,280 @@
+use bytes::Bytes;
+use constants::STREAM_CHUNK_SIZE;
+use db::media_session::{MediaSession, MediaSessionLiveInput, MediaSessionLiveInputCodec};
+use ffmpeg::{FfmpegConfig, TransformError};
+use log::*;
+use mp3::{StreamCodec, StreamError, StreamFormat, StreamValidator};
+use tokio::task::JoinHandle;
+use upload::recording::LiveRecorder;
+
+use crate::channel::{SendError, Sender};
+
+/// how the audio sent by a live encoder is checked before it is broadcasted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveInput {
+  /// the frames are validated as `codec` and broadcasted as is,
+  /// the encoder is disconnected if they are invalid or their format changes,
+  /// or if MP3 input is not in the stream format of the station
+  Validate(StreamCodec),
+  /// MP3 input in the stream format of the station is broadcasted as is,
+  /// anything else is transcoded to it
+  Transcode,
+}
+
+impl LiveInput {
+  /// the input of an encoder that sends `content_type`,
+  /// None if the content type is not supported and the input is not transcoded
+  pub fn new(content_type: &str, transcode: bool) -> Option<Self> {
+    if transcode {
+      Some(Self::Transcode)
+    } else {
+      StreamCodec::from_content_type(content_type).map(Self::Validate)
+    }
+  }
+
+  /// content type of the stream that is broadcasted to the listeners
+  pub fn content_type(&self) -> &'static str {
+    match self {
+      Self::Validate(codec) => codec.content_type(),
+      Self::Transcode => StreamCodec::Mp3.content_type(),
+    }
+  }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LiveInputError {
+  #[error("invalid audio data: {0}")]
+  Invalid(#[from] StreamError),
+  #[error("input format {0} does not match the stream format of the station")]
+  FormatMismatch(StreamFormat),
+  #[error("transcoder spawn: {0}")]
+  Spawn(std::io::Error),
+  #[error("transcoder io: {0}")]
+  TranscodeIo(std::io::Error),
+  #[error("transcoder exit: {0}")]
+  TranscodeExit(String),
+}
+
+impl From<TransformError> for LiveInputError {
+  fn from(e: TransformError) -> Self {
+    match e {
+      TransformError::Io(e) => Self::TranscodeIo(e),
+      TransformError::Exit { status, stderr } => {
+        Self::TranscodeExit(format!("{status}: {}", stderr.unwrap_or_default()))
+      }
+    }
+  }
+}
+
+/// sends the live audio to the listeners and to the recording of the session
+pub(crate) struct Broadcast {
+  pub sender: Sender,
+  pub recorder: Option<LiveRecorder>,
+}
+
+impl Broadcast {
+  /// returns false if the channel was terminated
+  fn send(&mut self, bytes: Bytes) -> bool {
+    if let Some(recorder) = self.recorder.as_mut() {
+      recorder.push(bytes.clone());
+    }
+
+    match self.sender.send(bytes) {
+      Ok(_) => true,
+      Err(SendError::NoSubscribers(_)) => true,
+      Err(SendError::Terminated(_)) => false,
+    }
+  }
+}
+
+struct Transcoder {
+  input: spsc::Sender<Bytes>,
+  /// the task that broadcasts the output of the transcoder
+  output: JoinHandle<Result<(), LiveInputError>>,
+}
+
+/// validates the audio of a live media session and broadcasts it, or its transcoded output
+pub(crate) struct LiveInputProcessor {
+  mode: LiveInput,
+  media_session_id: String,
+  validator: StreamValidator,
+  /// the bytes received before the format of the input is known
+  pending: Vec<Bytes>,
+  reported: bool,
+  /// None after the input is transcoded, the broadcast is owned by the transcoder output task
+  broadcast: Option<Broadcast>,
+  transcoder: Option<Transcoder>,
+}
+
+impl LiveInputProcessor {
+  pub fn new(mode: LiveInput, media_session_id: String, broadcast: Broadcast) -> Self {
+    let codec = match mode {
+      LiveInput::Validate(codec) => codec,
+      LiveInput::Transcode => StreamCodec::Mp3,
+    };
+
+    Self {
+      mode,
+      media_session_id,
+      validator: StreamValidator::new(codec),
+      pending: vec![],
+      reported: false,
+      broadcast: Some(broadcast),
+      transcoder: None,
+    }
+  }
+
+  /// returns Ok(false) if the channel was terminated
+  pub async fn push(&mut self, bytes: Bytes) -> Result<bool, LiveInputError> {
+    if let Some(transcoder) = &self.transcoder {
+      return Ok(transcoder.input.send(bytes).await.is_ok());
+    }
+
+    match self.validator.push(&bytes) {
+      Err(e) => match self.mode {
+        LiveInput::Validate(_) => Err(e.into()),
+        LiveInput::Transcode => {
+          info!(
+            target: "media",
+            "live media session {} input will be transcoded: {e}",
+            self.media_session_id
+          );
+          self.pending.push(bytes);
+          self.start_transcoding().await
+        }
+      },
+
+      Ok(()) => {
+        self.pending.push(bytes);
+
+        let format = match self.validator.format() {
+          None => return Ok(true),
+          Some(format) => format,
+        };
+
+        if !self.reported {
+          if !is_stream_format(format) {
+            match self.mode {
+              LiveInput::Transcode => {
+                self.reported = true;
+                info!(
+                  target: "media",
+                  "live media session {} input {format} will be transcoded",
+                  self.media_session_id
+                );
+                return self.start_transcoding().await;
+              }
+
+              // the MP3 channel of the station is handed over with its listeners,
+              // that could not decode another sample rate or channels
+              // nothing of the input was broadcasted yet, it is held in pending until the format is known
+              LiveInput::Validate(StreamCodec::Mp3) => {
+                return Err(LiveInputError::FormatMismatch(format));
+              }
+
+              LiveInput::Validate(_) => {}
+            }
+          }
+
+          self.reported = true;
+          self.report(false);
+        }
+
+        let broadcast = match &mut self.broadcast {
+          Some(broadcast) => broadcast,
+          None => return Ok(true),
+        };
+
+        for bytes in self.pending.drain(..) {
+          if !broadcast.send(bytes) {
+            return Ok(false);
+          }
+        }
+
+        Ok(true)
+      }
+    }
+  }
+
+  /// waits for the transcoder to send the rest of its output
+  pub async fn end(self) -> Result<(), LiveInputError> {
+    // with the average bitrate of the whole session
+    if self.validator.format().is_some() {
+      self.report(self.transcoder.is_some());
+    }
+
+    match self.transcoder {
+      None => Ok(()),
+      Some(Transcoder { input, output }) => {
+        drop(input);
+        match output.await {
+          Ok(result) => result,
+          // the task does not panic
+          Err(_) => Ok(()),
+        }
+      }
+    }
+  }
+
+  async fn start_transcoding(&mut self) -> Result<bool, LiveInputError> {
+    let mut broadcast = match self.broadcast.take() {
+      Some(broadcast) => broadcast,
+      None => return Ok(true),
+    };
+
+    let (input, mut reader) = ffmpeg::transform(FfmpegConfig::default(), STREAM_CHUNK_SIZE)
+      .map_err(LiveInputError::Spawn)?;
+
+    let output = tokio::spawn(async move {
+      while let Some(item) = reader.recv().await {
+        if !broadcast.send(item?) {
+          break;
+        }
+      }
+      Ok::<(), LiveInputError>(())
+    });
+
+    self.report(true);
+
+    let transcoder = self.transcoder.insert(Transcoder { input, output });
+
+    for bytes in self.pending.drain(..) {
+      if transcoder.input.send(bytes).await.is_err() {
+        return Ok(false);
+      }
+    }
+
+    Ok(true)
+  }
+
+  fn report(&self, transcoded: bool) {
+    let format = self.validator.format();
+    let input = MediaSessionLiveInput {
+      codec: format.map(|format| match format.codec {
+        StreamCodec::Mp3 => MediaSessionLiveInputCodec::Mp3,
+        StreamCodec::Aac => MediaSessionLiveInputCodec::Aac,
+      }),
+      sample_rate: format.map(|format| format.sample_rate),
+      channels: format.map(|format| format.channels),
+      kbitrate: self.validator.kbitrate(),
+      transcoded,
+    };
+
+    let id = self.media_session_id.clone();
+    tokio::spawn(async move {
+      if let Err(e) = MediaSession::set_live_input(&id, input).await {
+        warn!(
+          target: "media",
+          "error saving live media session {id} input: {e} => {e:?}"
+        );
+      }
+    });
+  }
+}
+
+/// the format of the stream broadcasted to the listeners of a station
+fn is_stream_format(format: StreamFormat) -> bool {
+  format.codec == StreamCodec::Mp3
+    && format.sample_rate == FfmpegConfig::FREQ as u32
+    && format.channels == FfmpegConfig::CHANNELS
+}
@@ -1,7 +1,9 @@
 pub mod external_relay;
 pub mod internal_relay;
 pub mod live;
+pub mod live_input;
 pub mod playlist;
+pub mod transition;
 
 pub(crate) mod util;
 
@@ -1,11 +1,17 @@
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::Instant;
 
+use bytes::{Bytes, BytesMut};
 use constants::{
-  PLAYLIST_NO_LISTENERS_SHUTDOWN_DELAY_SECS, STREAM_BURST_LENGTH, STREAM_CHUNK_SIZE,
+  PLAYLIST_GAPLESS_WINDOW_MS, PLAYLIST_NO_LISTENERS_SHUTDOWN_DELAY_SECS, STREAM_BURST_LENGTH,
+  STREAM_CHUNK_SIZE,
 };
+use db::event::{Event, NowPlayingChanged};
 use db::media_session::MediaSessionNowPlaying;
 use db::play_history_item::{self, PlayHistoryItem};
+use db::playlist::Playlist;
+use db::playlist_item::PlaylistItem;
+use db::station::Station;
 use db::{audio_chunk::AudioChunk, audio_file::AudioFile, Model};
 use drop_tracer::{DropTracer, Token};
 use log::*;
@@ -20,30 +26,321 @@ use mongodb::options::FindOneOptions;
 use atomic_float::AtomicF64;
 
 use shutdown::Shutdown;
+use std::collections::VecDeque;
 use std::sync::Arc;
 use stream_util::{IntoTryBytesStreamChunked, IntoTryBytesStreamRated};
 use tokio::task::JoinHandle;
 
+use super::transition;
 use crate::channel::{SendError, Sender};
 
+/// extra audio read around a transition window for the decoder warmup frames
+const TRANSITION_MARGIN_MS: u32 = 200;
+
+/// the files played by a playlist source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaylistSelection {
+  /// the station files in their order, only `file_ids` if not empty
+  Files { file_ids: Vec<String> },
+  /// a named playlist of the station, interleaved with its rotations
+  Playlist { playlist_id: String },
+}
+
+impl PlaylistSelection {
+  /// the default playlist of the station, or all the station files if it has none
+  pub fn for_station(station: &Station) -> Self {
+    match &station.default_playlist_id {
+      Some(playlist_id) => Self::Playlist {
+        playlist_id: playlist_id.clone(),
+      },
+      None => Self::Files { file_ids: vec![] },
+    }
+  }
+
+  pub async fn for_station_id(station_id: &str) -> Result<Self, mongodb::error::Error> {
+    match Station::get_by_id(station_id).await? {
+      Some(station) => Ok(Self::for_station(&station)),
+      None => Ok(Self::Files { file_ids: vec![] }),
+    }
+  }
+
+  pub fn playlist_id(&self) -> Option<&str> {
+    match self {
+      Self::Files { .. } => None,
+      Self::Playlist { playlist_id } => Some(playlist_id.as_str()),
+    }
+  }
+
+  /// a files subset (from a schedule slot) always starts from its first file
+  pub fn is_resumable(&self) -> bool {
+    match self {
+      Self::Files { file_ids } => file_ids.is_empty(),
+      Self::Playlist { .. } => true,
+    }
+  }
+
+  /// returns true if the selection has at least one file
+  pub async fn is_playable(&self, station_id: &str) -> Result<bool, mongodb::error::Error> {
+    match self {
+      Self::Files { file_ids } => {
+        let mut filter = doc! { AudioFile::KEY_STATION_ID: station_id };
+        if !file_ids.is_empty() {
+          filter.insert(AudioFile::KEY_ID, doc! { "$in": file_ids });
+        }
+        AudioFile::exists(filter).await
+      }
+      Self::Playlist { playlist_id } => {
+        let filter = doc! {
+          PlaylistItem::KEY_PLAYLIST_ID: playlist_id,
+          PlaylistItem::KEY_STATION_ID: station_id,
+        };
+        PlaylistItem::exists(filter).await
+      }
+    }
+  }
+}
+
+/// a file to play and its position in the main playlist
+/// a rotation file keeps the position of the main playlist file played before it
+#[derive(Debug)]
+struct Entry {
+  file: AudioFile,
+  order: f64,
+}
+
+impl Entry {
+  fn from_file(file: AudioFile) -> Self {
+    Self {
+      order: file.order,
+      file,
+    }
+  }
+}
+
+#[derive(Debug)]
+struct RotationState {
+  playlist_id: String,
+  every: u64,
+  /// the last played file of the rotation playlist and its order
+  current: Option<(String, f64)>,
+}
+
+/// the start of the next file left over by a transition
+/// the next file is streamed from chunk NEXT_I with PREFIX before it
+#[derive(Debug)]
+struct Carry {
+  file_id: String,
+  next_i: f64,
+  prefix: Bytes,
+}
+
+/// walks the files of a playlist selection
+#[derive(Debug)]
+struct Cursor {
+  station_id: String,
+  selection: PlaylistSelection,
+  rotations: Vec<RotationState>,
+  /// files of the main playlist played in this session
+  played: u64,
+  /// indexes of the rotations due before the next file of the main playlist
+  pending: VecDeque<usize>,
+  /// true if the last entry was a rotation file
+  in_rotation: bool,
+  /// the last file of the main playlist and its order
+  main: Option<(String, f64)>,
+}
+
+impl Cursor {
+  async fn new(
+    station_id: &str,
+    selection: PlaylistSelection,
+  ) -> Result<Self, mongodb::error::Error> {
+    let rotations = match &selection {
+      PlaylistSelection::Files { .. } => vec![],
+      PlaylistSelection::Playlist { playlist_id } => {
+        match Playlist::get_by_id(playlist_id).await? {
+          None => vec![],
+          Some(playlist) => playlist
+            .rotations
+            .into_iter()
+            .map(|rotation| RotationState {
+              playlist_id: rotation.playlist_id,
+              every: (rotation.every as u64).max(1),
+              current: None,
+            })
+            .collect(),
+        }
+      }
+    };
+
+    Ok(Self {
+      station_id: station_id.to_string(),
+      selection,
+      rotations,
+      played: 0,
+      pending: VecDeque::new(),
+      in_rotation: false,
+      main: None,
+    })
+  }
+
+  async fn entry_for_item(
+    item: Option<PlaylistItem>,
+  ) -> Result<Option<Entry>, mongodb::error::Error> {
+    let item = match item {
+      None => return Ok(None),
+      Some(item) => item,
+    };
+
+    let file = AudioFile::get_by_id(&item.file_id).await?;
+    Ok(file.map(|file| Entry {
+      file,
+      order: item.order,
+    }))
+  }
+
+  async fn first(&self) -> Result<Option<Entry>, mongodb::error::Error> {
+    match &self.selection {
+      PlaylistSelection::Files { file_ids } => {
+        let file = AudioFile::playlist_first(&self.station_id, file_ids).await?;
+        Ok(file.map(Entry::from_file))
+      }
+      PlaylistSelection::Playlist { playlist_id } => {
+        let item = PlaylistItem::playlist_first(playlist_id).await?;
+        Self::entry_for_item(item).await
+      }
+    }
+  }
+
+  /// the entry of `file_id` if it is still in the main playlist
+  async fn find(&self, file_id: &str) -> Result<Option<Entry>, mongodb::error::Error> {
+    match &self.selection {
+      PlaylistSelection::Files { .. } => {
+        let filter =
+          doc! { AudioFile::KEY_ID: file_id, AudioFile::KEY_STATION_ID: &self.station_id };
+        let file = AudioFile::cl().find_one(filter, None).await?;
+        Ok(file.map(Entry::from_file))
+      }
+      PlaylistSelection::Playlist { playlist_id } => {
+        let filter = doc! {
+          PlaylistItem::KEY_PLAYLIST_ID: playlist_id,
+          PlaylistItem::KEY_FILE_ID: file_id,
+        };
+        let item = PlaylistItem::cl().find_one(filter, None).await?;
+        Self::entry_for_item(item).await
+      }
+    }
+  }
+
+  /// the file of the main playlist that follows `current_id`
+  async fn main_after(
+    &self,
+    current_id: &str,
+    current_order: f64,
+  ) -> Result<Option<Entry>, mongodb::error::Error> {
+    match &self.selection {
+      PlaylistSelection::Files { file_ids } => {
+        let file =
+          AudioFile::playlist_next(&self.station_id, current_id, current_order, file_ids).await?;
+        Ok(file.map(Entry::from_file))
+      }
+      PlaylistSelection::Playlist { playlist_id } => {
+        let item = PlaylistItem::playlist_next(playlist_id, current_id, current_order).await?;
+        Self::entry_for_item(item).await
+      }
+    }
+  }
+
+  /// the next file of the rotation at `index`, None if the rotation playlist is empty
+  async fn rotation_next(&mut self, index: usize) -> Result<Option<Entry>, mongodb::error::Error> {
+    let rotation = &mut self.rotations[index];
+    let item = match &rotation.current {
+      None => PlaylistItem::playlist_first(&rotation.playlist_id).await?,
+      Some((file_id, order)) => {
+        PlaylistItem::playlist_next(&rotation.playlist_id, file_id, *order).await?
+      }
+    };
+
+    let item = match item {
+      None => return Ok(None),
+      Some(item) => item,
+    };
+
+    rotation.current = Some((item.file_id.clone(), item.order));
+
+    let order = match &self.main {
+      Some((_, order)) => *order,
+      None => item.order,
+    };
+
+    let file = AudioFile::get_by_id(&item.file_id).await?;
+    Ok(file.map(|file| Entry { file, order }))
+  }
+
+  /// marks `entry` as the current file of the main playlist
+  fn start(&mut self, entry: &Entry) {
+    self.main = Some((entry.file.id.clone(), entry.order));
+  }
+
+  async fn next(&mut self) -> Result<Option<Entry>, mongodb::error::Error> {
+    if !self.in_rotation {
+      self.played += 1;
+      for (index, rotation) in self.rotations.iter().enumerate() {
+        if self.played.is_multiple_of(rotation.every) {
+          self.pending.push_back(index);
+        }
+      }
+    }
+
+    while let Some(index) = self.pending.pop_front() {
+      if let Some(entry) = self.rotation_next(index).await? {
+        self.in_rotation = true;
+        return Ok(Some(entry));
+      }
+    }
+
+    self.in_rotation = false;
+
+    let next = match &self.main {
+      None => self.first().await?,
+      Some((current_id, current_order)) => self.main_after(current_id, *current_order).await?,
+    };
+
+    if let Some(entry) = &next {
+      self.start(entry);
+    }
+
+    Ok(next)
+  }
+}
+
+/// plays the playlist of a station
+///
+/// if `session_id` is set the playlist is played as part of that open media session (eg: the external relay fallback)
+/// and no playlist session is opened, otherwise the playlist opens and closes its own session
+#[allow(clippy::too_many_arguments)]
 pub fn run_playlist_source(
   sender: Sender,
   deployment_id: String,
   task_id: String,
   station_id: String,
   resume: bool,
+  selection: PlaylistSelection,
+  session_id: Option<String>,
   drop_tracer: DropTracer,
   shutdown: Shutdown,
 ) -> JoinHandle<Result<(), mongodb::error::Error>> {
   tokio::spawn(async move {
-    let (resume_playlist_id, start_file_id, i, part) = if resume {
-      resume_info_for_station(&station_id).await?
+    let mut cursor = Cursor::new(&station_id, selection).await?;
+
+    let (resume_playlist_id, start_entry, i, part) = if resume && cursor.selection.is_resumable() {
+      resume_info(&cursor).await?
     } else {
-      let file = AudioFile::playlist_first(&station_id).await?;
-      (None, file, 0.0, 0)
+      let entry = cursor.first().await?;
+      (None, entry, 0.0, 0)
     };
 
-    let start_file = match start_file_id {
+    let start_entry = match start_entry {
       None => {
         info!(
           target: "media",
@@ -51,18 +348,23 @@ pub fn run_playlist_source(
         );
         return Ok(());
       }
-      Some(id) => id,
+      Some(entry) => entry,
     };
 
+    cursor.start(&start_entry);
+
+    let playlist_id = cursor.selection.playlist_id().map(ToString::to_string);
+    let start_file = &start_entry.file;
+
     info!(
       target: "media",
-      "media session (playlist) start for station {} file_id={} order={} chunk={} part={}",
-      station_id, start_file.id, start_file.order, i, part
+      "media session (playlist) start for station {} playlist_id={:?} file_id={} order={} chunk={} part={}",
+      station_id, playlist_id, start_file.id, start_entry.order, i, part
     );
 
     let out = PlaylistIndexInfoOut(Arc::new(Inner {
       file_id: Mutex::new(start_file.id.clone()),
-      file_order: AtomicF64::new(start_file.order),
+      file_order: AtomicF64::new(start_entry.order),
       i: AtomicF64::new(i),
       part: AtomicUsize::new(part),
       transfer: AtomicU64::new(0),
@@ -73,12 +375,18 @@ pub fn run_playlist_source(
       Some(title) => Some(MediaSessionNowPlaying {
         title: title.clone(),
         artist: start_file.metadata.artist.clone(),
+        play_history_item_id: None,
       }),
     };
 
-    let media_session_id = db::media_session::MediaSession::uid();
+    let own_session = session_id.is_none();
 
-    let media_session_doc = {
+    let media_session_id = match session_id {
+      Some(id) => id,
+      None => db::media_session::MediaSession::uid(),
+    };
+
+    let dropper = if own_session {
       use db::media_session::*;
       let now = DateTime::now();
       let media_session_doc = MediaSession {
@@ -87,13 +395,15 @@ pub fn run_playlist_source(
         deployment_id: deployment_id.clone(),
         transfer_bytes: 0,
         now_playing,
+        dead_air: None,
         kind: MediaSessionKind::Playlist {
           resumed_from: resume_playlist_id,
+          playlist_id,
           last_audio_chunk_date: DateTime::now(),
           last_audio_chunk_i: i,
           last_audio_chunk_skip_parts: part,
           last_audio_file_id: start_file.id.clone(),
-          last_audio_file_order: start_file.order,
+          last_audio_file_order: start_entry.order,
         },
         state: MediaSessionState::Open,
         closed_at: None,
@@ -104,15 +414,16 @@ pub fn run_playlist_source(
       };
 
       MediaSession::insert(&media_session_doc).await?;
-      media_session_doc
-    };
 
-    let dropper = MediaSessionDropper {
-      id: media_session_doc.id,
-      station_id: media_session_doc.station_id,
-      out: out.clone(),
-      token: Some(drop_tracer.token()),
-      start: Instant::now(),
+      Some(MediaSessionDropper {
+        id: media_session_doc.id,
+        station_id: media_session_doc.station_id,
+        out: out.clone(),
+        token: Some(drop_tracer.token()),
+        start: Instant::now(),
+      })
+    } else {
+      None
     };
 
     let signal = shutdown.signal();
@@ -126,17 +437,24 @@ pub fn run_playlist_source(
 
         let mut no_listeners_since: Option<Instant> = None;
 
-        let mut current_file = start_file;
+        let mut current = start_entry;
+
+        // the next entry, taken from the cursor in advance to prepare a transition
+        let mut upcoming: Option<Entry> = None;
+
+        let carry = Arc::new(Mutex::new(None::<Carry>));
 
         'files: loop {
           let (i, part) = if first {
             first = false;
             (i, part)
           } else {
-            let next_file =
-              AudioFile::playlist_next(&station_id, &current_file.id, current_file.order).await?;
+            let next = match upcoming.take() {
+              Some(entry) => Some(entry),
+              None => cursor.next().await?,
+            };
 
-            match next_file {
+            match next {
               None => {
                 info!(
                   "stopping playlist for station {} (no files found in account)",
@@ -146,14 +464,24 @@ pub fn run_playlist_source(
                 break 'files;
               }
 
-              Some(next_file) => {
-                current_file = next_file;
+              Some(next) => {
+                current = next;
               }
             }
 
             (0.0, 0)
           };
 
+          let current_file = &current.file;
+
+          // the start of this file may have been already mixed with the end of the previous one
+          let (i, part, prefix) = match carry.lock().take() {
+            Some(carry) if carry.file_id == current_file.id => {
+              (carry.next_i - 1.0, 0, Some(carry.prefix))
+            }
+            _ => (i, part, None),
+          };
+
           info!(
             "start playback of audio file {}: '{}' for station {}",
             current_file.id,
@@ -188,8 +516,11 @@ pub fn run_playlist_source(
             let now_playing = MediaSessionNowPlaying {
               title,
               artist: current_file.metadata.artist.clone(),
+              play_history_item_id: Some(play_history_item.id.clone()),
             };
 
+            sender.set_now_playing(Some(now_playing.clone()));
+
             let update = doc! {
               "$set": {
                 db::media_session::MediaSession::KEY_NOW_PLAYING: now_playing,
@@ -198,21 +529,126 @@ pub fn run_playlist_source(
             };
 
             db::media_session::MediaSession::update_by_id(&media_session_id, update).await?;
-            PlayHistoryItem::insert(play_history_item).await?;
+            PlayHistoryItem::insert(&play_history_item).await?;
 
-            use db::media_session::MediaSession;
-            MediaSession::set_file_chunk_part(&media_session_id, &current_file.id, i, part as f64)
+            Event::dispatch_detached(NowPlayingChanged {
+              station_id: station_id.to_string(),
+              title: play_history_item.title,
+              artist: play_history_item.artist,
+            });
+
+            // the chunk position is only tracked in playlist sessions
+            if own_session {
+              use db::media_session::MediaSession;
+              MediaSession::set_file_chunk_part(
+                &media_session_id,
+                &current_file.id,
+                i,
+                part as f64,
+              )
               .await?;
+            }
           }
 
           out.set_file_id(current_file.id.clone());
-          out.set_file_order(current_file.order);
+          out.set_file_order(current.order);
           out.set_i(i);
           out.set_part(part);
 
+          let transition = match Station::get_by_id(&station_id).await? {
+            Some(station) => station.playlist_transition,
+            None => Default::default(),
+          };
+
+          let mut tail_task = None;
+
+          if transition::is_applicable(&transition, current_file.duration_ms) {
+            if upcoming.is_none() {
+              upcoming = cursor.next().await?;
+            }
+
+            if let Some(next) = &upcoming {
+              let window_ms = (transition.crossfade_ms.max(PLAYLIST_GAPLESS_WINDOW_MS)
+                + TRANSITION_MARGIN_MS) as usize;
+
+              let tail_len = window_ms * current_file.bytes_sec / 1000;
+              // one more chunk because the last chunk of a file is usually shorter
+              let tail_chunks =
+                (tail_len.div_ceil(current_file.chunk_len) + 1).min(current_file.chunk_count);
+              let tail_start = (current_file.chunk_count - tail_chunks) as f64;
+
+              let head_len = window_ms * next.file.bytes_sec / 1000;
+              let head_chunks = head_len
+                .div_ceil(next.file.chunk_len)
+                .clamp(1, next.file.chunk_count.max(1));
+
+              if tail_start > i {
+                let file_id = current_file.id.clone();
+                let tail_end = current_file.chunk_count as f64;
+                let next_id = next.file.id.clone();
+                let station_id = station_id.clone();
+                let carry = carry.clone();
+
+                let task = tokio::spawn(async move {
+                  let tail = AudioChunk::read_range(&file_id, tail_start, tail_end).await?;
+                  let head = AudioChunk::read_range(&next_id, 0.0, head_chunks as f64).await?;
+
+                  match transition::splice(tail.clone(), head, transition).await {
+                    Ok(splice) => {
+                      carry.lock().replace(Carry {
+                        file_id: next_id,
+                        next_i: head_chunks as f64,
+                        prefix: splice.head,
+                      });
+
+                      let mut bytes =
+                        BytesMut::with_capacity(splice.tail.len() + splice.mixed.len());
+                      bytes.extend_from_slice(&splice.tail);
+                      bytes.extend_from_slice(&splice.mixed);
+                      Ok(bytes.freeze())
+                    }
+
+                    Err(e) => {
+                      warn!(
+                        target: "media",
+                        "playlist transition for station {station_id} from file {file_id} to file {next_id} failed, playing files without transition: {e}"
+                      );
+                      Ok(tail)
+                    }
+                  }
+                });
+
+                tail_task = Some((tail_start, tail_end, task));
+              }
+            }
+          }
+
+          let body_start = if prefix.is_some() { i + 1.0 } else { i };
+          let body_end = tail_task.as_ref().map(|(start, _, _)| *start);
+
+          let file_id = current_file.id.clone();
+          let tail_stream =
+            futures_util::stream::iter(tail_task).then(move |(start, end, task)| {
+              let file_id = file_id.clone();
+              async move {
+                match task.await {
+                  Ok(r) => r,
+                  Err(_) => AudioChunk::read_range(&file_id, start, end).await,
+                }
+              }
+            });
+
+          let stream = futures_util::stream::iter(prefix.map(Ok))
+            .chain(AudioChunk::stream_range(
+              &current_file.id,
+              body_start,
+              body_end,
+            ))
+            .chain(tail_stream);
+
           let mut first_item = true;
 
-          let stream = AudioChunk::stream_from(&current_file.id, i).inspect(|_| {
+          let stream = stream.inspect(|_| {
             if first_item {
               first_item = false;
             } else {
@@ -309,11 +745,19 @@ pub fn run_playlist_source(
         Ok(())
       };
 
-      let health_handle = crate::health::run_health_check_interval_for_station_and_media_session(
-        &station_id,
-        &media_session_id,
-        &task_id,
-      );
+      // a reused session is health checked by the source that opened it
+      let health_handle = async {
+        if own_session {
+          crate::health::run_health_check_interval_for_station_and_media_session(
+            &station_id,
+            &media_session_id,
+            &task_id,
+          )
+          .await
+        } else {
+          std::future::pending().await
+        }
+      };
 
       let r = tokio::select! {
         r = handle => r,
@@ -344,13 +788,16 @@ pub fn run_playlist_source(
   })
 }
 
-async fn resume_info_for_station(
-  station_id: &str,
-) -> Result<(Option<String>, Option<AudioFile>, f64, usize), mongodb::error::Error> {
+/// resumes from the last playlist session of the station with the same playlist
+async fn resume_info(
+  cursor: &Cursor,
+) -> Result<(Option<String>, Option<Entry>, f64, usize), mongodb::error::Error> {
   use db::media_session::{MediaSession, MediaSessionKind};
+  let station_id = cursor.station_id.as_str();
   let filter = doc! {
     MediaSession::KEY_STATION_ID: station_id,
     MediaSessionKind::KEY_ENUM_TAG: MediaSessionKind::KEY_ENUM_VARIANT_PLAYLIST,
+    MediaSessionKind::KEY_PLAYLIST_ID: cursor.selection.playlist_id(),
   };
   let sort = doc! {
     MediaSession::KEY_CREATED_AT: -1
@@ -360,8 +807,8 @@ async fn resume_info_for_station(
 
   let session = match MediaSession::cl().find_one(filter, options).await? {
     None => {
-      let file = AudioFile::playlist_first(station_id).await?;
-      return Ok((None, file, 0.0, 0));
+      let entry = cursor.first().await?;
+      return Ok((None, entry, 0.0, 0));
     }
     Some(session) => session,
   };
@@ -375,12 +822,13 @@ async fn resume_info_for_station(
         station_id
       );
 
-      let file = AudioFile::playlist_first(station_id).await?;
-      return Ok((None, file, 0.0, 0));
+      let entry = cursor.first().await?;
+      return Ok((None, entry, 0.0, 0));
     }
 
     MediaSessionKind::Playlist {
       resumed_from: _,
+      playlist_id: _,
       last_audio_file_id,
       last_audio_file_order,
       last_audio_chunk_i,
@@ -394,18 +842,17 @@ async fn resume_info_for_station(
     ),
   };
 
-  let filter = doc! { AudioFile::KEY_ID: &audio_file_id, AudioFile::KEY_STATION_ID: station_id };
-  let file = match AudioFile::cl().find_one(filter, None).await? {
+  // the last file may be a rotation file or may have been removed from the playlist
+  // in that case we continue with the file of the playlist that follows its position
+  let entry = match cursor.find(&audio_file_id).await? {
     None => {
-      match AudioFile::playlist_next(station_id, &audio_file_id, audio_file_order).await? {
-        None => return Ok((Some(session.id), None, 0.0, 0)),
-        Some(file) => return Ok((Some(session.id), Some(file), 0.0, 0)),
-      };
+      let entry = cursor.main_after(&audio_file_id, audio_file_order).await?;
+      return Ok((Some(session.id), entry, 0.0, 0));
     }
-    Some(file) => file,
+    Some(entry) => entry,
   };
 
-  Ok((Some(session.id), Some(file), i, parts))
+  Ok((Some(session.id), Some(entry), i, parts))
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,417 @@
+//! crossfade and gapless transitions between the files of a playlist
+//!
+//! the files of a playlist are stored as independently encoded mp3 streams,
+//! concatenating them cuts the audio in the middle of the encoder delay and padding
+//! and breaks the bit reservoir of the first frames of the next file
+//!
+//! here we decode the end of the current file and the start of the next one,
+//! mix them and re-encode only that window, the result is spliced on mp3 frame boundaries
+//! so the rest of both files is still streamed as it is stored
+use bytes::{Bytes, BytesMut};
+use constants::{
+  AUDIO_FILE_BYTERATE, PLAYLIST_GAPLESS_WINDOW_MS, PLAYLIST_TRANSITION_WARMUP_FRAMES,
+};
+use db::station::PlaylistTransition;
+use ffmpeg::{FfmpegConfig, Format, TransformError};
+
+/// encoder delay of lame, used when the encoded output has no lame tag
+const DEFAULT_ENCODER_DELAY: usize = 576 + 529;
+
+/// an mp3 frame found in a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInfo {
+  /// offset of the frame header in the buffer
+  pub offset: usize,
+  /// size of the frame in bytes, including the header
+  pub size: usize,
+  /// samples per channel of the frame
+  pub samples: usize,
+  /// the bit reservoir back pointer of the frame, 0 if the frame does not use data of previous frames
+  pub main_data_begin: usize,
+  /// true for the Xing / Info frame at the start of a file, it carries no audio
+  pub is_info: bool,
+}
+
+impl FrameInfo {
+  pub fn end(&self) -> usize {
+    self.offset + self.size
+  }
+}
+
+/// returns the complete mp3 frames of `data`
+/// leading bytes that are not part of a frame and a trailing incomplete frame are ignored
+pub fn frames(data: &[u8]) -> Vec<FrameInfo> {
+  let meta = match mp3::read_from_slice(data) {
+    Ok(meta) => meta,
+    Err(_) => return vec![],
+  };
+
+  meta
+    .frames
+    .iter()
+    .filter_map(|frame| {
+      let offset = frame.offset as usize;
+      let size = frame.size as usize;
+      let end = offset + size;
+      if end > data.len() {
+        return None;
+      }
+
+      let mpeg1 = frame.version == mp3::Version::MPEG1;
+      let samples = match frame.layer {
+        mp3::Layer::Layer1 => 384,
+        mp3::Layer::Layer2 => 1152,
+        _ => {
+          if mpeg1 {
+            1152
+          } else {
+            576
+          }
+        }
+      };
+
+      let side_info = offset + 4 + if frame.crc == mp3::CRC::Added { 2 } else { 0 };
+      let main_data_begin = match (frame.layer, data.get(side_info..side_info + 2)) {
+        (mp3::Layer::Layer3, Some(bytes)) => {
+          let v = u16::from_be_bytes([bytes[0], bytes[1]]);
+          if mpeg1 {
+            (v >> 7) as usize
+          } else {
+            (v >> 8) as usize
+          }
+        }
+        _ => 0,
+      };
+
+      let head = &data[offset..end.min(offset + 64)];
+      let is_info = head.windows(4).any(|w| w == b"Xing" || w == b"Info");
+
+      Some(FrameInfo {
+        offset,
+        size,
+        samples,
+        main_data_begin,
+        is_info,
+      })
+    })
+    .collect()
+}
+
+/// returns the index of the first frame of the last `window` samples of `frames`
+/// or None if the frames are shorter than the window
+pub fn tail_split(frames: &[FrameInfo], window: usize) -> Option<usize> {
+  let mut acc = 0;
+  for (i, frame) in frames.iter().enumerate().rev() {
+    if frame.is_info {
+      return None;
+    }
+
+    acc += frame.samples;
+    if acc >= window {
+      return Some(i);
+    }
+  }
+
+  None
+}
+
+/// returns the index of the first frame after the first `window` samples of `frames`
+/// the split is moved forward to a frame that does not use the bit reservoir of the previous frames,
+/// so it can be played after a re-encoded window without glitches
+/// returns None if the frames are shorter than the window
+pub fn head_split(frames: &[FrameInfo], window: usize) -> Option<usize> {
+  let mut acc = 0;
+  let mut min = None;
+  for (i, frame) in frames.iter().enumerate() {
+    if acc >= window {
+      if min.is_none() {
+        min = Some(i);
+      }
+
+      if frame.main_data_begin == 0 {
+        return Some(i);
+      }
+    }
+
+    if !frame.is_info {
+      acc += frame.samples;
+    }
+  }
+
+  match min {
+    Some(i) => Some(i),
+    None if acc >= window => Some(frames.len()),
+    None => None,
+  }
+}
+
+/// mixes the end of `tail` with the start of `head` over `crossfade` samples per channel
+/// with an equal power fade, with a 0 `crossfade` the samples are concatenated
+/// the samples are interleaved with `channels` channels
+pub fn mix(tail: &[i16], head: &[i16], crossfade: usize, channels: usize) -> Vec<i16> {
+  let crossfade = (crossfade * channels).min(tail.len()).min(head.len());
+  let crossfade = crossfade - crossfade % channels;
+
+  let tail_only = tail.len() - crossfade;
+
+  let mut out = Vec::with_capacity(tail.len() + head.len() - crossfade);
+  out.extend_from_slice(&tail[..tail_only]);
+
+  let frames = crossfade / channels;
+  for i in 0..frames {
+    let t = (i as f64 + 0.5) / frames as f64 * std::f64::consts::FRAC_PI_2;
+    let (fade_in, fade_out) = t.sin_cos();
+    for c in 0..channels {
+      let a = tail[tail_only + i * channels + c] as f64;
+      let b = head[i * channels + c] as f64;
+      let v = a * fade_out + b * fade_in;
+      out.push(v.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16);
+    }
+  }
+
+  out.extend_from_slice(&head[crossfade..]);
+  out
+}
+
+/// reads the encoder delay from the lame tag of an Info frame
+fn encoder_delay(data: &[u8], info: &FrameInfo) -> Option<usize> {
+  let frame = &data[info.offset..info.end()];
+  let tag = frame
+    .windows(4)
+    .position(|w| w == b"Xing" || w == b"Info")?;
+
+  // the lame tag follows 120 bytes of xing data, the delay is the first 12 bits at offset 21
+  let delay = frame.get(tag + 120 + 21..tag + 120 + 23)?;
+  Some(((delay[0] as usize) << 4) | ((delay[1] as usize) >> 4))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransitionError {
+  #[error("ffmpeg: {0:?}")]
+  Ffmpeg(TransformError),
+  #[error("not enough audio for the transition window")]
+  TooShort,
+}
+
+impl From<TransformError> for TransitionError {
+  fn from(e: TransformError) -> Self {
+    Self::Ffmpeg(e)
+  }
+}
+
+/// the result of a transition between two files
+#[derive(Debug, Clone)]
+pub struct Splice {
+  /// bytes of the end of the current file to stream before the transition as stored
+  pub tail: Bytes,
+  /// the re-encoded transition
+  pub mixed: Bytes,
+  /// bytes of the start of the next file to stream after the transition as stored
+  pub head: Bytes,
+}
+
+fn pcm_config() -> FfmpegConfig {
+  FfmpegConfig {
+    format: Format::PCM,
+    ..FfmpegConfig::default()
+  }
+}
+
+fn to_samples(pcm: &[u8]) -> Vec<i16> {
+  pcm
+    .chunks_exact(2)
+    .map(|b| i16::from_le_bytes([b[0], b[1]]))
+    .collect()
+}
+
+/// computes the transition between the end of a file (`tail`) and the start of the next one (`head`)
+///
+/// `tail` must be the last bytes of the current file and `head` the first bytes of the next file
+pub async fn splice(
+  tail: Bytes,
+  head: Bytes,
+  transition: PlaylistTransition,
+) -> Result<Splice, TransitionError> {
+  let freq = FfmpegConfig::FREQ as usize;
+  let channels = FfmpegConfig::CHANNELS as usize;
+
+  let window_ms = transition.crossfade_ms.max(PLAYLIST_GAPLESS_WINDOW_MS) as usize;
+  let window = window_ms * freq / 1000;
+  let crossfade = transition.crossfade_ms as usize * freq / 1000;
+
+  let tail_frames = frames(&tail);
+  let head_frames = frames(&head);
+
+  let s = tail_split(&tail_frames, window).ok_or(TransitionError::TooShort)?;
+  let k = head_split(&head_frames, window).ok_or(TransitionError::TooShort)?;
+
+  // the frames before the window are decoded to refill the bit reservoir and then discarded
+  let warmup_start = s.saturating_sub(PLAYLIST_TRANSITION_WARMUP_FRAMES);
+  let tail_end = tail_frames.last().map(FrameInfo::end).unwrap_or(tail.len());
+  let tail_pcm = ffmpeg::transform_bytes(
+    FfmpegConfig {
+      input_format: Some(Format::MP3),
+      ..pcm_config()
+    },
+    tail.slice(tail_frames[warmup_start].offset..tail_end),
+  )
+  .await?;
+  let tail_pcm = to_samples(&tail_pcm);
+
+  let window_samples: usize = tail_frames[s..].iter().map(|f| f.samples).sum::<usize>() * channels;
+  if tail_pcm.len() < window_samples {
+    return Err(TransitionError::TooShort);
+  }
+
+  // the head is decoded from the start of the file so the decoder drops the encoder delay
+  let head_end = head_frames
+    .get(k)
+    .map(|f| f.offset)
+    .unwrap_or_else(|| head_frames.last().map(FrameInfo::end).unwrap_or(0));
+  let head_pcm = ffmpeg::transform_bytes(
+    FfmpegConfig {
+      input_format: Some(Format::MP3),
+      ..pcm_config()
+    },
+    head.slice(..head_end),
+  )
+  .await?;
+  let head_pcm = to_samples(&head_pcm);
+
+  let lead_available = tail_pcm.len() - window_samples;
+  let mixed = mix(&tail_pcm[lead_available..], &head_pcm, crossfade, channels);
+
+  // the encoder delays its output, we feed it with the decoded audio before the window
+  // and drop the frames of the delay, so the first kept frame starts with the window
+  let spf = tail_frames[s].samples;
+  let lead_max = lead_available / channels;
+  let delay = DEFAULT_ENCODER_DELAY;
+  let lead = (delay.div_ceil(spf) * spf - delay).min(lead_max);
+
+  let mut pcm = BytesMut::with_capacity((lead * channels + mixed.len()) * 2);
+  for v in tail_pcm[lead_available - lead * channels..lead_available]
+    .iter()
+    .chain(mixed.iter())
+  {
+    pcm.extend_from_slice(&v.to_le_bytes());
+  }
+
+  let encoded = ffmpeg::transform_bytes(
+    FfmpegConfig {
+      input_format: Some(Format::PCM),
+      format: Format::MP3,
+      kbitrate: AUDIO_FILE_BYTERATE * 8 / 1000,
+      ..FfmpegConfig::default()
+    },
+    pcm.freeze(),
+  )
+  .await?;
+
+  let encoded_frames = frames(&encoded);
+  let delay = match encoded_frames.iter().find(|f| f.is_info) {
+    Some(info) => encoder_delay(&encoded, info).unwrap_or(delay),
+    None => delay,
+  };
+
+  let audio_frames = encoded_frames
+    .iter()
+    .filter(|f| !f.is_info)
+    .collect::<Vec<_>>();
+
+  let dropped = (delay + lead) / spf;
+  let keep = (mixed.len() / channels / spf).min(audio_frames.len().saturating_sub(dropped));
+  let mixed = match (audio_frames.get(dropped), keep) {
+    (Some(first), keep) if keep != 0 => {
+      let last = audio_frames[dropped + keep - 1];
+      Bytes::copy_from_slice(&encoded[first.offset..last.end()])
+    }
+    _ => return Err(TransitionError::TooShort),
+  };
+
+  Ok(Splice {
+    tail: tail.slice(..tail_frames[s].offset),
+    mixed,
+    head: head.slice(head_end..),
+  })
+}
+
+/// returns true if the transition should be applied to a file of `duration_ms`
+pub fn is_applicable(transition: &PlaylistTransition, duration_ms: f64) -> bool {
+  transition.is_enabled()
+    && duration_ms > (transition.crossfade_ms.max(PLAYLIST_GAPLESS_WINDOW_MS) * 2) as f64
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // mpeg 1 layer 3, 128 kbps, 44100 hz, no crc, no padding
+  const HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x64];
+  const FRAME_SIZE: usize = 417;
+
+  fn frame(main_data_begin: u16) -> Vec<u8> {
+    let mut frame = vec![0u8; FRAME_SIZE];
+    frame[..4].copy_from_slice(&HEADER);
+    frame[4..6].copy_from_slice(&(main_data_begin << 7).to_be_bytes());
+    frame
+  }
+
+  fn frames_with(main_data_begins: &[u16]) -> Vec<u8> {
+    main_data_begins.iter().flat_map(|m| frame(*m)).collect()
+  }
+
+  #[test]
+  fn frames_parses_complete_frames() {
+    let mut data = vec![1, 2, 3];
+    data.extend(frames_with(&[0, 10, 0]));
+    data.extend(&frame(0)[..100]);
+
+    let list = frames(&data);
+    assert_eq!(list.len(), 3);
+    assert_eq!(list[0].offset, 3);
+    assert_eq!(list[0].size, FRAME_SIZE);
+    assert_eq!(list[0].samples, 1152);
+    assert_eq!(list[1].main_data_begin, 10);
+    assert_eq!(list[2].end(), 3 + FRAME_SIZE * 3);
+  }
+
+  #[test]
+  fn tail_split_covers_the_window() {
+    let list = frames(&frames_with(&[0, 0, 0, 0]));
+    assert_eq!(tail_split(&list, 1152), Some(3));
+    assert_eq!(tail_split(&list, 1153), Some(2));
+    assert_eq!(tail_split(&list, 1152 * 4), Some(0));
+    assert_eq!(tail_split(&list, 1152 * 4 + 1), None);
+  }
+
+  #[test]
+  fn head_split_avoids_bit_reservoir() {
+    let list = frames(&frames_with(&[0, 0, 5, 3, 0, 0]));
+    assert_eq!(head_split(&list, 1152), Some(1));
+    assert_eq!(head_split(&list, 1153), Some(4));
+    assert_eq!(head_split(&list, 1152 * 6), Some(6));
+    assert_eq!(head_split(&list, 1152 * 6 + 1), None);
+
+    let list = frames(&frames_with(&[0, 0, 5, 3]));
+    assert_eq!(head_split(&list, 1153), Some(2));
+  }
+
+  #[test]
+  fn mix_concatenates_without_crossfade() {
+    let out = mix(&[1, 2, 3, 4], &[5, 6], 0, 2);
+    assert_eq!(out, vec![1, 2, 3, 4, 5, 6]);
+  }
+
+  #[test]
+  fn mix_overlaps_with_crossfade() {
+    let tail = vec![1000i16; 8];
+    let head = vec![-1000i16; 8];
+    let out = mix(&tail, &head, 2, 2);
+    assert_eq!(out.len(), 12);
+    assert_eq!(&out[..4], &[1000; 4]);
+    assert_eq!(&out[8..], &[-1000; 4]);
+    // equal power fade, the first overlapped sample is still mostly the tail
+    assert!(out[4] > 0);
+    assert!(out[6] < 0);
+  }
+}
@@ -1,11 +1,15 @@
 pub mod channel;
+pub mod dead_air;
 pub mod drop;
 pub mod handle;
 pub mod health;
+pub mod now_playing;
+pub mod relay_auth;
+pub mod rendition;
+pub mod schedule;
 
 use constants::MEDIA_LOCK_TIMEOUT_SECS;
 use db::{
-  audio_file::AudioFile,
   probe::{Probe, ProbeResult},
   run_transaction,
   station::{OwnerDeploymentInfo, Station},
@@ -13,6 +17,7 @@ use db::{
 };
 use drop::{MapEntryRelease, OwnerDeploymentDropper};
 use drop_tracer::DropTracer;
+use mailer::send::Mailer;
 use mongodb::bson::doc;
 use mongodb::options::FindOneAndUpdateOptions;
 use parking_lot::Mutex;
@@ -21,11 +26,15 @@ use shutdown::Shutdown;
 use std::collections::HashMap;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use time::OffsetDateTime;
+use tokio::sync::{watch, Mutex as AsyncMutex, OwnedMutexGuard};
 
 use channel::{Receiver, Sender};
 use handle::internal_relay::GetInternalRelayError;
+use handle::playlist::PlaylistSelection;
 use handle::{get_internal_relay_source, run_external_relay_source, run_playlist_source};
+use relay_auth::RelayAuth;
+use schedule::StationSource;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum ProbeCodec {
@@ -36,6 +45,21 @@ pub enum ProbeCodec {
 #[derive(Debug)]
 pub struct Handle {
   sender: Sender,
+  /// set by playlist_restart while the station is live
+  /// the playlist fallback will start from the top when the live source ends
+  restart_playlist: bool,
+  /// the playlist or external relay source started by this deployment
+  /// checked against the station schedule to switch the source at slot boundaries
+  source: Option<StationSource>,
+  /// the station dj connected to a live source, None for the station source password
+  dj_id: Option<String>,
+  /// the end of the allowed time of the dj connected to a live source, watched by the live source
+  /// to disconnect the dj when it's reached
+  dj_allowed_until: Option<watch::Sender<Option<OffsetDateTime>>>,
+  /// the media session of a live source
+  /// the dead air failover of the source opens a newer session for the station,
+  /// so the live session can't be resolved as the current session of the station
+  media_session_id: Option<String>,
 }
 
 impl Drop for Handle {
@@ -72,7 +96,55 @@ impl Info {
 impl Handle {
   #[inline(always)]
   pub fn new(sender: Sender) -> Self {
-    Self { sender }
+    Self {
+      sender,
+      restart_playlist: false,
+      source: None,
+      dj_id: None,
+      dj_allowed_until: None,
+      media_session_id: None,
+    }
+  }
+
+  #[inline(always)]
+  pub fn live(
+    sender: Sender,
+    media_session_id: String,
+    dj_id: Option<String>,
+    dj_allowed_until: watch::Sender<Option<OffsetDateTime>>,
+  ) -> Self {
+    Self {
+      sender,
+      restart_playlist: false,
+      source: None,
+      dj_id,
+      dj_allowed_until: Some(dj_allowed_until),
+      media_session_id: Some(media_session_id),
+    }
+  }
+
+  #[inline(always)]
+  pub fn with_source(sender: Sender, source: StationSource) -> Self {
+    Self {
+      sender,
+      restart_playlist: false,
+      source: Some(source),
+      dj_id: None,
+      dj_allowed_until: None,
+      media_session_id: None,
+    }
+  }
+
+  /// creates the sender for a new source of the station that preempts this one
+  /// if the content type of the new source is the same as this one,
+  /// the new sender broadcasts to the same channel and the current listeners are kept connected
+  /// otherwise a new channel is created and the current listeners will need to resubscribe
+  pub fn handover(&self, info: Info) -> Sender {
+    if info.content_type == self.sender.info.content_type {
+      self.sender.handover(info)
+    } else {
+      Sender::new(self.sender.station_id.clone(), info)
+    }
   }
 
   #[allow(clippy::bool_comparison)]
@@ -92,6 +164,23 @@ impl Handle {
   pub fn info(&self) -> &Info {
     &self.sender.info
   }
+
+  #[inline(always)]
+  pub fn dj_id(&self) -> Option<&str> {
+    self.dj_id.as_deref()
+  }
+
+  /// moves the disconnection of the dj connected to a live source, None cancels it
+  pub fn set_dj_allowed_until(&self, allowed_until: Option<OffsetDateTime>) {
+    if let Some(sender) = &self.dj_allowed_until {
+      sender.send_replace(allowed_until);
+    }
+  }
+
+  #[inline(always)]
+  pub fn media_session_id(&self) -> Option<&str> {
+    self.media_session_id.as_deref()
+  }
 }
 
 type Map = HashMap<String, Arc<AsyncMutex<Option<Handle>>>>;
@@ -99,6 +188,8 @@ type Map = HashMap<String, Arc<AsyncMutex<Option<Handle>>>>;
 #[derive(Debug, Clone)]
 pub struct MediaSessionMap {
   deployment_id: String,
+  relay_auth: RelayAuth,
+  mailer: Mailer,
   drop_tracer: DropTracer,
   shutdown: Shutdown,
   map: Arc<Mutex<Map>>,
@@ -119,15 +210,32 @@ pub enum SubscribeError {
 }
 
 impl MediaSessionMap {
-  pub fn new(deployment_id: String, drop_tracer: DropTracer, shutdown: Shutdown) -> Self {
+  pub fn new(
+    deployment_id: String,
+    relay_auth: RelayAuth,
+    mailer: Mailer,
+    drop_tracer: DropTracer,
+    shutdown: Shutdown,
+  ) -> Self {
     Self {
       deployment_id,
+      relay_auth,
+      mailer,
       drop_tracer,
       shutdown,
       map: Arc::new(Mutex::new(Map::new())),
     }
   }
 
+  pub fn relay_auth(&self) -> &RelayAuth {
+    &self.relay_auth
+  }
+
+  /// the mailer of the dead air notifications
+  pub fn mailer(&self) -> &Mailer {
+    &self.mailer
+  }
+
   pub async fn lock(&self, station_id: &str) -> OwnedMutexGuard<Option<Handle>> {
     let item = {
       let mut lock = self.map.lock();
@@ -145,6 +253,22 @@ impl MediaSessionMap {
       })
   }
 
+  /// the media session of the live source of the station, None if it is not connected to this deployment
+  pub async fn live_media_session_id(&self, station_id: &str) -> Option<String> {
+    let entry = { self.map.lock().get(station_id)?.clone() };
+
+    let timeout = tokio::time::Duration::from_secs(MEDIA_LOCK_TIMEOUT_SECS);
+    let handle = tokio::time::timeout(timeout, entry.lock())
+      .await
+      .unwrap_or_else(|_| {
+        panic!(
+          "media lock timeout elapsed for station {station_id} on call to live_media_session_id()"
+        )
+      });
+
+    handle.as_ref()?.media_session_id().map(String::from)
+  }
+
   pub async fn terminate(&self, station_id: &str) -> Option<Handle> {
     let entry = { self.map.lock().remove(station_id)? };
 
@@ -184,14 +308,148 @@ impl MediaSessionMap {
     }
   }
 
+  /// releases the handle of a task whose source has ended
+  ///
+  /// if the task is a live source with listeners, the station fallback (external relay or playlist)
+  /// takes over its channel so the listeners are not disconnected,
+  /// otherwise the handle is released like with terminate_task
+  pub async fn release_task(&self, station_id: &str, task_id: &str) -> Option<Handle> {
+    let entry = {
+      let lock = self.map.lock();
+      lock.get(station_id)?.clone()
+    };
+
+    let timeout = tokio::time::Duration::from_secs(MEDIA_LOCK_TIMEOUT_SECS);
+    let mut lock = tokio::time::timeout(timeout, entry.lock())
+      .await
+      .unwrap_or_else(|_| {
+        panic!("media lock timeout elapsed for station {station_id}, task {task_id} on call to release_task()")
+      });
+
+    let handle = match &*lock {
+      Some(item) if item.info().task_id == task_id => lock.take()?,
+      _ => return None,
+    };
+
+    if handle.info().kind == Kind::Live
+      && handle.sender.receiver_count() != 0
+      && self.shutdown.is_open()
+    {
+      match self.start_fallback(station_id, &handle).await {
+        Ok(None) => {}
+        Ok(Some(fallback)) => {
+          log::info!(
+            target: "media",
+            "live source for station {station_id} ended, {:?} fallback took over the channel",
+            fallback.info().kind
+          );
+          *lock = Some(fallback);
+        }
+        Err(e) => {
+          log::warn!(
+            target: "media",
+            "error starting fallback for station {station_id} after live source ended: {e} => {e:?}"
+          );
+        }
+      }
+    }
+
+    Some(handle)
+  }
+
+  /// starts the external relay or the playlist of the station on the channel of a live source that has ended
+  /// returns None if the station has no fallback source, if the content type of the live source is not
+  /// the one of the fallback or if the station is now owned by another deployment
+  async fn start_fallback(
+    &self,
+    station_id: &str,
+    live: &Handle,
+  ) -> Result<Option<Handle>, SubscribeError> {
+    let content_type = "audio/mpeg".to_string();
+    if live.info().content_type != content_type {
+      return Ok(None);
+    }
+
+    let station = match Station::get_by_id(station_id).await? {
+      None => return Ok(None),
+      Some(station) => station,
+    };
+
+    let source = match StationSource::resolve(&station).await? {
+      StationSource::ExternalRelayRedirect { .. } => return Ok(None),
+      source => source,
+    };
+
+    if !source.is_playable(station_id).await? {
+      return Ok(None);
+    }
+
+    let codec_info = codec_info_for_source(&station, &source).await?;
+
+    let task_id = Station::random_owner_task_id();
+
+    let owner_deployment_info = OwnerDeploymentInfo {
+      content_type: content_type.clone(),
+      deployment_id: self.deployment_id.clone(),
+      task_id: task_id.clone(),
+      health_checked_at: Some(DateTime::now()),
+    };
+
+    // the live source could have taken the ownership of the station from a task of this deployment
+    // and its dropper could have released it already, so we take it if it is ours or nobody's
+    const KEY_OWNER_DEPLOYMENT_ID: &str = db::key!(
+      Station::KEY_OWNER_DEPLOYMENT_INFO,
+      OwnerDeploymentInfo::KEY_DEPLOYMENT_ID
+    );
+
+    let filter = doc! {
+      Station::KEY_ID: station_id,
+      "$or": [
+        { Station::KEY_OWNER_DEPLOYMENT_INFO: null },
+        { KEY_OWNER_DEPLOYMENT_ID: &self.deployment_id },
+      ],
+    };
+
+    let update = doc! {
+      "$set": { Station::KEY_OWNER_DEPLOYMENT_INFO: owner_deployment_info }
+    };
+
+    let r = Station::cl().update_one(filter, update, None).await?;
+    if r.matched_count == 0 {
+      return Ok(None);
+    }
+
+    let owner_deployment_dropper = OwnerDeploymentDropper::new(
+      station_id.to_string(),
+      task_id.clone(),
+      self.drop_tracer.token(),
+    );
+
+    let sender = live.handover(Info::new(source.kind(), task_id.clone(), content_type));
+
+    self.spawn_station_source(
+      station_id,
+      task_id,
+      sender.clone(),
+      source.clone(),
+      codec_info,
+      !live.restart_playlist,
+      owner_deployment_dropper,
+    );
+
+    Ok(Some(Handle::with_source(sender, source)))
+  }
+
   pub async fn playlist_restart(&self, station_id: &str) -> Result<(), PlaylistRestartError> {
     let mut lock = self.lock(station_id).await;
-    // TODO: allow to restart playlist even if it's live
-    // so when live transmission end the playlist starts from the top
-    match &*lock {
+    match &mut *lock {
       None => {}
       Some(handle) => match handle.info().kind {
-        Kind::Live => return Err(PlaylistRestartError::Live),
+        // the playlist will start from the top when the live source ends
+        Kind::Live => {
+          handle.restart_playlist = true;
+          return Ok(());
+        }
         Kind::ExternalRelay => return Err(PlaylistRestartError::ExternalRelay),
         Kind::InternalRelay => return Err(PlaylistRestartError::InternalRelay),
         Kind::Playlist => {}
@@ -225,6 +483,12 @@ impl MediaSessionMap {
 
       tx_try!(Station::update_by_id_with_session(&station.id, update, &mut session).await);
 
+      // restart the playlist of the active schedule slot if any
+      let selection = match tx_try!(StationSource::resolve(&station).await) {
+        StationSource::Playlist(selection) => selection,
+        _ => PlaylistSelection::for_station(&station),
+      };
+
       let new_sender = Sender::new(
         station_id.to_string(),
         Info::new(
@@ -234,7 +498,10 @@ impl MediaSessionMap {
         ),
       );
 
-      let new_handle = Handle::new(new_sender.clone());
+      let new_handle = Handle::with_source(
+        new_sender.clone(),
+        StationSource::Playlist(selection.clone()),
+      );
       *lock = Some(new_handle);
 
       let map_entry_release = MapEntryRelease::new(
@@ -260,6 +527,8 @@ impl MediaSessionMap {
             task_id,
             station.id,
             false,
+            selection,
+            None,
             drop_tracer,
             shutdown
           ).await;
@@ -277,92 +546,25 @@ impl MediaSessionMap {
     match &*lock {
       Some(handle) => Ok(handle.sender.subscribe()),
       None => {
-        // external relay redirect
-        let station: Station;
-        match Station::get_by_id(station_id).await? {
+        let station = match Station::get_by_id(station_id).await? {
           None => return Err(SubscribeError::StationNotFound(station_id.to_string())),
-          Some(s) => {
-            station = s;
-            if let Some(url) = &station.external_relay_url {
-              if station.external_relay_redirect {
-                return Err(SubscribeError::ExternalRelayRedirect(url.clone()));
-              }
-            }
-          }
-        }
-
-        let probe_result: Option<(ProbeCodec, usize)>;
-
-        if station.external_relay_url.is_some() {
-          let filter = doc! {
-            ProbeResult::KEY_ENUM_TAG: ProbeResult::KEY_ENUM_VARIANT_OK,
-            Probe::KEY_STATION_ID: station_id,
-          };
-
-          let sort = doc! {
-            Probe::KEY_CREATED_AT: -1
-          };
-
-          let options = mongodb::options::FindOneOptions::builder()
-            .sort(sort)
-            .build();
-
-          let last_probe = Probe::cl().find_one(filter, options).await?;
-
-          match last_probe {
-            None => {
-              probe_result = None;
-            }
-
-            Some(doc) => {
-              let mut streams = doc
-                .streams()
-                .into_iter()
-                .filter_map(|(codec, bitrate)| match codec?.as_ref() {
-                  "mp3" => Some((ProbeCodec::Mp3, bitrate)),
-                  "aac" => Some((ProbeCodec::Aac, bitrate)),
-                  _ => None,
-                })
-                .collect::<Vec<(ProbeCodec, Option<usize>)>>();
-
-              streams.sort_by(|(_, br1), (_, br2)| {
-                use std::cmp::Ordering;
-
-                match (br1, br2) {
-                  (Some(br1), Some(br2)) => br2.cmp(br1),
-                  (Some(_), None) => Ordering::Less,
-                  (None, Some(_)) => Ordering::Greater,
-                  (None, None) => Ordering::Equal,
-                }
-              });
+          Some(station) => station,
+        };
 
-              let first = streams.first();
+        // the source of the active schedule slot or the station default
+        let source = StationSource::resolve(&station).await?;
 
-              match first {
-                None => probe_result = None,
-                Some((codec, br)) => {
-                  let bitrate = br.unwrap_or(128_000).max(64_000).min(320_000);
-                  probe_result = Some((*codec, bitrate));
-                }
-              }
-            }
-          }
-        } else {
-          probe_result = None;
+        // external relay redirect
+        if let StationSource::ExternalRelayRedirect { url } = source {
+          return Err(SubscribeError::ExternalRelayRedirect(url));
         }
 
-        let task_id = Station::random_owner_task_id();
+        let probe_result = codec_info_for_source(&station, &source).await?;
 
-        let map_entry_release =
-          MapEntryRelease::new(station_id.to_string(), task_id.clone(), self.clone());
+        let task_id = Station::random_owner_task_id();
 
-        let content_type = match probe_result {
-          None => "audio/mpeg".to_string(),
-          Some((codec, _)) => match codec {
-            ProbeCodec::Mp3 => "audio/mpeg".to_string(),
-            ProbeCodec::Aac => "audio/aac".to_string(),
-          },
-        };
+        // external relays always output mp3 so they can fail over to other urls or the playlist
+        let content_type = "audio/mpeg".to_string();
 
         let owner_deployment_info = OwnerDeploymentInfo {
           content_type: content_type.clone(),
@@ -399,7 +601,7 @@ impl MediaSessionMap {
         };
 
         let sender: Sender;
-        let info: Info;
+        let handle: Handle;
         // this station is the document BEFORE the update
         // that means that if owner_deployment_info is null it was taken by this task
         // otherwise it was already taken by another task
@@ -411,89 +613,43 @@ impl MediaSessionMap {
               self.drop_tracer.token(),
             );
 
-            match station.external_relay_url {
-              // 1) external relay
-              Some(url) => {
-                info = Info::new(Kind::ExternalRelay, task_id.clone(), content_type);
-                sender = Sender::new(station_id.to_string(), info);
-
-                {
-                  let sender = sender.clone();
-                  let deployment_id = self.deployment_id.clone();
-                  let task_id = task_id.clone();
-                  let station_id = station_id.to_string();
-                  let drop_tracer = self.drop_tracer.clone();
-                  let shutdown = self.shutdown.clone();
-                  let codec_info = probe_result;
-                  tokio::spawn(async move {
-                    let _ = run_external_relay_source(
-                      sender,
-                      deployment_id,
-                      task_id,
-                      station_id,
-                      url,
-                      codec_info,
-                      drop_tracer,
-                      shutdown,
-                    )
-                    .await
-                    .unwrap();
-
-                    drop(owner_deployment_dropper);
-                    drop(map_entry_release);
-                  });
-                };
-              }
-
-              // 2) playlist
-              None => {
-                let file_filter = doc! { AudioFile::KEY_STATION_ID: station_id };
-                let has_playlist_files = AudioFile::exists(file_filter).await?;
-                if !has_playlist_files {
-                  return Err(SubscribeError::PlaylistEmpty);
-                }
-
-                info = Info::new(Kind::Playlist, task_id.clone(), "audio/mpeg".to_string());
-                sender = Sender::new(station_id.to_string(), info);
-
-                {
-                  let sender = sender.clone();
-                  let deployment_id = self.deployment_id.clone();
-                  let task_id = task_id.clone();
-                  let station_id = station_id.to_string();
-                  let drop_tracer = self.drop_tracer.clone();
-                  let shutdown = self.shutdown.clone();
-
-                  tokio::spawn(async move {
-                    let _ = run_playlist_source(
-                      sender,
-                      deployment_id,
-                      task_id,
-                      station_id,
-                      true,
-                      drop_tracer,
-                      shutdown,
-                    )
-                    .await;
-                    drop(owner_deployment_dropper);
-                    drop(map_entry_release);
-                  });
-                };
-              }
+            // 1) external relay
+            // 2) playlist
+            if !source.is_playable(station_id).await? {
+              return Err(SubscribeError::PlaylistEmpty);
             }
+
+            let info = Info::new(source.kind(), task_id.clone(), content_type);
+            sender = Sender::new(station_id.to_string(), info);
+
+            self.spawn_station_source(
+              station_id,
+              task_id,
+              sender.clone(),
+              source.clone(),
+              probe_result,
+              true,
+              owner_deployment_dropper,
+            );
+
+            handle = Handle::with_source(sender.clone(), source);
           }
 
           Some(owner_info) => {
-            info = Info::new(
+            let info = Info::new(
               Kind::InternalRelay,
               task_id.clone(),
               owner_info.content_type.clone(),
             );
             sender = Sender::new(station_id.to_string(), info);
 
+            let map_entry_release =
+              MapEntryRelease::new(station_id.to_string(), task_id.clone(), self.clone());
+
             {
               let sender = sender.clone();
               let deployment_id = self.deployment_id.clone();
+              let relay_auth = self.relay_auth.clone();
               let task_id = task_id.clone();
               let station_id = station_id.to_string();
               let drop_tracer = self.drop_tracer.clone();
@@ -502,6 +658,7 @@ impl MediaSessionMap {
               let spawn = get_internal_relay_source(
                 sender,
                 deployment_id,
+                relay_auth,
                 task_id,
                 station_id,
                 owner_info.clone(),
@@ -515,17 +672,148 @@ impl MediaSessionMap {
                 drop(map_entry_release);
               });
             };
+
+            handle = Handle::new(sender.clone());
           }
         };
 
         let receiver = sender.subscribe();
-        let handle = Handle::new(sender);
         *lock = Some(handle);
 
         Ok(receiver)
       }
     }
   }
+
+  /// spawns the external relay or playlist source of a station on `sender`
+  /// the map entry and the station ownership of `task_id` are released when the source ends
+  #[allow(clippy::too_many_arguments)]
+  fn spawn_station_source(
+    &self,
+    station_id: &str,
+    task_id: String,
+    sender: Sender,
+    source: StationSource,
+    codec_info: Option<(ProbeCodec, usize)>,
+    resume: bool,
+    owner_deployment_dropper: OwnerDeploymentDropper,
+  ) {
+    let map_entry_release =
+      MapEntryRelease::new(station_id.to_string(), task_id.clone(), self.clone());
+
+    let deployment_id = self.deployment_id.clone();
+    let station_id = station_id.to_string();
+    let drop_tracer = self.drop_tracer.clone();
+    let shutdown = self.shutdown.clone();
+    let mailer = self.mailer.clone();
+
+    tokio::spawn(async move {
+      match source {
+        StationSource::ExternalRelay { urls } => {
+          let _ = run_external_relay_source(
+            sender,
+            deployment_id,
+            task_id,
+            station_id,
+            urls,
+            codec_info,
+            mailer,
+            drop_tracer,
+            shutdown,
+          )
+          .await;
+        }
+
+        StationSource::Playlist(selection) => {
+          let _ = run_playlist_source(
+            sender,
+            deployment_id,
+            task_id,
+            station_id,
+            resume,
+            selection,
+            None,
+            drop_tracer,
+            shutdown,
+          )
+          .await;
+        }
+
+        // redirects are resolved before any source is started
+        StationSource::ExternalRelayRedirect { .. } => {}
+      }
+
+      drop(owner_deployment_dropper);
+      drop(map_entry_release);
+    });
+  }
+}
+
+/// the codec and bitrate of the station external relay if `source` relays it
+/// the urls of schedule slots and the backup urls are not probed
+async fn codec_info_for_source(
+  station: &Station,
+  source: &StationSource,
+) -> Result<Option<(ProbeCodec, usize)>, mongodb::error::Error> {
+  match source {
+    StationSource::ExternalRelay { urls }
+      if urls.first() == station.external_relay_url.as_ref() =>
+    {
+      last_probe_codec(&station.id).await
+    }
+    _ => Ok(None),
+  }
+}
+
+/// the codec and bitrate of the last successful probe of the station external relay
+async fn last_probe_codec(
+  station_id: &str,
+) -> Result<Option<(ProbeCodec, usize)>, mongodb::error::Error> {
+  let filter = doc! {
+    ProbeResult::KEY_ENUM_TAG: ProbeResult::KEY_ENUM_VARIANT_OK,
+    Probe::KEY_STATION_ID: station_id,
+  };
+
+  let sort = doc! {
+    Probe::KEY_CREATED_AT: -1
+  };
+
+  let options = mongodb::options::FindOneOptions::builder()
+    .sort(sort)
+    .build();
+
+  let doc = match Probe::cl().find_one(filter, options).await? {
+    None => return Ok(None),
+    Some(doc) => doc,
+  };
+
+  let mut streams = doc
+    .streams()
+    .into_iter()
+    .filter_map(|(codec, bitrate)| match codec?.as_ref() {
+      "mp3" => Some((ProbeCodec::Mp3, bitrate)),
+      "aac" => Some((ProbeCodec::Aac, bitrate)),
+      _ => None,
+    })
+    .collect::<Vec<(ProbeCodec, Option<usize>)>>();
+
+  streams.sort_by(|(_, br1), (_, br2)| {
+    use std::cmp::Ordering;
+
+    match (br1, br2) {
+      (Some(br1), Some(br2)) => br2.cmp(br1),
+      (Some(_), None) => Ordering::Less,
+      (None, Some(_)) => Ordering::Greater,
+      (None, None) => Ordering::Equal,
+    }
+  });
+
+  let probe_codec = streams.first().map(|(codec, br)| {
+    let bitrate = br.unwrap_or(128_000).max(64_000).min(320_000);
+    (*codec, bitrate)
+  });
+
+  Ok(probe_codec)
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -534,8 +822,6 @@ pub enum PlaylistRestartError {
   Db(#[from] mongodb::error::Error),
   #[error("station not found")]
   NotFound,
-  #[error("live")]
-  Live,
   #[error("external relay")]
   ExternalRelay,
   #[error("internal relay")]
@@ -0,0 +1,54 @@
+use std::convert::Infallible;
+use std::future::Future;
+
+use db::{media_session::MediaSession, Model};
+
+use crate::channel::Sender;
+
+async fn run_now_playing_sync_interval<F, Fut>(sender: &Sender, mut get: F) -> Infallible
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<Option<MediaSession>, mongodb::error::Error>>,
+{
+  let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+    constants::MEDIA_SESSION_NOW_PLAYING_SYNC_INTERVAL_SECS,
+  ));
+
+  loop {
+    interval.tick().await;
+
+    match get().await {
+      Ok(None) => sender.set_now_playing(None),
+      Ok(Some(media_session)) => sender.set_now_playing(media_session.now_playing),
+      Err(e) => {
+        // we don't cancel the media session on now playing errors
+        // the health check will do it if the database is down
+        log::warn!(
+          target: "media-now-playing",
+          "error getting now playing info for station {}: {} => {:?}",
+          sender.station_id,
+          e,
+          e,
+        );
+      }
+    }
+  }
+}
+
+/// keeps the channel now playing info in sync with the $media_sessions document
+/// used for live media sessions where the now playing info is updated from the metadata endpoint
+pub async fn run_now_playing_sync_interval_for_media_session(
+  sender: &Sender,
+  media_session_id: &str,
+) -> Infallible {
+  run_now_playing_sync_interval(sender, || MediaSession::get_by_id(media_session_id)).await
+}
+
+/// keeps the channel now playing info in sync with the current media session of the station
+/// used for internal relays, where the media session runs in other deployment
+pub async fn run_now_playing_sync_interval_for_station(
+  sender: &Sender,
+  station_id: &str,
+) -> Infallible {
+  run_now_playing_sync_interval(sender, || MediaSession::get_current_for_station(station_id)).await
+}
@@ -0,0 +1,314 @@
+use constants::{
+  HEADER_RELAY_EXPIRES, HEADER_RELAY_NONCE, HEADER_RELAY_SIGNATURE, HEADER_RELAY_SOURCE_DEPLOYMENT,
+  RELAY_SIGNATURE_CLOCK_SKEW_SECS, RELAY_SIGNATURE_TTL_SECS,
+};
+use hyper::http::HeaderMap;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, thiserror::Error)]
+pub enum RelayAuthError {
+  #[error("relay secret not configured")]
+  NotConfigured,
+  #[error("relay signature headers missing or malformed")]
+  Missing,
+  #[error("relay signature expired")]
+  Expired,
+  #[error("relay signature invalid")]
+  InvalidSignature,
+  #[error("relay nonce already used")]
+  Replayed,
+}
+
+impl RelayAuthError {
+  /// reported to the requesting deployment in the relay rejection code header
+  pub fn code(&self) -> &'static str {
+    match self {
+      RelayAuthError::NotConfigured => "RELAY_AUTH_NOT_CONFIGURED",
+      RelayAuthError::Missing => "RELAY_AUTH_MISSING",
+      RelayAuthError::Expired => "RELAY_AUTH_EXPIRED",
+      RelayAuthError::InvalidSignature => "RELAY_AUTH_INVALID_SIGNATURE",
+      RelayAuthError::Replayed => "RELAY_AUTH_REPLAYED",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RelaySignature {
+  pub source_deployment_id: String,
+  pub expires: u64,
+  pub nonce: String,
+  pub signature: String,
+}
+
+impl RelaySignature {
+  pub fn from_headers(headers: &HeaderMap) -> Result<Self, RelayAuthError> {
+    let get = |name: &str| {
+      headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(ToString::to_string)
+        .ok_or(RelayAuthError::Missing)
+    };
+
+    Ok(Self {
+      source_deployment_id: get(HEADER_RELAY_SOURCE_DEPLOYMENT)?,
+      expires: get(HEADER_RELAY_EXPIRES)?
+        .parse()
+        .map_err(|_| RelayAuthError::Missing)?,
+      nonce: get(HEADER_RELAY_NONCE)?,
+      signature: get(HEADER_RELAY_SIGNATURE)?,
+    })
+  }
+
+  pub fn headers(&self) -> [(&'static str, String); 4] {
+    [
+      (
+        HEADER_RELAY_SOURCE_DEPLOYMENT,
+        self.source_deployment_id.clone(),
+      ),
+      (HEADER_RELAY_EXPIRES, self.expires.to_string()),
+      (HEADER_RELAY_NONCE, self.nonce.clone()),
+      (HEADER_RELAY_SIGNATURE, self.signature.clone()),
+    ]
+  }
+}
+
+/// signs and verifies the internal relay requests between the deployments of a cluster
+///
+/// signatures are bound to the station and to both deployments, they expire after
+/// [`RELAY_SIGNATURE_TTL_SECS`] and each nonce is accepted only once
+#[derive(Clone)]
+pub struct RelayAuth {
+  secret: Option<Arc<str>>,
+  nonces: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl std::fmt::Debug for RelayAuth {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("RelayAuth")
+      .field("configured", &self.is_configured())
+      .finish()
+  }
+}
+
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+fn message(
+  station_id: &str,
+  source_deployment_id: &str,
+  target_deployment_id: &str,
+  expires: u64,
+  nonce: &str,
+) -> String {
+  format!("v1\n{station_id}\n{source_deployment_id}\n{target_deployment_id}\n{expires}\n{nonce}")
+}
+
+impl RelayAuth {
+  pub fn new(secret: Option<&str>) -> Self {
+    Self {
+      secret: secret.map(Arc::from),
+      nonces: Default::default(),
+    }
+  }
+
+  pub fn is_configured(&self) -> bool {
+    self.secret.is_some()
+  }
+
+  pub fn sign(
+    &self,
+    station_id: &str,
+    source_deployment_id: &str,
+    target_deployment_id: &str,
+  ) -> Result<RelaySignature, RelayAuthError> {
+    self.sign_at(
+      station_id,
+      source_deployment_id,
+      target_deployment_id,
+      now(),
+    )
+  }
+
+  fn sign_at(
+    &self,
+    station_id: &str,
+    source_deployment_id: &str,
+    target_deployment_id: &str,
+    now: u64,
+  ) -> Result<RelaySignature, RelayAuthError> {
+    let secret = self.secret.as_ref().ok_or(RelayAuthError::NotConfigured)?;
+    let expires = now + RELAY_SIGNATURE_TTL_SECS;
+    let nonce = format!("{:032x}", rand::random::<u128>());
+    let signature = crypt::hmac_sha256(
+      secret.as_bytes(),
+      message(
+        station_id,
+        source_deployment_id,
+        target_deployment_id,
+        expires,
+        &nonce,
+      ),
+    );
+
+    Ok(RelaySignature {
+      source_deployment_id: source_deployment_id.to_string(),
+      expires,
+      nonce,
+      signature,
+    })
+  }
+
+  pub fn verify(
+    &self,
+    station_id: &str,
+    target_deployment_id: &str,
+    signature: &RelaySignature,
+  ) -> Result<(), RelayAuthError> {
+    self.verify_at(station_id, target_deployment_id, signature, now())
+  }
+
+  fn verify_at(
+    &self,
+    station_id: &str,
+    target_deployment_id: &str,
+    signature: &RelaySignature,
+    now: u64,
+  ) -> Result<(), RelayAuthError> {
+    let secret = self.secret.as_ref().ok_or(RelayAuthError::NotConfigured)?;
+
+    if signature.expires + RELAY_SIGNATURE_CLOCK_SKEW_SECS < now
+      || signature.expires > now + RELAY_SIGNATURE_TTL_SECS + RELAY_SIGNATURE_CLOCK_SKEW_SECS
+    {
+      return Err(RelayAuthError::Expired);
+    }
+
+    let valid = crypt::verify_hmac_sha256(
+      secret.as_bytes(),
+      message(
+        station_id,
+        &signature.source_deployment_id,
+        target_deployment_id,
+        signature.expires,
+        &signature.nonce,
+      ),
+      &signature.signature,
+    );
+
+    if !valid {
+      return Err(RelayAuthError::InvalidSignature);
+    }
+
+    let mut nonces = self.nonces.lock();
+    nonces.retain(|_, expires| *expires + RELAY_SIGNATURE_CLOCK_SKEW_SECS >= now);
+    if nonces.contains_key(&signature.nonce) {
+      return Err(RelayAuthError::Replayed);
+    }
+    nonces.insert(signature.nonce.clone(), signature.expires);
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+  #[test]
+  fn sign_and_verify() {
+    let auth = RelayAuth::new(Some(SECRET));
+    let sig = auth.sign_at("station", "source", "target", 1000).unwrap();
+    assert_eq!(auth.verify_at("station", "target", &sig, 1001), Ok(()));
+  }
+
+  #[test]
+  fn headers_roundtrip() {
+    let auth = RelayAuth::new(Some(SECRET));
+    let sig = auth.sign("station", "source", "target").unwrap();
+    let mut headers = HeaderMap::new();
+    for (name, value) in sig.headers() {
+      headers.insert(name, value.parse().unwrap());
+    }
+    assert_eq!(RelaySignature::from_headers(&headers), Ok(sig));
+    assert_eq!(
+      RelaySignature::from_headers(&HeaderMap::new()),
+      Err(RelayAuthError::Missing)
+    );
+  }
+
+  #[test]
+  fn rejects_other_station_target_or_secret() {
+    let auth = RelayAuth::new(Some(SECRET));
+    let sig = auth.sign_at("station", "source", "target", 1000).unwrap();
+    assert_eq!(
+      auth.verify_at("other", "target", &sig, 1000),
+      Err(RelayAuthError::InvalidSignature)
+    );
+    assert_eq!(
+      auth.verify_at("station", "other", &sig, 1000),
+      Err(RelayAuthError::InvalidSignature)
+    );
+
+    let other = RelayAuth::new(Some("fedcba9876543210fedcba9876543210"));
+    assert_eq!(
+      other.verify_at("station", "target", &sig, 1000),
+      Err(RelayAuthError::InvalidSignature)
+    );
+  }
+
+  #[test]
+  fn rejects_expired() {
+    let auth = RelayAuth::new(Some(SECRET));
+    let sig = auth.sign_at("station", "source", "target", 1000).unwrap();
+    let late = 1000 + RELAY_SIGNATURE_TTL_SECS + RELAY_SIGNATURE_CLOCK_SKEW_SECS + 1;
+    assert_eq!(
+      auth.verify_at("station", "target", &sig, late),
+      Err(RelayAuthError::Expired)
+    );
+    assert_eq!(
+      auth.verify_at(
+        "station",
+        "target",
+        &sig,
+        1000 - RELAY_SIGNATURE_CLOCK_SKEW_SECS - 1
+      ),
+      Err(RelayAuthError::Expired)
+    );
+  }
+
+  #[test]
+  fn rejects_replayed_nonce() {
+    let auth = RelayAuth::new(Some(SECRET));
+    let sig = auth.sign_at("station", "source", "target", 1000).unwrap();
+    assert_eq!(auth.verify_at("station", "target", &sig, 1000), Ok(()));
+    assert_eq!(
+      auth.verify_at("station", "target", &sig, 1001),
+      Err(RelayAuthError::Replayed)
+    );
+  }
+
+  #[test]
+  fn not_configured() {
+    let auth = RelayAuth::new(None);
+    assert_eq!(
+      auth.sign("station", "source", "target").unwrap_err(),
+      RelayAuthError::NotConfigured
+    );
+    let sig = RelayAuth::new(Some(SECRET))
+      .sign("station", "source", "target")
+      .unwrap();
+    assert_eq!(
+      auth.verify("station", "target", &sig),
+      Err(RelayAuthError::NotConfigured)
+    );
+  }
+}
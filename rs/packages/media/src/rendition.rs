@@ -0,0 +1,344 @@
+//! on demand output renditions of a station
+//!
+//! each rendition is an ffmpeg transform fed from the station main output (the media session sender)
+//! the transform is started with the first listener of the rendition and stopped
+//! when the rendition has no listeners for RENDITION_NO_LISTENERS_SHUTDOWN_DELAY_SECS
+
+use constants::RENDITION_NO_LISTENERS_SHUTDOWN_DELAY_SECS;
+use db::station::{Station, StationRendition, StationRenditionCodec};
+use drop_tracer::DropTracer;
+use ffmpeg::{FfmpegConfig, Format, TransformError};
+use log::*;
+use parking_lot::Mutex;
+use shutdown::Shutdown;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::channel::{Receiver, RecvError, SendError, Sender};
+use crate::{Info, MediaSessionMap, SubscribeError};
+
+#[derive(Debug)]
+struct Entry {
+  rendition: StationRendition,
+  sender: Sender,
+}
+
+type Map = HashMap<(String, String), Entry>;
+
+#[derive(Debug, Clone)]
+pub struct RenditionMap {
+  media_sessions: MediaSessionMap,
+  drop_tracer: DropTracer,
+  shutdown: Shutdown,
+  map: Arc<Mutex<Map>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RenditionSubscribeError {
+  #[error("rendition not found: {0}")]
+  NotFound(String),
+  #[error("subscribe: {0}")]
+  Subscribe(#[from] SubscribeError),
+  #[error("ffmpeg spawn: {0}")]
+  Spawn(#[from] std::io::Error),
+}
+
+/// the ffmpeg output format, encoder, encoder profile and frequency of a rendition codec
+fn codec_config(codec: StationRenditionCodec) -> (Format, &'static str, Option<&'static str>, u16) {
+  match codec {
+    StationRenditionCodec::Mp3 => (Format::MP3, "libmp3lame", None, FfmpegConfig::FREQ),
+    StationRenditionCodec::Aac => (Format::AAC, "aac", None, FfmpegConfig::FREQ),
+    StationRenditionCodec::AacHe => (
+      Format::AAC,
+      "libfdk_aac",
+      Some("aac_he"),
+      FfmpegConfig::FREQ,
+    ),
+    // opus only supports 48kHz output
+    StationRenditionCodec::Opus => (Format::OGG, "libopus", None, 48_000),
+  }
+}
+
+/// if the ffmpeg binary has the encoder of the codec, eg: aac-he needs an ffmpeg built with libfdk_aac
+pub async fn is_codec_available(codec: StationRenditionCodec) -> bool {
+  let (_, encoder, _, _) = codec_config(codec);
+  ffmpeg::encoders::has(encoder).await
+}
+
+/// the ffmpeg config used to transcode the station main output to the rendition
+pub fn ffmpeg_config(rendition: &StationRendition) -> FfmpegConfig {
+  let (format, codec, profile, freq) = codec_config(rendition.codec);
+
+  FfmpegConfig {
+    format,
+    codec: Some(codec),
+    profile,
+    freq,
+    kbitrate: rendition.kbitrate as usize,
+    ..FfmpegConfig::default()
+  }
+}
+
+impl RenditionMap {
+  pub fn new(media_sessions: MediaSessionMap, drop_tracer: DropTracer, shutdown: Shutdown) -> Self {
+    Self {
+      media_sessions,
+      drop_tracer,
+      shutdown,
+      map: Arc::new(Mutex::new(Map::new())),
+    }
+  }
+
+  /// subscribe to a rendition of the station, starting the transform if needed
+  pub async fn subscribe(
+    &self,
+    station: &Station,
+    slug: &str,
+  ) -> Result<Receiver, RenditionSubscribeError> {
+    let rendition = match station.renditions.iter().find(|r| r.slug == slug) {
+      Some(rendition) => rendition.clone(),
+      None => return Err(RenditionSubscribeError::NotFound(slug.to_string())),
+    };
+
+    let key = (station.id.clone(), rendition.slug.clone());
+
+    if let Some(rx) = self.subscribe_running(&key, &rendition) {
+      return Ok(rx);
+    }
+
+    let source = self.media_sessions.subscribe(&station.id).await?;
+
+    let (writer, mut reader) = ffmpeg::transform(
+      ffmpeg_config(&rendition),
+      rendition.kbitrate as usize * 1000 / 8,
+    )?;
+
+    let (sender, rx) = {
+      let mut map = self.map.lock();
+
+      // another listener could have started the rendition while we were subscribing to the source
+      if let Some(rx) = subscribe_entry(&mut map, &key, &rendition) {
+        return Ok(rx);
+      }
+
+      let info = Info::new(
+        source.kind(),
+        Station::random_owner_task_id(),
+        rendition.codec.content_type().to_string(),
+      );
+
+      let sender = Sender::new(station.id.clone(), info);
+      let rx = sender.subscribe();
+
+      map.insert(
+        key.clone(),
+        Entry {
+          rendition: rendition.clone(),
+          sender: sender.clone(),
+        },
+      );
+
+      (sender, rx)
+    };
+
+    info!(
+      target: "media",
+      "starting rendition {} for station {}",
+      rendition.slug, station.id
+    );
+
+    let token = self.drop_tracer.token();
+    let me = self.clone();
+
+    tokio::spawn(async move {
+      let (station_id, slug) = key.clone();
+
+      let feed = {
+        let sender = sender.clone();
+        let mut source = source;
+        let media_sessions = me.media_sessions.clone();
+        let station_id = station_id.clone();
+        async move {
+          loop {
+            match source.recv().await {
+              Err(RecvError::Lagged(_)) => continue,
+
+              // the media session ended, follow the next one of the station
+              // if it has the same content type (ffmpeg cannot switch the input format)
+              Err(RecvError::Closed) => match media_sessions.subscribe(&station_id).await {
+                Ok(next) if next.content_type() == source.content_type() => {
+                  source = next;
+                  sender.set_now_playing(source.now_playing());
+                }
+                Ok(_) => return "source content type changed",
+                Err(_) => return "source resubscribe",
+              },
+
+              Ok(bytes) => {
+                if let Some(now_playing) = source.now_playing_changed() {
+                  sender.set_now_playing(now_playing);
+                }
+
+                if writer.send(bytes).await.is_err() {
+                  return "ffmpeg stdin closed";
+                }
+              }
+            }
+          }
+        }
+      };
+
+      let output = {
+        let sender = sender.clone();
+        let me = me.clone();
+        let key = key.clone();
+        async move {
+          let mut no_listeners_since: Option<Instant> = None;
+
+          loop {
+            match reader.recv().await {
+              None => return "ffmpeg stdout end",
+              Some(Err(TransformError::Io(e))) => {
+                warn!(target: "media", "rendition {slug} for station {station_id} ffmpeg io error: {e}");
+                return "ffmpeg io error";
+              }
+              Some(Err(TransformError::Exit { status, stderr })) => {
+                warn!(
+                  target: "media",
+                  "rendition {slug} for station {station_id} ffmpeg exit with status {status}: {}",
+                  stderr.as_deref().unwrap_or("")
+                );
+                return "ffmpeg exit";
+              }
+
+              Some(Ok(bytes)) => match sender.send(bytes) {
+                Ok(_) => {
+                  no_listeners_since = None;
+                }
+
+                // check if shutdown delay is elapsed
+                Err(SendError::NoSubscribers(_)) => match no_listeners_since {
+                  Some(instant) => {
+                    if instant.elapsed()
+                      > Duration::from_secs(RENDITION_NO_LISTENERS_SHUTDOWN_DELAY_SECS)
+                      && me.try_remove(&key, &sender)
+                    {
+                      return "no listeners shutdown delay elapsed";
+                    }
+                  }
+                  None => {
+                    no_listeners_since = Some(Instant::now());
+                  }
+                },
+
+                Err(SendError::Terminated(_)) => return "terminated",
+              },
+            }
+          }
+        }
+      };
+
+      let signal = me.shutdown.signal();
+
+      let reason = tokio::select! {
+        reason = feed => reason,
+        reason = output => reason,
+        _ = signal => "shutdown",
+      };
+
+      me.remove(&key, &sender);
+
+      info!(
+        target: "media",
+        "shutting down rendition {} for station {} ({reason})",
+        key.1, key.0
+      );
+
+      drop(token);
+    });
+
+    Ok(rx)
+  }
+
+  fn subscribe_running(
+    &self,
+    key: &(String, String),
+    rendition: &StationRendition,
+  ) -> Option<Receiver> {
+    subscribe_entry(&mut self.map.lock(), key, rendition)
+  }
+
+  /// removes the entry only if it still has no listeners
+  /// the check is done under the map lock, so no listener can subscribe in between
+  fn try_remove(&self, key: &(String, String), sender: &Sender) -> bool {
+    let mut map = self.map.lock();
+    if sender.receiver_count() != 0 {
+      return false;
+    }
+    remove_entry(&mut map, key, sender);
+    true
+  }
+
+  fn remove(&self, key: &(String, String), sender: &Sender) {
+    remove_entry(&mut self.map.lock(), key, sender);
+  }
+}
+
+/// subscribes to the running entry of the rendition
+/// if the rendition was edited since the entry was started, the entry is terminated
+/// so its listeners reconnect to the new settings
+fn subscribe_entry(
+  map: &mut Map,
+  key: &(String, String),
+  rendition: &StationRendition,
+) -> Option<Receiver> {
+  let entry = map.get(key)?;
+  if entry.rendition == *rendition && !entry.sender.is_terminated() {
+    return Some(entry.sender.subscribe());
+  }
+
+  let entry = map.remove(key)?;
+  entry.sender.terminate();
+  None
+}
+
+fn remove_entry(map: &mut Map, key: &(String, String), sender: &Sender) {
+  if let Some(entry) = map.get(key) {
+    if entry.sender.is_same_channel(sender) {
+      map.remove(key);
+    }
+  }
+  sender.terminate();
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn opus_renditions_are_48khz_ogg() {
+    let config = ffmpeg_config(&StationRendition {
+      slug: "opus".into(),
+      codec: StationRenditionCodec::Opus,
+      kbitrate: 48,
+    });
+
+    assert_eq!(config.format, Format::OGG);
+    assert_eq!(config.codec, Some("libopus"));
+    assert_eq!(config.freq, 48_000);
+    assert_eq!(config.kbitrate, 48);
+  }
+
+  #[test]
+  fn aac_he_renditions_use_he_profile() {
+    let config = ffmpeg_config(&StationRendition {
+      slug: "aac-64".into(),
+      codec: StationRenditionCodec::AacHe,
+      kbitrate: 64,
+    });
+
+    assert_eq!(config.format, Format::AAC);
+    assert_eq!(config.profile, Some("aac_he"));
+  }
+}
@@ -0,0 +1,213 @@
+use constants::STATION_SCHEDULE_CHECK_INTERVAL_SECS;
+use db::station::{OwnerDeploymentInfo, Station};
+use db::station_schedule::{StationSchedule, StationScheduleSource};
+use db::Model;
+use mongodb::bson::doc;
+use serde_util::DateTime;
+use tokio::task::JoinHandle;
+
+use crate::drop::OwnerDeploymentDropper;
+use crate::handle::playlist::PlaylistSelection;
+use crate::{Handle, Info, Kind, MediaSessionMap};
+
+/// the source a station plays when it has no live source connected
+/// resolved from the active slot of the station schedule or the station defaults
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StationSource {
+  /// the listeners are redirected to the station external relay url
+  ExternalRelayRedirect {
+    url: String,
+  },
+  ExternalRelay {
+    urls: Vec<String>,
+  },
+  Playlist(PlaylistSelection),
+}
+
+impl StationSource {
+  /// the source of the station at this moment
+  pub async fn resolve(station: &Station) -> Result<Self, mongodb::error::Error> {
+    let slot = StationSchedule::active_for_station(station).await?;
+    let source = match slot.map(|slot| slot.source) {
+      Some(StationScheduleSource::Playlist {
+        playlist_id: Some(playlist_id),
+        file_ids: _,
+      }) => Self::Playlist(PlaylistSelection::Playlist { playlist_id }),
+      Some(StationScheduleSource::Playlist {
+        playlist_id: None,
+        file_ids,
+      }) => Self::Playlist(PlaylistSelection::Files { file_ids }),
+      Some(StationScheduleSource::ExternalRelay { url }) => Self::ExternalRelay { urls: vec![url] },
+      Some(StationScheduleSource::Live) | None => Self::default_for_station(station),
+    };
+
+    Ok(source)
+  }
+
+  /// the source of the station when no slot of its schedule is active
+  pub fn default_for_station(station: &Station) -> Self {
+    if let Some(url) = &station.external_relay_url {
+      if station.external_relay_redirect {
+        return Self::ExternalRelayRedirect { url: url.clone() };
+      }
+    }
+
+    let urls = station.external_relay_urls();
+    if urls.is_empty() {
+      Self::Playlist(PlaylistSelection::for_station(station))
+    } else {
+      Self::ExternalRelay { urls }
+    }
+  }
+
+  /// returns true if the source has something to play
+  /// (external relays always have, playlists need at least one file)
+  pub async fn is_playable(&self, station_id: &str) -> Result<bool, mongodb::error::Error> {
+    match self {
+      Self::ExternalRelayRedirect { .. } | Self::ExternalRelay { .. } => Ok(true),
+      Self::Playlist(selection) => selection.is_playable(station_id).await,
+    }
+  }
+
+  pub fn kind(&self) -> Kind {
+    match self {
+      Self::ExternalRelayRedirect { .. } | Self::ExternalRelay { .. } => Kind::ExternalRelay,
+      Self::Playlist(_) => Kind::Playlist,
+    }
+  }
+}
+
+impl MediaSessionMap {
+  /// periodically switches the playlist and external relay sessions of this deployment
+  /// to the source of the active slot of the station schedule
+  pub fn start_schedule_job(&self) -> JoinHandle<()> {
+    let map = self.clone();
+    tokio::spawn(async move {
+      let duration = tokio::time::Duration::from_secs(STATION_SCHEDULE_CHECK_INTERVAL_SECS);
+      let mut interval = tokio::time::interval(duration);
+      interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+      loop {
+        interval.tick().await;
+
+        if !map.shutdown.is_open() {
+          break;
+        }
+
+        let station_ids = { map.map.lock().keys().cloned().collect::<Vec<String>>() };
+
+        for station_id in station_ids {
+          if let Err(e) = map.check_schedule(&station_id).await {
+            log::warn!(
+              target: "media",
+              "error checking schedule for station {station_id}: {e} => {e:?}"
+            );
+          }
+        }
+      }
+    })
+  }
+
+  async fn check_schedule(&self, station_id: &str) -> Result<(), mongodb::error::Error> {
+    let mut lock = self.lock(station_id).await;
+
+    let (current, current_task_id) = match &*lock {
+      Some(handle) if !handle.is_terminated() => match &handle.source {
+        Some(source) => (source.clone(), handle.info().task_id.clone()),
+        None => return Ok(()),
+      },
+      _ => return Ok(()),
+    };
+
+    let station = match Station::get_by_id(station_id).await? {
+      None => return Ok(()),
+      Some(station) => station,
+    };
+
+    let source = StationSource::resolve(&station).await?;
+    if source == current {
+      return Ok(());
+    }
+
+    // the listeners will reconnect to be redirected or to get an empty playlist error
+    if let StationSource::ExternalRelayRedirect { .. } = source {
+      log::info!(
+        target: "media",
+        "schedule switch for station {station_id}: external relay redirect, terminating session"
+      );
+      lock.take();
+      return Ok(());
+    }
+
+    if !source.is_playable(station_id).await? {
+      log::info!(
+        target: "media",
+        "schedule switch for station {station_id}: {source:?} has nothing to play, terminating session"
+      );
+      lock.take();
+      return Ok(());
+    }
+
+    let codec_info = crate::codec_info_for_source(&station, &source).await?;
+
+    let task_id = Station::random_owner_task_id();
+    let content_type = "audio/mpeg".to_string();
+
+    let owner_deployment_info = OwnerDeploymentInfo {
+      content_type: content_type.clone(),
+      deployment_id: self.deployment_id.clone(),
+      task_id: task_id.clone(),
+      health_checked_at: Some(DateTime::now()),
+    };
+
+    const KEY_OWNER_TASK_ID: &str = db::key!(
+      Station::KEY_OWNER_DEPLOYMENT_INFO,
+      OwnerDeploymentInfo::KEY_TASK_ID
+    );
+
+    let filter = doc! {
+      Station::KEY_ID: station_id,
+      KEY_OWNER_TASK_ID: &current_task_id,
+    };
+
+    let update = doc! {
+      "$set": { Station::KEY_OWNER_DEPLOYMENT_INFO: owner_deployment_info }
+    };
+
+    let r = Station::cl().update_one(filter, update, None).await?;
+    if r.matched_count == 0 {
+      return Ok(());
+    }
+
+    let owner_deployment_dropper = OwnerDeploymentDropper::new(
+      station_id.to_string(),
+      task_id.clone(),
+      self.drop_tracer.token(),
+    );
+
+    log::info!(
+      target: "media",
+      "schedule switch for station {station_id}: {current:?} => {source:?}"
+    );
+
+    let sender = match &*lock {
+      Some(handle) => handle.handover(Info::new(source.kind(), task_id.clone(), content_type)),
+      None => return Ok(()),
+    };
+
+    self.spawn_station_source(
+      station_id,
+      task_id,
+      sender.clone(),
+      source.clone(),
+      codec_info,
+      true,
+      owner_deployment_dropper,
+    );
+
+    // dropping the previous handle terminates its source
+    *lock = Some(Handle::with_source(sender, source));
+
+    Ok(())
+  }
+}
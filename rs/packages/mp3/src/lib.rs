@@ -1,9 +1,14 @@
 pub use enums::{ChannelType, Copyright, Emphasis, Error, Genre, Layer, Status, Version, CRC};
 pub use metadata::{read_from_file, read_from_slice};
+pub use stream::{
+  adts_header, mpeg_header, FrameHeader, StreamCodec, StreamError, StreamFormat, StreamValidator,
+  MAX_UNSYNCED_BYTES,
+};
 pub use types::{AudioTag, Frame, MP3Metadata, OptionalAudioTags, Url};
 
 mod consts;
 mod enums;
 mod metadata;
+mod stream;
 mod types;
 mod utils;
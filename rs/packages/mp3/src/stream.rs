@@ -0,0 +1,564 @@
+//! frame level validation of a live stream of MPEG audio or AAC ADTS frames
+//!
+//! the validator is fed the bytes as they arrive, it finds the frame boundaries
+//! and checks that every frame has the codec, sample rate and channels of the first one
+
+use std::fmt;
+
+use consts::{BITRATES, SAMPLES_PER_FRAME, SAMPLING_FREQ};
+use enums::{ChannelType, Layer, Version};
+use utils::{get_layer_value, get_line, get_samp_line};
+
+/// max bytes that can be received without finding a valid frame
+/// before the stream is considered invalid
+pub const MAX_UNSYNCED_BYTES: usize = 1024 * 64;
+
+/// enough bytes to read the header of any frame or ID3v2 tag
+const MAX_HEADER_LEN: usize = 10;
+
+#[rustfmt::skip]
+const ADTS_SAMPLE_RATES: [u32; 13] = [
+  96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum StreamCodec {
+  /// MPEG 1, 2 or 2.5 audio, usually layer 3
+  Mp3,
+  /// AAC in ADTS frames
+  Aac,
+}
+
+impl StreamCodec {
+  /// the codec of a stream sent with this content type, None if the content type is not supported
+  pub fn from_content_type(content_type: &str) -> Option<Self> {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    match mime.to_ascii_lowercase().as_str() {
+      "audio/mpeg" | "audio/mp3" | "audio/mpeg3" | "audio/x-mpeg" => Some(Self::Mp3),
+      "audio/aac" | "audio/aacp" | "audio/x-aac" | "audio/x-aacp" => Some(Self::Aac),
+      _ => None,
+    }
+  }
+
+  pub fn content_type(&self) -> &'static str {
+    match self {
+      Self::Mp3 => "audio/mpeg",
+      Self::Aac => "audio/aac",
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::Mp3 => "mp3",
+      Self::Aac => "aac",
+    }
+  }
+
+  fn other(&self) -> Self {
+    match self {
+      Self::Mp3 => Self::Aac,
+      Self::Aac => Self::Mp3,
+    }
+  }
+}
+
+impl fmt::Display for StreamCodec {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct StreamFormat {
+  pub codec: StreamCodec,
+  pub sample_rate: u32,
+  pub channels: u8,
+}
+
+impl fmt::Display for StreamFormat {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let channels = match self.channels {
+      1 => "mono".to_string(),
+      2 => "stereo".to_string(),
+      n => format!("{n} channels"),
+    };
+    write!(f, "{} {} Hz {}", self.codec, self.sample_rate, channels)
+  }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FrameHeader {
+  pub format: StreamFormat,
+  /// length of the frame in bytes, including the header
+  pub len: usize,
+  /// number of samples per channel in the frame
+  pub samples: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StreamError {
+  /// no valid frame was found in MAX_UNSYNCED_BYTES
+  Unsynced { expected: StreamCodec },
+  /// the stream contains frames of another codec or container
+  CodecMismatch {
+    expected: StreamCodec,
+    found: &'static str,
+  },
+  /// the sample rate or the channels of the frames changed in the middle of the stream
+  FormatChanged {
+    from: StreamFormat,
+    to: StreamFormat,
+  },
+}
+
+impl fmt::Display for StreamError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Unsynced { expected } => write!(
+        f,
+        "no valid {expected} frames found in the last {MAX_UNSYNCED_BYTES} bytes"
+      ),
+      Self::CodecMismatch { expected, found } => {
+        write!(
+          f,
+          "expected {expected} frames but the stream contains {found}"
+        )
+      }
+      Self::FormatChanged { from, to } => {
+        write!(f, "stream format changed from {from} to {to}")
+      }
+    }
+  }
+}
+
+impl std::error::Error for StreamError {}
+
+/// parses the header of a MPEG audio frame at the start of `buf`
+pub fn mpeg_header(buf: &[u8]) -> Option<FrameHeader> {
+  if buf.len() < 4 {
+    return None;
+  }
+
+  let header = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+  if header & 0xffe00000 != 0xffe00000 {
+    return None;
+  }
+
+  let version = Version::from((header >> 19) & 3);
+  let layer = Layer::from((header >> 17) & 3);
+  if version == Version::Reserved || layer == Layer::Reserved {
+    return None;
+  }
+
+  // index 0 is free format and 15 is invalid, both have a bitrate of 0
+  let bitrate = BITRATES[get_line(version, layer)][((header >> 12) & 0xf) as usize] as u64;
+  let sample_rate = SAMPLING_FREQ[get_samp_line(version)][((header >> 10) & 3) as usize] as u64;
+  if bitrate == 0 || sample_rate == 0 {
+    return None;
+  }
+
+  let padding = (header >> 9) & 1;
+  let channels = match ChannelType::from((header >> 6) & 3) {
+    ChannelType::SingleChannel => 1,
+    _ => 2,
+  };
+
+  let version_line = if version == Version::MPEG1 { 0 } else { 1 };
+  let samples = SAMPLES_PER_FRAME[version_line][get_layer_value(layer)];
+
+  let len = match layer {
+    Layer::Layer1 => (12 * bitrate * 1000 / sample_rate + padding as u64) * 4,
+    _ => samples as u64 / 8 * bitrate * 1000 / sample_rate + padding as u64,
+  };
+
+  Some(FrameHeader {
+    format: StreamFormat {
+      codec: StreamCodec::Mp3,
+      sample_rate: sample_rate as u32,
+      channels,
+    },
+    len: len as usize,
+    samples,
+  })
+}
+
+/// parses the header of an AAC ADTS frame at the start of `buf`
+pub fn adts_header(buf: &[u8]) -> Option<FrameHeader> {
+  if buf.len() < 7 {
+    return None;
+  }
+
+  // 12 bits of sync word and 2 bits of layer that are always 0
+  if buf[0] != 0xff || buf[1] & 0xf6 != 0xf0 {
+    return None;
+  }
+
+  let sample_rate = *ADTS_SAMPLE_RATES.get(((buf[2] >> 2) & 0xf) as usize)?;
+
+  // channel configuration 0 is defined in the stream and 7 is 7.1
+  let channels = match ((buf[2] & 1) << 2) | (buf[3] >> 6) {
+    0 => return None,
+    7 => 8,
+    n => n,
+  };
+
+  let len = (((buf[3] & 3) as usize) << 11) | ((buf[4] as usize) << 3) | ((buf[5] >> 5) as usize);
+  if len < 7 {
+    return None;
+  }
+
+  let blocks = (buf[6] & 3) as u32 + 1;
+
+  Some(FrameHeader {
+    format: StreamFormat {
+      codec: StreamCodec::Aac,
+      sample_rate,
+      channels,
+    },
+    len,
+    samples: 1024 * blocks,
+  })
+}
+
+/// length of the ID3v2 tag at the start of `buf`
+fn id3v2_len(buf: &[u8]) -> Option<usize> {
+  if buf.len() < 10 || &buf[0..3] != b"ID3" {
+    return None;
+  }
+
+  let size = ((buf[6] as usize & 0x7f) << 21)
+    | ((buf[7] as usize & 0x7f) << 14)
+    | ((buf[8] as usize & 0x7f) << 7)
+    | (buf[9] as usize & 0x7f);
+
+  Some(10 + size)
+}
+
+/// other formats that encoders can send instead of the declared one
+fn container_name(buf: &[u8]) -> Option<&'static str> {
+  if buf.starts_with(b"OggS") {
+    Some("ogg")
+  } else if buf.starts_with(b"fLaC") {
+    Some("flac")
+  } else if buf.starts_with(b"RIFF") {
+    Some("wav")
+  } else {
+    None
+  }
+}
+
+fn parse_header(codec: StreamCodec, buf: &[u8]) -> Option<FrameHeader> {
+  match codec {
+    StreamCodec::Mp3 => mpeg_header(buf),
+    StreamCodec::Aac => adts_header(buf),
+  }
+}
+
+/// two consecutive headers of the same format, to not sync on random bytes
+fn synced_header(codec: StreamCodec, buf: &[u8]) -> Option<FrameHeader> {
+  let header = parse_header(codec, buf)?;
+  let next = parse_header(codec, buf.get(header.len..)?)?;
+  if next.format == header.format {
+    Some(header)
+  } else {
+    None
+  }
+}
+
+#[derive(Debug)]
+pub struct StreamValidator {
+  codec: StreamCodec,
+  format: Option<StreamFormat>,
+  synced: bool,
+  /// bytes of the incomplete frame or tag at the end of the received data
+  buf: Vec<u8>,
+  unsynced_bytes: usize,
+  frames: u64,
+  frame_bytes: u64,
+  samples: u64,
+}
+
+impl StreamValidator {
+  pub fn new(codec: StreamCodec) -> Self {
+    Self {
+      codec,
+      format: None,
+      synced: false,
+      buf: vec![],
+      unsynced_bytes: 0,
+      frames: 0,
+      frame_bytes: 0,
+      samples: 0,
+    }
+  }
+
+  pub fn codec(&self) -> StreamCodec {
+    self.codec
+  }
+
+  /// the format of the first frame of the stream, None until a frame is found
+  pub fn format(&self) -> Option<StreamFormat> {
+    self.format
+  }
+
+  pub fn frames(&self) -> u64 {
+    self.frames
+  }
+
+  /// average bitrate of the received frames in kbps
+  pub fn kbitrate(&self) -> Option<u32> {
+    let format = self.format?;
+    if self.samples == 0 {
+      return None;
+    }
+
+    let bits_per_sec = self.frame_bytes * 8 * format.sample_rate as u64 / self.samples;
+    Some((bits_per_sec / 1000) as u32)
+  }
+
+  /// validates the next bytes of the stream
+  ///
+  /// after an error the validator must not be used again
+  pub fn push(&mut self, data: &[u8]) -> Result<(), StreamError> {
+    self.buf.extend_from_slice(data);
+
+    let mut pos = 0;
+    let result = loop {
+      let rest = &self.buf[pos..];
+      if rest.len() < MAX_HEADER_LEN {
+        break Ok(());
+      }
+
+      // some encoders send ID3v2 tags between frames
+      if let Some(len) = id3v2_len(rest) {
+        if rest.len() < len {
+          break Ok(());
+        }
+        pos += len;
+        continue;
+      }
+
+      if self.synced {
+        if let Some(header) = parse_header(self.codec, rest) {
+          if rest.len() < header.len {
+            break Ok(());
+          }
+
+          if let Err(e) = self.frame(header) {
+            break Err(e);
+          }
+
+          pos += header.len;
+          continue;
+        }
+
+        self.synced = false;
+      } else {
+        if let Some(header) = parse_header(self.codec, rest) {
+          if rest.len() < header.len + MAX_HEADER_LEN {
+            break Ok(());
+          }
+
+          if synced_header(self.codec, rest).is_some() {
+            self.synced = true;
+            self.unsynced_bytes = 0;
+            continue;
+          }
+        }
+
+        if let Some(found) = container_name(rest) {
+          break Err(StreamError::CodecMismatch {
+            expected: self.codec,
+            found,
+          });
+        }
+
+        let other = self.codec.other();
+        if synced_header(other, rest).is_some() {
+          break Err(StreamError::CodecMismatch {
+            expected: self.codec,
+            found: other.as_str(),
+          });
+        }
+      }
+
+      pos += 1;
+      self.unsynced_bytes += 1;
+      if self.unsynced_bytes > MAX_UNSYNCED_BYTES {
+        break Err(StreamError::Unsynced {
+          expected: self.codec,
+        });
+      }
+    };
+
+    self.buf.drain(..pos);
+    result
+  }
+
+  fn frame(&mut self, header: FrameHeader) -> Result<(), StreamError> {
+    match self.format {
+      None => self.format = Some(header.format),
+      Some(format) => {
+        if format != header.format {
+          return Err(StreamError::FormatChanged {
+            from: format,
+            to: header.format,
+          });
+        }
+      }
+    }
+
+    self.frames += 1;
+    self.frame_bytes += header.len as u64;
+    self.samples += header.samples as u64;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  /// MPEG 1 layer 3 128 kbps 44100 Hz, byte 3 sets the channel mode
+  fn mp3_frame(channel_mode: u8) -> Vec<u8> {
+    let mut frame = vec![0xff, 0xfb, 0x90, channel_mode];
+    let len = mpeg_header(&frame).unwrap().len;
+    frame.resize(len, 0);
+    frame
+  }
+
+  /// AAC LC 44100 Hz stereo of 100 bytes
+  fn adts_frame() -> Vec<u8> {
+    let mut frame = vec![0xff, 0xf1, 0x50, 0x80, 0x0c, 0x9f, 0xfc];
+    frame.resize(100, 0);
+    frame
+  }
+
+  fn push_chunked(validator: &mut StreamValidator, data: &[u8]) -> Result<(), StreamError> {
+    for chunk in data.chunks(333) {
+      validator.push(chunk)?;
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn mp3_header() {
+    let header = mpeg_header(&mp3_frame(0x64)).unwrap();
+    assert_eq!(header.len, 417);
+    assert_eq!(header.samples, 1152);
+    assert_eq!(header.format.sample_rate, 44100);
+    assert_eq!(header.format.channels, 2);
+    assert!(adts_header(&mp3_frame(0x64)).is_none());
+  }
+
+  #[test]
+  fn aac_header() {
+    let header = adts_header(&adts_frame()).unwrap();
+    assert_eq!(header.len, 100);
+    assert_eq!(header.samples, 1024);
+    assert_eq!(header.format.codec, StreamCodec::Aac);
+    assert_eq!(header.format.sample_rate, 44100);
+    assert_eq!(header.format.channels, 2);
+    assert!(mpeg_header(&adts_frame()).is_none());
+  }
+
+  #[test]
+  fn content_type() {
+    assert_eq!(
+      StreamCodec::from_content_type("audio/mpeg"),
+      Some(StreamCodec::Mp3)
+    );
+    assert_eq!(
+      StreamCodec::from_content_type("Audio/AACP; charset=binary"),
+      Some(StreamCodec::Aac)
+    );
+    assert_eq!(StreamCodec::from_content_type("audio/ogg"), None);
+  }
+
+  #[test]
+  fn valid_mp3_stream() {
+    let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x05hello".to_vec();
+    for _ in 0..20 {
+      data.extend(mp3_frame(0x64));
+    }
+
+    let mut validator = StreamValidator::new(StreamCodec::Mp3);
+    push_chunked(&mut validator, &data).unwrap();
+
+    let format = validator.format().unwrap();
+    assert_eq!(format.codec, StreamCodec::Mp3);
+    assert_eq!(format.sample_rate, 44100);
+    assert_eq!(format.channels, 2);
+    assert_eq!(validator.kbitrate(), Some(127));
+    assert_eq!(validator.frames(), 20);
+  }
+
+  #[test]
+  fn mp3_channels_changed() {
+    let mut data = vec![];
+    for _ in 0..10 {
+      data.extend(mp3_frame(0x64));
+    }
+    for _ in 0..10 {
+      data.extend(mp3_frame(0xc4));
+    }
+
+    let mut validator = StreamValidator::new(StreamCodec::Mp3);
+    let err = push_chunked(&mut validator, &data).unwrap_err();
+    match err {
+      StreamError::FormatChanged { from, to } => {
+        assert_eq!(from.channels, 2);
+        assert_eq!(to.channels, 1);
+      }
+      e => panic!("unexpected error {:?}", e),
+    }
+  }
+
+  #[test]
+  fn aac_sent_as_mp3() {
+    let mut data = vec![];
+    for _ in 0..10 {
+      data.extend(adts_frame());
+    }
+
+    let mut validator = StreamValidator::new(StreamCodec::Mp3);
+    let err = push_chunked(&mut validator, &data).unwrap_err();
+    assert_eq!(
+      err,
+      StreamError::CodecMismatch {
+        expected: StreamCodec::Mp3,
+        found: "aac"
+      }
+    );
+  }
+
+  #[test]
+  fn ogg_sent_as_mp3() {
+    let mut data = b"OggS".to_vec();
+    data.resize(4096, 0);
+
+    let mut validator = StreamValidator::new(StreamCodec::Mp3);
+    let err = push_chunked(&mut validator, &data).unwrap_err();
+    assert_eq!(
+      err,
+      StreamError::CodecMismatch {
+        expected: StreamCodec::Mp3,
+        found: "ogg"
+      }
+    );
+  }
+
+  #[test]
+  fn unsynced_stream() {
+    let data = vec![0u8; MAX_UNSYNCED_BYTES * 2];
+
+    let mut validator = StreamValidator::new(StreamCodec::Aac);
+    let err = push_chunked(&mut validator, &data).unwrap_err();
+    assert_eq!(
+      err,
+      StreamError::Unsynced {
+        expected: StreamCodec::Aac
+      }
+    );
+  }
+}
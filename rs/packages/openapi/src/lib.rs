@@ -1,5 +1,6 @@
 use schemars::{
   gen::{SchemaGenerator, SchemaSettings},
+  schema::Schema,
   JsonSchema,
 };
 use ts_rs::TS;
@@ -26,6 +27,14 @@ pub fn default_generator() -> SchemaGenerator {
   default_settings().into_generator()
 }
 
+/// a reference to a schema shared in the components of the openapi document,
+/// for the large types that would otherwise be inlined in every schema that uses them
+///
+/// the referenced schema is added to the components by openapi-generator.js
+pub fn component_ref(name: &str) -> Schema {
+  Schema::new_ref(format!("#/components/schemas/{name}"))
+}
+
 pub fn export_schema<T: JsonSchema>(target: &str) -> Result<(), ExportError> {
   let mut gen = default_generator();
   export_schema_with_generator::<T>(&mut gen, target)
@@ -48,8 +48,15 @@ impl RouterServer {
     app.with(http::middleware::server);
     app.get("/status", http::middleware::status);
 
-    app.get("/:id([a-zA-Z0-9]+)", RouterHandler::new());
-    app.get("/:id([a-zA-Z0-9]+).m3u", HlsHandler::new());
+    app.get(
+      "/:id([a-zA-Z0-9]+)",
+      RouterHandler::new(RouteTarget::Stream),
+    );
+    app.get(
+      "/:id([a-zA-Z0-9]+).m3u8",
+      RouterHandler::new(RouteTarget::Hls),
+    );
+    app.get("/:id([a-zA-Z0-9]+).m3u", M3uHandler::new());
     app.get("/:id([a-zA-Z0-9]+).pls", PlsHandler::new());
 
     let app = app.build().expect("prex app build router");
@@ -114,25 +121,38 @@ impl Drop for RouterServer {
   }
 }
 
+/// the target of a router redirect in the stream node of the station
+#[derive(Debug, Clone, Copy)]
+enum RouteTarget {
+  /// the progressive stream
+  Stream,
+  /// the HLS playlist of the stream
+  Hls,
+}
+
 #[derive(Debug, Clone)]
-struct HlsHandler {}
+struct M3uHandler {}
 
-impl HlsHandler {
+impl M3uHandler {
   fn new() -> Self {
     Self {}
   }
 }
 
 #[async_trait]
-impl Handler for HlsHandler {
+impl Handler for M3uHandler {
   async fn call(&self, req: Request, _: Next) -> Response {
     let id = req.param("id").unwrap();
+    let url = match route(id).await {
+      Ok(url) => url,
+      Err(e) => return route_error_response(id, e),
+    };
+
     let mut res = Response::new(StatusCode::OK);
-    res.headers_mut().append(
-      CONTENT_TYPE,
-      HeaderValue::from_static("application/vnd.apple.mpegurl"),
-    );
-    *res.body_mut() = Body::from(hls_contents(id));
+    res
+      .headers_mut()
+      .append(CONTENT_TYPE, HeaderValue::from_static("audio/x-mpegurl"));
+    *res.body_mut() = Body::from(m3u_contents(&url));
     res
   }
 }
@@ -150,21 +170,28 @@ impl PlsHandler {
 impl Handler for PlsHandler {
   async fn call(&self, req: Request, _: Next) -> Response {
     let id = req.param("id").unwrap();
+    let url = match route(id).await {
+      Ok(url) => url,
+      Err(e) => return route_error_response(id, e),
+    };
+
     let mut res = Response::new(StatusCode::OK);
     res
       .headers_mut()
       .append(CONTENT_TYPE, HeaderValue::from_static("audio/x-scpls"));
-    *res.body_mut() = Body::from(pls_contents(id));
+    *res.body_mut() = Body::from(pls_contents(id, &url));
     res
   }
 }
 
 #[derive(Debug, Clone)]
-struct RouterHandler {}
+struct RouterHandler {
+  target: RouteTarget,
+}
 
 impl RouterHandler {
-  fn new() -> Self {
-    Self {}
+  fn new(target: RouteTarget) -> Self {
+    Self { target }
   }
 }
 
@@ -173,36 +200,11 @@ impl Handler for RouterHandler {
   async fn call(&self, req: Request, _: Next) -> Response {
     let id = req.param("id").unwrap();
     let url = match route(id).await {
-      Err(e) => {
-        let (status, message) = match e {
-          RouteError::Db(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            String::from("Internal Server Error (DB)"),
-          ),
-          RouteError::NotFound => (
-            StatusCode::NOT_FOUND,
-            format!("Station with id {id} not found"),
-          ),
-          RouteError::NotStreaming => (
-            StatusCode::SERVICE_UNAVAILABLE,
-            format!("Station with id {id} is not actively streaming right now"),
-          ),
-        };
-
-        let mut res = Response::new(status);
-        *res.body_mut() = Body::from(message);
-        res.headers_mut().append(
-          CONTENT_TYPE,
-          HeaderValue::from_static("text/plain;charset=utf-8"),
-        );
-
-        res
-          .headers_mut()
-          .append(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
-
-        return res;
-      }
-      Ok(url) => url,
+      Err(e) => return route_error_response(id, e),
+      Ok(url) => match self.target {
+        RouteTarget::Stream => url,
+        RouteTarget::Hls => format!("{url}/hls/live.m3u8"),
+      },
     };
 
     let mut res = Response::new(StatusCode::FOUND);
@@ -218,6 +220,36 @@ impl Handler for RouterHandler {
   }
 }
 
+fn route_error_response(id: &str, e: RouteError) -> Response {
+  let (status, message) = match e {
+    RouteError::Db(_) => (
+      StatusCode::INTERNAL_SERVER_ERROR,
+      String::from("Internal Server Error (DB)"),
+    ),
+    RouteError::NotFound => (
+      StatusCode::NOT_FOUND,
+      format!("Station with id {id} not found"),
+    ),
+    RouteError::NotStreaming => (
+      StatusCode::SERVICE_UNAVAILABLE,
+      format!("Station with id {id} is not actively streaming right now"),
+    ),
+  };
+
+  let mut res = Response::new(status);
+  *res.body_mut() = Body::from(message);
+  res.headers_mut().append(
+    CONTENT_TYPE,
+    HeaderValue::from_static("text/plain;charset=utf-8"),
+  );
+
+  res
+    .headers_mut()
+    .append(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+  res
+}
+
 #[derive(Debug, Clone)]
 pub enum RouteError {
   Db(mongodb::error::Error),
@@ -231,6 +263,8 @@ impl From<mongodb::error::Error> for RouteError {
   }
 }
 
+/// returns the url of the progressive stream of the station in its stream node
+/// the HLS playlist of the station is served at {url}/hls/live.m3u8
 pub async fn route(id: &str) -> Result<String, RouteError> {
   let station = Station::get_by_id(id).await?;
 
@@ -243,18 +277,18 @@ pub async fn route(id: &str) -> Result<String, RouteError> {
   Ok(url)
 }
 
-pub fn pls_contents(id: &str) -> String {
+pub fn pls_contents(id: &str, url: &str) -> String {
   format!(
     "\
 [Playlist]
 NumberOfEntries=1
-File1=http://stream.openstream.test/{id}
+File1={url}
 Title1={id}
 Length=-1
 Version=2"
   )
 }
 
-pub fn hls_contents(id: &str) -> String {
-  format!("http://stream.openstream.test/{id}")
+pub fn m3u_contents(url: &str) -> String {
+  format!("{url}\n")
 }
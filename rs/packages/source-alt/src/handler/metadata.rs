@@ -2,12 +2,18 @@ use crate::http::write_response_head;
 use crate::RequestHead;
 use crate::{error::HandlerError, http::ResponseHead};
 use db::{
-  media_session::MediaSession, play_history_item::PlayHistoryItem, station::Station, Model,
+  event::{Event, NowPlayingChanged},
+  media_session::MediaSession,
+  play_history_item::PlayHistoryItem,
+  station::Station,
+  station_dj::{SourceAuthError, StationDj},
+  Model,
 };
 use hyper::header::{CONNECTION, CONTENT_TYPE, WWW_AUTHENTICATE};
 use hyper::Version;
 use hyper::{header::CONTENT_LENGTH, http::HeaderValue, HeaderMap, StatusCode};
 use lazy_regex::{Lazy, Regex};
+use media::MediaSessionMap;
 use mongodb::bson::doc;
 use regex_static::lazy_regex;
 use serde::{Deserialize, Serialize};
@@ -24,6 +30,8 @@ pub enum MetadataError {
   NotLiveStreaming,
   #[error("Invalid credentials")]
   InvalidCredentials,
+  #[error("The live source of the station is connected with other credentials")]
+  OtherSource,
   #[error("Internal server error (db)")]
   Db(#[from] mongodb::error::Error),
   #[error("Invalid query string: {0}")]
@@ -46,12 +54,14 @@ pub async fn metadata(
   remote_addr: SocketAddr,
   head: RequestHead,
   deployment_id: String,
+  media_sessions: MediaSessionMap,
 ) -> Result<(), HandlerError> {
   async fn metadata(
     _local_addr: SocketAddr,
     _remote_addr: SocketAddr,
     head: RequestHead,
     deployment_id: String,
+    media_sessions: MediaSessionMap,
   ) -> Result<(), MetadataError> {
     let mut query: MetadataQueryString = serde_qs::from_str(head.uri.query().unwrap_or(""))?;
 
@@ -91,8 +101,6 @@ pub async fn metadata(
       Some(station) => station,
     };
 
-    let password = station.source_password;
-
     let basic_auth = match head.headers.get("authorization") {
       None => return Err(MetadataError::InvalidCredentials),
       Some(header) => match header.to_str() {
@@ -104,30 +112,48 @@ pub async fn metadata(
       },
     };
 
-    if basic_auth.user_id != "source" || basic_auth.password != password {
-      return Err(MetadataError::InvalidCredentials);
-    }
+    let auth_dj =
+      match StationDj::authenticate_source(&station, &basic_auth.user_id, &basic_auth.password)
+        .await
+      {
+        Ok(dj) => dj,
+        Err(SourceAuthError::Db(e)) => return Err(MetadataError::Db(e)),
+        Err(_) => return Err(MetadataError::InvalidCredentials),
+      };
+
+    // while the live source is in dead air failover the playlist session is the newest of the station,
+    // so the live session is resolved by the handle if the source is connected to this deployment
+    let media_session = match media_sessions.live_media_session_id(&station.id).await {
+      Some(media_session_id) => MediaSession::get_by_id(&media_session_id).await?,
+      None => MediaSession::get_current_for_station(&station.id).await?,
+    };
 
-    let media_session = match MediaSession::get_current_for_station(&station.id).await? {
+    let media_session = match media_session {
       None => return Err(MetadataError::NotLiveStreaming),
       Some(session) => session,
     };
 
     use db::media_session::MediaSessionKind::*;
     use db::media_session::MediaSessionNowPlaying;
-    let (media_session_id, current_now_playing) = match &media_session.kind {
+    let (media_session_id, current_now_playing, dj) = match &media_session.kind {
       Playlist { .. } => return Err(MetadataError::NotLiveStreaming),
       ExternalRelay { .. } => return Err(MetadataError::NotLiveStreaming),
-      Live { .. } => (media_session.id, media_session.now_playing),
-    };
+      Live { dj, .. } => {
+        // a dj can only update the metadata of its own live session
+        if let Some(auth_dj) = &auth_dj {
+          if dj.as_ref().map(|dj| dj.dj_id.as_str()) != Some(auth_dj.id.as_str()) {
+            return Err(MetadataError::OtherSource);
+          }
+        }
 
-    let new_now_playing = MediaSessionNowPlaying {
-      title: query.song,
-      artist: query.artist,
+        (media_session.id, media_session.now_playing, dj.clone())
+      }
     };
 
-    if current_now_playing.as_ref() == Some(&new_now_playing) {
-      return Ok(());
+    if let Some(current) = &current_now_playing {
+      if current.title == query.song && current.artist == query.artist {
+        return Ok(());
+      }
     }
 
     let now = DateTime::now();
@@ -135,13 +161,20 @@ pub async fn metadata(
     let play_history_item = PlayHistoryItem {
       id: PlayHistoryItem::uid(),
       station_id: station_id.to_string(),
-      kind: db::play_history_item::Kind::Live,
-      title: new_now_playing.title.clone(),
-      artist: new_now_playing.artist.clone(),
+      kind: db::play_history_item::Kind::Live { dj },
+      title: query.song,
+      artist: query.artist,
       deployment_id: deployment_id.clone(),
       created_at: now,
     };
 
+    // the live recordings are linked to the play history item through the now playing info
+    let new_now_playing = MediaSessionNowPlaying {
+      title: play_history_item.title.clone(),
+      artist: play_history_item.artist.clone(),
+      play_history_item_id: Some(play_history_item.id.clone()),
+    };
+
     let update = doc! {
       "$set": {
         MediaSession::KEY_NOW_PLAYING: new_now_playing,
@@ -151,18 +184,25 @@ pub async fn metadata(
     MediaSession::update_by_id(&media_session_id, update).await?;
     PlayHistoryItem::insert(&play_history_item).await?;
 
+    Event::dispatch_detached(NowPlayingChanged {
+      station_id: station_id.to_string(),
+      title: play_history_item.title,
+      artist: play_history_item.artist,
+    });
+
     Ok(())
   }
 
   use MetadataError::*;
   let (status, message, is_auth_fail) =
-    match metadata(local_addr, remote_addr, head, deployment_id).await {
+    match metadata(local_addr, remote_addr, head, deployment_id, media_sessions).await {
       Ok(()) => (StatusCode::OK, String::from("metadata info updated"), false),
       Err(e) => {
         let message = format!("{}", e);
         let (status, is_auth_fail) = match e {
           Db(_) => (StatusCode::INTERNAL_SERVER_ERROR, false),
           InvalidCredentials => (StatusCode::UNAUTHORIZED, true),
+          OtherSource => (StatusCode::FORBIDDEN, false),
           Query(_) => (StatusCode::BAD_REQUEST, false),
           InvalidQuery(_) => (StatusCode::BAD_REQUEST, false),
           NotFound => (StatusCode::NOT_FOUND, false),
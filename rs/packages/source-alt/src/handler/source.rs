@@ -4,7 +4,9 @@ use std::net::SocketAddr;
 
 use db::{
   deployment::Deployment,
+  media_session::MediaSession,
   station::{OwnerDeploymentInfo, Station},
+  station_dj::{SourceAuthError, StationDj},
   Model,
 };
 use drop_tracer::DropTracer;
@@ -15,14 +17,20 @@ use hyper::{
 };
 use log::*;
 use media::{
-  channel::Sender, drop::MapEntryRelease, handle::live::LiveError, Handle, Info, Kind,
-  MediaSessionMap,
+  channel::Sender,
+  drop::MapEntryRelease,
+  handle::{
+    live::LiveError,
+    live_input::{LiveInput, LiveInputError},
+  },
+  Handle, Info, Kind, MediaSessionMap,
 };
 use serde_util::DateTime;
 use shutdown::Shutdown;
 use stream_util::IntoTryBytesStream;
 use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
+use tokio::sync::watch;
 
 #[allow(clippy::declare_interior_mutable_const)]
 const WWW_AUTHENTICATE_BASIC_AUTH: HeaderValue =
@@ -44,6 +52,8 @@ enum IntermediateError {
   Db(#[from] mongodb::error::Error),
   #[error("content type required")]
   ContentTypeRequired,
+  #[error("content type not supported: {0}")]
+  ContentTypeNotSupported(String),
   #[error("station not found")]
   StationNotFound,
   #[error("deployment not found")]
@@ -54,6 +64,10 @@ enum IntermediateError {
   BasicAuthMissing,
   #[error("basic auth mismatch")]
   BasicAuthMismatch,
+  #[error("dj disabled: {0}")]
+  DjDisabled(String),
+  #[error("dj not allowed now: {0}")]
+  DjNotAllowedNow(String),
   #[error("mountpoint in use")]
   MountpoingInUse,
 }
@@ -83,6 +97,17 @@ pub async fn source(
       Some(t) => t.to_string(),
     };
 
+    let input = match Station::get_by_id(&station_id).await? {
+      None => return Err(IntermediateError::StationNotFound),
+      Some(station) => match LiveInput::new(&content_type, station.live_input.transcode) {
+        None => return Err(IntermediateError::ContentTypeNotSupported(content_type)),
+        Some(input) => input,
+      },
+    };
+
+    // the content type of the broadcasted stream, not the one sent by the encoder
+    let content_type = input.content_type().to_string();
+
     let task_id = Station::random_owner_task_id();
 
     let info = OwnerDeploymentInfo {
@@ -140,8 +165,6 @@ pub async fn source(
       Some(h) => h.as_bytes().eq_ignore_ascii_case(b"100-continue"),
     };
 
-    let password = station.source_password;
-
     let basic_auth = match head.headers.get("authorization") {
       None => None,
       Some(header) => match header.to_str() {
@@ -158,10 +181,27 @@ pub async fn source(
       Some(creds) => (creds.user_id, creds.password),
     };
 
-    if auth_user != "source" || auth_password != password {
-      return Err(IntermediateError::BasicAuthMismatch);
+    let dj = match StationDj::authenticate_source(&station, &auth_user, &auth_password).await {
+      Ok(dj) => dj,
+      Err(SourceAuthError::Db(e)) => return Err(IntermediateError::Db(e)),
+      Err(SourceAuthError::Mismatch) => return Err(IntermediateError::BasicAuthMismatch),
+      Err(SourceAuthError::Disabled(username)) => {
+        return Err(IntermediateError::DjDisabled(username))
+      }
+      Err(SourceAuthError::NotAllowedNow(username)) => {
+        return Err(IntermediateError::DjNotAllowedNow(username))
+      }
     };
 
+    // a dj with an allowed time is disconnected when it ends
+    // the end is moved (or cancelled) when the allowed time of the dj is updated, see Handle::set_dj_allowed_until
+    let (dj_allowed_until, allowed_until) = watch::channel(
+      dj.as_ref()
+        .and_then(|dj| dj.allowed_until(*DateTime::now(), station.tz())),
+    );
+
+    let media_session_id = MediaSession::uid();
+
     let (sender, map_entry_release) = {
       let mut lock = media_sessions.lock(&station_id).await;
       match &*lock {
@@ -177,8 +217,17 @@ pub async fn source(
       }
 
       let info = Info::new(Kind::Live, task_id.clone(), content_type.clone());
-      let sender = Sender::new(station_id.clone(), info);
-      let handle = Handle::new(sender.clone());
+      // take over the channel of the running fallback source (if any) so its listeners are kept connected
+      let sender = match &*lock {
+        Some(handle) => handle.handover(info),
+        None => Sender::new(station_id.clone(), info),
+      };
+      let handle = Handle::live(
+        sender.clone(),
+        media_session_id.clone(),
+        dj.as_ref().map(|dj| dj.id.clone()),
+        dj_allowed_until,
+      );
       let map_entry_release =
         MapEntryRelease::new(station_id.clone(), task_id.clone(), media_sessions.clone());
       *lock = Some(handle);
@@ -216,8 +265,12 @@ pub async fn source(
       sender,
       map_entry_release,
       task_id,
+      media_session_id,
       real_ip,
       is_continue,
+      input,
+      dj,
+      allowed_until,
       dropper,
     )))
   }
@@ -248,11 +301,24 @@ pub async fn source(
           status = StatusCode::FORBIDDEN;
           message = "content-type header is required".into();
         }
+        ContentTypeNotSupported(content_type) => {
+          status = StatusCode::FORBIDDEN;
+          message =
+            format!("content-type {content_type} is not supported, send audio/mpeg or audio/aac");
+        }
         BasicAuthMismatch => {
           status = StatusCode::UNAUTHORIZED;
           message = "authorization credentials are required".into();
           headers.append(WWW_AUTHENTICATE, WWW_AUTHENTICATE_BASIC_AUTH);
         }
+        DjDisabled(username) => {
+          status = StatusCode::FORBIDDEN;
+          message = format!("dj {username} is disabled");
+        }
+        DjNotAllowedNow(username) => {
+          status = StatusCode::FORBIDDEN;
+          message = format!("dj {username} is not allowed to broadcast at this time");
+        }
         BasicAuthMissing => {
           status = StatusCode::UNAUTHORIZED;
           message = "authorization credentials doesn't match".into();
@@ -294,8 +360,61 @@ pub async fn source(
     // passthrough
     Ok(None) => {}
 
-    Ok(Some((sender, map_entry_release, task_id, real_ip, is_continue, dropper))) => {
+    Ok(Some((
+      sender,
+      map_entry_release,
+      task_id,
+      media_session_id,
+      real_ip,
+      is_continue,
+      input,
+      dj,
+      allowed_until,
+      dropper,
+    ))) => {
       tokio::spawn(async move {
+        let kick = dj.as_ref().map(|_| {
+          let media_sessions = media_sessions.clone();
+          let station_id = station_id.clone();
+          let task_id = task_id.clone();
+          let mut allowed_until = allowed_until;
+          tokio::spawn(async move {
+            loop {
+              let until = *allowed_until.borrow_and_update();
+              let end = async {
+                match until {
+                  None => std::future::pending().await,
+                  Some(until) => {
+                    let ms = (until - *DateTime::now()).whole_milliseconds().max(0) as u64;
+                    tokio::time::sleep(std::time::Duration::from_millis(ms)).await
+                  }
+                }
+              };
+
+              tokio::select! {
+                _ = end => break,
+                r = allowed_until.changed() => {
+                  // the handle of the live source was dropped
+                  if r.is_err() {
+                    return;
+                  }
+                }
+              }
+            }
+
+            if media_sessions
+              .terminate_task(&station_id, &task_id)
+              .await
+              .is_some()
+            {
+              info!(
+                target: "source-alt",
+                "live source of station {station_id} disconnected at the end of the dj allowed time"
+              );
+            }
+          })
+        });
+
         let (reader, mut writer) = socket.into_split();
 
         let user_agent = head
@@ -323,13 +442,20 @@ pub async fn source(
             deployment_id,
             task_id,
             station_id,
+            media_session_id,
             reader.into_bytes_stream(1000),
+            input,
+            dj.as_ref().map(StationDj::attribution),
             request_document,
+            media_sessions.mailer().clone(),
             shutdown,
             drop_tracer,
           )
           .await;
           drop(map_entry_release);
+          if let Some(kick) = kick {
+            kick.abort();
+          }
           r
         };
 
@@ -357,8 +483,11 @@ pub async fn source(
 
           Err(e) => {
             let (status, body) = match e {
-              LiveError::Db(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal error creating live media session, try again later or report it to the administrators"),
-              LiveError::Data(_) => (StatusCode::FORBIDDEN, "io error reading data"),
+              LiveError::Db(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal error creating live media session, try again later or report it to the administrators".to_string()),
+              LiveError::Data(_) => (StatusCode::FORBIDDEN, "io error reading data".to_string()),
+              LiveError::Input(LiveInputError::Invalid(e)) => (StatusCode::FORBIDDEN, format!("invalid audio data: {e}, check the format settings of the encoder")),
+              LiveError::Input(LiveInputError::FormatMismatch(format)) => (StatusCode::FORBIDDEN, format!("the audio format {format} does not match the stream format of the station, change the format settings of the encoder or enable the live input transcoding")),
+              LiveError::Input(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal error transcoding the audio, try again later or report it to the administrators".to_string()),
             };
 
             if is_continue {
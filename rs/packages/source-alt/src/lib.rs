@@ -127,7 +127,15 @@ pub async fn handle_connection(
     (&Method::GET, "/status") => status(socket, head).await,
     (_, "/status") => method_not_allowed(socket, head, HeaderValue::from_static("GET")).await,
     (&Method::GET, "/admin/metadata") => {
-      metadata(socket, remote_addr, local_addr, head, deployment_id.clone()).await
+      metadata(
+        socket,
+        remote_addr,
+        local_addr,
+        head,
+        deployment_id.clone(),
+        media_sessions.clone(),
+      )
+      .await
     }
     (_, "/admin/metadata") => {
       method_not_allowed(socket, head, HeaderValue::from_static("GET")).await
@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use constants::{
+  STREAM_HLS_PLAYLIST_SEGMENTS, STREAM_HLS_PLAYLIST_WAIT_SECS, STREAM_HLS_SEGMENT_DURATION_SECS,
+};
+use db::station::Station;
+use db::Model;
+use hyper::header::{ACCEPT_RANGES, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::http::HeaderValue;
+use hyper::{Body, StatusCode};
+use prex::{handler::Handler, Next, Request, Response};
+use std::time::{Duration, SystemTime};
+
+use super::session::{self, HlsQuery};
+use super::{proxy, HlsMap, HlsStream};
+use crate::transfer_map::TransferTracer;
+use crate::{
+  EndReason, StreamError, ACCEPT_RANGES_NONE, CACHE_CONTROL_NO_CACHE, CONTENT_TYPE_MPEG,
+};
+
+#[allow(clippy::declare_interior_mutable_const)]
+const CONTENT_TYPE_APPLE_MPEGURL: HeaderValue =
+  HeaderValue::from_static("application/vnd.apple.mpegurl");
+
+/// bandwidth announced in the master playlist when the stream bitrate is not known
+const DEFAULT_BANDWIDTH: u64 = 128_000;
+
+/// segments are immutable, they can be cached while they are in the playlist window
+fn cache_control_segment() -> HeaderValue {
+  let max_age = STREAM_HLS_SEGMENT_DURATION_SECS * STREAM_HLS_PLAYLIST_SEGMENTS as u64;
+  HeaderValue::from_str(&format!("public, max-age={max_age}")).unwrap()
+}
+
+fn playlist_response(m3u8: String) -> Response {
+  let mut res = Response::new(StatusCode::OK);
+  res
+    .headers_mut()
+    .append(CONTENT_TYPE, CONTENT_TYPE_APPLE_MPEGURL);
+  res
+    .headers_mut()
+    .append(CACHE_CONTROL, CACHE_CONTROL_NO_CACHE);
+  *res.body_mut() = Body::from(m3u8);
+  res
+}
+
+async fn get_station(station_id: &str) -> Result<Station, StreamError> {
+  match Station::get_by_id(station_id).await? {
+    Some(station) => Ok(station),
+    None => Err(StreamError::StationNotFound(station_id.to_string())),
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct HlsPlaylistHandler {
+  hls: HlsMap,
+  transfer_map: TransferTracer,
+}
+
+impl HlsPlaylistHandler {
+  pub fn new(hls: HlsMap, transfer_map: TransferTracer) -> Self {
+    Self { hls, transfer_map }
+  }
+
+  /// admits a new listener the same way as a progressive connection
+  /// and opens its session once the stream has segments
+  async fn open_session(
+    &self,
+    req: &Request,
+    station: &Station,
+    session_id: Option<String>,
+  ) -> Result<(String, HlsStream), StreamError> {
+    let start_time = SystemTime::now();
+
+    let listener_slot = crate::admit_listener(
+      req,
+      station,
+      &self.hls.deployment_id,
+      &self.transfer_map,
+      &self.hls.drop_tracer,
+    )
+    .await?;
+
+    let stream = self.hls.get_or_start(station).await?;
+
+    if !stream
+      .wait_ready(Duration::from_secs(STREAM_HLS_PLAYLIST_WAIT_SECS))
+      .await
+    {
+      return Err(StreamError::HlsNotReady);
+    }
+
+    let connection = crate::open_connection(
+      req,
+      station,
+      &self.hls.deployment_id,
+      listener_slot,
+      &self.hls.drop_tracer,
+      start_time,
+    )
+    .await?;
+
+    let session_id = session_id.unwrap_or_else(|| connection.id.clone());
+
+    self
+      .hls
+      .sessions
+      .insert(session_id.clone(), station.id.clone(), connection);
+
+    Ok((session_id, stream))
+  }
+
+  async fn handle(&self, req: Request) -> Result<Response, StreamError> {
+    let station_id = req.param("id").unwrap();
+    let session_id = req.qs::<HlsQuery>().unwrap_or_default().session;
+
+    let station = get_station(station_id).await?;
+
+    if let Some(target) = proxy::forward_target(&req, &station, &self.hls.deployment_id) {
+      return Ok(proxy::forward(&req, &self.hls.deployment_id, target).await?);
+    }
+
+    let session_id = match session_id {
+      Some(session_id) => session_id,
+      None => {
+        // a new listener gets a master playlist that points to its session
+        let (session_id, stream) = self.open_session(&req, &station, None).await?;
+        let bandwidth = stream.bandwidth().unwrap_or(DEFAULT_BANDWIDTH);
+        return Ok(playlist_response(super::master_playlist(
+          &session_id,
+          bandwidth,
+        )));
+      }
+    };
+
+    let stream = if self.hls.sessions.touch(&station.id, &session_id) {
+      // the access settings are read from the station on every playlist reload
+      // so a change also applies to the open sessions
+      if let Err(denied) = crate::check_listener_access(&req, &station.listener_access) {
+        crate::record_rejection(
+          &req,
+          &station,
+          &self.hls.deployment_id,
+          denied.into(),
+          self.hls.drop_tracer.token(),
+        );
+        self
+          .hls
+          .sessions
+          .close(&session_id, EndReason::ListenerAccess);
+        return Err(StreamError::ListenerAccessDenied(denied));
+      }
+
+      if self.transfer_map.is_blocked(&station.account_id) {
+        return Err(StreamError::TransferLimitReached);
+      }
+
+      let stream = self.hls.get_or_start(&station).await?;
+
+      if !stream
+        .wait_ready(Duration::from_secs(STREAM_HLS_PLAYLIST_WAIT_SECS))
+        .await
+      {
+        return Err(StreamError::HlsNotReady);
+      }
+
+      stream
+    } else {
+      // the session was closed for inactivity or it was opened by a deployment
+      // that no longer owns the station, it is opened again with the same id
+      if !session::is_valid_id(&session_id) {
+        return Err(StreamError::HlsSessionNotFound);
+      }
+
+      let (_, stream) = self
+        .open_session(&req, &station, Some(session_id.clone()))
+        .await?;
+
+      stream
+    };
+
+    match stream.playlist(&session_id) {
+      Some(m3u8) => Ok(playlist_response(m3u8)),
+      None => Err(StreamError::HlsNotReady),
+    }
+  }
+}
+
+#[async_trait]
+impl Handler for HlsPlaylistHandler {
+  async fn call(&self, req: Request, _: Next) -> Response {
+    self.handle(req).await.into()
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct HlsSegmentHandler {
+  hls: HlsMap,
+  transfer_map: TransferTracer,
+}
+
+impl HlsSegmentHandler {
+  pub fn new(hls: HlsMap, transfer_map: TransferTracer) -> Self {
+    Self { hls, transfer_map }
+  }
+
+  async fn handle(&self, req: Request) -> Result<Response, StreamError> {
+    let station_id = req.param("id").unwrap();
+
+    let sequence = match req.param("segment").and_then(|s| s.parse::<u64>().ok()) {
+      Some(sequence) => sequence,
+      None => return Err(StreamError::HlsSegmentNotFound),
+    };
+
+    let session_id = match req.qs::<HlsQuery>().unwrap_or_default().session {
+      Some(session_id) => session_id,
+      None => return Err(StreamError::HlsSessionNotFound),
+    };
+
+    if !self.hls.sessions.touch(station_id, &session_id) {
+      // the station could be segmented by another deployment
+      let station = get_station(station_id).await?;
+      if let Some(target) = proxy::forward_target(&req, &station, &self.hls.deployment_id) {
+        return Ok(proxy::forward(&req, &self.hls.deployment_id, target).await?);
+      }
+
+      return Err(StreamError::HlsSessionNotFound);
+    }
+
+    let stream = match self.hls.get(station_id) {
+      Some(stream) => stream,
+      None => return Err(StreamError::HlsSegmentNotFound),
+    };
+
+    if self.transfer_map.is_blocked(&stream.account_id) {
+      return Err(StreamError::TransferLimitReached);
+    }
+
+    let segment = match stream.segment(sequence) {
+      Some(segment) => segment,
+      None => return Err(StreamError::HlsSegmentNotFound),
+    };
+
+    self
+      .transfer_map
+      .increment(&stream.account_id, segment.data.len());
+    self
+      .hls
+      .sessions
+      .add_transfer(&session_id, segment.data.len());
+
+    let mut res = Response::new(StatusCode::OK);
+    res.headers_mut().append(CONTENT_TYPE, CONTENT_TYPE_MPEG);
+    res.headers_mut().append(
+      CONTENT_LENGTH,
+      HeaderValue::from_str(&segment.data.len().to_string()).unwrap(),
+    );
+    res.headers_mut().append(ACCEPT_RANGES, ACCEPT_RANGES_NONE);
+    res
+      .headers_mut()
+      .append(CACHE_CONTROL, cache_control_segment());
+    *res.body_mut() = Body::from(segment.data);
+    Ok(res)
+  }
+}
+
+#[async_trait]
+impl Handler for HlsSegmentHandler {
+  async fn call(&self, req: Request, _: Next) -> Response {
+    self.handle(req).await.into()
+  }
+}
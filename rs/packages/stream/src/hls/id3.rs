@@ -0,0 +1,119 @@
+//! ID3v2.4 tags for HLS packed audio segments
+//!
+//! every packed audio segment must start with an ID3 tag with a PRIV frame
+//! that contains the MPEG-2 (90kHz) timestamp of the first sample of the segment
+//! we also add TIT2 (title) and TPE1 (artist) frames as timed metadata with the now playing info
+
+use bytes::{BufMut, Bytes, BytesMut};
+use db::media_session::MediaSessionNowPlaying;
+use std::time::Duration;
+
+const TRANSPORT_STREAM_TIMESTAMP_OWNER: &[u8] = b"com.apple.streaming.transportStreamTimestamp\0";
+
+/// text encoding byte of the text frames
+const ENCODING_UTF8: u8 = 0x03;
+
+/// the timestamps are 33 bit values
+const TIMESTAMP_MASK: u64 = (1 << 33) - 1;
+
+/// converts the elapsed time since the start of the stream to a 90kHz timestamp
+pub fn timestamp(elapsed: Duration) -> u64 {
+  (elapsed.as_nanos() * 90_000 / 1_000_000_000) as u64 & TIMESTAMP_MASK
+}
+
+fn syncsafe(n: usize) -> [u8; 4] {
+  [
+    ((n >> 21) & 0x7f) as u8,
+    ((n >> 14) & 0x7f) as u8,
+    ((n >> 7) & 0x7f) as u8,
+    (n & 0x7f) as u8,
+  ]
+}
+
+fn put_frame(buf: &mut BytesMut, id: &[u8; 4], data: &[&[u8]]) {
+  let len = data.iter().map(|d| d.len()).sum();
+  buf.put_slice(id);
+  buf.put_slice(&syncsafe(len));
+  buf.put_u16(0);
+  for d in data {
+    buf.put_slice(d);
+  }
+}
+
+/// creates the ID3 tag to prepend to a segment
+pub fn tag(elapsed: Duration, now_playing: Option<&MediaSessionNowPlaying>) -> Bytes {
+  let mut frames = BytesMut::new();
+
+  put_frame(
+    &mut frames,
+    b"PRIV",
+    &[
+      TRANSPORT_STREAM_TIMESTAMP_OWNER,
+      &timestamp(elapsed).to_be_bytes(),
+    ],
+  );
+
+  if let Some(now_playing) = now_playing {
+    put_frame(
+      &mut frames,
+      b"TIT2",
+      &[&[ENCODING_UTF8], now_playing.title.as_bytes()],
+    );
+
+    if let Some(artist) = &now_playing.artist {
+      put_frame(&mut frames, b"TPE1", &[&[ENCODING_UTF8], artist.as_bytes()]);
+    }
+  }
+
+  let mut buf = BytesMut::with_capacity(10 + frames.len());
+  buf.put_slice(b"ID3");
+  // version 2.4.0
+  buf.put_slice(&[4, 0]);
+  // flags
+  buf.put_u8(0);
+  buf.put_slice(&syncsafe(frames.len()));
+  buf.put_slice(&frames);
+
+  buf.freeze()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn timestamp_is_90khz() {
+    assert_eq!(timestamp(Duration::from_secs(1)), 90_000);
+    assert_eq!(timestamp(Duration::from_millis(1500)), 135_000);
+  }
+
+  #[test]
+  fn tag_has_priv_timestamp() {
+    let tag = tag(Duration::from_secs(2), None);
+    assert_eq!(&tag[0..5], b"ID3\x04\x00");
+
+    let body_len = tag.len() - 10;
+    assert_eq!(&tag[6..10], &syncsafe(body_len));
+
+    assert_eq!(&tag[10..14], b"PRIV");
+    let owner_end = 20 + TRANSPORT_STREAM_TIMESTAMP_OWNER.len();
+    assert_eq!(&tag[20..owner_end], TRANSPORT_STREAM_TIMESTAMP_OWNER);
+    assert_eq!(&tag[owner_end..owner_end + 8], &180_000u64.to_be_bytes());
+    assert_eq!(tag.len(), owner_end + 8);
+  }
+
+  #[test]
+  fn tag_has_now_playing_frames() {
+    let now_playing = MediaSessionNowPlaying {
+      title: "Title".into(),
+      artist: Some("Artist".into()),
+      play_history_item_id: None,
+    };
+
+    let tag = tag(Duration::ZERO, Some(&now_playing));
+    let tit2 = tag.windows(4).position(|w| w == b"TIT2").unwrap();
+    assert_eq!(&tag[tit2 + 10..tit2 + 16], b"\x03Title");
+    let tpe1 = tag.windows(4).position(|w| w == b"TPE1").unwrap();
+    assert_eq!(&tag[tpe1 + 10..tpe1 + 17], b"\x03Artist");
+  }
+}
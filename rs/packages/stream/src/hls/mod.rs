@@ -0,0 +1,524 @@
+//! native HLS output
+//!
+//! the output of the station media session is cut into packed audio (mp3) segments
+//! of STREAM_HLS_SEGMENT_DURATION_SECS and a sliding window of the last segments
+//! is served as a live playlist
+//!
+//! a station is only segmented while its HLS stream is being requested,
+//! the segmenter task stops after STREAM_HLS_IDLE_SHUTDOWN_SECS without requests
+//!
+//! segments are only kept in the deployment that owns the station media session,
+//! the requests that land in other deployments are forwarded to it (see [`proxy`])
+
+pub mod handler;
+pub mod id3;
+pub mod proxy;
+pub mod session;
+
+use bytes::{Bytes, BytesMut};
+use constants::{
+  STREAM_HLS_IDLE_SHUTDOWN_SECS, STREAM_HLS_PLAYLIST_SEGMENTS, STREAM_HLS_SEGMENT_DURATION_SECS,
+};
+use db::media_session::MediaSessionNowPlaying;
+use db::station::Station;
+use drop_tracer::{DropTracer, Token};
+use log::*;
+use media::channel::{Receiver, RecvError};
+use media::MediaSessionMap;
+use parking_lot::Mutex;
+use serde_util::DateTime;
+use shutdown::Shutdown;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::StreamError;
+use session::HlsSessionMap;
+
+/// max amount of unparseable data that we keep waiting for a frame boundary
+const MAX_PENDING_LEN: usize = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+  pub sequence: u64,
+  pub duration: Duration,
+  pub program_date_time: DateTime,
+  pub now_playing: Option<MediaSessionNowPlaying>,
+  pub discontinuity: bool,
+  /// the ID3 tag followed by the mp3 frames
+  pub data: Bytes,
+}
+
+/// cuts an mp3 byte stream at frame boundaries
+///
+/// the frame headers are parsed once as the data arrives,
+/// `scanned` is the offset in `pending` up to which the frames were already counted
+#[derive(Debug)]
+pub struct Segmenter {
+  target: Duration,
+  pending: BytesMut,
+  /// offset of the first frame of the segment in pending
+  start: Option<usize>,
+  scanned: usize,
+  /// duration of the complete frames between start and scanned
+  duration: Duration,
+}
+
+impl Segmenter {
+  pub fn new(target: Duration) -> Self {
+    Self {
+      target,
+      pending: BytesMut::new(),
+      start: None,
+      scanned: 0,
+      duration: Duration::ZERO,
+    }
+  }
+
+  /// discards the incomplete segment, used when the source changes
+  pub fn reset(&mut self) {
+    self.pending.clear();
+    self.start = None;
+    self.scanned = 0;
+    self.duration = Duration::ZERO;
+  }
+
+  /// adds data to the segmenter and returns the frames and the duration of a segment
+  /// when at least target duration of complete frames is available
+  pub fn push(&mut self, data: &[u8]) -> Option<(Bytes, Duration)> {
+    self.pending.extend_from_slice(data);
+
+    loop {
+      let buf = &self.pending[self.scanned..];
+
+      let header = match mp3::mpeg_header(buf) {
+        Some(header) => header,
+        None => {
+          // not at a frame boundary, skip a byte until the stream is in sync again
+          if buf.len() < 4 {
+            break;
+          }
+          self.scanned += 1;
+          continue;
+        }
+      };
+
+      if header.len > buf.len() {
+        break;
+      }
+
+      self.start.get_or_insert(self.scanned);
+      self.scanned += header.len;
+      self.duration += Duration::from_nanos(
+        header.samples as u64 * 1_000_000_000 / header.format.sample_rate as u64,
+      );
+
+      if self.duration >= self.target {
+        let mut segment = self.pending.split_to(self.scanned);
+        let frames = segment.split_off(self.start.unwrap_or(0));
+        let duration = self.duration;
+
+        self.start = None;
+        self.scanned = 0;
+        self.duration = Duration::ZERO;
+
+        return Some((frames.freeze(), duration));
+      }
+    }
+
+    if self.pending.len() > MAX_PENDING_LEN {
+      self.reset();
+    }
+
+    None
+  }
+}
+
+/// renders the master playlist that points a new listener to its session media playlist
+pub fn master_playlist(session_id: &str, bandwidth: u64) -> String {
+  let mut m3u8 = String::new();
+
+  let _ = writeln!(m3u8, "#EXTM3U");
+  let _ = writeln!(m3u8, "#EXT-X-VERSION:3");
+  // mp4a.40.34 is the codec id of mp3 in HLS
+  let _ = writeln!(
+    m3u8,
+    "#EXT-X-STREAM-INF:BANDWIDTH={bandwidth},CODECS=\"mp4a.40.34\""
+  );
+  let _ = writeln!(m3u8, "live.m3u8?session={session_id}");
+
+  m3u8
+}
+
+/// renders the live playlist for the sliding window of segments
+pub fn playlist(
+  media_sequence: u64,
+  discontinuity_sequence: u64,
+  segments: &VecDeque<Segment>,
+  session_id: &str,
+) -> String {
+  let target_duration = segments
+    .iter()
+    .map(|s| s.duration.as_secs_f64().ceil() as u64)
+    .max()
+    .unwrap_or(STREAM_HLS_SEGMENT_DURATION_SECS)
+    .max(STREAM_HLS_SEGMENT_DURATION_SECS);
+
+  let mut m3u8 = String::new();
+
+  // write! to a String cannot fail
+  let _ = writeln!(m3u8, "#EXTM3U");
+  let _ = writeln!(m3u8, "#EXT-X-VERSION:3");
+  let _ = writeln!(m3u8, "#EXT-X-TARGETDURATION:{target_duration}");
+  let _ = writeln!(m3u8, "#EXT-X-MEDIA-SEQUENCE:{media_sequence}");
+  let _ = writeln!(
+    m3u8,
+    "#EXT-X-DISCONTINUITY-SEQUENCE:{discontinuity_sequence}"
+  );
+
+  for segment in segments {
+    if segment.discontinuity {
+      let _ = writeln!(m3u8, "#EXT-X-DISCONTINUITY");
+    }
+
+    let title = crate::icy::stream_title(segment.now_playing.as_ref()).replace(['\r', '\n'], " ");
+
+    let _ = writeln!(
+      m3u8,
+      "#EXT-X-PROGRAM-DATE-TIME:{}",
+      segment.program_date_time
+    );
+    let _ = writeln!(
+      m3u8,
+      "#EXTINF:{:.3},{title}",
+      segment.duration.as_secs_f64()
+    );
+    let _ = writeln!(m3u8, "{}.mp3?session={session_id}", segment.sequence);
+  }
+
+  m3u8
+}
+
+#[derive(Debug)]
+struct HlsState {
+  media_sequence: u64,
+  discontinuity_sequence: u64,
+  segments: VecDeque<Segment>,
+  last_access: Instant,
+}
+
+/// the segments of a station that is being segmented
+#[derive(Debug, Clone)]
+pub struct HlsStream {
+  pub station_id: String,
+  pub account_id: String,
+  state: Arc<Mutex<HlsState>>,
+  /// count of segments created so far
+  segment_count: Arc<watch::Sender<u64>>,
+}
+
+impl HlsStream {
+  fn new(station_id: String, account_id: String) -> Self {
+    Self {
+      station_id,
+      account_id,
+      state: Arc::new(Mutex::new(HlsState {
+        media_sequence: 0,
+        discontinuity_sequence: 0,
+        segments: VecDeque::new(),
+        last_access: Instant::now(),
+      })),
+      segment_count: Arc::new(watch::channel(0).0),
+    }
+  }
+
+  fn push_segment(&self, segment: Segment) {
+    {
+      let mut state = self.state.lock();
+      state.segments.push_back(segment);
+      while state.segments.len() > STREAM_HLS_PLAYLIST_SEGMENTS {
+        if let Some(removed) = state.segments.pop_front() {
+          state.media_sequence = removed.sequence + 1;
+          if removed.discontinuity {
+            state.discontinuity_sequence += 1;
+          }
+        }
+      }
+    }
+
+    self.segment_count.send_modify(|n| *n += 1);
+  }
+
+  fn idle_for(&self) -> Duration {
+    self.state.lock().last_access.elapsed()
+  }
+
+  /// waits until the stream has at least one segment, returns false on timeout
+  pub async fn wait_ready(&self, timeout: Duration) -> bool {
+    let mut rx = self.segment_count.subscribe();
+    let ready = async move { rx.wait_for(|n| *n > 0).await.is_ok() };
+    tokio::time::timeout(timeout, ready).await.unwrap_or(false)
+  }
+
+  /// the bitrate of the last segment in bits per second, used in the master playlist
+  pub fn bandwidth(&self) -> Option<u64> {
+    let state = self.state.lock();
+    let segment = state.segments.back()?;
+    let secs = segment.duration.as_secs_f64();
+    if secs == 0.0 {
+      return None;
+    }
+    Some((segment.data.len() as f64 * 8.0 / secs).ceil() as u64)
+  }
+
+  /// the live playlist of a session for the current window, None if there are no segments yet
+  pub fn playlist(&self, session_id: &str) -> Option<String> {
+    let mut state = self.state.lock();
+    state.last_access = Instant::now();
+    if state.segments.is_empty() {
+      return None;
+    }
+    Some(playlist(
+      state.media_sequence,
+      state.discontinuity_sequence,
+      &state.segments,
+      session_id,
+    ))
+  }
+
+  pub fn segment(&self, sequence: u64) -> Option<Segment> {
+    let mut state = self.state.lock();
+    state.last_access = Instant::now();
+    state
+      .segments
+      .iter()
+      .find(|s| s.sequence == sequence)
+      .cloned()
+  }
+}
+
+/// the HLS streams and listener sessions of this deployment
+#[derive(Debug, Clone)]
+pub struct HlsMap {
+  deployment_id: String,
+  media_sessions: MediaSessionMap,
+  shutdown: Shutdown,
+  drop_tracer: DropTracer,
+  streams: Arc<Mutex<HashMap<String, HlsStream>>>,
+  sessions: HlsSessionMap,
+}
+
+impl HlsMap {
+  pub fn new(
+    deployment_id: String,
+    media_sessions: MediaSessionMap,
+    shutdown: Shutdown,
+    drop_tracer: DropTracer,
+  ) -> Self {
+    Self {
+      deployment_id,
+      media_sessions,
+      shutdown,
+      drop_tracer,
+      streams: Arc::new(Mutex::new(HashMap::new())),
+      sessions: HlsSessionMap::new(),
+    }
+  }
+
+  /// starts the task that closes the idle listener sessions
+  pub fn start_background_task(&self) {
+    self.sessions.start_background_task(self.shutdown.clone());
+  }
+
+  /// returns the stream of the station only if it is already being segmented
+  pub fn get(&self, station_id: &str) -> Option<HlsStream> {
+    self.streams.lock().get(station_id).cloned()
+  }
+
+  /// returns the stream of the station, starting the segmenter if needed
+  pub async fn get_or_start(&self, station: &Station) -> Result<HlsStream, StreamError> {
+    let station_id = station.id.as_str();
+
+    if let Some(stream) = self.get(station_id) {
+      return Ok(stream);
+    }
+
+    let rx = self.media_sessions.subscribe(&station.id).await?;
+
+    if rx.content_type() != "audio/mpeg" {
+      return Err(StreamError::HlsUnsupportedContentType(
+        rx.content_type().to_string(),
+      ));
+    }
+
+    let stream = {
+      let mut streams = self.streams.lock();
+      // another request could have started the segmenter while we were subscribing
+      if let Some(stream) = streams.get(station_id) {
+        return Ok(stream.clone());
+      }
+
+      let stream = HlsStream::new(station.id.clone(), station.account_id.clone());
+      streams.insert(station_id.to_string(), stream.clone());
+      stream
+    };
+
+    info!("HLS START station {station_id}");
+
+    tokio::spawn(
+      self
+        .clone()
+        .run(stream.clone(), rx, self.drop_tracer.token()),
+    );
+
+    Ok(stream)
+  }
+
+  async fn run(self, stream: HlsStream, mut rx: Receiver, token: Token) {
+    let station_id = stream.station_id.clone();
+
+    let mut segmenter = Segmenter::new(Duration::from_secs(STREAM_HLS_SEGMENT_DURATION_SECS));
+    let mut now_playing = rx.now_playing();
+    let mut sequence = 0u64;
+    let mut elapsed = Duration::ZERO;
+    let mut last_end: Option<DateTime> = None;
+    let mut discontinuity = false;
+
+    let mut idle_check = tokio::time::interval(Duration::from_secs(5));
+    let signal = self.shutdown.signal();
+    tokio::pin!(signal);
+
+    let end_reason = loop {
+      tokio::select! {
+        _ = &mut signal => break "shutdown",
+
+        _ = idle_check.tick() => {
+          if stream.idle_for() > Duration::from_secs(STREAM_HLS_IDLE_SHUTDOWN_SECS) {
+            break "idle";
+          }
+        }
+
+        r = rx.recv() => match r {
+          Err(RecvError::Lagged(_)) => continue,
+
+          Err(RecvError::Closed) => {
+            match self.media_sessions.subscribe(&station_id).await {
+              Ok(new_rx) if new_rx.content_type() == "audio/mpeg" => {
+                rx = new_rx;
+                now_playing = rx.now_playing();
+                segmenter.reset();
+                discontinuity = true;
+              }
+              Ok(_) => break "resubscribe-content-type",
+              Err(_) => break "resubscribe",
+            }
+          }
+
+          Ok(bytes) => {
+            if let Some(changed) = rx.now_playing_changed() {
+              now_playing = changed;
+            }
+
+            if let Some((frames, duration)) = segmenter.push(&bytes) {
+              let tag = id3::tag(elapsed, now_playing.as_ref());
+
+              let mut data = BytesMut::with_capacity(tag.len() + frames.len());
+              data.extend_from_slice(&tag);
+              data.extend_from_slice(&frames);
+
+              let program_date_time = match last_end {
+                Some(end) => end,
+                None => (DateTime::now().inner() - duration).into(),
+              };
+
+              stream.push_segment(Segment {
+                sequence,
+                duration,
+                program_date_time,
+                now_playing: now_playing.clone(),
+                discontinuity,
+                data: data.freeze(),
+              });
+
+              sequence += 1;
+              elapsed += duration;
+              last_end = Some((program_date_time.inner() + duration).into());
+              discontinuity = false;
+            }
+          }
+        }
+      }
+    };
+
+    info!("HLS END station {station_id} reason={end_reason}");
+
+    {
+      let mut streams = self.streams.lock();
+      if let Some(current) = streams.get(&station_id) {
+        if Arc::ptr_eq(&current.state, &stream.state) {
+          streams.remove(&station_id);
+        }
+      }
+    }
+
+    drop(token);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  /// MPEG1 Layer III 128kbps 44.1kHz frame without padding, 417 bytes, 1152 samples
+  fn frames(n: usize) -> Vec<u8> {
+    let mut frame = vec![0u8; 417];
+    frame[0..4].copy_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+    frame.repeat(n)
+  }
+
+  #[test]
+  fn segmenter_cuts_at_frame_boundaries() {
+    let mut segmenter = Segmenter::new(Duration::from_secs(1));
+
+    // 1 second is 38.28 frames
+    let data = frames(50);
+    let mut segments = vec![];
+    for chunk in data.chunks(1000) {
+      if let Some(segment) = segmenter.push(chunk) {
+        segments.push(segment);
+      }
+    }
+
+    assert_eq!(segments.len(), 1);
+    let (bytes, duration) = &segments[0];
+    assert_eq!(bytes.len(), 39 * 417);
+    assert!(*duration >= Duration::from_secs(1));
+    assert_eq!(&bytes[0..2], &[0xff, 0xfb]);
+  }
+
+  #[test]
+  fn playlist_has_window_and_program_date_time() {
+    let now = DateTime::now();
+    let segment = |sequence: u64, discontinuity: bool| Segment {
+      sequence,
+      duration: Duration::from_millis(6_008),
+      program_date_time: now,
+      now_playing: None,
+      discontinuity,
+      data: Bytes::new(),
+    };
+
+    let segments = VecDeque::from(vec![segment(4, false), segment(5, true)]);
+    let m3u8 = playlist(4, 1, &segments, "s1");
+
+    assert!(m3u8.starts_with("#EXTM3U\n"));
+    assert!(m3u8.contains("#EXT-X-TARGETDURATION:7\n"));
+    assert!(m3u8.contains("#EXT-X-MEDIA-SEQUENCE:4\n"));
+    assert!(m3u8.contains("#EXT-X-DISCONTINUITY-SEQUENCE:1\n"));
+    assert!(m3u8.contains(&format!("#EXT-X-PROGRAM-DATE-TIME:{now}\n")));
+    assert!(m3u8.contains("#EXTINF:6.008,\n4.mp3?session=s1\n"));
+    assert!(m3u8.contains("#EXT-X-DISCONTINUITY\n#EXT-X-PROGRAM-DATE-TIME"));
+  }
+}
@@ -0,0 +1,138 @@
+//! forwarding of HLS requests to the deployment that segments the station
+//!
+//! the segments and the listener sessions of a station only exist in the memory
+//! of the deployment that owns the station media session, so the HLS requests
+//! that land in any other deployment are proxied to it
+
+use constants::{
+  FORWARD_IP_HEADER, INTERNAL_RELAY_REJECTION_CODE_HEADER, STREAM_HLS_FORWARDED_HEADER,
+  STREAM_HLS_FORWARD_TIMEOUT_SECS,
+};
+use db::deployment::Deployment;
+use db::station::Station;
+use db::Model;
+use hyper::header::{
+  HeaderName, ACCEPT_LANGUAGE, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, ORIGIN, REFERER,
+  RETRY_AFTER, USER_AGENT,
+};
+use hyper::Body;
+use prex::{Request, Response};
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HlsForwardError {
+  #[error("db: {0}")]
+  Db(#[from] mongodb::error::Error),
+  #[error("deployment not found: {0}")]
+  DeploymentNotFound(String),
+  #[error("deployment no port")]
+  DeploymentNoPort,
+  #[error("create request: {0}")]
+  CreateRequest(hyper::http::Error),
+  #[error("send request: {0}")]
+  SendRequest(hyper::Error),
+  #[error("forward timeout")]
+  Timeout,
+}
+
+/// returns the id of the deployment that the request must be forwarded to,
+/// None if the request must be served by this deployment
+pub fn forward_target<'a>(
+  req: &Request,
+  station: &'a Station,
+  deployment_id: &str,
+) -> Option<&'a str> {
+  // requests forwarded by another deployment are always served here to avoid loops
+  if prex::request::is_trusted_ip(req.remote_addr().ip())
+    && req.headers().contains_key(STREAM_HLS_FORWARDED_HEADER)
+  {
+    return None;
+  }
+
+  match &station.owner_deployment_info {
+    Some(info) if info.deployment_id != deployment_id => Some(&info.deployment_id),
+    _ => None,
+  }
+}
+
+/// proxies the request to the deployment `target_deployment_id` and streams back its response
+pub async fn forward(
+  req: &Request,
+  deployment_id: &str,
+  target_deployment_id: &str,
+) -> Result<Response, HlsForwardError> {
+  let deployment = match Deployment::get_by_id(target_deployment_id).await? {
+    Some(deployment) => deployment,
+    None => {
+      return Err(HlsForwardError::DeploymentNotFound(
+        target_deployment_id.to_string(),
+      ))
+    }
+  };
+
+  let port = {
+    use rand::seq::SliceRandom;
+    match deployment.stream_ports.choose(&mut rand::thread_rng()) {
+      Some(port) => *port,
+      None => return Err(HlsForwardError::DeploymentNoPort),
+    }
+  };
+
+  let path_and_query = match req.uri().path_and_query() {
+    Some(path_and_query) => path_and_query.as_str(),
+    None => req.uri().path(),
+  };
+
+  let url = format!("http://{}:{}{}", deployment.local_ip, port, path_and_query);
+
+  let mut builder = hyper::Request::builder()
+    .uri(url)
+    .header("connection", "close")
+    .header(FORWARD_IP_HEADER, req.isomorphic_ip().to_string())
+    .header(STREAM_HLS_FORWARDED_HEADER, deployment_id);
+
+  // the headers used by the listener access checks and the connection analytics
+  for name in [USER_AGENT, REFERER, ORIGIN, ACCEPT_LANGUAGE] {
+    if let Some(value) = req.headers().get(&name) {
+      builder = builder.header(name, value);
+    }
+  }
+
+  let hyper_req = builder
+    .body(Body::empty())
+    .map_err(HlsForwardError::CreateRequest)?;
+
+  let client = hyper::Client::default();
+
+  let hyper_res = match tokio::time::timeout(
+    Duration::from_secs(STREAM_HLS_FORWARD_TIMEOUT_SECS),
+    client.request(hyper_req),
+  )
+  .await
+  {
+    Err(_) => return Err(HlsForwardError::Timeout),
+    Ok(Err(e)) => return Err(HlsForwardError::SendRequest(e)),
+    Ok(Ok(res)) => res,
+  };
+
+  let mut res = Response::new(hyper_res.status());
+  for name in [CONTENT_TYPE, CONTENT_LENGTH, CACHE_CONTROL, RETRY_AFTER] {
+    if let Some(value) = hyper_res.headers().get(&name) {
+      res.headers_mut().append(name, value.clone());
+    }
+  }
+
+  if let Some(value) = hyper_res
+    .headers()
+    .get(INTERNAL_RELAY_REJECTION_CODE_HEADER)
+  {
+    res.headers_mut().append(
+      HeaderName::from_static(INTERNAL_RELAY_REJECTION_CODE_HEADER),
+      value.clone(),
+    );
+  }
+
+  *res.body_mut() = hyper_res.into_body();
+
+  Ok(res)
+}
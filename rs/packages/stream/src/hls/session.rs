@@ -0,0 +1,149 @@
+//! HLS listener sessions
+//!
+//! an HLS client makes a new request for every playlist reload and every segment,
+//! so a listener is tracked as a session that is opened by its first playlist request
+//! and identified by the `session` query param of the playlist and segment urls
+//!
+//! the session holds the listener slot and the StreamConnection of the listener
+//! the same way a progressive connection does, and it is closed after
+//! STREAM_HLS_SESSION_IDLE_SECS without requests
+
+use constants::{STREAM_CONNECTION_MAX_DURATION_SECS, STREAM_HLS_SESSION_IDLE_SECS};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use shutdown::Shutdown;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{EndReason, OpenedConnection};
+
+/// max length of a session id received in the query string
+const SESSION_ID_MAX_LEN: usize = 64;
+
+/// the query string of the playlist and segment requests
+#[derive(Debug, Default, Deserialize)]
+pub struct HlsQuery {
+  pub session: Option<String>,
+}
+
+/// session ids are the ids of the stream connections that opened them
+pub fn is_valid_id(id: &str) -> bool {
+  !id.is_empty() && id.len() <= SESSION_ID_MAX_LEN && id.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+#[derive(Debug)]
+struct HlsSession {
+  station_id: String,
+  created_at: Instant,
+  last_access: Instant,
+  connection: OpenedConnection,
+}
+
+/// the open HLS sessions of this deployment by session id
+#[derive(Debug, Clone, Default)]
+pub struct HlsSessionMap {
+  sessions: Arc<Mutex<HashMap<String, HlsSession>>>,
+}
+
+impl HlsSessionMap {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub(crate) fn insert(&self, id: String, station_id: String, connection: OpenedConnection) {
+    let now = Instant::now();
+    let session = HlsSession {
+      station_id,
+      created_at: now,
+      last_access: now,
+      connection,
+    };
+
+    // a concurrent request could have reopened the same session
+    let replaced = self.sessions.lock().insert(id, session);
+    drop(replaced);
+  }
+
+  /// marks the session as active, returns false if the session is not open in this deployment
+  pub fn touch(&self, station_id: &str, id: &str) -> bool {
+    match self.sessions.lock().get_mut(id) {
+      Some(session) if session.station_id == station_id => {
+        session.last_access = Instant::now();
+        true
+      }
+      _ => false,
+    }
+  }
+
+  /// adds the bytes of a served segment to the session connection
+  pub fn add_transfer(&self, id: &str, len: usize) {
+    if let Some(session) = self.sessions.lock().get(id) {
+      session
+        .connection
+        .transfer_bytes
+        .fetch_add(len as u64, Ordering::Relaxed);
+    }
+  }
+
+  /// closes the session, the stream connection is updated and the listener slot released
+  pub(crate) fn close(&self, id: &str, reason: EndReason) {
+    let session = self.sessions.lock().remove(id);
+    if let Some(session) = session {
+      *session.connection.end_reason.lock() = reason;
+      drop(session);
+    }
+  }
+
+  fn close_expired(&self) {
+    let idle = Duration::from_secs(STREAM_HLS_SESSION_IDLE_SECS);
+    let max_time = Duration::from_secs(STREAM_CONNECTION_MAX_DURATION_SECS);
+
+    let expired = self
+      .sessions
+      .lock()
+      .iter()
+      .filter_map(|(id, session)| {
+        if session.last_access.elapsed() > idle {
+          Some((id.clone(), EndReason::Idle))
+        } else if session.created_at.elapsed() > max_time {
+          Some((id.clone(), EndReason::MaxTime))
+        } else {
+          None
+        }
+      })
+      .collect::<Vec<_>>();
+
+    for (id, reason) in expired {
+      self.close(&id, reason);
+    }
+  }
+
+  fn close_all(&self) {
+    let sessions = std::mem::take(&mut *self.sessions.lock());
+    for (_, session) in sessions {
+      *session.connection.end_reason.lock() = EndReason::Shutdown;
+      drop(session);
+    }
+  }
+
+  /// closes the expired sessions periodically and all the sessions on shutdown
+  pub fn start_background_task(&self, shutdown: Shutdown) {
+    let map = self.clone();
+    tokio::spawn(async move {
+      let mut interval = tokio::time::interval(Duration::from_secs(5));
+      let signal = shutdown.signal();
+      tokio::pin!(signal);
+
+      loop {
+        tokio::select! {
+          _ = &mut signal => break,
+          _ = interval.tick() => map.close_expired(),
+        }
+      }
+
+      map.close_all();
+    });
+  }
+}
@@ -0,0 +1,178 @@
+//! ICY (shoutcast / icecast) in-band metadata
+//!
+//! when a client requests the stream with the `Icy-MetaData: 1` header
+//! we respond with an `icy-metaint: N` header and after every N bytes of audio
+//! we insert a metadata block in the response body
+//!
+//! a metadata block is a length byte (L) followed by L * 16 bytes of metadata
+//! (padded with zeros), an empty block (L = 0) means that the metadata has not changed
+
+use bytes::{BufMut, Bytes, BytesMut};
+use db::media_session::MediaSessionNowPlaying;
+use hyper::http::HeaderMap;
+
+/// max length of the metadata content of a block (the length byte is a u8 multiplied by 16)
+const MAX_METADATA_LEN: usize = u8::MAX as usize * 16;
+
+static EMPTY_BLOCK: &[u8] = &[0];
+
+/// returns true if the request has the `Icy-MetaData: 1` header
+pub fn is_requested(headers: &HeaderMap) -> bool {
+  match headers.get("icy-metadata") {
+    None => false,
+    Some(v) => matches!(v.to_str(), Ok(v) if v.trim() == "1"),
+  }
+}
+
+/// the StreamTitle of the metadata block, eg: "Artist - Title"
+pub fn stream_title(now_playing: Option<&MediaSessionNowPlaying>) -> String {
+  match now_playing {
+    None => String::new(),
+    Some(now_playing) => match &now_playing.artist {
+      Some(artist) if !artist.trim().is_empty() => format!("{} - {}", artist, now_playing.title),
+      _ => now_playing.title.clone(),
+    },
+  }
+}
+
+/// creates a metadata block (including the leading length byte) for the now playing info
+pub fn metadata_block(now_playing: Option<&MediaSessionNowPlaying>) -> Bytes {
+  const PREFIX: &str = "StreamTitle='";
+  const SUFFIX: &str = "';";
+
+  // there is no escape sequence in the icy metadata, a quote in the title would end the
+  // StreamTitle value early in most clients, so we replace it with a typographic quote
+  let mut title = stream_title(now_playing).replace('\'', "’");
+
+  let max_title_len = MAX_METADATA_LEN - PREFIX.len() - SUFFIX.len();
+  if title.len() > max_title_len {
+    let mut end = max_title_len;
+    while !title.is_char_boundary(end) {
+      end -= 1;
+    }
+    title.truncate(end);
+  }
+
+  let len = PREFIX.len() + title.len() + SUFFIX.len();
+  let blocks = len.div_ceil(16);
+
+  let mut buf = BytesMut::with_capacity(1 + blocks * 16);
+  buf.put_u8(blocks as u8);
+  buf.put_slice(PREFIX.as_bytes());
+  buf.put_slice(title.as_bytes());
+  buf.put_slice(SUFFIX.as_bytes());
+  buf.resize(1 + blocks * 16, 0);
+
+  buf.freeze()
+}
+
+/// interleaves metadata blocks in the audio stream every `metaint` bytes
+#[derive(Debug)]
+pub struct IcyWriter {
+  metaint: usize,
+  remaining: usize,
+  pending: Option<Bytes>,
+}
+
+impl IcyWriter {
+  pub fn new(metaint: usize, now_playing: Option<&MediaSessionNowPlaying>) -> Self {
+    Self {
+      metaint,
+      remaining: metaint,
+      pending: Some(metadata_block(now_playing)),
+    }
+  }
+
+  /// the now playing info will be sent in the next metadata block
+  pub fn set_now_playing(&mut self, now_playing: Option<&MediaSessionNowPlaying>) {
+    self.pending = Some(metadata_block(now_playing));
+  }
+
+  /// returns the bytes with the metadata blocks inserted where needed
+  pub fn write(&mut self, mut bytes: Bytes) -> Bytes {
+    if bytes.len() < self.remaining {
+      self.remaining -= bytes.len();
+      return bytes;
+    }
+
+    let mut buf = BytesMut::with_capacity(bytes.len() + 1);
+
+    while !bytes.is_empty() {
+      if self.remaining == 0 {
+        match self.pending.take() {
+          Some(block) => buf.put_slice(&block),
+          None => buf.put_slice(EMPTY_BLOCK),
+        }
+        self.remaining = self.metaint;
+      }
+
+      let n = self.remaining.min(bytes.len());
+      buf.put_slice(&bytes.split_to(n));
+      self.remaining -= n;
+    }
+
+    buf.freeze()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn np(title: &str, artist: Option<&str>) -> MediaSessionNowPlaying {
+    MediaSessionNowPlaying {
+      title: title.to_string(),
+      artist: artist.map(ToString::to_string),
+      play_history_item_id: None,
+    }
+  }
+
+  #[test]
+  fn metadata_block_is_padded() {
+    let block = metadata_block(Some(&np("Title", Some("Artist"))));
+    let content = b"StreamTitle='Artist - Title';";
+    assert_eq!(block[0] as usize, content.len().div_ceil(16));
+    assert_eq!(block.len(), 1 + block[0] as usize * 16);
+    assert_eq!(&block[1..1 + content.len()], content);
+    assert!(block[1 + content.len()..].iter().all(|b| *b == 0));
+  }
+
+  #[test]
+  fn metadata_block_replaces_quotes() {
+    let block = metadata_block(Some(&np("Don't Stop", Some("Guns N' Roses"))));
+    let content = "StreamTitle='Guns N’ Roses - Don’t Stop';".as_bytes();
+    assert_eq!(&block[1..1 + content.len()], content);
+    assert!(block[1 + content.len()..].iter().all(|b| *b == 0));
+  }
+
+  #[test]
+  fn metadata_block_is_truncated() {
+    let title = "a".repeat(MAX_METADATA_LEN * 2);
+    let block = metadata_block(Some(&np(&title, None)));
+    assert_eq!(block[0], u8::MAX);
+    assert_eq!(block.len(), 1 + MAX_METADATA_LEN);
+  }
+
+  #[test]
+  fn writer_interleaves_blocks() {
+    let mut writer = IcyWriter::new(4, Some(&np("T", None)));
+    let first = metadata_block(Some(&np("T", None)));
+
+    let out = writer.write(Bytes::from_static(b"abcdefghij"));
+
+    let mut expected = b"abcd".to_vec();
+    expected.extend_from_slice(&first);
+    expected.extend_from_slice(b"efgh");
+    expected.push(0);
+    expected.extend_from_slice(b"ij");
+
+    assert_eq!(&out[..], &expected[..]);
+
+    writer.set_now_playing(None);
+    let out = writer.write(Bytes::from_static(b"klm"));
+    let mut expected = b"kl".to_vec();
+    expected.extend_from_slice(&metadata_block(None));
+    expected.extend_from_slice(b"m");
+    assert_eq!(&out[..], &expected[..]);
+  }
+}
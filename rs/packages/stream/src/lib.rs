@@ -1,19 +1,30 @@
 use async_trait::async_trait;
-use constants::HEADER_RELAY_SOURCE_DEPLOYMENT;
+use bytes::Bytes;
 use db::account::Account;
+use db::event::{AudioListenerEnd, AudioListenerStart, Event};
+use db::plan::{ListenersLimitPolicy, Plan};
+use db::station::listener_access::{ListenerAccessDenied, ListenerAccessSettings};
 use db::station::Station;
 use db::stream_connection::lite::StreamConnectionLite;
+use db::stream_connection::rejection::{
+  StreamConnectionRejection, StreamConnectionRejectionReason,
+};
 use db::stream_connection::StreamConnection;
 use db::Model;
 use drop_tracer::{DropTracer, Token};
 use futures::stream::FuturesUnordered;
 use futures::TryStreamExt;
-use hyper::header::{HeaderName, ACCEPT_RANGES, CACHE_CONTROL, RETRY_AFTER, LOCATION};
+use hls::handler::{HlsPlaylistHandler, HlsSegmentHandler};
+use hls::proxy::HlsForwardError;
+use hls::HlsMap;
+use hyper::header::{HeaderName, ACCEPT_RANGES, CACHE_CONTROL, LOCATION, RETRY_AFTER};
 use hyper::{header::CONTENT_TYPE, http::HeaderValue, Body, Server, StatusCode};
 use ip_counter::IpCounter;
 use log::*;
 use media::channel::RecvError;
 use media::handle::internal_relay::GetInternalRelayError;
+use media::relay_auth::{RelayAuthError, RelaySignature};
+use media::rendition::{RenditionMap, RenditionSubscribeError};
 use media::{MediaSessionMap, SubscribeError};
 use mongodb::bson::doc;
 use parking_lot::Mutex;
@@ -32,6 +43,8 @@ use tokio::time::{sleep, Duration};
 use transfer_map::TransferTracer;
 
 mod error;
+pub mod hls;
+pub mod icy;
 pub mod transfer_map;
 
 #[allow(clippy::declare_interior_mutable_const)]
@@ -53,8 +66,8 @@ const CACHE_CONTROL_NO_CACHE: HeaderValue = HeaderValue::from_static("no-cache")
 const TEXT_PLAIN_UTF8: HeaderValue = HeaderValue::from_static("text/plain;charset=utf-8");
 
 #[allow(clippy::declare_interior_mutable_const)]
-const APPLICATION_JSON_UTF8: HeaderValue = HeaderValue::from_static("application/json;charset=utf-8");
-
+const APPLICATION_JSON_UTF8: HeaderValue =
+  HeaderValue::from_static("application/json;charset=utf-8");
 
 #[derive(Debug)]
 pub struct StreamServer {
@@ -65,6 +78,9 @@ pub struct StreamServer {
   drop_tracer: DropTracer,
   transfer_map: TransferTracer,
   ip_counter: IpCounter,
+  hls: HlsMap,
+  renditions: RenditionMap,
+  capacity_message_file: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
@@ -87,7 +103,19 @@ impl StreamServer {
     shutdown: Shutdown,
     drop_tracer: DropTracer,
     media_sessions: MediaSessionMap,
+    capacity_message_file: Option<String>,
   ) -> Self {
+    let hls = HlsMap::new(
+      deployment_id.clone(),
+      media_sessions.clone(),
+      shutdown.clone(),
+      drop_tracer.clone(),
+    );
+    let renditions = RenditionMap::new(
+      media_sessions.clone(),
+      drop_tracer.clone(),
+      shutdown.clone(),
+    );
     Self {
       deployment_id,
       addrs,
@@ -96,6 +124,9 @@ impl StreamServer {
       media_sessions,
       transfer_map: TransferTracer::new(),
       ip_counter: IpCounter::new(),
+      hls,
+      renditions,
+      capacity_message_file,
     }
   }
 
@@ -103,6 +134,11 @@ impl StreamServer {
   pub fn start(
     self,
   ) -> Result<impl Future<Output = Result<(), hyper::Error>> + 'static, StreamServerError> {
+    let capacity_message = match &self.capacity_message_file {
+      None => None,
+      Some(path) => Some(Bytes::from(std::fs::read(path)?)),
+    };
+
     let mut app = prex::prex();
 
     app.with(http::middleware::server);
@@ -123,17 +159,28 @@ impl StreamServer {
     app.get("/stream/:id.pls", LinkHandler::new(LinkHandlerKind::Pls));
 
     app.get(
-      "/stream/:id",
-      StreamHandler::new(
-        self.deployment_id.clone(),
-        self.media_sessions.clone(),
-        self.transfer_map.clone(),
-        self.shutdown.clone(),
-        self.drop_tracer.clone(),
-        self.ip_counter.clone(),
-      ),
+      "/stream/:id/hls/live.m3u8",
+      HlsPlaylistHandler::new(self.hls.clone(), self.transfer_map.clone()),
+    );
+
+    app.get(
+      "/stream/:id/hls/:segment([0-9]+).mp3",
+      HlsSegmentHandler::new(self.hls.clone(), self.transfer_map.clone()),
+    );
+
+    let stream_handler = StreamHandler::new(
+      self.deployment_id.clone(),
+      self.media_sessions.clone(),
+      self.renditions.clone(),
+      self.transfer_map.clone(),
+      self.shutdown.clone(),
+      self.drop_tracer.clone(),
+      self.ip_counter.clone(),
+      capacity_message,
     );
 
+    app.get("/stream/:id", stream_handler.clone());
+
     app.get(
       "/relay/:id",
       RelayHandler::new(self.deployment_id.clone(), self.media_sessions.clone()),
@@ -141,6 +188,9 @@ impl StreamServer {
 
     app.get("/stream/:id/is-hls-redirect", IsHlsRedirectHandler {});
 
+    // output renditions, this must be the last /stream/:id/* route
+    app.get("/stream/:id/:rendition", stream_handler);
+
     let app = app.build().expect("prex app build stream");
 
     let futs = FuturesUnordered::new();
@@ -193,6 +243,7 @@ impl StreamServer {
 
     Ok(async move {
       self.transfer_map.start_background_task();
+      self.hls.start_background_task();
       futs.try_collect().await?;
       drop(self);
       Ok(())
@@ -223,33 +274,17 @@ impl RelayHandler {
   pub async fn handle(&self, req: Request) -> Result<Response, StreamError> {
     let station_id = req.param("id").unwrap().to_string();
 
-    let source_deployment_id = match req.headers().get(HEADER_RELAY_SOURCE_DEPLOYMENT) {
-      None => None,
-      Some(v) => match v.to_str() {
-        Err(_) => None,
-        Ok(v) => Some(v),
-      },
-    };
+    let signature = RelaySignature::from_headers(req.headers()).map_err(StreamError::RelayAuth)?;
 
-    if let Some(id) = source_deployment_id {
-      if id == self.deployment_id {
-        return Err(StreamError::RelayDeploymentSourceIsTarget);
-      }
+    if signature.source_deployment_id == self.deployment_id {
+      return Err(StreamError::RelayDeploymentSourceIsTarget);
     }
 
-    // match req.headers().get(X_OPENSTREAM_RELAY_CODE) {
-    //   None => return Err(RelayError::RelayCodeMismatch),
-    //   Some(v) => match v.to_str() {
-    //     Err(_) => {
-    //       return Err(RelayError::RelayCodeMismatch);
-    //     }
-    //     Ok(v) => {
-    //       if v != self.deployment_id {
-    //         return Err(RelayError::RelayCodeMismatch);
-    //       }
-    //     }
-    //   },
-    // }
+    self
+      .media_sessions
+      .relay_auth()
+      .verify(&station_id, &self.deployment_id, &signature)
+      .map_err(StreamError::RelayAuth)?;
 
     let mut rx = self.media_sessions.subscribe(&station_id).await?;
 
@@ -318,28 +353,35 @@ impl Handler for RelayHandler {
 struct StreamHandler {
   deployment_id: String,
   media_sessions: MediaSessionMap,
+  renditions: RenditionMap,
   transfer_map: TransferTracer,
   shutdown: Shutdown,
   drop_tracer: DropTracer,
   ip_counter: IpCounter,
+  capacity_message: Option<Bytes>,
 }
 
 impl StreamHandler {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     deployment_id: String,
     media_sessions: MediaSessionMap,
+    renditions: RenditionMap,
     transfer_map: TransferTracer,
     shutdown: Shutdown,
     drop_tracer: DropTracer,
     ip_counter: IpCounter,
+    capacity_message: Option<Bytes>,
   ) -> Self {
     Self {
       deployment_id,
       media_sessions,
+      renditions,
       transfer_map,
       shutdown,
       drop_tracer,
       ip_counter,
+      capacity_message,
     }
   }
 
@@ -349,13 +391,16 @@ impl StreamHandler {
     let Self {
       deployment_id,
       media_sessions,
+      renditions,
       drop_tracer,
       ip_counter,
       shutdown,
       transfer_map,
+      capacity_message,
     } = self;
 
     let station_id = req.param("id").unwrap().to_string();
+    let rendition = req.param("rendition").map(ToString::to_string);
 
     tokio::spawn(async move {
       let ip = req.isomorphic_ip();
@@ -381,11 +426,30 @@ impl StreamHandler {
         None => return Err(StreamError::StationNotFound(station_id.to_string())),
       };
 
-      // TODO: check account limits
-      let rx = match media_sessions.subscribe(&station.id).await {
+      // open connections of the account are cut off in the body loop below
+      // when the account runs out of transfer
+      let listener_slot = match admit_listener(&req, &station, &deployment_id, &transfer_map, &drop_tracer).await {
+        Ok(listener_slot) => listener_slot,
+        Err(StreamError::ListenersLimitReached) => {
+          let policy = listeners_limit_policy(&station.account_id).await?;
+          let content_type = stream_content_type(&station, rendition.as_deref());
+          match listeners_limit_response(policy, capacity_message, content_type) {
+            Some(res) => return Ok(res),
+            None => return Err(StreamError::ListenersLimitReached),
+          }
+        }
+        Err(e) => return Err(e),
+      };
+
+      let subscribed = match &rendition {
+        None => media_sessions.subscribe(&station.id).await.map_err(StreamError::from),
+        Some(slug) => renditions.subscribe(&station, slug).await.map_err(StreamError::from),
+      };
+
+      let rx = match subscribed {
         Ok(rx) => rx,
         Err(e) => {
-          if matches!(e, SubscribeError::ExternalRelayRedirect(_)) {
+          if matches!(e, StreamError::Subscribe(SubscribeError::ExternalRelayRedirect(_))) {
             let token = drop_tracer.token();
             tokio::spawn(async move {
               // create redirect session
@@ -422,78 +486,37 @@ impl StreamHandler {
             });
           }
           
-          return Err(e.into());
+          return Err(e);
         }
       };
       let content_type = rx.content_type().to_string();
 
       let is_mp3 = content_type == "audio/mpeg";
 
-      let conn_doc = {
-        let now = DateTime::now();
-        let request = db::http::Request::from_http(&req);
-
-        StreamConnection {
-          id: StreamConnection::uid(),
-          station_id: station.id.clone(),
-          deployment_id: deployment_id.clone(),
-          is_open: true,
-          ip: request.real_ip,
-          country_code: request.country_code,
-          transfer_bytes: None,
-          duration_ms: None,
-          request,
-          created_at: now,
-          is_external_relay_redirect: false,
-          abnormally_closed: false,
-          last_transfer_at: now,
-          closed_at: None,
-        }
-      };
-
-      let conn_doc_lite = StreamConnectionLite::from_stream_connection_ref(&conn_doc);
-
-      StreamConnection::insert(&conn_doc).await?;
-      debug!(
-        "StreamConnection::insert called for station {station_id}, connection_id: {}",
-        conn_doc.id
-      );
-
-      StreamConnectionLite::insert(&conn_doc_lite).await?;
-      debug!(
-        "StreamConnectionLite::insert called for station {station_id}, connection_id: {}",
-        conn_doc_lite.id
-      );
-
-      Account::increment_used_listeners(&station.account_id).await?;
-
-      let transfer_bytes = Arc::new(AtomicU64::new(0));
-
-      let conn_id = conn_doc.id;
-      let end_reason = Arc::new(Mutex::new(EndReason::None));
+      // icy in-band metadata is only sent to clients that request it
+      let icy_writer = icy::is_requested(req.headers()).then(|| {
+        icy::IcyWriter::new(constants::STREAM_ICY_METAINT, rx.now_playing().as_ref())
+      });
 
-      let station_name = station.name;
-      let ip = conn_doc.ip;
-      let domain = conn_doc_lite.domain.clone();
+      let icy_headers = icy_writer.as_ref().map(|_| icy_headers(&station));
 
-      let connection_dropper = StreamConnectionDropper(Some(StreamConnectionDropperInner {
-        id: conn_id.clone(),
+      let OpenedConnection {
+        id: conn_id,
         ip,
-        station_id: station_id.clone(),
-        station_name: station_name.clone(),
-        domain: domain.clone(),
-        account_id: station.account_id.clone(),
-        transfer_bytes: transfer_bytes.clone(),
-        token: drop_tracer.token(),
-        start_time,
-        end_reason: end_reason.clone(),
-      }));
+        domain,
+        transfer_bytes,
+        end_reason,
+        dropper: connection_dropper,
+      } = open_connection(&req, &station, &deployment_id, listener_slot, &drop_tracer, start_time).await?;
+
+      let station_name = station.name.clone();
 
       let (mut body_sender, response_body) = Body::channel();
 
       tokio::spawn({
         let transfer_map = transfer_map.clone();
         let mut rx = rx;
+        let mut icy_writer = icy_writer;
 
         let mut loop_i = 0usize;
 
@@ -561,6 +584,16 @@ impl StreamHandler {
                       }
                     };
 
+                    let bytes = match &mut icy_writer {
+                      None => bytes,
+                      Some(writer) => {
+                        if let Some(now_playing) = rx.now_playing_changed() {
+                          writer.set_now_playing(now_playing.as_ref());
+                        }
+                        writer.write(bytes)
+                      }
+                    };
+
                     let r = tokio::select! {
                       _ = sleep(Duration::from_millis(100_000)) => return EndReason::BodySendTimeout,
                       r = body_sender.send_data(bytes) => r,
@@ -573,6 +606,10 @@ impl StreamHandler {
                         transfer_bytes.fetch_add(len as u64, Ordering::Relaxed);
                       }
                     };
+
+                    if transfer_map.is_blocked(&station.account_id) {
+                      return EndReason::TransferLimit
+                    }
                   }
                 }
               }
@@ -589,13 +626,24 @@ impl StreamHandler {
               } else if loop_i > 60 {
                 return EndReason::NoRestartLoop
               } else {
-                let new_rx = match media_sessions.subscribe(&station.id).await {
-                  Ok(rx) => rx,
-                  Err(e) => {
-                    return EndReason::Resubscribe(e)
-                  }
+                let new_rx = match &rendition {
+                  None => match media_sessions.subscribe(&station.id).await {
+                    Ok(rx) => rx,
+                    Err(e) => {
+                      return EndReason::Resubscribe(e)
+                    }
+                  },
+                  Some(slug) => match renditions.subscribe(&station, slug).await {
+                    Ok(rx) => rx,
+                    Err(e) => {
+                      return EndReason::RenditionResubscribe(e)
+                    }
+                  },
                 };
                 rx = new_rx;
+                if let Some(writer) = &mut icy_writer {
+                  writer.set_now_playing(rx.now_playing().as_ref());
+                }
                 continue 'root;  
               }
             };
@@ -635,6 +683,12 @@ impl StreamHandler {
         .headers_mut()
         .append(CACHE_CONTROL, CACHE_CONTROL_NO_CACHE);
 
+      if let Some(headers) = icy_headers {
+        for (name, value) in headers {
+          res.headers_mut().append(name, value);
+        }
+      }
+
       *res.body_mut() = response_body;
 
       Ok(res)
@@ -662,6 +716,7 @@ struct StreamConnectionDropperInner {
   station_name: String,
   station_id: String,
   account_id: String,
+  listener_slot: ListenerSlot,
   transfer_bytes: Arc<AtomicU64>,
   start_time: SystemTime,
   end_reason: Arc<Mutex<EndReason>>,
@@ -680,6 +735,12 @@ pub enum EndReason {
   NoRestartData,
   NoRestartLoop,
   Resubscribe(SubscribeError),
+  RenditionResubscribe(RenditionSubscribeError),
+  TransferLimit,
+  /// an HLS session without playlist or segment requests
+  Idle,
+  /// an HLS session denied by the station listener access settings on a playlist reload
+  ListenerAccess,
 }
 
 impl Display for EndReason {
@@ -695,6 +756,10 @@ impl Display for EndReason {
       EndReason::NoRestartData => f.write_str("no-restart-data"),
       EndReason::NoRestartLoop => f.write_str("no-restart-loop"),
       EndReason::Resubscribe(e) => write!(f, "resubscribe: {}", e),
+      EndReason::RenditionResubscribe(e) => write!(f, "rendition-resubscribe: {}", e),
+      EndReason::TransferLimit => f.write_str("transfer-limit"),
+      EndReason::Idle => f.write_str("idle"),
+      EndReason::ListenerAccess => f.write_str("listener-access"),
     }
   }
 }
@@ -706,8 +771,9 @@ impl Drop for StreamConnectionDropper {
       ip,
       station_name,
       station_id,
-      domain,
       account_id,
+      domain,
+      listener_slot,
       transfer_bytes,
       start_time,
       end_reason,
@@ -763,17 +829,308 @@ impl Drop for StreamConnectionDropper {
         debug!("StreamConnectionLite closed for station {station_id}, matched: {matched}, modified: {modified}", matched=r.matched_count, modified=r.modified_count);
       }
 
-      let r = Account::decrement_used_listeners(&account_id)
-        .await
-        .expect("error at Account::decrement_used_listeners");
+      Event::dispatch_subscribed_detached(
+        account_id,
+        AudioListenerEnd {
+          station_id,
+          connection_id: id,
+        },
+      );
 
-      debug!("Account::decrement_used_listeners called for account {account_id}, matched: {matched}, modified: {modified}", matched=r.matched_count, modified=r.modified_count);
+      drop(listener_slot);
 
       drop(token);
     });
   }
 }
 
+/// an acquired slot in the account listeners quota
+/// the account used listeners counter is decremented when this is dropped
+#[derive(Debug)]
+struct ListenerSlot(Option<(String, Token)>);
+
+impl Drop for ListenerSlot {
+  fn drop(&mut self) {
+    if let Some((account_id, token)) = self.0.take() {
+      tokio::spawn(async move {
+        let r = Account::decrement_used_listeners(&account_id)
+          .await
+          .expect("error at Account::decrement_used_listeners");
+
+        debug!("Account::decrement_used_listeners called for account {account_id}, matched: {matched}, modified: {modified}", matched=r.matched_count, modified=r.modified_count);
+
+        drop(token);
+      });
+    }
+  }
+}
+
+/// runs the admission checks that every listener goes through, progressive or HLS,
+/// and acquires a slot in the account listeners quota
+/// the rejections are recorded, a full station returns StreamError::ListenersLimitReached
+async fn admit_listener(
+  req: &Request,
+  station: &Station,
+  deployment_id: &str,
+  transfer_map: &TransferTracer,
+  drop_tracer: &DropTracer,
+) -> Result<ListenerSlot, StreamError> {
+  // the station listener access settings are checked before using any of the account quotas
+  if let Err(denied) = check_listener_access(req, &station.listener_access) {
+    record_rejection(
+      req,
+      station,
+      deployment_id,
+      denied.into(),
+      drop_tracer.token(),
+    );
+    return Err(StreamError::ListenerAccessDenied(denied));
+  }
+
+  // accounts that used all the transfer of the billing period cannot start new connections
+  if transfer_map.is_blocked(&station.account_id)
+    || is_transfer_blocked(&station.account_id).await?
+  {
+    record_rejection(
+      req,
+      station,
+      deployment_id,
+      StreamConnectionRejectionReason::TransferLimit,
+      drop_tracer.token(),
+    );
+    return Err(StreamError::TransferLimitReached);
+  }
+
+  // the listener slot is acquired with an atomic conditional increment on the account document
+  // so the limit is consistent across deployments, the slot is released when listener_slot is dropped
+  match Account::try_increment_used_listeners(&station.account_id).await? {
+    true => {
+      let token = drop_tracer.token();
+      Ok(ListenerSlot(Some((station.account_id.clone(), token))))
+    }
+    false => {
+      record_rejection(
+        req,
+        station,
+        deployment_id,
+        StreamConnectionRejectionReason::ListenersLimit,
+        drop_tracer.token(),
+      );
+      Err(StreamError::ListenersLimitReached)
+    }
+  }
+}
+
+/// the open StreamConnection of an admitted listener
+/// the documents are closed and the listener slot released when `dropper` is dropped
+#[derive(Debug)]
+struct OpenedConnection {
+  id: String,
+  ip: IpAddr,
+  domain: Option<String>,
+  transfer_bytes: Arc<AtomicU64>,
+  end_reason: Arc<Mutex<EndReason>>,
+  dropper: StreamConnectionDropper,
+}
+
+async fn open_connection(
+  req: &Request,
+  station: &Station,
+  deployment_id: &str,
+  listener_slot: ListenerSlot,
+  drop_tracer: &DropTracer,
+  start_time: SystemTime,
+) -> Result<OpenedConnection, StreamError> {
+  let station_id = &station.id;
+
+  let conn_doc = {
+    let now = DateTime::now();
+    let request = db::http::Request::from_http(req);
+
+    StreamConnection {
+      id: StreamConnection::uid(),
+      station_id: station.id.clone(),
+      deployment_id: deployment_id.to_string(),
+      is_open: true,
+      ip: request.real_ip,
+      country_code: request.country_code,
+      transfer_bytes: None,
+      duration_ms: None,
+      request,
+      created_at: now,
+      is_external_relay_redirect: false,
+      abnormally_closed: false,
+      last_transfer_at: now,
+      closed_at: None,
+    }
+  };
+
+  let conn_doc_lite = StreamConnectionLite::from_stream_connection_ref(&conn_doc);
+
+  StreamConnection::insert(&conn_doc).await?;
+  debug!(
+    "StreamConnection::insert called for station {station_id}, connection_id: {}",
+    conn_doc.id
+  );
+
+  StreamConnectionLite::insert(&conn_doc_lite).await?;
+  debug!(
+    "StreamConnectionLite::insert called for station {station_id}, connection_id: {}",
+    conn_doc_lite.id
+  );
+
+  Event::dispatch_subscribed_detached(
+    station.account_id.clone(),
+    AudioListenerStart {
+      station_id: station.id.clone(),
+      connection_id: conn_doc.id.clone(),
+    },
+  );
+
+  let transfer_bytes = Arc::new(AtomicU64::new(0));
+  let end_reason = Arc::new(Mutex::new(EndReason::None));
+
+  let dropper = StreamConnectionDropper(Some(StreamConnectionDropperInner {
+    id: conn_doc.id.clone(),
+    ip: conn_doc.ip,
+    station_id: station.id.clone(),
+    station_name: station.name.clone(),
+    account_id: station.account_id.clone(),
+    domain: conn_doc_lite.domain.clone(),
+    listener_slot,
+    transfer_bytes: transfer_bytes.clone(),
+    token: drop_tracer.token(),
+    start_time,
+    end_reason: end_reason.clone(),
+  }));
+
+  Ok(OpenedConnection {
+    id: conn_doc.id,
+    ip: conn_doc.ip,
+    domain: conn_doc_lite.domain,
+    transfer_bytes,
+    end_reason,
+    dropper,
+  })
+}
+
+async fn listeners_limit_policy(
+  account_id: &str,
+) -> Result<ListenersLimitPolicy, mongodb::error::Error> {
+  let account = match Account::get_by_id(account_id).await? {
+    None => return Ok(ListenersLimitPolicy::default()),
+    Some(account) => account,
+  };
+
+  match Plan::get_by_id(&account.plan_id).await? {
+    None => Ok(ListenersLimitPolicy::default()),
+    Some(plan) => Ok(plan.listeners_limit_policy),
+  }
+}
+
+async fn is_transfer_blocked(account_id: &str) -> Result<bool, mongodb::error::Error> {
+  match Account::get_by_id(account_id).await? {
+    None => Ok(false),
+    Some(account) => account.is_transfer_blocked().await,
+  }
+}
+
+/// checks the address, country and referer of the request against the station listener access settings
+/// the referer is taken from the origin header for the players that don't send one
+pub(crate) fn check_listener_access(
+  req: &Request,
+  access: &ListenerAccessSettings,
+) -> Result<(), ListenerAccessDenied> {
+  let request = db::http::Request::from_http(req);
+  let referer = request
+    .headers
+    .get("referer")
+    .or_else(|| request.headers.get("origin"));
+  access.check(request.real_ip, request.country_code, referer)
+}
+
+/// inserts the rejection document in the background
+fn record_rejection(
+  req: &Request,
+  station: &Station,
+  deployment_id: &str,
+  reason: StreamConnectionRejectionReason,
+  token: Token,
+) {
+  let request = db::http::Request::from_http(req);
+  let doc = StreamConnectionRejection {
+    id: StreamConnectionRejection::uid(),
+    station_id: station.id.clone(),
+    account_id: station.account_id.clone(),
+    deployment_id: deployment_id.to_string(),
+    reason,
+    ip: request.real_ip,
+    country_code: request.country_code,
+    created_at: DateTime::now(),
+  };
+
+  tokio::spawn(async move {
+    if let Err(e) = StreamConnectionRejection::insert(&doc).await {
+      warn!(
+        "error inserting stream connection rejection for station {}: {} => {:?}",
+        doc.station_id, e, e
+      );
+    }
+    drop(token);
+  });
+}
+
+/// the content type of the stream requested by a listener
+fn stream_content_type<'a>(station: &'a Station, rendition: Option<&str>) -> &'a str {
+  match rendition {
+    Some(slug) => {
+      let rendition = station
+        .renditions
+        .iter()
+        .find(|rendition| rendition.slug == slug);
+      match rendition {
+        Some(rendition) => rendition.codec.content_type(),
+        None => "audio/mpeg",
+      }
+    }
+    // a station that is not on air starts its playlist for the listener, that is always mp3
+    None => match &station.owner_deployment_info {
+      Some(info) => info.content_type.as_str(),
+      None => "audio/mpeg",
+    },
+  }
+}
+
+/// the response to a new listener when the account is at its listeners limit, None if the listener is rejected
+///
+/// the capacity message is an mp3 file, so it is only sent to the listeners of mp3 streams
+fn listeners_limit_response(
+  policy: ListenersLimitPolicy,
+  capacity_message: Option<Bytes>,
+  content_type: &str,
+) -> Option<Response> {
+  match (policy, capacity_message) {
+    (ListenersLimitPolicy::CapacityMessage, Some(message)) if content_type == "audio/mpeg" => {
+      Some(capacity_message_response(message, content_type))
+    }
+    _ => None,
+  }
+}
+
+fn capacity_message_response(message: Bytes, content_type: &str) -> Response {
+  let mut res = Response::new(StatusCode::OK);
+  res.headers_mut().append(
+    CONTENT_TYPE,
+    HeaderValue::from_str(content_type).unwrap_or(CONTENT_TYPE_MPEG),
+  );
+  res.headers_mut().append(ACCEPT_RANGES, ACCEPT_RANGES_NONE);
+  res
+    .headers_mut()
+    .append(CACHE_CONTROL, CACHE_CONTROL_NO_CACHE);
+  *res.body_mut() = Body::from(message);
+  res
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum StreamError {
   #[error("db: {0}")]
@@ -786,12 +1143,43 @@ pub enum StreamError {
   Subscribe(#[from] SubscribeError),
   #[error("deployment target is source")]
   RelayDeploymentSourceIsTarget,
+  #[error("relay auth: {0}")]
+  RelayAuth(RelayAuthError),
+  #[error("listeners limit reached")]
+  ListenersLimitReached,
+  #[error("transfer limit reached")]
+  TransferLimitReached,
+  #[error("listener access denied: {0}")]
+  ListenerAccessDenied(ListenerAccessDenied),
+  #[error("hls unsupported content type: {0}")]
+  HlsUnsupportedContentType(String),
+  #[error("hls stream not ready")]
+  HlsNotReady,
+  #[error("hls segment not found")]
+  HlsSegmentNotFound,
+  #[error("hls session not found")]
+  HlsSessionNotFound,
+  #[error("hls forward: {0}")]
+  HlsForward(#[from] HlsForwardError),
+  #[error("rendition not found: {0}")]
+  RenditionNotFound(String),
+  #[error("rendition spawn: {0}")]
+  RenditionSpawn(std::io::Error),
+}
+
+impl From<RenditionSubscribeError> for StreamError {
+  fn from(e: RenditionSubscribeError) -> Self {
+    match e {
+      RenditionSubscribeError::NotFound(slug) => StreamError::RenditionNotFound(slug),
+      RenditionSubscribeError::Subscribe(e) => StreamError::Subscribe(e),
+      RenditionSubscribeError::Spawn(e) => StreamError::RenditionSpawn(e),
+    }
+  }
 }
 
 impl From<StreamError> for Response {
   fn from(e: StreamError) -> Self {
     let (status, code, message, retry_after_secs) = match e {
-      
       StreamError::Db(_e) => (
         StatusCode::INTERNAL_SERVER_ERROR,
         "INTERNAL_DB",
@@ -821,8 +1209,96 @@ impl From<StreamError> for Response {
         Some(5u32),
       ),
 
+      StreamError::RelayAuth(e) => (
+        StatusCode::FORBIDDEN,
+        e.code(),
+        format!("relay request rejected: {e}"),
+        None,
+      ),
+
+      StreamError::ListenersLimitReached => (
+        StatusCode::SERVICE_UNAVAILABLE,
+        "LISTENERS_LIMIT_REACHED",
+        "This station is at its listeners capacity, try again later".into(),
+        Some(constants::STREAM_LISTENERS_LIMIT_RETRY_AFTER_SECS),
+      ),
+
+      StreamError::TransferLimitReached => (
+        StatusCode::SERVICE_UNAVAILABLE,
+        "TRANSFER_LIMIT_REACHED",
+        "This station reached its transfer limit, try again later".into(),
+        Some(constants::STREAM_TRANSFER_LIMIT_RETRY_AFTER_SECS),
+      ),
+
+      StreamError::ListenerAccessDenied(denied) => {
+        let (code, message) = match denied {
+          ListenerAccessDenied::Ip => (
+            "LISTENER_IP_BLOCKED",
+            "This station is not available from your network",
+          ),
+          ListenerAccessDenied::Country => (
+            "LISTENER_COUNTRY_BLOCKED",
+            "This station is not available in your country",
+          ),
+          ListenerAccessDenied::Referer => (
+            "LISTENER_REFERER_BLOCKED",
+            "This station cannot be played from this site",
+          ),
+        };
+
+        (StatusCode::FORBIDDEN, code, message.into(), None)
+      }
+
+      StreamError::HlsUnsupportedContentType(content_type) => (
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        "HLS_UNSUPPORTED_CONTENT_TYPE",
+        format!("HLS is not available for streams of type {content_type}"),
+        None,
+      ),
+
+      StreamError::HlsNotReady => (
+        StatusCode::SERVICE_UNAVAILABLE,
+        "HLS_NOT_READY",
+        "HLS stream is starting, try again in a few seconds".into(),
+        Some(2u32),
+      ),
+
+      StreamError::HlsSegmentNotFound => (
+        StatusCode::NOT_FOUND,
+        "HLS_SEGMENT_NOT_FOUND",
+        "HLS segment not found, reload the playlist".into(),
+        None,
+      ),
+
+      StreamError::HlsSessionNotFound => (
+        StatusCode::NOT_FOUND,
+        "HLS_SESSION_NOT_FOUND",
+        "HLS session not found, reload the stream".into(),
+        None,
+      ),
+
+      StreamError::HlsForward(_e) => (
+        StatusCode::BAD_GATEWAY,
+        "INTERNAL_HLS_FORWARD",
+        "internal server error (hf)".into(),
+        Some(5u32),
+      ),
+
+      StreamError::RenditionNotFound(slug) => (
+        StatusCode::NOT_FOUND,
+        "RENDITION_NOT_FOUND",
+        format!("rendition {slug} not found for this station"),
+        None,
+      ),
+
+      StreamError::RenditionSpawn(_e) => (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "INTERNAL_RENDITION_SPAWN",
+        "internal server error (rs)".into(),
+        None,
+      ),
+
       StreamError::Subscribe(e) => match e {
-        
         SubscribeError::ExternalRelayRedirect(url) => {
           let mut res = Response::new(StatusCode::FOUND);
           if let Ok(v) = HeaderValue::from_str(&url) {
@@ -888,6 +1364,13 @@ impl From<StreamError> for Response {
             None,
           ),
 
+          GetInternalRelayError::RelayAuth(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "INTERNAL_RELAY_AUTH",
+            "internal server error (ra)".into(),
+            None,
+          ),
+
           GetInternalRelayError::DeploymentNotFound(_) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             "INTERNAL_DEPLOYMENT_NOT_FOUND",
@@ -1049,11 +1532,41 @@ impl<'a> Display for PlsContents<'a> {
   }
 }
 
+fn icy_headers(station: &Station) -> Vec<(HeaderName, HeaderValue)> {
+  let mut headers = vec![(
+    HeaderName::from_static("icy-metaint"),
+    HeaderValue::from(constants::STREAM_ICY_METAINT),
+  )];
+
+  // from_bytes allows non ascii (utf-8) station names
+  if let Ok(v) = HeaderValue::from_bytes(station.name.as_bytes()) {
+    headers.push((HeaderName::from_static("icy-name"), v));
+  }
+
+  headers.push((
+    HeaderName::from_static("icy-genre"),
+    HeaderValue::from_static(station.type_of_content.display_name()),
+  ));
+
+  if let Some(slogan) = &station.slogan {
+    if let Ok(v) = HeaderValue::from_bytes(slogan.as_bytes()) {
+      headers.push((HeaderName::from_static("icy-description"), v));
+    }
+  }
+
+  if let Some(url) = &station.website_url {
+    if let Ok(v) = HeaderValue::from_str(url) {
+      headers.push((HeaderName::from_static("icy-url"), v));
+    }
+  }
+
+  headers
+}
 
 fn find_first_frame_index(data: &[u8]) -> Option<usize> {
   match mp3::read_from_slice(data) {
     Err(_) => None,
-    Ok(meta) => { 
+    Ok(meta) => {
       // let offsets_str = meta.frames.windows(2).map(|fs| {
       //   let f1 = &fs[0];
       //   let f2 = &fs[1];
@@ -1106,7 +1619,7 @@ impl From<IsHlsRedirectError> for Response {
     res.headers_mut().append(CONTENT_TYPE, TEXT_PLAIN_UTF8);
     *res.body_mut() = Body::from(message);
     res
-  } 
+  }
 }
 
 impl IsHlsRedirectHandler {
@@ -1118,7 +1631,7 @@ impl IsHlsRedirectHandler {
         Some(station) => station,
         None => return Err(IsHlsRedirectError::StationNotFound(station_id.to_string())),
       };
-      
+
       let is_hls_redirect = {
         if !station.external_relay_redirect {
           false
@@ -1130,18 +1643,25 @@ impl IsHlsRedirectHandler {
               Ok(url) => {
                 let path = url.path().to_lowercase();
                 path.ends_with(".m3u8") || path.ends_with(".m3u")
-              },
-            }
+              }
+            },
           }
         }
       };
 
       let mut res = Response::new(StatusCode::OK);
-      res.headers_mut().insert(HeaderName::from_static(constants::IS_HLS_REDIRECT_HEADER), HeaderValue::from_str(&is_hls_redirect.to_string()).unwrap());
-      res.headers_mut().insert(CONTENT_TYPE, APPLICATION_JSON_UTF8);
+      res.headers_mut().insert(
+        HeaderName::from_static(constants::IS_HLS_REDIRECT_HEADER),
+        HeaderValue::from_str(&is_hls_redirect.to_string()).unwrap(),
+      );
+      res
+        .headers_mut()
+        .insert(CONTENT_TYPE, APPLICATION_JSON_UTF8);
       *res.body_mut() = Body::from(is_hls_redirect.to_string());
       Ok(res)
-    }).await.unwrap()
+    })
+    .await
+    .unwrap()
   }
 }
 
@@ -1150,4 +1670,40 @@ impl Handler for IsHlsRedirectHandler {
   async fn call(&self, req: Request, _: Next) -> Response {
     self.handle(req).await.into()
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn listeners_limit_capacity_message() {
+    let message = Bytes::from_static(b"mp3");
+
+    let res = listeners_limit_response(
+      ListenersLimitPolicy::CapacityMessage,
+      Some(message.clone()),
+      "audio/mpeg",
+    )
+    .expect("capacity message response");
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(CONTENT_TYPE).unwrap(), "audio/mpeg");
+
+    // the mp3 message is not sent to listeners of other formats
+    assert!(listeners_limit_response(
+      ListenersLimitPolicy::CapacityMessage,
+      Some(message.clone()),
+      "audio/aac",
+    )
+    .is_none());
+
+    assert!(
+      listeners_limit_response(ListenersLimitPolicy::Reject, Some(message), "audio/mpeg").is_none()
+    );
+
+    assert!(
+      listeners_limit_response(ListenersLimitPolicy::CapacityMessage, None, "audio/mpeg").is_none()
+    );
+  }
+}
@@ -1,8 +1,9 @@
 use constants::TRANSFER_SAVE_INTERVAL_MILLIS;
 use db::account::Account;
+use db::Model;
 use log::*;
 use parking_lot::RwLock;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::time::Duration;
@@ -12,6 +13,9 @@ const SAVE_INTERVAL: Duration = Duration::from_millis(TRANSFER_SAVE_INTERVAL_MIL
 #[derive(Debug, Default, Clone)]
 pub struct TransferTracer {
   current: Arc<RwLock<BTreeMap<String, AtomicUsize>>>,
+  /// accounts that used all the transfer of their billing period
+  /// and whose plan does not allow overage, refreshed on every save
+  blocked: Arc<RwLock<BTreeSet<String>>>,
 }
 
 impl TransferTracer {
@@ -32,6 +36,11 @@ impl TransferTracer {
     }
   }
 
+  /// returns true if the connections of this account must be cut off
+  pub fn is_blocked(&self, account_id: &str) -> bool {
+    self.blocked.read().contains(account_id)
+  }
+
   pub fn start_background_task(&self) -> tokio::task::JoinHandle<()> {
     let me = self.clone();
     tokio::spawn(async move {
@@ -55,20 +64,64 @@ impl TransferTracer {
     }
 
     if now.is_empty() {
-      debug!("saving transfer, 0 stations to update");
+      debug!("saving transfer, 0 accounts to update");
     } else {
-      debug!("saving transfer for {} stations", now.len());
+      debug!("saving transfer for {} accounts", now.len());
     }
 
     for (id, value) in now.iter() {
-      debug!("saving transfer for station {id} increment: {value}");
+      debug!("saving transfer for account {id} increment: {value}");
       let r = Account::increment_used_transfer(id, *value).await;
       match r {
-        Err(e) => warn!("error saving transfer for station {id}: {e}"),
-        Ok(r) => debug!(
-          "transfer saved for station {id}, matched: {}, modified: {}",
-          r.matched_count, r.modified_count
-        ),
+        Err(e) => warn!("error saving transfer for account {id}: {e}"),
+        Ok(None) => debug!("transfer not saved for account {id}, account not found"),
+        Ok(Some(account)) => {
+          debug!(
+            "transfer saved for account {id}, used: {}, total: {}",
+            account.limits.transfer.used, account.limits.transfer.total
+          );
+          self.refresh_blocked(&account).await;
+        }
+      }
+    }
+
+    // blocked accounts have no transfer to save, so we check them here
+    // to unblock them when the billing period rolls over or the plan changes
+    let blocked = self
+      .blocked
+      .read()
+      .iter()
+      .filter(|id| !now.contains_key(*id))
+      .cloned()
+      .collect::<Vec<_>>();
+
+    for id in blocked {
+      match Account::get_by_id(&id).await {
+        Err(e) => warn!("error checking blocked transfer for account {id}: {e}"),
+        Ok(None) => {
+          self.blocked.write().remove(&id);
+        }
+        Ok(Some(account)) => self.refresh_blocked(&account).await,
+      }
+    }
+  }
+
+  async fn refresh_blocked(&self, account: &Account) {
+    match account.is_transfer_blocked().await {
+      Err(e) => warn!(
+        "error checking blocked transfer for account {}: {e}",
+        account.id
+      ),
+      Ok(true) => {
+        if self.blocked.write().insert(account.id.clone()) {
+          info!(
+            "transfer limit reached for account {}, cutting off connections",
+            account.id
+          );
+        }
+      }
+      Ok(false) => {
+        self.blocked.write().remove(&account.id);
       }
     }
   }
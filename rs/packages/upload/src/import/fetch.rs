@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use async_stream::try_stream;
+use bytes::Bytes;
+use constants::{AUDIO_IMPORT_URL_CONNECT_TIMEOUT_SECS, AUDIO_IMPORT_URL_READ_TIMEOUT_SECS};
+use ip::resolve_public;
+use tokio::time::timeout;
+use tokio_stream::Stream;
+use url::Url;
+
+use super::ImportStreamError;
+
+pub struct FetchResponse<S> {
+  /// the last segment of the url path
+  pub filename: String,
+  /// the content-length of the response, if any
+  pub len: Option<u64>,
+  pub data: S,
+}
+
+/// parses an url that can be imported, only http and https urls are accepted
+pub fn parse_url(url: &str) -> Result<Url, ImportStreamError> {
+  let url = Url::parse(url).map_err(|e| ImportStreamError::Fetch(format!("invalid url: {e}")))?;
+
+  match url.scheme() {
+    "http" | "https" => {}
+    scheme => {
+      return Err(ImportStreamError::Fetch(format!(
+        "unsupported url scheme: {scheme}"
+      )))
+    }
+  };
+
+  if url.host_str().is_none() {
+    return Err(ImportStreamError::Fetch(String::from("url has no host")));
+  }
+
+  Ok(url)
+}
+
+fn filename(url: &Url) -> String {
+  let segment = url
+    .path_segments()
+    .and_then(|mut segments| segments.next_back())
+    .filter(|segment| !segment.is_empty());
+
+  match segment {
+    Some(segment) => percent_encoding::percent_decode_str(segment)
+      .decode_utf8_lossy()
+      .into_owned(),
+    None => url.host_str().unwrap_or_default().to_string(),
+  }
+}
+
+/// starts a GET request to `url` and returns the response body as a stream
+///
+/// the host is resolved before the request and all its addresses must be public,
+/// redirects are not followed so the request can not be sent to other hosts
+pub async fn fetch(
+  url: &str,
+) -> Result<
+  FetchResponse<impl Stream<Item = Result<Bytes, ImportStreamError>> + Send + 'static>,
+  ImportStreamError,
+> {
+  let url = parse_url(url)?;
+
+  let addr = resolve_public(&url)
+    .await
+    .map_err(|e| ImportStreamError::Fetch(e.to_string()))?;
+  let host = url.host_str().unwrap_or_default().to_string();
+
+  // the request goes to the address we checked, not to a new resolution of the host
+  let client = reqwest::Client::builder()
+    .resolve(&host, addr)
+    .redirect(reqwest::redirect::Policy::none())
+    .connect_timeout(Duration::from_secs(AUDIO_IMPORT_URL_CONNECT_TIMEOUT_SECS))
+    .build()?;
+
+  // a server that accepts the connection and then stops sending data would hold the import forever,
+  // so each read has a timeout, an overall timeout would cut the downloads of the large archives
+  let read_timeout = Duration::from_secs(AUDIO_IMPORT_URL_READ_TIMEOUT_SECS);
+
+  let mut response = timeout(read_timeout, client.get(url.clone()).send())
+    .await
+    .map_err(|_| read_timeout_error())??;
+
+  let status = response.status();
+  if !status.is_success() {
+    return Err(ImportStreamError::Fetch(format!(
+      "server responded with status {status}"
+    )));
+  }
+
+  let len = response.content_length();
+
+  let data = try_stream! {
+    while let Some(data) = timeout(read_timeout, response.chunk())
+      .await
+      .map_err(|_| read_timeout_error())??
+    {
+      yield data;
+    }
+  };
+
+  Ok(FetchResponse {
+    filename: filename(&url),
+    len,
+    data,
+  })
+}
+
+fn read_timeout_error() -> ImportStreamError {
+  ImportStreamError::Fetch(format!(
+    "server did not send data in {AUDIO_IMPORT_URL_READ_TIMEOUT_SECS} seconds"
+  ))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn parse_urls() {
+    assert!(parse_url("https://example.com/song.mp3").is_ok());
+    assert!(parse_url("http://example.com:8080/a/b.mp3").is_ok());
+    assert!(parse_url("ftp://example.com/song.mp3").is_err());
+    assert!(parse_url("file:///etc/passwd").is_err());
+    assert!(parse_url("not a url").is_err());
+  }
+
+  #[test]
+  fn url_filenames() {
+    let url = Url::parse("https://example.com/music/My%20Song.mp3?x=1").unwrap();
+    assert_eq!(filename(&url), "My Song.mp3");
+    let url = Url::parse("https://example.com/").unwrap();
+    assert_eq!(filename(&url), "example.com");
+  }
+}
@@ -0,0 +1,717 @@
+//! bulk import of audio files from archives and urls
+//!
+//! imports run as background jobs in the deployment that created them,
+//! if that deployment stops the job is taken over by another deployment
+//! and the items that were already imported are skipped
+use std::time::{Duration, Instant};
+
+use async_stream::try_stream;
+use bytes::Bytes;
+use constants::{
+  AUDIO_IMPORT_CANCEL_CHECK_INTERVAL_SECS, AUDIO_IMPORT_MAX_ARCHIVE_ENTRIES,
+  AUDIO_IMPORT_MAX_ARCHIVE_SIZE, AUDIO_IMPORT_MAX_ENTRY_SIZE, AUDIO_IMPORT_MAX_URLS,
+};
+use db::audio_file::{AudioFile, DuplicateMode};
+use db::audio_import::{
+  AudioImport, AudioImportArchiveFormat, AudioImportItem, AudioImportItemState, AudioImportSource,
+  AudioImportState,
+};
+use db::audio_upload_operation::{AudioUploadOperation, State};
+use db::resumable_upload::{ResumableUpload, ResumableUploadState};
+use db::resumable_upload_part::ResumableUploadPart;
+use db::station::Station;
+use db::Model;
+use futures_util::TryStreamExt;
+use log::*;
+use serde_util::DateTime;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+
+use crate::{upload_audio_file, UploadError};
+
+use reader::{ByteReader, Compression};
+
+pub mod fetch;
+pub mod reader;
+pub mod tar;
+pub mod zip;
+
+/// size of the chunks sent to the transcoder for archive entries
+const ENTRY_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// how often the deployment looks for jobs of stopped deployments
+const RESUME_JOB_INTERVAL: Duration = Duration::from_secs(60);
+
+const AUDIO_EXTENSIONS: &[&str] = &[
+  "mp3", "wav", "flac", "ogg", "oga", "opus", "m4a", "aac", "aif", "aiff", "wma", "webm",
+];
+
+/// errors reading the source of an import
+#[derive(Debug, thiserror::Error)]
+pub enum ImportStreamError {
+  #[error("mongo: {0}")]
+  Mongo(#[from] mongodb::error::Error),
+  #[error("http: {0}")]
+  Http(#[from] reqwest::Error),
+  #[error("fetch: {0}")]
+  Fetch(String),
+  #[error("decompress: {0}")]
+  Decompress(std::io::Error),
+  #[error("archive: {0}")]
+  Archive(String),
+  #[error("import cancelled")]
+  Cancelled,
+}
+
+impl ImportStreamError {
+  /// the error reported to an item whose data was interrupted by this error
+  fn for_item(&self) -> Self {
+    match self {
+      Self::Cancelled => Self::Cancelled,
+      e => Self::Archive(e.to_string()),
+    }
+  }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+  #[error("mongo: {0}")]
+  Mongo(#[from] mongodb::error::Error),
+  #[error("station not found: {0}")]
+  StationNotFound(String),
+  #[error("upload not found: {0}")]
+  UploadNotFound(String),
+  #[error("upload not open: {0}")]
+  UploadNotOpen(String),
+  #[error("upload incomplete: received {offset} of {len} bytes")]
+  UploadIncomplete { offset: u64, len: u64 },
+  #[error("unknown archive format: {0}")]
+  UnknownArchiveFormat(String),
+  #[error("no urls to import")]
+  NoUrls,
+  #[error("too many urls, max is {0}")]
+  TooManyUrls(usize),
+  #[error("invalid url: {0}")]
+  InvalidUrl(String),
+  #[error("import not found: {0}")]
+  NotFound(String),
+}
+
+/// the name of the files and directories that are not imported from archives
+fn is_hidden(name: &str) -> bool {
+  name
+    .split('/')
+    .any(|part| part.starts_with('.') || part == "__MACOSX")
+}
+
+/// if an archive entry looks like an audio file by its name
+pub fn is_audio_entry(name: &str) -> bool {
+  if name.ends_with('/') || is_hidden(name) {
+    return false;
+  }
+
+  match basename(name).rsplit_once('.') {
+    None => false,
+    Some((_, ext)) => AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()),
+  }
+}
+
+fn basename(name: &str) -> &str {
+  name.rsplit('/').next().unwrap_or(name)
+}
+
+async fn check_station(station_id: &str) -> Result<(), ImportError> {
+  match Station::get_by_id(station_id).await? {
+    Some(_) => Ok(()),
+    None => Err(ImportError::StationNotFound(station_id.to_string())),
+  }
+}
+
+async fn start(job: AudioImport) -> Result<AudioImport, ImportError> {
+  AudioImport::insert(&job).await?;
+  tokio::spawn(run(job.id.clone()));
+  Ok(job)
+}
+
+/// creates and starts an import of the files at `urls`
+pub async fn create_from_urls(
+  deployment_id: String,
+  station_id: &str,
+  urls: Vec<String>,
+  on_duplicate: DuplicateMode,
+) -> Result<AudioImport, ImportError> {
+  if urls.is_empty() {
+    return Err(ImportError::NoUrls);
+  }
+
+  if urls.len() > AUDIO_IMPORT_MAX_URLS {
+    return Err(ImportError::TooManyUrls(AUDIO_IMPORT_MAX_URLS));
+  }
+
+  for url in urls.iter() {
+    if fetch::parse_url(url).is_err() {
+      return Err(ImportError::InvalidUrl(url.clone()));
+    }
+  }
+
+  check_station(station_id).await?;
+
+  let items = urls
+    .iter()
+    .map(|url| AudioImportItem {
+      name: url.clone(),
+      operation_id: AudioFile::uid(),
+      state: AudioImportItemState::Pending,
+    })
+    .collect();
+
+  let now = DateTime::now();
+  let job = AudioImport {
+    id: AudioImport::uid(),
+    station_id: station_id.to_string(),
+    deployment_id,
+    source: AudioImportSource::Urls { urls },
+    on_duplicate,
+    items,
+    state: AudioImportState::Pending,
+    created_at: now,
+    updated_at: now,
+  };
+
+  start(job).await
+}
+
+/// creates and starts an import of the audio files in an archive sent as a resumable upload
+///
+/// the upload must be complete and is removed when the import ends
+pub async fn create_from_archive(
+  deployment_id: String,
+  station_id: &str,
+  upload_id: &str,
+  on_duplicate: DuplicateMode,
+) -> Result<AudioImport, ImportError> {
+  check_station(station_id).await?;
+
+  let upload = match ResumableUpload::get_by_id(upload_id).await? {
+    Some(upload) if upload.station_id == station_id => upload,
+    _ => return Err(ImportError::UploadNotFound(upload_id.to_string())),
+  };
+
+  if !upload.is_open() {
+    return Err(ImportError::UploadNotOpen(upload.id));
+  }
+
+  if !upload.is_complete() {
+    return Err(ImportError::UploadIncomplete {
+      offset: upload.offset,
+      len: upload.len,
+    });
+  }
+
+  let format = match AudioImportArchiveFormat::from_filename(&upload.filename) {
+    Some(format) => format,
+    None => return Err(ImportError::UnknownArchiveFormat(upload.filename)),
+  };
+
+  // the upload can no longer be appended to or committed
+  let claimed = ResumableUpload::set_state_if(
+    &upload.id,
+    ResumableUploadState::KEY_ENUM_VARIANT_OPEN,
+    ResumableUploadState::Committing,
+  )
+  .await?;
+
+  if !claimed {
+    return Err(ImportError::UploadNotOpen(upload.id));
+  }
+
+  let now = DateTime::now();
+  let job = AudioImport {
+    id: AudioImport::uid(),
+    station_id: station_id.to_string(),
+    deployment_id,
+    source: AudioImportSource::Archive {
+      upload_id: upload.id,
+      filename: upload.filename,
+      format,
+    },
+    on_duplicate,
+    items: vec![],
+    state: AudioImportState::Pending,
+    created_at: now,
+    updated_at: now,
+  };
+
+  start(job).await
+}
+
+/// cancels a pending or running import, the files imported before the cancellation are kept
+///
+/// cancelling an import that already ended returns it unchanged
+pub async fn cancel(job_id: &str) -> Result<AudioImport, ImportError> {
+  AudioImport::set_state_if(
+    job_id,
+    &[
+      AudioImportState::KEY_ENUM_VARIANT_PENDING,
+      AudioImportState::KEY_ENUM_VARIANT_RUNNING,
+    ],
+    AudioImportState::Cancelled {
+      cancelled_at: DateTime::now(),
+    },
+  )
+  .await?;
+
+  match AudioImport::get_by_id(job_id).await? {
+    Some(job) => Ok(job),
+    None => Err(ImportError::NotFound(job_id.to_string())),
+  }
+}
+
+/// checks if the job was cancelled, at most every AUDIO_IMPORT_CANCEL_CHECK_INTERVAL_SECS
+pub struct CancelCheck {
+  job_id: String,
+  checked_at: Option<Instant>,
+  cancelled: bool,
+}
+
+impl CancelCheck {
+  pub fn new(job_id: String) -> Self {
+    Self {
+      job_id,
+      checked_at: None,
+      cancelled: false,
+    }
+  }
+
+  pub async fn is_cancelled(&mut self) -> Result<bool, mongodb::error::Error> {
+    if self.cancelled {
+      return Ok(true);
+    }
+
+    if let Some(checked_at) = self.checked_at {
+      if checked_at.elapsed() < Duration::from_secs(AUDIO_IMPORT_CANCEL_CHECK_INTERVAL_SECS) {
+        return Ok(false);
+      }
+    }
+
+    self.checked_at = Some(Instant::now());
+    self.cancelled = AudioImport::is_cancelled(&self.job_id).await?;
+    Ok(self.cancelled)
+  }
+}
+
+/// ends `stream` with a Cancelled error when the job is cancelled
+fn cancellable<S: Stream<Item = Result<Bytes, ImportStreamError>> + Send + 'static>(
+  job_id: String,
+  stream: S,
+) -> impl Stream<Item = Result<Bytes, ImportStreamError>> + Send + 'static {
+  try_stream! {
+    let mut cancel = CancelCheck::new(job_id);
+    tokio::pin!(stream);
+    while let Some(data) = stream.try_next().await? {
+      if cancel.is_cancelled().await? {
+        Err(ImportStreamError::Cancelled)?;
+      }
+      yield data;
+    }
+  }
+}
+
+/// uploads the items of a job in order
+pub struct Importer<'a> {
+  job: &'a AudioImport,
+  index: usize,
+  /// decompressed size of the archive entries imported by this run
+  archive_len: u64,
+}
+
+impl<'a> Importer<'a> {
+  pub fn new(job: &'a AudioImport) -> Self {
+    Self {
+      job,
+      index: 0,
+      archive_len: 0,
+    }
+  }
+
+  /// if the job reached AUDIO_IMPORT_MAX_ARCHIVE_ENTRIES items, the following entries are ignored
+  pub fn is_full(&self) -> bool {
+    self.index >= AUDIO_IMPORT_MAX_ARCHIVE_ENTRIES
+  }
+
+  /// checks the decompressed size of an archive entry against the entry and the archive limits
+  /// returns the error message of the item if the entry is too large to be imported
+  pub fn check_entry_size(&mut self, size: u64) -> Result<Option<String>, ImportStreamError> {
+    if size > AUDIO_IMPORT_MAX_ENTRY_SIZE {
+      return Ok(Some(format!(
+        "the file is larger than {AUDIO_IMPORT_MAX_ENTRY_SIZE} bytes"
+      )));
+    }
+
+    self.archive_len = self.archive_len.saturating_add(size);
+    if self.archive_len > AUDIO_IMPORT_MAX_ARCHIVE_SIZE {
+      return Err(ImportStreamError::Archive(format!(
+        "the archive is larger than {AUDIO_IMPORT_MAX_ARCHIVE_SIZE} bytes"
+      )));
+    }
+
+    Ok(None)
+  }
+
+  /// gets (or adds to the job) the item for the next entry of the source
+  /// returns None if the item was already handled by this or a previous run of the job
+  pub async fn next_item(
+    &mut self,
+    name: String,
+  ) -> Result<Option<(usize, AudioImportItem)>, ImportStreamError> {
+    if self.is_full() {
+      return Ok(None);
+    }
+
+    let index = self.index;
+    self.index += 1;
+
+    let item = match self.job.items.get(index) {
+      Some(item) => item.clone(),
+      None => {
+        let item = AudioImportItem {
+          name,
+          operation_id: AudioFile::uid(),
+          state: AudioImportItemState::Pending,
+        };
+        AudioImport::push_item(&self.job.id, item.clone()).await?;
+        item
+      }
+    };
+
+    if !matches!(item.state, AudioImportItemState::Pending) {
+      return Ok(None);
+    }
+
+    match AudioUploadOperation::get_by_id(&item.operation_id).await? {
+      None => {}
+
+      Some(operation) => match operation.state {
+        // the job was stopped after the upload of this item but before updating the item
+        State::Success { .. } => {
+          let state = match AudioFile::get_by_id(&operation.id).await? {
+            Some(file) => AudioImportItemState::Success {
+              audio_file_id: file.id,
+            },
+            None => AudioImportItemState::Skipped,
+          };
+          AudioImport::set_item_state(&self.job.id, index, state).await?;
+          return Ok(None);
+        }
+
+        // the upload of this item was interrupted, we start it again
+        State::Pending | State::Error { .. } => {
+          AudioUploadOperation::clean_up_chunks_after_error(&operation.id).await?;
+          AudioUploadOperation::delete_by_id(&operation.id).await?;
+        }
+      },
+    }
+
+    Ok(Some((index, item)))
+  }
+
+  /// uploads the data of an item and stores the result in the job
+  pub async fn upload<S: Stream<Item = Result<Bytes, ImportStreamError>> + Send + 'static>(
+    &self,
+    index: usize,
+    item: AudioImportItem,
+    filename: String,
+    len: Option<u64>,
+    data: S,
+  ) -> Result<AudioImportItemState, mongodb::error::Error> {
+    let result = upload_audio_file(
+      self.job.deployment_id.clone(),
+      self.job.station_id.clone(),
+      Some(item.operation_id),
+      len,
+      filename,
+      self.job.on_duplicate,
+      data,
+    )
+    .await;
+
+    let state = match result {
+      Ok(file) => AudioImportItemState::Success {
+        audio_file_id: file.id,
+      },
+      Err(UploadError::Stream(ImportStreamError::Cancelled)) => AudioImportItemState::Cancelled,
+      Err(e) => {
+        warn!(
+          target: "audio-import",
+          "audio import {} item {} error: {} => {:?}", self.job.id, item.name, e, e
+        );
+        AudioImportItemState::Error {
+          message: e.to_string(),
+        }
+      }
+    };
+
+    AudioImport::set_item_state(&self.job.id, index, state.clone()).await?;
+
+    Ok(state)
+  }
+
+  /// imports the next `len` bytes of `reader` as the entry `name` of an archive
+  pub async fn import_entry<S: Stream<Item = Result<Bytes, ImportStreamError>>>(
+    &mut self,
+    name: String,
+    len: u64,
+    reader: &mut ByteReader<S>,
+    cancel: &mut CancelCheck,
+  ) -> Result<(), ImportStreamError> {
+    let (index, item) = match self.next_item(name).await? {
+      Some(next) => next,
+      None => {
+        reader.skip(len).await?;
+        return Ok(());
+      }
+    };
+
+    if let Some(message) = self.check_entry_size(len)? {
+      AudioImport::set_item_state(&self.job.id, index, AudioImportItemState::Error { message })
+        .await?;
+      reader.skip(len).await?;
+      return Ok(());
+    }
+
+    let filename = basename(&item.name).to_string();
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, ImportStreamError>>(2);
+
+    let upload = self.upload(index, item, filename, Some(len), ReceiverStream::new(rx));
+
+    let feed = async move {
+      let mut remaining = len;
+      let mut tx = Some(tx);
+
+      let result = async {
+        while remaining > 0 {
+          if cancel.is_cancelled().await? {
+            return Err(ImportStreamError::Cancelled);
+          }
+
+          let chunk = match reader
+            .read_chunk(remaining.min(ENTRY_CHUNK_SIZE) as usize)
+            .await?
+          {
+            Some(chunk) => chunk,
+            None => {
+              return Err(ImportStreamError::Archive(String::from(
+                "unexpected end of archive",
+              )))
+            }
+          };
+
+          remaining -= chunk.len() as u64;
+
+          // if the upload ended before the end of the entry we keep reading to reach the next entry
+          if let Some(sender) = &tx {
+            if sender.send(Ok(chunk)).await.is_err() {
+              tx = None;
+            }
+          }
+        }
+
+        Ok(())
+      }
+      .await;
+
+      // the upload must not take a truncated entry as a complete file
+      if let (Err(e), Some(tx)) = (&result, &tx) {
+        let _ = tx.send(Err(e.for_item())).await;
+      }
+
+      result
+    };
+
+    let (uploaded, fed) = tokio::join!(upload, feed);
+    fed?;
+    uploaded?;
+
+    Ok(())
+  }
+}
+
+async fn import_urls(
+  job: &AudioImport,
+  urls: &[String],
+  cancel: &mut CancelCheck,
+) -> Result<(), ImportStreamError> {
+  let mut importer = Importer::new(job);
+
+  for url in urls {
+    if cancel.is_cancelled().await? {
+      return Err(ImportStreamError::Cancelled);
+    }
+
+    let (index, item) = match importer.next_item(url.clone()).await? {
+      Some(next) => next,
+      None => continue,
+    };
+
+    match fetch::fetch(url).await {
+      Err(e) => {
+        let state = AudioImportItemState::Error {
+          message: e.to_string(),
+        };
+        AudioImport::set_item_state(&job.id, index, state).await?;
+      }
+
+      Ok(response) => {
+        let data = cancellable(job.id.clone(), response.data);
+        let state = importer
+          .upload(index, item, response.filename, response.len, data)
+          .await?;
+
+        if matches!(state, AudioImportItemState::Cancelled) {
+          return Err(ImportStreamError::Cancelled);
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
+fn upload_stream(
+  upload_id: &str,
+) -> impl Stream<Item = Result<Bytes, ImportStreamError>> + Send + 'static {
+  ResumableUploadPart::stream(upload_id).map_err(ImportStreamError::from)
+}
+
+async fn import_source(job: &AudioImport) -> Result<(), ImportStreamError> {
+  let mut cancel = CancelCheck::new(job.id.clone());
+
+  match &job.source {
+    AudioImportSource::Urls { urls } => import_urls(job, urls, &mut cancel).await,
+
+    AudioImportSource::Archive {
+      upload_id, format, ..
+    } => match format {
+      AudioImportArchiveFormat::Zip => zip::import(job, upload_id, &mut cancel).await,
+
+      AudioImportArchiveFormat::Tar => {
+        let reader = ByteReader::new(upload_stream(upload_id));
+        tar::import(job, reader, &mut cancel).await
+      }
+
+      AudioImportArchiveFormat::TarGz => {
+        let reader = ByteReader::new(reader::inflate(
+          upload_stream(upload_id),
+          Compression::Gzip,
+          AUDIO_IMPORT_MAX_ARCHIVE_SIZE,
+        ));
+        tar::import(job, reader, &mut cancel).await
+      }
+    },
+  }
+}
+
+async fn run_inner(job_id: &str) -> Result<(), mongodb::error::Error> {
+  let job = match AudioImport::get_by_id(job_id).await? {
+    Some(job) => job,
+    None => return Ok(()),
+  };
+
+  let claimed = AudioImport::set_state_if(
+    &job.id,
+    &[
+      AudioImportState::KEY_ENUM_VARIANT_PENDING,
+      AudioImportState::KEY_ENUM_VARIANT_RUNNING,
+    ],
+    AudioImportState::Running,
+  )
+  .await?;
+
+  if !claimed {
+    return Ok(());
+  }
+
+  info!(target: "audio-import", "audio import {} started", job.id);
+
+  let result = import_source(&job).await;
+
+  let state = match result {
+    Ok(()) => AudioImportState::Finished {
+      finished_at: DateTime::now(),
+    },
+    Err(ImportStreamError::Cancelled) => AudioImportState::Cancelled {
+      cancelled_at: DateTime::now(),
+    },
+    Err(e) => {
+      warn!(target: "audio-import", "audio import {} failed: {} => {:?}", job.id, e, e);
+      AudioImportState::Failed {
+        message: e.to_string(),
+        failed_at: DateTime::now(),
+      }
+    }
+  };
+
+  // if the job was cancelled while running the cancelled state is kept
+  AudioImport::set_state_if(
+    &job.id,
+    &[AudioImportState::KEY_ENUM_VARIANT_RUNNING],
+    state,
+  )
+  .await?;
+
+  AudioImport::cancel_pending_items(&job.id).await?;
+
+  if let AudioImportSource::Archive { upload_id, .. } = &job.source {
+    ResumableUpload::remove(upload_id).await?;
+  }
+
+  info!(target: "audio-import", "audio import {} ended", job.id);
+
+  Ok(())
+}
+
+/// runs an import job until it ends
+pub async fn run(job_id: String) {
+  if let Err(e) = run_inner(&job_id).await {
+    error!(target: "audio-import", "mongodb error running audio import {job_id}: {e} => {e:?}");
+  }
+}
+
+/// periodically takes over and resumes the import jobs of deployments that are no longer active
+pub async fn start_resume_job(deployment_id: String) {
+  info!(target: "audio-import", "audio import resume job started");
+  let mut interval = tokio::time::interval(RESUME_JOB_INTERVAL);
+  loop {
+    // first tick is instantaneous
+    interval.tick().await;
+
+    match AudioImport::take_over_orphans(&deployment_id).await {
+      Ok(ids) => {
+        for id in ids {
+          info!(target: "audio-import", "resuming audio import {id}");
+          tokio::spawn(run(id));
+        }
+      }
+      Err(e) => {
+        error!(target: "audio-import", "mongodb error taking over audio imports: {e} => {e:?}");
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn audio_entries() {
+    assert!(is_audio_entry("song.mp3"));
+    assert!(is_audio_entry("Music/Album/01 - Track.FLAC"));
+    assert!(!is_audio_entry("Music/cover.jpg"));
+    assert!(!is_audio_entry("Music/"));
+    assert!(!is_audio_entry("__MACOSX/Music/._song.mp3"));
+    assert!(!is_audio_entry("Music/.hidden.mp3"));
+    assert!(!is_audio_entry("README"));
+  }
+}
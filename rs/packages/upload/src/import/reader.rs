@@ -0,0 +1,252 @@
+use std::io::Write;
+use std::pin::Pin;
+
+use async_stream::try_stream;
+use bytes::{Buf, Bytes};
+use flate2::write::{DeflateDecoder, GzDecoder};
+use tokio_stream::{Stream, StreamExt};
+
+use super::ImportStreamError;
+
+/// reads bytes in arbitrary amounts from a stream of chunks
+pub struct ByteReader<S> {
+  stream: Pin<Box<S>>,
+  buf: Bytes,
+}
+
+impl<S: Stream<Item = Result<Bytes, ImportStreamError>>> ByteReader<S> {
+  pub fn new(stream: S) -> Self {
+    Self {
+      stream: Box::pin(stream),
+      buf: Bytes::new(),
+    }
+  }
+
+  /// returns false if the stream has ended
+  async fn fill(&mut self) -> Result<bool, ImportStreamError> {
+    while self.buf.is_empty() {
+      match self.stream.next().await {
+        None => return Ok(false),
+        Some(item) => self.buf = item?,
+      }
+    }
+    Ok(true)
+  }
+
+  /// reads up to `max` bytes, returns None if the stream has ended
+  pub async fn read_chunk(&mut self, max: usize) -> Result<Option<Bytes>, ImportStreamError> {
+    if !self.fill().await? {
+      return Ok(None);
+    }
+    let n = max.min(self.buf.len());
+    Ok(Some(self.buf.split_to(n)))
+  }
+
+  /// reads exactly `n` bytes, returns None if the stream has ended before the first byte
+  pub async fn read_exact(&mut self, n: usize) -> Result<Option<Bytes>, ImportStreamError> {
+    if self.buf.len() >= n {
+      return Ok(Some(self.buf.split_to(n)));
+    }
+
+    let mut out = Vec::with_capacity(n);
+    while out.len() < n {
+      match self.read_chunk(n - out.len()).await? {
+        Some(chunk) => out.extend_from_slice(&chunk),
+        None if out.is_empty() => return Ok(None),
+        None => {
+          return Err(ImportStreamError::Archive(
+            "unexpected end of archive".into(),
+          ))
+        }
+      }
+    }
+
+    Ok(Some(Bytes::from(out)))
+  }
+
+  /// discards exactly `n` bytes
+  pub async fn skip(&mut self, mut n: u64) -> Result<(), ImportStreamError> {
+    while n > 0 {
+      if !self.fill().await? {
+        return Err(ImportStreamError::Archive(
+          "unexpected end of archive".into(),
+        ));
+      }
+      let len = n.min(self.buf.len() as u64) as usize;
+      self.buf.advance(len);
+      n -= len as u64;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+  /// a gzip file, as in .tar.gz archives
+  Gzip,
+  /// raw deflate data, as in zip entries
+  Deflate,
+}
+
+enum Decoder {
+  Gzip(GzDecoder<Vec<u8>>),
+  Deflate(DeflateDecoder<Vec<u8>>),
+}
+
+impl Decoder {
+  fn new(compression: Compression) -> Self {
+    match compression {
+      Compression::Gzip => Self::Gzip(GzDecoder::new(vec![])),
+      Compression::Deflate => Self::Deflate(DeflateDecoder::new(vec![])),
+    }
+  }
+
+  fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+    match self {
+      Self::Gzip(decoder) => decoder.write_all(data),
+      Self::Deflate(decoder) => decoder.write_all(data),
+    }
+  }
+
+  fn take(&mut self) -> Vec<u8> {
+    match self {
+      Self::Gzip(decoder) => std::mem::take(decoder.get_mut()),
+      Self::Deflate(decoder) => std::mem::take(decoder.get_mut()),
+    }
+  }
+
+  fn finish(self) -> std::io::Result<Vec<u8>> {
+    match self {
+      Self::Gzip(decoder) => decoder.finish(),
+      Self::Deflate(decoder) => decoder.finish(),
+    }
+  }
+}
+
+/// compressed data is written to the decoder in pieces of this size,
+/// so the output of a highly compressed chunk is checked against the max len before it grows
+const INFLATE_INPUT_CHUNK_SIZE: usize = 1024;
+
+fn too_large(max_len: u64) -> ImportStreamError {
+  ImportStreamError::Archive(format!(
+    "the decompressed data is larger than {max_len} bytes"
+  ))
+}
+
+/// decompresses a stream of compressed chunks
+/// the stream fails if the decompressed data is larger than `max_len`
+pub fn inflate<S: Stream<Item = Result<Bytes, ImportStreamError>> + Send + 'static>(
+  stream: S,
+  compression: Compression,
+  max_len: u64,
+) -> impl Stream<Item = Result<Bytes, ImportStreamError>> + Send + 'static {
+  try_stream! {
+    let mut decoder = Decoder::new(compression);
+    let mut len = 0u64;
+    tokio::pin!(stream);
+
+    while let Some(data) = stream.next().await {
+      let data = data?;
+      for piece in data.chunks(INFLATE_INPUT_CHUNK_SIZE) {
+        decoder.write_all(piece).map_err(ImportStreamError::Decompress)?;
+        let out = decoder.take();
+        len += out.len() as u64;
+        if len > max_len {
+          Err(too_large(max_len))?;
+        }
+        if !out.is_empty() {
+          yield Bytes::from(out);
+        }
+      }
+    }
+
+    let out = decoder.finish().map_err(ImportStreamError::Decompress)?;
+    len += out.len() as u64;
+    if len > max_len {
+      Err(too_large(max_len))?;
+    }
+    if !out.is_empty() {
+      yield Bytes::from(out);
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use flate2::write::{DeflateEncoder, GzEncoder};
+  use futures_util::TryStreamExt;
+
+  fn chunks(data: &[u8], size: usize) -> impl Stream<Item = Result<Bytes, ImportStreamError>> {
+    let chunks: Vec<_> = data
+      .chunks(size)
+      .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+      .collect();
+    tokio_stream::iter(chunks)
+  }
+
+  #[tokio::test]
+  async fn byte_reader_read_and_skip() {
+    let data: Vec<u8> = (0..=255).collect();
+    let mut reader = ByteReader::new(chunks(&data, 7));
+
+    let head = reader.read_exact(10).await.unwrap().unwrap();
+    assert_eq!(&head[..], &data[0..10]);
+
+    reader.skip(100).await.unwrap();
+
+    let next = reader.read_exact(20).await.unwrap().unwrap();
+    assert_eq!(&next[..], &data[110..130]);
+
+    reader.skip(126).await.unwrap();
+    assert!(reader.read_exact(1).await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn byte_reader_truncated() {
+    let mut reader = ByteReader::new(chunks(&[1, 2, 3], 2));
+    assert!(reader.read_exact(4).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn inflate_gzip_and_deflate() {
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+    let mut gz = GzEncoder::new(vec![], flate2::Compression::default());
+    gz.write_all(&data).unwrap();
+    let gz = gz.finish().unwrap();
+
+    let mut deflate = DeflateEncoder::new(vec![], flate2::Compression::default());
+    deflate.write_all(&data).unwrap();
+    let deflate = deflate.finish().unwrap();
+
+    for (compressed, compression) in [(gz, Compression::Gzip), (deflate, Compression::Deflate)] {
+      let stream = tokio_stream::iter(
+        compressed
+          .chunks(1000)
+          .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+          .collect::<Vec<_>>(),
+      );
+      let out: Vec<Bytes> = inflate(stream, compression, data.len() as u64)
+        .try_collect()
+        .await
+        .unwrap();
+      assert_eq!(out.concat(), data);
+    }
+  }
+
+  #[tokio::test]
+  async fn inflate_max_len() {
+    let data = vec![0u8; 10_000_000];
+
+    let mut deflate = DeflateEncoder::new(vec![], flate2::Compression::default());
+    deflate.write_all(&data).unwrap();
+    let deflate = deflate.finish().unwrap();
+
+    let stream = tokio_stream::iter(vec![Ok(Bytes::from(deflate))]);
+    let result: Result<Vec<Bytes>, _> = inflate(stream, Compression::Deflate, 1_000_000)
+      .try_collect()
+      .await;
+    assert!(matches!(result, Err(ImportStreamError::Archive(_))));
+  }
+}
@@ -0,0 +1,318 @@
+use bytes::Bytes;
+use constants::AUDIO_IMPORT_MAX_ARCHIVE_ENTRIES;
+use db::audio_import::AudioImport;
+use log::*;
+use tokio_stream::Stream;
+
+use super::reader::ByteReader;
+use super::{CancelCheck, ImportStreamError, Importer};
+
+pub const BLOCK_SIZE: u64 = 512;
+
+/// the gnu long name and pax headers are read in memory, this is the max size we accept for them
+const MAX_META_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+  File,
+  /// gnu long name, the data of the entry is the name of the next entry
+  LongName,
+  /// pax extended header for the next entry
+  Pax,
+  /// pax extended header for all the following entries
+  GlobalPax,
+  Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+  pub name: String,
+  pub size: u64,
+  pub kind: EntryKind,
+}
+
+fn field_str(field: &[u8]) -> String {
+  let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+  String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn parse_octal(field: &[u8]) -> Result<u64, String> {
+  let s = field_str(field);
+  let s = s.trim_matches(|c: char| c == ' ' || c == '\0');
+  if s.is_empty() {
+    return Ok(0);
+  }
+  u64::from_str_radix(s, 8).map_err(|_| format!("invalid octal number in tar header: {s:?}"))
+}
+
+fn parse_size(field: &[u8]) -> Result<u64, String> {
+  // gnu base-256 encoding for files larger than 8GB
+  if field[0] & 0x80 != 0 {
+    let mut value: u64 = (field[0] & 0x7f) as u64;
+    for b in &field[1..] {
+      value = value
+        .checked_mul(256)
+        .and_then(|v| v.checked_add(*b as u64))
+        .ok_or_else(|| String::from("tar entry size overflow"))?;
+    }
+    return Ok(value);
+  }
+
+  parse_octal(field)
+}
+
+/// parses a 512 bytes tar header block, returns None for the zero blocks at the end of the archive
+pub fn parse_header(block: &[u8]) -> Result<Option<Header>, String> {
+  if block.len() != BLOCK_SIZE as usize {
+    return Err(String::from("invalid tar header size"));
+  }
+
+  if block.iter().all(|b| *b == 0) {
+    return Ok(None);
+  }
+
+  let expected = parse_octal(&block[148..156])?;
+  let sum: u64 = block
+    .iter()
+    .enumerate()
+    .map(|(i, b)| {
+      if (148..156).contains(&i) {
+        b' ' as u64
+      } else {
+        *b as u64
+      }
+    })
+    .sum();
+
+  if sum != expected {
+    return Err(String::from("invalid tar header checksum"));
+  }
+
+  let mut name = field_str(&block[0..100]);
+
+  if &block[257..262] == b"ustar" {
+    let prefix = field_str(&block[345..500]);
+    if !prefix.is_empty() {
+      name = format!("{prefix}/{name}");
+    }
+  }
+
+  let size = parse_size(&block[124..136])?;
+
+  let kind = match block[156] {
+    b'0' | b'\0' | b'7' => EntryKind::File,
+    b'L' => EntryKind::LongName,
+    b'x' => EntryKind::Pax,
+    b'g' => EntryKind::GlobalPax,
+    _ => EntryKind::Other,
+  };
+
+  Ok(Some(Header { name, size, kind }))
+}
+
+/// size of an entry's data including the padding up to the next block
+pub fn padded(size: u64) -> u64 {
+  size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+}
+
+/// the name of the next entry from the data of a gnu long name entry
+pub fn long_name(data: &[u8]) -> String {
+  field_str(data)
+}
+
+/// the path record of a pax extended header
+pub fn pax_path(data: &[u8]) -> Option<String> {
+  let mut rest = data;
+  while !rest.is_empty() {
+    // each record is "<len> <key>=<value>\n" where len includes itself
+    let space = rest.iter().position(|b| *b == b' ')?;
+    let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+    if len <= space || len > rest.len() {
+      return None;
+    }
+
+    let record = &rest[space + 1..len];
+    let record = record.strip_suffix(b"\n").unwrap_or(record);
+    if let Some(value) = record.strip_prefix(b"path=") {
+      return Some(String::from_utf8_lossy(value).into_owned());
+    }
+
+    rest = &rest[len..];
+  }
+
+  None
+}
+
+async fn read_meta<S: Stream<Item = Result<Bytes, ImportStreamError>>>(
+  reader: &mut ByteReader<S>,
+  size: u64,
+) -> Result<Bytes, ImportStreamError> {
+  if size > MAX_META_SIZE {
+    return Err(ImportStreamError::Archive(String::from(
+      "tar extended header too large",
+    )));
+  }
+
+  let data = match reader.read_exact(size as usize).await? {
+    Some(data) => data,
+    None => {
+      return Err(ImportStreamError::Archive(String::from(
+        "unexpected end of archive",
+      )))
+    }
+  };
+
+  reader.skip(padded(size) - size).await?;
+
+  Ok(data)
+}
+
+/// imports the audio files of a tar archive, the archive is read sequentially
+pub async fn import<S: Stream<Item = Result<Bytes, ImportStreamError>>>(
+  job: &AudioImport,
+  mut reader: ByteReader<S>,
+  cancel: &mut CancelCheck,
+) -> Result<(), ImportStreamError> {
+  let mut importer = Importer::new(job);
+  let mut next_name: Option<String> = None;
+
+  loop {
+    if cancel.is_cancelled().await? {
+      return Err(ImportStreamError::Cancelled);
+    }
+
+    let block = match reader.read_exact(BLOCK_SIZE as usize).await? {
+      Some(block) => block,
+      None => break,
+    };
+
+    let header = match parse_header(&block).map_err(ImportStreamError::Archive)? {
+      Some(header) => header,
+      None => break,
+    };
+
+    match header.kind {
+      EntryKind::LongName => {
+        let data = read_meta(&mut reader, header.size).await?;
+        next_name = Some(long_name(&data));
+      }
+
+      EntryKind::Pax => {
+        let data = read_meta(&mut reader, header.size).await?;
+        if let Some(path) = pax_path(&data) {
+          next_name = Some(path);
+        }
+      }
+
+      EntryKind::File => {
+        let name = next_name.take().unwrap_or(header.name);
+        if super::is_audio_entry(&name) {
+          if importer.is_full() {
+            info!(
+              target: "audio-import",
+              "audio import {} reached {AUDIO_IMPORT_MAX_ARCHIVE_ENTRIES} files, the following entries are ignored",
+              job.id
+            );
+            break;
+          }
+
+          importer
+            .import_entry(name, header.size, &mut reader, cancel)
+            .await?;
+          reader.skip(padded(header.size) - header.size).await?;
+        } else {
+          reader.skip(padded(header.size)).await?;
+        }
+      }
+
+      EntryKind::GlobalPax | EntryKind::Other => {
+        next_name = None;
+        reader.skip(padded(header.size)).await?;
+      }
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn header(name: &str, size: u64, kind: u8) -> Vec<u8> {
+    let mut block = vec![0u8; 512];
+    block[..name.len()].copy_from_slice(name.as_bytes());
+    let size = format!("{size:011o}\0");
+    block[124..136].copy_from_slice(size.as_bytes());
+    block[156] = kind;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+    block[148..156].copy_from_slice(b"        ");
+    let sum: u64 = block.iter().map(|b| *b as u64).sum();
+    let sum = format!("{sum:06o}\0 ");
+    block[148..156].copy_from_slice(sum.as_bytes());
+    block
+  }
+
+  #[test]
+  fn parse_file_header() {
+    let block = header("music/song.mp3", 1234, b'0');
+    let header = parse_header(&block).unwrap().unwrap();
+    assert_eq!(
+      header,
+      Header {
+        name: String::from("music/song.mp3"),
+        size: 1234,
+        kind: EntryKind::File,
+      }
+    );
+  }
+
+  #[test]
+  fn parse_header_with_prefix() {
+    let mut block = header("song.mp3", 1, b'0');
+    block[345..350].copy_from_slice(b"music");
+    block[148..156].copy_from_slice(b"        ");
+    let sum: u64 = block.iter().map(|b| *b as u64).sum();
+    block[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+    let header = parse_header(&block).unwrap().unwrap();
+    assert_eq!(header.name, "music/song.mp3");
+  }
+
+  #[test]
+  fn parse_header_bad_checksum() {
+    let mut block = header("song.mp3", 1, b'0');
+    block[0] = b'x';
+    assert!(parse_header(&block).is_err());
+  }
+
+  #[test]
+  fn parse_end_of_archive() {
+    assert_eq!(parse_header(&[0u8; 512]).unwrap(), None);
+  }
+
+  #[test]
+  fn parse_base_256_size() {
+    let mut field = [0u8; 12];
+    field[0] = 0x80;
+    field[11] = 1;
+    field[10] = 2;
+    assert_eq!(parse_size(&field).unwrap(), 513);
+  }
+
+  #[test]
+  fn padded_sizes() {
+    assert_eq!(padded(0), 0);
+    assert_eq!(padded(1), 512);
+    assert_eq!(padded(512), 512);
+    assert_eq!(padded(513), 1024);
+  }
+
+  #[test]
+  fn pax_records() {
+    let data = b"30 mtime=1350244992.023960108\n28 path=music/long-name.mp3\n";
+    assert_eq!(pax_path(data), Some(String::from("music/long-name.mp3")));
+    assert_eq!(pax_path(b"20 ctime=1350244992\n"), None);
+    assert_eq!(long_name(b"music/long-name.mp3\0\0"), "music/long-name.mp3");
+  }
+}
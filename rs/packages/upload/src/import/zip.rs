@@ -0,0 +1,459 @@
+use bytes::Bytes;
+use constants::AUDIO_IMPORT_MAX_ARCHIVE_ENTRIES;
+use db::audio_import::{AudioImport, AudioImportItemState};
+use db::resumable_upload::ResumableUpload;
+use db::resumable_upload_part::ResumableUploadPart;
+use db::Model;
+use futures_util::TryStreamExt;
+use log::*;
+
+use super::reader::{inflate, Compression};
+use super::{CancelCheck, ImportStreamError, Importer};
+
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const EOCD_SIZE: usize = 22;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const ZIP64_LOCATOR_SIZE: usize = 20;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_EOCD_SIZE: usize = 56;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+pub const LOCAL_HEADER_SIZE: usize = 30;
+const MAX_COMMENT_SIZE: usize = 0xffff;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+  u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+  u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], at: usize) -> u64 {
+  u64::from_le_bytes(data[at..at + 8].try_into().unwrap())
+}
+
+fn invalid(message: &str) -> String {
+  format!("invalid zip archive: {message}")
+}
+
+/// location of the central directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CentralDirectory {
+  pub entries: u64,
+  pub size: u64,
+  pub offset: u64,
+}
+
+/// the end of central directory record found in the tail of the archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eocd {
+  Zip32(CentralDirectory),
+  /// the archive uses zip64, the zip64 end of central directory record is at `offset`
+  Zip64 {
+    offset: u64,
+  },
+}
+
+/// finds and parses the end of central directory record in the last bytes of the archive
+///
+/// `tail_offset` is the position of `tail` in the archive
+pub fn parse_eocd(tail: &[u8], tail_offset: u64) -> Result<Eocd, String> {
+  if tail.len() < EOCD_SIZE {
+    return Err(invalid("end of central directory not found"));
+  }
+
+  // the record is followed by a comment of up to 64KB so we search it backwards
+  let pos = (0..=tail.len() - EOCD_SIZE)
+    .rev()
+    .find(|i| {
+      u32_at(tail, *i) == EOCD_SIGNATURE
+        && i + EOCD_SIZE + u16_at(tail, i + 20) as usize == tail.len()
+    })
+    .ok_or_else(|| invalid("end of central directory not found"))?;
+
+  let record = &tail[pos..];
+  let entries = u16_at(record, 10);
+  let size = u32_at(record, 12);
+  let offset = u32_at(record, 16);
+
+  if entries == 0xffff || size == 0xffff_ffff || offset == 0xffff_ffff {
+    if pos < ZIP64_LOCATOR_SIZE {
+      return Err(invalid("zip64 locator not found"));
+    }
+
+    let locator = &tail[pos - ZIP64_LOCATOR_SIZE..pos];
+    if u32_at(locator, 0) != ZIP64_LOCATOR_SIGNATURE {
+      return Err(invalid("zip64 locator not found"));
+    }
+
+    return Ok(Eocd::Zip64 {
+      offset: u64_at(locator, 8),
+    });
+  }
+
+  // the central directory ends where the end of central directory record starts
+  if offset as u64 + size as u64 > tail_offset + pos as u64 {
+    return Err(invalid("central directory out of bounds"));
+  }
+
+  Ok(Eocd::Zip32(CentralDirectory {
+    entries: entries as u64,
+    size: size as u64,
+    offset: offset as u64,
+  }))
+}
+
+/// parses the zip64 end of central directory record
+pub fn parse_zip64_eocd(record: &[u8]) -> Result<CentralDirectory, String> {
+  if record.len() < ZIP64_EOCD_SIZE || u32_at(record, 0) != ZIP64_EOCD_SIGNATURE {
+    return Err(invalid("zip64 end of central directory not found"));
+  }
+
+  Ok(CentralDirectory {
+    entries: u64_at(record, 32),
+    size: u64_at(record, 40),
+    offset: u64_at(record, 48),
+  })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+  pub name: String,
+  pub method: u16,
+  pub encrypted: bool,
+  pub compressed_size: u64,
+  pub size: u64,
+  pub local_header_offset: u64,
+}
+
+/// parses the entries of the central directory
+pub fn parse_central_directory(data: &[u8], entries: u64) -> Result<Vec<Entry>, String> {
+  let mut list = vec![];
+  let mut pos = 0;
+
+  for _ in 0..entries {
+    if pos + CENTRAL_HEADER_SIZE > data.len() || u32_at(data, pos) != CENTRAL_HEADER_SIGNATURE {
+      return Err(invalid("bad central directory entry"));
+    }
+
+    let header = &data[pos..];
+    let flags = u16_at(header, 8);
+    let method = u16_at(header, 10);
+    let mut compressed_size = u32_at(header, 20) as u64;
+    let mut size = u32_at(header, 24) as u64;
+    let name_len = u16_at(header, 28) as usize;
+    let extra_len = u16_at(header, 30) as usize;
+    let comment_len = u16_at(header, 32) as usize;
+    let mut local_header_offset = u32_at(header, 42) as u64;
+
+    let name_start = CENTRAL_HEADER_SIZE;
+    let extra_start = name_start + name_len;
+    let end = extra_start + extra_len + comment_len;
+    if pos + end > data.len() {
+      return Err(invalid("bad central directory entry"));
+    }
+
+    let name = String::from_utf8_lossy(&header[name_start..extra_start]).into_owned();
+
+    // the zip64 extra field has the values that do not fit in the header, in this order
+    let mut extra = &header[extra_start..extra_start + extra_len];
+    while extra.len() >= 4 {
+      let id = u16_at(extra, 0);
+      let len = (u16_at(extra, 2) as usize).min(extra.len() - 4);
+      let mut field = &extra[4..4 + len];
+      if id == 0x0001 {
+        for value in [&mut size, &mut compressed_size, &mut local_header_offset] {
+          if *value == 0xffff_ffff && field.len() >= 8 {
+            *value = u64_at(field, 0);
+            field = &field[8..];
+          }
+        }
+      }
+      extra = &extra[4 + len..];
+    }
+
+    list.push(Entry {
+      name,
+      method,
+      encrypted: flags & 1 != 0,
+      compressed_size,
+      size,
+      local_header_offset,
+    });
+
+    pos += end;
+  }
+
+  Ok(list)
+}
+
+/// the position of the data of an entry from its local header
+pub fn local_data_offset(header: &[u8], local_header_offset: u64) -> Result<u64, String> {
+  if header.len() < LOCAL_HEADER_SIZE || u32_at(header, 0) != LOCAL_HEADER_SIGNATURE {
+    return Err(invalid("bad local file header"));
+  }
+
+  let name_len = u16_at(header, 26) as u64;
+  let extra_len = u16_at(header, 28) as u64;
+
+  Ok(local_header_offset.saturating_add(LOCAL_HEADER_SIZE as u64 + name_len + extra_len))
+}
+
+async fn read(upload_id: &str, start: u64, end: u64) -> Result<Bytes, ImportStreamError> {
+  let data = ResumableUploadPart::read_range(upload_id, start, end).await?;
+  if data.len() as u64 != end - start {
+    return Err(ImportStreamError::Archive(invalid(
+      "unexpected end of archive",
+    )));
+  }
+  Ok(data)
+}
+
+async fn read_entries(upload_id: &str, len: u64) -> Result<Vec<Entry>, ImportStreamError> {
+  let tail_len = len.min((EOCD_SIZE + MAX_COMMENT_SIZE) as u64);
+  let tail_offset = len - tail_len;
+  let tail = read(upload_id, tail_offset, len).await?;
+
+  let directory = match parse_eocd(&tail, tail_offset).map_err(ImportStreamError::Archive)? {
+    Eocd::Zip32(directory) => directory,
+    Eocd::Zip64 { offset } => {
+      if offset.saturating_add(ZIP64_EOCD_SIZE as u64) > len {
+        return Err(ImportStreamError::Archive(invalid(
+          "zip64 end of central directory out of bounds",
+        )));
+      }
+      let record = read(upload_id, offset, offset + ZIP64_EOCD_SIZE as u64).await?;
+      parse_zip64_eocd(&record).map_err(ImportStreamError::Archive)?
+    }
+  };
+
+  if directory.offset.saturating_add(directory.size) > len {
+    return Err(ImportStreamError::Archive(invalid(
+      "central directory out of bounds",
+    )));
+  }
+
+  let data = read(
+    upload_id,
+    directory.offset,
+    directory.offset + directory.size,
+  )
+  .await?;
+
+  parse_central_directory(&data, directory.entries).map_err(ImportStreamError::Archive)
+}
+
+/// imports the audio files of a zip archive, the entries are read from their position in the upload
+pub async fn import(
+  job: &AudioImport,
+  upload_id: &str,
+  cancel: &mut CancelCheck,
+) -> Result<(), ImportStreamError> {
+  let upload = match ResumableUpload::get_by_id(upload_id).await? {
+    Some(upload) => upload,
+    None => {
+      return Err(ImportStreamError::Archive(format!(
+        "upload {upload_id} not found"
+      )))
+    }
+  };
+
+  let entries = read_entries(&upload.id, upload.len).await?;
+
+  let mut importer = Importer::new(job);
+
+  for entry in entries {
+    if !super::is_audio_entry(&entry.name) {
+      continue;
+    }
+
+    if importer.is_full() {
+      info!(
+        target: "audio-import",
+        "audio import {} reached {AUDIO_IMPORT_MAX_ARCHIVE_ENTRIES} files, the following entries are ignored",
+        job.id
+      );
+      break;
+    }
+
+    if cancel.is_cancelled().await? {
+      return Err(ImportStreamError::Cancelled);
+    }
+
+    let (index, item) = match importer.next_item(entry.name.clone()).await? {
+      Some(next) => next,
+      None => continue,
+    };
+
+    let unsupported = if entry.encrypted {
+      Some(String::from("encrypted zip entries are not supported"))
+    } else if entry.method != METHOD_STORED && entry.method != METHOD_DEFLATE {
+      Some(format!(
+        "zip compression method {} is not supported",
+        entry.method
+      ))
+    } else {
+      // the data of a stored entry is its compressed size, whatever its declared size is
+      importer.check_entry_size(entry.size.max(entry.compressed_size))?
+    };
+
+    if let Some(message) = unsupported {
+      AudioImport::set_item_state(&job.id, index, AudioImportItemState::Error { message }).await?;
+      continue;
+    }
+
+    if entry.local_header_offset >= upload.len {
+      return Err(ImportStreamError::Archive(invalid("entry out of bounds")));
+    }
+
+    let header_end = (entry.local_header_offset + LOCAL_HEADER_SIZE as u64).min(upload.len);
+    let header = read(&upload.id, entry.local_header_offset, header_end).await?;
+    let start =
+      local_data_offset(&header, entry.local_header_offset).map_err(ImportStreamError::Archive)?;
+    let end = start.saturating_add(entry.compressed_size);
+
+    if end > upload.len {
+      return Err(ImportStreamError::Archive(invalid("entry out of bounds")));
+    }
+
+    let compressed =
+      ResumableUploadPart::stream_range(&upload.id, start, end).map_err(ImportStreamError::from);
+
+    let filename = super::basename(&entry.name).to_string();
+
+    let state = if entry.method == METHOD_DEFLATE {
+      // the declared size is the limit, so a zip entry can not inflate past the checked size
+      let data = super::cancellable(
+        job.id.clone(),
+        inflate(compressed, Compression::Deflate, entry.size),
+      );
+      importer
+        .upload(index, item, filename, Some(entry.size), data)
+        .await?
+    } else {
+      let data = super::cancellable(job.id.clone(), compressed);
+      importer
+        .upload(index, item, filename, Some(entry.size), data)
+        .await?
+    };
+
+    if matches!(state, AudioImportItemState::Cancelled) {
+      return Err(ImportStreamError::Cancelled);
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  /// a zip archive with one stored entry
+  fn stored_zip(name: &str, content: &[u8], comment: &[u8]) -> Vec<u8> {
+    let mut zip = vec![];
+
+    zip.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+    zip.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    zip.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&0u16.to_le_bytes());
+    zip.extend_from_slice(name.as_bytes());
+    zip.extend_from_slice(content);
+
+    let cd_offset = zip.len();
+    zip.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+    zip.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    zip.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    zip.extend_from_slice(&0u32.to_le_bytes());
+    zip.extend_from_slice(name.as_bytes());
+    let cd_size = zip.len() - cd_offset;
+
+    zip.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+    zip.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+    zip.extend_from_slice(&(cd_size as u32).to_le_bytes());
+    zip.extend_from_slice(&(cd_offset as u32).to_le_bytes());
+    zip.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+    zip.extend_from_slice(comment);
+
+    zip
+  }
+
+  #[test]
+  fn read_stored_entry() {
+    let content = b"hello audio";
+    let zip = stored_zip("music/song.mp3", content, b"a comment");
+
+    let directory = match parse_eocd(&zip, 0).unwrap() {
+      Eocd::Zip32(directory) => directory,
+      Eocd::Zip64 { .. } => panic!("unexpected zip64"),
+    };
+    assert_eq!(directory.entries, 1);
+
+    let start = directory.offset as usize;
+    let end = start + directory.size as usize;
+    let entries = parse_central_directory(&zip[start..end], directory.entries).unwrap();
+    assert_eq!(
+      entries,
+      vec![Entry {
+        name: String::from("music/song.mp3"),
+        method: METHOD_STORED,
+        encrypted: false,
+        compressed_size: content.len() as u64,
+        size: content.len() as u64,
+        local_header_offset: 0,
+      }]
+    );
+
+    let data_start = local_data_offset(&zip[..LOCAL_HEADER_SIZE], 0).unwrap() as usize;
+    assert_eq!(&zip[data_start..data_start + content.len()], content);
+  }
+
+  #[test]
+  fn eocd_in_tail() {
+    let zip = stored_zip("song.mp3", b"data", b"");
+    let tail_offset = zip.len() - 30;
+    let eocd = parse_eocd(&zip[tail_offset..], tail_offset as u64).unwrap();
+    assert!(matches!(
+      eocd,
+      Eocd::Zip32(CentralDirectory { entries: 1, .. })
+    ));
+  }
+
+  #[test]
+  fn not_a_zip() {
+    assert!(parse_eocd(&[0u8; 100], 0).is_err());
+    assert!(parse_eocd(b"PK", 0).is_err());
+  }
+
+  #[test]
+  fn zip64_extra_field() {
+    let zip = stored_zip("song.mp3", b"data", b"");
+    let cd_offset = u32_at(&zip, zip.len() - 6) as usize;
+
+    // rewrite the central directory entry with zip64 sizes
+    let mut entry = zip[cd_offset..cd_offset + CENTRAL_HEADER_SIZE].to_vec();
+    entry[20..24].copy_from_slice(&0xffff_ffffu32.to_le_bytes());
+    entry[24..28].copy_from_slice(&0xffff_ffffu32.to_le_bytes());
+    entry[30..32].copy_from_slice(&20u16.to_le_bytes());
+    entry.extend_from_slice(b"song.mp3");
+    entry.extend_from_slice(&1u16.to_le_bytes());
+    entry.extend_from_slice(&16u16.to_le_bytes());
+    entry.extend_from_slice(&5_000_000_000u64.to_le_bytes());
+    entry.extend_from_slice(&4_000_000_000u64.to_le_bytes());
+
+    let entries = parse_central_directory(&entry, 1).unwrap();
+    assert_eq!(entries[0].size, 5_000_000_000);
+    assert_eq!(entries[0].compressed_size, 4_000_000_000);
+    assert_eq!(entries[0].local_header_offset, 0);
+  }
+}
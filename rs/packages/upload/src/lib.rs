@@ -2,12 +2,13 @@
 
 use std::process::ExitStatus;
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use constants::{AUDIO_FILE_BYTERATE, AUDIO_FILE_CHUNK_SIZE};
-use db::account::Account;
+use db::account::{Account, LimitKind};
 use db::audio_chunk::AudioChunk;
-use db::audio_file::{AudioFile, Metadata};
+use db::audio_file::{AudioFile, DuplicateMode, Metadata};
 use db::audio_upload_operation::{AudioUploadOperation, State};
+use db::event::{Event, UploadFinished};
 use db::station::Station;
 use db::{run_transaction, storage_quota, Model};
 use ffmpeg::{transform, FfmpegConfig, TransformError};
@@ -15,8 +16,15 @@ use log::*;
 use serde_util::DateTime;
 use sha2::{Digest, Sha256};
 use std::error::Error;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_stream::{Stream, StreamExt};
 
+pub mod import;
+pub mod process;
+pub mod recording;
+pub mod resumable;
+
 macro_rules! check_quota {
   ($station_id:expr, $file_len:expr) => {
     match storage_quota!($station_id) {
@@ -50,6 +58,8 @@ pub enum UploadError<E> {
   },
   #[error("ffmpeg io: {0}")]
   FfmpegIo(std::io::Error),
+  #[error("spool io: {0}")]
+  Spool(std::io::Error),
   #[error("station not found: {0}")]
   StationNotFound(String),
   #[error("account not found: {0}")]
@@ -58,6 +68,8 @@ pub enum UploadError<E> {
   QuotaExceeded,
   #[error("file empty")]
   Empty,
+  #[error("duplicate of audio file: {0}")]
+  Duplicate(String),
 }
 
 impl<E> From<TransformError> for UploadError<E> {
@@ -69,42 +81,71 @@ impl<E> From<TransformError> for UploadError<E> {
   }
 }
 
-async fn upload_audio_file_internal<E: Error, S: Stream<Item = Result<Bytes, E>>>(
-  station_id: String,
-  audio_file_id: String,
-  estimated_len: Option<u64>,
-  filename: String,
-  data: S,
-) -> Result<AudioFile, UploadError<E>> {
-  let station = match Station::get_by_id(&station_id).await? {
-    Some(station) => station,
-    None => return Err(UploadError::StationNotFound(station_id)),
-  };
+/// the original input of an upload with audio processing, stored until it is encoded
+/// the file is removed on drop
+struct SpoolFile {
+  path: PathBuf,
+}
 
-  let account = match Account::get_by_id(&station.account_id).await? {
-    Some(account) => account,
-    None => return Err(UploadError::AccountNotFound(station_id)),
-  };
+impl SpoolFile {
+  fn new(audio_file_id: &str) -> Self {
+    let path = std::env::temp_dir().join(format!("openstream-upload-{audio_file_id}"));
+    Self { path }
+  }
 
-  if let Some(len) = estimated_len {
-    check_quota!(&account.id, len);
+  /// sends the contents of the file to the encoder
+  async fn read_into(&self, writer: spsc::Sender<Bytes>) -> Result<(), std::io::Error> {
+    let mut file = tokio::fs::File::open(&self.path).await?;
+    loop {
+      let mut buf = BytesMut::with_capacity(AUDIO_FILE_CHUNK_SIZE);
+      let n = file.read_buf(&mut buf).await?;
+      if n == 0 {
+        break;
+      }
+
+      if writer.send(buf.freeze()).await.is_err() {
+        trace!("upload spool send error: SendError");
+        break;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl Drop for SpoolFile {
+  fn drop(&mut self) {
+    if let Err(e) = std::fs::remove_file(&self.path) {
+      if e.kind() != std::io::ErrorKind::NotFound {
+        warn!("error removing upload spool file: {} => {:?}", e, e);
+      }
+    }
   }
+}
 
+/// where the input of an upload is written
+enum InputSink {
+  Transform(spsc::Sender<Bytes>),
+  Spool(tokio::fs::File),
+}
+
+/// reads the input of an upload into `sink`
+/// returns the sha256, the metadata and the analysis of the input
+async fn read_input<E: Error, S: Stream<Item = Result<Bytes, E>>>(
+  data: S,
+  sink: InputSink,
+) -> Result<(String, Metadata, Option<ffmpeg::analysis::Analysis>), UploadError<E>> {
   tokio::pin!(data);
 
   let (meta_tx, meta_rx) = tokio::sync::mpsc::channel(1);
   let meta_get = ffmpeg::metadata::get(meta_rx);
 
-  let config = FfmpegConfig {
-    format: ffmpeg::Format::MP3,
-    kbitrate: AUDIO_FILE_BYTERATE * 8 / 1000,
-    ..Default::default()
-  };
-
-  let (writer, mut reader) =
-    transform(config, AUDIO_FILE_CHUNK_SIZE).map_err(UploadError::FfmpegSpawn)?;
+  let (analysis_tx, analysis_rx) = tokio::sync::mpsc::channel(1);
+  let analysis_get = ffmpeg::analysis::get(analysis_rx);
 
   let writer_f = async move {
+    let mut sink = sink;
+    let mut hasher = Sha256::new();
     loop {
       trace!("upload writer recv loop");
       let item = data.next().await;
@@ -120,115 +161,248 @@ async fn upload_audio_file_internal<E: Error, S: Stream<Item = Result<Bytes, E>>
         Some(Ok(bytes)) => {
           let len = bytes.len();
           trace!("upload writer recv item: {len} bytes");
+          hasher.update(bytes.as_ref());
           let _ = meta_tx.send(bytes.clone()).await;
-          match writer.send(bytes).await {
-            Ok(()) => {
-              trace!("upload writer send item: {len} bytes");
-              continue;
-            }
-            Err(_e) => {
-              trace!("upload writer send error: SendError");
-              break;
+          let _ = analysis_tx.send(bytes.clone()).await;
+          match &mut sink {
+            InputSink::Transform(writer) => match writer.send(bytes).await {
+              Ok(()) => {
+                trace!("upload writer send item: {len} bytes");
+                continue;
+              }
+              Err(_e) => {
+                trace!("upload writer send error: SendError");
+                break;
+              }
+            },
+            InputSink::Spool(file) => {
+              file
+                .write_all(bytes.as_ref())
+                .await
+                .map_err(UploadError::Spool)?;
             }
           }
         }
       }
     }
 
-    Ok(())
+    if let InputSink::Spool(file) = &mut sink {
+      file.flush().await.map_err(UploadError::Spool)?;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
   };
 
-  let reader_f = {
-    let audio_file_id = audio_file_id.clone();
-    let station_id = station_id.clone();
+  let (meta_get, analysis_get, write) = tokio::join!(meta_get, analysis_get, writer_f);
+  let source_sha256 = write?;
+
+  let metadata = match meta_get {
+    Err(e) => {
+      warn!("upload metadata error: {} => {:?}", e, e);
+      Metadata::default()
+    }
+    Ok(map) => Metadata::from(map.into_iter()),
+  };
 
-    async move {
-      let mut hasher = Sha256::new();
+  let analysis = match analysis_get {
+    Err(e) => {
+      warn!("upload analysis error: {} => {:?}", e, e);
+      None
+    }
+    Ok(analysis) => Some(analysis),
+  };
 
-      let mut file_len = 0u64;
-      let mut file_duration_ms = 0.0;
-      let mut chunk_count = 0;
+  Ok((source_sha256, metadata, analysis))
+}
 
-      loop {
-        let bytes = match reader.recv().await {
-          None => {
-            trace!("upload reader recv end");
-            break;
-          }
-          Some(Err(e)) => {
-            trace!("upload reader recv error: {:?}", e);
-            return Err(e.into());
-          }
-          Some(Ok(bytes)) => {
-            trace!("upload reader recv item: {} bytes", bytes.len());
-            bytes
-          }
-        };
+/// stores the output of the encoder as the chunks of `audio_file_id`
+/// the output is also sent to `analysis_tx` if it is set
+/// returns the len, duration, chunk count and sha256 of the output
+async fn store_output<E>(
+  mut reader: tokio::sync::mpsc::Receiver<Result<Bytes, TransformError>>,
+  account_id: &str,
+  station_id: &str,
+  audio_file_id: &str,
+  analysis_tx: Option<tokio::sync::mpsc::Sender<Bytes>>,
+) -> Result<(u64, f64, usize, String), UploadError<E>> {
+  let mut hasher = Sha256::new();
+
+  let mut file_len = 0u64;
+  let mut file_duration_ms = 0.0;
+  let mut chunk_count = 0;
+
+  loop {
+    let bytes = match reader.recv().await {
+      None => {
+        trace!("upload reader recv end");
+        break;
+      }
+      Some(Err(e)) => {
+        trace!("upload reader recv error: {:?}", e);
+        return Err(e.into());
+      }
+      Some(Ok(bytes)) => {
+        trace!("upload reader recv item: {} bytes", bytes.len());
+        bytes
+      }
+    };
 
-        hasher.update(bytes.as_ref());
+    hasher.update(bytes.as_ref());
 
-        let i = chunk_count;
-        chunk_count += 1;
+    if let Some(tx) = &analysis_tx {
+      let _ = tx.send(bytes.clone()).await;
+    }
 
-        let len = bytes.len();
-        file_len += len as u64;
+    let i = chunk_count;
+    chunk_count += 1;
 
-        check_quota!(&account.id, file_len);
+    let len = bytes.len();
+    file_len += len as u64;
 
-        let duration_ms = bytes.len() as f64 / AUDIO_FILE_BYTERATE as f64 * 1000.0;
+    check_quota!(account_id, file_len);
 
-        let start_ms = file_duration_ms;
-        file_duration_ms += duration_ms;
+    let duration_ms = bytes.len() as f64 / AUDIO_FILE_BYTERATE as f64 * 1000.0;
 
-        let end_ms = start_ms + duration_ms;
+    let start_ms = file_duration_ms;
+    file_duration_ms += duration_ms;
 
-        let document = AudioChunk {
-          id: AudioChunk::uid(),
-          station_id: station_id.clone(),
-          audio_file_id: audio_file_id.clone(),
-          duration_ms,
-          start_ms,
-          end_ms,
-          i,
-          len,
-          bytes_sec: AUDIO_FILE_BYTERATE,
-          data: bytes,
-          created_at: DateTime::now(),
-        };
+    let end_ms = start_ms + duration_ms;
 
-        AudioChunk::insert(&document).await?;
-        trace!("upload audio chunk #{i} inserted");
-      }
+    let document = AudioChunk {
+      id: AudioChunk::uid(),
+      station_id: station_id.to_string(),
+      audio_file_id: audio_file_id.to_string(),
+      duration_ms,
+      start_ms,
+      end_ms,
+      i,
+      len,
+      bytes_sec: AUDIO_FILE_BYTERATE,
+      data: bytes,
+      created_at: DateTime::now(),
+    };
 
-      let sha256_array = hasher.finalize();
-      let sha256 = hex::encode(sha256_array);
+    AudioChunk::insert(&document).await?;
+    trace!("upload audio chunk #{i} inserted");
+  }
 
-      Ok((file_len, file_duration_ms, chunk_count, sha256))
-    }
+  let sha256_array = hasher.finalize();
+  let sha256 = hex::encode(sha256_array);
+
+  Ok((file_len, file_duration_ms, chunk_count, sha256))
+}
+
+async fn upload_audio_file_internal<E: Error, S: Stream<Item = Result<Bytes, E>>>(
+  station_id: String,
+  audio_file_id: String,
+  estimated_len: Option<u64>,
+  filename: String,
+  on_duplicate: DuplicateMode,
+  data: S,
+) -> Result<AudioFile, UploadError<E>> {
+  let station = match Station::get_by_id(&station_id).await? {
+    Some(station) => station,
+    None => return Err(UploadError::StationNotFound(station_id)),
   };
 
-  let (meta_get, write, read) = tokio::join!(meta_get, writer_f, reader_f);
-  write?;
-  let (file_len, file_duration_ms, chunk_count, sha256) = read?;
+  let account = match Account::get_by_id(&station.account_id).await? {
+    Some(account) => account,
+    None => return Err(UploadError::AccountNotFound(station_id)),
+  };
 
-  if file_len == 0 {
-    return Err(UploadError::Empty);
+  if let Some(len) = estimated_len {
+    check_quota!(&account.id, len);
   }
 
-  let metadata = match meta_get {
-    Err(e) => {
-      warn!("upload metadata error: {} => {:?}", e, e);
-      Metadata::default()
-    }
-    Ok(map) => Metadata::from(map.into_iter()),
+  let processing = station.audio_processing;
+
+  let config = FfmpegConfig {
+    format: ffmpeg::Format::MP3,
+    kbitrate: AUDIO_FILE_BYTERATE * 8 / 1000,
+    ..Default::default()
   };
 
+  let (source_sha256, metadata, analysis, (file_len, file_duration_ms, chunk_count, sha256)) =
+    if processing.is_enabled() {
+      // the input is stored in a temporary file and analyzed before it is encoded,
+      // so the normalization and the trimming are applied in the only encoding of the original input
+      let spool = SpoolFile::new(&audio_file_id);
+      let spool_file = tokio::fs::File::create(&spool.path)
+        .await
+        .map_err(UploadError::Spool)?;
+
+      let (source_sha256, metadata, source_analysis) =
+        read_input(data, InputSink::Spool(spool_file)).await?;
+
+      let filters = source_analysis.as_ref().and_then(|analysis| {
+        let duration_ms = analysis.duration? * 1000.0;
+        let analysis = process::to_db_analysis(analysis, duration_ms)?;
+        let (gain_db, trim) = process::adjustments(&analysis, &processing);
+        process::filter_graph(gain_db, trim, duration_ms)
+      });
+
+      let config = FfmpegConfig { filters, ..config };
+
+      let (writer, reader) =
+        transform(config, AUDIO_FILE_CHUNK_SIZE).map_err(UploadError::FfmpegSpawn)?;
+
+      let (analysis_tx, analysis_rx) = tokio::sync::mpsc::channel(1);
+      let analysis_get = ffmpeg::analysis::get(analysis_rx);
+
+      let (spool_read, store, analysis_get) = tokio::join!(
+        spool.read_into(writer),
+        store_output(
+          reader,
+          &account.id,
+          &station_id,
+          &audio_file_id,
+          Some(analysis_tx)
+        ),
+        analysis_get,
+      );
+
+      spool_read.map_err(UploadError::Spool)?;
+      let output = store?;
+
+      // the analysis of the processed output
+      let analysis = match analysis_get {
+        Err(e) => {
+          warn!("upload analysis error: {} => {:?}", e, e);
+          None
+        }
+        Ok(analysis) => process::to_db_analysis(&analysis, output.1),
+      };
+
+      (source_sha256, metadata, analysis, output)
+    } else {
+      let (writer, reader) =
+        transform(config, AUDIO_FILE_CHUNK_SIZE).map_err(UploadError::FfmpegSpawn)?;
+
+      let (input, store) = tokio::join!(
+        read_input(data, InputSink::Transform(writer)),
+        store_output(reader, &account.id, &station_id, &audio_file_id, None),
+      );
+
+      let (source_sha256, metadata, source_analysis) = input?;
+      let output = store?;
+
+      let analysis =
+        source_analysis.and_then(|analysis| process::to_db_analysis(&analysis, output.1));
+
+      (source_sha256, metadata, analysis, output)
+    };
+
+  if file_len == 0 {
+    return Err(UploadError::Empty);
+  }
+
   let order = AudioFile::next_max_order(&station_id, None).await?;
 
   let file = AudioFile {
     id: audio_file_id,
     station_id,
     sha256,
+    source_sha256: Some(source_sha256),
     len: file_len,
     duration_ms: file_duration_ms,
     chunk_count,
@@ -237,11 +411,41 @@ async fn upload_audio_file_internal<E: Error, S: Stream<Item = Result<Bytes, E>>
     bytes_sec: AUDIO_FILE_BYTERATE,
     filename,
     metadata,
+    analysis,
+    processing,
+    media_session_id: None,
     order,
     created_at: DateTime::now(),
   };
 
-  run_transaction!(session => {
+  // the duplicate check is in the insert transaction, the transactions of two uploads of the same content
+  // both update the account document, so they conflict and the retried one sees the file of the other
+  let (account, storage_used_before) = run_transaction!(session => {
+
+    let duplicate = match on_duplicate {
+      DuplicateMode::Force => None,
+      DuplicateMode::Reject | DuplicateMode::Link => tx_try!(AudioFile::find_duplicate(
+        &file.station_id,
+        &file.id,
+        &file.sha256,
+        file.source_sha256.as_deref(),
+        Some(&mut session),
+      ).await),
+    };
+
+    if let Some(duplicate) = duplicate {
+      if on_duplicate == DuplicateMode::Reject {
+        return Err(UploadError::Duplicate(duplicate.id));
+      }
+
+      trace!(
+        "upload linked to existing audio file station_id={}, audio_file_id={}",
+        file.station_id,
+        duplicate.id
+      );
+      AudioUploadOperation::clean_up_chunks_after_error(&file.id).await?;
+      return Ok(duplicate);
+    }
 
     let station = match tx_try!(Station::get_by_id_with_session(&file.station_id, &mut session).await) {
       None => return Err(UploadError::StationNotFound(file.station_id)),
@@ -257,11 +461,22 @@ async fn upload_audio_file_internal<E: Error, S: Stream<Item = Result<Bytes, E>>
       return Err(UploadError::QuotaExceeded);
     }
 
+    let storage_used_before = account.limits.storage.used;
     account.limits.storage.used += file.len;
 
     tx_try!(Account::replace_with_session(&account.id, &account, &mut session).await);
     tx_try!(AudioFile::insert_with_session(&file, &mut session).await);
     trace!("audio file uploaded station_id={}, audio_file_id={}", station.id, file.id);
+
+    (account, storage_used_before)
+  });
+
+  account.dispatch_crossed_threshold(LimitKind::Storage, storage_used_before);
+
+  Event::dispatch_detached(UploadFinished {
+    station_id: file.station_id.clone(),
+    audio_file_id: file.id.clone(),
+    filename: file.filename.clone(),
   });
 
   Ok(file)
@@ -276,6 +491,7 @@ async fn upload_audio_file_inner_spawn<
   audio_file_id: Option<String>,
   estimated_len: Option<u64>,
   filename: String,
+  on_duplicate: DuplicateMode,
   data: S,
 ) -> Result<AudioFile, UploadError<E>> {
   let audio_file_id = audio_file_id.unwrap_or_else(AudioFile::uid);
@@ -290,8 +506,15 @@ async fn upload_audio_file_inner_spawn<
 
   AudioUploadOperation::insert(&operation).await?;
 
-  let result =
-    upload_audio_file_internal(station_id, audio_file_id, estimated_len, filename, data).await;
+  let result = upload_audio_file_internal(
+    station_id,
+    audio_file_id,
+    estimated_len,
+    filename,
+    on_duplicate,
+    data,
+  )
+  .await;
 
   match result.as_ref() {
     Ok(_) => {
@@ -345,6 +568,7 @@ pub async fn upload_audio_file<
   audio_file_id: Option<String>,
   estimated_len: Option<u64>,
   filename: String,
+  on_duplicate: DuplicateMode,
   data: S,
 ) -> Result<AudioFile, UploadError<E>> {
   tokio::spawn(upload_audio_file_inner_spawn(
@@ -353,6 +577,7 @@ pub async fn upload_audio_file<
     audio_file_id,
     estimated_len,
     filename,
+    on_duplicate,
     data,
   ))
   .await
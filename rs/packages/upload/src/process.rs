@@ -0,0 +1,420 @@
+use std::collections::HashSet;
+use std::process::ExitStatus;
+
+use bytes::Bytes;
+use constants::{AUDIO_FILE_BYTERATE, AUDIO_FILE_CHUNK_SIZE};
+use db::account::Account;
+use db::audio_chunk::AudioChunk;
+use db::audio_file::{AudioFile, AudioFileAnalysis, AudioProcessing};
+use db::station::Station;
+use db::{run_transaction, Model};
+use ffmpeg::{transform, FfmpegConfig, TransformError};
+use log::*;
+use mongodb::bson::doc;
+use serde_util::DateTime;
+use sha2::{Digest, Sha256};
+use tokio_stream::StreamExt;
+
+/// ids of the files being processed in this process
+static PROCESSING: parking_lot::Mutex<Option<HashSet<String>>> = parking_lot::Mutex::new(None);
+
+struct ProcessingGuard(String);
+
+impl ProcessingGuard {
+  fn acquire(file_id: &str) -> Option<Self> {
+    let mut lock = PROCESSING.lock();
+    let set = lock.get_or_insert_with(HashSet::new);
+    if set.insert(file_id.to_string()) {
+      Some(Self(file_id.to_string()))
+    } else {
+      None
+    }
+  }
+}
+
+impl Drop for ProcessingGuard {
+  fn drop(&mut self) {
+    if let Some(set) = PROCESSING.lock().as_mut() {
+      set.remove(&self.0);
+    }
+  }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessError {
+  #[error("mongo: {0}")]
+  Mongo(#[from] mongodb::error::Error),
+  #[error("ffmpeg spawn io: {0}")]
+  FfmpegSpawn(std::io::Error),
+  #[error("ffmpeg exit: status: {status}, stderr: {stderr:?}")]
+  FfmpegExit {
+    status: ExitStatus,
+    stderr: Option<String>,
+  },
+  #[error("ffmpeg io: {0}")]
+  FfmpegIo(std::io::Error),
+  #[error("ffmpeg analysis: {0}")]
+  Analysis(std::io::Error),
+  #[error("audio file not found: {0}")]
+  AudioFileNotFound(String),
+  #[error("station not found: {0}")]
+  StationNotFound(String),
+  #[error("account not found: {0}")]
+  AccountNotFound(String),
+  #[error("audio file is already being processed: {0}")]
+  AlreadyProcessing(String),
+  #[error("processed file is empty")]
+  Empty,
+}
+
+impl From<TransformError> for ProcessError {
+  fn from(e: TransformError) -> Self {
+    match e {
+      TransformError::Io(e) => ProcessError::FfmpegIo(e),
+      TransformError::Exit { status, stderr } => ProcessError::FfmpegExit { status, stderr },
+    }
+  }
+}
+
+/// converts the output of the ffmpeg analysis of a file of `duration_ms` into its db representation
+/// returns None if the loudness could not be measured
+pub(crate) fn to_db_analysis(
+  analysis: &ffmpeg::analysis::Analysis,
+  duration_ms: f64,
+) -> Option<AudioFileAnalysis> {
+  Some(AudioFileAnalysis {
+    integrated_lufs: analysis.integrated_lufs?,
+    true_peak_dbtp: analysis.true_peak_dbtp?,
+    leading_silence_ms: analysis.leading_silence() * 1000.0,
+    trailing_silence_ms: analysis.trailing_silence(duration_ms / 1000.0) * 1000.0,
+  })
+}
+
+/// gain in dB and durations in ms to trim from the start and the end
+/// that the audio processing `settings` apply to a file with `analysis`
+pub(crate) fn adjustments(
+  analysis: &AudioFileAnalysis,
+  settings: &AudioProcessing,
+) -> (f64, (f64, f64)) {
+  let gain = match settings.target_lufs {
+    Some(target) => analysis.gain_db(target),
+    None => 0.0,
+  };
+
+  let trim = if settings.trim_silence {
+    analysis.trim_ms()
+  } else {
+    (0.0, 0.0)
+  };
+
+  (gain, trim)
+}
+
+/// ffmpeg filter graph that applies `gain_db` and the trim to an audio of `duration_ms`
+/// returns None if there is nothing to apply
+pub(crate) fn filter_graph(
+  gain_db: f64,
+  (trim_start_ms, trim_end_ms): (f64, f64),
+  duration_ms: f64,
+) -> Option<String> {
+  let mut filters = vec![];
+  if trim_start_ms != 0.0 || trim_end_ms != 0.0 {
+    let end = (duration_ms - trim_end_ms).max(trim_start_ms) / 1000.0;
+    filters.push(format!(
+      "atrim=start={}:end={},asetpts=PTS-STARTPTS",
+      trim_start_ms / 1000.0,
+      end
+    ));
+  }
+
+  if gain_db != 0.0 {
+    filters.push(format!("volume={gain_db:.2}dB"));
+  }
+
+  if filters.is_empty() {
+    None
+  } else {
+    Some(filters.join(","))
+  }
+}
+
+/// analyzes the audio of a stored file
+async fn analyze_stored(file: &AudioFile) -> Result<Option<AudioFileAnalysis>, ProcessError> {
+  let (tx, rx) = tokio::sync::mpsc::channel(1);
+  let analysis = ffmpeg::analysis::get(rx);
+
+  let write = async move {
+    let stream = AudioChunk::stream(&file.id);
+    tokio::pin!(stream);
+    while let Some(bytes) = stream.next().await {
+      if tx.send(bytes?).await.is_err() {
+        break;
+      }
+    }
+
+    Ok::<(), mongodb::error::Error>(())
+  };
+
+  let (analysis, write) = tokio::join!(analysis, write);
+  write?;
+  let analysis = analysis.map_err(ProcessError::Analysis)?;
+
+  Ok(to_db_analysis(&analysis, file.duration_ms))
+}
+
+/// applies the audio processing settings of its station to an audio file
+///
+/// the file is re-encoded from its stored chunks and the chunks are replaced in a transaction
+/// files that already comply with the settings of the station are returned unchanged
+pub async fn process_audio_file(file_id: &str) -> Result<AudioFile, ProcessError> {
+  let _guard = match ProcessingGuard::acquire(file_id) {
+    Some(guard) => guard,
+    None => return Err(ProcessError::AlreadyProcessing(file_id.to_string())),
+  };
+
+  let mut file = match AudioFile::get_by_id(file_id).await? {
+    Some(file) => file,
+    None => return Err(ProcessError::AudioFileNotFound(file_id.to_string())),
+  };
+
+  let station = match Station::get_by_id(&file.station_id).await? {
+    Some(station) => station,
+    None => return Err(ProcessError::StationNotFound(file.station_id)),
+  };
+
+  let settings = station.audio_processing;
+
+  if file.processing == settings {
+    return Ok(file);
+  }
+
+  let analysis = match file.analysis {
+    Some(analysis) => Some(analysis),
+    None => analyze_stored(&file).await?,
+  };
+
+  let (gain_db, (trim_start_ms, trim_end_ms)) = match &analysis {
+    None => (0.0, (0.0, 0.0)),
+    Some(analysis) => adjustments(analysis, &settings),
+  };
+
+  let filters = match filter_graph(gain_db, (trim_start_ms, trim_end_ms), file.duration_ms) {
+    Some(filters) => filters,
+    // nothing to re-encode, we only record that the file complies with the settings
+    None => {
+      file.analysis = analysis;
+      file.processing = settings;
+      let update = doc! {
+        "$set": {
+          AudioFile::KEY_ANALYSIS: mongodb::bson::to_bson(&file.analysis).unwrap(),
+          AudioFile::KEY_PROCESSING: mongodb::bson::to_bson(&file.processing).unwrap(),
+        }
+      };
+      AudioFile::update_by_id(&file.id, update).await?;
+      return Ok(file);
+    }
+  };
+
+  // the new chunks are written with a temporary file id until they replace the current ones
+  let tmp_id = AudioFile::uid();
+
+  let r = encode(&file, &tmp_id, filters).await;
+
+  let (len, duration_ms, chunk_count, sha256, new_analysis) = match r {
+    Ok(v) => v,
+    Err(e) => {
+      let filter = doc! { AudioChunk::KEY_AUDIO_FILE_ID: &tmp_id };
+      if let Err(e) = AudioChunk::cl().delete_many(filter, None).await {
+        warn!(
+          "error cleaning up chunks of audio file processing after error: {} => {:?}",
+          e, e
+        );
+      }
+
+      return Err(e);
+    }
+  };
+
+  let prev_len = file.len;
+
+  file.len = len;
+  file.duration_ms = duration_ms;
+  file.chunk_count = chunk_count;
+  file.sha256 = sha256;
+  file.analysis = new_analysis.or(analysis);
+  file.processing = settings;
+
+  let exists = run_transaction!(session => {
+    let station = match tx_try!(Station::get_by_id_with_session(&file.station_id, &mut session).await) {
+      None => return Err(ProcessError::StationNotFound(file.station_id)),
+      Some(station) => station,
+    };
+
+    let mut account = match tx_try!(Account::get_by_id_with_session(&station.account_id, &mut session).await) {
+      None => return Err(ProcessError::AccountNotFound(station.account_id)),
+      Some(account) => account,
+    };
+
+    // the file may have been deleted while it was processed
+    let exists = tx_try!(AudioFile::exists_with_session(doc!{ AudioFile::KEY_ID: &file.id }, &mut session).await);
+
+    if exists {
+      account.limits.storage.used = (account.limits.storage.used + file.len).saturating_sub(prev_len);
+      tx_try!(Account::replace_with_session(&account.id, &account, &mut session).await);
+
+      tx_try!(AudioChunk::delete_by_audio_file_id_with_session(&file.id, &mut session).await);
+
+      let filter = doc!{ AudioChunk::KEY_AUDIO_FILE_ID: &tmp_id };
+      let update = doc!{ "$set": { AudioChunk::KEY_AUDIO_FILE_ID: &file.id } };
+      tx_try!(AudioChunk::cl().update_many_with_session(filter, update, None, &mut session).await);
+
+      tx_try!(AudioFile::replace_with_session(&file.id, &file, &mut session).await);
+    } else {
+      let filter = doc!{ AudioChunk::KEY_AUDIO_FILE_ID: &tmp_id };
+      tx_try!(AudioChunk::cl().delete_many_with_session(filter, None, &mut session).await);
+    }
+
+    exists
+  });
+
+  if !exists {
+    return Err(ProcessError::AudioFileNotFound(file.id));
+  }
+
+  info!(
+    "audio file {} processed gain={gain_db}dB trim_start={trim_start_ms}ms trim_end={trim_end_ms}ms",
+    file.id
+  );
+
+  Ok(file)
+}
+
+/// re-encodes the chunks of `file` with `filters` into chunks of `tmp_id`
+/// returns the len, duration, chunk count, sha256 and analysis of the new audio
+async fn encode(
+  file: &AudioFile,
+  tmp_id: &str,
+  filters: String,
+) -> Result<(u64, f64, usize, String, Option<AudioFileAnalysis>), ProcessError> {
+  let config = FfmpegConfig {
+    format: ffmpeg::Format::MP3,
+    kbitrate: AUDIO_FILE_BYTERATE * 8 / 1000,
+    filters: Some(filters),
+    ..Default::default()
+  };
+
+  let (writer, mut reader) =
+    transform(config, AUDIO_FILE_CHUNK_SIZE).map_err(ProcessError::FfmpegSpawn)?;
+
+  let (analysis_tx, analysis_rx) = tokio::sync::mpsc::channel::<Bytes>(1);
+  let analysis = ffmpeg::analysis::get(analysis_rx);
+
+  let writer_f = async move {
+    let stream = AudioChunk::stream(&file.id);
+    tokio::pin!(stream);
+    while let Some(bytes) = stream.next().await {
+      if writer.send(bytes?).await.is_err() {
+        break;
+      }
+    }
+
+    Ok::<(), ProcessError>(())
+  };
+
+  let reader_f = async move {
+    let mut hasher = Sha256::new();
+    let mut file_len = 0u64;
+    let mut file_duration_ms = 0.0;
+    let mut chunk_count = 0;
+
+    while let Some(bytes) = reader.recv().await {
+      let bytes = bytes?;
+
+      hasher.update(bytes.as_ref());
+      let _ = analysis_tx.send(bytes.clone()).await;
+
+      let i = chunk_count;
+      chunk_count += 1;
+
+      let len = bytes.len();
+      file_len += len as u64;
+
+      let duration_ms = len as f64 / AUDIO_FILE_BYTERATE as f64 * 1000.0;
+      let start_ms = file_duration_ms;
+      file_duration_ms += duration_ms;
+
+      let document = AudioChunk {
+        id: AudioChunk::uid(),
+        station_id: file.station_id.clone(),
+        audio_file_id: tmp_id.to_string(),
+        duration_ms,
+        start_ms,
+        end_ms: start_ms + duration_ms,
+        i,
+        len,
+        bytes_sec: AUDIO_FILE_BYTERATE,
+        data: bytes,
+        created_at: DateTime::now(),
+      };
+
+      AudioChunk::insert(&document).await?;
+    }
+
+    drop(analysis_tx);
+
+    let sha256 = hex::encode(hasher.finalize());
+
+    Ok::<_, ProcessError>((file_len, file_duration_ms, chunk_count, sha256))
+  };
+
+  let (write, read, analysis) = tokio::join!(writer_f, reader_f, analysis);
+  write?;
+  let (len, duration_ms, chunk_count, sha256) = read?;
+
+  if len == 0 {
+    return Err(ProcessError::Empty);
+  }
+
+  let analysis = match analysis {
+    Ok(analysis) => to_db_analysis(&analysis, duration_ms),
+    Err(e) => {
+      warn!("audio file processing analysis error: {} => {:?}", e, e);
+      None
+    }
+  };
+
+  Ok((len, duration_ms, chunk_count, sha256, analysis))
+}
+
+/// processes in the background the files of a station that do not comply with its audio processing settings
+/// returns the number of files to process
+pub async fn process_station_files(station_id: &str) -> Result<u64, ProcessError> {
+  let station = match Station::get_by_id(station_id).await? {
+    Some(station) => station,
+    None => return Err(ProcessError::StationNotFound(station_id.to_string())),
+  };
+
+  let filter = doc! {
+    AudioFile::KEY_STATION_ID: &station.id,
+    AudioFile::KEY_PROCESSING: { "$ne": mongodb::bson::to_bson(&station.audio_processing).unwrap() },
+  };
+
+  let ids = AudioFile::cl()
+    .distinct(AudioFile::KEY_ID, filter, None)
+    .await?
+    .into_iter()
+    .filter_map(|id| id.as_str().map(ToString::to_string))
+    .collect::<Vec<String>>();
+
+  let count = ids.len() as u64;
+
+  tokio::spawn(async move {
+    for id in ids {
+      if let Err(e) = process_audio_file(&id).await {
+        warn!("error processing audio file {}: {} => {:?}", id, e, e);
+      }
+    }
+  });
+
+  Ok(count)
+}
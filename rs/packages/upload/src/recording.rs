@@ -0,0 +1,499 @@
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use constants::{AUDIO_FILE_BYTERATE, AUDIO_FILE_CHUNK_SIZE, LIVE_RECORDING_MIN_SEGMENT_SECS};
+use db::account::{Account, LimitKind};
+use db::audio_chunk::AudioChunk;
+use db::audio_file::{AudioFile, Metadata};
+use db::live_recording::LiveRecording;
+use db::media_session::MediaSessionNowPlaying;
+use db::station::{LiveRecordingSettings, Station};
+use db::{run_transaction, storage_quota, Model};
+use ffmpeg::{transform, FfmpegConfig, TransformError};
+use log::*;
+use mongodb::bson::doc;
+use serde_util::DateTime;
+use sha2::{Digest, Sha256};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+/// chunks of the live stream buffered while the recorder is busy
+const BUFFER_LEN: usize = 256;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecordingError {
+  #[error("mongo: {0}")]
+  Mongo(#[from] mongodb::error::Error),
+  #[error("ffmpeg spawn io: {0}")]
+  FfmpegSpawn(std::io::Error),
+  #[error("ffmpeg exit: status: {status}, stderr: {stderr:?}")]
+  FfmpegExit {
+    status: ExitStatus,
+    stderr: Option<String>,
+  },
+  #[error("ffmpeg io: {0}")]
+  FfmpegIo(std::io::Error),
+  #[error("station not found: {0}")]
+  StationNotFound(String),
+  #[error("account not found: {0}")]
+  AccountNotFound(String),
+  #[error("quota exceeded")]
+  QuotaExceeded,
+}
+
+impl From<TransformError> for RecordingError {
+  fn from(e: TransformError) -> Self {
+    match e {
+      TransformError::Io(e) => RecordingError::FfmpegIo(e),
+      TransformError::Exit { status, stderr } => RecordingError::FfmpegExit { status, stderr },
+    }
+  }
+}
+
+/// records a live media session into LiveRecording documents
+///
+/// the bytes pushed to the recorder are transcoded and stored like uploaded files in a background task,
+/// the last recording of the session is committed when the recorder is dropped
+#[derive(Debug)]
+pub struct LiveRecorder {
+  station_id: String,
+  sender: mpsc::Sender<Bytes>,
+  overflow: bool,
+}
+
+impl LiveRecorder {
+  /// returns None if the station does not record its live broadcasts
+  pub fn start(
+    station: &Station,
+    media_session_id: &str,
+    now_playing: watch::Receiver<Option<MediaSessionNowPlaying>>,
+  ) -> Option<Self> {
+    if !station.live_recording.enabled {
+      return None;
+    }
+
+    let (sender, receiver) = mpsc::channel(BUFFER_LEN);
+
+    let recording_session = RecordingSession {
+      station_id: station.id.clone(),
+      media_session_id: media_session_id.to_string(),
+      settings: station.live_recording,
+    };
+
+    info!(
+      target: "live-recording",
+      "recording live media session {} of station {}",
+      recording_session.media_session_id,
+      recording_session.station_id
+    );
+
+    tokio::spawn(recording_session.run(receiver, now_playing));
+
+    Some(Self {
+      station_id: station.id.clone(),
+      sender,
+      overflow: false,
+    })
+  }
+
+  /// the bytes are dropped if the recorder can't keep up with the broadcast
+  pub fn push(&mut self, bytes: Bytes) {
+    match self.sender.try_send(bytes) {
+      Ok(()) => self.overflow = false,
+      Err(mpsc::error::TrySendError::Full(_)) => {
+        if !self.overflow {
+          warn!(
+            target: "live-recording",
+            "live recording of station {} is falling behind, dropping bytes", self.station_id
+          );
+          self.overflow = true;
+        }
+      }
+      // the recording stopped after an error
+      Err(mpsc::error::TrySendError::Closed(_)) => {}
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+struct RecordingSession {
+  station_id: String,
+  media_session_id: String,
+  settings: LiveRecordingSettings,
+}
+
+impl RecordingSession {
+  fn split_after(&self) -> Option<Duration> {
+    match self.settings.split_secs {
+      0 => None,
+      secs => Some(Duration::from_secs(
+        (secs as u64).max(LIVE_RECORDING_MIN_SEGMENT_SECS),
+      )),
+    }
+  }
+
+  async fn run(
+    self,
+    mut receiver: mpsc::Receiver<Bytes>,
+    mut now_playing: watch::Receiver<Option<MediaSessionNowPlaying>>,
+  ) {
+    let split_after = self.split_after();
+    let min_duration = Duration::from_secs(LIVE_RECORDING_MIN_SEGMENT_SECS);
+    let mut split_on_now_playing = self.settings.split_on_now_playing;
+    let mut segment: Option<Segment> = None;
+
+    loop {
+      tokio::select! {
+        item = receiver.recv() => {
+          let bytes = match item {
+            None => break,
+            Some(bytes) => bytes,
+          };
+
+          if let (Some(current), Some(split_after)) = (&segment, split_after) {
+            if current.started.elapsed() >= split_after {
+              if let Some(current) = segment.take() {
+                current.finish();
+              }
+            }
+          }
+
+          let current = segment.get_or_insert_with(|| {
+            let now_playing = now_playing.borrow_and_update().clone();
+            Segment::start(self.clone(), now_playing)
+          });
+
+          if current.sender.send(bytes).await.is_err() {
+            // the segment stopped with an error, it is logged when finished
+            if let Some(current) = segment.take() {
+              current.finish();
+            }
+            break;
+          }
+        }
+
+        r = now_playing.changed(), if split_on_now_playing => {
+          match r {
+            Err(_) => split_on_now_playing = false,
+            Ok(()) => {
+              if let Some(current) = &segment {
+                if current.started.elapsed() >= min_duration {
+                  if let Some(current) = segment.take() {
+                    current.finish();
+                  }
+                }
+              }
+            }
+          }
+        }
+      }
+    }
+
+    if let Some(current) = segment.take() {
+      current.finish();
+    }
+  }
+}
+
+#[derive(Debug)]
+struct Segment {
+  sender: mpsc::Sender<Bytes>,
+  started: Instant,
+  task: JoinHandle<Result<Option<LiveRecording>, RecordingError>>,
+}
+
+impl Segment {
+  fn start(
+    recording_session: RecordingSession,
+    now_playing: Option<MediaSessionNowPlaying>,
+  ) -> Self {
+    let (sender, receiver) = mpsc::channel(16);
+    let task = tokio::spawn(record(recording_session, now_playing, receiver));
+    Self {
+      sender,
+      started: Instant::now(),
+      task,
+    }
+  }
+
+  /// closes the input of the segment and commits it in the background
+  fn finish(self) {
+    let Self { sender, task, .. } = self;
+    drop(sender);
+    tokio::spawn(async move {
+      match task.await {
+        Ok(Ok(Some(recording))) => info!(
+          target: "live-recording",
+          "live recording {} of station {} stored: {} bytes",
+          recording.id,
+          recording.station_id,
+          recording.file.len
+        ),
+        Ok(Ok(None)) => {}
+        Ok(Err(e)) => warn!(target: "live-recording", "live recording error: {e} => {e:?}"),
+        Err(e) => error!(target: "live-recording", "live recording task panicked: {e} => {e:?}"),
+      }
+    });
+  }
+}
+
+/// the name of the file of a recording
+fn filename(now_playing: Option<&MediaSessionNowPlaying>, started_at: DateTime) -> String {
+  match now_playing {
+    Some(MediaSessionNowPlaying {
+      title,
+      artist: Some(artist),
+      ..
+    }) => format!("{artist} - {title}.mp3"),
+    Some(MediaSessionNowPlaying {
+      title,
+      artist: None,
+      ..
+    }) => format!("{title}.mp3"),
+    None => format!(
+      "live-{:04}-{:02}-{:02}-{:02}{:02}{:02}.mp3",
+      started_at.year(),
+      started_at.month() as u8,
+      started_at.day(),
+      started_at.hour(),
+      started_at.minute(),
+      started_at.second(),
+    ),
+  }
+}
+
+async fn record(
+  recording_session: RecordingSession,
+  now_playing: Option<MediaSessionNowPlaying>,
+  receiver: mpsc::Receiver<Bytes>,
+) -> Result<Option<LiveRecording>, RecordingError> {
+  let id = LiveRecording::uid();
+  let result = record_internal(&id, recording_session, now_playing, receiver).await;
+
+  if result.is_err() {
+    let filter = doc! { AudioChunk::KEY_AUDIO_FILE_ID: &id };
+    if let Err(e) = AudioChunk::cl().delete_many(filter, None).await {
+      warn!(
+        target: "live-recording",
+        "error cleaning up chunks of live recording {id} after error: {e} => {e:?}"
+      );
+    }
+  }
+
+  result
+}
+
+async fn record_internal(
+  id: &str,
+  recording_session: RecordingSession,
+  now_playing: Option<MediaSessionNowPlaying>,
+  mut receiver: mpsc::Receiver<Bytes>,
+) -> Result<Option<LiveRecording>, RecordingError> {
+  let started_at = DateTime::now();
+
+  let station = match Station::get_by_id(&recording_session.station_id).await? {
+    Some(station) => station,
+    None => {
+      return Err(RecordingError::StationNotFound(
+        recording_session.station_id,
+      ))
+    }
+  };
+
+  let play_history_item_id = now_playing
+    .as_ref()
+    .and_then(|now_playing| now_playing.play_history_item_id.clone());
+
+  // the quota is fetched once per segment, the account storage is updated when the segment is committed
+  let max_len = match storage_quota!(&station.account_id) {
+    None => return Err(RecordingError::AccountNotFound(station.account_id.clone())),
+    Some(max) => max,
+  };
+
+  let config = FfmpegConfig {
+    format: ffmpeg::Format::MP3,
+    kbitrate: AUDIO_FILE_BYTERATE * 8 / 1000,
+    ..Default::default()
+  };
+
+  let (writer, mut reader) =
+    transform(config, AUDIO_FILE_CHUNK_SIZE).map_err(RecordingError::FfmpegSpawn)?;
+
+  let writer_f = async move {
+    while let Some(bytes) = receiver.recv().await {
+      if writer.send(bytes).await.is_err() {
+        break;
+      }
+    }
+  };
+
+  let reader_f = async {
+    let mut hasher = Sha256::new();
+
+    let mut file_len = 0u64;
+    let mut file_duration_ms = 0.0;
+    let mut chunk_count = 0;
+
+    loop {
+      let bytes = match reader.recv().await {
+        None => break,
+        Some(Err(e)) => return Err(e.into()),
+        Some(Ok(bytes)) => bytes,
+      };
+
+      hasher.update(bytes.as_ref());
+
+      let i = chunk_count;
+      chunk_count += 1;
+
+      let len = bytes.len();
+      file_len += len as u64;
+
+      if file_len > max_len {
+        return Err(RecordingError::QuotaExceeded);
+      }
+
+      let duration_ms = len as f64 / AUDIO_FILE_BYTERATE as f64 * 1000.0;
+
+      let start_ms = file_duration_ms;
+      file_duration_ms += duration_ms;
+
+      let document = AudioChunk {
+        id: AudioChunk::uid(),
+        station_id: station.id.clone(),
+        audio_file_id: id.to_string(),
+        duration_ms,
+        start_ms,
+        end_ms: start_ms + duration_ms,
+        i,
+        len,
+        bytes_sec: AUDIO_FILE_BYTERATE,
+        data: bytes,
+        created_at: DateTime::now(),
+      };
+
+      AudioChunk::insert(&document).await?;
+    }
+
+    Ok((
+      file_len,
+      file_duration_ms,
+      chunk_count,
+      hex::encode(hasher.finalize()),
+    ))
+  };
+
+  let ((), read) = tokio::join!(writer_f, reader_f);
+  let (file_len, file_duration_ms, chunk_count, sha256) = read?;
+
+  if file_len == 0 {
+    return Ok(None);
+  }
+
+  let ended_at = DateTime::now();
+
+  let (title, artist) = match &now_playing {
+    None => (None, None),
+    Some(now_playing) => (Some(now_playing.title.clone()), now_playing.artist.clone()),
+  };
+
+  let file = AudioFile {
+    id: id.to_string(),
+    station_id: station.id.clone(),
+    sha256,
+    source_sha256: None,
+    len: file_len,
+    duration_ms: file_duration_ms,
+    chunk_count,
+    chunk_len: AUDIO_FILE_CHUNK_SIZE,
+    chunk_duration_ms: AUDIO_FILE_CHUNK_SIZE as f64 / AUDIO_FILE_BYTERATE as f64 * 1000.0,
+    bytes_sec: AUDIO_FILE_BYTERATE,
+    filename: filename(now_playing.as_ref(), started_at),
+    metadata: Metadata {
+      title: title.clone(),
+      artist: artist.clone(),
+      ..Default::default()
+    },
+    analysis: None,
+    processing: Default::default(),
+    media_session_id: Some(recording_session.media_session_id.clone()),
+    // the order is set when the recording is published
+    order: 0.0,
+    created_at: ended_at,
+  };
+
+  let recording = LiveRecording {
+    id: id.to_string(),
+    station_id: station.id.clone(),
+    media_session_id: recording_session.media_session_id,
+    play_history_item_id,
+    title,
+    artist,
+    file,
+    started_at,
+    ended_at,
+    expires_at: LiveRecording::expires_at(recording_session.settings.retention_days),
+    created_at: ended_at,
+  };
+
+  let (account, storage_used_before) = run_transaction!(session => {
+    let station = match tx_try!(Station::get_by_id_with_session(&recording.station_id, &mut session).await) {
+      None => return Err(RecordingError::StationNotFound(recording.station_id)),
+      Some(station) => station,
+    };
+
+    let mut account = match tx_try!(Account::get_by_id_with_session(&station.account_id, &mut session).await) {
+      None => return Err(RecordingError::AccountNotFound(station.account_id)),
+      Some(account) => account,
+    };
+
+    if account.limits.storage.avail() < recording.file.len {
+      return Err(RecordingError::QuotaExceeded);
+    }
+
+    let storage_used_before = account.limits.storage.used;
+    account.limits.storage.used += recording.file.len;
+
+    tx_try!(Account::replace_with_session(&account.id, &account, &mut session).await);
+    tx_try!(LiveRecording::insert_with_session(&recording, &mut session).await);
+
+    (account, storage_used_before)
+  });
+
+  account.dispatch_crossed_threshold(LimitKind::Storage, storage_used_before);
+
+  Ok(Some(recording))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn recording_filename() {
+    // 2024-03-05T21:07:09Z
+    let started_at = DateTime::from_bson(mongodb::bson::DateTime::from_millis(1_709_672_829_000));
+
+    let now_playing = MediaSessionNowPlaying {
+      title: "Song".into(),
+      artist: Some("Artist".into()),
+      play_history_item_id: None,
+    };
+
+    assert_eq!(
+      filename(Some(&now_playing), started_at),
+      "Artist - Song.mp3"
+    );
+
+    let now_playing = MediaSessionNowPlaying {
+      title: "Song".into(),
+      artist: None,
+      play_history_item_id: None,
+    };
+
+    assert_eq!(filename(Some(&now_playing), started_at), "Song.mp3");
+
+    assert_eq!(filename(None, started_at), "live-2024-03-05-210709.mp3");
+  }
+}
@@ -0,0 +1,337 @@
+use std::convert::Infallible;
+
+use bytes::{Bytes, BytesMut};
+use constants::RESUMABLE_UPLOAD_PART_SIZE;
+use db::account::Account;
+use db::audio_file::{AudioFile, DuplicateMode};
+use db::resumable_upload::{ResumableUpload, ResumableUploadState};
+use db::resumable_upload_part::ResumableUploadPart;
+use db::station::Station;
+use db::Model;
+use log::*;
+use serde_util::DateTime;
+use std::error::Error;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::{upload_audio_file, UploadError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResumableUploadError<E> {
+  #[error("stream: {0}")]
+  Stream(E),
+  #[error("mongo: {0}")]
+  Mongo(#[from] mongodb::error::Error),
+  #[error("station not found: {0}")]
+  StationNotFound(String),
+  #[error("account not found: {0}")]
+  AccountNotFound(String),
+  #[error("upload not found: {0}")]
+  NotFound(String),
+  #[error("upload not open: {0}")]
+  NotOpen(String),
+  #[error("quota exceeded")]
+  QuotaExceeded,
+  #[error("file empty")]
+  Empty,
+  #[error("offset mismatch: expected {expected}, received {received}")]
+  OffsetMismatch { expected: u64, received: u64 },
+  #[error("upload too large: declared len is {len}")]
+  TooLarge { len: u64 },
+  #[error("upload incomplete: received {offset} of {len} bytes")]
+  Incomplete { offset: u64, len: u64 },
+  #[error("upload: {0}")]
+  Upload(#[from] UploadError<mongodb::error::Error>),
+}
+
+/// creates a resumable upload of `len` bytes
+pub async fn create(
+  station_id: &str,
+  filename: String,
+  len: u64,
+  on_duplicate: DuplicateMode,
+) -> Result<ResumableUpload, ResumableUploadError<Infallible>> {
+  if len == 0 {
+    return Err(ResumableUploadError::Empty);
+  }
+
+  let station = match Station::get_by_id(station_id).await? {
+    Some(station) => station,
+    None => {
+      return Err(ResumableUploadError::StationNotFound(
+        station_id.to_string(),
+      ))
+    }
+  };
+
+  let account = match Account::get_by_id(&station.account_id).await? {
+    Some(account) => account,
+    None => return Err(ResumableUploadError::AccountNotFound(station.account_id)),
+  };
+
+  // the declared len of the other pending uploads is reserved
+  let pending = ResumableUpload::pending_len_for_account(&account.id).await?;
+  if len.saturating_add(pending) > account.limits.storage.avail() {
+    return Err(ResumableUploadError::QuotaExceeded);
+  }
+
+  let now = DateTime::now();
+  let upload = ResumableUpload {
+    id: ResumableUpload::uid(),
+    station_id: station.id,
+    filename,
+    len,
+    offset: 0,
+    on_duplicate,
+    state: ResumableUploadState::Open,
+    created_at: now,
+    updated_at: now,
+  };
+
+  ResumableUpload::insert(&upload).await?;
+
+  // another upload may have been created for the account in the meantime
+  let pending = ResumableUpload::pending_len_for_account(&account.id).await?;
+  if pending > account.limits.storage.avail() {
+    ResumableUpload::delete_by_id(&upload.id).await?;
+    return Err(ResumableUploadError::QuotaExceeded);
+  }
+
+  Ok(upload)
+}
+
+async fn write_part<E>(
+  upload: &ResumableUpload,
+  offset: u64,
+  data: Bytes,
+) -> Result<u64, ResumableUploadError<E>> {
+  let len = data.len() as u64;
+  let end = offset + len;
+
+  if end > upload.len {
+    return Err(ResumableUploadError::TooLarge { len: upload.len });
+  }
+
+  let part = ResumableUploadPart {
+    id: ResumableUploadPart::uid(),
+    upload_id: upload.id.clone(),
+    station_id: upload.station_id.clone(),
+    offset,
+    len,
+    data,
+    created_at: DateTime::now(),
+  };
+
+  ResumableUploadPart::insert(&part).await?;
+
+  // another request appended to (or committed) the upload in the meantime
+  if !ResumableUpload::advance(&upload.id, offset, end).await? {
+    ResumableUploadPart::delete_by_id(&part.id).await?;
+    return match ResumableUpload::get_by_id(&upload.id).await? {
+      None => Err(ResumableUploadError::NotFound(upload.id.clone())),
+      Some(current) if !current.is_open() => Err(ResumableUploadError::NotOpen(upload.id.clone())),
+      Some(current) => Err(ResumableUploadError::OffsetMismatch {
+        expected: current.offset,
+        received: offset,
+      }),
+    };
+  }
+
+  trace!(
+    "resumable upload {} part stored at {offset}: {len} bytes",
+    upload.id
+  );
+
+  Ok(end)
+}
+
+/// appends the bytes of `data` to an open upload starting at `offset`
+///
+/// the bytes are stored in parts of up to RESUMABLE_UPLOAD_PART_SIZE bytes,
+/// if `data` fails the parts that were completely received are kept
+/// and the client can continue from the offset of the upload
+pub async fn append<E: Error, S: Stream<Item = Result<Bytes, E>>>(
+  upload_id: &str,
+  offset: u64,
+  data: S,
+) -> Result<ResumableUpload, ResumableUploadError<E>> {
+  let upload = match ResumableUpload::get_by_id(upload_id).await? {
+    Some(upload) => upload,
+    None => return Err(ResumableUploadError::NotFound(upload_id.to_string())),
+  };
+
+  if !upload.is_open() {
+    return Err(ResumableUploadError::NotOpen(upload.id));
+  }
+
+  if upload.offset != offset {
+    return Err(ResumableUploadError::OffsetMismatch {
+      expected: upload.offset,
+      received: offset,
+    });
+  }
+
+  tokio::pin!(data);
+
+  let mut current = offset;
+  let mut buf = BytesMut::new();
+
+  loop {
+    match data.next().await {
+      None => break,
+      Some(Err(e)) => {
+        trace!("resumable upload {} recv error: {:?}", upload.id, e);
+        return Err(ResumableUploadError::Stream(e));
+      }
+      Some(Ok(bytes)) => {
+        buf.extend_from_slice(bytes.as_ref());
+        while buf.len() >= RESUMABLE_UPLOAD_PART_SIZE {
+          let part = buf.split_to(RESUMABLE_UPLOAD_PART_SIZE).freeze();
+          current = write_part(&upload, current, part).await?;
+        }
+      }
+    }
+  }
+
+  if !buf.is_empty() {
+    write_part(&upload, current, buf.freeze()).await?;
+  }
+
+  match ResumableUpload::get_by_id(&upload.id).await? {
+    Some(upload) => Ok(upload),
+    None => Err(ResumableUploadError::NotFound(upload.id)),
+  }
+}
+
+/// sends the received bytes of a complete upload to the transcoder and stores the audio file
+///
+/// committing an already committed upload returns its audio file
+pub async fn commit(
+  deployment_id: String,
+  upload_id: &str,
+) -> Result<AudioFile, ResumableUploadError<Infallible>> {
+  let upload = match ResumableUpload::get_by_id(upload_id).await? {
+    Some(upload) => upload,
+    None => return Err(ResumableUploadError::NotFound(upload_id.to_string())),
+  };
+
+  match &upload.state {
+    ResumableUploadState::Open => {}
+    ResumableUploadState::Committing => return Err(ResumableUploadError::NotOpen(upload.id)),
+    ResumableUploadState::Committed { audio_file_id, .. } => {
+      return match AudioFile::get_by_id(audio_file_id).await? {
+        Some(file) => Ok(file),
+        None => Err(ResumableUploadError::NotOpen(upload.id)),
+      };
+    }
+  }
+
+  if !upload.is_complete() {
+    return Err(ResumableUploadError::Incomplete {
+      offset: upload.offset,
+      len: upload.len,
+    });
+  }
+
+  let claimed = ResumableUpload::set_state_if(
+    &upload.id,
+    ResumableUploadState::KEY_ENUM_VARIANT_OPEN,
+    ResumableUploadState::Committing,
+  )
+  .await?;
+
+  if !claimed {
+    return Err(ResumableUploadError::NotOpen(upload.id));
+  }
+
+  // the commit continues if the request is dropped, so the upload is not left in the committing state
+  tokio::spawn(commit_claimed(deployment_id, upload))
+    .await
+    .unwrap()
+}
+
+/// commits an upload that was set to the committing state by this deployment
+async fn commit_claimed(
+  deployment_id: String,
+  upload: ResumableUpload,
+) -> Result<AudioFile, ResumableUploadError<Infallible>> {
+  let upload_file = upload_audio_file(
+    deployment_id,
+    upload.station_id.clone(),
+    None,
+    Some(upload.len),
+    upload.filename.clone(),
+    upload.on_duplicate,
+    ResumableUploadPart::stream(&upload.id),
+  );
+
+  // the upload is touched while the commit runs, the uploads that stop being touched
+  // were interrupted and are set back to open by ResumableUpload::reset_stale_committing
+  let touch = async {
+    let mut interval = tokio::time::interval(ResumableUpload::COMMITTING_TOUCH_INTERVAL);
+    // first tick is instantaneous
+    interval.tick().await;
+    loop {
+      interval.tick().await;
+      if let Err(e) = ResumableUpload::touch_committing(&upload.id).await {
+        warn!(
+          "error touching committing resumable upload {}: {e} => {e:?}",
+          upload.id
+        );
+      }
+    }
+  };
+
+  let result = tokio::select! {
+    result = upload_file => result,
+    _ = touch => unreachable!(),
+  };
+
+  match result {
+    Ok(file) => {
+      let state = ResumableUploadState::Committed {
+        audio_file_id: file.id.clone(),
+        committed_at: DateTime::now(),
+      };
+
+      ResumableUpload::set_state_if(
+        &upload.id,
+        ResumableUploadState::KEY_ENUM_VARIANT_COMMITTING,
+        state,
+      )
+      .await?;
+
+      // the upload document is kept until it expires so the commit can be retried
+      ResumableUploadPart::delete_by_upload_id(&upload.id).await?;
+
+      Ok(file)
+    }
+
+    Err(e) => {
+      // the received bytes are kept so the commit can be retried
+      ResumableUpload::set_state_if(
+        &upload.id,
+        ResumableUploadState::KEY_ENUM_VARIANT_COMMITTING,
+        ResumableUploadState::Open,
+      )
+      .await?;
+
+      Err(e.into())
+    }
+  }
+}
+
+/// cancels an upload that is not being committed and removes the received bytes
+pub async fn abort(upload_id: &str) -> Result<(), ResumableUploadError<Infallible>> {
+  let upload = match ResumableUpload::get_by_id(upload_id).await? {
+    Some(upload) => upload,
+    None => return Err(ResumableUploadError::NotFound(upload_id.to_string())),
+  };
+
+  if matches!(upload.state, ResumableUploadState::Committing) {
+    return Err(ResumableUploadError::NotOpen(upload.id));
+  }
+
+  ResumableUpload::remove(&upload.id).await?;
+
+  Ok(())
+}
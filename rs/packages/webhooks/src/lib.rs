@@ -0,0 +1,275 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use constants::validate::VALIDATE_WEBHOOK_URL_MAX_LEN;
+use constants::{
+  HEADER_WEBHOOK_DELIVERY, HEADER_WEBHOOK_EVENT, HEADER_WEBHOOK_SIGNATURE,
+  HEADER_WEBHOOK_TIMESTAMP, WEBHOOK_DELIVERY_CHECK_INTERVAL_SECS, WEBHOOK_DELIVERY_CONCURRENCY,
+  WEBHOOK_DELIVERY_LEASE_SECS, WEBHOOK_DELIVERY_MAX_ATTEMPTS, WEBHOOK_DELIVERY_RETRY_BASE_SECS,
+  WEBHOOK_DELIVERY_RETRY_MAX_SECS, WEBHOOK_DELIVERY_TIMEOUT_SECS,
+};
+use db::webhook::Webhook;
+use db::webhook_delivery::{WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryState};
+use db::Model;
+use ip::{is_public_ip, resolve_public, ResolvePublicError};
+use log::*;
+use serde_util::DateTime;
+use tokio::sync::Semaphore;
+use url::{Host, Url};
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookUrlError {
+  #[error("invalid url: {0}")]
+  Invalid(#[from] url::ParseError),
+  #[error("url is too long")]
+  TooLong,
+  #[error("unsupported url scheme: {0}")]
+  Scheme(String),
+  #[error("url has no host")]
+  NoHost,
+  #[error("url host is not a public address")]
+  NotPublic,
+}
+
+/// parses the url of a webhook, only http and https urls to public hosts are accepted
+///
+/// hostnames are resolved again on each delivery, see [`send`]
+pub fn parse_url(url: &str) -> Result<Url, WebhookUrlError> {
+  if url.len() > VALIDATE_WEBHOOK_URL_MAX_LEN {
+    return Err(WebhookUrlError::TooLong);
+  }
+
+  let url = Url::parse(url)?;
+
+  match url.scheme() {
+    "http" | "https" => {}
+    scheme => return Err(WebhookUrlError::Scheme(scheme.to_string())),
+  }
+
+  match url.host() {
+    None => return Err(WebhookUrlError::NoHost),
+    Some(Host::Ipv4(ip)) if !is_public_ip(ip.into()) => return Err(WebhookUrlError::NotPublic),
+    Some(Host::Ipv6(ip)) if !is_public_ip(ip.into()) => return Err(WebhookUrlError::NotPublic),
+    Some(Host::Domain("localhost")) => return Err(WebhookUrlError::NotPublic),
+    Some(_) => {}
+  }
+
+  Ok(url)
+}
+
+/// the signature sent in the HEADER_WEBHOOK_SIGNATURE header
+pub fn sign(secret: &str, timestamp: u64, payload: &str) -> String {
+  crypt::hmac_sha256(secret, format!("{timestamp}.{payload}"))
+}
+
+/// the delay before the next attempt of a delivery that failed `attempts` times
+pub fn retry_delay_secs(attempts: usize) -> u64 {
+  let exp = attempts.saturating_sub(1).min(32) as u32;
+  WEBHOOK_DELIVERY_RETRY_BASE_SECS
+    .saturating_mul(2u64.saturating_pow(exp))
+    .min(WEBHOOK_DELIVERY_RETRY_MAX_SECS)
+}
+
+#[derive(Debug, thiserror::Error)]
+enum SendError {
+  #[error("{0}")]
+  Url(#[from] WebhookUrlError),
+  #[error("{0}")]
+  Resolve(#[from] ResolvePublicError),
+  #[error("request error: {0}")]
+  Request(#[from] reqwest::Error),
+}
+
+/// sends the payload of the delivery to the webhook url, returns the response status
+///
+/// the request goes to the resolved address that was checked to be public
+/// and redirects are not followed
+async fn post(webhook: &Webhook, delivery: &WebhookDelivery) -> Result<u16, SendError> {
+  let url = parse_url(&webhook.url)?;
+  let addr = resolve_public(&url).await?;
+  let host = url.host_str().unwrap_or_default().to_string();
+
+  let client = reqwest::Client::builder()
+    .resolve(&host, addr)
+    .redirect(reqwest::redirect::Policy::none())
+    .timeout(Duration::from_secs(WEBHOOK_DELIVERY_TIMEOUT_SECS))
+    .build()?;
+
+  let timestamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+
+  let signature = sign(&webhook.secret, timestamp, &delivery.payload);
+
+  let kind = event_kind_name(delivery);
+
+  let res = client
+    .post(url)
+    .header(reqwest::header::CONTENT_TYPE, "application/json")
+    .header(HEADER_WEBHOOK_DELIVERY, &delivery.id)
+    .header(HEADER_WEBHOOK_EVENT, kind)
+    .header(HEADER_WEBHOOK_TIMESTAMP, timestamp.to_string())
+    .header(HEADER_WEBHOOK_SIGNATURE, signature)
+    .body(delivery.payload.clone())
+    .send()
+    .await?;
+
+  Ok(res.status().as_u16())
+}
+
+/// the serialized name of the event kind, as in the payload
+fn event_kind_name(delivery: &WebhookDelivery) -> String {
+  match mongodb::bson::to_bson(&delivery.event_kind) {
+    Ok(mongodb::bson::Bson::String(kind)) => kind,
+    _ => String::new(),
+  }
+}
+
+/// makes one attempt of the delivery
+pub async fn send(webhook: &Webhook, delivery: &WebhookDelivery) -> WebhookDeliveryAttempt {
+  let at = DateTime::now();
+  let start = Instant::now();
+
+  let (status, error) = if !webhook.enabled {
+    (None, Some(String::from("webhook is disabled")))
+  } else {
+    match post(webhook, delivery).await {
+      Ok(status) if (200..300).contains(&status) => (Some(status), None),
+      Ok(status) => (
+        Some(status),
+        Some(format!("url responded with status {status}")),
+      ),
+      Err(e) => (None, Some(e.to_string())),
+    }
+  };
+
+  WebhookDeliveryAttempt {
+    at,
+    status,
+    error,
+    duration_ms: start.elapsed().as_millis() as u64,
+  }
+}
+
+async fn deliver(delivery: WebhookDelivery) -> Result<(), mongodb::error::Error> {
+  let attempt = match Webhook::get_by_id(&delivery.webhook_id).await? {
+    Some(webhook) => send(&webhook, &delivery).await,
+    None => WebhookDeliveryAttempt {
+      at: DateTime::now(),
+      status: None,
+      error: Some(String::from("webhook not found")),
+      duration_ms: 0,
+    },
+  };
+
+  let attempts = delivery.attempts.len() + 1;
+
+  let (state, next_attempt_at) = if attempt.error.is_none() {
+    (WebhookDeliveryState::Success, None)
+  } else if attempts >= WEBHOOK_DELIVERY_MAX_ATTEMPTS {
+    (WebhookDeliveryState::Failed, None)
+  } else {
+    let delay = time::Duration::SECOND * retry_delay_secs(attempts) as f64;
+    let next = DateTime::from(DateTime::now().inner().saturating_add(delay));
+    (WebhookDeliveryState::Pending, Some(next))
+  };
+
+  match &attempt.error {
+    None => debug!(
+      target: "webhooks",
+      "delivery {} of webhook {} sent",
+      delivery.id, delivery.webhook_id
+    ),
+    Some(e) => info!(
+      target: "webhooks",
+      "delivery {} of webhook {} attempt {attempts} failed ({state:?}): {e}",
+      delivery.id, delivery.webhook_id
+    ),
+  }
+
+  WebhookDelivery::record_attempt(&delivery.id, attempt, state, next_attempt_at).await
+}
+
+/// sends the due webhook deliveries, in all deployments
+///
+/// a delivery is taken by one deployment at a time, see [`WebhookDelivery::claim_due`]
+pub async fn start_delivery_job() {
+  let semaphore = Arc::new(Semaphore::new(WEBHOOK_DELIVERY_CONCURRENCY));
+  let mut interval =
+    tokio::time::interval(Duration::from_secs(WEBHOOK_DELIVERY_CHECK_INTERVAL_SECS));
+
+  loop {
+    interval.tick().await;
+
+    loop {
+      let permit = match semaphore.clone().acquire_owned().await {
+        Ok(permit) => permit,
+        Err(_) => return,
+      };
+
+      let delivery = match WebhookDelivery::claim_due(WEBHOOK_DELIVERY_LEASE_SECS).await {
+        Ok(Some(delivery)) => delivery,
+        Ok(None) => break,
+        Err(e) => {
+          error!(target: "webhooks", "error getting due webhook deliveries: {e} => {e:?}");
+          break;
+        }
+      };
+
+      tokio::spawn(async move {
+        let id = delivery.id.clone();
+        if let Err(e) = deliver(delivery).await {
+          error!(target: "webhooks", "error recording webhook delivery {id}: {e} => {e:?}");
+        }
+        drop(permit);
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn webhook_urls() {
+    assert!(parse_url("https://example.com/hooks/openstream").is_ok());
+    assert!(parse_url("http://example.com:8080/").is_ok());
+    assert!(parse_url("https://8.8.8.8/hook").is_ok());
+    assert!(parse_url("ftp://example.com/").is_err());
+    assert!(parse_url("not an url").is_err());
+    assert!(parse_url("http://localhost:3000/").is_err());
+    assert!(parse_url("http://127.0.0.1/").is_err());
+    assert!(parse_url("http://169.254.169.254/latest/meta-data").is_err());
+    assert!(parse_url("http://[::1]/").is_err());
+    let long = format!(
+      "https://example.com/{}",
+      "a".repeat(VALIDATE_WEBHOOK_URL_MAX_LEN)
+    );
+    assert!(parse_url(&long).is_err());
+  }
+
+  #[test]
+  fn retry_delays() {
+    assert_eq!(retry_delay_secs(1), WEBHOOK_DELIVERY_RETRY_BASE_SECS);
+    assert_eq!(retry_delay_secs(2), WEBHOOK_DELIVERY_RETRY_BASE_SECS * 2);
+    assert_eq!(retry_delay_secs(3), WEBHOOK_DELIVERY_RETRY_BASE_SECS * 4);
+    assert_eq!(retry_delay_secs(100), WEBHOOK_DELIVERY_RETRY_MAX_SECS);
+  }
+
+  #[test]
+  fn signature() {
+    let a = sign("secret", 1700000000, r#"{"kind":"live.connected"}"#);
+    assert_eq!(a.len(), 64);
+    assert_eq!(
+      a,
+      crypt::hmac_sha256("secret", r#"1700000000.{"kind":"live.connected"}"#)
+    );
+    assert_ne!(
+      a,
+      sign("secret", 1700000001, r#"{"kind":"live.connected"}"#)
+    );
+    assert_ne!(a, sign("other", 1700000000, r#"{"kind":"live.connected"}"#));
+  }
+}